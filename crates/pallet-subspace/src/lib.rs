@@ -56,7 +56,8 @@ use subspace_core_primitives::crypto::kzg;
 use subspace_core_primitives::crypto::kzg::Kzg;
 use subspace_core_primitives::{
     PublicKey, Randomness, RewardSignature, RootBlock, SectorId, SectorIndex, SegmentIndex,
-    SolutionRange, PIECES_IN_SEGMENT, PIECE_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+    SlotNumber, SolutionRange, PIECES_IN_SEGMENT, PIECE_SIZE, RECORDED_HISTORY_SEGMENT_SIZE,
+    RECORD_SIZE,
 };
 use subspace_solving::REWARD_SIGNING_CONTEXT;
 use subspace_verification::{
@@ -682,8 +683,10 @@ impl<T: Config> Pallet<T> {
             } else {
                 next_solution_range = derive_next_solution_range(
                     // If Era start slot is not found it means we have just finished the first era
-                    u64::from(EraStartSlot::<T>::get().unwrap_or_else(GenesisSlot::<T>::get)),
-                    u64::from(current_slot),
+                    SlotNumber::new(u64::from(
+                        EraStartSlot::<T>::get().unwrap_or_else(GenesisSlot::<T>::get),
+                    )),
+                    SlotNumber::new(u64::from(current_slot)),
                     slot_probability,
                     solution_ranges.current,
                     T::EraDuration::get()
@@ -1314,7 +1317,7 @@ fn check_vote<T: Config>(
     let pieces_in_segment = vote_verification_data.pieces_in_segment;
     let position = u32::try_from(piece_index % u64::from(pieces_in_segment))
         .expect("Position within segment always fits into u32; qed");
-    let segment_index: SegmentIndex = piece_index / SegmentIndex::from(pieces_in_segment);
+    let segment_index = SegmentIndex::new(piece_index / u64::from(pieces_in_segment));
 
     let records_root = if let Some(records_root) = Pallet::<T>::records_root(segment_index) {
         records_root
@@ -1330,7 +1333,7 @@ fn check_vote<T: Config>(
 
     if let Err(error) = verify_solution::<FarmerPublicKey, T::AccountId>(
         solution,
-        slot.into(),
+        SlotNumber::new(u64::from(slot)),
         VerifySolutionParams {
             global_randomness: &vote_verification_data.global_randomness,
             solution_range: vote_verification_data.solution_range,
@@ -1434,8 +1437,8 @@ fn check_root_blocks<T: Config>(root_blocks: &[RootBlock]) -> Result<(), Transac
     };
 
     // Segment in root blocks should monotonically increase
-    if first_root_block.segment_index() > 0
-        && !RecordsRoot::<T>::contains_key(first_root_block.segment_index() - 1)
+    if first_root_block.segment_index() > SegmentIndex::ZERO
+        && !RecordsRoot::<T>::contains_key(first_root_block.segment_index() - SegmentIndex::ONE)
     {
         return Err(InvalidTransaction::BadMandatory.into());
     }
@@ -1451,7 +1454,7 @@ fn check_root_blocks<T: Config>(root_blocks: &[RootBlock]) -> Result<(), Transac
         let segment_index = root_block.segment_index();
 
         // Segment in root blocks should monotonically increase
-        if segment_index != last_segment_index + 1 {
+        if segment_index != last_segment_index + SegmentIndex::ONE {
             return Err(InvalidTransaction::BadMandatory.into());
         }
 
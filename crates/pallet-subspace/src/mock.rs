@@ -40,8 +40,8 @@ use subspace_core_primitives::crypto::kzg::{Kzg, Witness};
 use subspace_core_primitives::crypto::{blake2b_256_254_hash, kzg};
 use subspace_core_primitives::{
     ArchivedBlockProgress, Blake2b256Hash, Chunk, LastArchivedBlock, Piece, Randomness,
-    RecordsRoot, RootBlock, SegmentIndex, Solution, SolutionRange, PIECE_SIZE,
-    RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+    RecordsRoot, RootBlock, SectorIndex, SegmentIndex, SlotNumber, Solution, SolutionRange,
+    PIECE_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
 };
 use subspace_solving::{create_chunk_signature, derive_global_challenge, REWARD_SIGNING_CONTEXT};
 
@@ -191,7 +191,7 @@ pub fn go_to_block(
         Solution {
             public_key: FarmerPublicKey::unchecked_from(keypair.public.to_bytes()),
             reward_address,
-            sector_index: 0,
+            sector_index: SectorIndex::ZERO,
             total_pieces: NonZeroU64::new(1).unwrap(),
             piece_offset: 0,
             piece_record_hash: Default::default(),
@@ -269,7 +269,7 @@ pub fn generate_equivocation_proof(
             Solution {
                 public_key: public_key.clone(),
                 reward_address,
-                sector_index: 0,
+                sector_index: SectorIndex::ZERO,
                 total_pieces: NonZeroU64::new(1).unwrap(),
                 piece_offset,
                 piece_record_hash: Default::default(),
@@ -357,7 +357,8 @@ pub fn create_signed_vote(
     let reward_signing_context = schnorrkel::signing_context(REWARD_SIGNING_CONTEXT);
 
     // TODO: global challenge will be necessary in the future when we actually verify the chunk
-    let _global_challenge = derive_global_challenge(global_randomnesses, slot.into());
+    let _global_challenge =
+        derive_global_challenge(global_randomnesses, SlotNumber::new(slot.into()));
 
     let chunk = Default::default();
 
@@ -368,7 +369,7 @@ pub fn create_signed_vote(
         solution: Solution {
             public_key: FarmerPublicKey::unchecked_from(keypair.public.to_bytes()),
             reward_address,
-            sector_index: 0,
+            sector_index: SectorIndex::ZERO,
             total_pieces: NonZeroU64::new(1).unwrap(),
             piece_offset: 0,
             piece_record_hash: blake2b_256_254_hash(&piece[..RECORD_SIZE as usize]),
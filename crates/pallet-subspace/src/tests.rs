@@ -43,7 +43,7 @@ use sp_runtime::transaction_validity::{
 use sp_runtime::DispatchError;
 use std::assert_matches::assert_matches;
 use std::collections::BTreeMap;
-use subspace_core_primitives::Piece;
+use subspace_core_primitives::{Piece, SectorIndex, SegmentIndex};
 use subspace_runtime_primitives::{FindBlockRewardAddress, FindVotingRewardAddresses};
 use subspace_solving::REWARD_SIGNING_CONTEXT;
 use subspace_verification::Error as VerificationError;
@@ -523,7 +523,7 @@ fn store_root_block_works() {
 
         progress_to_block(&keypair, 1, 1);
 
-        let root_block = create_root_block(0);
+        let root_block = create_root_block(SegmentIndex::ZERO);
 
         let call = Call::<Test>::store_root_blocks {
             root_blocks: vec![root_block],
@@ -550,7 +550,7 @@ fn store_root_block_validate_unsigned_prevents_duplicates() {
 
         progress_to_block(&keypair, 1, 1);
 
-        let root_block = create_root_block(0);
+        let root_block = create_root_block(SegmentIndex::ZERO);
 
         let inner = Call::store_root_blocks {
             root_blocks: vec![root_block],
@@ -601,7 +601,10 @@ fn store_root_block_validate_unsigned_prevents_duplicates() {
         );
 
         let inner2 = Call::store_root_blocks {
-            root_blocks: vec![create_root_block(1), create_root_block(1)],
+            root_blocks: vec![
+                create_root_block(SegmentIndex::ONE),
+                create_root_block(SegmentIndex::ONE),
+            ],
         };
 
         // Same root block can't be included twice even in the same extrinsic
@@ -1206,7 +1209,7 @@ fn vote_equivocation_parent_block_plus_vote() {
         // Parent block author + sector index + slot matches that of the vote
 
         let slot = Subspace::current_slot();
-        let sector_index = 0;
+        let sector_index = SectorIndex::ZERO;
         let reward_address = 1;
         ParentBlockAuthorInfo::<Test>::put((
             FarmerPublicKey::unchecked_from(keypair.public.to_bytes()),
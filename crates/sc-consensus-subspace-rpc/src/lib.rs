@@ -44,20 +44,24 @@ use sp_runtime::generic::BlockId;
 use sp_runtime::traits::Block as BlockT;
 use std::marker::PhantomData;
 use std::num::{NonZeroU16, NonZeroU32};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use subspace_archiving::archiver::ArchivedSegment;
 use subspace_core_primitives::{
-    Piece, PieceIndex, RecordsRoot, SegmentIndex, Solution, RECORDED_HISTORY_SEGMENT_SIZE,
-    RECORD_SIZE,
+    Piece, PieceIndex, RecordsRoot, SegmentIndex, SlotNumber, Solution, PIECES_IN_SEGMENT,
+    RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
 };
 use subspace_rpc_primitives::{
-    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SlotInfo, SolutionResponse,
-    MAX_SEGMENT_INDEXES_PER_REQUEST,
+    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SegmentPieces, SlotInfo,
+    SolutionResponse, MAX_PIECES_PER_SEGMENT_REQUEST, MAX_SEGMENT_INDEXES_PER_REQUEST,
 };
 
 const SOLUTION_TIMEOUT: Duration = Duration::from_secs(2);
 const REWARD_SIGNING_TIMEOUT: Duration = Duration::from_millis(500);
+/// Max number of `subspace_segmentPieces` requests processed concurrently, requests above the limit
+/// are rejected right away so that farmers syncing history can't occupy all blocking threads
+const MAX_CONCURRENT_SEGMENT_REQUESTS: usize = 4;
 
 /// Provides rpc methods for interacting with Subspace.
 #[rpc(client, server)]
@@ -104,6 +108,29 @@ pub trait SubspaceRpcApi {
 
     #[method(name = "subspace_getPiece", blocking)]
     fn get_piece(&self, piece_index: PieceIndex) -> RpcResult<Option<Piece>>;
+
+    /// Up to `limit` consecutive pieces of archived segment starting at `first_position` together
+    /// with records root of the segment, `None` if segment is not archived yet.
+    ///
+    /// `limit` can't exceed [`MAX_PIECES_PER_SEGMENT_REQUEST`], fewer pieces are returned at the
+    /// end of the segment or if the rest of the pieces is not available. Requests above the limit
+    /// of concurrent requests fail and should be retried later.
+    #[method(name = "subspace_segmentPieces", blocking)]
+    fn segment_pieces(
+        &self,
+        segment_index: SegmentIndex,
+        first_position: u32,
+        limit: u32,
+    ) -> RpcResult<Option<SegmentPieces>>;
+}
+
+/// Decrements number of segment requests in progress when dropped
+struct SegmentRequestGuard<'a>(&'a AtomicUsize);
+
+impl Drop for SegmentRequestGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
 }
 
 #[derive(Default)]
@@ -128,6 +155,7 @@ pub struct SubspaceRpc<Block, Client, PC> {
     solution_response_senders: Arc<Mutex<SolutionResponseSenders>>,
     reward_signature_senders: Arc<Mutex<BlockSignatureSenders>>,
     piece_cache: PC,
+    segment_requests: Arc<AtomicUsize>,
     _phantom: PhantomData<Block>,
 }
 
@@ -159,6 +187,7 @@ impl<Block, Client, PC> SubspaceRpc<Block, Client, PC> {
             solution_response_senders: Arc::default(),
             reward_signature_senders: Arc::default(),
             piece_cache,
+            segment_requests: Arc::default(),
             _phantom: PhantomData::default(),
         }
     }
@@ -204,7 +233,9 @@ where
                 // TODO: Fetch this from the runtime
                 space_l: NonZeroU16::new(20).expect("Not zero; qed"),
                 // TODO: Fetch this from the runtime
-                sector_expiration: 100,
+                sector_expiration: SegmentIndex::new(100),
+                // Rotation of the global salt is not supported yet
+                rotation: 0,
             }
         };
 
@@ -222,7 +253,7 @@ where
 
         let mut solution_response_senders = solution_response_senders.lock();
 
-        if *solution_response_senders.current_slot == solution_response.slot_number {
+        if *solution_response_senders.current_slot == solution_response.slot_number.get() {
             if let Some(mut sender) = solution_response_senders.senders.pop() {
                 let _ = sender.send(solution_response);
             }
@@ -301,7 +332,7 @@ where
 
                     // This will be sent to the farmer
                     SlotInfo {
-                        slot_number: new_slot_info.slot.into(),
+                        slot_number: SlotNumber::new(new_slot_info.slot.into()),
                         global_challenge: new_slot_info.global_challenge,
                         solution_range: new_slot_info.solution_range,
                         voting_solution_range: new_slot_info.voting_solution_range,
@@ -486,4 +517,71 @@ where
             JsonRpseeError::Custom("Internal error during `get_piece` call".to_string())
         })
     }
+
+    fn segment_pieces(
+        &self,
+        segment_index: SegmentIndex,
+        first_position: u32,
+        limit: u32,
+    ) -> RpcResult<Option<SegmentPieces>> {
+        if limit > MAX_PIECES_PER_SEGMENT_REQUEST {
+            return Err(JsonRpseeError::Custom(format!(
+                "limit exceeds the maximum of {MAX_PIECES_PER_SEGMENT_REQUEST} pieces"
+            )));
+        }
+
+        if self.segment_requests.fetch_add(1, Ordering::AcqRel) >= MAX_CONCURRENT_SEGMENT_REQUESTS {
+            self.segment_requests.fetch_sub(1, Ordering::AcqRel);
+
+            return Err(JsonRpseeError::Custom(
+                "Too many concurrent segment requests, retry later".to_string(),
+            ));
+        }
+        let _segment_request_guard = SegmentRequestGuard(&self.segment_requests);
+
+        let best_block_id = BlockId::Hash(self.client.info().best_hash);
+        let records_root = self
+            .client
+            .runtime_api()
+            .records_root(&best_block_id, segment_index)
+            .map_err(|error| {
+                error!("Failed to get data from runtime API (records_root): {error}");
+
+                JsonRpseeError::Custom("Internal error during `records_root` call".to_string())
+            })?;
+        let records_root = match records_root {
+            Some(records_root) => records_root,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let first_piece_index = segment_index.first_piece_index();
+        let end_position = first_position.saturating_add(limit).min(PIECES_IN_SEGMENT);
+        let mut pieces = Vec::with_capacity(end_position.saturating_sub(first_position) as usize);
+        for position in first_position..end_position {
+            let piece_index = first_piece_index + PieceIndex::from(position);
+            let maybe_piece = self.piece_cache.get_piece(piece_index).map_err(|error| {
+                error!("Failed to get piece with index {piece_index} from cache: {error}");
+
+                JsonRpseeError::Custom("Internal error during `segment_pieces` call".to_string())
+            })?;
+
+            match maybe_piece {
+                Some(piece) => {
+                    pieces.push(piece);
+                }
+                None => {
+                    warn!("Piece {piece_index} of archived segment {segment_index} not found");
+                    break;
+                }
+            }
+        }
+
+        Ok(Some(SegmentPieces {
+            records_root,
+            first_position,
+            pieces,
+        }))
+    }
 }
@@ -82,8 +82,8 @@ use subspace_core_primitives::crypto::kzg;
 use subspace_core_primitives::crypto::kzg::Kzg;
 use subspace_core_primitives::objects::BlockObjectMapping;
 use subspace_core_primitives::{
-    Blake2b256Hash, BlockWeight, RootBlock, SectorId, SegmentIndex, Solution, SolutionRange,
-    PIECES_IN_SEGMENT, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+    Blake2b256Hash, BlockWeight, RootBlock, SectorId, SegmentIndex, SlotNumber, Solution,
+    SolutionRange, PIECES_IN_SEGMENT, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
 };
 use subspace_solving::{derive_global_challenge, REWARD_SIGNING_CONTEXT};
 use subspace_verification::{Error as VerificationPrimitiveError, VerifySolutionParams};
@@ -922,7 +922,7 @@ where
         );
         let position = u32::try_from(piece_index % u64::from(PIECES_IN_SEGMENT))
             .expect("Position within segment always fits into u32; qed");
-        let segment_index: SegmentIndex = piece_index / SegmentIndex::from(PIECES_IN_SEGMENT);
+        let segment_index = SegmentIndex::from_piece_index(piece_index);
 
         // This is not a very nice hack due to the fact that at the time first block is produced
         // extrinsics with root blocks are not yet in runtime.
@@ -1104,7 +1104,7 @@ where
         let added_weight = {
             let global_challenge = derive_global_challenge(
                 &subspace_digest_items.global_randomness,
-                pre_digest.slot.into(),
+                SlotNumber::new(u64::from(pre_digest.slot)),
             );
 
             let sector_id = SectorId::new(
@@ -1139,7 +1139,7 @@ where
                 aux_schema::load_records_root(self.client.as_ref(), segment_index)
                     .map_err(|e| ConsensusError::ClientImport(e.to_string()))?
             {
-                if &found_records_root != records_root {
+                if !found_records_root.ct_eq(records_root) {
                     return Err(ConsensusError::ClientImport(
                         Error::<Block::Header>::DifferentRecordsRoot(segment_index).to_string(),
                     ));
@@ -41,7 +41,7 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use subspace_core_primitives::{
-    Randomness, RewardSignature, SectorId, SegmentIndex, Solution, PIECES_IN_SEGMENT,
+    Randomness, RewardSignature, SectorId, SegmentIndex, SlotNumber, Solution, PIECES_IN_SEGMENT,
 };
 use subspace_solving::derive_global_challenge;
 use subspace_verification::{
@@ -154,7 +154,8 @@ where
             extract_global_randomness_for_block(self.client.as_ref(), &parent_block_id).ok()?;
         let (solution_range, voting_solution_range) =
             extract_solution_ranges_for_block(self.client.as_ref(), &parent_block_id).ok()?;
-        let global_challenge = derive_global_challenge(&global_randomness, slot.into());
+        let global_challenge =
+            derive_global_challenge(&global_randomness, SlotNumber::new(u64::from(slot)));
 
         let maybe_root_plot_public_key = self
             .client
@@ -209,7 +210,7 @@ where
 
             let piece_index =
                 sector_id.derive_piece_index(solution.piece_offset, solution.total_pieces);
-            let segment_index: SegmentIndex = piece_index / SegmentIndex::from(PIECES_IN_SEGMENT);
+            let segment_index = SegmentIndex::from_piece_index(piece_index);
             let position = u32::try_from(piece_index % u64::from(PIECES_IN_SEGMENT))
                 .expect("Position within segment always fits into u32; qed");
             let mut maybe_records_root = runtime_api
@@ -249,7 +250,7 @@ where
 
             let solution_verification_result = verify_solution(
                 &solution,
-                slot.into(),
+                SlotNumber::new(u64::from(slot)),
                 VerifySolutionParams {
                     global_randomness: &global_randomness,
                     solution_range: voting_solution_range,
@@ -74,7 +74,8 @@ use subspace_core_primitives::crypto::kzg;
 use subspace_core_primitives::crypto::kzg::Kzg;
 use subspace_core_primitives::objects::BlockObjectMapping;
 use subspace_core_primitives::{
-    ChunkSignature, FlatPieces, Piece, Solution, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+    ChunkSignature, FlatPieces, Piece, SectorIndex, Solution, RECORDED_HISTORY_SEGMENT_SIZE,
+    RECORD_SIZE,
 };
 use subspace_solving::{create_chunk_signature, REWARD_SIGNING_CONTEXT};
 use substrate_test_runtime::{Block as TestBlock, Hash};
@@ -687,7 +688,7 @@ pub fn dummy_claim_slot(
             solution: Solution {
                 public_key: FarmerPublicKey::unchecked_from([0u8; 32]),
                 reward_address: FarmerPublicKey::unchecked_from([0u8; 32]),
-                sector_index: 0,
+                sector_index: SectorIndex::ZERO,
                 total_pieces: NonZeroU64::new(1).unwrap(),
                 piece_offset: 0,
                 piece_record_hash: Default::default(),
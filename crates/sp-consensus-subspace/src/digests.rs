@@ -27,7 +27,7 @@ use sp_runtime::DigestItem;
 use sp_std::collections::btree_map::{BTreeMap, Entry};
 use sp_std::fmt;
 use subspace_core_primitives::{
-    PublicKey, Randomness, RecordsRoot, SegmentIndex, Solution, SolutionRange,
+    PublicKey, Randomness, RecordsRoot, SegmentIndex, SlotNumber, Solution, SolutionRange,
 };
 use subspace_verification::derive_randomness;
 
@@ -644,8 +644,8 @@ pub fn derive_next_solution_range<Header: HeaderT>(
         solution_range_override
     } else {
         subspace_verification::derive_next_solution_range(
-            u64::from(era_start_slot),
-            u64::from(current_slot),
+            SlotNumber::new(u64::from(era_start_slot)),
+            SlotNumber::new(u64::from(current_slot)),
             slot_probability,
             current_solution_range,
             era_duration
@@ -42,7 +42,7 @@ use sp_runtime::{ConsensusEngineId, DigestItem};
 use sp_std::vec::Vec;
 use subspace_core_primitives::{
     BlockNumber, PublicKey, Randomness, RecordsRoot, RewardSignature, RootBlock, SegmentIndex,
-    Solution, SolutionRange, PUBLIC_KEY_LENGTH, REWARD_SIGNATURE_LENGTH,
+    SlotNumber, Solution, SolutionRange, PUBLIC_KEY_LENGTH, REWARD_SIGNATURE_LENGTH,
 };
 use subspace_solving::REWARD_SIGNING_CONTEXT;
 use subspace_verification::{check_reward_signature, verify_solution, Error, VerifySolutionParams};
@@ -511,8 +511,12 @@ where
     }
 
     // Verify that solution is valid
-    verify_solution(&pre_digest.solution, slot.into(), verify_solution_params)
-        .map_err(|error| VerificationError::VerificationError(slot, error))?;
+    verify_solution(
+        &pre_digest.solution,
+        SlotNumber::new(u64::from(slot)),
+        verify_solution_params,
+    )
+    .map_err(|error| VerificationError::VerificationError(slot, error))?;
 
     Ok(CheckedHeader::Checked(
         header,
@@ -8,7 +8,7 @@ use sp_core::crypto::UncheckedFrom;
 use sp_runtime::traits::BlakeTwo256;
 use sp_runtime::{Digest, DigestItem};
 use std::num::NonZeroU64;
-use subspace_core_primitives::{ChunkSignature, Solution};
+use subspace_core_primitives::{ChunkSignature, SectorIndex, Solution};
 use subspace_solving::REWARD_SIGNING_CONTEXT;
 
 type Header = sp_runtime::generic::Header<u32, BlakeTwo256>;
@@ -22,7 +22,7 @@ fn test_is_equivocation_proof_valid() {
     let solution = Solution {
         public_key: offender.clone(),
         reward_address: (),
-        sector_index: 0,
+        sector_index: SectorIndex::ZERO,
         total_pieces: NonZeroU64::new(1).unwrap(),
         piece_offset: 0,
         piece_record_hash: Default::default(),
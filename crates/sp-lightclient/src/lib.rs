@@ -39,7 +39,7 @@ use subspace_core_primitives::crypto::kzg;
 use subspace_core_primitives::crypto::kzg::Kzg;
 use subspace_core_primitives::{
     BlockWeight, PublicKey, Randomness, RecordsRoot, RewardSignature, SectorId, SegmentIndex,
-    SolutionRange, PIECES_IN_SEGMENT,
+    SlotNumber, SolutionRange, PIECES_IN_SEGMENT,
 };
 use subspace_solving::{derive_global_challenge, REWARD_SIGNING_CONTEXT};
 use subspace_verification::{
@@ -349,7 +349,7 @@ impl<Header: HeaderT, Store: Storage<Header>> HeaderImporter<Header, Store> {
         );
         let position = u32::try_from(piece_index % u64::from(PIECES_IN_SEGMENT))
             .expect("Position within segment always fits into u32; qed");
-        let segment_index: SegmentIndex = piece_index / SegmentIndex::from(PIECES_IN_SEGMENT);
+        let segment_index = SegmentIndex::from_piece_index(piece_index);
 
         let records_root =
             self.find_records_root_for_segment_index(segment_index, parent_header.header.hash())?;
@@ -359,7 +359,7 @@ impl<Header: HeaderT, Store: Storage<Header>> HeaderImporter<Header, Store> {
 
         verify_solution(
             &header_digests.pre_digest.solution,
-            header_digests.pre_digest.slot.into(),
+            SlotNumber::new(header_digests.pre_digest.slot.into()),
             VerifySolutionParams {
                 global_randomness: &header_digests.global_randomness,
                 solution_range: header_digests.solution_range,
@@ -541,7 +541,7 @@ impl<Header: HeaderT, Store: Storage<Header>> HeaderImporter<Header, Store> {
     ) -> BlockWeight {
         let global_challenge = derive_global_challenge(
             &header_digests.global_randomness,
-            header_digests.pre_digest.slot.into(),
+            SlotNumber::new(header_digests.pre_digest.slot.into()),
         );
 
         let local_challenge = sector_id.derive_local_challenge(&global_challenge);
@@ -24,8 +24,8 @@ use subspace_archiving::archiver::{ArchivedSegment, Archiver};
 use subspace_core_primitives::crypto::kzg::{Kzg, Witness};
 use subspace_core_primitives::crypto::{blake2b_256_254_hash, kzg};
 use subspace_core_primitives::{
-    Chunk, Piece, PublicKey, Randomness, RecordsRoot, SectorId, SegmentIndex, Solution,
-    SolutionRange, PIECE_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+    Chunk, Piece, PublicKey, Randomness, RecordsRoot, SectorId, SectorIndex, SegmentIndex,
+    SlotNumber, Solution, SolutionRange, PIECE_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
 };
 use subspace_solving::{
     create_chunk_signature, derive_chunk_otp, derive_global_challenge, REWARD_SIGNING_CONTEXT,
@@ -98,14 +98,14 @@ fn valid_header(
         space_l,
     } = params;
     let chunks_in_sector = u64::from(RECORD_SIZE) * u64::from(u8::BITS) / u64::from(space_l.get());
-    let global_challenge = derive_global_challenge(&randomness, slot);
+    let global_challenge = derive_global_challenge(&randomness, SlotNumber::new(slot));
     let archived_segment = archived_segment(kzg.clone());
     let segment_index = archived_segment.root_block.segment_index();
     let records_root = archived_segment.root_block.records_root();
     let total_pieces = NonZeroU64::new(archived_segment.pieces.count() as u64).unwrap();
 
     // There may be no solution in the first sector, iterate until we get a solution
-    for sector_index in 0.. {
+    for sector_index in (0..).map(SectorIndex::new) {
         let sector_id = SectorId::new(&PublicKey::from(keypair.public.to_bytes()), sector_index);
         let local_challenge = sector_id.derive_local_challenge(&global_challenge);
         let audit_index: u64 = local_challenge % chunks_in_sector;
@@ -863,8 +863,8 @@ fn test_next_solution_range_digest_with_adjustment_enabled() {
     // add next solution range
     remove_seal(&mut header);
     let next_solution_range = subspace_verification::derive_next_solution_range(
-        u64::from(header_at_4.era_start_slot),
-        u64::from(pre_digest.slot),
+        SlotNumber::new(u64::from(header_at_4.era_start_slot)),
+        SlotNumber::new(u64::from(pre_digest.slot)),
         constants.slot_probability,
         solution_range,
         constants.era_duration,
@@ -982,8 +982,8 @@ fn test_enable_solution_range_adjustment_without_override() {
         .override_cumulative_weight(header_at_4.header.hash(), 0);
     let pre_digest = extract_pre_digest(&header).unwrap();
     let next_solution_range = subspace_verification::derive_next_solution_range(
-        u64::from(header_at_4.era_start_slot),
-        u64::from(pre_digest.slot),
+        SlotNumber::new(u64::from(header_at_4.era_start_slot)),
+        SlotNumber::new(u64::from(pre_digest.slot)),
         constants.slot_probability,
         solution_range,
         constants.era_duration,
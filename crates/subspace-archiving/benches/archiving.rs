@@ -19,6 +19,22 @@ fn criterion_benchmark(c: &mut Criterion) {
             archiver.add_block(input.clone(), Default::default());
         })
     });
+
+    let mut pieces_buffers = Vec::new();
+    c.bench_function("segment-archiving-reuse-buffers", |b| {
+        b.iter(|| {
+            let archived_segments = archiver.add_block_with_pieces_buffers(
+                input.clone(),
+                Default::default(),
+                &mut pieces_buffers,
+            );
+            pieces_buffers.extend(
+                archived_segments
+                    .into_iter()
+                    .map(|archived_segment| archived_segment.pieces),
+            );
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
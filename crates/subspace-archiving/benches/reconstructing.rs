@@ -0,0 +1,82 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::prelude::SliceRandom;
+use rand::{thread_rng, Rng};
+use subspace_archiving::archiver::Archiver;
+use subspace_archiving::reconstructor::{Reconstructor, RecoveryStrategy};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{Piece, PIECES_IN_SEGMENT, RECORD_SIZE};
+
+// This is helpful for overriding locally for benching different parameters
+const RECORDED_HISTORY_SEGMENT_SIZE: u32 = RECORD_SIZE * PIECES_IN_SEGMENT / 2;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut input = vec![0u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
+    thread_rng().fill(input.as_mut_slice());
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let mut archiver = Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg).unwrap();
+    let archived_segment = archiver
+        .add_block(input, Default::default())
+        .into_iter()
+        .next()
+        .unwrap();
+    let pieces = archived_segment
+        .pieces
+        .as_pieces()
+        .map(|piece| Some(Piece::try_from(piece).unwrap()))
+        .collect::<Vec<_>>();
+    let lost_pieces = PIECES_IN_SEGMENT as usize / 2;
+
+    let random_loss = {
+        let mut pieces = pieces.clone();
+        let mut indexes = (0..pieces.len()).collect::<Vec<_>>();
+        indexes.shuffle(&mut thread_rng());
+        for index in indexes.into_iter().take(lost_pieces) {
+            pieces[index].take();
+        }
+        pieces
+    };
+    let contiguous_tail_loss = {
+        let mut pieces = pieces;
+        pieces.iter_mut().rev().take(lost_pieces).for_each(|piece| {
+            piece.take();
+        });
+        pieces
+    };
+
+    c.bench_function("segment-reconstruction-random-loss", |b| {
+        b.iter(|| {
+            Reconstructor::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE)
+                .unwrap()
+                .add_segment(&random_loss)
+                .unwrap();
+        })
+    });
+
+    c.bench_function("segment-reconstruction-contiguous-tail-loss", |b| {
+        b.iter(|| {
+            Reconstructor::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE)
+                .unwrap()
+                .add_segment(&contiguous_tail_loss)
+                .unwrap();
+        })
+    });
+
+    c.bench_function(
+        "segment-reconstruction-contiguous-tail-loss-erasure-decoding",
+        |b| {
+            b.iter(|| {
+                Reconstructor::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE)
+                    .unwrap()
+                    .add_segment_with_strategy(
+                        &contiguous_tail_loss,
+                        RecoveryStrategy::ErasureDecoding,
+                    )
+                    .unwrap();
+            })
+        },
+    );
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
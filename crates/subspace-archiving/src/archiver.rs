@@ -32,7 +32,7 @@ use subspace_core_primitives::objects::{
 };
 use subspace_core_primitives::{
     crypto, ArchivedBlockProgress, Blake2b256Hash, BlockNumber, FlatPieces, LastArchivedBlock,
-    RootBlock, BLAKE2B_256_HASH_SIZE, WITNESS_SIZE,
+    RootBlock, SegmentIndex, BLAKE2B_256_HASH_SIZE, WITNESS_SIZE,
 };
 
 const INITIAL_LAST_ARCHIVED_BLOCK: LastArchivedBlock = LastArchivedBlock {
@@ -105,6 +105,12 @@ pub enum SegmentItem {
     /// Root block
     #[codec(index = 6)]
     RootBlock(RootBlock),
+    /// Marks the rest of the segment as padding, only present in segments force-completed with
+    /// [`Archiver::flush_partial`].
+    ///
+    /// Padding is filled with zero bytes, which also decode as this variant.
+    #[codec(index = 0)]
+    Padding,
 }
 
 /// Archived segment as a combination of root block hash, segment index and corresponding pieces
@@ -193,10 +199,14 @@ pub struct Archiver {
     segment_size: u32,
     /// Erasure coding data structure
     reed_solomon: ReedSolomon,
+    /// Record shards buffer that is reused for every segment
+    record_shards: RecordShards,
+    /// Buffer for hashes of records that is reused for every segment
+    record_hashes: Vec<u8>,
     /// KZG instance
     kzg: Kzg,
     /// An index of the current segment
-    segment_index: u64,
+    segment_index: SegmentIndex,
     /// Hash of the root block of the previous segment
     prev_root_block_hash: Blake2b256Hash,
     /// Last archived block
@@ -242,6 +252,9 @@ impl Archiver {
         let parity_shards = data_shards;
         let reed_solomon = ReedSolomon::new(data_shards as usize, parity_shards as usize)
             .expect("ReedSolomon must always be correctly instantiated");
+        let record_shards = RecordShards::new(data_shards, parity_shards, record_size);
+        let record_hashes =
+            Vec::with_capacity((data_shards + parity_shards) as usize * BLAKE2B_256_HASH_SIZE);
 
         Ok(Self {
             buffer: VecDeque::default(),
@@ -250,10 +263,12 @@ impl Archiver {
             parity_shards,
             segment_size,
             reed_solomon,
+            record_shards,
+            record_hashes,
             // TODO: Probably should check degree from public parameters against erasure coding
             //  setup
             kzg,
-            segment_index: 0,
+            segment_index: SegmentIndex::ZERO,
             prev_root_block_hash: Blake2b256Hash::default(),
             last_archived_block: INITIAL_LAST_ARCHIVED_BLOCK,
         })
@@ -272,7 +287,7 @@ impl Archiver {
     ) -> Result<Self, ArchiverInstantiationError> {
         let mut archiver = Self::new(record_size, segment_size, kzg)?;
 
-        archiver.segment_index = root_block.segment_index() + 1;
+        archiver.segment_index = root_block.segment_index() + SegmentIndex::ONE;
         archiver.prev_root_block_hash = root_block.hash();
         archiver.last_archived_block = root_block.last_archived_block();
 
@@ -331,11 +346,40 @@ impl Archiver {
         }
     }
 
+    /// Index of the segment that is currently being filled, equals the number of segments archived
+    /// so far
+    pub fn segment_index(&self) -> SegmentIndex {
+        self.segment_index
+    }
+
+    /// Number of bytes buffered for the segment that is currently being filled (encoded size of
+    /// buffered segment items, including root block of the previous segment), always smaller than
+    /// segment size after [`Archiver::add_block`] returns
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.iter().map(Encode::encoded_size).sum()
+    }
+
     /// Adds new block to internal buffer, potentially producing pieces and root block headers
     pub fn add_block(
         &mut self,
         bytes: Vec<u8>,
         object_mapping: BlockObjectMapping,
+    ) -> Vec<ArchivedSegment> {
+        self.add_block_with_pieces_buffers(bytes, object_mapping, &mut Vec::new())
+    }
+
+    /// Same as [`Archiver::add_block`], but pieces of produced archived segments are written into
+    /// buffers taken from `pieces_buffers` instead of allocating new ones.
+    ///
+    /// Buffers that don't have the number of pieces corresponding to one segment are dropped, new
+    /// buffers are allocated when `pieces_buffers` is exhausted. Callers that archive continuously
+    /// can push [`ArchivedSegment::pieces`] back into `pieces_buffers` once they are done with
+    /// archived segment to avoid allocating memory for pieces on every segment.
+    pub fn add_block_with_pieces_buffers(
+        &mut self,
+        bytes: Vec<u8>,
+        object_mapping: BlockObjectMapping,
+        pieces_buffers: &mut Vec<FlatPieces>,
     ) -> Vec<ArchivedSegment> {
         // Append new block to the buffer
         self.buffer.push_back(SegmentItem::Block {
@@ -346,12 +390,108 @@ impl Archiver {
         let mut archived_segments = Vec::new();
 
         while let Some(segment) = self.produce_segment() {
-            archived_segments.push(self.produce_archived_segment(segment));
+            let pieces_count = (self.data_shards + self.parity_shards) as usize;
+            let pieces = loop {
+                match pieces_buffers.pop() {
+                    Some(mut pieces) => {
+                        if pieces.count() == pieces_count {
+                            pieces.fill(0);
+                            break pieces;
+                        }
+                    }
+                    None => {
+                        break FlatPieces::new(pieces_count);
+                    }
+                }
+            };
+            archived_segments.push(self.produce_archived_segment(segment, pieces));
         }
 
         archived_segments
     }
 
+    /// Force-complete the segment that is currently being filled even though there is not enough
+    /// data for a full segment, meant to be used on shutdown so that buffered blocks are archived
+    /// instead of waiting for more blocks to arrive.
+    ///
+    /// The rest of the last produced segment is marked with [`SegmentItem::Padding`] that is ignored
+    /// by [`Reconstructor`](crate::reconstructor::Reconstructor). Its root block records all
+    /// buffered blocks as fully archived, so archiver created with [`Archiver::with_initial_state`]
+    /// from that root block and the last buffered block will continue with the next block without
+    /// archiving anything twice.
+    ///
+    /// Buffer may contain more than one segment worth of data right after restart with partially
+    /// archived block, in which case full segments are produced first. Returns empty vector if there
+    /// are no buffered blocks.
+    pub fn flush_partial(&mut self) -> Vec<ArchivedSegment> {
+        let pieces_count = (self.data_shards + self.parity_shards) as usize;
+        let mut archived_segments = Vec::new();
+
+        while let Some(segment) = self.produce_segment() {
+            archived_segments
+                .push(self.produce_archived_segment(segment, FlatPieces::new(pieces_count)));
+        }
+
+        if let Some(segment) = self.produce_padded_segment() {
+            archived_segments
+                .push(self.produce_archived_segment(segment, FlatPieces::new(pieces_count)));
+        }
+
+        archived_segments
+    }
+
+    /// Produce segment out of all buffer contents followed by padding, buffer contents must fit
+    /// into one segment
+    fn produce_padded_segment(&mut self) -> Option<Segment> {
+        if self
+            .buffer
+            .iter()
+            .all(|segment_item| matches!(segment_item, SegmentItem::RootBlock(_)))
+        {
+            return None;
+        }
+
+        let mut segment = Segment::V0 {
+            items: Vec::with_capacity(self.buffer.len() + 1),
+        };
+        let mut last_archived_block = self.last_archived_block;
+
+        for segment_item in self.buffer.drain(..) {
+            match &segment_item {
+                SegmentItem::Block { .. } => {
+                    // Skip block number increase in case of the very first block
+                    if last_archived_block != INITIAL_LAST_ARCHIVED_BLOCK {
+                        last_archived_block.number += 1;
+                    }
+                    last_archived_block.set_complete();
+                }
+                SegmentItem::BlockStart { .. } => {
+                    unreachable!("Buffer never contains SegmentItem::BlockStart; qed");
+                }
+                SegmentItem::BlockContinuation { .. } | SegmentItem::RootBlock(_) => {
+                    // Continuation finishes partially archived block, which is marked as complete
+                    // below
+                }
+                SegmentItem::Padding => {
+                    unreachable!("Buffer never contains SegmentItem::Padding; qed");
+                }
+            }
+
+            segment.push_item(segment_item);
+        }
+
+        // All buffered blocks were included in the segment
+        last_archived_block.set_complete();
+        self.last_archived_block = last_archived_block;
+
+        // Buffer contents after `produce_segment()` returned `None` take less than segment size
+        // minus 2 bytes, which leaves enough space for padding marker even if it increases length
+        // of compact encoding of items count
+        segment.push_item(SegmentItem::Padding);
+
+        Some(segment)
+    }
+
     /// Try to slice buffer contents into segments if there is enough data, producing one segment at
     /// a time
     fn produce_segment(&mut self) -> Option<Segment> {
@@ -406,6 +546,9 @@ impl Archiver {
                             fits into the segment; qed",
                         );
                     }
+                    SegmentItem::Padding => {
+                        unreachable!("Buffer never contains SegmentItem::Padding; qed");
+                    }
                 };
 
                 if spill_over > inner_bytes_size {
@@ -444,6 +587,9 @@ impl Archiver {
                 SegmentItem::RootBlock(_) => {
                     // We are not interested in root block here
                 }
+                SegmentItem::Padding => {
+                    unreachable!("Buffer never contains SegmentItem::Padding; qed");
+                }
             }
 
             segment.push_item(segment_item);
@@ -559,6 +705,9 @@ impl Archiver {
                         fits into the segment; qed",
                     );
                 }
+                SegmentItem::Padding => {
+                    unreachable!("Buffer never contains SegmentItem::Padding; qed");
+                }
             };
 
             // Push back shortened segment item
@@ -574,8 +723,13 @@ impl Archiver {
         Some(segment)
     }
 
-    // Take segment as an input, apply necessary transformations and produce archived segment
-    fn produce_archived_segment(&mut self, segment: Segment) -> ArchivedSegment {
+    // Take segment as an input, apply necessary transformations and produce archived segment with
+    // pieces written into provided zeroed buffer
+    fn produce_archived_segment(
+        &mut self,
+        segment: Segment,
+        mut pieces: FlatPieces,
+    ) -> ArchivedSegment {
         // Create mappings
         let object_mapping = {
             let mut corrected_object_mapping = vec![
@@ -621,7 +775,7 @@ impl Archiver {
                             }
                         }
                     }
-                    SegmentItem::RootBlock(_) => {
+                    SegmentItem::RootBlock(_) | SegmentItem::Padding => {
                         // Ignore, no objects mappings here
                     }
                 }
@@ -631,46 +785,32 @@ impl Archiver {
             corrected_object_mapping
         };
 
-        let mut record_shards = RecordShards::new(
-            self.data_shards,
-            self.parity_shards,
-            self.record_size,
-            &segment,
-        );
+        self.record_shards.set_segment(&segment);
 
         drop(segment);
 
-        let mut record_shards_slices = record_shards.as_mut_slices();
+        let mut record_shards_slices = self.record_shards.as_mut_slices();
 
         // Apply erasure coding to to create parity shards/records
         self.reed_solomon
             .encode(&mut record_shards_slices)
             .expect("Encoding is running with fixed parameters and should never fail; qed");
 
-        let mut pieces = FlatPieces::new(record_shards_slices.len());
         drop(record_shards_slices);
 
-        let record_shards_hashes = record_shards
+        self.record_hashes.clear();
+        for shard in self
+            .record_shards
             .as_bytes()
-            .as_ref()
             .chunks_exact(self.record_size as usize)
-            .map(blake2b_256_254_hash)
-            .collect::<Vec<_>>();
-        let data = {
-            let mut data = Vec::with_capacity(
-                (self.data_shards + self.parity_shards) as usize * BLAKE2B_256_HASH_SIZE,
-            );
-
-            for shard in &record_shards_hashes {
-                // TODO: Eventually we need to commit to data itself, not hashes
-                data.extend_from_slice(shard);
-            }
-
-            data
-        };
+        {
+            // TODO: Eventually we need to commit to data itself, not hashes
+            self.record_hashes
+                .extend_from_slice(&blake2b_256_254_hash(shard));
+        }
         let polynomial = self
             .kzg
-            .poly(&data)
+            .poly(&self.record_hashes)
             .expect("Internally produced values must never fail; qed");
         let commitment = self
             .kzg
@@ -683,9 +823,8 @@ impl Archiver {
             .as_pieces_mut()
             .enumerate()
             .zip(
-                record_shards
+                self.record_shards
                     .as_bytes()
-                    .as_ref()
                     .chunks_exact(self.record_size as usize),
             )
             .for_each(|((position, piece), shard_chunk)| {
@@ -711,7 +850,7 @@ impl Archiver {
         };
 
         // Update state
-        self.segment_index += 1;
+        self.segment_index += SegmentIndex::ONE;
         self.prev_root_block_hash = root_block.hash();
 
         // Add root block to the beginning of the buffer to be the first thing included in the next
@@ -774,3 +913,127 @@ pub fn is_piece_record_hash_valid(
         witness,
     )
 }
+
+/// Root block chain validation error
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+pub enum RootBlockChainError {
+    /// Root block of the first segment doesn't have empty previous root block hash
+    #[cfg_attr(
+        feature = "thiserror",
+        error("Root block of the first segment doesn't have empty previous root block hash")
+    )]
+    InvalidGenesisPrevHash,
+    /// Segment indexes are not consecutive
+    #[cfg_attr(
+        feature = "thiserror",
+        error(
+            "Segment index {segment_index} doesn't follow previous segment index \
+            {prev_segment_index}"
+        )
+    )]
+    NonConsecutiveSegmentIndex {
+        /// Segment index of the previous root block
+        prev_segment_index: SegmentIndex,
+        /// Segment index of the root block
+        segment_index: SegmentIndex,
+    },
+    /// Previous root block hash doesn't match hash of the previous root block
+    #[cfg_attr(
+        feature = "thiserror",
+        error("Root block of segment {segment_index} doesn't reference previous root block")
+    )]
+    PrevHashMismatch {
+        /// Segment index of the root block
+        segment_index: SegmentIndex,
+    },
+    /// Records root is a commitment to empty records, which is never produced by archiver
+    #[cfg_attr(
+        feature = "thiserror",
+        error("Root block of segment {segment_index} has empty records root")
+    )]
+    EmptyRecordsRoot {
+        /// Segment index of the root block
+        segment_index: SegmentIndex,
+    },
+    /// Last archived block doesn't progress compared to previous root block
+    #[cfg_attr(
+        feature = "thiserror",
+        error(
+            "Last archived block of segment {segment_index} doesn't progress compared to previous \
+            root block"
+        )
+    )]
+    LastArchivedBlockRegression {
+        /// Segment index of the root block
+        segment_index: SegmentIndex,
+    },
+}
+
+/// Validate that root blocks form a consistent chain.
+///
+/// Checks that segment indexes are consecutive, each root block references the hash of the
+/// previous one, records roots are non-empty and last archived block advances between adjacent
+/// root blocks. In case the first root block is for the first segment it must also reference empty
+/// hash, for other starting points caller is responsible for making sure the first root block is
+/// trusted.
+pub fn validate_root_block_chain(root_blocks: &[RootBlock]) -> Result<(), RootBlockChainError> {
+    if let Some(first_root_block) = root_blocks.first() {
+        if first_root_block.segment_index() == SegmentIndex::ZERO
+            && first_root_block.prev_root_block_hash() != Blake2b256Hash::default()
+        {
+            return Err(RootBlockChainError::InvalidGenesisPrevHash);
+        }
+    }
+
+    for root_block in root_blocks {
+        if root_block.records_root() == Commitment::default() {
+            return Err(RootBlockChainError::EmptyRecordsRoot {
+                segment_index: root_block.segment_index(),
+            });
+        }
+    }
+
+    for window in root_blocks.windows(2) {
+        let (prev_root_block, root_block) = (&window[0], &window[1]);
+        let segment_index = root_block.segment_index();
+
+        if prev_root_block.segment_index() + SegmentIndex::ONE != segment_index {
+            return Err(RootBlockChainError::NonConsecutiveSegmentIndex {
+                prev_segment_index: prev_root_block.segment_index(),
+                segment_index,
+            });
+        }
+
+        if root_block.prev_root_block_hash() != prev_root_block.hash() {
+            return Err(RootBlockChainError::PrevHashMismatch { segment_index });
+        }
+
+        let prev_last_archived_block = prev_root_block.last_archived_block();
+        let last_archived_block = root_block.last_archived_block();
+        let progressed = match last_archived_block
+            .number
+            .cmp(&prev_last_archived_block.number)
+        {
+            Ordering::Less => false,
+            // Same block can only continue to be archived if it was archived partially before
+            Ordering::Equal => match (
+                prev_last_archived_block.partial_archived(),
+                last_archived_block.partial_archived(),
+            ) {
+                (Some(prev_archived_bytes), Some(archived_bytes)) => {
+                    archived_bytes > prev_archived_bytes
+                }
+                (Some(_), None) => true,
+                (None, _) => false,
+            },
+            Ordering::Greater => true,
+        };
+
+        if !progressed {
+            return Err(RootBlockChainError::LastArchivedBlockRegression { segment_index });
+        }
+    }
+
+    Ok(())
+}
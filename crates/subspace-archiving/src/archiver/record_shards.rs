@@ -4,36 +4,22 @@ use crate::archiver::Segment;
 use crate::utils::{Gf16Element, GF_16_ELEMENT_BYTES};
 use alloc::vec;
 use alloc::vec::Vec;
-use core::mem::ManuallyDrop;
+use core::fmt;
 use parity_scale_codec::{Encode, Output};
 
 /// Container that allows SCALE-encoding into directly while making sure nothing is written past
 /// data shard space and without extra memory copies.
-struct WritableShards {
-    data_shards_size: u32,
-    shards: Vec<Gf16Element>,
+struct WritableShards<'a> {
+    data_shards: &'a mut [u8],
     cursor: usize,
 }
 
-impl Output for WritableShards {
+impl Output for WritableShards<'_> {
     fn write(&mut self, buf: &[u8]) {
-        // SAFETY:
-        // Lifetime is the same as source, de-allocation is prevented with `ManuallyDrop`, no
-        // re-allocation will happen so interpreting one vector as different vector is fine.
-        unsafe {
-            let mut target = ManuallyDrop::new(Vec::from_raw_parts(
-                self.shards.as_mut_ptr() as *mut u8,
-                self.shards.len() * GF_16_ELEMENT_BYTES,
-                self.shards.len() * GF_16_ELEMENT_BYTES,
-            ));
-
-            // May panic only if inputs are incorrect.
-            target[..self.data_shards_size as usize][self.cursor..][..buf.len()]
-                .as_mut()
-                .copy_from_slice(buf);
+        // May panic only if inputs are incorrect.
+        self.data_shards[self.cursor..][..buf.len()].copy_from_slice(buf);
 
-            self.cursor += buf.len();
-        }
+        self.cursor += buf.len();
     }
 }
 
@@ -41,63 +27,87 @@ impl Output for WritableShards {
 /// for more convenient management.
 ///
 /// Allows to accessing underlying data both as list of shards for erasure coding and regular slice
-/// of bytes for other purposes, also implements [`std::io::Write`] so that it can be used with
+/// of bytes for other purposes, also implements [`Output`] so that it can be used with
 /// `parity-scale-codec` to write encoded data right into [`RecordShards`].
+///
+/// Memory is allocated once and reused for every subsequent segment.
+#[derive(Clone)]
 pub(super) struct RecordShards {
     shards: Vec<Gf16Element>,
+    /// Number of data shards
+    data_shards: u32,
     /// Shard size in bytes
     shard_size: u32,
 }
 
+impl fmt::Debug for RecordShards {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordShards")
+            .field(
+                "shards",
+                &(self.shards.len() * GF_16_ELEMENT_BYTES / self.shard_size as usize),
+            )
+            .field("data_shards", &self.data_shards)
+            .field("shard_size", &self.shard_size)
+            .finish()
+    }
+}
+
 impl RecordShards {
-    /// Create new `Shards` struct for specified number of shards and shard size in bytes.
+    /// Create new zeroed `RecordShards` for specified number of shards and shard size in bytes.
     ///
-    /// Panics if shard size is not multiple of 2 or encoded segment doesn't fit into data shards.
-    pub(super) fn new(
-        data_shards: u32,
-        parity_shards: u32,
-        shard_size: u32,
-        segment: &Segment,
-    ) -> Self {
+    /// Panics if shard size is not multiple of 2.
+    pub(super) fn new(data_shards: u32, parity_shards: u32, shard_size: u32) -> Self {
         assert_eq!(shard_size as usize % GF_16_ELEMENT_BYTES, 0);
 
-        let mut writable_shards = WritableShards {
-            data_shards_size: data_shards * shard_size,
+        Self {
             shards: vec![
                 Gf16Element::default();
                 ((data_shards + parity_shards) * shard_size) as usize / GF_16_ELEMENT_BYTES
             ],
-            cursor: 0,
-        };
-
-        segment.encode_to(&mut writable_shards);
-
-        Self {
-            shards: writable_shards.shards,
+            data_shards,
             shard_size,
         }
     }
 
+    /// Replace contents of data shards with encoded segment, parity shards are left untouched and
+    /// are expected to be overwritten with erasure coding afterwards.
+    ///
+    /// Panics if encoded segment doesn't fit into data shards.
+    pub(super) fn set_segment(&mut self, segment: &Segment) {
+        let data_shards_size = (self.data_shards * self.shard_size) as usize;
+        let data_shards = &mut self.as_bytes_mut()[..data_shards_size];
+        // Encoded segment might be smaller than data shards, make sure nothing from previous
+        // segment remains in the tail
+        data_shards.fill(0);
+
+        segment.encode_to(&mut WritableShards {
+            data_shards,
+            cursor: 0,
+        });
+    }
+
     /// Access internal record shards as contiguous memory slice.
-    pub(super) fn as_bytes(&mut self) -> impl AsRef<[u8]> + '_ {
-        // SAFETY:
-        // Returned lifetime is the same as source, de-allocation is prevented with `ManuallyDrop`
-        // and specific type is erased from API, so nothing except reading bytes is possible.
+    pub(super) fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `Gf16Element` is an array of bytes without padding, so the same memory can be
+        // interpreted as a slice of bytes of corresponding length with the same lifetime.
         unsafe {
-            /// Private wrapper just so that `AsRef<[u8]>` can be implemented on it.
-            struct AsRefRecordShards(ManuallyDrop<Vec<u8>>);
-
-            impl AsRef<[u8]> for AsRefRecordShards {
-                fn as_ref(&self) -> &[u8] {
-                    &self.0
-                }
-            }
+            core::slice::from_raw_parts(
+                self.shards.as_ptr() as *const u8,
+                self.shards.len() * GF_16_ELEMENT_BYTES,
+            )
+        }
+    }
 
-            AsRefRecordShards(ManuallyDrop::new(Vec::from_raw_parts(
+    /// Access internal record shards as contiguous mutable memory slice.
+    fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `Gf16Element` is an array of bytes without padding, so the same memory can be
+        // interpreted as a slice of bytes of corresponding length with the same lifetime.
+        unsafe {
+            core::slice::from_raw_parts_mut(
                 self.shards.as_mut_ptr() as *mut u8,
                 self.shards.len() * GF_16_ELEMENT_BYTES,
-                self.shards.len() * GF_16_ELEMENT_BYTES,
-            )))
+            )
         }
     }
 
@@ -50,6 +50,25 @@ pub enum ReconstructorError {
         expected_segment_index: SegmentIndex,
         actual_segment_index: SegmentIndex,
     },
+    /// Some of the data pieces are missing, which [`RecoveryStrategy::DataPieces`] can't recover
+    #[cfg_attr(
+        feature = "thiserror",
+        error("Some of the data pieces are missing, erasure decoding is required")
+    )]
+    MissingDataPieces,
+}
+
+/// Way segment data is recovered from pieces
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RecoveryStrategy {
+    /// Records of data pieces are concatenated as is, without erasure decoding.
+    ///
+    /// Data pieces precede parity pieces in a segment, so this works as long as all missing pieces
+    /// are parity pieces, which is the case when download of a segment was cut off after at least
+    /// half of the pieces.
+    DataPieces,
+    /// Missing data pieces are recovered using erasure coding, works with any half of the pieces
+    ErasureDecoding,
 }
 
 /// Data structure that contains information reconstructed from given segment (potentially using
@@ -114,48 +133,94 @@ impl Reconstructor {
     pub fn add_segment(
         &mut self,
         segment_pieces: &[Option<Piece>],
+    ) -> Result<ReconstructedContents, ReconstructorError> {
+        let recovery_strategy = self.recovery_strategy(segment_pieces);
+        self.add_segment_with_strategy(segment_pieces, recovery_strategy)
+    }
+
+    /// The cheapest strategy that can recover segment data from given pieces, used by
+    /// [`Reconstructor::add_segment`]
+    pub fn recovery_strategy(&self, segment_pieces: &[Option<Piece>]) -> RecoveryStrategy {
+        let data_shard_count = self.reed_solomon.data_shard_count();
+
+        if segment_pieces.len() >= data_shard_count
+            && segment_pieces
+                .iter()
+                .take(data_shard_count)
+                .all(Option::is_some)
+        {
+            RecoveryStrategy::DataPieces
+        } else {
+            RecoveryStrategy::ErasureDecoding
+        }
+    }
+
+    /// Same as [`Reconstructor::add_segment`], but segment data is recovered with explicitly
+    /// specified strategy, both strategies produce the same result when data pieces are available.
+    pub fn add_segment_with_strategy(
+        &mut self,
+        segment_pieces: &[Option<Piece>],
+        recovery_strategy: RecoveryStrategy,
     ) -> Result<ReconstructedContents, ReconstructorError> {
         let mut segment_data = Vec::with_capacity(self.segment_size as usize);
-        if !segment_pieces
-            .iter()
-            .take(self.reed_solomon.data_shard_count())
-            .all(|maybe_piece| {
-                if let Some(piece) = maybe_piece {
+        match recovery_strategy {
+            RecoveryStrategy::DataPieces => {
+                if self.recovery_strategy(segment_pieces) != RecoveryStrategy::DataPieces {
+                    return Err(ReconstructorError::MissingDataPieces);
+                }
+
+                for piece in segment_pieces
+                    .iter()
+                    .take(self.reed_solomon.data_shard_count())
+                    .flatten()
+                {
                     segment_data.extend_from_slice(&piece[..self.record_size as usize]);
-                    true
-                } else {
-                    false
                 }
-            })
-        {
-            // If not all data pieces are available, need to reconstruct data shards using erasure
-            // coding.
-            let mut shards = segment_pieces
-                .iter()
-                .map(|maybe_piece| maybe_piece.as_ref().map(utils::slice_to_arrays))
-                .collect::<Vec<_>>();
-
-            self.reed_solomon
-                .reconstruct_data(&mut shards)
-                .map_err(ReconstructorError::DataShardsReconstruction)?;
-
-            segment_data.clear();
-            shards
-                .into_iter()
-                .take(self.reed_solomon.data_shard_count())
-                .for_each(|maybe_piece| {
-                    let piece = maybe_piece.expect(
-                        "All data shards are available after successful reconstruction; qed",
-                    );
-
-                    for chunk in piece.iter().take(self.record_size as usize / 2) {
-                        segment_data.extend_from_slice(chunk.as_ref());
-                    }
-                });
+            }
+            RecoveryStrategy::ErasureDecoding => {
+                self.reconstruct_segment_data(segment_pieces, &mut segment_data)?;
+            }
         }
 
-        let Segment::V0 { items } = Segment::decode(&mut segment_data.as_ref())
-            .map_err(ReconstructorError::SegmentDecoding)?;
+        self.decode_segment(&segment_data)
+    }
+
+    /// Reconstruct data shards using erasure coding and write them into `segment_data`
+    fn reconstruct_segment_data(
+        &self,
+        segment_pieces: &[Option<Piece>],
+        segment_data: &mut Vec<u8>,
+    ) -> Result<(), ReconstructorError> {
+        let mut shards = segment_pieces
+            .iter()
+            .map(|maybe_piece| maybe_piece.as_ref().map(utils::slice_to_arrays))
+            .collect::<Vec<_>>();
+
+        self.reed_solomon
+            .reconstruct_data(&mut shards)
+            .map_err(ReconstructorError::DataShardsReconstruction)?;
+
+        shards
+            .into_iter()
+            .take(self.reed_solomon.data_shard_count())
+            .for_each(|maybe_piece| {
+                let piece = maybe_piece
+                    .expect("All data shards are available after successful reconstruction; qed");
+
+                for chunk in piece.iter().take(self.record_size as usize / 2) {
+                    segment_data.extend_from_slice(chunk.as_ref());
+                }
+            });
+
+        Ok(())
+    }
+
+    fn decode_segment(
+        &mut self,
+        segment_data: &[u8],
+    ) -> Result<ReconstructedContents, ReconstructorError> {
+        let Segment::V0 { items } =
+            Segment::decode(&mut &*segment_data).map_err(ReconstructorError::SegmentDecoding)?;
 
         let mut reconstructed_contents = ReconstructedContents::default();
         let mut next_block_number = 0;
@@ -204,13 +269,14 @@ impl Reconstructor {
                     if let Some(last_segment_index) = self.last_segment_index {
                         if last_segment_index != segment_index {
                             return Err(ReconstructorError::IncorrectSegmentOrder {
-                                expected_segment_index: last_segment_index + 1,
-                                actual_segment_index: segment_index + 1,
+                                expected_segment_index: last_segment_index + SegmentIndex::ONE,
+                                actual_segment_index: segment_index + SegmentIndex::ONE,
                             });
                         }
                     }
 
-                    self.last_segment_index.replace(segment_index + 1);
+                    self.last_segment_index
+                        .replace(segment_index + SegmentIndex::ONE);
 
                     let LastArchivedBlock {
                         number,
@@ -221,9 +287,13 @@ impl Reconstructor {
 
                     match archived_progress {
                         ArchivedBlockProgress::Complete => {
-                            reconstructed_contents
-                                .blocks
-                                .push((next_block_number, mem::take(&mut partial_block)));
+                            // Previous segment might have ended with complete block or padding,
+                            // in which case there is nothing left to finish
+                            if !partial_block.is_empty() {
+                                reconstructed_contents
+                                    .blocks
+                                    .push((next_block_number, mem::take(&mut partial_block)));
+                            }
 
                             next_block_number = number + 1;
                         }
@@ -237,6 +307,16 @@ impl Reconstructor {
                         }
                     }
                 }
+                SegmentItem::Padding => {
+                    // Segment was force-completed, all blocks in it are complete
+                    if !partial_block.is_empty() {
+                        reconstructed_contents
+                            .blocks
+                            .push((next_block_number, mem::take(&mut partial_block)));
+
+                        next_block_number += 1;
+                    }
+                }
             }
         }
 
@@ -245,7 +325,7 @@ impl Reconstructor {
         }
 
         if self.last_segment_index.is_none() {
-            self.last_segment_index.replace(0);
+            self.last_segment_index.replace(SegmentIndex::ZERO);
         }
 
         Ok(reconstructed_contents)
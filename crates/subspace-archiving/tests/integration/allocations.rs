@@ -0,0 +1,141 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::objects::BlockObjectMapping;
+use subspace_core_primitives::{FlatPieces, PIECE_SIZE, RECORD_SIZE};
+
+// This is data + parity shards
+const PIECES_IN_SEGMENT: u32 = 8;
+// In terms of source data that can be stored in the segment, not the size after archiving
+const SEGMENT_SIZE: u32 = RECORD_SIZE * PIECES_IN_SEGMENT / 2;
+
+/// Allocator that counts bytes allocated by current thread, so that tests running concurrently do
+/// not affect each other
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_allocation(size: usize) {
+    // Thread local might be already destroyed when thread exits
+    let _ = ALLOCATED_BYTES.try_with(|allocated_bytes| {
+        allocated_bytes.set(allocated_bytes.get() + size);
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation(new_size.saturating_sub(layout.size()));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns result of `f` and number of bytes allocated while running it
+fn allocated_bytes<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATED_BYTES.with(Cell::get);
+    let result = f();
+    let after = ALLOCATED_BYTES.with(Cell::get);
+
+    (result, after - before)
+}
+
+/// Archive blocks and return number of produced segments along with number of bytes allocated by
+/// archiver in the process
+fn archive_blocks(
+    archiver: &mut Archiver,
+    blocks: Vec<Vec<u8>>,
+    pieces_buffers: Option<&mut Vec<FlatPieces>>,
+) -> (usize, usize) {
+    let mut segments = 0;
+    let mut total_allocated_bytes = 0;
+
+    match pieces_buffers {
+        Some(pieces_buffers) => {
+            for block in blocks {
+                let (archived_segments, allocated_bytes) = allocated_bytes(|| {
+                    archiver.add_block_with_pieces_buffers(
+                        block,
+                        BlockObjectMapping::default(),
+                        pieces_buffers,
+                    )
+                });
+                total_allocated_bytes += allocated_bytes;
+                segments += archived_segments.len();
+                // Return buffers back so they can be reused for next segments
+                pieces_buffers.extend(
+                    archived_segments
+                        .into_iter()
+                        .map(|archived_segment| archived_segment.pieces),
+                );
+            }
+        }
+        None => {
+            for block in blocks {
+                let (archived_segments, allocated_bytes) =
+                    allocated_bytes(|| archiver.add_block(block, BlockObjectMapping::default()));
+                total_allocated_bytes += allocated_bytes;
+                segments += archived_segments.len();
+            }
+        }
+    }
+
+    (segments, total_allocated_bytes)
+}
+
+#[test]
+fn archiver_reuses_buffers() {
+    let kzg = Kzg::random(PIECES_IN_SEGMENT).unwrap();
+    let pieces_size = PIECES_IN_SEGMENT as usize * PIECE_SIZE;
+    let record_shards_size = (PIECES_IN_SEGMENT * RECORD_SIZE) as usize;
+    let blocks = || {
+        (0..16_u8)
+            .map(|index| vec![index; SEGMENT_SIZE as usize / 4])
+            .collect::<Vec<_>>()
+    };
+
+    let mut archiver = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg.clone()).unwrap();
+    let mut archiver_with_buffers = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg).unwrap();
+    let mut pieces_buffers = Vec::new();
+
+    // Warm up, so that pieces buffers are available for reuse
+    archive_blocks(&mut archiver, blocks(), None);
+    archive_blocks(
+        &mut archiver_with_buffers,
+        blocks(),
+        Some(&mut pieces_buffers),
+    );
+
+    let (segments, allocated_bytes) = archive_blocks(&mut archiver, blocks(), None);
+    let (segments_with_buffers, allocated_bytes_with_buffers) = archive_blocks(
+        &mut archiver_with_buffers,
+        blocks(),
+        Some(&mut pieces_buffers),
+    );
+
+    assert!(segments > 0);
+    assert_eq!(segments, segments_with_buffers);
+    // Pieces are allocated for every segment unless buffers are provided
+    assert!(allocated_bytes >= segments * pieces_size);
+    assert!(allocated_bytes - allocated_bytes_with_buffers >= segments * pieces_size);
+    // Neither record shards nor pieces are allocated per segment when buffers are reused
+    assert!(allocated_bytes_with_buffers / segments < record_shards_size);
+}
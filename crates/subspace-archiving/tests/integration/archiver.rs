@@ -3,12 +3,14 @@ use std::assert_matches::assert_matches;
 use std::io::Write;
 use std::iter;
 use subspace_archiving::archiver;
-use subspace_archiving::archiver::{Archiver, ArchiverInstantiationError};
+use subspace_archiving::archiver::{
+    validate_root_block_chain, Archiver, ArchiverInstantiationError, RootBlockChainError,
+};
 use subspace_core_primitives::crypto::kzg::{Commitment, Kzg};
 use subspace_core_primitives::objects::{BlockObject, BlockObjectMapping, PieceObject};
 use subspace_core_primitives::{
-    ArchivedBlockProgress, Blake2b256Hash, LastArchivedBlock, RootBlock, BLAKE2B_256_HASH_SIZE,
-    RECORD_SIZE,
+    ArchivedBlockProgress, Blake2b256Hash, LastArchivedBlock, RootBlock, SegmentIndex,
+    BLAKE2B_256_HASH_SIZE, RECORD_SIZE,
 };
 
 // This is data + parity shards
@@ -113,7 +115,10 @@ fn archiver() {
         first_archived_segment.pieces.count(),
         PIECES_IN_SEGMENT as usize
     );
-    assert_eq!(first_archived_segment.root_block.segment_index(), 0);
+    assert_eq!(
+        first_archived_segment.root_block.segment_index(),
+        SegmentIndex::ZERO
+    );
     assert_eq!(
         first_archived_segment.root_block.prev_root_block_hash(),
         [0u8; BLAKE2B_256_HASH_SIZE]
@@ -218,7 +223,7 @@ fn archiver() {
     }
 
     // Check that both archived segments have expected content and valid pieces in them
-    let mut expected_segment_index = 1_u64;
+    let mut expected_segment_index = SegmentIndex::ONE;
     let mut previous_root_block_hash = first_archived_segment.root_block.hash();
     let last_root_block = archived_segments.iter().last().unwrap().root_block;
     for archived_segment in archived_segments {
@@ -243,7 +248,7 @@ fn archiver() {
             ));
         }
 
-        expected_segment_index += 1;
+        expected_segment_index += SegmentIndex::ONE;
         previous_root_block_hash = archived_segment.root_block.hash();
     }
 
@@ -319,7 +324,7 @@ fn invalid_usage() {
             SEGMENT_SIZE,
             kzg.clone(),
             RootBlock::V0 {
-                segment_index: 0,
+                segment_index: SegmentIndex::ZERO,
                 records_root: Commitment::default(),
                 prev_root_block_hash: Blake2b256Hash::default(),
                 last_archived_block: LastArchivedBlock {
@@ -347,7 +352,7 @@ fn invalid_usage() {
             SEGMENT_SIZE,
             kzg,
             RootBlock::V0 {
-                segment_index: 0,
+                segment_index: SegmentIndex::ZERO,
                 records_root: Commitment::default(),
                 prev_root_block_hash: Blake2b256Hash::default(),
                 last_archived_block: LastArchivedBlock {
@@ -435,6 +440,62 @@ fn spill_over_edge_case() {
     );
 }
 
+#[test]
+fn archiving_progress() {
+    let kzg = Kzg::random(PIECES_IN_SEGMENT).unwrap();
+    let mut archiver = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg.clone()).unwrap();
+    assert_eq!(archiver.segment_index(), SegmentIndex::ZERO);
+    assert_eq!(archiver.buffered_bytes(), 0);
+
+    let block = vec![0u8; SEGMENT_SIZE as usize / 3];
+    let mut archived_segments_count = 0;
+    let mut last_root_block = None;
+    for _ in 0..10 {
+        let buffered_bytes_before = archiver.buffered_bytes();
+        let archived_segments = archiver.add_block(block.clone(), BlockObjectMapping::default());
+
+        for archived_segment in &archived_segments {
+            assert_eq!(
+                archived_segment.root_block.segment_index(),
+                SegmentIndex::new(archived_segments_count)
+            );
+            archived_segments_count += 1;
+            last_root_block.replace(archived_segment.root_block);
+        }
+
+        // Segment index only advances when segments are complete
+        assert_eq!(
+            archiver.segment_index(),
+            SegmentIndex::new(archived_segments_count)
+        );
+        assert!(archiver.buffered_bytes() < SEGMENT_SIZE as usize);
+        if archived_segments.is_empty() {
+            // Enum variant followed by block bytes
+            assert_eq!(
+                archiver.buffered_bytes(),
+                buffered_bytes_before + 1 + block.encoded_size()
+            );
+        }
+    }
+    assert!(archived_segments_count >= 3);
+
+    // Archiver restarted from the last root block continues with the next segment
+    let archiver_with_initial_state = Archiver::with_initial_state(
+        RECORD_SIZE,
+        SEGMENT_SIZE,
+        kzg,
+        last_root_block.unwrap(),
+        &block,
+        BlockObjectMapping::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        archiver_with_initial_state.segment_index(),
+        SegmentIndex::new(archived_segments_count)
+    );
+    assert!(archiver_with_initial_state.buffered_bytes() < SEGMENT_SIZE as usize);
+}
+
 #[test]
 fn object_on_the_edge_of_segment() {
     let kzg = Kzg::random(PIECES_IN_SEGMENT).unwrap();
@@ -469,7 +530,7 @@ fn object_on_the_edge_of_segment() {
                     - 1
                     - 1
                     - RootBlock::V0 {
-                        segment_index: 0,
+                        segment_index: SegmentIndex::ZERO,
                         records_root: Default::default(),
                         prev_root_block_hash: Default::default(),
                         last_archived_block: LastArchivedBlock {
@@ -512,3 +573,110 @@ fn object_on_the_edge_of_segment() {
         108
     );
 }
+
+#[test]
+fn root_block_chain_validation() {
+    let kzg = Kzg::random(PIECES_IN_SEGMENT).unwrap();
+    let mut archiver = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg).unwrap();
+
+    let root_blocks = archiver
+        .add_block(
+            vec![0u8; SEGMENT_SIZE as usize * 3],
+            BlockObjectMapping::default(),
+        )
+        .into_iter()
+        .chain(archiver.add_block(
+            vec![0u8; SEGMENT_SIZE as usize / 3],
+            BlockObjectMapping::default(),
+        ))
+        .chain(archiver.add_block(
+            vec![0u8; SEGMENT_SIZE as usize],
+            BlockObjectMapping::default(),
+        ))
+        .map(|archived_segment| archived_segment.root_block)
+        .collect::<Vec<_>>();
+    assert!(root_blocks.len() >= 3);
+
+    assert_eq!(validate_root_block_chain(&root_blocks), Ok(()));
+    // Chain doesn't have to start from genesis
+    assert_eq!(validate_root_block_chain(&root_blocks[1..]), Ok(()));
+    assert_eq!(validate_root_block_chain(&[]), Ok(()));
+
+    // Segment missing in the middle
+    {
+        let mut root_blocks = root_blocks.clone();
+        root_blocks.remove(1);
+        assert_eq!(
+            validate_root_block_chain(&root_blocks),
+            Err(RootBlockChainError::NonConsecutiveSegmentIndex {
+                prev_segment_index: SegmentIndex::ZERO,
+                segment_index: SegmentIndex::new(2),
+            })
+        );
+    }
+
+    // Root block that doesn't reference previous one
+    {
+        let mut root_blocks = root_blocks.clone();
+        let RootBlock::V0 {
+            prev_root_block_hash,
+            ..
+        } = &mut root_blocks[2];
+        prev_root_block_hash[0] ^= 1;
+        assert_eq!(
+            validate_root_block_chain(&root_blocks),
+            Err(RootBlockChainError::PrevHashMismatch {
+                segment_index: SegmentIndex::new(2)
+            })
+        );
+    }
+
+    // First segment must reference empty hash
+    {
+        let mut root_blocks = root_blocks.clone();
+        let RootBlock::V0 {
+            prev_root_block_hash,
+            ..
+        } = &mut root_blocks[0];
+        prev_root_block_hash[0] ^= 1;
+        assert_eq!(
+            validate_root_block_chain(&root_blocks[..1]),
+            Err(RootBlockChainError::InvalidGenesisPrevHash)
+        );
+    }
+
+    // Empty records root
+    {
+        let mut root_blocks = root_blocks.clone();
+        let RootBlock::V0 { records_root, .. } = &mut root_blocks[1];
+        *records_root = Commitment::default();
+        assert_eq!(
+            validate_root_block_chain(&root_blocks),
+            Err(RootBlockChainError::EmptyRecordsRoot {
+                segment_index: SegmentIndex::ONE
+            })
+        );
+    }
+
+    // Last archived block going backwards
+    {
+        let mut root_blocks = root_blocks[..2].to_vec();
+        let prev_last_archived_block = root_blocks[0].last_archived_block();
+        let RootBlock::V0 {
+            last_archived_block,
+            ..
+        } = &mut root_blocks[1];
+        *last_archived_block = prev_last_archived_block;
+        if let Some(archived_bytes) = last_archived_block.partial_archived() {
+            last_archived_block.set_partial_archived(archived_bytes - 1);
+        } else {
+            last_archived_block.number -= 1;
+        }
+        assert_eq!(
+            validate_root_block_chain(&root_blocks),
+            Err(RootBlockChainError::LastArchivedBlockRegression {
+                segment_index: SegmentIndex::ONE
+            })
+        );
+    }
+}
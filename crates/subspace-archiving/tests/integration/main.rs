@@ -1,4 +1,5 @@
 #![feature(assert_matches)]
 
+mod allocations;
 mod archiver;
 mod reconstructor;
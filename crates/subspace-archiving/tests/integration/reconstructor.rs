@@ -1,13 +1,14 @@
 use std::assert_matches::assert_matches;
 use std::iter;
-use subspace_archiving::archiver::Archiver;
+use subspace_archiving::archiver::{validate_root_block_chain, Archiver};
 use subspace_archiving::reconstructor::{
-    Reconstructor, ReconstructorError, ReconstructorInstantiationError,
+    Reconstructor, ReconstructorError, ReconstructorInstantiationError, RecoveryStrategy,
 };
 use subspace_core_primitives::crypto::kzg::Kzg;
 use subspace_core_primitives::objects::BlockObjectMapping;
 use subspace_core_primitives::{
-    ArchivedBlockProgress, FlatPieces, LastArchivedBlock, Piece, PIECE_SIZE, RECORD_SIZE,
+    ArchivedBlockProgress, FlatPieces, LastArchivedBlock, Piece, SegmentIndex, PIECE_SIZE,
+    RECORD_SIZE,
 };
 
 // This is data + parity shards
@@ -75,7 +76,10 @@ fn basic() {
         // Second block is finished, but also third is included
         assert_eq!(contents.blocks, vec![(1, block_1), (2, block_2.clone())]);
         assert!(contents.root_block.is_some());
-        assert_eq!(contents.root_block.unwrap().segment_index(), 0);
+        assert_eq!(
+            contents.root_block.unwrap().segment_index(),
+            SegmentIndex::ZERO
+        );
         assert_eq!(
             contents.root_block.unwrap().last_archived_block(),
             LastArchivedBlock {
@@ -94,7 +98,10 @@ fn basic() {
         // Only third block is fully contained
         assert_eq!(contents.blocks, vec![(2, block_2)]);
         assert!(contents.root_block.is_some());
-        assert_eq!(contents.root_block.unwrap().segment_index(), 0);
+        assert_eq!(
+            contents.root_block.unwrap().segment_index(),
+            SegmentIndex::ZERO
+        );
         assert_eq!(
             contents.root_block.unwrap().last_archived_block(),
             LastArchivedBlock {
@@ -114,7 +121,10 @@ fn basic() {
         // Nothing is fully contained here
         assert_eq!(contents.blocks, vec![]);
         assert!(contents.root_block.is_some());
-        assert_eq!(contents.root_block.unwrap().segment_index(), 1);
+        assert_eq!(
+            contents.root_block.unwrap().segment_index(),
+            SegmentIndex::ONE
+        );
         assert_eq!(
             contents.root_block.unwrap().last_archived_block(),
             LastArchivedBlock {
@@ -133,7 +143,10 @@ fn basic() {
         // Nothing is fully contained here
         assert_eq!(contents.blocks, vec![]);
         assert!(contents.root_block.is_some());
-        assert_eq!(contents.root_block.unwrap().segment_index(), 1);
+        assert_eq!(
+            contents.root_block.unwrap().segment_index(),
+            SegmentIndex::ONE
+        );
         assert_eq!(
             contents.root_block.unwrap().last_archived_block(),
             LastArchivedBlock {
@@ -153,7 +166,10 @@ fn basic() {
         // Nothing is fully contained here
         assert_eq!(contents.blocks, vec![]);
         assert!(contents.root_block.is_some());
-        assert_eq!(contents.root_block.unwrap().segment_index(), 2);
+        assert_eq!(
+            contents.root_block.unwrap().segment_index(),
+            SegmentIndex::new(2)
+        );
         assert_eq!(
             contents.root_block.unwrap().last_archived_block(),
             LastArchivedBlock {
@@ -174,7 +190,10 @@ fn basic() {
         // Nothing is fully contained here
         assert_eq!(contents.blocks, vec![]);
         assert!(contents.root_block.is_some());
-        assert_eq!(contents.root_block.unwrap().segment_index(), 2);
+        assert_eq!(
+            contents.root_block.unwrap().segment_index(),
+            SegmentIndex::new(2)
+        );
         assert_eq!(
             contents.root_block.unwrap().last_archived_block(),
             LastArchivedBlock {
@@ -194,7 +213,10 @@ fn basic() {
         // Enough data to reconstruct fourth block
         assert_eq!(contents.blocks, vec![(3, block_3)]);
         assert!(contents.root_block.is_some());
-        assert_eq!(contents.root_block.unwrap().segment_index(), 3);
+        assert_eq!(
+            contents.root_block.unwrap().segment_index(),
+            SegmentIndex::new(3)
+        );
         assert_eq!(
             contents.root_block.unwrap().last_archived_block(),
             LastArchivedBlock {
@@ -215,7 +237,10 @@ fn basic() {
         // Nothing is fully contained here
         assert_eq!(contents.blocks, vec![]);
         assert!(contents.root_block.is_some());
-        assert_eq!(contents.root_block.unwrap().segment_index(), 3);
+        assert_eq!(
+            contents.root_block.unwrap().segment_index(),
+            SegmentIndex::new(3)
+        );
         assert_eq!(
             contents.root_block.unwrap().last_archived_block(),
             LastArchivedBlock {
@@ -373,8 +398,8 @@ fn invalid_usage() {
         assert_eq!(
             result,
             Err(ReconstructorError::IncorrectSegmentOrder {
-                expected_segment_index: 1,
-                actual_segment_index: 2
+                expected_segment_index: SegmentIndex::ONE,
+                actual_segment_index: SegmentIndex::new(2)
             })
         );
 
@@ -391,9 +416,175 @@ fn invalid_usage() {
         assert_eq!(
             result,
             Err(ReconstructorError::IncorrectSegmentOrder {
-                expected_segment_index: 2,
-                actual_segment_index: 3
+                expected_segment_index: SegmentIndex::new(2),
+                actual_segment_index: SegmentIndex::new(3)
             })
         );
     }
 }
+
+#[test]
+fn flush_partial() {
+    let kzg = Kzg::random(PIECES_IN_SEGMENT).unwrap();
+    let mut archiver = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg.clone()).unwrap();
+    // Block that fits into the segment fully
+    let block_0 = rand::random::<[u8; SEGMENT_SIZE as usize / 2]>().to_vec();
+    // Block that overflows into the next segment
+    let block_1 = rand::random::<[u8; SEGMENT_SIZE as usize]>().to_vec();
+    // Block that stays buffered
+    let block_2 = rand::random::<[u8; SEGMENT_SIZE as usize / 8]>().to_vec();
+    // Block archived after restart that overflows into the next segment
+    let block_3 = rand::random::<[u8; SEGMENT_SIZE as usize]>().to_vec();
+
+    let mut archived_segments = archiver
+        .add_block(block_0.clone(), BlockObjectMapping::default())
+        .into_iter()
+        .chain(archiver.add_block(block_1.clone(), BlockObjectMapping::default()))
+        .chain(archiver.add_block(block_2.clone(), BlockObjectMapping::default()))
+        .collect::<Vec<_>>();
+    assert_eq!(archived_segments.len(), 1);
+
+    // Shutdown, everything buffered is archived into padded segment
+    let flushed_segments = archiver.flush_partial();
+    assert_eq!(flushed_segments.len(), 1);
+    let flushed_root_block = flushed_segments[0].root_block;
+    assert_eq!(flushed_root_block.segment_index(), SegmentIndex::ONE);
+    assert_eq!(
+        flushed_root_block.last_archived_block(),
+        LastArchivedBlock {
+            number: 2,
+            archived_progress: ArchivedBlockProgress::Complete
+        }
+    );
+    archived_segments.extend(flushed_segments);
+    // Nothing left to flush
+    assert!(archiver.flush_partial().is_empty());
+
+    // Restart continues with the next block and the next segment
+    let mut archiver = Archiver::with_initial_state(
+        RECORD_SIZE,
+        SEGMENT_SIZE,
+        kzg,
+        flushed_root_block,
+        &block_2,
+        BlockObjectMapping::default(),
+    )
+    .unwrap();
+    assert_eq!(archiver.segment_index(), SegmentIndex::new(2));
+    archived_segments.extend(archiver.add_block(block_3.clone(), BlockObjectMapping::default()));
+    archived_segments.extend(archiver.flush_partial());
+    assert_eq!(archived_segments.len(), 4);
+
+    validate_root_block_chain(
+        &archived_segments
+            .iter()
+            .map(|archived_segment| archived_segment.root_block)
+            .collect::<Vec<_>>(),
+    )
+    .unwrap();
+
+    let mut reconstructor = Reconstructor::new(RECORD_SIZE, SEGMENT_SIZE).unwrap();
+    let blocks = archived_segments
+        .iter()
+        .map(|archived_segment| {
+            reconstructor
+                .add_segment(&pieces_to_option_of_pieces(&flat_pieces_to_regular(
+                    &archived_segment.pieces,
+                )))
+                .unwrap()
+                .blocks
+        })
+        .collect::<Vec<_>>();
+
+    // Padding is ignored and every block is reconstructed exactly once, blocks are complete as soon
+    // as padded segment is added
+    assert_eq!(
+        blocks,
+        vec![
+            vec![(0, block_0)],
+            vec![(1, block_1), (2, block_2)],
+            vec![],
+            vec![(3, block_3)],
+        ]
+    );
+}
+
+#[test]
+fn recovery_strategies() {
+    let kzg = Kzg::random(PIECES_IN_SEGMENT).unwrap();
+    let mut archiver = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg).unwrap();
+    // Block that occupies multiple segments
+    let block_0 = rand::random::<[u8; SEGMENT_SIZE as usize * 2]>().to_vec();
+    let block_1 = rand::random::<[u8; SEGMENT_SIZE as usize / 2]>().to_vec();
+    let archived_segments = archiver
+        .add_block(block_0, BlockObjectMapping::default())
+        .into_iter()
+        .chain(archiver.add_block(block_1, BlockObjectMapping::default()))
+        .collect::<Vec<_>>();
+    assert_eq!(archived_segments.len(), 2);
+
+    let parity_pieces = PIECES_IN_SEGMENT as usize / 2;
+    let mut fast_reconstructor = Reconstructor::new(RECORD_SIZE, SEGMENT_SIZE).unwrap();
+    let mut erasure_reconstructor = Reconstructor::new(RECORD_SIZE, SEGMENT_SIZE).unwrap();
+
+    for archived_segment in &archived_segments {
+        let pieces = pieces_to_option_of_pieces(&flat_pieces_to_regular(&archived_segment.pieces));
+
+        for lost_pieces in 1..=parity_pieces {
+            // Download that was cut off loses contiguous tail of the segment
+            let mut pieces = pieces.clone();
+            pieces.iter_mut().rev().take(lost_pieces).for_each(|piece| {
+                piece.take();
+            });
+
+            let reconstructor = Reconstructor::new(RECORD_SIZE, SEGMENT_SIZE).unwrap();
+            assert_eq!(
+                reconstructor.recovery_strategy(&pieces),
+                RecoveryStrategy::DataPieces
+            );
+            assert_eq!(
+                reconstructor
+                    .clone()
+                    .add_segment_with_strategy(&pieces, RecoveryStrategy::DataPieces)
+                    .unwrap(),
+                reconstructor
+                    .clone()
+                    .add_segment_with_strategy(&pieces, RecoveryStrategy::ErasureDecoding)
+                    .unwrap(),
+            );
+        }
+
+        // Both strategies must also agree when segments are added in sequence and blocks span
+        // segments
+        let mut pieces = pieces;
+        pieces
+            .iter_mut()
+            .rev()
+            .take(parity_pieces)
+            .for_each(|piece| {
+                piece.take();
+            });
+        assert_eq!(
+            fast_reconstructor.add_segment(&pieces).unwrap(),
+            erasure_reconstructor
+                .add_segment_with_strategy(&pieces, RecoveryStrategy::ErasureDecoding)
+                .unwrap(),
+        );
+
+        // Loss of any data piece requires erasure decoding
+        let mut pieces =
+            pieces_to_option_of_pieces(&flat_pieces_to_regular(&archived_segment.pieces));
+        pieces[0].take();
+        let reconstructor = Reconstructor::new(RECORD_SIZE, SEGMENT_SIZE).unwrap();
+        assert_eq!(
+            reconstructor.recovery_strategy(&pieces),
+            RecoveryStrategy::ErasureDecoding
+        );
+        assert_eq!(
+            reconstructor
+                .clone()
+                .add_segment_with_strategy(&pieces, RecoveryStrategy::DataPieces),
+            Err(ReconstructorError::MissingDataPieces)
+        );
+    }
+}
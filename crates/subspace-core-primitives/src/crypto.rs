@@ -18,7 +18,74 @@
 pub mod kzg;
 
 use crate::{Blake2b256Hash, BLAKE2B_256_HASH_SIZE};
-use blake2_rfc::blake2b::{blake2b, Blake2b};
+use blake2_rfc::blake2b::Blake2b;
+use core::fmt;
+
+/// Incremental BLAKE2b-256 hashing of data that is available in parts, for instance while it is
+/// streamed to or from disk.
+///
+/// Hash of data supplied in any number of [`Blake2b256Hasher::update()`] calls is the same as
+/// [`blake2b_256_hash()`] of concatenated data.
+#[derive(Clone)]
+pub struct Blake2b256Hasher {
+    state: Blake2b,
+}
+
+impl fmt::Debug for Blake2b256Hasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Blake2b256Hasher").finish_non_exhaustive()
+    }
+}
+
+impl Default for Blake2b256Hasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Blake2b256Hasher {
+    /// Create new hasher
+    pub fn new() -> Self {
+        Self {
+            state: Blake2b::new(BLAKE2B_256_HASH_SIZE),
+        }
+    }
+
+    /// Create new keyed hasher.
+    ///
+    /// PANIC: Panics if key is longer than 64 bytes.
+    pub fn with_key(key: &[u8]) -> Self {
+        Self {
+            state: Blake2b::with_key(BLAKE2B_256_HASH_SIZE, key),
+        }
+    }
+
+    /// Add `data` to hashed data
+    pub fn update(&mut self, data: &[u8]) {
+        self.state.update(data);
+    }
+
+    /// Hash of all data added so far
+    pub fn finalize(self) -> Blake2b256Hash {
+        self.state
+            .finalize()
+            .as_bytes()
+            .try_into()
+            .expect("Initialized with correct length; qed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for Blake2b256Hasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
 
 /// BLAKE2b-256 hashing of a single value.
 pub fn blake2b_256_hash(data: &[u8]) -> Blake2b256Hash {
@@ -39,21 +106,16 @@ pub fn blake2b_256_254_hash(data: &[u8]) -> Blake2b256Hash {
 ///
 /// PANIC: Panics if key is longer than 64 bytes.
 pub fn blake2b_256_hash_with_key(data: &[u8], key: &[u8]) -> Blake2b256Hash {
-    blake2b(BLAKE2B_256_HASH_SIZE, key, data)
-        .as_bytes()
-        .try_into()
-        .expect("Initialized with correct length; qed")
+    let mut hasher = Blake2b256Hasher::with_key(key);
+    hasher.update(data);
+    hasher.finalize()
 }
 
 /// BLAKE2b-256 hashing of a list of values.
 pub fn blake2b_256_hash_list(data: &[&[u8]]) -> Blake2b256Hash {
-    let mut state = Blake2b::new(BLAKE2B_256_HASH_SIZE);
+    let mut hasher = Blake2b256Hasher::new();
     for d in data {
-        state.update(d);
+        hasher.update(d);
     }
-    state
-        .finalize()
-        .as_bytes()
-        .try_into()
-        .expect("Initialized with correct length; qed")
+    hasher.finalize()
 }
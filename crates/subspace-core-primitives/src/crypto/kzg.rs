@@ -8,6 +8,7 @@ mod tests;
 extern crate alloc;
 
 use alloc::vec::Vec;
+use core::fmt;
 use dusk_bls12_381::{G1Affine, G2Affine, G2Prepared};
 pub use dusk_bytes;
 use dusk_bytes::{DeserializableSlice, Serializable};
@@ -20,6 +21,7 @@ use dusk_plonk::fft::polynomial::Polynomial as PlonkPolynomial;
 use dusk_plonk::prelude::BlsScalar;
 use parity_scale_codec::{Decode, Encode, EncodeLike, Input};
 use scale_info::{Type, TypeInfo};
+use subtle::ConstantTimeEq;
 
 const TEST_PUBLIC_PARAMETERS: &[u8] = include_bytes!("kzg/test-public-parameters.bin");
 
@@ -46,6 +48,24 @@ fn test_public_parameters_correct() {
     );
 }
 
+/// Identifier of the set of KZG public parameters.
+///
+/// Commitments and witnesses created with one set of parameters can't be verified with another,
+/// so anything derived from them (like plots) must record identifier of parameters used.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Encode, Decode, TypeInfo)]
+pub struct KzgParametersId(pub u16);
+
+impl fmt::Display for KzgParametersId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl KzgParametersId {
+    /// Identifier of [`test_public_parameters()`]
+    pub const TEST: Self = Self(0);
+}
+
 /// Commitment to polynomial
 #[derive(Debug, Clone)]
 pub struct Polynomial(PlonkPolynomial);
@@ -78,6 +98,12 @@ impl Commitment {
     pub fn try_from_bytes(bytes: &[u8; 48]) -> Result<Self, dusk_bytes::Error> {
         Ok(Commitment(G1Affine::from_bytes(bytes)?))
     }
+
+    /// Compare commitments in constant time, time taken doesn't depend on whether or where
+    /// commitments differ, use instead of `==` when checking commitments during verification
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0).into()
+    }
 }
 
 impl From<Commitment> for [u8; 48] {
@@ -247,6 +273,7 @@ impl TypeInfo for Witness {
 #[derive(Debug, Clone)]
 pub struct Kzg {
     public_parameters: PublicParameters,
+    id: KzgParametersId,
 }
 
 // Most of below implementation and comments are basically taken following code samples (and
@@ -255,19 +282,33 @@ pub struct Kzg {
 // https://github.com/maticnetwork/avail/blob/76e2b45d13975ba87b632f62f29497f279986cbc/kate/src/com.rs
 // https://github.com/maticnetwork/avail/blob/76e2b45d13975ba87b632f62f29497f279986cbc/kate/proof/src/lib.rs
 impl Kzg {
-    /// Create new instance with given public parameters
+    /// Create new instance with given public parameters, identified as [`KzgParametersId::TEST`]
+    /// since test parameters are the only ones in use right now
     pub fn new(public_parameters: PublicParameters) -> Self {
-        Self { public_parameters }
+        Self::with_id(public_parameters, KzgParametersId::TEST)
+    }
+
+    /// Create new instance with given public parameters and their identifier
+    pub fn with_id(public_parameters: PublicParameters, id: KzgParametersId) -> Self {
+        Self {
+            public_parameters,
+            id,
+        }
+    }
+
+    /// Identifier of public parameters used by this instance
+    pub fn id(&self) -> KzgParametersId {
+        self.id
     }
 
-    #[cfg(feature = "std")]
+    #[cfg(feature = "rand")]
     /// For testing purposes only.
     ///
     /// Returns an error if the configured degree is less than one.
     pub fn random(max_degree: u32) -> Result<Self, Error> {
         let public_parameters =
             PublicParameters::setup(max_degree as usize, &mut rand::thread_rng())?;
-        Ok(Self { public_parameters })
+        Ok(Self::new(public_parameters))
     }
 
     // /// Runs a one-time trusted setup of the universal reference values `KZG_PARAMETERS`. The
@@ -1,5 +1,5 @@
 use crate::crypto::kzg::dusk_bytes::Serializable;
-use crate::crypto::kzg::{BlsScalar, Kzg};
+use crate::crypto::kzg::{BlsScalar, Commitment, Kzg};
 
 #[test]
 fn basic() {
@@ -33,3 +33,43 @@ fn basic() {
         );
     }
 }
+
+#[test]
+fn commitment_ct_eq() {
+    let kzg = Kzg::random(256).unwrap();
+    let commitments = (1..5)
+        .map(|seed| {
+            let mut data = [0u8; 256];
+            data.chunks_exact_mut(BlsScalar::SIZE)
+                .for_each(|chunk| chunk[0] = seed);
+            kzg.commit(&kzg.poly(&data).unwrap()).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for (index, commitment) in commitments.iter().enumerate() {
+        let same_commitment = Commitment::try_from_bytes(&commitment.to_bytes()).unwrap();
+        assert!(commitment.ct_eq(commitment));
+        assert!(commitment.ct_eq(&same_commitment));
+
+        for (other_index, other_commitment) in commitments.iter().enumerate() {
+            assert_eq!(commitment.ct_eq(other_commitment), index == other_index);
+            assert_eq!(
+                commitment.ct_eq(other_commitment),
+                commitment == other_commitment
+            );
+        }
+    }
+
+    // Negated point has the same `x` coordinate and differs only in `y`, its encoding differs
+    // only in a single flag bit, comparison must not stop after the first matching coordinate
+    let commitment = commitments[1];
+    let negated = Commitment(-commitment.0);
+    assert_eq!(commitment.to_bytes()[1..], negated.to_bytes()[1..]);
+    assert!(!commitment.ct_eq(&negated));
+    assert!(!negated.ct_eq(&commitment));
+
+    // Point at infinity
+    assert!(Commitment::default().ct_eq(&Commitment::default()));
+    assert!(!commitment.ct_eq(&Commitment::default()));
+    assert!(!Commitment::default().ct_eq(&commitment));
+}
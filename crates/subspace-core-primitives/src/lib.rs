@@ -37,7 +37,7 @@ use core::convert::AsRef;
 use core::fmt;
 use core::num::{NonZeroU16, NonZeroU64};
 use core::ops::{Deref, DerefMut};
-use derive_more::{Add, Display, Div, Mul, Rem, Sub};
+use derive_more::{Add, AddAssign, Display, Div, From, Into, Mul, Rem, Sub, SubAssign};
 use num_traits::{WrappingAdd, WrappingSub};
 use parity_scale_codec::{Decode, Encode};
 use scale_info::TypeInfo;
@@ -69,7 +69,59 @@ pub type Randomness = [u8; RANDOMNESS_LENGTH];
 pub type BlockNumber = u32;
 
 /// Slot number in Subspace network.
-pub type SlotNumber = u64;
+///
+/// Conversions to and from `u64` are explicit to prevent mixing slot numbers up with other
+/// integers:
+///
+/// ```compile_fail
+/// use subspace_core_primitives::{SegmentIndex, SlotNumber};
+///
+/// fn slot(_slot_number: SlotNumber) {}
+///
+/// slot(SegmentIndex::ZERO);
+/// ```
+#[derive(
+    Debug,
+    Display,
+    Default,
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Add,
+    AddAssign,
+    Sub,
+    SubAssign,
+    From,
+    Into,
+    Encode,
+    Decode,
+    TypeInfo,
+)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[repr(transparent)]
+pub struct SlotNumber(u64);
+
+impl SlotNumber {
+    /// Create new slot number
+    pub const fn new(slot_number: u64) -> Self {
+        Self(slot_number)
+    }
+
+    /// Slot number as `u64`
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Checked subtraction, `None` is returned if `other` is after `self`
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+}
 
 /// Type of solution range.
 pub type SolutionRange = u64;
@@ -79,8 +131,93 @@ pub type SolutionRange = u64;
 /// The closer solution's tag is to the target, the heavier it is.
 pub type BlockWeight = u128;
 
-/// Segment index type.
-pub type SegmentIndex = u64;
+/// Segment index of archived history of the blockchain.
+///
+/// Conversions to and from `u64` are explicit to prevent mixing segment indexes up with other
+/// integers:
+///
+/// ```compile_fail
+/// use subspace_core_primitives::{SectorIndex, SegmentIndex};
+///
+/// fn segment(_segment_index: SegmentIndex) {}
+///
+/// segment(SectorIndex::ZERO);
+/// ```
+///
+/// ```compile_fail
+/// use subspace_core_primitives::SegmentIndex;
+///
+/// fn segment(_segment_index: SegmentIndex) {}
+///
+/// segment(1_u64);
+/// ```
+#[derive(
+    Debug,
+    Display,
+    Default,
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Add,
+    AddAssign,
+    Sub,
+    SubAssign,
+    From,
+    Into,
+    Encode,
+    Decode,
+    TypeInfo,
+)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[repr(transparent)]
+pub struct SegmentIndex(u64);
+
+impl SegmentIndex {
+    /// Segment index of the first segment
+    pub const ZERO: Self = Self(0);
+    /// Segment index one, to step to the next segment
+    pub const ONE: Self = Self(1);
+
+    /// Create new segment index
+    pub const fn new(segment_index: u64) -> Self {
+        Self(segment_index)
+    }
+
+    /// Segment index as `u64`
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Segment that contains piece with `piece_index`
+    pub const fn from_piece_index(piece_index: PieceIndex) -> Self {
+        Self(piece_index / PIECES_IN_SEGMENT as u64)
+    }
+
+    /// Index of the first piece in this segment
+    pub const fn first_piece_index(self) -> PieceIndex {
+        self.0 * PIECES_IN_SEGMENT as u64
+    }
+
+    /// Checked addition, `None` is returned on overflow
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    /// Checked subtraction, `None` is returned if `other` is greater than `self`
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// Saturating subtraction, stops at [`SegmentIndex::ZERO`]
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+}
 
 /// Records root type.
 pub type RecordsRoot = Commitment;
@@ -124,6 +261,12 @@ impl From<[u8; PUBLIC_KEY_LENGTH]> for PublicKey {
     }
 }
 
+impl From<&PublicKey> for PublicKey {
+    fn from(public_key: &PublicKey) -> Self {
+        *public_key
+    }
+}
+
 impl From<PublicKey> for [u8; PUBLIC_KEY_LENGTH] {
     fn from(public_key: PublicKey) -> Self {
         public_key.0
@@ -485,7 +628,7 @@ impl RootBlock {
     }
 
     /// Segment index
-    pub fn segment_index(&self) -> u64 {
+    pub fn segment_index(&self) -> SegmentIndex {
         match self {
             Self::V0 { segment_index, .. } => *segment_index,
         }
@@ -522,8 +665,75 @@ impl RootBlock {
 /// Piece index in consensus
 pub type PieceIndex = u64;
 
-/// Sector index in consensus
-pub type SectorIndex = u64;
+/// Sector index in consensus.
+///
+/// Conversions to and from `u64` are explicit to prevent mixing sector indexes up with other
+/// integers, sector offsets within a plot in particular:
+///
+/// ```compile_fail
+/// use subspace_core_primitives::{PublicKey, SectorId};
+///
+/// let sector_offset = 0_u64;
+/// SectorId::new(&PublicKey::default(), sector_offset);
+/// ```
+#[derive(
+    Debug,
+    Display,
+    Default,
+    Copy,
+    Clone,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    Add,
+    AddAssign,
+    Sub,
+    SubAssign,
+    From,
+    Into,
+    Encode,
+    Decode,
+    TypeInfo,
+)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[repr(transparent)]
+pub struct SectorIndex(u64);
+
+impl SectorIndex {
+    /// Sector index zero
+    pub const ZERO: Self = Self(0);
+
+    /// Create new sector index
+    pub const fn new(sector_index: u64) -> Self {
+        Self(sector_index)
+    }
+
+    /// Sector index as `u64`
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Index of the sector `sector_offset` sectors after this one, `None` on overflow
+    pub fn checked_offset(self, sector_offset: u64) -> Option<Self> {
+        self.0.checked_add(sector_offset).map(Self)
+    }
+
+    /// Index of the sector `sector_offset` sectors after this one.
+    ///
+    /// Panics on overflow in debug builds, same as regular integer addition.
+    pub fn offset(self, sector_offset: u64) -> Self {
+        Self(self.0 + sector_offset)
+    }
+
+    /// Number of sectors from `first_sector_index` to this sector, `None` if this sector comes
+    /// before `first_sector_index`
+    pub fn offset_from(self, first_sector_index: Self) -> Option<u64> {
+        self.0.checked_sub(first_sector_index.0)
+    }
+}
 
 /// Hash of `PieceIndex`
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Decode, Encode)]
@@ -626,7 +836,7 @@ where
         Self {
             public_key,
             reward_address,
-            sector_index: 0,
+            sector_index: SectorIndex::ZERO,
             total_pieces: NonZeroU64::new(1).expect("1 is not 0; qed"),
             piece_offset: 0,
             piece_record_hash: Blake2b256Hash::default(),
@@ -648,6 +858,111 @@ pub fn bidirectional_distance<T: WrappingSub + Ord>(a: &T, b: &T) -> T {
     diff.min(diff2)
 }
 
+/// Probability that audit of a single sector produces a chunk within `solution_range` during a
+/// slot.
+///
+/// Chunk is within solution range when its bidirectional distance to local challenge is at most
+/// half of the solution range, expanded chunks are uniformly distributed across the whole
+/// [`SolutionRange`] space.
+pub fn sector_solution_probability(solution_range: SolutionRange) -> f64 {
+    let values_within_solution_range = u128::from(solution_range / 2) * 2 + 1;
+
+    values_within_solution_range as f64 / (u128::from(SolutionRange::MAX) + 1) as f64
+}
+
+/// Expected number of solutions per slot produced by `sector_count` sectors with `solution_range`
+pub fn expected_solutions_per_slot(sector_count: u64, solution_range: SolutionRange) -> f64 {
+    sector_count as f64 * sector_solution_probability(solution_range)
+}
+
+/// Solution range at which `sector_count` sectors are expected to produce solutions with
+/// `slot_probability`, this is the value solution range adjustment converges to when
+/// `sector_count` is the total number of sectors pledged to the network.
+///
+/// Saturates at [`SolutionRange::MAX`], zero `sector_count` is treated as a single sector.
+pub fn solution_range_for_sectors(
+    sector_count: u64,
+    slot_probability: (u64, u64),
+) -> SolutionRange {
+    let solution_range = (u128::from(SolutionRange::MAX) + 1)
+        .saturating_mul(u128::from(slot_probability.0))
+        / u128::from(slot_probability.1)
+        / u128::from(sector_count.max(1));
+
+    SolutionRange::try_from(solution_range).unwrap_or(SolutionRange::MAX)
+}
+
+/// Number of sectors pledged to the network that solution range adjustment converges to
+/// `solution_range` with at `slot_probability`, inverse of [`solution_range_for_sectors()`].
+///
+/// Saturates at [`u64::MAX`], zero `solution_range` is treated as `1`.
+pub fn solution_range_to_sectors(
+    solution_range: SolutionRange,
+    slot_probability: (u64, u64),
+) -> u64 {
+    let sector_count = (u128::from(SolutionRange::MAX) + 1)
+        .saturating_mul(u128::from(slot_probability.0))
+        / u128::from(slot_probability.1)
+        / u128::from(solution_range.max(1));
+
+    u64::try_from(sector_count).unwrap_or(u64::MAX)
+}
+
+/// Space in bytes pledged to the network that solution range adjustment converges to
+/// `solution_range` with at `slot_probability`, sectors are plotted with `space_l`.
+///
+/// Saturates at [`u64::MAX`].
+pub fn solution_range_to_space(
+    solution_range: SolutionRange,
+    slot_probability: (u64, u64),
+    space_l: NonZeroU16,
+) -> u64 {
+    solution_range_to_sectors(solution_range, slot_probability)
+        .saturating_mul(plot_sector_size(space_l))
+}
+
+/// Solution range at which `space` bytes pledged to the network with sectors plotted with `space_l`
+/// are expected to produce solutions with `slot_probability`, inverse of
+/// [`solution_range_to_space()`].
+pub fn space_to_solution_range(
+    space: u64,
+    slot_probability: (u64, u64),
+    space_l: NonZeroU16,
+) -> SolutionRange {
+    solution_range_for_sectors(space / plot_sector_size(space_l), slot_probability)
+}
+
+/// Probability of two independent events with probabilities `a` and `b` (for instance slot
+/// probability and probability of the farmer winning the slot) both happening.
+///
+/// Result is reduced, if it still doesn't fit into `u64` it is approximated with a fraction that
+/// does, zero denominator is treated as `1`.
+pub fn combine_slot_probabilities(a: (u64, u64), b: (u64, u64)) -> (u64, u64) {
+    fn gcd(mut a: u128, mut b: u128) -> u128 {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    }
+
+    let mut numerator = u128::from(a.0) * u128::from(b.0);
+    let mut denominator = u128::from(a.1.max(1)) * u128::from(b.1.max(1));
+    let divisor = gcd(numerator, denominator);
+    numerator /= divisor;
+    denominator /= divisor;
+
+    // Drop the same number of least significant bits from both to fit into `u64`
+    let excess_bits =
+        (u128::BITS - numerator.max(denominator).leading_zeros()).saturating_sub(u64::BITS);
+    numerator >>= excess_bits;
+    denominator = (denominator >> excess_bits).max(1);
+
+    (
+        u64::try_from(numerator).expect("Shifted to fit into u64 above; qed"),
+        u64::try_from(denominator).expect("Shifted to fit into u64 above; qed"),
+    )
+}
+
 #[allow(clippy::assign_op_pattern, clippy::ptr_offset_with_cast)]
 mod private_u256 {
     //! This module is needed to scope clippy allows
@@ -839,13 +1154,29 @@ impl SectorId {
     /// Create new sector ID by deriving it from public key and sector index
     pub fn new(public_key: &PublicKey, sector_index: SectorIndex) -> Self {
         Self(blake2b_256_hash_with_key(
-            &sector_index.to_le_bytes(),
+            &sector_index.get().to_le_bytes(),
+            public_key.as_ref(),
+        ))
+    }
+
+    /// Create new sector ID for sector encoded under `rotation` of the global salt, rotation `0`
+    /// means no rotation and results in the same sector ID as [`SectorId::new()`]
+    pub fn new_rotated(public_key: &PublicKey, sector_index: SectorIndex, rotation: u64) -> Self {
+        if rotation == 0 {
+            return Self::new(public_key, sector_index);
+        }
+
+        Self(blake2b_256_hash_with_key(
+            &(sector_index, rotation).encode(),
             public_key.as_ref(),
         ))
     }
 
     /// Derive piece index that should be stored in sector at `piece_offset` when number of pieces
-    /// of blockchain_history is `total_pieces`
+    /// of blockchain_history is `total_pieces`.
+    ///
+    /// Piece indexes are uniformly distributed across `0..total_pieces` for any `total_pieces`,
+    /// including history of a single piece, in which case every offset maps to piece `0`.
     pub fn derive_piece_index(
         &self,
         piece_offset: PieceIndex,
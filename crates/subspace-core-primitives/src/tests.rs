@@ -1,6 +1,340 @@
-use crate::U256;
+use crate::crypto::{
+    blake2b_256_hash, blake2b_256_hash_list, blake2b_256_hash_with_key, Blake2b256Hasher,
+};
+use crate::{
+    bidirectional_distance, combine_slot_probabilities, expected_solutions_per_slot,
+    sector_solution_probability, solution_range_for_sectors, solution_range_to_sectors,
+    solution_range_to_space, space_to_solution_range, Chunk, PublicKey, SectorId, SectorIndex,
+    SegmentIndex, SlotNumber, Solution, SolutionRange, U256,
+};
+use core::num::{NonZeroU16, NonZeroU64};
+use parity_scale_codec::{Decode, Encode};
+// Tests in this module are also run on `wasm32-unknown-unknown` with `wasm-bindgen-test`
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
 
 #[test]
 fn piece_distance_middle() {
     assert_eq!(U256::MIDDLE, U256::MAX / 2);
 }
+
+#[test]
+fn blake2b_256_known_value() {
+    assert_eq!(
+        hex::encode(blake2b_256_hash(b"abc")),
+        "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319"
+    );
+}
+
+#[test]
+fn blake2b_256_hasher_known_values() {
+    assert_eq!(
+        hex::encode(Blake2b256Hasher::new().finalize()),
+        "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+    );
+
+    // The same hash regardless of how data is split
+    let mut hasher = Blake2b256Hasher::new();
+    hasher.update(b"a");
+    hasher.update(b"");
+    hasher.update(b"bc");
+    assert_eq!(
+        hex::encode(hasher.finalize()),
+        "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319"
+    );
+
+    // Data larger than a single block, split at and around block boundaries
+    let mut data = [0u8; 1000];
+    data.iter_mut()
+        .enumerate()
+        .for_each(|(i, byte)| *byte = i as u8);
+    for chunk_size in [1, 127, 128, 129, 1000] {
+        let mut hasher = Blake2b256Hasher::new();
+        for chunk in data.chunks(chunk_size) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize(), blake2b_256_hash(&data));
+    }
+    assert_eq!(
+        blake2b_256_hash_list(&[&data[..500], &data[500..]]),
+        blake2b_256_hash(&data)
+    );
+
+    let mut hasher = Blake2b256Hasher::with_key(b"key");
+    hasher.update(&data);
+    assert_eq!(hasher.finalize(), blake2b_256_hash_with_key(&data, b"key"));
+    assert_ne!(
+        blake2b_256_hash_with_key(&data, b"key"),
+        blake2b_256_hash(&data)
+    );
+}
+
+#[test]
+fn sector_id_and_local_challenge_known_values() {
+    let mut public_key = [0u8; 32];
+    public_key
+        .iter_mut()
+        .enumerate()
+        .for_each(|(index, byte)| *byte = index as u8);
+    let public_key = PublicKey::from(public_key);
+
+    let sector_id = SectorId::new(&public_key, SectorIndex::new(5));
+    assert_eq!(
+        hex::encode(sector_id),
+        "1b0e63fc74fa7c8b519335f7192f3618eb4242b7ff8776d8df99b49ee788fd15"
+    );
+    assert_ne!(sector_id, SectorId::new(&public_key, SectorIndex::new(6)));
+    assert_eq!(
+        SectorId::new_rotated(&public_key, SectorIndex::new(5), 0),
+        sector_id
+    );
+    let rotated_sector_id = SectorId::new_rotated(&public_key, SectorIndex::new(5), 1);
+    assert_ne!(rotated_sector_id, sector_id);
+    assert_ne!(
+        rotated_sector_id,
+        SectorId::new_rotated(&public_key, SectorIndex::new(5), 2)
+    );
+    assert_ne!(
+        rotated_sector_id,
+        SectorId::new_rotated(&public_key, SectorIndex::new(6), 1)
+    );
+
+    let mut global_challenge = [0u8; 32];
+    global_challenge
+        .iter_mut()
+        .enumerate()
+        .for_each(|(index, byte)| *byte = 0xff - index as u8);
+    assert_eq!(
+        sector_id.derive_local_challenge(&global_challenge),
+        13210433005899677128
+    );
+}
+
+#[test]
+fn chunk_within_solution_range() {
+    let local_challenge: SolutionRange = 13210433005899677128;
+    let chunk = Chunk([1, 2, 3, 4, 5, 6, 7, 8]);
+
+    let expanded_chunk = chunk.expand(local_challenge);
+    assert_eq!(expanded_chunk, 10297974137895562292);
+
+    let distance = bidirectional_distance(&local_challenge, &expanded_chunk);
+    assert_eq!(distance, 2912458868004114836);
+    assert_eq!(
+        distance,
+        bidirectional_distance(&expanded_chunk, &local_challenge)
+    );
+
+    // Chunk is within solution range when distance is at most half of it
+    let solution_range = distance * 2;
+    assert!(distance <= solution_range / 2);
+    assert!(distance > (solution_range - 2) / 2);
+
+    // Distance wraps around
+    assert_eq!(bidirectional_distance(&1u64, &SolutionRange::MAX), 2);
+}
+
+#[test]
+fn solution_probability_known_values() {
+    // Only exact match with local challenge
+    assert_eq!(sector_solution_probability(0), 1.0 / 2f64.powi(64));
+    assert_eq!(sector_solution_probability(SolutionRange::MAX), 1.0);
+    assert_eq!(sector_solution_probability(SolutionRange::MAX / 4), 0.25);
+    assert_eq!(
+        sector_solution_probability(SolutionRange::MAX / 1024),
+        1.0 / 1024.0
+    );
+
+    assert_eq!(expected_solutions_per_slot(0, SolutionRange::MAX), 0.0);
+    assert_eq!(
+        expected_solutions_per_slot(1000, SolutionRange::MAX / 4),
+        250.0
+    );
+    assert_eq!(
+        expected_solutions_per_slot(1024 * 1024, SolutionRange::MAX / 1024),
+        1024.0
+    );
+}
+
+#[test]
+fn solution_range_for_sectors_known_values() {
+    assert_eq!(solution_range_for_sectors(4, (1, 1)), 1 << 62);
+    assert_eq!(solution_range_for_sectors(1, (1, 6)), 3074457345618258602);
+    assert_eq!(solution_range_for_sectors(1000, (1, 6)), 3074457345618258);
+    // Saturates instead of overflowing
+    assert_eq!(solution_range_for_sectors(1, (1, 1)), SolutionRange::MAX);
+    assert_eq!(solution_range_for_sectors(0, (1, 6)), 3074457345618258602);
+
+    // Network with this solution range produces solutions with desired slot probability
+    let sector_count = 1_000_000;
+    let solution_range = solution_range_for_sectors(sector_count, (1, 6));
+    let expected_solutions = expected_solutions_per_slot(sector_count, solution_range);
+    assert!((expected_solutions - 1.0 / 6.0).abs() < 1e-9);
+}
+
+#[test]
+fn solution_range_to_sectors_known_values() {
+    assert_eq!(solution_range_to_sectors(1 << 62, (1, 1)), 4);
+    assert_eq!(solution_range_to_sectors(SolutionRange::MAX, (1, 1)), 1);
+    assert_eq!(solution_range_to_sectors(SolutionRange::MAX, (1, 6)), 0);
+    assert_eq!(solution_range_to_sectors(1, (1, 6)), 3074457345618258602);
+    // Saturates instead of overflowing
+    assert_eq!(solution_range_to_sectors(1, (1, 1)), u64::MAX);
+    assert_eq!(solution_range_to_sectors(0, (1, 1)), u64::MAX);
+    assert_eq!(solution_range_to_sectors(0, (1, 6)), 3074457345618258602);
+
+    // Inverse of `solution_range_for_sectors()`
+    for sector_count in [1, 4, 1000, 1_000_000, 1 << 30] {
+        for slot_probability in [(1, 1), (1, 6), (3, 7)] {
+            assert_eq!(
+                solution_range_to_sectors(
+                    solution_range_for_sectors(sector_count, slot_probability),
+                    slot_probability
+                ),
+                sector_count
+            );
+        }
+    }
+}
+
+#[test]
+fn solution_range_to_space_known_values() {
+    let space_l = NonZeroU16::new(20).unwrap();
+    // 2.5 MiB sectors
+    let plot_sector_size = 2621440;
+
+    assert_eq!(
+        solution_range_to_space(1 << 62, (1, 1), space_l),
+        4 * plot_sector_size
+    );
+    assert_eq!(
+        space_to_solution_range(4 * plot_sector_size, (1, 1), space_l),
+        1 << 62
+    );
+    // Partial sectors are not counted
+    assert_eq!(
+        space_to_solution_range(5 * plot_sector_size - 1, (1, 1), space_l),
+        1 << 62
+    );
+    assert_eq!(
+        solution_range_to_space(SolutionRange::MAX, (1, 1), space_l),
+        plot_sector_size
+    );
+    // Saturates instead of overflowing
+    assert_eq!(solution_range_to_space(1, (1, 1), space_l), u64::MAX);
+    assert_eq!(
+        space_to_solution_range(0, (1, 1), space_l),
+        SolutionRange::MAX
+    );
+    assert_eq!(space_to_solution_range(u64::MAX, (1, 6), space_l), 436906);
+}
+
+#[test]
+fn combine_slot_probabilities_known_values() {
+    assert_eq!(combine_slot_probabilities((1, 6), (1, 1)), (1, 6));
+    assert_eq!(combine_slot_probabilities((1, 6), (3, 4)), (1, 8));
+    assert_eq!(combine_slot_probabilities((2, 1), (3, 1)), (6, 1));
+    assert_eq!(combine_slot_probabilities((0, 6), (1, 2)), (0, 1));
+    // Zero denominator is treated as `1`
+    assert_eq!(combine_slot_probabilities((1, 0), (1, 0)), (1, 1));
+    // Reduced result fits without approximation
+    assert_eq!(
+        combine_slot_probabilities((u64::MAX, u64::MAX), (1, u64::MAX)),
+        (1, u64::MAX)
+    );
+    // Approximated when reduced result doesn't fit
+    assert_eq!(
+        combine_slot_probabilities((1, u64::MAX), (1, u64::MAX - 1)),
+        (0, u64::MAX - 2)
+    );
+    assert_eq!(
+        combine_slot_probabilities((u64::MAX, 1), (u64::MAX, 1)),
+        (u64::MAX - 1, 1)
+    );
+}
+
+#[test]
+fn solution_encoding() {
+    let solution = Solution::genesis_solution(PublicKey::from([1; 32]), PublicKey::from([2; 32]));
+
+    // All fields have fixed size, so there is no length prefix anywhere
+    let encoded = solution.encode();
+    assert_eq!(encoded.len(), 272);
+    assert_eq!(
+        Solution::<PublicKey, PublicKey>::decode(&mut encoded.as_slice()).unwrap(),
+        solution
+    );
+
+    // Truncated input results in an error rather than panic
+    for length in 0..encoded.len() {
+        assert!(Solution::<PublicKey, PublicKey>::decode(&mut &encoded[..length]).is_err());
+    }
+
+    // Witness is validated during decoding
+    let mut encoded = encoded;
+    let witness_offset = 32 + 32 + 8 + 8 + 8 + 32;
+    encoded[witness_offset..][..48].fill(0xff);
+    assert!(Solution::<PublicKey, PublicKey>::decode(&mut encoded.as_slice()).is_err());
+}
+
+#[test]
+fn index_newtypes() {
+    // Newtypes are encoded exactly like integers they wrap, so stored data remains compatible
+    assert_eq!(SectorIndex::new(5).encode(), 5u64.encode());
+    assert_eq!(SegmentIndex::new(5).encode(), 5u64.encode());
+    assert_eq!(SlotNumber::new(5).encode(), 5u64.encode());
+    assert_eq!(
+        SegmentIndex::decode(&mut 5u64.encode().as_slice()).unwrap(),
+        SegmentIndex::new(5)
+    );
+
+    assert_eq!(u64::from(SectorIndex::from(7u64)), 7);
+    assert_eq!(SectorIndex::new(7).offset(3), SectorIndex::new(10));
+    assert_eq!(
+        SectorIndex::new(10).offset_from(SectorIndex::new(7)),
+        Some(3)
+    );
+    assert_eq!(SectorIndex::new(7).offset_from(SectorIndex::new(10)), None);
+    assert_eq!(SectorIndex::new(u64::MAX).checked_offset(1), None);
+
+    assert_eq!(SegmentIndex::from_piece_index(255), SegmentIndex::ZERO);
+    assert_eq!(SegmentIndex::from_piece_index(256), SegmentIndex::ONE);
+    assert_eq!(SegmentIndex::new(2).first_piece_index(), 512);
+    assert_eq!(SegmentIndex::ONE + SegmentIndex::ONE, SegmentIndex::new(2));
+    assert_eq!(SegmentIndex::ZERO.checked_sub(SegmentIndex::ONE), None);
+}
+
+#[test]
+fn derive_piece_index_small_total_pieces() {
+    let public_key = PublicKey::from([1u8; 32]);
+    let sector_id = SectorId::new(&public_key, SectorIndex::ZERO);
+
+    // Freshly started network has a single piece, every piece offset maps to it
+    let total_pieces = NonZeroU64::new(1).unwrap();
+    for piece_offset in 0..1000 {
+        assert_eq!(sector_id.derive_piece_index(piece_offset, total_pieces), 0);
+    }
+
+    for total_pieces in [2, 3, 5, 7, 13, 251] {
+        let mut counts = vec![0u64; total_pieces as usize];
+        let samples_per_piece = 1000;
+        for piece_offset in 0..total_pieces * samples_per_piece {
+            let piece_index =
+                sector_id.derive_piece_index(piece_offset, NonZeroU64::new(total_pieces).unwrap());
+            assert!(piece_index < total_pieces);
+            counts[piece_index as usize] += 1;
+        }
+
+        // Chi-squared statistic of uniform distribution, for up to 250 degrees of freedom 400 is
+        // far beyond any reasonable significance level, while biased derivation blows past it
+        let expected = samples_per_piece as f64;
+        let chi_squared = counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum::<f64>();
+        assert!(
+            chi_squared < 400.0,
+            "Piece selection for {total_pieces} total pieces is not uniform: {chi_squared}"
+        );
+    }
+}
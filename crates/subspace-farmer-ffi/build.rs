@@ -0,0 +1,25 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").expect("Always set by Cargo; qed");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("Always set by Cargo; qed"));
+
+    cbindgen::generate(&crate_dir)
+        .expect("Failed to generate C header")
+        .write_to_file(out_dir.join("subspace_farmer_ffi.h"));
+
+    // Used by tests to compile C programs against generated header for the same target
+    println!(
+        "cargo:rustc-env=SUBSPACE_FARMER_FFI_INCLUDE_DIR={}",
+        out_dir.display()
+    );
+    for variable in ["TARGET", "HOST"] {
+        println!(
+            "cargo:rustc-env={variable}={}",
+            env::var(variable).expect("Always set by Cargo; qed")
+        );
+    }
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,646 @@
+//! C API for auditing and plotting sectors of Subspace farmer plots.
+//!
+//! Ownership rules:
+//! * objects returned through out pointers are owned by the caller and must be freed with the
+//!   corresponding `*_free` function exactly once;
+//! * all other pointers are borrowed only for the duration of the call, buffers are never retained;
+//! * string returned by [`subspace_last_error`] is owned by the library and is valid until the next
+//!   call into the library from the same thread.
+//!
+//! Every function returns [`SubspaceResult`], Rust panics never cross the boundary and are
+//! reported as [`SubspaceResult::Panic`] instead.
+
+#![warn(missing_docs)]
+
+use async_trait::async_trait;
+use futures::executor::block_on;
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{c_void, CStr, CString};
+use std::io::{self, Cursor};
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::{fmt, ptr, slice};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, Piece, PieceIndex, PublicKey, SectorIndex, SegmentIndex,
+    SolutionRange, BLAKE2B_256_HASH_SIZE, PIECE_SIZE, PUBLIC_KEY_LENGTH,
+};
+use subspace_farmer::single_disk_plot::farming::{audit_sector, EligibleSector};
+use subspace_farmer::single_disk_plot::idle_verification::sector_checksum;
+use subspace_farmer::single_disk_plot::piece_receiver::PieceReceiver;
+use subspace_farmer::single_disk_plot::plotting::plot_sector;
+use subspace_farmer::single_disk_plot::read_only::PlotReader;
+use subspace_farmer::single_disk_plot::sector_metadata::{
+    SectorMetadataRecord, SECTOR_METADATA_RECORD_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Result of the call
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SubspaceResult {
+    /// Call succeeded
+    Ok = 0,
+    /// Required pointer was null
+    NullPointer = 1,
+    /// Argument has invalid value
+    InvalidArgument = 2,
+    /// Failed to open plot
+    PlotOpen = 3,
+    /// Audit failed
+    Audit = 4,
+    /// Plotting failed
+    Plotting = 5,
+    /// Panic happened inside of the library, this is a bug
+    Panic = 6,
+}
+
+struct FfiError {
+    result: SubspaceResult,
+    message: String,
+}
+
+impl FfiError {
+    fn new(result: SubspaceResult, message: impl fmt::Display) -> Self {
+        Self {
+            result,
+            message: message.to_string(),
+        }
+    }
+
+    fn null_pointer(argument: &str) -> Self {
+        Self::new(
+            SubspaceResult::NullPointer,
+            format!("`{argument}` must not be null"),
+        )
+    }
+}
+
+fn set_last_error(message: String) {
+    // Interior null bytes are not expected in error messages, but must not cause another error
+    let message = CString::new(message.replace('\0', " ")).expect("Null bytes were removed; qed");
+    LAST_ERROR.with(|last_error| last_error.borrow_mut().replace(message));
+}
+
+/// Run `f` converting both errors and panics into result code and last error
+fn ffi_call<F>(f: F) -> SubspaceResult
+where
+    F: FnOnce() -> Result<(), FfiError>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            LAST_ERROR.with(|last_error| last_error.borrow_mut().take());
+            SubspaceResult::Ok
+        }
+        Ok(Err(error)) => {
+            set_last_error(error.message);
+            error.result
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Unknown panic".to_string());
+            set_last_error(format!("Panic: {message}"));
+            SubspaceResult::Panic
+        }
+    }
+}
+
+/// # Safety
+/// `pointer` must be null or valid for reads of `len` bytes.
+unsafe fn borrow_slice<'a>(
+    pointer: *const u8,
+    len: usize,
+    argument: &str,
+) -> Result<&'a [u8], FfiError> {
+    if pointer.is_null() {
+        return Err(FfiError::null_pointer(argument));
+    }
+
+    Ok(slice::from_raw_parts(pointer, len))
+}
+
+/// # Safety
+/// `pointer` must be null or valid for writes of `len` bytes.
+unsafe fn borrow_slice_mut<'a>(
+    pointer: *mut u8,
+    len: usize,
+    argument: &str,
+) -> Result<&'a mut [u8], FfiError> {
+    if pointer.is_null() {
+        return Err(FfiError::null_pointer(argument));
+    }
+
+    Ok(slice::from_raw_parts_mut(pointer, len))
+}
+
+/// # Safety
+/// `pointer` must be null or point to [`BLAKE2B_256_HASH_SIZE`] readable bytes.
+unsafe fn read_hash(pointer: *const u8, argument: &str) -> Result<Blake2b256Hash, FfiError> {
+    let mut hash = Blake2b256Hash::default();
+    hash.copy_from_slice(borrow_slice(pointer, BLAKE2B_256_HASH_SIZE, argument)?);
+    Ok(hash)
+}
+
+/// # Safety
+/// `pointer` must be null or point to [`PUBLIC_KEY_LENGTH`] readable bytes.
+unsafe fn read_public_key(pointer: *const u8) -> Result<PublicKey, FfiError> {
+    let mut public_key = [0; PUBLIC_KEY_LENGTH];
+    public_key.copy_from_slice(borrow_slice(pointer, PUBLIC_KEY_LENGTH, "public_key")?);
+    Ok(PublicKey::from(public_key))
+}
+
+/// Farmer protocol info, mirrors `FarmerProtocolInfo` from Rust API
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SubspaceFarmerProtocolInfo {
+    /// Genesis hash of the chain
+    pub genesis_hash: [u8; 32],
+    /// The size of data in one piece (in bytes), must not be zero
+    pub record_size: u32,
+    /// Recorded history is encoded and plotted in segments of this size (in bytes)
+    pub recorded_history_segment_size: u32,
+    /// Total number of pieces stored on the network, must not be zero
+    pub total_pieces: u64,
+    /// Space parameter for proof-of-replication in bits, must not be zero
+    pub space_l: u16,
+    /// Number of segments after which sector expires
+    pub sector_expiration: u64,
+    /// Rotation of the global salt sector is encoded with, `0` if no rotation. Sectors plotted
+    /// under rotation can only be audited with the same rotation.
+    pub rotation: u64,
+}
+
+impl From<&FarmerProtocolInfo> for SubspaceFarmerProtocolInfo {
+    fn from(farmer_protocol_info: &FarmerProtocolInfo) -> Self {
+        Self {
+            genesis_hash: farmer_protocol_info.genesis_hash,
+            record_size: farmer_protocol_info.record_size.get(),
+            recorded_history_segment_size: farmer_protocol_info.recorded_history_segment_size,
+            total_pieces: farmer_protocol_info.total_pieces.get(),
+            space_l: farmer_protocol_info.space_l.get(),
+            sector_expiration: farmer_protocol_info.sector_expiration.get(),
+            rotation: farmer_protocol_info.rotation,
+        }
+    }
+}
+
+/// # Safety
+/// `farmer_protocol_info` must be null or valid for reads.
+unsafe fn read_farmer_protocol_info(
+    farmer_protocol_info: *const SubspaceFarmerProtocolInfo,
+) -> Result<FarmerProtocolInfo, FfiError> {
+    let farmer_protocol_info = farmer_protocol_info
+        .as_ref()
+        .ok_or_else(|| FfiError::null_pointer("farmer_protocol_info"))?;
+    let zero_error = |field: &str| {
+        FfiError::new(
+            SubspaceResult::InvalidArgument,
+            format!("Farmer protocol info field `{field}` must not be zero"),
+        )
+    };
+
+    Ok(FarmerProtocolInfo {
+        genesis_hash: farmer_protocol_info.genesis_hash,
+        record_size: NonZeroU32::new(farmer_protocol_info.record_size)
+            .ok_or_else(|| zero_error("record_size"))?,
+        recorded_history_segment_size: farmer_protocol_info.recorded_history_segment_size,
+        total_pieces: NonZeroU64::new(farmer_protocol_info.total_pieces)
+            .ok_or_else(|| zero_error("total_pieces"))?,
+        space_l: NonZeroU16::new(farmer_protocol_info.space_l)
+            .ok_or_else(|| zero_error("space_l"))?,
+        sector_expiration: SegmentIndex::new(farmer_protocol_info.sector_expiration),
+        rotation: farmer_protocol_info.rotation,
+    })
+}
+
+/// Result of sector audit
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SubspaceAuditResult {
+    /// Whether sector is eligible for solution, the rest of fields are only set if it is
+    pub eligible: bool,
+    /// Sector index
+    pub sector_index: u64,
+    /// Derived local challenge
+    pub local_challenge: u64,
+    /// Audit index corresponding to the challenge used
+    pub audit_index: u64,
+    /// Offset of the piece in sector where chunk is located
+    pub audit_piece_offset: u64,
+    /// Chunk at audit index
+    pub chunk: [u8; 8],
+    /// Expanded version of the above chunk
+    pub expanded_chunk: u64,
+}
+
+impl From<Option<EligibleSector>> for SubspaceAuditResult {
+    fn from(eligible_sector: Option<EligibleSector>) -> Self {
+        match eligible_sector {
+            Some(eligible_sector) => {
+                let mut chunk = [0; 8];
+                chunk.copy_from_slice(eligible_sector.chunk.as_ref());
+
+                Self {
+                    eligible: true,
+                    sector_index: eligible_sector.sector_index.get(),
+                    local_challenge: eligible_sector.local_challenge,
+                    audit_index: eligible_sector.audit_index,
+                    audit_piece_offset: eligible_sector.audit_piece_offset,
+                    chunk,
+                    expanded_chunk: eligible_sector.expanded_chunk,
+                }
+            }
+            None => Self {
+                eligible: false,
+                sector_index: 0,
+                local_challenge: 0,
+                audit_index: 0,
+                audit_piece_offset: 0,
+                chunk: [0; 8],
+                expanded_chunk: 0,
+            },
+        }
+    }
+}
+
+/// Plot opened in read-only mode, opaque for C code
+pub struct SubspacePlot {
+    inner: PlotReader,
+    kzg: Kzg,
+}
+
+/// Last error message that happened on this thread or null if the last call succeeded.
+///
+/// Returned string is owned by the library and is valid until the next call into the library from
+/// the same thread.
+#[no_mangle]
+pub extern "C" fn subspace_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map(|message| message.as_ptr())
+            .unwrap_or(ptr::null())
+    })
+}
+
+/// Size of the piece in bytes
+#[no_mangle]
+pub extern "C" fn subspace_piece_size() -> usize {
+    PIECE_SIZE
+}
+
+/// Size of the plotted sector in bytes for given space parameter
+#[no_mangle]
+pub extern "C" fn subspace_plot_sector_size(space_l: u16) -> u64 {
+    match NonZeroU16::new(space_l) {
+        Some(space_l) => plot_sector_size(space_l),
+        None => 0,
+    }
+}
+
+/// Size of encoded sector metadata record in bytes, record includes rotation sector was encoded
+/// with and sector checksum in addition to sector metadata itself, exactly like records farmer
+/// stores for every sector of the plot
+#[no_mangle]
+pub extern "C" fn subspace_sector_metadata_size() -> usize {
+    SECTOR_METADATA_RECORD_SIZE
+}
+
+/// Open plot located in `directory` in read-only mode, on success `*out_plot` must be freed with
+/// [`subspace_plot_free`].
+///
+/// # Safety
+/// `directory` must be a valid null-terminated UTF-8 string, `out_plot` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn subspace_plot_open(
+    directory: *const c_char,
+    out_plot: *mut *mut SubspacePlot,
+) -> SubspaceResult {
+    ffi_call(|| {
+        if directory.is_null() {
+            return Err(FfiError::null_pointer("directory"));
+        }
+        if out_plot.is_null() {
+            return Err(FfiError::null_pointer("out_plot"));
+        }
+        let directory = CStr::from_ptr(directory).to_str().map_err(|error| {
+            FfiError::new(
+                SubspaceResult::InvalidArgument,
+                format!("`directory` is not valid UTF-8: {error}"),
+            )
+        })?;
+
+        let plot = PlotReader::open(Path::new(directory))
+            .map_err(|error| FfiError::new(SubspaceResult::PlotOpen, error))?;
+
+        out_plot.write(Box::into_raw(Box::new(SubspacePlot {
+            inner: plot,
+            kzg: Kzg::new(kzg::test_public_parameters()),
+        })));
+
+        Ok(())
+    })
+}
+
+/// Free plot opened with [`subspace_plot_open`], does nothing if `plot` is null.
+///
+/// # Safety
+/// `plot` must be null or a pointer returned by [`subspace_plot_open`] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn subspace_plot_free(plot: *mut SubspacePlot) {
+    if !plot.is_null() {
+        // Dropping only closes files, but panic must not cross the boundary regardless
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(plot))));
+    }
+}
+
+/// Number of sectors in the plot, sectors are identified by offset in `0..sector_count`.
+///
+/// # Safety
+/// `plot` must be a valid plot, `out_sector_count` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn subspace_plot_sector_count(
+    plot: *const SubspacePlot,
+    out_sector_count: *mut u64,
+) -> SubspaceResult {
+    ffi_call(|| {
+        let plot = plot
+            .as_ref()
+            .ok_or_else(|| FfiError::null_pointer("plot"))?;
+        if out_sector_count.is_null() {
+            return Err(FfiError::null_pointer("out_sector_count"));
+        }
+
+        out_sector_count.write(plot.inner.sector_count());
+
+        Ok(())
+    })
+}
+
+/// Index of the sector at offset 0, sector at offset `N` has index `first_sector_index + N`.
+///
+/// # Safety
+/// `plot` must be a valid plot, `out_first_sector_index` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn subspace_plot_first_sector_index(
+    plot: *const SubspacePlot,
+    out_first_sector_index: *mut u64,
+) -> SubspaceResult {
+    ffi_call(|| {
+        let plot = plot
+            .as_ref()
+            .ok_or_else(|| FfiError::null_pointer("plot"))?;
+        if out_first_sector_index.is_null() {
+            return Err(FfiError::null_pointer("out_first_sector_index"));
+        }
+
+        out_first_sector_index.write(plot.inner.info().first_sector_index().get());
+
+        Ok(())
+    })
+}
+
+/// Farmer protocol info plot was created with.
+///
+/// # Safety
+/// `plot` must be a valid plot, `out_farmer_protocol_info` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn subspace_plot_farmer_protocol_info(
+    plot: *const SubspacePlot,
+    out_farmer_protocol_info: *mut SubspaceFarmerProtocolInfo,
+) -> SubspaceResult {
+    ffi_call(|| {
+        let plot = plot
+            .as_ref()
+            .ok_or_else(|| FfiError::null_pointer("plot"))?;
+        if out_farmer_protocol_info.is_null() {
+            return Err(FfiError::null_pointer("out_farmer_protocol_info"));
+        }
+
+        out_farmer_protocol_info.write(plot.inner.farmer_protocol_info().into());
+
+        Ok(())
+    })
+}
+
+/// Audit sector at `sector_offset` within plot.
+///
+/// # Safety
+/// `plot` must be a valid plot, `global_challenge` must point to 32 readable bytes, `out_result`
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn subspace_plot_audit(
+    plot: *const SubspacePlot,
+    sector_offset: u64,
+    global_challenge: *const u8,
+    solution_range: SolutionRange,
+    out_result: *mut SubspaceAuditResult,
+) -> SubspaceResult {
+    ffi_call(|| {
+        let plot = plot
+            .as_ref()
+            .ok_or_else(|| FfiError::null_pointer("plot"))?;
+        let global_challenge = read_hash(global_challenge, "global_challenge")?;
+        if out_result.is_null() {
+            return Err(FfiError::null_pointer("out_result"));
+        }
+
+        let eligible_sector = plot
+            .inner
+            .audit_sector(sector_offset, &plot.kzg, &global_challenge, solution_range)
+            .map_err(|error| FfiError::new(SubspaceResult::Audit, error))?;
+
+        out_result.write(eligible_sector.into());
+
+        Ok(())
+    })
+}
+
+/// Audit plotted sector that is already in memory.
+///
+/// # Safety
+/// `public_key` and `global_challenge` must point to 32 readable bytes, `farmer_protocol_info`
+/// must be valid for reads, `sector` must be valid for reads of `sector_len` bytes, `out_result`
+/// must be valid for writes.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn subspace_audit_sector(
+    public_key: *const u8,
+    sector_index: u64,
+    farmer_protocol_info: *const SubspaceFarmerProtocolInfo,
+    global_challenge: *const u8,
+    solution_range: SolutionRange,
+    sector: *const u8,
+    sector_len: usize,
+    out_result: *mut SubspaceAuditResult,
+) -> SubspaceResult {
+    ffi_call(|| {
+        let public_key = read_public_key(public_key)?;
+        let farmer_protocol_info = read_farmer_protocol_info(farmer_protocol_info)?;
+        let global_challenge = read_hash(global_challenge, "global_challenge")?;
+        let sector = borrow_slice(sector, sector_len, "sector")?;
+        if out_result.is_null() {
+            return Err(FfiError::null_pointer("out_result"));
+        }
+
+        let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+        if sector.len() as u64 != plot_sector_size {
+            return Err(FfiError::new(
+                SubspaceResult::InvalidArgument,
+                format!("Sector must be {plot_sector_size} bytes, {sector_len} bytes given"),
+            ));
+        }
+
+        // Sector is assumed to be plotted with the same KZG parameters the library uses
+        let kzg = Kzg::new(kzg::test_public_parameters());
+        let eligible_sector = audit_sector(
+            &public_key,
+            SectorIndex::new(sector_index),
+            &farmer_protocol_info,
+            kzg.id(),
+            &kzg,
+            &global_challenge,
+            solution_range,
+            Cursor::new(sector),
+        )
+        .map_err(|error| FfiError::new(SubspaceResult::Audit, error))?;
+
+        out_result.write(eligible_sector.into());
+
+        Ok(())
+    })
+}
+
+/// Callback that writes piece with `piece_index` into `piece`, which has space for exactly
+/// [`subspace_piece_size`] bytes.
+///
+/// Must return 0 on success and any other value if piece can't be retrieved.
+pub type SubspaceGetPieceCallback =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, piece_index: u64, piece: *mut u8) -> i32>;
+
+struct CallbackPieceReceiver {
+    get_piece: unsafe extern "C" fn(*mut c_void, u64, *mut u8) -> i32,
+    user_data: *mut c_void,
+}
+
+// SAFETY: Piece receiver is only used by `subspace_plot_sector` on the calling thread and doesn't
+// outlive the call, `user_data` is never accessed concurrently.
+unsafe impl Send for CallbackPieceReceiver {}
+// SAFETY: See `Send` implementation above.
+unsafe impl Sync for CallbackPieceReceiver {}
+
+#[async_trait]
+impl PieceReceiver for CallbackPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let mut piece = Piece::default();
+        // SAFETY: Piece has space for exactly `PIECE_SIZE` bytes, callback is trusted to follow
+        // the contract
+        let result = unsafe { (self.get_piece)(self.user_data, piece_index, piece.as_mut_ptr()) };
+
+        if result != 0 {
+            return Err(format!("Get piece callback returned {result}").into());
+        }
+
+        Ok(Some(piece))
+    }
+}
+
+/// Plot sector with `sector_index` using pieces retrieved with `get_piece` callback.
+///
+/// `sector_out` must have space for exactly [`subspace_plot_sector_size`] bytes and
+/// `sector_metadata_out` for exactly [`subspace_sector_metadata_size`] bytes, sector metadata
+/// record with rotation from `farmer_protocol_info` and checksum of the sector is written into the
+/// latter. Callback is called on the calling thread, `user_data` is passed to it as is.
+///
+/// # Safety
+/// `public_key` must point to 32 readable bytes, `farmer_protocol_info` must be valid for reads,
+/// `sector_out` and `sector_metadata_out` must be valid for writes of `sector_out_len` and
+/// `sector_metadata_out_len` bytes respectively.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn subspace_plot_sector(
+    public_key: *const u8,
+    sector_index: u64,
+    farmer_protocol_info: *const SubspaceFarmerProtocolInfo,
+    get_piece: SubspaceGetPieceCallback,
+    user_data: *mut c_void,
+    sector_out: *mut u8,
+    sector_out_len: usize,
+    sector_metadata_out: *mut u8,
+    sector_metadata_out_len: usize,
+) -> SubspaceResult {
+    ffi_call(|| {
+        let public_key = read_public_key(public_key)?;
+        let farmer_protocol_info = read_farmer_protocol_info(farmer_protocol_info)?;
+        let get_piece = get_piece.ok_or_else(|| FfiError::null_pointer("get_piece"))?;
+        let sector_out = borrow_slice_mut(sector_out, sector_out_len, "sector_out")?;
+        let sector_metadata_out = borrow_slice_mut(
+            sector_metadata_out,
+            sector_metadata_out_len,
+            "sector_metadata_out",
+        )?;
+
+        let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+        if sector_out.len() as u64 != plot_sector_size {
+            return Err(FfiError::new(
+                SubspaceResult::InvalidArgument,
+                format!(
+                    "Sector output must be {plot_sector_size} bytes, {sector_out_len} bytes given"
+                ),
+            ));
+        }
+        if sector_metadata_out.len() != SECTOR_METADATA_RECORD_SIZE {
+            return Err(FfiError::new(
+                SubspaceResult::InvalidArgument,
+                format!(
+                    "Sector metadata output must be {SECTOR_METADATA_RECORD_SIZE} bytes, \
+                    {sector_metadata_out_len} bytes given"
+                ),
+            ));
+        }
+
+        let piece_receiver = CallbackPieceReceiver {
+            get_piece,
+            user_data,
+        };
+
+        let plotted_sector = block_on(plot_sector(
+            &public_key,
+            SectorIndex::new(sector_index),
+            &piece_receiver,
+            &AtomicBool::new(false),
+            &farmer_protocol_info,
+            &mut *sector_out,
+            io::sink(),
+        ))
+        .map_err(|error| FfiError::new(SubspaceResult::Plotting, error))?;
+
+        sector_metadata_out.copy_from_slice(
+            &SectorMetadataRecord {
+                sector_metadata: plotted_sector.sector_metadata,
+                plotted_at_slot: None,
+                rotation: plotted_sector.rotation,
+                checksum: Some(sector_checksum(sector_out)),
+            }
+            .encode(),
+        );
+
+        Ok(())
+    })
+}
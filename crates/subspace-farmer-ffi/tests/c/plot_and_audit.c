@@ -0,0 +1,229 @@
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "subspace_farmer_ffi.h"
+
+/* Plot created by the test harness, must match constants in `tests/c_api.rs` */
+#define FIRST_SECTOR_INDEX 7
+#define SECTOR_COUNT 2
+
+#define CHECK(condition)                                                       \
+  do {                                                                         \
+    if (!(condition)) {                                                        \
+      const char *last_error = subspace_last_error();                          \
+      fprintf(stderr, "%s:%d: check `%s` failed, last error: %s\n", __FILE__,  \
+              __LINE__, #condition, last_error ? last_error : "none");         \
+      return 1;                                                                \
+    }                                                                          \
+  } while (0)
+
+/* Deterministic pseudo-random pieces, contents are irrelevant for audit */
+static int32_t get_piece(void *user_data, uint64_t piece_index,
+                         uint8_t *piece) {
+  uint64_t *calls = (uint64_t *)user_data;
+  size_t piece_size = subspace_piece_size();
+  uint64_t state = piece_index * 6364136223846793005ULL + 1442695040888963407ULL;
+
+  for (size_t i = 0; i < piece_size; i++) {
+    state = state * 6364136223846793005ULL + 1442695040888963407ULL;
+    piece[i] = (uint8_t)(state >> 56);
+  }
+  (*calls)++;
+
+  return 0;
+}
+
+static int32_t failing_get_piece(void *user_data, uint64_t piece_index,
+                                 uint8_t *piece) {
+  (void)user_data;
+  (void)piece_index;
+  (void)piece;
+
+  return -1;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 3) {
+    fprintf(stderr, "Usage: %s <plot directory> <nonexistent directory>\n",
+            argv[0]);
+    return 2;
+  }
+
+  uint8_t public_key[32];
+  uint8_t global_challenge[32];
+  for (size_t i = 0; i < 32; i++) {
+    public_key[i] = (uint8_t)i;
+    global_challenge[i] = (uint8_t)(255 - i);
+  }
+
+  SubspaceFarmerProtocolInfo farmer_protocol_info;
+  memset(&farmer_protocol_info, 0, sizeof(farmer_protocol_info));
+  farmer_protocol_info.record_size = 32768 - 48;
+  farmer_protocol_info.recorded_history_segment_size =
+      farmer_protocol_info.record_size * 256 / 2;
+  farmer_protocol_info.total_pieces = 1024;
+  farmer_protocol_info.space_l = 16;
+  farmer_protocol_info.sector_expiration = 1;
+
+  uint64_t sector_size =
+      subspace_plot_sector_size(farmer_protocol_info.space_l);
+  size_t sector_metadata_size = subspace_sector_metadata_size();
+  CHECK(sector_size > 0);
+  CHECK(sector_metadata_size > 0);
+  CHECK(sector_size % subspace_piece_size() == 0);
+  CHECK(subspace_plot_sector_size(0) == 0);
+
+  uint8_t *sector = malloc(sector_size);
+  uint8_t *sector_metadata = malloc(sector_metadata_size);
+  CHECK(sector != NULL && sector_metadata != NULL);
+
+  uint64_t calls = 0;
+  CHECK(subspace_plot_sector(public_key, 7, &farmer_protocol_info, get_piece,
+                             &calls, sector, sector_size, sector_metadata,
+                             sector_metadata_size) == SUBSPACE_RESULT_OK);
+  CHECK(calls == sector_size / subspace_piece_size());
+  CHECK(subspace_last_error() == NULL);
+
+  /* Errors are reported through result code and last error */
+  CHECK(subspace_plot_sector(public_key, 7, &farmer_protocol_info,
+                             failing_get_piece, NULL, sector, sector_size,
+                             sector_metadata, sector_metadata_size) ==
+        SUBSPACE_RESULT_PLOTTING);
+  CHECK(subspace_last_error() != NULL);
+  CHECK(subspace_plot_sector(public_key, 7, &farmer_protocol_info, NULL, NULL,
+                             sector, sector_size, sector_metadata,
+                             sector_metadata_size) ==
+        SUBSPACE_RESULT_NULL_POINTER);
+  CHECK(subspace_plot_sector(public_key, 7, &farmer_protocol_info, get_piece,
+                             &calls, sector, sector_size - 1, sector_metadata,
+                             sector_metadata_size) ==
+        SUBSPACE_RESULT_INVALID_ARGUMENT);
+
+  /* Plot again since failed attempt above has overwritten the sector */
+  CHECK(subspace_plot_sector(public_key, 7, &farmer_protocol_info, get_piece,
+                             &calls, sector, sector_size, sector_metadata,
+                             sector_metadata_size) == SUBSPACE_RESULT_OK);
+
+  /* Max solution range makes any sector eligible */
+  SubspaceAuditResult audit_result;
+  CHECK(subspace_audit_sector(public_key, 7, &farmer_protocol_info,
+                              global_challenge, UINT64_MAX, sector,
+                              sector_size, &audit_result) ==
+        SUBSPACE_RESULT_OK);
+  CHECK(audit_result.eligible);
+  CHECK(audit_result.sector_index == 7);
+  CHECK(audit_result.audit_piece_offset < sector_size / subspace_piece_size());
+
+  /* Audit is deterministic */
+  SubspaceAuditResult audit_result_again;
+  CHECK(subspace_audit_sector(public_key, 7, &farmer_protocol_info,
+                              global_challenge, UINT64_MAX, sector,
+                              sector_size, &audit_result_again) ==
+        SUBSPACE_RESULT_OK);
+  CHECK(audit_result_again.audit_index == audit_result.audit_index);
+  CHECK(memcmp(audit_result_again.chunk, audit_result.chunk,
+               sizeof(audit_result.chunk)) == 0);
+
+  /* Sector ID and so local challenge depend on rotation sector is encoded with */
+  SubspaceFarmerProtocolInfo rotated_farmer_protocol_info = farmer_protocol_info;
+  rotated_farmer_protocol_info.rotation = 3;
+  CHECK(subspace_audit_sector(public_key, 7, &rotated_farmer_protocol_info,
+                              global_challenge, UINT64_MAX, sector,
+                              sector_size, &audit_result_again) ==
+        SUBSPACE_RESULT_OK);
+  CHECK(audit_result_again.local_challenge != audit_result.local_challenge);
+
+  /* Zero solution range makes sector ineligible */
+  CHECK(subspace_audit_sector(public_key, 7, &farmer_protocol_info,
+                              global_challenge, 0, sector, sector_size,
+                              &audit_result) == SUBSPACE_RESULT_OK);
+  CHECK(!audit_result.eligible);
+
+  CHECK(subspace_audit_sector(public_key, 7, &farmer_protocol_info,
+                              global_challenge, UINT64_MAX, sector,
+                              sector_size - 1, &audit_result) ==
+        SUBSPACE_RESULT_INVALID_ARGUMENT);
+
+  /* Plot created by the test harness from the same pieces */
+  SubspacePlot *plot = NULL;
+  CHECK(subspace_plot_open(argv[1], &plot) == SUBSPACE_RESULT_OK);
+  CHECK(plot != NULL);
+
+  uint64_t sector_count = 0;
+  CHECK(subspace_plot_sector_count(plot, &sector_count) == SUBSPACE_RESULT_OK);
+  CHECK(sector_count == SECTOR_COUNT);
+
+  uint64_t first_sector_index = 0;
+  CHECK(subspace_plot_first_sector_index(plot, &first_sector_index) ==
+        SUBSPACE_RESULT_OK);
+  CHECK(first_sector_index == FIRST_SECTOR_INDEX);
+
+  SubspaceFarmerProtocolInfo plot_farmer_protocol_info;
+  CHECK(subspace_plot_farmer_protocol_info(plot, &plot_farmer_protocol_info) ==
+        SUBSPACE_RESULT_OK);
+  CHECK(memcmp(plot_farmer_protocol_info.genesis_hash,
+               farmer_protocol_info.genesis_hash,
+               sizeof(farmer_protocol_info.genesis_hash)) == 0);
+  CHECK(plot_farmer_protocol_info.record_size ==
+        farmer_protocol_info.record_size);
+  CHECK(plot_farmer_protocol_info.recorded_history_segment_size ==
+        farmer_protocol_info.recorded_history_segment_size);
+  CHECK(plot_farmer_protocol_info.total_pieces ==
+        farmer_protocol_info.total_pieces);
+  CHECK(plot_farmer_protocol_info.space_l == farmer_protocol_info.space_l);
+  CHECK(plot_farmer_protocol_info.sector_expiration ==
+        farmer_protocol_info.sector_expiration);
+  CHECK(plot_farmer_protocol_info.rotation == farmer_protocol_info.rotation);
+
+  /* Auditing sectors of the plot gives the same results as auditing the same
+   * sectors plotted in memory */
+  for (uint64_t sector_offset = 0; sector_offset < sector_count;
+       sector_offset++) {
+    uint64_t sector_index = first_sector_index + sector_offset;
+
+    CHECK(subspace_plot_sector(public_key, sector_index, &farmer_protocol_info,
+                               get_piece, &calls, sector, sector_size,
+                               sector_metadata, sector_metadata_size) ==
+          SUBSPACE_RESULT_OK);
+    CHECK(subspace_audit_sector(public_key, sector_index, &farmer_protocol_info,
+                                global_challenge, UINT64_MAX, sector,
+                                sector_size, &audit_result) ==
+          SUBSPACE_RESULT_OK);
+
+    CHECK(subspace_plot_audit(plot, sector_offset, global_challenge, UINT64_MAX,
+                              &audit_result_again) == SUBSPACE_RESULT_OK);
+    CHECK(audit_result_again.eligible);
+    CHECK(audit_result_again.sector_index == sector_index);
+    CHECK(audit_result_again.local_challenge == audit_result.local_challenge);
+    CHECK(audit_result_again.audit_index == audit_result.audit_index);
+    CHECK(audit_result_again.audit_piece_offset ==
+          audit_result.audit_piece_offset);
+    CHECK(memcmp(audit_result_again.chunk, audit_result.chunk,
+                 sizeof(audit_result.chunk)) == 0);
+    CHECK(audit_result_again.expanded_chunk == audit_result.expanded_chunk);
+  }
+
+  /* Sectors past the end of the plot can't be audited */
+  CHECK(subspace_plot_audit(plot, sector_count, global_challenge, UINT64_MAX,
+                            &audit_result) == SUBSPACE_RESULT_AUDIT);
+  CHECK(subspace_plot_audit(NULL, 0, global_challenge, UINT64_MAX,
+                            &audit_result) == SUBSPACE_RESULT_NULL_POINTER);
+
+  subspace_plot_free(plot);
+
+  /* Opening plot that doesn't exist fails and doesn't touch output */
+  plot = NULL;
+  CHECK(subspace_plot_open(argv[2], &plot) == SUBSPACE_RESULT_PLOT_OPEN);
+  CHECK(plot == NULL);
+  CHECK(strlen(subspace_last_error()) > 0);
+  CHECK(subspace_plot_open(NULL, &plot) == SUBSPACE_RESULT_NULL_POINTER);
+  /* Freeing null is a no-op */
+  subspace_plot_free(NULL);
+
+  free(sector);
+  free(sector_metadata);
+
+  return 0;
+}
@@ -0,0 +1,133 @@
+#![cfg(unix)]
+
+use async_trait::async_trait;
+use futures::executor::block_on;
+use std::env;
+use std::error::Error;
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{Piece, PieceIndex, PublicKey, SectorIndex, SegmentIndex};
+use subspace_farmer::single_disk_plot::piece_receiver::PieceReceiver;
+use subspace_farmer::single_disk_plot::plot_writer::PlotWriter;
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tempfile::TempDir;
+
+/// Must match `FIRST_SECTOR_INDEX` in C test program
+const FIRST_SECTOR_INDEX: u64 = 7;
+/// Must match `SECTOR_COUNT` in C test program
+const SECTOR_COUNT: u64 = 2;
+
+/// Directory where Cargo puts library artifacts, test executable is in its `deps` subdirectory
+fn library_directory() -> PathBuf {
+    let mut directory = env::current_exe().unwrap();
+    directory.pop();
+    if directory.ends_with("deps") {
+        directory.pop();
+    }
+    directory
+}
+
+/// Same pseudo-random pieces as `get_piece()` in C test program produces
+struct LcgPieceReceiver;
+
+#[async_trait]
+impl PieceReceiver for LcgPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        const MULTIPLIER: u64 = 6364136223846793005;
+        const INCREMENT: u64 = 1442695040888963407;
+
+        let mut piece = Piece::default();
+        let mut state = piece_index.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        for byte in piece.iter_mut() {
+            state = state.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+            *byte = (state >> 56) as u8;
+        }
+
+        Ok(Some(piece))
+    }
+}
+
+/// Create plot with the same public key and protocol parameters C test program uses and plot all
+/// of its sectors
+fn create_plot(directory: &Path) {
+    let mut public_key = [0; 32];
+    for (i, byte) in public_key.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let record_size = 32768 - 48;
+    let farmer_protocol_info = FarmerProtocolInfo {
+        genesis_hash: [0; 32],
+        record_size: NonZeroU32::new(record_size).unwrap(),
+        recorded_history_segment_size: record_size * 256 / 2,
+        total_pieces: NonZeroU64::new(1024).unwrap(),
+        space_l: NonZeroU16::new(16).unwrap(),
+        sector_expiration: SegmentIndex::new(1),
+        rotation: 0,
+    };
+
+    let mut plot_writer = PlotWriter::create(
+        directory,
+        PublicKey::from(public_key),
+        SectorIndex::new(FIRST_SECTOR_INDEX),
+        SECTOR_COUNT,
+        farmer_protocol_info,
+        Kzg::new(kzg::test_public_parameters()).id(),
+    )
+    .unwrap();
+    for _ in 0..SECTOR_COUNT {
+        block_on(plot_writer.plot_sector(&LcgPieceReceiver, &AtomicBool::new(false))).unwrap();
+    }
+}
+
+#[test]
+fn plot_and_audit() {
+    let build_directory = TempDir::new().unwrap();
+    let library_directory = library_directory();
+    let executable = build_directory.path().join("plot_and_audit");
+
+    let compiler = cc::Build::new()
+        .cargo_metadata(false)
+        .target(env!("TARGET"))
+        .host(env!("HOST"))
+        .opt_level(0)
+        .out_dir(build_directory.path())
+        .include(env!("SUBSPACE_FARMER_FFI_INCLUDE_DIR"))
+        .warnings_into_errors(true)
+        .get_compiler();
+
+    let status = compiler
+        .to_command()
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/c/plot_and_audit.c"
+        ))
+        .arg("-o")
+        .arg(&executable)
+        .arg(format!("-L{}", library_directory.display()))
+        .arg("-lsubspace_farmer_ffi")
+        .arg(format!("-Wl,-rpath,{}", library_directory.display()))
+        .status()
+        .unwrap();
+    assert!(status.success(), "Failed to compile C test program");
+
+    let plot_directory = TempDir::new().unwrap();
+    create_plot(plot_directory.path());
+
+    let output = Command::new(&executable)
+        .arg(plot_directory.path())
+        .arg(build_directory.path().join("missing-plot"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "C test program failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -3,20 +3,20 @@ use futures::executor::block_on;
 use memmap2::Mmap;
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
 use std::sync::atomic::AtomicBool;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::{env, fs, io};
 use subspace_archiving::archiver::Archiver;
 use subspace_core_primitives::crypto::kzg;
-use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
 use subspace_core_primitives::{
-    plot_sector_size, Blake2b256Hash, Piece, PublicKey, SolutionRange, PIECES_IN_SEGMENT,
-    RECORD_SIZE,
+    plot_sector_size, Blake2b256Hash, Piece, PublicKey, SectorIndex, SegmentIndex, SolutionRange,
+    PIECES_IN_SEGMENT, PIECE_SIZE, RECORD_SIZE,
 };
 use subspace_farmer::file_ext::FileExt;
-use subspace_farmer::single_disk_plot::farming::audit_sector;
+use subspace_farmer::single_disk_plot::farming::{audit_piece_offset, audit_sector, AuditIter};
 use subspace_farmer::single_disk_plot::plotting::plot_sector;
+use subspace_farmer::single_disk_plot::read_planner::ReadPlanner;
 use subspace_rpc_primitives::FarmerProtocolInfo;
 use utils::BenchPieceReceiver;
 
@@ -34,10 +34,11 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         .unwrap_or(10);
 
     let public_key = PublicKey::default();
-    let sector_index = 0;
+    let sector_index = SectorIndex::ZERO;
     let input = vec![1u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
     let kzg = Kzg::new(kzg::test_public_parameters());
-    let mut archiver = Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg).unwrap();
+    let mut archiver =
+        Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone()).unwrap();
     let piece = Piece::try_from(
         archiver
             .add_block(input, Default::default())
@@ -52,14 +53,14 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     .unwrap();
 
     let cancelled = AtomicBool::new(false);
-    let farmer_protocol_info = FarmerProtocolInfo {
-        genesis_hash: Default::default(),
-        record_size: NonZeroU32::new(RECORD_SIZE).unwrap(),
-        recorded_history_segment_size: RECORDED_HISTORY_SEGMENT_SIZE,
-        total_pieces: NonZeroU64::new(1).unwrap(),
-        space_l: NonZeroU16::new(20).unwrap(),
-        sector_expiration: 1,
-    };
+    let farmer_protocol_info = FarmerProtocolInfo::builder()
+        .record_size(RECORD_SIZE)
+        .recorded_history_segment_size(RECORDED_HISTORY_SEGMENT_SIZE)
+        .total_pieces(1)
+        .space_l(20)
+        .sector_expiration(SegmentIndex::ONE)
+        .build()
+        .unwrap();
     let global_challenge = Blake2b256Hash::default();
     let solution_range = SolutionRange::MAX;
 
@@ -90,6 +91,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
                 black_box(&public_key),
                 black_box(sector_index),
                 black_box(&farmer_protocol_info),
+                black_box(KzgParametersId::TEST),
+                black_box(&kzg),
                 black_box(&global_challenge),
                 black_box(solution_range),
                 black_box(io::Cursor::new(&plotted_sector)),
@@ -99,25 +102,44 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     });
 
     group.throughput(Throughput::Elements(sectors_count));
-    group.bench_function("disk", |b| {
-        let plot_file_path = base_path.join("subspace_bench_sector.bin");
-        let mut plot_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&plot_file_path)
-            .unwrap();
-
-        plot_file
-            .preallocate(plot_sector_size * sectors_count)
-            .unwrap();
-        plot_file.advise_random_access().unwrap();
-
-        for _i in 0..sectors_count {
-            plot_file.write_all(plotted_sector.as_slice()).unwrap();
-        }
+    let plot_file_path = base_path.join("subspace_bench_sector.bin");
+    let mut plot_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&plot_file_path)
+        .unwrap();
 
+    plot_file
+        .preallocate(plot_sector_size * sectors_count)
+        .unwrap();
+    plot_file.advise_random_access().unwrap();
+
+    for _i in 0..sectors_count {
+        plot_file.write_all(plotted_sector.as_slice()).unwrap();
+    }
+    // Dirty pages can't be dropped from page cache, make sure everything is on disk
+    plot_file.sync_all().unwrap();
+
+    let audit_plot = |plot_mmap: &Mmap| {
+        AuditIter::new(
+            black_box(&plot_mmap[..]),
+            black_box(&public_key),
+            black_box(0),
+            black_box(&farmer_protocol_info),
+            black_box(KzgParametersId::TEST),
+            black_box(&kzg),
+            black_box(&global_challenge),
+            black_box(solution_range),
+            &cancelled,
+        )
+        .unwrap()
+        .for_each(|audit_result| {
+            black_box(audit_result);
+        });
+    };
+    let map_plot = || {
         let plot_mmap = unsafe { Mmap::map(&plot_file).unwrap() };
 
         #[cfg(unix)]
@@ -125,31 +147,93 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             plot_mmap.advise(memmap2::Advice::Random).unwrap();
         }
 
+        plot_mmap
+    };
+
+    group.bench_function("disk", |b| {
+        let plot_mmap = map_plot();
+
         b.iter_custom(|iters| {
             let start = Instant::now();
             for _i in 0..iters {
-                for (sector_index, sector) in plot_mmap
-                    .chunks_exact(plot_sector_size as usize)
-                    .enumerate()
-                    .map(|(sector_index, sector)| (sector_index as u64, sector))
-                {
-                    audit_sector(
-                        black_box(&public_key),
-                        black_box(sector_index),
-                        black_box(&farmer_protocol_info),
-                        black_box(&global_challenge),
-                        black_box(solution_range),
-                        black_box(io::Cursor::new(sector)),
-                    )
-                    .unwrap();
-                }
+                audit_plot(&plot_mmap);
             }
             start.elapsed()
         });
+    });
+
+    // Positional reads of audited pieces, the way plots on network file systems are audited,
+    // individually and coalesced
+    let audited_piece_offsets = (0..sectors_count)
+        .map(|sector_offset| {
+            let piece_offset = audit_piece_offset(
+                &public_key,
+                sector_index.offset(sector_offset),
+                &farmer_protocol_info,
+                &global_challenge,
+            );
+            sector_offset * plot_sector_size + piece_offset * PIECE_SIZE as u64
+        })
+        .collect::<Vec<_>>();
+    let coalescing_gap = env::var("COALESCE_GAP")
+        .map(|coalescing_gap| coalescing_gap.parse().unwrap())
+        .unwrap_or(plot_sector_size);
+    let read_planner = {
+        let mut read_planner = ReadPlanner::new(coalescing_gap);
+        for &offset in &audited_piece_offsets {
+            read_planner.add(offset, PIECE_SIZE);
+        }
+        read_planner
+    };
+    eprintln!(
+        "{} audit reads are coalesced into {} with gap of {} bytes",
+        audited_piece_offsets.len(),
+        read_planner.plan().len(),
+        coalescing_gap,
+    );
+
+    group.bench_function("disk-pread", |b| {
+        let mut piece = Piece::default();
+
+        b.iter(|| {
+            for &offset in &audited_piece_offsets {
+                plot_file
+                    .read_exact_at(&mut piece, black_box(offset))
+                    .unwrap();
+                black_box(&piece);
+            }
+        });
+    });
 
-        drop(plot_file);
-        fs::remove_file(plot_file_path).unwrap();
+    group.bench_function("disk-coalesced", |b| {
+        b.iter(|| {
+            black_box(read_planner.read(black_box(&plot_file)).unwrap());
+        });
     });
+
+    // Cold cache mode drops plot file from page cache before every iteration, so that audit reads
+    // actually hit the disk
+    if env::var("DROP_CACHE").map_or(false, |drop_cache| drop_cache == "1") {
+        group.bench_function("disk-cold", |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _i in 0..iters {
+                    // Pages that are still mapped are not dropped, so memory mapping is re-created
+                    // after cache is dropped
+                    plot_file.drop_cache(0, 0).unwrap();
+                    let plot_mmap = map_plot();
+
+                    let start = Instant::now();
+                    audit_plot(&plot_mmap);
+                    elapsed += start.elapsed();
+                }
+                elapsed
+            });
+        });
+    }
+
+    drop(plot_file);
+    fs::remove_file(plot_file_path).unwrap();
     group.finish();
 }
 
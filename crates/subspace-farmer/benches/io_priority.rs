@@ -0,0 +1,250 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use futures::executor::block_on;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{env, fs, io, thread};
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, Piece, PublicKey, SectorIndex, SegmentIndex, SolutionRange,
+    PIECES_IN_SEGMENT, RECORD_SIZE,
+};
+use subspace_farmer::file_ext::FileExt;
+use subspace_farmer::io_priority::{set_current_thread_io_priority, IoPriority};
+use subspace_farmer::single_disk_plot::plotting::plot_sector;
+use subspace_farmer::single_disk_plot::remote::audit_sectors;
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use utils::BenchPieceReceiver;
+
+mod utils;
+
+// This is helpful for overriding locally for benching different parameters
+pub const RECORDED_HISTORY_SEGMENT_SIZE: u32 = RECORD_SIZE * PIECES_IN_SEGMENT / 2;
+/// Size of a single write of the background writer that imitates plotting
+const WRITE_SIZE: usize = 16 * 1024 * 1024;
+/// Background writer wraps around after writing this many bytes
+const WRITER_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Writes to a file in the background the same way plotting does until dropped
+struct BackgroundWriter {
+    stop: Arc<AtomicBool>,
+    join_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(join_handle) = self.join_handle.take() {
+            join_handle.join().unwrap();
+        }
+    }
+}
+
+impl BackgroundWriter {
+    fn start(path: &Path, io_priority: Option<IoPriority>) -> Self {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let join_handle = thread::spawn({
+            let stop = Arc::clone(&stop);
+
+            move || {
+                if let Some(io_priority) = io_priority {
+                    set_current_thread_io_priority(io_priority).unwrap();
+                }
+
+                let buffer = vec![1u8; WRITE_SIZE];
+                let mut offset = 0;
+                while !stop.load(Ordering::Acquire) {
+                    file.write_all_at(&buffer, offset).unwrap();
+                    // Writeback is what delays audit reads, so it is triggered explicitly
+                    file.sync_data().unwrap();
+                    offset = (offset + WRITE_SIZE as u64) % WRITER_FILE_SIZE;
+                }
+            }
+        });
+
+        Self {
+            stop,
+            join_handle: Some(join_handle),
+        }
+    }
+}
+
+fn print_latency_percentiles(name: &str, latencies: &mut [Duration]) {
+    if latencies.is_empty() {
+        return;
+    }
+
+    latencies.sort();
+    let percentile = |percentile: usize| latencies[(latencies.len() - 1) * percentile / 100];
+    println!(
+        "{name}: p50 {:?}, p99 {:?}, max {:?} over {} audits",
+        percentile(50),
+        percentile(99),
+        percentile(100),
+        latencies.len()
+    );
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let base_path = env::var("BASE_PATH")
+        .map(|base_path| base_path.parse().unwrap())
+        .unwrap_or_else(|_error| env::temp_dir());
+    let sectors_count = env::var("SECTORS_COUNT")
+        .map(|sectors_count| sectors_count.parse().unwrap())
+        .unwrap_or(10);
+
+    let public_key = PublicKey::default();
+    let first_sector_index = SectorIndex::ZERO;
+    let input = vec![1u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let mut archiver =
+        Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone()).unwrap();
+    let piece = Piece::try_from(
+        archiver
+            .add_block(input, Default::default())
+            .into_iter()
+            .next()
+            .unwrap()
+            .pieces
+            .as_pieces()
+            .next()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let cancelled = AtomicBool::new(false);
+    let farmer_protocol_info = FarmerProtocolInfo::builder()
+        .record_size(RECORD_SIZE)
+        .recorded_history_segment_size(RECORDED_HISTORY_SEGMENT_SIZE)
+        .total_pieces(1)
+        .space_l(20)
+        .sector_expiration(SegmentIndex::ONE)
+        .build()
+        .unwrap();
+    let global_challenge = Blake2b256Hash::default();
+    let solution_range = SolutionRange::MAX;
+
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+    let plotted_sector = {
+        let mut plotted_sector = vec![0u8; plot_sector_size as usize];
+
+        block_on(plot_sector(
+            &public_key,
+            first_sector_index,
+            &BenchPieceReceiver::new(piece),
+            &cancelled,
+            &farmer_protocol_info,
+            plotted_sector.as_mut_slice(),
+            io::sink(),
+        ))
+        .unwrap();
+
+        plotted_sector
+    };
+
+    let plot_file_path = base_path.join("subspace_bench_io_priority_sector.bin");
+    let mut plot_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&plot_file_path)
+        .unwrap();
+
+    plot_file
+        .preallocate(plot_sector_size * sectors_count)
+        .unwrap();
+    plot_file.advise_random_access().unwrap();
+
+    for _i in 0..sectors_count {
+        plot_file.write_all(plotted_sector.as_slice()).unwrap();
+    }
+    // Dirty pages can't be dropped from page cache, make sure everything is on disk
+    plot_file.sync_all().unwrap();
+
+    let writer_file_path = base_path.join("subspace_bench_io_priority_writer.bin");
+    let sector_offsets = (0..sectors_count).collect::<Vec<_>>();
+
+    // Every audit reads from disk rather than page cache, latency of each audit is recorded to
+    // print percentiles in addition to what criterion reports
+    let audit_plot = |plot_file: &File, latencies: &mut Vec<Duration>| {
+        plot_file.drop_cache(0, 0).unwrap();
+
+        let start = Instant::now();
+        block_on(audit_sectors(
+            black_box(plot_file),
+            black_box(&public_key),
+            black_box(first_sector_index),
+            black_box(&sector_offsets),
+            black_box(&farmer_protocol_info),
+            black_box(KzgParametersId::TEST),
+            black_box(&kzg),
+            black_box(&global_challenge),
+            black_box(solution_range),
+        ))
+        .unwrap();
+        let elapsed = start.elapsed();
+
+        latencies.push(elapsed);
+        elapsed
+    };
+
+    let mut group = c.benchmark_group("audit-during-plotting");
+    group.throughput(Throughput::Elements(sectors_count));
+
+    {
+        let _background_writer = BackgroundWriter::start(&writer_file_path, None);
+        let mut latencies = Vec::new();
+
+        group.bench_function("default-priority", |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| audit_plot(&plot_file, &mut latencies))
+                    .sum()
+            });
+        });
+
+        print_latency_percentiles("default-priority", &mut latencies);
+    }
+
+    // Priority of the bench thread is changed permanently, so this goes last
+    if set_current_thread_io_priority(IoPriority::High).unwrap() {
+        let _background_writer = BackgroundWriter::start(&writer_file_path, Some(IoPriority::Idle));
+        let mut latencies = Vec::new();
+
+        group.bench_function("audit-priority", |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| audit_plot(&plot_file, &mut latencies))
+                    .sum()
+            });
+        });
+
+        print_latency_percentiles("audit-priority", &mut latencies);
+    } else {
+        println!("I/O priority is not supported, build with `io-priority` feature on Linux");
+    }
+
+    group.finish();
+
+    drop(plot_file);
+    fs::remove_file(plot_file_path).unwrap();
+    fs::remove_file(writer_file_path).unwrap();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,19 +1,23 @@
+use async_trait::async_trait;
 use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
 use futures::executor::block_on;
 use rand::{thread_rng, Rng};
 use rayon::current_num_threads;
 use rayon::prelude::*;
+use std::error::Error;
 use std::io;
-use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
 use std::sync::atomic::AtomicBool;
 use std::time::Instant;
 use subspace_archiving::archiver::Archiver;
 use subspace_core_primitives::crypto::kzg;
 use subspace_core_primitives::crypto::kzg::Kzg;
 use subspace_core_primitives::{
-    plot_sector_size, Piece, PublicKey, PIECES_IN_SEGMENT, RECORD_SIZE,
+    plot_sector_size, Piece, PieceIndex, PublicKey, RecordsRoot, SectorIndex, SegmentIndex,
+    PIECES_IN_SEGMENT, RECORD_SIZE,
 };
-use subspace_farmer::single_disk_plot::plotting::plot_sector;
+use subspace_farmer::single_disk_plot::piece_receiver::PieceReceiver;
+use subspace_farmer::single_disk_plot::plotting::{plot_sector, SequentialSectorDestination};
+use subspace_farmer::single_disk_plot::witness_cache::WitnessCache;
 use subspace_rpc_primitives::FarmerProtocolInfo;
 use utils::BenchPieceReceiver;
 
@@ -22,36 +26,62 @@ mod utils;
 // This is helpful for overriding locally for benching different parameters
 const RECORDED_HISTORY_SEGMENT_SIZE: u32 = RECORD_SIZE * PIECES_IN_SEGMENT / 2;
 
+/// Returns the same piece for every piece index and verifies it every time like plotting does,
+/// piece must be the first piece of its segment
+struct VerifyingBenchPieceReceiver {
+    piece: Piece,
+    kzg: Kzg,
+    records_root: RecordsRoot,
+    witness_cache: WitnessCache,
+}
+
+#[async_trait]
+impl PieceReceiver for VerifyingBenchPieceReceiver {
+    async fn get_piece(
+        &self,
+        _piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        if !self.witness_cache.is_piece_valid(
+            &self.kzg,
+            PIECES_IN_SEGMENT,
+            &self.piece,
+            self.records_root,
+            0,
+            RECORD_SIZE,
+        ) {
+            return Err("Piece failed verification".into());
+        }
+
+        Ok(Some(self.piece.clone()))
+    }
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let public_key = PublicKey::default();
-    let sector_index = 0;
+    let sector_index = SectorIndex::ZERO;
     let mut input = vec![0u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
     thread_rng().fill(input.as_mut_slice());
     let kzg = Kzg::new(kzg::test_public_parameters());
-    let mut archiver = Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg).unwrap();
-    let piece = Piece::try_from(
-        archiver
-            .add_block(input, Default::default())
-            .into_iter()
-            .next()
-            .unwrap()
-            .pieces
-            .as_pieces()
-            .next()
-            .unwrap(),
-    )
-    .unwrap();
+    let mut archiver =
+        Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone()).unwrap();
+    let archived_segment = archiver
+        .add_block(input, Default::default())
+        .into_iter()
+        .next()
+        .unwrap();
+    let records_root = archived_segment.root_block.records_root();
+    let piece = Piece::try_from(archived_segment.pieces.as_pieces().next().unwrap()).unwrap();
 
     let cancelled = AtomicBool::new(false);
-    let farmer_protocol_info = FarmerProtocolInfo {
-        genesis_hash: Default::default(),
-        record_size: NonZeroU32::new(RECORD_SIZE).unwrap(),
-        recorded_history_segment_size: RECORDED_HISTORY_SEGMENT_SIZE,
-        total_pieces: NonZeroU64::new(1).unwrap(),
-        space_l: NonZeroU16::new(20).unwrap(),
-        sector_expiration: 1,
-    };
-    let piece_receiver = BenchPieceReceiver::new(piece);
+    let farmer_protocol_info = FarmerProtocolInfo::builder()
+        .record_size(RECORD_SIZE)
+        .recorded_history_segment_size(RECORDED_HISTORY_SEGMENT_SIZE)
+        .total_pieces(1)
+        .space_l(20)
+        .sector_expiration(SegmentIndex::ONE)
+        .build()
+        .unwrap();
+    let piece_receiver = BenchPieceReceiver::new(piece.clone());
 
     let mut group = c.benchmark_group("sector-plotting");
     group.throughput(Throughput::Bytes(plot_sector_size(
@@ -65,7 +95,7 @@ fn criterion_benchmark(c: &mut Criterion) {
                 black_box(&piece_receiver),
                 black_box(&cancelled),
                 black_box(&farmer_protocol_info),
-                black_box(io::sink()),
+                black_box(SequentialSectorDestination::new(io::sink())),
                 black_box(io::sink()),
             ))
             .unwrap();
@@ -78,7 +108,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     ));
     group.bench_function("no-writes-multi-thread", |b| {
         b.iter_custom(|iters| {
-            let sectors = (0..thread_count).collect::<Vec<_>>();
+            let sectors = (0..thread_count).map(SectorIndex::new).collect::<Vec<_>>();
             let start = Instant::now();
             for _i in 0..iters {
                 sectors.par_iter().for_each(|&sector_index| {
@@ -88,7 +118,7 @@ fn criterion_benchmark(c: &mut Criterion) {
                         black_box(&piece_receiver),
                         black_box(&cancelled),
                         black_box(&farmer_protocol_info),
-                        black_box(io::sink()),
+                        black_box(SequentialSectorDestination::new(io::sink())),
                         black_box(io::sink()),
                     ))
                     .unwrap();
@@ -97,6 +127,34 @@ fn criterion_benchmark(c: &mut Criterion) {
             start.elapsed()
         })
     });
+
+    // With `total_pieces` of 1 the same piece is plotted over and over again, witness cache only
+    // verifies it once
+    group.throughput(Throughput::Bytes(plot_sector_size(
+        farmer_protocol_info.space_l,
+    )));
+    for (name, witness_cache_entries) in [("verifying-no-cache", 0), ("verifying-cached", 1)] {
+        let piece_receiver = VerifyingBenchPieceReceiver {
+            piece: piece.clone(),
+            kzg: kzg.clone(),
+            records_root,
+            witness_cache: WitnessCache::new(witness_cache_entries),
+        };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                block_on(plot_sector(
+                    black_box(&public_key),
+                    black_box(sector_index),
+                    black_box(&piece_receiver),
+                    black_box(&cancelled),
+                    black_box(&farmer_protocol_info),
+                    black_box(SequentialSectorDestination::new(io::sink())),
+                    black_box(io::sink()),
+                ))
+                .unwrap();
+            })
+        });
+    }
     group.finish();
 }
 
@@ -4,16 +4,15 @@ use memmap2::Mmap;
 use schnorrkel::Keypair;
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
 use std::sync::atomic::AtomicBool;
 use std::time::Instant;
 use std::{env, fs, io};
 use subspace_archiving::archiver::Archiver;
 use subspace_core_primitives::crypto::kzg;
-use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
 use subspace_core_primitives::{
-    plot_sector_size, Blake2b256Hash, Piece, PublicKey, SolutionRange, PIECES_IN_SEGMENT,
-    RECORD_SIZE,
+    plot_sector_size, Blake2b256Hash, Piece, PublicKey, SectorIndex, SegmentIndex, SolutionRange,
+    PIECES_IN_SEGMENT, RECORD_SIZE,
 };
 use subspace_farmer::file_ext::FileExt;
 use subspace_farmer::single_disk_plot::farming::audit_sector;
@@ -37,10 +36,11 @@ pub fn criterion_benchmark(c: &mut Criterion) {
 
     let keypair = Keypair::from_bytes(&[0; 96]).unwrap();
     let public_key = PublicKey::from(keypair.public.to_bytes());
-    let sector_index = 0;
+    let sector_index = SectorIndex::ZERO;
     let input = vec![1u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
     let kzg = Kzg::new(kzg::test_public_parameters());
-    let mut archiver = Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg).unwrap();
+    let mut archiver =
+        Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone()).unwrap();
     let piece = Piece::try_from(
         archiver
             .add_block(input, Default::default())
@@ -55,14 +55,14 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     .unwrap();
 
     let cancelled = AtomicBool::new(false);
-    let farmer_protocol_info = FarmerProtocolInfo {
-        genesis_hash: Default::default(),
-        record_size: NonZeroU32::new(RECORD_SIZE).unwrap(),
-        recorded_history_segment_size: RECORDED_HISTORY_SEGMENT_SIZE,
-        total_pieces: NonZeroU64::new(1).unwrap(),
-        space_l: NonZeroU16::new(20).unwrap(),
-        sector_expiration: 1,
-    };
+    let farmer_protocol_info = FarmerProtocolInfo::builder()
+        .record_size(RECORD_SIZE)
+        .recorded_history_segment_size(RECORDED_HISTORY_SEGMENT_SIZE)
+        .total_pieces(1)
+        .space_l(20)
+        .sector_expiration(SegmentIndex::ONE)
+        .build()
+        .unwrap();
     let global_challenge = Blake2b256Hash::default();
     let solution_range = SolutionRange::MAX;
     let reward_address = PublicKey::default();
@@ -91,6 +91,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         &public_key,
         sector_index,
         &farmer_protocol_info,
+        KzgParametersId::TEST,
+        &kzg,
         &global_challenge,
         solution_range,
         io::Cursor::new(plotted_sector),
@@ -0,0 +1,169 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use futures::executor::block_on;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::{env, fs, io};
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, Piece, PublicKey, SectorIndex, SegmentIndex, SolutionRange,
+    PIECES_IN_SEGMENT, RECORD_SIZE,
+};
+use subspace_farmer::file_ext::FileExt;
+use subspace_farmer::single_disk_plot::plotting::plot_sector;
+use subspace_farmer::single_disk_plot::remote::{audit_sectors, serve_plot, RemoteReadAt};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+use utils::BenchPieceReceiver;
+
+mod utils;
+
+// This is helpful for overriding locally for benching different parameters
+pub const RECORDED_HISTORY_SEGMENT_SIZE: u32 = RECORD_SIZE * PIECES_IN_SEGMENT / 2;
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let base_path = env::var("BASE_PATH")
+        .map(|base_path| base_path.parse().unwrap())
+        .unwrap_or_else(|_error| env::temp_dir());
+    let sectors_count = env::var("SECTORS_COUNT")
+        .map(|sectors_count| sectors_count.parse().unwrap())
+        .unwrap_or(10);
+
+    let public_key = PublicKey::default();
+    let first_sector_index = SectorIndex::ZERO;
+    let input = vec![1u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let mut archiver =
+        Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone()).unwrap();
+    let piece = Piece::try_from(
+        archiver
+            .add_block(input, Default::default())
+            .into_iter()
+            .next()
+            .unwrap()
+            .pieces
+            .as_pieces()
+            .next()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let cancelled = AtomicBool::new(false);
+    let farmer_protocol_info = FarmerProtocolInfo::builder()
+        .record_size(RECORD_SIZE)
+        .recorded_history_segment_size(RECORDED_HISTORY_SEGMENT_SIZE)
+        .total_pieces(1)
+        .space_l(20)
+        .sector_expiration(SegmentIndex::ONE)
+        .build()
+        .unwrap();
+    let global_challenge = Blake2b256Hash::default();
+    let solution_range = SolutionRange::MAX;
+
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+    let plotted_sector = {
+        let mut plotted_sector = vec![0u8; plot_sector_size as usize];
+
+        block_on(plot_sector(
+            &public_key,
+            first_sector_index,
+            &BenchPieceReceiver::new(piece),
+            &cancelled,
+            &farmer_protocol_info,
+            plotted_sector.as_mut_slice(),
+            io::sink(),
+        ))
+        .unwrap();
+
+        plotted_sector
+    };
+
+    let plot_file_path = base_path.join("subspace_bench_remote_sector.bin");
+    let mut plot_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&plot_file_path)
+        .unwrap();
+
+    plot_file
+        .preallocate(plot_sector_size * sectors_count)
+        .unwrap();
+    plot_file.advise_random_access().unwrap();
+
+    for _i in 0..sectors_count {
+        plot_file.write_all(plotted_sector.as_slice()).unwrap();
+    }
+    plot_file.sync_all().unwrap();
+    let plot_file = Arc::new(plot_file);
+
+    let runtime = Runtime::new().unwrap();
+    let remote_read_at = runtime.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve_plot(
+            listener,
+            base_path.clone(),
+            Arc::clone(&plot_file),
+            None,
+            NonZeroUsize::new(1).unwrap(),
+        ));
+
+        RemoteReadAt::connect(address, None).await.unwrap()
+    });
+
+    let sector_offsets = (0..sectors_count).collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("audit");
+    group.throughput(Throughput::Elements(sectors_count));
+    group.bench_function("local", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(audit_sectors(
+                    black_box(plot_file.as_ref()),
+                    black_box(&public_key),
+                    black_box(first_sector_index),
+                    black_box(&sector_offsets),
+                    black_box(&farmer_protocol_info),
+                    black_box(KzgParametersId::TEST),
+                    black_box(&kzg),
+                    black_box(&global_challenge),
+                    black_box(solution_range),
+                ))
+                .unwrap();
+        })
+    });
+    group.bench_function("remote-loopback", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(audit_sectors(
+                    black_box(&remote_read_at),
+                    black_box(&public_key),
+                    black_box(first_sector_index),
+                    black_box(&sector_offsets),
+                    black_box(&farmer_protocol_info),
+                    black_box(KzgParametersId::TEST),
+                    black_box(&kzg),
+                    black_box(&global_challenge),
+                    black_box(solution_range),
+                ))
+                .unwrap();
+        })
+    });
+    group.finish();
+
+    drop(remote_read_at);
+    drop(runtime);
+    drop(plot_file);
+    fs::remove_file(plot_file_path).unwrap();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
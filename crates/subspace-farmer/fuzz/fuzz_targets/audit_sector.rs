@@ -0,0 +1,96 @@
+//! Audit of a sector read from arbitrary bytes must never panic, corrupted or truncated sectors
+//! and sector metadata result in errors or no solution.
+//!
+//! Input layout: sector index (8 bytes, little-endian), global challenge (32 bytes), encoded sector
+//! metadata and sector contents, shorter inputs are padded with zeroes up to sector index and
+//! global challenge, sector metadata and sector may be truncated or have any length.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use parity_scale_codec::Decode;
+use schnorrkel::{ExpansionMode, Keypair, MiniSecretKey};
+use std::io::Cursor;
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    PublicKey, SectorIndex, SegmentIndex, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+};
+use subspace_farmer::single_disk_plot::farming::{audit_sector, read_winning_piece};
+use subspace_farmer::single_disk_plot::SectorMetadata;
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+thread_local! {
+    static KZG: Kzg = Kzg::new(kzg::test_public_parameters());
+    static KEYPAIR: Keypair = MiniSecretKey::from_bytes(&[1; 32])
+        .expect("Correct length; qed")
+        .expand_to_keypair(ExpansionMode::Ed25519);
+}
+
+fn farmer_protocol_info() -> FarmerProtocolInfo {
+    FarmerProtocolInfo {
+        genesis_hash: Default::default(),
+        record_size: NonZeroU32::new(RECORD_SIZE).expect("Not zero; qed"),
+        recorded_history_segment_size: RECORDED_HISTORY_SEGMENT_SIZE,
+        total_pieces: NonZeroU64::new(1024).expect("Not zero; qed"),
+        // Sector of 4 pieces, the same as in seed corpus
+        space_l: NonZeroU16::new(16).expect("Not zero; qed"),
+        sector_expiration: SegmentIndex::ONE,
+        rotation: 0,
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut header = [0u8; 8 + 32];
+    let header_len = data.len().min(header.len());
+    header[..header_len].copy_from_slice(&data[..header_len]);
+    let data = &data[header_len..];
+    let (sector_index, global_challenge) = header.split_at(8);
+    let sector_index = SectorIndex::new(u64::from_le_bytes(
+        sector_index.try_into().expect("Correct length; qed"),
+    ));
+    let global_challenge = global_challenge.try_into().expect("Correct length; qed");
+    let (sector_metadata, sector) = data.split_at(data.len().min(SectorMetadata::encoded_size()));
+
+    let public_key = PublicKey::default();
+    let farmer_protocol_info = farmer_protocol_info();
+    let maybe_eligible_sector = KZG.with(|kzg| {
+        audit_sector(
+            &public_key,
+            sector_index,
+            &farmer_protocol_info,
+            kzg.id(),
+            kzg,
+            &global_challenge,
+            // Every audited chunk is eligible, so that solution is created from every sector
+            u64::MAX,
+            Cursor::new(sector),
+        )
+    });
+    let eligible_sector = match maybe_eligible_sector {
+        Ok(Some(eligible_sector)) => eligible_sector,
+        Ok(None) | Err(_) => {
+            return;
+        }
+    };
+
+    if let Ok(decoded_sector_metadata) = SectorMetadata::decode(&mut &*sector_metadata) {
+        let _ = read_winning_piece(
+            &eligible_sector.sector_id,
+            &farmer_protocol_info,
+            Cursor::new(sector),
+            &decoded_sector_metadata,
+            eligible_sector.audit_index,
+        );
+    }
+
+    let _ = KEYPAIR.with(|keypair| {
+        eligible_sector.try_into_solution(
+            keypair,
+            public_key,
+            &farmer_protocol_info,
+            sector_metadata,
+        )
+    });
+});
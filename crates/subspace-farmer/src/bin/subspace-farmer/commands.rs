@@ -1,6 +1,16 @@
 mod bench;
+mod convert;
 mod farm;
 mod info;
+mod migrate;
+mod plot_server;
+mod replay_audit;
+mod simulate;
 
+pub(crate) use convert::convert;
 pub(crate) use farm::farm_multi_disk;
 pub(crate) use info::info;
+pub(crate) use migrate::migrate;
+pub(crate) use plot_server::plot_server;
+pub(crate) use replay_audit::replay_audit;
+pub(crate) use simulate::simulate;
@@ -0,0 +1,47 @@
+use crate::commands::farm::run_disk_farms;
+use crate::{DiskFarm, FarmingArgs};
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::sync::Arc;
+use subspace_farmer::single_disk_plot::legacy_plot::LegacyPlot;
+use tracing::info;
+
+/// Plot disk farms using pieces of legacy plot where possible, exits once plotting is complete
+pub(crate) async fn convert(
+    base_path: &Path,
+    disk_farms: Vec<DiskFarm>,
+    legacy_plot_directory: &Path,
+    farming_args: FarmingArgs,
+) -> Result<()> {
+    let legacy_plot_directory = legacy_plot_directory.canonicalize().map_err(|error| {
+        anyhow!(
+            "Failed to access legacy plot at {}: {error}",
+            legacy_plot_directory.display()
+        )
+    })?;
+    // Plot files of both formats have the same name, so they must never share a directory
+    for disk_farm in &disk_farms {
+        if disk_farm.directory.canonicalize()? == legacy_plot_directory {
+            return Err(anyhow!(
+                "New plot must be in a different directory than legacy plot {}",
+                legacy_plot_directory.display()
+            ));
+        }
+    }
+
+    let legacy_plot = LegacyPlot::open(&legacy_plot_directory)?;
+    info!(
+        legacy_plot = %legacy_plot_directory.display(),
+        pieces = legacy_plot.piece_count(),
+        "Converting legacy plot"
+    );
+
+    run_disk_farms(
+        base_path,
+        disk_farms,
+        farming_args,
+        Some(Arc::new(legacy_plot)),
+        true,
+    )
+    .await
+}
@@ -1,20 +1,61 @@
-use crate::utils::shutdown_signal;
-use crate::{DiskFarm, FarmingArgs, Multiaddr};
-use anyhow::{anyhow, Result};
+use crate::exit_code::ConfigError;
+use crate::utils::{read_mnemonic, shutdown_signal};
+use crate::{
+    AuditOrderArg, DiskFarm, EvictionPolicyArg, FarmingArgs, MetricsPushProtocolArg, Multiaddr,
+    RemotePlot, SectorTimingsFormatArg, StorageBackendArg,
+};
+use anyhow::{anyhow, Context, Result};
+use futures::channel::{mpsc, oneshot};
 use futures::stream::FuturesUnordered;
 use futures::{FutureExt, StreamExt};
 use parking_lot::Mutex;
 use std::collections::HashMap;
-use std::sync::Arc;
-use subspace_core_primitives::{PieceIndexHash, SectorIndex};
+use std::fs;
+use std::future::Future;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use subspace_core_primitives::crypto::kzg::{test_public_parameters, Kzg};
+use subspace_core_primitives::{PieceIndexHash, PublicKey, SectorIndex, PIECE_SIZE};
+use subspace_farmer::memory_budget::MemoryBudget;
+use subspace_farmer::metrics_push::{
+    hostname, push_metrics, MetricSample, MetricsPushOptions, MetricsPushProtocol,
+};
+use subspace_farmer::piece_cache::{populate_piece_cache, EvictionPolicy, FarmerPieceCache};
+use subspace_farmer::piece_serving::{PieceServer, PieceServingError, PieceServingLimits};
+use subspace_farmer::reward_signing::reward_signing;
+use subspace_farmer::root_block_store::RootBlockStore;
+use subspace_farmer::single_disk_plot::audit_cache::AuditCache;
+use subspace_farmer::single_disk_plot::audit_coordinator::AuditCoordinator;
+use subspace_farmer::single_disk_plot::audit_order::{audit_coverage, AuditOrder};
+use subspace_farmer::single_disk_plot::dry_run::DryRunOptions;
+use subspace_farmer::single_disk_plot::duplicate_plot::DuplicatePlotDetector;
+use subspace_farmer::single_disk_plot::idle_verification::{
+    IdleVerificationOptions, VerificationCoverage,
+};
 use subspace_farmer::single_disk_plot::piece_reader::PieceReader;
+use subspace_farmer::single_disk_plot::piece_receiver::{
+    BandwidthLimit, PieceDownloads, PieceReceiver,
+};
+use subspace_farmer::single_disk_plot::plotting_progress::{
+    FilesystemProgressStore, ProgressStore,
+};
+use subspace_farmer::single_disk_plot::remote::{tls_connector, RemoteReadAt};
+use subspace_farmer::single_disk_plot::remote_farming::farm_remote_plot;
+use subspace_farmer::single_disk_plot::sector_timings::{SectorTimings, SectorTimingsFormat};
+use subspace_farmer::single_disk_plot::segment_receiver::SegmentPieceReceiver;
+use subspace_farmer::single_disk_plot::solution_outlook::SolutionOutlook;
+use subspace_farmer::single_disk_plot::storage_backend::StorageBackend;
+use subspace_farmer::single_disk_plot::witness_cache::WitnessCache;
 use subspace_farmer::single_disk_plot::{SingleDiskPlot, SingleDiskPlotOptions};
-use subspace_farmer::NodeRpcClient;
+use subspace_farmer::{Identity, NodeRpcClient, RpcClient};
 use subspace_networking::{
     create, BootstrappedNetworkingParameters, Config, Node, NodeRunner, PieceByHashRequestHandler,
     PieceByHashResponse, PieceKey,
 };
-use tokio::runtime::Handle;
 use tracing::{debug, error, info, trace};
 
 #[derive(Debug, Copy, Clone)]
@@ -24,20 +65,152 @@ struct PieceDetails {
     piece_offset: u64,
 }
 
+/// Metrics of a single plot that are pushed to collector
+#[derive(Debug)]
+struct PlotMetrics {
+    public_key: PublicKey,
+    plotted_sectors_count: Arc<AtomicU64>,
+    target_sectors_count: u64,
+    max_sectors_per_slot: Option<NonZeroU64>,
+    verification_coverage: VerificationCoverage,
+    audit_cache: AuditCache,
+    witness_cache: WitnessCache,
+    solution_outlook: SolutionOutlook,
+    audit_coordinator: AuditCoordinator,
+}
+
+impl PlotMetrics {
+    fn samples(&self) -> [MetricSample; 9] {
+        let solution_outlook = self.solution_outlook.latest();
+        [
+            MetricSample {
+                name: "subspace_farmer_plotted_sectors",
+                public_key: self.public_key,
+                value: self.plotted_sectors_count.load(Ordering::Relaxed),
+            },
+            MetricSample {
+                name: "subspace_farmer_target_sectors",
+                public_key: self.public_key,
+                value: self.target_sectors_count,
+            },
+            // Metric values are integers, so fractions are pushed in parts per million
+            MetricSample {
+                name: "subspace_farmer_audit_coverage_ppm",
+                public_key: self.public_key,
+                value: (audit_coverage(
+                    self.plotted_sectors_count.load(Ordering::Relaxed),
+                    self.max_sectors_per_slot,
+                ) * 1_000_000.0) as u64,
+            },
+            MetricSample {
+                name: "subspace_farmer_verification_coverage_ppm",
+                public_key: self.public_key,
+                value: (self.verification_coverage.fraction(Instant::now()) * 1_000_000.0) as u64,
+            },
+            MetricSample {
+                name: "subspace_farmer_audit_cache_hit_rate_ppm",
+                public_key: self.public_key,
+                value: (self.audit_cache.stats().hit_rate().unwrap_or_default() * 1_000_000.0)
+                    as u64,
+            },
+            MetricSample {
+                name: "subspace_farmer_witness_cache_hit_rate_ppm",
+                public_key: self.public_key,
+                value: (self.witness_cache.stats().hit_rate().unwrap_or_default() * 1_000_000.0)
+                    as u64,
+            },
+            MetricSample {
+                name: "subspace_farmer_expected_solutions_per_day_ppm",
+                public_key: self.public_key,
+                value: (solution_outlook
+                    .map(|report| report.expected_solutions_per_day())
+                    .unwrap_or_default()
+                    * 1_000_000.0) as u64,
+            },
+            MetricSample {
+                name: "subspace_farmer_best_miss_ratio_ppm",
+                public_key: self.public_key,
+                value: (solution_outlook
+                    .and_then(|report| report.best_miss_ratio)
+                    .unwrap_or_default()
+                    * 1_000_000.0) as u64,
+            },
+            MetricSample {
+                name: "subspace_farmer_plotting_paused_ms",
+                public_key: self.public_key,
+                value: self.audit_coordinator.total_paused_time().as_millis() as u64,
+            },
+        ]
+    }
+}
+
 #[derive(Debug)]
 struct ReadersAndPieces {
     readers: Vec<PieceReader>,
     pieces: HashMap<PieceIndexHash, PieceDetails>,
 }
 
+/// Validate all disk farms without writing anything to disk and print report of every plot as
+/// JSON
+async fn dry_run_multi_disk(
+    disk_farms: Vec<DiskFarm>,
+    node_rpc_url: &str,
+    storage_backend: Option<StorageBackend>,
+    derived_identity: Option<&Identity>,
+) -> Result<(), anyhow::Error> {
+    info!("Connecting to node at {}", node_rpc_url);
+    let rpc_client = NodeRpcClient::new(node_rpc_url).await?;
+
+    let mut reports = Vec::with_capacity(disk_farms.len());
+    for disk_farm in &disk_farms {
+        let report = SingleDiskPlot::dry_run(DryRunOptions {
+            directory: &disk_farm.directory,
+            allocated_space: disk_farm.allocated_plotting_space,
+            rpc_client: &rpc_client,
+            storage_backend,
+            plot_layout: &disk_farm.plot_layout,
+            derived_identity,
+        })
+        .with_context(|| {
+            format!(
+                "Dry run of plot at {} failed",
+                disk_farm.directory.display()
+            )
+        })?;
+
+        reports.push(report);
+    }
+
+    println!("{}", serde_json::to_string_pretty(&reports)?);
+
+    Ok(())
+}
+
 /// Start farming by using multiple replica plot in specified path and connecting to WebSocket
 /// server at specified address.
 pub(crate) async fn farm_multi_disk(
+    base_path: &Path,
+    disk_farms: Vec<DiskFarm>,
+    farming_args: FarmingArgs,
+) -> Result<(), anyhow::Error> {
+    run_disk_farms(base_path, disk_farms, farming_args, None, false).await
+}
+
+/// Run disk farms with pieces from `local_pieces` tried before the network during plotting, exits
+/// once initial plotting of all farms is complete if `exit_when_plotted` is set.
+///
+/// Data shared by all disk farms is stored in `base_path`.
+pub(super) async fn run_disk_farms(
+    base_path: &Path,
     disk_farms: Vec<DiskFarm>,
     farming_args: FarmingArgs,
+    local_pieces: Option<Arc<dyn PieceReceiver + Send + Sync>>,
+    exit_when_plotted: bool,
 ) -> Result<(), anyhow::Error> {
     if disk_farms.is_empty() {
-        return Err(anyhow!("There must be at least one disk farm provided"));
+        return Err(
+            ConfigError("There must be at least one disk farm provided".to_string()).into(),
+        );
     }
 
     let signal = shutdown_signal();
@@ -53,26 +226,202 @@ pub(crate) async fn farm_multi_disk(
         disk_concurrency,
         disable_farming,
         enable_dsn,
+        piece_cache_size,
+        piece_cache_eviction_policy,
+        piece_cache_pin_ahead,
+        node_segment_downloads,
+        storage_backend,
+        download_bandwidth_limit,
+        serving_bandwidth_limit,
+        serving_max_concurrent_requests_per_peer,
+        serving_max_queued_requests_per_peer,
+        piece_fetch_timeout,
+        slow_piece_threshold,
+        recent_pieces_ttl,
+        memory_budget,
+        pause_plotting_during_audit,
+        pause_encoding_during_audit,
+        audit_order,
+        max_sectors_per_slot,
+        audit_replay_log_size,
+        audit_cache_size,
+        coalesce_audit_reads,
+        witness_cache_entries,
+        slot_probability,
+        io_priority,
+        resumable_plotting,
+        startup_check,
+        idle_verification,
+        idle_verification_margin,
+        verification_coverage_days,
+        pause_on_duplicate_plot,
+        metrics_push_endpoint,
+        metrics_push_protocol,
+        metrics_push_interval,
+        sector_timings_format,
+        dry_run,
+        identity_from_mnemonic,
+        identity_derivation_path,
+        mnemonic_fd,
+        remote_plot: remote_plots,
     } = farming_args;
 
+    for disk_farm in &disk_farms {
+        if disk_farm.allocated_plotting_space < 1024 * 1024 {
+            return Err(ConfigError(format!(
+                "Plot size is too low ({0} bytes). Did you mean {0}G or {0}T?",
+                disk_farm.allocated_plotting_space
+            ))
+            .into());
+        }
+    }
+
+    let storage_backend = match storage_backend {
+        StorageBackendArg::Auto => None,
+        StorageBackendArg::Local => Some(StorageBackend::Local),
+        StorageBackendArg::Network => Some(StorageBackend::Network),
+    };
+
+    let audit_order = match audit_order {
+        AuditOrderArg::Sequential => AuditOrder::Sequential,
+        AuditOrderArg::RandomPerSlot => AuditOrder::RandomPerSlot,
+        AuditOrderArg::RecentWinnersFirst => AuditOrder::RecentWinnersFirst,
+    };
+
+    let piece_cache_eviction_policy = match piece_cache_eviction_policy {
+        EvictionPolicyArg::Lru => EvictionPolicy::Lru,
+        EvictionPolicyArg::Lfu => EvictionPolicy::Lfu,
+        EvictionPolicyArg::Segmented => EvictionPolicy::Segmented,
+    };
+
+    let metrics_push_protocol = match metrics_push_protocol {
+        MetricsPushProtocolArg::Statsd => MetricsPushProtocol::Statsd,
+        MetricsPushProtocolArg::Graphite => MetricsPushProtocol::Graphite,
+    };
+
+    let sector_timings_format =
+        sector_timings_format.map(|sector_timings_format| match sector_timings_format {
+            SectorTimingsFormatArg::Csv => SectorTimingsFormat::Csv,
+            SectorTimingsFormatArg::Json => SectorTimingsFormat::Json,
+        });
+
+    let derived_identity = if identity_from_mnemonic {
+        let mnemonic = read_mnemonic(mnemonic_fd)?;
+        let identity = Identity::from_mnemonic(&mnemonic, &identity_derivation_path)
+            .map_err(|error| ConfigError(format!("Failed to derive identity: {error}")))?;
+        info!(
+            public_key = %hex::encode(identity.public_key().to_bytes()),
+            derivation_path = %identity_derivation_path,
+            "Derived identity from mnemonic"
+        );
+
+        Some(identity)
+    } else {
+        None
+    };
+
+    if dry_run {
+        return dry_run_multi_disk(
+            disk_farms,
+            &node_rpc_url,
+            storage_backend,
+            derived_identity.as_ref(),
+        )
+        .await;
+    }
+
+    let bandwidth_limit = BandwidthLimit::new(
+        download_bandwidth_limit.and_then(|limit| NonZeroU64::new(limit.as_u64())),
+    );
+
+    let piece_server = PieceServer::new(PieceServingLimits {
+        bandwidth: serving_bandwidth_limit.and_then(|limit| NonZeroU64::new(limit.as_u64())),
+        max_concurrent_requests_per_peer: serving_max_concurrent_requests_per_peer,
+        max_queued_requests_per_peer: serving_max_queued_requests_per_peer,
+    });
+
+    let piece_downloads = PieceDownloads::new(Duration::from_secs(recent_pieces_ttl));
+
+    let memory_budget =
+        MemoryBudget::new(memory_budget.and_then(|budget| NonZeroU64::new(budget.as_u64())));
+
     let readers_and_pieces = Arc::new(Mutex::new(None));
 
-    let (node, node_runner) =
-        configure_dsn(enable_dsn, listen_on, bootstrap_nodes, &readers_and_pieces).await?;
+    let (node, node_runner) = configure_dsn(
+        enable_dsn,
+        listen_on,
+        bootstrap_nodes,
+        &readers_and_pieces,
+        &piece_server,
+    )
+    .await?;
     let mut single_disk_plots = Vec::with_capacity(disk_farms.len());
 
-    // TODO: Check plot and metadata sizes to ensure there is enough space for farmer to not
-    //  fail later
-    for disk_farm in disk_farms {
-        if disk_farm.allocated_plotting_space < 1024 * 1024 {
-            return Err(anyhow::anyhow!(
-                "Plot size is too low ({0} bytes). Did you mean {0}G or {0}T?",
-                disk_farm.allocated_plotting_space
-            ));
+    // Root blocks are shared by all plots, so they are not stored in any of them
+    fs::create_dir_all(base_path)?;
+    let root_block_store = RootBlockStore::open(base_path)?;
+
+    let piece_cache = FarmerPieceCache::new(
+        (piece_cache_size.as_u64() / PIECE_SIZE as u64) as usize,
+        piece_cache_eviction_policy,
+        root_block_store.clone(),
+        &memory_budget,
+    );
+    let piece_cache_population = {
+        info!("Connecting to node at {}", node_rpc_url);
+        let rpc_client = NodeRpcClient::new(&node_rpc_url).await?;
+
+        populate_piece_cache(rpc_client, piece_cache.clone())
+            .await
+            .map_err(|error| anyhow!("Failed to start piece cache population: {error}"))?
+    };
+
+    let node_segments = match NonZeroUsize::new(node_segment_downloads) {
+        Some(max_in_flight_segments) => {
+            let rpc_client = NodeRpcClient::new(&node_rpc_url).await?;
+            let farmer_protocol_info = rpc_client
+                .farmer_protocol_info()
+                .await
+                .map_err(|error| anyhow!("Failed to get farmer protocol info: {error}"))?;
+
+            Some(Arc::new(SegmentPieceReceiver::new(
+                rpc_client,
+                piece_cache.clone(),
+                root_block_store.clone(),
+                Kzg::new(test_public_parameters()),
+                memory_budget.clone(),
+                farmer_protocol_info.record_size.get(),
+                farmer_protocol_info.recorded_history_segment_size,
+                max_in_flight_segments,
+            )) as Arc<dyn PieceReceiver + Send + Sync>)
         }
+        None => None,
+    };
+
+    let duplicate_plot_detector =
+        DuplicatePlotDetector::new(pause_on_duplicate_plot, Instant::now());
 
+    for disk_farm in disk_farms {
         info!("Connecting to node at {}", node_rpc_url);
         let rpc_client = NodeRpcClient::new(&node_rpc_url).await?;
+        let progress_store = resumable_plotting.then(|| {
+            Arc::new(FilesystemProgressStore::new(
+                disk_farm.directory.join("plotting_progress"),
+            )) as Arc<dyn ProgressStore + Send + Sync>
+        });
+        let sector_timings = sector_timings_format
+            .map(|sector_timings_format| {
+                let directory = disk_farm
+                    .plot_layout
+                    .writable_directory(&disk_farm.directory);
+                fs::create_dir_all(directory)?;
+
+                SectorTimings::open(
+                    sector_timings_format,
+                    &directory.join(sector_timings_format.file_name()),
+                )
+            })
+            .transpose()?;
 
         let single_disk_plot = SingleDiskPlot::new(SingleDiskPlotOptions {
             directory: disk_farm.directory,
@@ -80,11 +429,57 @@ pub(crate) async fn farm_multi_disk(
             rpc_client,
             reward_address,
             dsn_node: node.clone(),
+            piece_cache: piece_cache.clone(),
+            piece_cache_pin_ahead,
+            storage_backend,
+            bandwidth_limit: bandwidth_limit.clone(),
+            piece_downloads: piece_downloads.clone(),
+            piece_fetch_timeout: Duration::from_secs(piece_fetch_timeout),
+            slow_piece_threshold: Duration::from_secs(slow_piece_threshold),
+            root_block_store: root_block_store.clone(),
+            pause_plotting_during_audit,
+            pause_encoding_during_audit,
+            audit_order,
+            max_sectors_per_slot,
+            memory_budget: memory_budget.clone(),
+            audit_replay_log_size: audit_replay_log_size.map(|size| size.as_u64()),
+            plot_layout: disk_farm.plot_layout,
+            io_priority,
+            local_pieces: local_pieces.clone(),
+            node_segments: node_segments.clone(),
+            audit_cache_size: audit_cache_size.as_u64(),
+            audit_read_coalescing_gap: coalesce_audit_reads.map(|gap| gap.as_u64()),
+            witness_cache_entries,
+            progress_store,
+            slot_probability,
+            startup_check,
+            idle_verification: idle_verification.then(|| IdleVerificationOptions {
+                margin: Duration::from_millis(idle_verification_margin),
+                coverage_window: Duration::from_secs(
+                    verification_coverage_days.get() * 24 * 60 * 60,
+                ),
+            }),
+            duplicate_plot_detector: Some(duplicate_plot_detector.clone()),
+            sector_timings,
+            derived_identity: derived_identity.clone(),
         })?;
 
         single_disk_plots.push(single_disk_plot);
     }
 
+    let mut remote_plots_stream = FuturesUnordered::new();
+    for remote_plot in remote_plots {
+        remote_plots_stream.push(
+            farm_remote_plot_with_identity(
+                remote_plot,
+                &node_rpc_url,
+                derived_identity.as_ref(),
+                reward_address,
+            )
+            .await?,
+        );
+    }
+
     // Store piece readers so we can reference them later
     let piece_readers = single_disk_plots
         .iter()
@@ -140,12 +535,52 @@ pub(crate) async fn farm_multi_disk(
         pieces: plotted_pieces,
     });
 
+    let plots_metrics = single_disk_plots
+        .iter()
+        .map(|single_disk_plot| PlotMetrics {
+            public_key: *single_disk_plot.public_key(),
+            plotted_sectors_count: Arc::new(AtomicU64::new(
+                single_disk_plot.plotted_sectors_count(),
+            )),
+            target_sectors_count: single_disk_plot.target_sectors_count(),
+            max_sectors_per_slot: single_disk_plot.max_sectors_per_slot(),
+            verification_coverage: single_disk_plot.verification_coverage(),
+            audit_cache: single_disk_plot.audit_cache(),
+            witness_cache: single_disk_plot.witness_cache(),
+            solution_outlook: single_disk_plot.solution_outlook(),
+            audit_coordinator: single_disk_plot.audit_coordinator(),
+        })
+        .collect::<Vec<_>>();
+
+    let (plotted_sender, mut plotted_receiver) = mpsc::unbounded::<()>();
+    let mut plots_to_plot = single_disk_plots.len();
     let mut single_disk_plots_stream = single_disk_plots
         .into_iter()
+        .zip(&plots_metrics)
         .enumerate()
-        .map(|(plot_offset, single_disk_plot)| {
+        .map(|(plot_offset, (single_disk_plot, plot_metrics))| {
             let readers_and_pieces = Arc::clone(&readers_and_pieces);
 
+            let target_sectors_count = plot_metrics.target_sectors_count;
+            let plotted_sectors_count = Arc::clone(&plot_metrics.plotted_sectors_count);
+            if plotted_sectors_count.load(Ordering::Relaxed) >= target_sectors_count {
+                let _ = plotted_sender.unbounded_send(());
+            }
+            single_disk_plot
+                .on_sector_plotted(Arc::new({
+                    let plotted_sender = plotted_sender.clone();
+
+                    move |_plotted_sector| {
+                        let plotted_sectors_count =
+                            plotted_sectors_count.fetch_add(1, Ordering::Relaxed) + 1;
+                        if plotted_sectors_count == target_sectors_count {
+                            info!(%plot_offset, "Initial plotting complete");
+                            let _ = plotted_sender.unbounded_send(());
+                        }
+                    }
+                }))
+                .detach();
+
             // Collect newly plotted pieces
             // TODO: Once we have replotting, this will have to be updated
             single_disk_plot
@@ -183,21 +618,81 @@ pub(crate) async fn farm_multi_disk(
     // event handlers
     drop(readers_and_pieces);
 
-    futures::select!(
+    let (metrics_shutdown_sender, metrics_shutdown_receiver) = oneshot::channel::<()>();
+    let metrics_push = metrics_push_endpoint.map(|endpoint| {
+        let options = MetricsPushOptions {
+            protocol: metrics_push_protocol,
+            endpoint,
+            interval: Duration::from_secs(metrics_push_interval.get()),
+            hostname: hostname(),
+        };
+        info!(endpoint = %options.endpoint, protocol = ?options.protocol, "Pushing metrics");
+
+        tokio::spawn(push_metrics(
+            options,
+            move || {
+                plots_metrics
+                    .iter()
+                    .flat_map(|plot_metrics| plot_metrics.samples())
+                    .collect()
+            },
+            async move {
+                let _ = metrics_shutdown_receiver.await;
+            },
+        ))
+    });
+
+    let result = futures::select!(
         // Signal future
         _ = Box::pin(async move {
             signal.await;
-        }).fuse() => {},
+        }).fuse() => Ok(()),
 
         // Plotting future
-        _ = Box::pin(async move {
+        result = Box::pin(async move {
             while let Some(result) = single_disk_plots_stream.next().await {
                 result?;
 
                 info!("Farm exited successfully");
             }
             anyhow::Ok(())
-        }).fuse() => {},
+        }).fuse() => result,
+
+        // Remote plots future
+        result = Box::pin(async move {
+            while let Some(result) = remote_plots_stream.next().await {
+                result?;
+
+                info!("Remote plot farming exited successfully");
+            }
+            futures::future::pending::<anyhow::Result<()>>().await
+        }).fuse() => result,
+
+        // Initial plotting completion future
+        _ = Box::pin(async move {
+            if !exit_when_plotted {
+                futures::future::pending::<()>().await;
+            }
+
+            while plots_to_plot > 0 && plotted_receiver.next().await.is_some() {
+                plots_to_plot -= 1;
+            }
+
+            info!("Initial plotting of all farms is complete, exiting");
+        }).fuse() => Ok(()),
+
+        // Piece cache population future
+        _ = Box::pin(async move {
+            piece_cache_population.await;
+
+            info!("Piece cache population exited.");
+            futures::future::pending::<()>().await
+        }).fuse() => Ok(()),
+
+        // Piece serving future
+        _ = Box::pin(async move {
+            piece_server.run().await;
+        }).fuse() => Ok(()),
 
         // Node runner future
         _ = Box::pin(async move {
@@ -208,10 +703,92 @@ pub(crate) async fn farm_multi_disk(
             } else {
                 futures::future::pending().await
             }
-        }).fuse() => {},
+        }).fuse() => Ok(()),
     );
 
-    anyhow::Ok(())
+    // Final values are pushed once more, so they land in time series
+    let _ = metrics_shutdown_sender.send(());
+    if let Some(metrics_push) = metrics_push {
+        if let Err(error) = metrics_push.await {
+            error!(%error, "Metrics push task failed");
+        }
+    }
+
+    result
+}
+
+/// Connect to plot server of `remote_plot` and return future that farms it and signs rewards with
+/// its identity
+async fn farm_remote_plot_with_identity(
+    remote_plot: RemotePlot,
+    node_rpc_url: &str,
+    derived_identity: Option<&Identity>,
+    reward_address: PublicKey,
+) -> Result<Pin<Box<dyn Future<Output = Result<()>> + Send>>> {
+    let RemotePlot {
+        address,
+        identity_directory,
+        tls,
+    } = remote_plot;
+
+    let identity = match identity_directory {
+        Some(identity_directory) => Identity::open_with(&identity_directory, derived_identity)?
+            .ok_or_else(|| {
+                ConfigError(format!(
+                    "No identity found in {} for remote plot at {address}",
+                    identity_directory.display()
+                ))
+            })?,
+        None => derived_identity.cloned().ok_or_else(|| {
+            ConfigError(format!(
+                "Remote plot at {address} requires either `identity` key or \
+                `--identity-from-mnemonic`"
+            ))
+        })?,
+    };
+
+    let tls = tls
+        .map(|tls| {
+            tls_connector(
+                &tls.ca_certificates,
+                tls.client_identity
+                    .as_ref()
+                    .map(|(certificate_chain, private_key)| {
+                        (certificate_chain.as_path(), private_key.as_path())
+                    }),
+            )
+            .map(|tls_connector| (tls_connector, tls.server_name))
+        })
+        .transpose()
+        .with_context(|| format!("Failed to configure TLS for remote plot at {address}"))?;
+    let reader = RemoteReadAt::connect(
+        address,
+        tls.as_ref()
+            .map(|(tls_connector, server_name)| (tls_connector.clone(), server_name.as_str())),
+    )
+    .await
+    .with_context(|| format!("Failed to connect to plot server at {address}"))?;
+
+    info!("Connecting to node at {}", node_rpc_url);
+    let rpc_client = NodeRpcClient::new(node_rpc_url).await?;
+    let reward_signing = reward_signing(rpc_client.clone(), identity.clone(), None)
+        .await
+        .map_err(|error| anyhow!("Failed to subscribe to reward signing notifications: {error}"))?;
+
+    Ok(Box::pin(async move {
+        let reward_signing = tokio::spawn(reward_signing);
+        let result = farm_remote_plot(
+            reader,
+            rpc_client,
+            identity,
+            reward_address,
+            Kzg::new(test_public_parameters()),
+        )
+        .await;
+        reward_signing.abort();
+
+        result.with_context(|| format!("Farming of remote plot at {address} failed"))
+    }))
 }
 
 async fn configure_dsn(
@@ -219,6 +796,7 @@ async fn configure_dsn(
     listen_on: Vec<Multiaddr>,
     bootstrap_nodes: Vec<Multiaddr>,
     readers_and_pieces: &Arc<Mutex<Option<ReadersAndPieces>>>,
+    piece_server: &PieceServer,
 ) -> Result<(Option<Node>, Option<NodeRunner>), anyhow::Error> {
     if !enable_dsn {
         info!("No DSN configured.");
@@ -226,67 +804,62 @@ async fn configure_dsn(
     }
 
     let weak_readers_and_pieces = Arc::downgrade(readers_and_pieces);
+    let piece_server = piece_server.clone();
 
-    let handle = Handle::current();
     let config = Config {
         listen_on,
         allow_non_globals_in_dht: true,
         networking_parameters_registry: BootstrappedNetworkingParameters::new(bootstrap_nodes)
             .boxed(),
-        request_response_protocols: vec![PieceByHashRequestHandler::create(move |req| {
-            let result = if let PieceKey::Sector(piece_index_hash) = req.key {
-                let (mut reader, piece_details) = {
-                    let readers_and_pieces = match weak_readers_and_pieces.upgrade() {
-                        Some(readers_and_pieces) => readers_and_pieces,
-                        None => {
-                            debug!("A readers and pieces are already dropped");
-                            return None;
-                        }
+        request_response_protocols: vec![PieceByHashRequestHandler::create_async(
+            move |peer_id, req| {
+                // `None` means no response, `Some(None)` means piece is not provided for this key
+                let maybe_reader_and_piece_details =
+                    if let PieceKey::Sector(piece_index_hash) = req.key {
+                        find_piece(&weak_readers_and_pieces, piece_index_hash).map(Some)
+                    } else {
+                        debug!(key=?req.key, "Incorrect piece request - unsupported key type.");
+
+                        Some(None)
                     };
-                    let readers_and_pieces = readers_and_pieces.lock();
-                    let readers_and_pieces = match readers_and_pieces.as_ref() {
-                        Some(readers_and_pieces) => readers_and_pieces,
+                let piece_server = piece_server.clone();
+
+                async move {
+                    let (mut reader, piece_details) = match maybe_reader_and_piece_details? {
+                        Some(reader_and_piece_details) => reader_and_piece_details,
                         None => {
-                            debug!(
-                                ?piece_index_hash,
-                                "Readers and pieces are not initialized yet"
-                            );
-                            return None;
+                            return Some(PieceByHashResponse {
+                                piece: None,
+                                retry_after_ms: None,
+                            });
                         }
                     };
-                    let piece_details =
-                        match readers_and_pieces.pieces.get(&piece_index_hash).copied() {
-                            Some(piece_details) => piece_details,
-                            None => {
-                                trace!(
-                                    ?piece_index_hash,
-                                    "Piece is not stored in any of the local plots"
-                                );
-                                return None;
-                            }
-                        };
-                    let reader = readers_and_pieces
-                        .readers
-                        .get(piece_details.plot_offset)
-                        .cloned()
-                        .expect("Offsets strictly correspond to existing plots; qed");
-                    (reader, piece_details)
-                };
-
-                let handle = handle.clone();
-                tokio::task::block_in_place(move || {
-                    handle.block_on(
-                        reader.read_piece(piece_details.sector_index, piece_details.piece_offset),
-                    )
-                })
-            } else {
-                debug!(key=?req.key, "Incorrect piece request - unsupported key type.");
 
-                None
-            };
-
-            Some(PieceByHashResponse { piece: result })
-        })],
+                    let result = piece_server
+                        .serve(peer_id, || {
+                            reader
+                                .read_piece(piece_details.sector_index, piece_details.piece_offset)
+                        })
+                        .await;
+
+                    match result {
+                        Ok(piece) => Some(PieceByHashResponse {
+                            piece,
+                            retry_after_ms: None,
+                        }),
+                        Err(PieceServingError::QueueFull { retry_after }) => {
+                            debug!(%peer_id, ?retry_after, "Piece request rejected, queue is full");
+
+                            Some(PieceByHashResponse {
+                                piece: None,
+                                retry_after_ms: Some(retry_after.as_millis() as u64),
+                            })
+                        }
+                        Err(PieceServingError::Stopped) => None,
+                    }
+                }
+            },
+        )],
         ..Config::with_generated_keypair()
     };
 
@@ -295,3 +868,45 @@ async fn configure_dsn(
         .map(|(node, node_runner)| (Some(node), Some(node_runner)))
         .map_err(Into::into)
 }
+
+/// Find reader of the plot that contains piece with `piece_index_hash`
+fn find_piece(
+    weak_readers_and_pieces: &Weak<Mutex<Option<ReadersAndPieces>>>,
+    piece_index_hash: PieceIndexHash,
+) -> Option<(PieceReader, PieceDetails)> {
+    let readers_and_pieces = match weak_readers_and_pieces.upgrade() {
+        Some(readers_and_pieces) => readers_and_pieces,
+        None => {
+            debug!("A readers and pieces are already dropped");
+            return None;
+        }
+    };
+    let readers_and_pieces = readers_and_pieces.lock();
+    let readers_and_pieces = match readers_and_pieces.as_ref() {
+        Some(readers_and_pieces) => readers_and_pieces,
+        None => {
+            debug!(
+                ?piece_index_hash,
+                "Readers and pieces are not initialized yet"
+            );
+            return None;
+        }
+    };
+    let piece_details = match readers_and_pieces.pieces.get(&piece_index_hash).copied() {
+        Some(piece_details) => piece_details,
+        None => {
+            trace!(
+                ?piece_index_hash,
+                "Piece is not stored in any of the local plots"
+            );
+            return None;
+        }
+    };
+    let reader = readers_and_pieces
+        .readers
+        .get(piece_details.plot_offset)
+        .cloned()
+        .expect("Offsets strictly correspond to existing plots; qed");
+
+    Some((reader, piece_details))
+}
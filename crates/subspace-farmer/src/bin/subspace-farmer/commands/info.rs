@@ -1,4 +1,6 @@
 use crate::DiskFarm;
+use subspace_farmer::single_disk_plot::read_only::PlotReader;
+use subspace_farmer::single_disk_plot::solution_outlook::SolutionOutlookReport;
 use subspace_farmer::single_disk_plot::{SingleDiskPlot, SingleDiskPlotSummary};
 
 pub(crate) fn info(disk_farms: Vec<DiskFarm>) {
@@ -7,7 +9,11 @@ pub(crate) fn info(disk_farms: Vec<DiskFarm>) {
             println!();
         }
 
-        let DiskFarm { directory, .. } = disk_farm;
+        let DiskFarm {
+            directory,
+            plot_layout,
+            ..
+        } = disk_farm;
 
         println!("Single disk farm {disk_farm_index}:");
         match SingleDiskPlot::collect_summary(directory) {
@@ -22,6 +28,47 @@ pub(crate) fn info(disk_farms: Vec<DiskFarm>) {
                     bytesize::to_string(info.allocated_space(), false)
                 );
                 println!("  Directory: {}", directory.display());
+                match PlotReader::open_with_layout(&directory, &plot_layout) {
+                    Ok(plot) => {
+                        let calculator = plot.layout_calculator();
+                        println!(
+                            "  Sector size: {}",
+                            bytesize::to_string(calculator.plot_sector_size(), true)
+                        );
+                        println!(
+                            "  Sectors: {} plotted out of {}",
+                            plot.sector_count(),
+                            plot.target_sector_count()
+                        );
+                        println!(
+                            "  Metadata overhead: {}",
+                            bytesize::to_string(
+                                calculator.metadata_overhead(plot.target_sector_count()),
+                                true
+                            )
+                        );
+                        println!(
+                            "  Usable fraction: {:.2}%",
+                            calculator.usable_fraction() * 100.0
+                        );
+                    }
+                    Err(error) => {
+                        println!("  Plot layout is unknown: {error}");
+                    }
+                }
+                match SolutionOutlookReport::load_from(&directory) {
+                    Ok(Some(report)) => {
+                        println!("  Solution outlook: {report}");
+                        println!(
+                            "  Expected time to solution: {:.2} days",
+                            report.expected_days_to_solution()
+                        );
+                    }
+                    Ok(None) => {}
+                    Err(error) => {
+                        println!("  Solution outlook is unavailable: {error}");
+                    }
+                }
             }
             SingleDiskPlotSummary::NotFound { directory } => {
                 println!("  Plot directory: {}", directory.display());
@@ -0,0 +1,61 @@
+use crate::utils::shutdown_signal;
+use anyhow::Context;
+use bytesize::ByteSize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use subspace_farmer::single_disk_plot::migration::migrate_plot;
+use tracing::info;
+
+/// Migrate plot from `source` to `destination` directory, Ctrl+C cancels migration, which can be
+/// resumed later by running the same command again
+pub(crate) async fn migrate(source: PathBuf, destination: PathBuf) -> anyhow::Result<()> {
+    let cancelled = Arc::new(AtomicBool::new(false));
+
+    let mut migration = tokio::task::spawn_blocking({
+        let cancelled = Arc::clone(&cancelled);
+        let source = source.clone();
+        let destination = destination.clone();
+
+        move || migrate_plot(&source, &destination, &cancelled)
+    });
+
+    let report = tokio::select! {
+        result = &mut migration => result,
+        _ = shutdown_signal() => {
+            cancelled.store(true, Ordering::Release);
+            migration.await
+        }
+    }?
+    .with_context(|| {
+        format!(
+            "Failed to migrate plot from {} to {}",
+            source.display(),
+            destination.display()
+        )
+    })?;
+
+    if report.completed {
+        info!(
+            "Migrated plot {} with {} sectors ({} copied, {} resumed), {} copied in {:?}, {} \
+            mismatch(es) were rewritten",
+            report.id,
+            report.sector_count,
+            report.copied_sectors,
+            report.resumed_sectors,
+            ByteSize::b(report.bytes_copied),
+            report.elapsed,
+            report.mismatches
+        );
+    } else {
+        info!(
+            "Migration of plot {} cancelled after {} of {} sectors, run the same command again to \
+            resume",
+            report.id,
+            report.resumed_sectors + report.copied_sectors,
+            report.sector_count
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use anyhow::Context;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use subspace_farmer::single_disk_plot::remote::{open_plot_file, serve_plot, tls_acceptor};
+use tokio::net::TcpListener;
+
+/// Serve plot file and metadata of the farm in `directory`, `tls` is a pair of paths to certificate chain and
+/// private key, `tls_client_ca` is a path to certificate authorities clients must be authenticated
+/// with
+pub(crate) async fn plot_server(
+    directory: PathBuf,
+    listen_on: SocketAddr,
+    tls: Option<(PathBuf, PathBuf)>,
+    tls_client_ca: Option<PathBuf>,
+    max_connections: NonZeroUsize,
+) -> anyhow::Result<()> {
+    let plot_file = open_plot_file(&directory)
+        .with_context(|| format!("Failed to open plot in {}", directory.display()))?;
+    let tls_acceptor = tls
+        .map(|(certificate_chain, private_key)| {
+            tls_acceptor(&certificate_chain, &private_key, tls_client_ca.as_deref())
+        })
+        .transpose()
+        .context("Failed to configure TLS")?;
+    let listener = TcpListener::bind(listen_on)
+        .await
+        .with_context(|| format!("Failed to listen on {listen_on}"))?;
+
+    serve_plot(
+        listener,
+        directory,
+        Arc::new(plot_file),
+        tls_acceptor,
+        max_connections,
+    )
+    .await?;
+
+    Ok(())
+}
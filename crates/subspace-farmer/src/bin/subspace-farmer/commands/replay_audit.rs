@@ -0,0 +1,107 @@
+use anyhow::Context;
+use std::path::Path;
+use subspace_core_primitives::crypto::kzg::{test_public_parameters, Kzg};
+use subspace_core_primitives::{bidirectional_distance, SectorId};
+use subspace_farmer::single_disk_plot::audit_replay::{read_audit_records, replay_audit as replay};
+
+pub(crate) fn replay_audit(file: &Path) -> anyhow::Result<()> {
+    let audit_records = read_audit_records(file)
+        .with_context(|| format!("Failed to read audit replay log {}", file.display()))?;
+    let kzg = Kzg::new(test_public_parameters());
+
+    println!(
+        "Replaying {} audit(s) from {}",
+        audit_records.len(),
+        file.display()
+    );
+
+    for (audit_record_index, audit_record) in audit_records.iter().enumerate() {
+        println!();
+        println!("Audit {audit_record_index}:");
+        println!("  Slot number: {}", audit_record.slot_number);
+        println!(
+            "  Global challenge: 0x{}",
+            hex::encode(audit_record.global_challenge)
+        );
+        println!("  Solution range: {}", audit_record.solution_range);
+        println!("  Public key: 0x{}", hex::encode(audit_record.public_key));
+        println!(
+            "  Reward address: 0x{}",
+            hex::encode(audit_record.reward_address)
+        );
+        println!("  Sector index: {}", audit_record.sector_index);
+        println!(
+            "  Genesis hash: 0x{}",
+            hex::encode(audit_record.genesis_hash)
+        );
+        println!("  Total pieces: {}", audit_record.total_pieces);
+        println!("  Space l: {}", audit_record.space_l);
+        println!("  KZG parameters ID: {}", audit_record.kzg_parameters_id);
+        println!("  Sector metadata: {:?}", audit_record.sector_metadata);
+        println!("  Rotation: {}", audit_record.rotation);
+
+        let sector_id = SectorId::new_rotated(
+            &audit_record.public_key,
+            audit_record.sector_index,
+            audit_record.rotation,
+        );
+        println!("  Sector ID: 0x{}", hex::encode(sector_id));
+        println!(
+            "  Local challenge: {}",
+            sector_id.derive_local_challenge(&audit_record.global_challenge)
+        );
+
+        let replayed_audit = match replay(audit_record, &kzg) {
+            Ok(replayed_audit) => replayed_audit,
+            Err(error) => {
+                println!("  Replay failed: {error}");
+                continue;
+            }
+        };
+
+        let eligible_sector = match replayed_audit.eligible_sector {
+            Some(eligible_sector) => eligible_sector,
+            None => {
+                println!("  Sector is not eligible");
+                continue;
+            }
+        };
+        println!("  Audit index: {}", eligible_sector.audit_index);
+        println!(
+            "  Audit piece offset: {}",
+            eligible_sector.audit_piece_offset
+        );
+        println!("  Chunk: 0x{}", hex::encode(eligible_sector.chunk));
+        println!("  Expanded chunk: {}", eligible_sector.expanded_chunk);
+        println!(
+            "  Distance: {}",
+            bidirectional_distance(
+                &eligible_sector.local_challenge,
+                &eligible_sector.expanded_chunk
+            )
+        );
+
+        match replayed_audit.solution {
+            Some(solution) => {
+                println!("  Solution:");
+                println!("    Total pieces: {}", solution.total_pieces);
+                println!("    Piece offset: {}", solution.piece_offset);
+                println!(
+                    "    Piece record hash: 0x{}",
+                    hex::encode(solution.piece_record_hash)
+                );
+                println!(
+                    "    Piece witness: 0x{}",
+                    hex::encode(solution.piece_witness.to_bytes())
+                );
+                println!("    Chunk: 0x{}", hex::encode(solution.chunk));
+                println!("    Chunk signature: created with throwaway key, not printed");
+            }
+            None => {
+                println!("  Piece failed to decode or verify, solution was not created");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use crate::SimulationArgs;
+use anyhow::Context;
+use std::time::Duration;
+use subspace_farmer::rpc_client::bench_rpc_client::BENCH_FARMER_PROTOCOL_INFO;
+use subspace_farmer::single_disk_plot::simulation::{simulate as run, SimulationOptions};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+pub(crate) async fn simulate(simulation_args: SimulationArgs) -> anyhow::Result<()> {
+    let SimulationArgs {
+        directory,
+        plot_size,
+        total_space_pledged,
+        solution_range,
+        slot_duration,
+        slot_probability,
+        space_l,
+        sample_sectors,
+        sample_slots,
+    } = simulation_args;
+
+    let farmer_protocol_info = FarmerProtocolInfo {
+        space_l,
+        ..BENCH_FARMER_PROTOCOL_INFO
+    };
+
+    let report = tokio::task::spawn_blocking(move || {
+        run(SimulationOptions {
+            directory: &directory,
+            farmer_protocol_info,
+            sample_sectors,
+            sample_slots,
+            plot_size: plot_size.as_u64(),
+            slot_duration: Duration::from_millis(slot_duration),
+            slot_probability,
+            total_space_pledged: total_space_pledged.as_u64(),
+            solution_range,
+        })
+    })
+    .await?
+    .context("Simulation failed")?;
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    let plot_size = report.target_sector_count * report.plot_sector_size;
+    let max_plot_size = report
+        .max_sector_count
+        .saturating_mul(report.plot_sector_size);
+    let expected_solutions = &report.expected_solutions;
+
+    eprintln!(
+        "Simulated plot: {} ({} sectors of {})",
+        bytesize::to_string(plot_size, true),
+        report.target_sector_count,
+        bytesize::to_string(report.plot_sector_size, true)
+    );
+    eprintln!(
+        "Measured on {} sample sector(s) over {} slot(s)",
+        report.sample_sectors, report.sample_slots
+    );
+    eprintln!(
+        "Sector plotting time: {:?} (whole plot: {:?}, excluding piece retrieval)",
+        report.sector_plotting_time, report.plotting_time
+    );
+    eprintln!(
+        "Sector audit time: {:?} on average, {:?} max",
+        report.sector_audit_time, report.max_sector_audit_time
+    );
+    eprintln!("Winning piece read time: {:?}", report.piece_read_time);
+    eprintln!(
+        "Time per slot: {:?} out of {:?}, {}",
+        report.slot_time,
+        report.slot_duration,
+        if report.meets_slot_deadline {
+            "fits into slot"
+        } else {
+            "SLOT DEADLINE WILL BE MISSED"
+        }
+    );
+    eprintln!(
+        "Largest plot this disk can audit in time: {}",
+        bytesize::to_string(max_plot_size, true)
+    );
+    eprintln!("Solution range: {}", expected_solutions.solution_range);
+    eprintln!(
+        "Expected solutions: {:.4} per day ({:.6} per slot), {:.4}% of {:.1} network solutions per \
+        day",
+        expected_solutions.solutions_per_day,
+        expected_solutions.solutions_per_slot,
+        expected_solutions.network_share * 100.0,
+        expected_solutions.network_solutions_per_day
+    );
+
+    Ok(())
+}
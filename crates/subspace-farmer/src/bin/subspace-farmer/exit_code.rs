@@ -0,0 +1,266 @@
+//! Exit codes of the farmer, so that orchestration (systemd, Kubernetes, scripts) can tell errors
+//! that restart will not fix from those it might.
+//!
+//! Codes follow `sysexits.h` where there is a matching one, `--help` lists them from [`EXIT_CODES`].
+
+#[cfg(test)]
+mod tests;
+
+use std::error::Error;
+use std::io;
+use std::process::ExitCode;
+use subspace_farmer::root_block_store::RootBlockStoreError;
+use subspace_farmer::single_disk_plot::{
+    BackgroundTaskError, FarmingError, PlottingError, SingleDiskPlotError,
+};
+use thiserror::Error;
+
+/// Invalid command line arguments or farm configuration that isn't caught by argument parsing
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub(crate) struct ConfigError(pub(crate) String);
+
+/// Category of fatal error the farmer exits with
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum FatalErrorKind {
+    /// Error that doesn't fit any other category
+    Other,
+    /// Invalid command line arguments or farm configuration
+    Config,
+    /// Plot was created with different format or protocol parameters, or is corrupted
+    IncompatiblePlot,
+    /// Reading from or writing to disk failed
+    DiskFailure,
+    /// Node is on a different chain or doesn't speak the protocol farmer expects
+    NodeIncompatibility,
+    /// Node or network is temporarily unreachable
+    TransientNetwork,
+}
+
+/// Exit code of a single fatal error kind
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ExitCodeEntry {
+    /// Error kind
+    pub(crate) kind: FatalErrorKind,
+    /// Process exit code
+    pub(crate) code: u8,
+    /// Name of the kind in JSON error summary
+    pub(crate) name: &'static str,
+    /// Whether restarting farmer without changes may help
+    pub(crate) restart: bool,
+    /// Human readable description for `--help`
+    pub(crate) description: &'static str,
+}
+
+/// Exit codes of all fatal error kinds, these are stable and must not be changed
+pub(crate) const EXIT_CODES: [ExitCodeEntry; 6] = [
+    ExitCodeEntry {
+        kind: FatalErrorKind::Other,
+        code: 1,
+        name: "other",
+        restart: true,
+        description: "error that doesn't fit any other category",
+    },
+    ExitCodeEntry {
+        kind: FatalErrorKind::IncompatiblePlot,
+        code: 65,
+        name: "incompatible_plot",
+        restart: false,
+        description: "plot was created with different format or protocol parameters, or is \
+            corrupted",
+    },
+    ExitCodeEntry {
+        kind: FatalErrorKind::DiskFailure,
+        code: 74,
+        name: "disk_failure",
+        restart: false,
+        description: "reading from or writing to disk failed",
+    },
+    ExitCodeEntry {
+        kind: FatalErrorKind::TransientNetwork,
+        code: 75,
+        name: "transient_network",
+        restart: true,
+        description: "node or network is temporarily unreachable",
+    },
+    ExitCodeEntry {
+        kind: FatalErrorKind::NodeIncompatibility,
+        code: 76,
+        name: "node_incompatibility",
+        restart: false,
+        description: "node is on a different chain (genesis hash) or doesn't speak the protocol \
+            farmer expects",
+    },
+    ExitCodeEntry {
+        kind: FatalErrorKind::Config,
+        code: 78,
+        name: "config",
+        restart: false,
+        description: "invalid command line arguments or farm configuration",
+    },
+];
+
+impl FatalErrorKind {
+    /// Classify error by the first error in its chain of causes that has a known kind
+    pub(crate) fn classify(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(Self::from_cause)
+            .unwrap_or(Self::Other)
+    }
+
+    /// Entry of this kind in [`EXIT_CODES`]
+    pub(crate) fn entry(self) -> &'static ExitCodeEntry {
+        EXIT_CODES
+            .iter()
+            .find(|entry| entry.kind == self)
+            .expect("Every kind has an entry in the table; qed")
+    }
+
+    fn from_cause(cause: &(dyn Error + 'static)) -> Option<Self> {
+        if cause.downcast_ref::<ConfigError>().is_some() || cause.is::<clap::Error>() {
+            Some(Self::Config)
+        } else if let Some(error) = cause.downcast_ref::<BackgroundTaskError>() {
+            Some(match error {
+                BackgroundTaskError::Plotting(error) => Self::from_plotting_error(error),
+                BackgroundTaskError::Farming(error) => Self::from_farming_error(error),
+            })
+        } else if let Some(error) = cause.downcast_ref::<PlottingError>() {
+            Some(Self::from_plotting_error(error))
+        } else if let Some(error) = cause.downcast_ref::<FarmingError>() {
+            Some(Self::from_farming_error(error))
+        } else if let Some(error) = cause.downcast_ref::<SingleDiskPlotError>() {
+            Some(Self::from_single_disk_plot_error(error))
+        } else if let Some(error) = cause.downcast_ref::<RootBlockStoreError>() {
+            Some(match error {
+                RootBlockStoreError::Io(error) => Self::from_io_error(error),
+                RootBlockStoreError::FailedToDecodeHeader(_)
+                | RootBlockStoreError::UnsupportedVersion { .. } => Self::IncompatiblePlot,
+                RootBlockStoreError::ConflictingRootBlock { .. }
+                | RootBlockStoreError::InvalidChain(_) => Self::NodeIncompatibility,
+            })
+        } else if let Some(error) = cause.downcast_ref::<jsonrpsee::core::Error>() {
+            Some(match error {
+                jsonrpsee::core::Error::ParseError(_) => Self::NodeIncompatibility,
+                _ => Self::TransientNetwork,
+            })
+        } else {
+            cause.downcast_ref::<io::Error>().map(Self::from_io_error)
+        }
+    }
+
+    fn from_single_disk_plot_error(error: &SingleDiskPlotError) -> Self {
+        match error {
+            SingleDiskPlotError::Io(error) => Self::from_io_error(error),
+            SingleDiskPlotError::FailedToOpenIdentity { .. }
+            | SingleDiskPlotError::StartupCheckFailed { .. } => Self::DiskFailure,
+            SingleDiskPlotError::CantResize { .. }
+            | SingleDiskPlotError::DirectoryNotWritable { .. }
+            | SingleDiskPlotError::InsufficientSpace { .. }
+            | SingleDiskPlotError::InvalidSectorAlignment { .. }
+            | SingleDiskPlotError::PlotLocked { .. }
+            | SingleDiskPlotError::PlotAlreadyExists { .. }
+            | SingleDiskPlotError::OverlayWithoutPlot { .. }
+            | SingleDiskPlotError::OverlayOfShardedPlot { .. }
+            | SingleDiskPlotError::OfflineAccessOfShardedPlot { .. }
+            | SingleDiskPlotError::PlotInfoNotFound { .. } => Self::Config,
+            SingleDiskPlotError::IdentityMismatch { .. }
+            | SingleDiskPlotError::FailedToDecodeMetadataHeader(_)
+            | SingleDiskPlotError::UnexpectedMetadataVersion(_)
+            | SingleDiskPlotError::FailedToDecodeSectorMetadataHeader(_)
+            | SingleDiskPlotError::UnexpectedSectorMetadataVersion(_)
+            | SingleDiskPlotError::UnexpectedSectorMetadataRecordSize(_)
+            | SingleDiskPlotError::FailedToDecodeProtocolInfo(_)
+            | SingleDiskPlotError::ProtocolInfoNotStored { .. }
+            | SingleDiskPlotError::InconsistentPlotLayout { .. }
+            | SingleDiskPlotError::InconsistentPlotShards { .. }
+            | SingleDiskPlotError::SectorIndexOutOfRange { .. } => Self::IncompatiblePlot,
+            SingleDiskPlotError::WrongChain { .. } => Self::NodeIncompatibility,
+            SingleDiskPlotError::NodeRpcError(_) => Self::TransientNetwork,
+        }
+    }
+
+    fn from_plotting_error(error: &PlottingError) -> Self {
+        match error {
+            PlottingError::FailedToGetFarmerProtocolInfo { .. }
+            | PlottingError::FailedToRetrievePiece { .. } => Self::TransientNetwork,
+            PlottingError::PieceNotFound { .. } => Self::Other,
+            PlottingError::ProgressStore { .. } => Self::DiskFailure,
+            PlottingError::Plot(error) => Self::from_single_disk_plot_error(error),
+            PlottingError::Io(error) => Self::from_io_error(error),
+        }
+    }
+
+    fn from_farming_error(error: &FarmingError) -> Self {
+        match error {
+            FarmingError::FailedToGetFarmerProtocolInfo { .. }
+            | FarmingError::FailedToSubmitSolutionsResponse { .. }
+            | FarmingError::FailedToGetPiece { .. } => Self::TransientNetwork,
+            FarmingError::FailedToMapPlot { .. } | FarmingError::FailedToMapMetadata { .. } => {
+                Self::DiskFailure
+            }
+            FarmingError::FailedToDecodeMetadata { .. }
+            | FarmingError::KzgParametersMismatch { .. } => Self::IncompatiblePlot,
+            FarmingError::RemoteIdentityMismatch { .. } => Self::Config,
+            FarmingError::PieceNotFound { .. } | FarmingError::Panicked { .. } => Self::Other,
+            FarmingError::Plot(error) => Self::from_single_disk_plot_error(error),
+            FarmingError::Io(error) => Self::from_io_error(error),
+        }
+    }
+
+    fn from_io_error(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::AddrNotAvailable
+            | io::ErrorKind::TimedOut => Self::TransientNetwork,
+            _ => Self::DiskFailure,
+        }
+    }
+}
+
+/// Exit codes section of `--help`
+pub(crate) fn exit_codes_help() -> String {
+    let mut help = String::from("EXIT CODES:\n    0     success\n");
+    for entry in &EXIT_CODES {
+        help.push_str(&format!(
+            "    {:<5} {} ({}, {})\n",
+            entry.code,
+            entry.description,
+            entry.name,
+            if entry.restart {
+                "restart may help"
+            } else {
+                "don't restart"
+            }
+        ));
+    }
+    help.push_str(
+        "\nLast line printed to stderr on failure is a JSON object with `exitCode`, `kind` and \
+        `error` fields",
+    );
+    help
+}
+
+/// Single-line JSON summary of the fatal error
+pub(crate) fn error_summary(kind: FatalErrorKind, error: &anyhow::Error) -> String {
+    let entry = kind.entry();
+    serde_json::json!({
+        "exitCode": entry.code,
+        "kind": entry.name,
+        "error": format!("{error:#}"),
+    })
+    .to_string()
+}
+
+/// Print fatal error followed by its JSON summary to stderr and return exit code for it
+pub(crate) fn report_fatal_error(error: &anyhow::Error) -> ExitCode {
+    let kind = FatalErrorKind::classify(error);
+
+    eprintln!("Error: {error:?}");
+    eprintln!("{}", error_summary(kind, error));
+
+    ExitCode::from(kind.entry().code)
+}
@@ -0,0 +1,166 @@
+use crate::exit_code::{error_summary, exit_codes_help, ConfigError, FatalErrorKind, EXIT_CODES};
+use anyhow::anyhow;
+use bytesize::ByteSize;
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use subspace_farmer::single_disk_plot::{
+    BackgroundTaskError, FarmingError, PlottingError, SingleDiskPlotError, SingleDiskPlotId,
+};
+
+fn exit_code(error: impl Into<anyhow::Error>) -> u8 {
+    FatalErrorKind::classify(&error.into()).entry().code
+}
+
+#[test]
+fn codes_are_unique_and_stable() {
+    let codes = EXIT_CODES
+        .iter()
+        .map(|entry| entry.code)
+        .collect::<HashSet<_>>();
+    assert_eq!(codes.len(), EXIT_CODES.len());
+    assert!(!codes.contains(&0));
+
+    assert_eq!(FatalErrorKind::Other.entry().code, 1);
+    assert_eq!(FatalErrorKind::IncompatiblePlot.entry().code, 65);
+    assert_eq!(FatalErrorKind::DiskFailure.entry().code, 74);
+    assert_eq!(FatalErrorKind::TransientNetwork.entry().code, 75);
+    assert_eq!(FatalErrorKind::NodeIncompatibility.entry().code, 76);
+    assert_eq!(FatalErrorKind::Config.entry().code, 78);
+}
+
+#[test]
+fn config_errors() {
+    assert_eq!(
+        exit_code(ConfigError("Plot size is too low".to_string())),
+        78
+    );
+    assert_eq!(
+        exit_code(SingleDiskPlotError::InsufficientSpace {
+            directory: PathBuf::from("/plot"),
+            required: ByteSize::gib(2),
+            available: ByteSize::gib(1),
+        }),
+        78
+    );
+    assert_eq!(
+        exit_code(SingleDiskPlotError::PlotLocked {
+            directory: PathBuf::from("/plot"),
+        }),
+        78
+    );
+}
+
+#[test]
+fn incompatible_plot_errors() {
+    assert_eq!(
+        exit_code(SingleDiskPlotError::UnexpectedMetadataVersion(9)),
+        65
+    );
+    assert_eq!(
+        exit_code(SingleDiskPlotError::ProtocolInfoNotStored {
+            id: SingleDiskPlotId::new(),
+        }),
+        65
+    );
+}
+
+#[test]
+fn disk_failure_errors() {
+    assert_eq!(exit_code(io::Error::from(io::ErrorKind::Other)), 74);
+    assert_eq!(
+        exit_code(BackgroundTaskError::Plotting(PlottingError::Io(
+            io::Error::from(io::ErrorKind::PermissionDenied)
+        ))),
+        74
+    );
+    assert_eq!(
+        exit_code(BackgroundTaskError::Farming(
+            FarmingError::FailedToMapPlot {
+                error: io::Error::from(io::ErrorKind::Other),
+            }
+        )),
+        74
+    );
+}
+
+#[test]
+fn node_incompatibility_errors() {
+    assert_eq!(
+        exit_code(SingleDiskPlotError::WrongChain {
+            id: SingleDiskPlotId::new(),
+            correct_chain: "00".repeat(32),
+            wrong_chain: "ff".repeat(32),
+        }),
+        76
+    );
+}
+
+#[test]
+fn transient_network_errors() {
+    assert_eq!(
+        exit_code(SingleDiskPlotError::NodeRpcError(
+            "Connection refused".into()
+        )),
+        75
+    );
+    assert_eq!(
+        exit_code(io::Error::from(io::ErrorKind::ConnectionRefused)),
+        75
+    );
+    assert_eq!(
+        exit_code(BackgroundTaskError::Plotting(
+            PlottingError::FailedToGetFarmerProtocolInfo {
+                error: "Node went away".into(),
+            }
+        )),
+        75
+    );
+}
+
+#[test]
+fn other_errors() {
+    assert_eq!(exit_code(anyhow!("Something unexpected")), 1);
+}
+
+#[test]
+fn nested_errors_are_classified() {
+    let error = anyhow::Error::from(SingleDiskPlotError::WrongChain {
+        id: SingleDiskPlotId::new(),
+        correct_chain: "00".repeat(32),
+        wrong_chain: "ff".repeat(32),
+    })
+    .context("Dry run of plot at /plot failed");
+    assert_eq!(exit_code(error), 76);
+
+    let error = BackgroundTaskError::Farming(FarmingError::Plot(
+        SingleDiskPlotError::UnexpectedSectorMetadataVersion(9),
+    ));
+    assert_eq!(exit_code(error), 65);
+}
+
+#[test]
+fn summary_is_single_json_line() {
+    let error =
+        anyhow::Error::from(io::Error::from(io::ErrorKind::Other)).context("Line one\nline two");
+    let summary = error_summary(FatalErrorKind::classify(&error), &error);
+
+    assert!(!summary.contains('\n'));
+    let summary = serde_json::from_str::<serde_json::Value>(&summary).unwrap();
+    assert_eq!(summary["exitCode"], 74);
+    assert_eq!(summary["kind"], "disk_failure");
+    assert!(summary["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Line one\nline two: "));
+}
+
+#[test]
+fn help_lists_every_code() {
+    let help = exit_codes_help();
+
+    for entry in &EXIT_CODES {
+        assert!(help.contains(&format!("    {:<5} {}", entry.code, entry.description)));
+        assert!(help.contains(entry.name));
+    }
+}
@@ -1,18 +1,25 @@
 mod commands;
+mod exit_code;
 mod ss58;
 mod utils;
 
+use crate::exit_code::{ConfigError, FatalErrorKind};
 use crate::utils::get_usable_plot_space;
 use anyhow::Result;
 use bytesize::ByteSize;
-use clap::{ArgEnum, Parser, ValueHint};
+use clap::{ArgEnum, CommandFactory, FromArgMatches, Parser, ValueHint};
 use ss58::parse_ss58_reward_address;
-use std::fs;
-use std::num::NonZeroU16;
+use std::net::SocketAddr;
+use std::num::{NonZeroU16, NonZeroU64, NonZeroUsize};
 use std::path::PathBuf;
+use std::process::ExitCode;
 use std::str::FromStr;
-use subspace_core_primitives::PublicKey;
-use subspace_farmer::single_disk_plot::SingleDiskPlot;
+use std::{fs, io};
+use subspace_core_primitives::{PublicKey, SolutionRange};
+use subspace_farmer::log_file::RotatingLogFile;
+use subspace_farmer::root_block_store::RootBlockStore;
+use subspace_farmer::single_disk_plot::{PlotLayout, SingleDiskPlot};
+use subspace_farmer::DerivationPath;
 use subspace_networking::libp2p::Multiaddr;
 use tempfile::TempDir;
 use tracing::info;
@@ -30,6 +37,9 @@ use tracing_subscriber::{fmt, EnvFilter};
 #[global_allocator]
 static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
+/// Name of the current log file in `--log-dir`
+const LOG_FILE_NAME: &str = "subspace-farmer.log";
+
 /// Arguments for farmer
 #[derive(Debug, Parser)]
 struct FarmingArgs {
@@ -58,6 +68,281 @@ struct FarmingArgs {
     /// Enable DSN and use DSN piece provider for plotting
     #[clap(long)]
     enable_dsn: bool,
+    /// Size of in-memory cache of recently archived pieces in human readable format (e.g. 1GiB),
+    /// it is populated proactively from archived segments announced by the node
+    #[clap(long, default_value = "1GiB")]
+    piece_cache_size: ByteSize,
+    /// Which pieces piece cache evicts first when full, `lfu` evicts least frequently used pieces,
+    /// `segmented` protects pieces that were used at least once after insertion
+    #[clap(arg_enum, long, default_value = "lru")]
+    piece_cache_eviction_policy: EvictionPolicyArg,
+    /// Number of upcoming sectors whose pieces are pinned in piece cache during plotting, so they
+    /// are not evicted before being plotted. At most half of the piece cache can be pinned, `0`
+    /// disables pinning
+    #[clap(long, default_value = "0")]
+    piece_cache_pin_ahead: u64,
+    /// Maximum number of archived segments downloaded from the node at the same time during
+    /// plotting. Segment of every piece that is not in piece cache yet is downloaded from the node
+    /// as a whole, verified and added to piece cache before trying DSN, which speeds up initial
+    /// plotting considerably. `0` disables downloading of segments from the node
+    #[clap(long, default_value = "2")]
+    node_segment_downloads: usize,
+    /// How plot files are accessed, `network` avoids memory mapping and preallocation that are
+    /// unreliable on network file systems (NFS, SMB) at the cost of performance, `auto` detects it
+    /// from the file system type (Linux only)
+    #[clap(arg_enum, long, default_value = "auto")]
+    storage_backend: StorageBackendArg,
+    /// Limit of download bandwidth used for retrieving pieces during plotting per second in human
+    /// readable format (e.g. 10MiB) or just bytes, shared by all plots, unlimited by default
+    #[clap(long)]
+    download_bandwidth_limit: Option<ByteSize>,
+    /// Limit of upload bandwidth used for serving pieces to other peers per second in human
+    /// readable format (e.g. 10MiB) or just bytes, shared by all peers, unlimited by default.
+    /// Requests of different peers are served in turns, so one peer can't starve others
+    #[clap(long)]
+    serving_bandwidth_limit: Option<ByteSize>,
+    /// Number of piece requests of a single peer that are served concurrently
+    #[clap(long, default_value = "4")]
+    serving_max_concurrent_requests_per_peer: NonZeroUsize,
+    /// Number of piece requests of a single peer that wait for their turn, further requests are
+    /// rejected with a hint when to retry
+    #[clap(long, default_value = "32")]
+    serving_max_queued_requests_per_peer: usize,
+    /// Timeout in seconds of a single attempt to retrieve piece during plotting, attempts that
+    /// time out are retried, so a hung peer doesn't stall plotting
+    #[clap(long, default_value = "60")]
+    piece_fetch_timeout: u64,
+    /// Pieces whose retrieval takes longer than this many seconds during plotting are logged along
+    /// with sector they belong to
+    #[clap(long, default_value = "10")]
+    slow_piece_threshold: u64,
+    /// Pieces downloaded during plotting are kept in memory for this many seconds, so that other
+    /// plots requesting the same piece don't download it again. Memory usage grows with download
+    /// rate, 0 disables it.
+    #[clap(long, default_value = "5")]
+    recent_pieces_ttl: u64,
+    /// Memory budget for sectors being plotted, piece cache and downloads in human readable format
+    /// (e.g. 4GiB) or just bytes, shared by all plots. When reached, plotting of new sectors waits
+    /// and piece cache evicts pieces. Unlimited by default
+    #[clap(long)]
+    memory_budget: Option<ByteSize>,
+    /// Pause plotting disk writes while plot is being audited to reduce audit latency, each pause
+    /// is bounded, so plotting still makes progress on large plots
+    #[clap(long)]
+    pause_plotting_during_audit: bool,
+    /// Also pause encoding of pieces while plot is being audited, sharing the same bounded pause
+    /// with disk writes
+    #[clap(long, requires = "pause-plotting-during-audit")]
+    pause_encoding_during_audit: bool,
+    /// Order in which sectors are audited, `random-per-slot` derives order from slot challenge,
+    /// `recent-winners-first` audits sectors that produced solutions recently first
+    #[clap(arg_enum, long, default_value = "sequential")]
+    audit_order: AuditOrderArg,
+    /// Audit at most this many sectors of every plot per slot, for disks (SMR, USB) that can't
+    /// audit the whole plot before slot deadline. Sectors are selected from slot challenge such that
+    /// every sector is audited in the same fraction of slots, rewards are reduced by the same
+    /// fraction. All sectors are audited by default
+    #[clap(long)]
+    max_sectors_per_slot: Option<NonZeroU64>,
+    /// Record inputs of audits that resulted in solutions into audit replay log in every plot
+    /// directory, up to specified size in human readable format (e.g. 10MiB) or just bytes. Use
+    /// `replay-audit` command to replay recorded audits. Disabled by default
+    #[clap(long)]
+    audit_replay_log_size: Option<ByteSize>,
+    /// Size of the per plot cache of pieces read during recent audits in human readable format
+    /// (e.g. 4MiB) or just bytes, consecutive slots auditing the same piece don't read it from
+    /// disk again. `0` disables the cache.
+    #[clap(long, default_value = "4MiB")]
+    audit_cache_size: ByteSize,
+    /// Coalesce audit reads of sectors whose audited pieces are at most this far apart in human
+    /// readable format (e.g. 1MiB) or just bytes into a single read, reduces number of syscalls and
+    /// round-trips for plots on network file systems. Only applies to plots that are not memory
+    /// mapped and have no overlay. Disabled by default
+    #[clap(long)]
+    coalesce_audit_reads: Option<ByteSize>,
+    /// Maximum number of piece witnesses verified during plotting that are remembered, so that the
+    /// same pieces plotted into many sectors (common on networks with short history) are not
+    /// verified again. `0` disables the cache.
+    #[clap(long, default_value = "0")]
+    witness_cache_entries: usize,
+    /// Slot probability of the network in `numerator/denominator` format, used to estimate space
+    /// pledged to the network from solution range
+    #[clap(long, default_value = "1/6", parse(try_from_str = parse_slot_probability))]
+    slot_probability: (u64, u64),
+    /// Set OS-level I/O priority of audit reads above plotting writes, only supported on Linux
+    /// when farmer is built with `io-priority` feature and I/O scheduler respects priorities (BFQ)
+    #[clap(long)]
+    io_priority: bool,
+    /// Save progress of plotting within a sector to `plotting_progress` directory of every plot, so
+    /// that sector interrupted by restart continues where it stopped instead of being plotted from
+    /// scratch
+    #[clap(long)]
+    resumable_plotting: bool,
+    /// Read and audit one random plotted sector of every plot on startup and verify resulting
+    /// solution, so that disk errors and corrupted KZG parameters are discovered before farming
+    /// starts rather than when the first chunk wins. Startup fails if the check fails
+    #[clap(long, parse(try_from_str), default_value = "true")]
+    startup_check: bool,
+    /// Verify checksums of plotted sectors one by one while disk is idle after audit, sectors that
+    /// fail verification are replotted. Verification pauses as soon as the next slot arrives
+    #[clap(long)]
+    idle_verification: bool,
+    /// Idle verification only happens while at least this many milliseconds are left before the
+    /// next slot is expected
+    #[clap(long, default_value = "200")]
+    idle_verification_margin: u64,
+    /// Verification coverage pushed as a metric is the fraction of plotted sectors verified during
+    /// this many last days
+    #[clap(long, default_value = "7")]
+    verification_coverage_days: NonZeroU64,
+    /// Stop submitting solutions and signing rewards once the same plot appears to be farmed by
+    /// another farmer, for instance after plot was copied to another machine. Detection is only
+    /// logged by default, since farmers sharing identity with different plots look the same
+    #[clap(long)]
+    pause_on_duplicate_plot: bool,
+    /// Push metrics of every plot (plotted and target sector counts, audit and verification
+    /// coverage) to collector at this `host:port` address, for farms behind NAT that can't be scraped. Disabled
+    /// by default
+    #[clap(long)]
+    metrics_push_endpoint: Option<String>,
+    /// Protocol metrics are pushed with, `statsd` sends gauges with DogStatsD-style tags over UDP,
+    /// `graphite` uses tagged plaintext protocol over TCP
+    #[clap(arg_enum, long, default_value = "statsd")]
+    metrics_push_protocol: MetricsPushProtocolArg,
+    /// Interval in seconds between metrics pushes
+    #[clap(long, default_value = "15")]
+    metrics_push_interval: NonZeroU64,
+    /// Export timings of every plotted and audited sector (`sector_index, plot_ms, audit_ms,
+    /// solutions`) into `sector_timings.csv` or `sector_timings.json` in every plot directory for
+    /// offline analysis. Sectors are audited every slot, so files grow quickly. Disabled by default
+    #[clap(arg_enum, long)]
+    sector_timings_format: Option<SectorTimingsFormatArg>,
+    /// Validate node connection, protocol compatibility, directories, available space and plot
+    /// layout of every disk farm without writing anything to disk, print report as JSON and exit
+    #[clap(long)]
+    dry_run: bool,
+    /// Derive identity of all plots from a BIP39 mnemonic (for instance the one of the reward
+    /// wallet) instead of generating random identity in every plot directory, so that it can be
+    /// recovered. Mnemonic is prompted for on start (see `--mnemonic-fd`) and never stored, only
+    /// public key and derivation path are stored in plot directory.
+    #[clap(long)]
+    identity_from_mnemonic: bool,
+    /// Derivation path of identity derived from mnemonic in Substrate format (`//hard/soft`, for
+    /// instance `//subspace//farmer`), empty path uses the key of the mnemonic itself
+    #[clap(long, default_value = "", requires = "identity-from-mnemonic")]
+    identity_derivation_path: DerivationPath,
+    /// Read mnemonic from this file descriptor instead of prompting for it in terminal, for
+    /// instance `--mnemonic-fd 3 3<mnemonic.txt`
+    #[clap(long, requires = "identity-from-mnemonic")]
+    mnemonic_fd: Option<i32>,
+    /// Farm plot served by `plot-server` command on another machine in addition to disk farms,
+    /// can be specified multiple times.
+    ///
+    /// Format is coma-separated string like this:
+    ///
+    /// address=192.168.1.10:30000,identity=/path/to/identity/directory
+    ///
+    /// `identity` is a directory with identity plot was created with (`identity.bin` from plot
+    /// directory), identity derived from mnemonic is used if not specified (see
+    /// `--identity-from-mnemonic`).
+    /// Optional `tls-ca=/path/to/ca.pem` enables TLS and verifies certificate of plot server
+    /// against certificate authorities from specified PEM file, `server-name=plots.example.com` is
+    /// required with it and is the name certificate was issued for.
+    /// Optional `tls-certificate=/path/to/cert.pem` and `tls-private-key=/path/to/key.pem` are
+    /// presented to plot server that requires client authentication (see `--tls-client-ca` of
+    /// `plot-server`).
+    #[clap(long)]
+    remote_plot: Vec<RemotePlot>,
+}
+
+/// Arguments for farming simulation
+#[derive(Debug, Parser)]
+struct SimulationArgs {
+    /// Directory on the disk to evaluate, a few sectors are plotted into a temporary file there
+    #[clap(value_hint = ValueHint::DirPath)]
+    directory: PathBuf,
+    /// Plot size to simulate in human readable format (e.g. 10GB, 2TiB) or just bytes
+    #[clap(long)]
+    plot_size: ByteSize,
+    /// Total space pledged to the network, including simulated plot, in human readable format
+    /// (e.g. 10GB, 2TiB) or just bytes
+    #[clap(long)]
+    total_space_pledged: ByteSize,
+    /// Solution range to compute expected solutions for, derived from total space pledged and
+    /// slot probability by default
+    #[clap(long)]
+    solution_range: Option<SolutionRange>,
+    /// Slot duration in milliseconds
+    #[clap(long, default_value = "1000")]
+    slot_duration: u64,
+    /// Slot probability in `numerator/denominator` format
+    #[clap(long, default_value = "1/6", parse(try_from_str = parse_slot_probability))]
+    slot_probability: (u64, u64),
+    /// Encoding parameter `space_l` sample sectors are plotted with, determines sector size
+    #[clap(long, default_value = "20")]
+    space_l: NonZeroU16,
+    /// Number of real sectors to plot and audit
+    #[clap(long, default_value = "4")]
+    sample_sectors: NonZeroU64,
+    /// Number of slots to audit sample sectors for
+    #[clap(long, default_value = "10")]
+    sample_slots: NonZeroU64,
+}
+
+fn parse_slot_probability(s: &str) -> Result<(u64, u64), String> {
+    let (numerator, denominator) = s
+        .split_once('/')
+        .ok_or("Must be in `numerator/denominator` format")?;
+    let numerator = numerator
+        .parse::<u64>()
+        .map_err(|error| format!("Failed to parse numerator \"{numerator}\": {error}"))?;
+    let denominator = denominator
+        .parse::<u64>()
+        .map_err(|error| format!("Failed to parse denominator \"{denominator}\": {error}"))?;
+    if denominator == 0 || numerator > denominator {
+        return Err("Slot probability must be within 0..=1 and denominator can't be 0".to_string());
+    }
+
+    Ok((numerator, denominator))
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum StorageBackendArg {
+    Auto,
+    Local,
+    Network,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum EvictionPolicyArg {
+    Lru,
+    Lfu,
+    Segmented,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum MetricsPushProtocolArg {
+    Statsd,
+    Graphite,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum SectorTimingsFormatArg {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ArgEnum)]
+enum AuditOrderArg {
+    Sequential,
+    RandomPerSlot,
+    RecentWinnersFirst,
 }
 
 #[derive(Debug, Clone, Copy, ArgEnum)]
@@ -80,6 +365,64 @@ enum Subcommand {
     Farm(FarmingArgs),
     /// Print information about farm and its content
     Info,
+    /// Replay audits recorded into audit replay log and print all intermediate values
+    ReplayAudit {
+        /// Path to audit replay log
+        #[clap(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+    },
+    /// Serve plot file of a single disk farm over the network, so that it can be audited remotely
+    PlotServer {
+        /// Directory of the single disk farm to serve
+        #[clap(value_hint = ValueHint::DirPath)]
+        directory: PathBuf,
+        /// Address to listen on for plot reads
+        #[clap(long)]
+        listen_on: SocketAddr,
+        /// Path to PEM file with TLS certificate chain, enables TLS together with
+        /// `--tls-private-key`
+        #[clap(long, value_hint = ValueHint::FilePath, requires = "tls-private-key")]
+        tls_certificate: Option<PathBuf>,
+        /// Path to PEM file with TLS private key
+        #[clap(long, value_hint = ValueHint::FilePath, requires = "tls-certificate")]
+        tls_private_key: Option<PathBuf>,
+        /// Path to PEM file with certificate authorities, clients are only accepted if they present
+        /// TLS certificate issued by one of them. Any client is accepted by default
+        #[clap(long, value_hint = ValueHint::FilePath, requires = "tls-certificate")]
+        tls_client_ca: Option<PathBuf>,
+        /// Maximum number of connections served at the same time, further connections wait until
+        /// some of the existing ones are closed
+        #[clap(long, default_value = "16")]
+        max_connections: NonZeroUsize,
+    },
+    /// Convert plot of the previous format into a new plot in a different directory, reusing its
+    /// pieces instead of retrieving them from the network. Pieces are verified and those that fail
+    /// verification are retrieved from the network. Legacy plot is only read and never modified,
+    /// interrupted conversion continues where it stopped when started again. Exits once the new
+    /// plot is fully plotted
+    Convert {
+        /// Directory of the legacy plot with `plot.bin` and `plot-offset-to-index.bin` files
+        #[clap(long, value_hint = ValueHint::DirPath)]
+        legacy_plot: PathBuf,
+        #[clap(flatten)]
+        farming_args: FarmingArgs,
+    },
+    /// Move plot to a different directory, usually on a different disk. Destination plot file is
+    /// preallocated, every sector is read back after copying and compared with the source.
+    /// Farmer must not be running on the plot during migration, source is never modified and
+    /// interrupted migration continues where it stopped when started again
+    Migrate {
+        /// Directory of the single disk farm to migrate
+        #[clap(value_hint = ValueHint::DirPath)]
+        source: PathBuf,
+        /// Directory to migrate single disk farm to
+        #[clap(value_hint = ValueHint::DirPath)]
+        destination: PathBuf,
+    },
+    /// Plot a few real sectors on a disk, measure audit and read latencies and extrapolate them to
+    /// the plot size, along with expected number of solutions. Report is printed to stdout as JSON
+    /// and human readable summary is printed to stderr
+    Simulate(SimulationArgs),
     // TODO: Update or remove
     // /// Benchmark disk in order to see a throughput of the disk for plotting
     // Bench {
@@ -110,6 +453,8 @@ struct DiskFarm {
     directory: PathBuf,
     /// How much space in bytes can farm use for plots (metadata space is not included)
     allocated_plotting_space: u64,
+    /// Where sector data and sector metadata are stored
+    plot_layout: PlotLayout,
 }
 
 impl FromStr for DiskFarm {
@@ -117,12 +462,16 @@ impl FromStr for DiskFarm {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parts = s.split(',').collect::<Vec<_>>();
-        if parts.len() != 3 {
-            return Err("Must contain 3 coma-separated components".to_string());
+        if !(2..=6).contains(&parts.len()) {
+            return Err("Must contain 2 to 6 coma-separated components".to_string());
         }
 
         let mut plot_directory = None;
         let mut allocated_plotting_space = None;
+        let mut metadata_directory = None;
+        let mut overlay_directory = None;
+        let mut sector_alignment = None;
+        let mut plot_shard_size = None;
 
         for part in parts {
             let part = part.splitn(2, '=').collect::<Vec<_>>();
@@ -151,9 +500,48 @@ impl FromStr for DiskFarm {
                             .as_u64(),
                     );
                 }
+                "metadata" => {
+                    metadata_directory.replace(PathBuf::try_from(value).map_err(|error| {
+                        format!("Failed to parse `metadata` \"{value}\": {error}")
+                    })?);
+                }
+                "overlay" => {
+                    overlay_directory.replace(PathBuf::try_from(value).map_err(|error| {
+                        format!("Failed to parse `overlay` \"{value}\": {error}")
+                    })?);
+                }
+                "alignment" => {
+                    sector_alignment.replace(
+                        value
+                            .parse::<ByteSize>()
+                            .ok()
+                            .and_then(|alignment| NonZeroU64::new(alignment.as_u64()))
+                            .filter(|alignment| alignment.is_power_of_two())
+                            .ok_or_else(|| {
+                                format!(
+                                    "Failed to parse `alignment` \"{value}\", must be a power \
+                                    of two"
+                                )
+                            })?,
+                    );
+                }
+                "shard" => {
+                    plot_shard_size.replace(
+                        value
+                            .parse::<ByteSize>()
+                            .ok()
+                            .and_then(|shard_size| NonZeroU64::new(shard_size.as_u64()))
+                            .ok_or_else(|| {
+                                format!(
+                                    "Failed to parse `shard` \"{value}\", must be a non-zero size"
+                                )
+                            })?,
+                    );
+                }
                 key => {
                     return Err(format!(
-                        "Key \"{key}\" is not supported, only `path` or `size`"
+                        "Key \"{key}\" is not supported, only `path`, `size`, `metadata`, \
+                        `overlay`, `alignment` or `shard`"
                     ));
                 }
             }
@@ -166,6 +554,124 @@ impl FromStr for DiskFarm {
             allocated_plotting_space: allocated_plotting_space.ok_or({
                 "`size` key is required with path to directory where plots will be stored"
             })?,
+            plot_layout: PlotLayout {
+                metadata_directory,
+                overlay_directory,
+                sector_alignment: sector_alignment
+                    .unwrap_or_else(|| PlotLayout::default().sector_alignment),
+                plot_shard_size,
+            },
+        })
+    }
+}
+
+/// TLS configuration of connection to plot server
+#[derive(Debug)]
+struct RemotePlotTls {
+    /// PEM file with certificate authorities certificate of plot server is verified against
+    ca_certificates: PathBuf,
+    /// Name certificate of plot server was issued for
+    server_name: String,
+    /// PEM files with certificate chain and private key presented to plot server
+    client_identity: Option<(PathBuf, PathBuf)>,
+}
+
+/// Plot served by `plot-server` command that is farmed over the network
+#[derive(Debug)]
+struct RemotePlot {
+    /// Address of plot server
+    address: SocketAddr,
+    /// Directory with identity plot was created with
+    identity_directory: Option<PathBuf>,
+    tls: Option<RemotePlotTls>,
+}
+
+impl FromStr for RemotePlot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(',').collect::<Vec<_>>();
+        if !(1..=6).contains(&parts.len()) {
+            return Err("Must contain 1 to 6 coma-separated components".to_string());
+        }
+
+        let mut address = None;
+        let mut identity_directory = None;
+        let mut ca_certificates = None;
+        let mut server_name = None;
+        let mut certificate_chain = None;
+        let mut private_key = None;
+
+        for part in parts {
+            let part = part.splitn(2, '=').collect::<Vec<_>>();
+            if part.len() != 2 {
+                return Err("Each component must contain = separating key from value".to_string());
+            }
+
+            let key = *part.first().expect("Length checked above; qed");
+            let value = *part.get(1).expect("Length checked above; qed");
+
+            match key {
+                "address" => {
+                    address.replace(value.parse::<SocketAddr>().map_err(|error| {
+                        format!("Failed to parse `address` \"{value}\": {error}")
+                    })?);
+                }
+                "identity" => {
+                    identity_directory.replace(PathBuf::from(value));
+                }
+                "tls-ca" => {
+                    ca_certificates.replace(PathBuf::from(value));
+                }
+                "server-name" => {
+                    server_name.replace(value.to_string());
+                }
+                "tls-certificate" => {
+                    certificate_chain.replace(PathBuf::from(value));
+                }
+                "tls-private-key" => {
+                    private_key.replace(PathBuf::from(value));
+                }
+                key => {
+                    return Err(format!(
+                        "Key \"{key}\" is not supported, only `address`, `identity`, `tls-ca`, \
+                        `server-name`, `tls-certificate` or `tls-private-key`"
+                    ));
+                }
+            }
+        }
+
+        let client_identity = match (certificate_chain, private_key) {
+            (Some(certificate_chain), Some(private_key)) => Some((certificate_chain, private_key)),
+            (None, None) => None,
+            _ => {
+                return Err(
+                    "`tls-certificate` and `tls-private-key` keys must be used together"
+                        .to_string(),
+                );
+            }
+        };
+        let tls = match (ca_certificates, server_name) {
+            (Some(ca_certificates), Some(server_name)) => Some(RemotePlotTls {
+                ca_certificates,
+                server_name,
+                client_identity,
+            }),
+            (None, None) => {
+                if client_identity.is_some() {
+                    return Err("`tls-certificate` key requires `tls-ca` key".to_string());
+                }
+                None
+            }
+            _ => {
+                return Err("`tls-ca` and `server-name` keys must be used together".to_string());
+            }
+        };
+
+        Ok(RemotePlot {
+            address: address.ok_or("`address` key is required with address of plot server")?,
+            identity_directory,
+            tls,
         })
     }
 }
@@ -175,12 +681,12 @@ impl FromStr for DiskFarm {
 struct Command {
     #[clap(subcommand)]
     subcommand: Subcommand,
-    /// Base path for data storage instead of platform-specific default
+    /// Base path for data storage instead of platform-specific default, when `--farm` is used only
+    /// data shared by all plots (like root blocks) is stored there
     #[clap(
         long,
         default_value_os_t = utils::default_base_path(),
         value_hint = ValueHint::FilePath,
-        conflicts_with = "farm",
         conflicts_with = "tmp"
     )]
     base_path: PathBuf,
@@ -192,6 +698,17 @@ struct Command {
     ///   path=/path/to/directory,size=5T
     ///
     /// `size` is max plot size in human readable format (e.g. 10GB, 2TiB) or just bytes.
+    /// Optional `metadata=/path/to/metadata/directory` stores compact sector metadata in a separate
+    /// directory (for instance on SSD), while sector data stays in `path`.
+    /// Optional `overlay=/path/to/overlay/directory` farms existing plot in `path` as read-only
+    /// snapshot (for instance ZFS or LVM snapshot that is being backed up), while metadata updates
+    /// and newly plotted sectors are written to overlay directory.
+    /// Optional `alignment=4KiB` makes sectors of new plots start at offsets that are multiples of
+    /// specified power of two, which may improve performance of some SSDs, existing plots keep
+    /// their alignment.
+    /// Optional `shard=2GiB` splits plot file of new plots into files of at most specified size for
+    /// file systems that limit the size of a single file (for instance FAT32), existing plots keep
+    /// their layout.
     /// TODO: Update overhead number here or account for it automatically
     /// Note that `size` is how much data will be plotted, you also need to account for metadata,
     /// which right now occupies up to 8% of the disk space.
@@ -201,22 +718,163 @@ struct Command {
     /// will be delete at the end of the process
     #[clap(long, conflicts_with = "base-path", conflicts_with = "farm")]
     tmp: bool,
+    /// Log filter directives in the same format as `RUST_LOG` environment variable (e.g.
+    /// `info,subspace_farmer::single_disk_plot=debug`), takes precedence over the environment
+    /// variable
+    #[clap(long, global = true)]
+    log: Option<String>,
+    /// Format of log output, `json` emits one JSON object per event with fields of all spans it
+    /// belongs to
+    #[clap(arg_enum, long, global = true, default_value = "text")]
+    log_format: LogFormat,
+    /// Write logs into files in this directory in addition to standard output, files are rotated
+    /// once they reach `--log-max-file-size`
+    #[clap(long, global = true, value_hint = ValueHint::DirPath)]
+    log_dir: Option<PathBuf>,
+    /// Maximum size of a single log file in `--log-dir` in human readable format (e.g. 100MiB) or
+    /// just bytes
+    #[clap(long, global = true, default_value = "100MiB")]
+    log_max_file_size: ByteSize,
+    /// Number of log files to keep in `--log-dir`, including the current one
+    #[clap(long, global = true, default_value = "10")]
+    log_max_files: NonZeroUsize,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn init_logging(command: &Command) -> Result<()> {
+    let env_filter = match &command.log {
+        Some(log) => EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .parse(log)?,
+        None => EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .from_env_lossy(),
+    };
+    let log_format = command.log_format;
+
+    let stdout_layer = match log_format {
+        LogFormat::Text => fmt::layer().with_span_events(FmtSpan::CLOSE).boxed(),
+        LogFormat::Json => fmt::layer().json().with_span_events(FmtSpan::CLOSE).boxed(),
+    };
+
+    let file_layer = command
+        .log_dir
+        .as_ref()
+        .map(|log_dir| {
+            let log_file = RotatingLogFile::open(
+                log_dir,
+                LOG_FILE_NAME,
+                command.log_max_file_size.as_u64(),
+                command.log_max_files,
+            )?;
+            let make_writer = move || log_file.clone();
+
+            io::Result::Ok(match log_format {
+                LogFormat::Text => fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(make_writer)
+                    .with_span_events(FmtSpan::CLOSE)
+                    .boxed(),
+                LogFormat::Json => fmt::layer()
+                    .json()
+                    .with_writer(make_writer)
+                    .with_span_events(FmtSpan::CLOSE)
+                    .boxed(),
+            })
+        })
+        .transpose()?;
+
     tracing_subscriber::registry()
-        .with(
-            fmt::layer().with_span_events(FmtSpan::CLOSE).with_filter(
-                EnvFilter::builder()
-                    .with_default_directive(LevelFilter::INFO.into())
-                    .from_env_lossy(),
-            ),
-        )
+        .with(env_filter)
+        .with(stdout_layer)
+        .with(file_layer)
         .init();
-    utils::raise_fd_limit();
 
-    let command = Command::parse();
+    Ok(())
+}
+
+/// Disk farms to plot, either explicitly specified or a single one in `base_path`
+fn disk_farms_for_plotting(
+    base_path: PathBuf,
+    farms: Vec<DiskFarm>,
+    farming_args: &FarmingArgs,
+) -> Result<Vec<DiskFarm>> {
+    let disk_farms = if farms.is_empty() {
+        if !base_path.exists() {
+            fs::create_dir_all(&base_path).unwrap_or_else(|error| {
+                panic!(
+                    "Failed to create data directory {:?}: {:?}",
+                    base_path, error
+                )
+            });
+        }
+
+        let plot_size = farming_args.plot_size.as_u64();
+
+        if plot_size < 1024 * 1024 {
+            return Err(ConfigError(format!(
+                "Plot size is too low ({0} bytes). Did you mean {0}G or {0}T?",
+                plot_size
+            ))
+            .into());
+        }
+
+        vec![DiskFarm {
+            directory: base_path,
+            allocated_plotting_space: get_usable_plot_space(plot_size),
+            plot_layout: PlotLayout::default(),
+        }]
+    } else {
+        for farm in &farms {
+            if !farm.directory.exists() {
+                return Err(ConfigError(format!(
+                    "Directory {} doesn't exist",
+                    farm.directory.display()
+                ))
+                .into());
+            }
+        }
+
+        farms
+    };
+
+    Ok(disk_farms)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let exit_codes_help = exit_code::exit_codes_help();
+    let command = match Command::command()
+        .after_help(exit_codes_help.as_str())
+        .try_get_matches()
+        .and_then(|matches| Command::from_arg_matches(&matches))
+    {
+        Ok(command) => command,
+        Err(error) => {
+            // Help and version are printed to stdout and exit successfully
+            if !error.use_stderr() {
+                error.exit();
+            }
+
+            let _ = error.print();
+            let error = anyhow::Error::from(error);
+            eprintln!(
+                "{}",
+                exit_code::error_summary(FatalErrorKind::Config, &error)
+            );
+
+            return ExitCode::from(FatalErrorKind::Config.entry().code);
+        }
+    };
+
+    match run(command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => exit_code::report_fatal_error(&error),
+    }
+}
+
+async fn run(command: Command) -> Result<()> {
+    init_logging(&command)?;
+    utils::raise_fd_limit();
 
     let (base_path, _tmp_directory) = if command.tmp {
         let tmp_directory = TempDir::new()?;
@@ -227,6 +885,10 @@ async fn main() -> Result<()> {
 
     match command.subcommand {
         Subcommand::Wipe => {
+            if base_path.exists() {
+                RootBlockStore::wipe(&base_path)?;
+            }
+
             let disk_farms = if command.farm.is_empty() {
                 if !base_path.exists() {
                     info!("Done");
@@ -239,11 +901,16 @@ async fn main() -> Result<()> {
                 vec![DiskFarm {
                     directory: base_path,
                     allocated_plotting_space: get_usable_plot_space(0),
+                    plot_layout: PlotLayout::default(),
                 }]
             } else {
                 for farm in &command.farm {
                     if !farm.directory.exists() {
-                        panic!("Directory {} doesn't exist", farm.directory.display());
+                        return Err(ConfigError(format!(
+                            "Directory {} doesn't exist",
+                            farm.directory.display()
+                        ))
+                        .into());
                     }
                 }
 
@@ -251,58 +918,69 @@ async fn main() -> Result<()> {
             };
 
             for farm in &disk_farms {
-                SingleDiskPlot::wipe(&farm.directory)?;
+                SingleDiskPlot::wipe(&farm.directory, &farm.plot_layout)?;
+                // Earlier versions stored root blocks alongside the first plot
+                RootBlockStore::wipe(&farm.directory)?;
             }
 
             info!("Done");
         }
         Subcommand::Farm(farming_args) => {
-            let disk_farms = if command.farm.is_empty() {
-                if !base_path.exists() {
-                    fs::create_dir_all(&base_path).unwrap_or_else(|error| {
-                        panic!(
-                            "Failed to create data directory {:?}: {:?}",
-                            base_path, error
-                        )
-                    });
-                }
-
-                let plot_size = farming_args.plot_size.as_u64();
-
-                if plot_size < 1024 * 1024 {
-                    return Err(anyhow::anyhow!(
-                        "Plot size is too low ({0} bytes). Did you mean {0}G or {0}T?",
-                        plot_size
-                    ));
-                }
+            let disk_farms =
+                disk_farms_for_plotting(base_path.clone(), command.farm, &farming_args)?;
 
-                vec![DiskFarm {
-                    directory: base_path,
-                    allocated_plotting_space: get_usable_plot_space(plot_size),
-                }]
-            } else {
-                for farm in &command.farm {
-                    if !farm.directory.exists() {
-                        panic!("Directory {} doesn't exist", farm.directory.display());
-                    }
-                }
-
-                command.farm
-            };
+            commands::farm_multi_disk(&base_path, disk_farms, farming_args).await?;
+        }
+        Subcommand::Convert {
+            legacy_plot,
+            farming_args,
+        } => {
+            let disk_farms =
+                disk_farms_for_plotting(base_path.clone(), command.farm, &farming_args)?;
 
-            commands::farm_multi_disk(disk_farms, farming_args).await?;
+            commands::convert(&base_path, disk_farms, &legacy_plot, farming_args).await?;
         }
         Subcommand::Info => {
             let disk_farms = if command.farm.is_empty() {
                 vec![DiskFarm {
                     directory: base_path,
                     allocated_plotting_space: get_usable_plot_space(0),
+                    plot_layout: PlotLayout::default(),
                 }]
             } else {
                 command.farm
             };
 
             commands::info(disk_farms);
+        }
+        Subcommand::ReplayAudit { file } => {
+            commands::replay_audit(&file)?;
+        }
+        Subcommand::PlotServer {
+            directory,
+            listen_on,
+            tls_certificate,
+            tls_private_key,
+            tls_client_ca,
+            max_connections,
+        } => {
+            commands::plot_server(
+                directory,
+                listen_on,
+                tls_certificate.zip(tls_private_key),
+                tls_client_ca,
+                max_connections,
+            )
+            .await?;
+        }
+        Subcommand::Migrate {
+            source,
+            destination,
+        } => {
+            commands::migrate(source, destination).await?;
+        }
+        Subcommand::Simulate(simulation_args) => {
+            commands::simulate(simulation_args).await?;
         } // TODO: Update or remove
           // Subcommand::Bench {
           //     plot_size,
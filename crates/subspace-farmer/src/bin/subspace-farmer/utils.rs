@@ -1,5 +1,7 @@
+use crate::exit_code::ConfigError;
 use std::path::PathBuf;
 use tokio::signal;
+use zeroize::Zeroizing;
 
 pub(crate) fn default_base_path() -> PathBuf {
     dirs::data_local_dir()
@@ -26,6 +28,46 @@ pub(crate) fn raise_fd_limit() {
     }
 }
 
+/// Read mnemonic of identity from file descriptor `mnemonic_fd` or prompt for it in terminal
+/// without echoing it
+pub(crate) fn read_mnemonic(mnemonic_fd: Option<i32>) -> anyhow::Result<Zeroizing<String>> {
+    let mnemonic = match mnemonic_fd {
+        #[cfg(unix)]
+        Some(mnemonic_fd) => {
+            use std::io::Read;
+            use std::os::unix::io::FromRawFd;
+
+            if mnemonic_fd < 0 {
+                return Err(ConfigError(format!("Invalid file descriptor {mnemonic_fd}")).into());
+            }
+
+            // SAFETY: File descriptor is provided by the user specifically for this purpose and
+            // not used anywhere else, it is closed once mnemonic is read
+            let mut file = unsafe { std::fs::File::from_raw_fd(mnemonic_fd) };
+            let mut mnemonic = Zeroizing::new(String::new());
+            file.read_to_string(&mut mnemonic).map_err(|error| {
+                ConfigError(format!(
+                    "Failed to read mnemonic from file descriptor {mnemonic_fd}: {error}"
+                ))
+            })?;
+            mnemonic
+        }
+        #[cfg(not(unix))]
+        Some(_mnemonic_fd) => {
+            return Err(ConfigError(
+                "Reading mnemonic from file descriptor is only supported on Unix".to_string(),
+            )
+            .into());
+        }
+        None => Zeroizing::new(
+            rpassword::prompt_password("Mnemonic of farmer identity: ")
+                .map_err(|error| ConfigError(format!("Failed to read mnemonic: {error}")))?,
+        ),
+    };
+
+    Ok(mnemonic)
+}
+
 pub(crate) fn get_usable_plot_space(allocated_space: u64) -> u64 {
     // TODO: Should account for database overhead of various additional databases.
     //  For now assume 92% will go for plot itself
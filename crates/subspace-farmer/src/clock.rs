@@ -0,0 +1,121 @@
+//! Source of time for deadlines and backoffs.
+//!
+//! Code that waits for deadlines takes a [`Clock`] instead of using timers directly, so that tests
+//! can control time with [`TestClock`] deterministically.
+
+#[cfg(test)]
+mod tests;
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// Source of time
+#[async_trait]
+pub trait Clock: Send + Sync {
+    /// Current time
+    fn now(&self) -> Instant;
+
+    /// Wait until `deadline`, returns immediately if deadline has passed already
+    async fn sleep_until(&self, deadline: Instant);
+}
+
+/// Clock backed by Tokio timers, respects paused time in tests
+#[derive(Debug, Default, Copy, Clone)]
+pub struct TokioClock;
+
+#[async_trait]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    async fn sleep_until(&self, deadline: Instant) {
+        tokio::time::sleep_until(deadline.into()).await;
+    }
+}
+
+#[derive(Debug)]
+struct TestClockInner {
+    now: Instant,
+    sleepers: Vec<(Instant, oneshot::Sender<()>)>,
+}
+
+/// Clock that only moves forward when advanced manually
+#[derive(Debug, Clone)]
+pub struct TestClock {
+    inner: Arc<Mutex<TestClockInner>>,
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Clock for TestClock {
+    fn now(&self) -> Instant {
+        self.inner.lock().now
+    }
+
+    async fn sleep_until(&self, deadline: Instant) {
+        let receiver = {
+            let mut inner = self.inner.lock();
+            if deadline <= inner.now {
+                return;
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            inner.sleepers.push((deadline, sender));
+            receiver
+        };
+
+        // Sender is only dropped together with the clock, in which case deadline will never come
+        if receiver.await.is_err() {
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+impl TestClock {
+    /// Create clock starting at current time
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(TestClockInner {
+                now: Instant::now(),
+                sleepers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Move time forward by `duration` and wake up everyone whose deadline has come
+    pub fn advance(&self, duration: Duration) {
+        let mut inner = self.inner.lock();
+        inner.now += duration;
+
+        let now = inner.now;
+        for (_deadline, sender) in inner
+            .sleepers
+            .drain_filter(|(deadline, _sender)| *deadline <= now)
+        {
+            // Sleeper might have been cancelled already, which is fine
+            let _ = sender.send(());
+        }
+    }
+
+    /// The earliest deadline somebody is waiting for
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let mut inner = self.inner.lock();
+        inner
+            .sleepers
+            .retain(|(_deadline, sender)| !sender.is_closed());
+        inner
+            .sleepers
+            .iter()
+            .map(|(deadline, _sender)| *deadline)
+            .min()
+    }
+}
@@ -0,0 +1,34 @@
+use crate::clock::{Clock, TestClock};
+use futures::FutureExt;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_clock() {
+    let clock = TestClock::new();
+    let start = clock.now();
+
+    // Deadlines in the past don't wait
+    clock.sleep_until(start).await;
+    assert_eq!(clock.next_deadline(), None);
+
+    let deadline = start + Duration::from_secs(10);
+    let mut sleep = Box::pin(clock.sleep_until(deadline));
+    assert!((&mut sleep).now_or_never().is_none());
+    assert_eq!(clock.next_deadline(), Some(deadline));
+
+    // Not there yet
+    clock.advance(Duration::from_secs(9));
+    assert!((&mut sleep).now_or_never().is_none());
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(clock.now(), deadline);
+    assert!((&mut sleep).now_or_never().is_some());
+    assert_eq!(clock.next_deadline(), None);
+
+    // Cancelled sleepers are forgotten
+    let mut sleep = Box::pin(clock.sleep_until(deadline + Duration::from_secs(1)));
+    assert!((&mut sleep).now_or_never().is_none());
+    assert!(clock.next_deadline().is_some());
+    drop(sleep);
+    assert_eq!(clock.next_deadline(), None);
+}
@@ -1,5 +1,10 @@
+#[cfg(test)]
+mod tests;
+
+use async_trait::async_trait;
 use std::fs::File;
 use std::io::Result;
+use tokio::task::spawn_blocking;
 
 pub trait FileExt {
     /// Make sure file has specified number of bytes allocated for it
@@ -9,6 +14,11 @@ pub trait FileExt {
     /// undesirable
     fn advise_random_access(&self) -> Result<()>;
 
+    /// Ask OS to drop cached pages of the file in the range `offset..offset + len` (`len` of `0`
+    /// means until the end of the file), only clean pages are dropped, so the file should be
+    /// synced first if it was written to. Does nothing on platforms where this is not supported.
+    fn drop_cache(&self, offset: u64, len: u64) -> Result<()>;
+
     /// Read exact number of bytes at a specific offset
     fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()>;
 
@@ -48,6 +58,30 @@ impl FileExt for File {
         Ok(())
     }
 
+    #[cfg(target_os = "linux")]
+    fn drop_cache(&self, offset: u64, len: u64) -> Result<()> {
+        use std::os::unix::io::AsRawFd;
+        let err = unsafe {
+            libc::posix_fadvise(
+                self.as_raw_fd(),
+                offset as libc::off_t,
+                len as libc::off_t,
+                libc::POSIX_FADV_DONTNEED,
+            )
+        };
+        if err != 0 {
+            Err(std::io::Error::from_raw_os_error(err))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn drop_cache(&self, _offset: u64, _len: u64) -> Result<()> {
+        // Not supported
+        Ok(())
+    }
+
     #[cfg(unix)]
     fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
         std::os::unix::fs::FileExt::read_exact_at(self, buf, offset)
@@ -114,3 +148,28 @@ impl FileExt for File {
         Ok(())
     }
 }
+
+/// Async version of [`FileExt`] for use within tokio runtime, potentially slow operations are
+/// offloaded to blocking thread pool, so they don't stall the runtime
+#[async_trait]
+pub trait AsyncFileExt {
+    /// Make sure file has specified number of bytes allocated for it
+    async fn preallocate(&self, len: u64) -> Result<()>;
+
+    /// Advise OS/file system that file will use random access and read-ahead behavior is
+    /// undesirable
+    async fn advise_random_access(&self) -> Result<()>;
+}
+
+#[async_trait]
+impl AsyncFileExt for tokio::fs::File {
+    async fn preallocate(&self, len: u64) -> Result<()> {
+        let file = self.try_clone().await?.into_std().await;
+        spawn_blocking(move || FileExt::preallocate(&file, len)).await?
+    }
+
+    async fn advise_random_access(&self) -> Result<()> {
+        let file = self.try_clone().await?.into_std().await;
+        spawn_blocking(move || FileExt::advise_random_access(&file)).await?
+    }
+}
@@ -0,0 +1,49 @@
+use crate::file_ext::{AsyncFileExt, FileExt};
+use std::fs;
+use tempfile::TempDir;
+use tokio::fs::OpenOptions;
+
+#[tokio::test]
+async fn async_preallocate() {
+    let directory = TempDir::new().unwrap();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(directory.path().join("file.bin"))
+        .await
+        .unwrap();
+
+    let len = 10 * 1024 * 1024;
+    file.preallocate(len).await.unwrap();
+    assert_eq!(file.metadata().await.unwrap().len(), len);
+
+    // Preallocation never shrinks the file
+    file.preallocate(len / 2).await.unwrap();
+    assert_eq!(file.metadata().await.unwrap().len(), len);
+
+    file.advise_random_access().await.unwrap();
+}
+
+#[test]
+fn drop_cache() {
+    let directory = TempDir::new().unwrap();
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(directory.path().join("file.bin"))
+        .unwrap();
+
+    let data = (0..=u8::MAX).cycle().take(1024 * 1024).collect::<Vec<_>>();
+    file.write_all_at(&data, 0).unwrap();
+    file.sync_all().unwrap();
+
+    // Both a range and the whole file can be dropped, contents must stay intact afterwards
+    file.drop_cache(4096, 8192).unwrap();
+    file.drop_cache(0, 0).unwrap();
+
+    let mut read_back = vec![0; data.len()];
+    file.read_exact_at(&mut read_back, 0).unwrap();
+    assert_eq!(read_back, data);
+}
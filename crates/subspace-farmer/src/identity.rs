@@ -1,10 +1,18 @@
-use anyhow::Error;
+#[cfg(test)]
+mod tests;
+
+use anyhow::{anyhow, Error};
+use bip39::{Language, Mnemonic};
+use blake2_rfc::blake2b::blake2b;
 use parity_scale_codec::{Decode, Encode};
 use schnorrkel::context::SigningContext;
+use schnorrkel::derive::{ChainCode, Derivation, CHAIN_CODE_LENGTH};
 use schnorrkel::{ExpansionMode, Keypair, PublicKey, SecretKey, Signature};
-use std::fs;
+use serde::{Deserialize, Serialize};
 use std::ops::Deref;
 use std::path::Path;
+use std::str::FromStr;
+use std::{fmt, fs, io};
 use subspace_core_primitives::{Chunk, ChunkSignature};
 use subspace_solving::{create_chunk_signature, REWARD_SIGNING_CONTEXT};
 use substrate_bip39::mini_secret_from_entropy;
@@ -25,6 +33,152 @@ fn keypair_from_entropy(entropy: &[u8]) -> Keypair {
         .expand_to_keypair(ExpansionMode::Ed25519)
 }
 
+/// Single step of [`DerivationPath`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum DeriveJunction {
+    /// Soft (non-hardened) derivation, public key can be derived from parent public key
+    Soft([u8; CHAIN_CODE_LENGTH]),
+    /// Hard derivation, derived key can't be linked to parent key without secret
+    Hard([u8; CHAIN_CODE_LENGTH]),
+}
+
+impl DeriveJunction {
+    /// Junction with chain code of SCALE-encoded numeric or string `code`, the same way as in
+    /// Substrate, such that paths result in the same keys as with `subkey` and wallets
+    fn new(code: &str, hard: bool) -> Self {
+        let encoded = match code.parse::<u64>() {
+            Ok(index) => index.encode(),
+            Err(_error) => code.encode(),
+        };
+
+        let mut chain_code = [0; CHAIN_CODE_LENGTH];
+        if encoded.len() > CHAIN_CODE_LENGTH {
+            chain_code.copy_from_slice(blake2b(CHAIN_CODE_LENGTH, &[], &encoded).as_bytes());
+        } else {
+            chain_code[..encoded.len()].copy_from_slice(&encoded);
+        }
+
+        if hard {
+            Self::Hard(chain_code)
+        } else {
+            Self::Soft(chain_code)
+        }
+    }
+}
+
+/// Error of parsing [`DerivationPath`]
+#[derive(Debug, thiserror::Error)]
+pub enum DerivationPathError {
+    /// Path doesn't start with `/`
+    #[error("Derivation path must be empty or start with `/`")]
+    MissingSeparator,
+    /// Path contains empty junction, which includes password after `///`
+    #[error("Derivation path contains empty junction, passwords (`///`) are not supported")]
+    EmptyJunction,
+}
+
+/// Derivation path of identity derived from a BIP39 mnemonic in Substrate format, `//` precedes
+/// hard junctions and `/` soft junctions (for instance `//subspace//farmer/0`), empty path uses
+/// the key of the mnemonic itself
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DerivationPath {
+    path: String,
+    junctions: Vec<DeriveJunction>,
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.path)
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = DerivationPathError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let mut junctions = Vec::new();
+        let mut remaining = path;
+        while !remaining.is_empty() {
+            let junction = remaining
+                .strip_prefix('/')
+                .ok_or(DerivationPathError::MissingSeparator)?;
+            let (junction, hard) = match junction.strip_prefix('/') {
+                Some(junction) => (junction, true),
+                None => (junction, false),
+            };
+            let (code, rest) = junction.split_at(junction.find('/').unwrap_or(junction.len()));
+            if code.is_empty() {
+                return Err(DerivationPathError::EmptyJunction);
+            }
+
+            junctions.push(DeriveJunction::new(code, hard));
+            remaining = rest;
+        }
+
+        Ok(Self {
+            path: path.to_string(),
+            junctions,
+        })
+    }
+}
+
+impl TryFrom<String> for DerivationPath {
+    type Error = DerivationPathError;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        path.parse()
+    }
+}
+
+impl From<DerivationPath> for String {
+    fn from(derivation_path: DerivationPath) -> Self {
+        derivation_path.path
+    }
+}
+
+/// How identity derived from a mnemonic was derived, stored in plot directory instead of the
+/// secret, so that the same identity is required when plot is opened again.
+///
+/// Stored on disk as JSON.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IdentityDerivation {
+    /// V0 of the derivation
+    #[serde(rename_all = "camelCase")]
+    V0 {
+        /// Public key of derived identity
+        public_key: subspace_core_primitives::PublicKey,
+        /// Derivation path identity was derived with from the mnemonic
+        derivation_path: DerivationPath,
+    },
+}
+
+impl IdentityDerivation {
+    /// Public key of derived identity
+    pub fn public_key(&self) -> &subspace_core_primitives::PublicKey {
+        let Self::V0 { public_key, .. } = self;
+        public_key
+    }
+
+    /// Derivation path identity was derived with from the mnemonic
+    pub fn derivation_path(&self) -> &DerivationPath {
+        let Self::V0 {
+            derivation_path, ..
+        } = self;
+        derivation_path
+    }
+}
+
+/// Where secret of the identity comes from
+#[derive(Clone)]
+enum IdentitySecret {
+    /// Random entropy stored in plot directory
+    Entropy(Zeroizing<Vec<u8>>),
+    /// Mnemonic that is provided every time identity is opened, only derivation is stored
+    Mnemonic(IdentityDerivation),
+}
+
 /// `Identity` struct is an abstraction of public & secret key related operations.
 ///
 /// It is basically a wrapper of the keypair (which holds public & secret keys)
@@ -32,7 +186,7 @@ fn keypair_from_entropy(entropy: &[u8]) -> Keypair {
 #[derive(Clone)]
 pub struct Identity {
     keypair: Zeroizing<Keypair>,
-    entropy: Zeroizing<Vec<u8>>,
+    secret: IdentitySecret,
     substrate_ctx: SigningContext,
 }
 
@@ -45,6 +199,12 @@ impl Deref for Identity {
 }
 
 impl Identity {
+    /// File with randomly generated identity
+    pub const FILE_NAME: &'static str = "identity.bin";
+    /// File with derivation of identity derived from a mnemonic, the mnemonic itself is never
+    /// stored
+    pub const DERIVATION_FILE_NAME: &'static str = "identity_derivation.json";
+
     /// Opens the existing identity, or creates a new one.
     pub fn open_or_create<B: AsRef<Path>>(base_directory: B) -> Result<Self, Error> {
         if let Some(identity) = Self::open(base_directory.as_ref())? {
@@ -55,9 +215,33 @@ impl Identity {
     }
 
     /// Opens the existing identity, returns `Ok(None)` if it doesn't exist.
+    ///
+    /// Identity derived from a mnemonic can't be opened without the mnemonic, use
+    /// [`Identity::open_with()`] for it.
     pub fn open<B: AsRef<Path>>(base_directory: B) -> Result<Option<Self>, Error> {
-        let identity_file = base_directory.as_ref().join("identity.bin");
+        Self::open_with(base_directory, None)
+    }
+
+    /// Opens the existing identity like [`Identity::open()`], but expects it to be `derived` from
+    /// a mnemonic with [`Identity::from_mnemonic()`] if provided.
+    ///
+    /// Returns `Ok(None)` if identity doesn't exist, in which case `derived` should be stored,
+    /// fails if existing identity is not the same as `derived`.
+    pub fn open_with<B: AsRef<Path>>(
+        base_directory: B,
+        derived: Option<&Self>,
+    ) -> Result<Option<Self>, Error> {
+        let identity_file = base_directory.as_ref().join(Self::FILE_NAME);
+        let derivation_file = base_directory.as_ref().join(Self::DERIVATION_FILE_NAME);
+
         if identity_file.exists() {
+            if derived.is_some() {
+                return Err(anyhow!(
+                    "Identity in {} was generated randomly and can't be derived from mnemonic",
+                    identity_file.display()
+                ));
+            }
+
             debug!("Opening existing keypair");
             let bytes = Zeroizing::new(fs::read(identity_file)?);
             let IdentityFileContents { entropy } =
@@ -65,9 +249,39 @@ impl Identity {
 
             Ok(Some(Self {
                 keypair: Zeroizing::new(keypair_from_entropy(&entropy)),
-                entropy: Zeroizing::new(entropy),
+                secret: IdentitySecret::Entropy(Zeroizing::new(entropy)),
                 substrate_ctx: schnorrkel::context::signing_context(REWARD_SIGNING_CONTEXT),
             }))
+        } else if derivation_file.exists() {
+            let derivation =
+                serde_json::from_slice::<IdentityDerivation>(&fs::read(&derivation_file)?)?;
+
+            let derived = derived.ok_or_else(|| {
+                anyhow!(
+                    "Identity {} is derived from mnemonic with derivation path \"{}\", mnemonic \
+                    must be provided to open it",
+                    derivation.public_key(),
+                    derivation.derivation_path()
+                )
+            })?;
+            let derived_derivation = derived
+                .derivation()
+                .ok_or_else(|| anyhow!("Provided identity is not derived from mnemonic"))?;
+            if derived_derivation != &derivation {
+                return Err(anyhow!(
+                    "Identity {} derived with derivation path \"{}\" doesn't match identity {} \
+                    derived with derivation path \"{}\" in {}, wrong mnemonic or derivation path \
+                    was provided",
+                    derived_derivation.public_key(),
+                    derived_derivation.derivation_path(),
+                    derivation.public_key(),
+                    derivation.derivation_path(),
+                    derivation_file.display()
+                ));
+            }
+
+            debug!("Opening existing keypair derived from mnemonic");
+            Ok(Some(derived.clone()))
         } else {
             debug!("Existing keypair not found");
             Ok(None)
@@ -76,22 +290,99 @@ impl Identity {
 
     /// Creates new identity, overrides identity that might already exist.
     pub fn create<B: AsRef<Path>>(base_directory: B) -> Result<Self, Error> {
-        let identity_file = base_directory.as_ref().join("identity.bin");
+        let identity = Self::generate();
+        identity.store(base_directory)?;
+
+        Ok(identity)
+    }
+
+    /// Generates new identity in memory without storing it, use [`Identity::store()`] to persist
+    /// it.
+    pub fn generate() -> Self {
         debug!("Generating new keypair");
         let entropy = rand::random::<[u8; ENTROPY_LENGTH]>().to_vec();
 
-        let identity_file_contents = IdentityFileContents { entropy };
-        fs::write(identity_file, identity_file_contents.encode())?;
+        Self {
+            keypair: Zeroizing::new(keypair_from_entropy(&entropy)),
+            secret: IdentitySecret::Entropy(Zeroizing::new(entropy)),
+            substrate_ctx: schnorrkel::context::signing_context(REWARD_SIGNING_CONTEXT),
+        }
+    }
 
-        let IdentityFileContents { entropy } = identity_file_contents;
+    /// Derives identity from BIP39 `phrase` (English word list) with `derivation_path` in memory
+    /// without storing it.
+    ///
+    /// Derivation is the same as of sr25519 keys in Substrate (`subkey`, wallets), so that the
+    /// same mnemonic and path result in the same public key anywhere. Storing identity derived
+    /// this way only stores its public key and derivation path, never the mnemonic.
+    pub fn from_mnemonic(phrase: &str, derivation_path: &DerivationPath) -> Result<Self, Error> {
+        debug!(%derivation_path, "Deriving keypair from mnemonic");
+        let mnemonic = Mnemonic::from_phrase(phrase.trim(), Language::English)
+            .map_err(|error| anyhow!("Invalid mnemonic: {error}"))?;
+        let mut secret_key = mini_secret_from_entropy(mnemonic.entropy(), "")
+            .map_err(|error| anyhow!("Invalid mnemonic entropy: {error:?}"))?
+            .expand(ExpansionMode::Ed25519);
+
+        for junction in &derivation_path.junctions {
+            secret_key = match junction {
+                DeriveJunction::Soft(chain_code) => {
+                    secret_key.derived_key_simple(ChainCode(*chain_code), b"").0
+                }
+                DeriveJunction::Hard(chain_code) => secret_key
+                    .hard_derive_mini_secret_key(Some(ChainCode(*chain_code)), b"")
+                    .0
+                    .expand(ExpansionMode::Ed25519),
+            };
+        }
+
+        let keypair = Zeroizing::new(secret_key.to_keypair());
+        let derivation = IdentityDerivation::V0 {
+            public_key: keypair.public.to_bytes().into(),
+            derivation_path: derivation_path.clone(),
+        };
 
         Ok(Self {
-            keypair: Zeroizing::new(keypair_from_entropy(&entropy)),
-            entropy: Zeroizing::new(entropy),
+            keypair,
+            secret: IdentitySecret::Mnemonic(derivation),
             substrate_ctx: schnorrkel::context::signing_context(REWARD_SIGNING_CONTEXT),
         })
     }
 
+    /// Stores identity, overrides identity that might already exist.
+    pub fn store<B: AsRef<Path>>(&self, base_directory: B) -> Result<(), Error> {
+        match &self.secret {
+            IdentitySecret::Entropy(entropy) => {
+                let identity_file = base_directory.as_ref().join(Self::FILE_NAME);
+                let identity_file_contents = Zeroizing::new(
+                    IdentityFileContents {
+                        entropy: entropy.to_vec(),
+                    }
+                    .encode(),
+                );
+                fs::write(identity_file, identity_file_contents.as_slice())?;
+            }
+            IdentitySecret::Mnemonic(derivation) => {
+                let derivation_file = base_directory.as_ref().join(Self::DERIVATION_FILE_NAME);
+                fs::write(derivation_file, serde_json::to_vec_pretty(derivation)?)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes identity stored in `base_directory`, whether it was generated or derived
+    pub fn wipe<B: AsRef<Path>>(base_directory: B) -> io::Result<()> {
+        for file_name in [Self::FILE_NAME, Self::DERIVATION_FILE_NAME] {
+            let file = base_directory.as_ref().join(file_name);
+            if file.exists() {
+                debug!("Deleting identity file at {}", file.display());
+                fs::remove_file(file)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Create identity from given entropy, overrides identity that might already exist.
     ///
     /// Primarily used for testing.
@@ -100,7 +391,7 @@ impl Identity {
         base_directory: B,
         entropy: Vec<u8>,
     ) -> Result<Self, Error> {
-        let identity_file = base_directory.as_ref().join("identity.bin");
+        let identity_file = base_directory.as_ref().join(Self::FILE_NAME);
         debug!("Creating identity from provided entropy");
 
         let identity_file_contents = IdentityFileContents { entropy };
@@ -110,7 +401,7 @@ impl Identity {
 
         Ok(Self {
             keypair: Zeroizing::new(keypair_from_entropy(&entropy)),
-            entropy: Zeroizing::new(entropy),
+            secret: IdentitySecret::Entropy(Zeroizing::new(entropy)),
             substrate_ctx: schnorrkel::context::signing_context(REWARD_SIGNING_CONTEXT),
         })
     }
@@ -125,9 +416,20 @@ impl Identity {
         &self.keypair.secret
     }
 
-    /// Returns entropy used to generate keypair.
-    pub fn entropy(&self) -> &[u8] {
-        &self.entropy
+    /// Returns entropy used to generate keypair, `None` for identity derived from a mnemonic.
+    pub fn entropy(&self) -> Option<&[u8]> {
+        match &self.secret {
+            IdentitySecret::Entropy(entropy) => Some(entropy),
+            IdentitySecret::Mnemonic(_derivation) => None,
+        }
+    }
+
+    /// Returns how identity was derived, `None` for randomly generated identity.
+    pub fn derivation(&self) -> Option<&IdentityDerivation> {
+        match &self.secret {
+            IdentitySecret::Entropy(_entropy) => None,
+            IdentitySecret::Mnemonic(derivation) => Some(derivation),
+        }
     }
 
     pub fn create_chunk_signature(&self, chunk: &Chunk) -> ChunkSignature {
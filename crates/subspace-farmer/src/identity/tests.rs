@@ -0,0 +1,183 @@
+use crate::identity::{DerivationPath, DerivationPathError, DeriveJunction, Identity};
+use schnorrkel::derive::{ChainCode, Derivation};
+use std::fs;
+use tempfile::TempDir;
+
+/// Development mnemonic of Substrate, test vectors below match keys `subkey` derives from it
+const DEV_PHRASE: &str = "bottom drive obey lake curtain smoke basket hold race lonely fit walk";
+
+fn derive(derivation_path: &str) -> Identity {
+    Identity::from_mnemonic(DEV_PHRASE, &derivation_path.parse().unwrap()).unwrap()
+}
+
+#[test]
+fn mnemonic_derivation_test_vectors() {
+    for (derivation_path, public_key) in [
+        (
+            "",
+            "46ebddef8cd9bb167dc30878d7113b7e168e6f0646beffd77d69d39bad76b47a",
+        ),
+        (
+            "//Alice",
+            "d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d",
+        ),
+        (
+            "//Bob",
+            "8eaf04151687736326c9fea17e25fc5287613693c912909cb226aa4794f26a48",
+        ),
+        (
+            "//Alice//stash",
+            "be5ddb1579b72e84524fc29e78609e3caf42e85aa118ebfe0b0ad404b5bdd25f",
+        ),
+    ] {
+        assert_eq!(
+            hex::encode(derive(derivation_path).public_key().to_bytes()),
+            public_key,
+            "Derivation path {derivation_path:?}"
+        );
+    }
+}
+
+#[test]
+fn soft_derivation_matches_public_derivation() {
+    let parent = derive("//Alice");
+
+    for code in [
+        "0",
+        "farmer",
+        "a-code-that-is-longer-than-chain-code-length",
+    ] {
+        let chain_code = match DeriveJunction::new(code, false) {
+            DeriveJunction::Soft(chain_code) => chain_code,
+            DeriveJunction::Hard(_chain_code) => unreachable!("Soft junction was created; qed"),
+        };
+        let (public_key, _chain_code) = parent
+            .public_key()
+            .derived_key_simple(ChainCode(chain_code), b"");
+
+        assert_eq!(derive(&format!("//Alice/{code}")).public_key(), &public_key);
+    }
+
+    // Hard derivation can't be done from public key
+    assert_ne!(
+        derive("//Alice/0").public_key(),
+        derive("//Alice//0").public_key()
+    );
+}
+
+#[test]
+fn derivation_path_parsing() {
+    let derivation_path = "//subspace//farmer/0".parse::<DerivationPath>().unwrap();
+    assert_eq!(
+        derivation_path.junctions,
+        vec![
+            DeriveJunction::new("subspace", true),
+            DeriveJunction::new("farmer", true),
+            DeriveJunction::new("0", false),
+        ]
+    );
+    assert_eq!(derivation_path.to_string(), "//subspace//farmer/0");
+    assert_eq!(
+        serde_json::from_str::<DerivationPath>(&serde_json::to_string(&derivation_path).unwrap())
+            .unwrap(),
+        derivation_path
+    );
+
+    // Numeric junctions are encoded as numbers, not strings
+    assert_eq!(
+        DeriveJunction::new("1", true),
+        DeriveJunction::Hard({
+            let mut chain_code = [0; 32];
+            chain_code[0] = 1;
+            chain_code
+        })
+    );
+
+    assert!("".parse::<DerivationPath>().unwrap().junctions.is_empty());
+    assert!(matches!(
+        "Alice".parse::<DerivationPath>(),
+        Err(DerivationPathError::MissingSeparator)
+    ));
+    for derivation_path in ["/", "//", "//Alice/", "//Alice///password"] {
+        assert!(
+            matches!(
+                derivation_path.parse::<DerivationPath>(),
+                Err(DerivationPathError::EmptyJunction)
+            ),
+            "Derivation path {derivation_path:?}"
+        );
+    }
+}
+
+#[test]
+fn invalid_mnemonic() {
+    let derivation_path = DerivationPath::default();
+
+    assert!(Identity::from_mnemonic("", &derivation_path).is_err());
+    assert!(Identity::from_mnemonic(
+        "bottom drive obey lake curtain smoke basket hold race lonely fit fit",
+        &derivation_path
+    )
+    .is_err());
+    // Surrounding whitespace, for instance trailing newline, is ignored
+    assert!(Identity::from_mnemonic(&format!(" {DEV_PHRASE}\n"), &derivation_path).is_ok());
+}
+
+#[test]
+fn derived_identity_storage() {
+    let directory = TempDir::new().unwrap();
+
+    let identity = derive("//farmer");
+    assert!(identity.entropy().is_none());
+    assert!(Identity::open_with(directory.path(), Some(&identity))
+        .unwrap()
+        .is_none());
+    identity.store(directory.path()).unwrap();
+
+    // Only public key and derivation path are stored
+    assert!(!directory.path().join(Identity::FILE_NAME).exists());
+    let contents =
+        fs::read_to_string(directory.path().join(Identity::DERIVATION_FILE_NAME)).unwrap();
+    assert!(contents.contains(&hex::encode(identity.public_key().to_bytes())));
+    assert!(contents.contains("//farmer"));
+    for word in DEV_PHRASE.split(' ') {
+        assert!(!contents.contains(word));
+    }
+
+    // The same mnemonic derived again (for instance on another machine) opens the identity
+    let reopened = Identity::open_with(directory.path(), Some(&derive("//farmer")))
+        .unwrap()
+        .unwrap();
+    assert_eq!(reopened.public_key(), identity.public_key());
+    assert_eq!(
+        reopened.secret_key().to_bytes(),
+        identity.secret_key().to_bytes()
+    );
+
+    // Mnemonic is required and must match
+    assert!(Identity::open(directory.path()).is_err());
+    assert!(Identity::open_with(directory.path(), Some(&derive("//farmer2"))).is_err());
+    assert!(Identity::open_with(directory.path(), Some(&Identity::generate())).is_err());
+
+    Identity::wipe(directory.path()).unwrap();
+    assert!(Identity::open(directory.path()).unwrap().is_none());
+}
+
+#[test]
+fn generated_identity_is_not_replaced_by_derived() {
+    let directory = TempDir::new().unwrap();
+
+    let identity = Identity::create(directory.path()).unwrap();
+    assert!(identity.derivation().is_none());
+    assert!(Identity::open_with(directory.path(), Some(&derive(""))).is_err());
+    assert_eq!(
+        Identity::open(directory.path())
+            .unwrap()
+            .unwrap()
+            .public_key(),
+        identity.public_key()
+    );
+
+    Identity::wipe(directory.path()).unwrap();
+    assert!(!directory.path().join(Identity::FILE_NAME).exists());
+}
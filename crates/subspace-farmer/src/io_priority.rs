@@ -0,0 +1,63 @@
+//! OS-level I/O priority of threads.
+//!
+//! Supported on Linux with `io-priority` feature enabled (and only has effect with I/O schedulers
+//! that respect priorities, like BFQ), no-op otherwise.
+
+#[cfg(test)]
+mod tests;
+
+use std::io;
+
+/// I/O priority class of a thread
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IoPriority {
+    /// Best-effort class with the highest priority, used for audit reads
+    High,
+    /// Idle class, thread only gets disk time when no other thread needs it, used for plotting
+    /// writes
+    Idle,
+}
+
+#[cfg(all(feature = "io-priority", target_os = "linux"))]
+impl IoPriority {
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+    fn ioprio(self) -> libc::c_int {
+        match self {
+            // Level 0 is the highest within the class
+            Self::High => Self::IOPRIO_CLASS_BE << Self::IOPRIO_CLASS_SHIFT,
+            Self::Idle => Self::IOPRIO_CLASS_IDLE << Self::IOPRIO_CLASS_SHIFT,
+        }
+    }
+}
+
+/// Set I/O priority of the current thread, returns `false` if not supported on this platform or
+/// with enabled features
+#[cfg(all(feature = "io-priority", target_os = "linux"))]
+pub fn set_current_thread_io_priority(io_priority: IoPriority) -> io::Result<bool> {
+    // `0` stands for calling thread
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IoPriority::IOPRIO_WHO_PROCESS,
+            0,
+            io_priority.ioprio(),
+        )
+    };
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(true)
+    }
+}
+
+/// Set I/O priority of the current thread, returns `false` if not supported on this platform or
+/// with enabled features
+#[cfg(not(all(feature = "io-priority", target_os = "linux")))]
+pub fn set_current_thread_io_priority(_io_priority: IoPriority) -> io::Result<bool> {
+    // Not supported
+    Ok(false)
+}
@@ -0,0 +1,18 @@
+use crate::io_priority::{set_current_thread_io_priority, IoPriority};
+use std::thread;
+
+#[test]
+fn set_io_priority() {
+    // Priority is per-thread, so it is changed in separate threads to not affect other tests
+    for io_priority in [IoPriority::High, IoPriority::Idle] {
+        let supported = thread::spawn(move || set_current_thread_io_priority(io_priority))
+            .join()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            supported,
+            cfg!(all(feature = "io-priority", target_os = "linux"))
+        );
+    }
+}
@@ -31,17 +31,28 @@
 //! are `target ± ½ * solution range` (while also handing overflow/underflow) when interpreted as
 //! 64-bit unsigned integers.
 
+pub mod clock;
 #[doc(hidden)]
 pub mod file_ext;
 pub(crate) mod identity;
+pub mod io_priority;
+pub mod log_file;
+pub mod memory_budget;
+pub mod metrics_push;
+pub mod object_fetcher;
 pub(crate) mod object_mappings;
+pub mod piece_cache;
+pub mod piece_serving;
+pub(crate) mod repeated_errors;
 pub mod reward_signing;
+pub mod root_block_store;
 pub mod rpc_client;
 pub mod single_disk_plot;
+pub mod testing;
 mod utils;
 pub mod ws_rpc_server;
 
-pub use identity::Identity;
+pub use identity::{DerivationPath, DerivationPathError, Identity, IdentityDerivation};
 pub use jsonrpsee;
 pub use object_mappings::{ObjectMappingError, ObjectMappings};
 pub use rpc_client::node_rpc_client::NodeRpcClient;
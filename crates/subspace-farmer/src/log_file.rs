@@ -0,0 +1,130 @@
+//! Log file with size-based rotation.
+//!
+//! Current log file is `<directory>/<file_name>`, once it would exceed maximum size it is renamed
+//! to `<file_name>.1`, previously rotated files are shifted to `<file_name>.2` and so on, files
+//! beyond maximum number are removed.
+
+#[cfg(test)]
+mod tests;
+
+use parking_lot::Mutex;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{fmt, fs, io};
+
+struct Inner {
+    path: PathBuf,
+    max_file_size: u64,
+    max_files: NonZeroUsize,
+    file: File,
+    file_size: u64,
+}
+
+impl Inner {
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let max_rotated_files = self.max_files.get() - 1;
+        if max_rotated_files > 0 {
+            match fs::remove_file(self.rotated_path(max_rotated_files)) {
+                Ok(()) => {}
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => {
+                    return Err(error);
+                }
+            }
+            for index in (1..max_rotated_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.file_size = 0;
+
+        Ok(())
+    }
+}
+
+/// Log file that is rotated when it reaches maximum size, cheap to clone, all clones write into
+/// the same file.
+///
+/// Every `write()` call is written into a single file as a whole, so it is expected that the whole
+/// log event is written at once.
+#[derive(Clone)]
+pub struct RotatingLogFile {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl fmt::Debug for RotatingLogFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock();
+        f.debug_struct("RotatingLogFile")
+            .field("path", &inner.path)
+            .field("max_file_size", &inner.max_file_size)
+            .field("max_files", &inner.max_files)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RotatingLogFile {
+    /// Open log file `file_name` in `directory` (created if doesn't exist), appending to existing
+    /// file if there is one.
+    ///
+    /// `max_files` includes the current log file.
+    pub fn open(
+        directory: &Path,
+        file_name: &str,
+        max_file_size: u64,
+        max_files: NonZeroUsize,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let path = directory.join(file_name);
+        let file = OpenOptions::new().append(true).create(true).open(&path)?;
+        let file_size = file.metadata()?.len();
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner {
+                path,
+                max_file_size,
+                max_files,
+                file,
+                file_size,
+            })),
+        })
+    }
+}
+
+impl Write for RotatingLogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.inner.lock();
+        if inner.file_size > 0 && inner.file_size + buf.len() as u64 > inner.max_file_size {
+            inner.rotate()?;
+        }
+
+        inner.file.write_all(buf)?;
+        inner.file_size += buf.len() as u64;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.lock().file.flush()
+    }
+}
@@ -0,0 +1,77 @@
+use crate::log_file::RotatingLogFile;
+use std::fs;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use tempfile::tempdir;
+
+#[test]
+fn rotation() {
+    let directory = tempdir().unwrap();
+    let path = directory.path().join("farmer.log");
+    let read = |file_name: &str| fs::read_to_string(directory.path().join(file_name)).unwrap();
+
+    let mut log_file = RotatingLogFile::open(
+        directory.path(),
+        "farmer.log",
+        10,
+        NonZeroUsize::new(3).unwrap(),
+    )
+    .unwrap();
+
+    log_file.write_all(b"first\n").unwrap();
+    // Clones write into the same file
+    log_file.clone().write_all(b"two\n").unwrap();
+    assert_eq!(read("farmer.log"), "first\ntwo\n");
+
+    // Doesn't fit anymore
+    log_file.write_all(b"third\n").unwrap();
+    assert_eq!(read("farmer.log"), "third\n");
+    assert_eq!(read("farmer.log.1"), "first\ntwo\n");
+
+    // Event larger than maximum size still goes into a single file
+    log_file.write_all(b"fourth is long\n").unwrap();
+    assert_eq!(read("farmer.log"), "fourth is long\n");
+    assert_eq!(read("farmer.log.1"), "third\n");
+    assert_eq!(read("farmer.log.2"), "first\ntwo\n");
+
+    // The oldest file is removed
+    log_file.write_all(b"fifth\n").unwrap();
+    assert_eq!(read("farmer.log"), "fifth\n");
+    assert_eq!(read("farmer.log.1"), "fourth is long\n");
+    assert_eq!(read("farmer.log.2"), "third\n");
+    assert!(!directory.path().join("farmer.log.3").exists());
+
+    // Reopened file is appended to
+    drop(log_file);
+    let mut log_file = RotatingLogFile::open(
+        directory.path(),
+        "farmer.log",
+        10,
+        NonZeroUsize::new(3).unwrap(),
+    )
+    .unwrap();
+    log_file.write_all(b"six\n").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "fifth\nsix\n");
+}
+
+#[test]
+fn single_file() {
+    let directory = tempdir().unwrap();
+
+    let mut log_file = RotatingLogFile::open(
+        directory.path(),
+        "farmer.log",
+        4,
+        NonZeroUsize::new(1).unwrap(),
+    )
+    .unwrap();
+
+    log_file.write_all(b"one\n").unwrap();
+    log_file.write_all(b"two\n").unwrap();
+
+    assert_eq!(
+        fs::read_to_string(directory.path().join("farmer.log")).unwrap(),
+        "two\n"
+    );
+    assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+}
@@ -0,0 +1,279 @@
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::SectorMetadata;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, mem};
+use subspace_core_primitives::crypto::kzg::PublicParameters;
+use subspace_core_primitives::{plot_sector_size, PieceIndex, PIECE_SIZE};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tokio::sync::Notify;
+
+/// Category of memory tracked by [`MemoryBudget`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MemoryCategory {
+    /// Sectors that are being plotted
+    SectorBuffers,
+    /// Pieces stored in farmer piece cache
+    PieceCache,
+    /// Pieces that are being downloaded
+    Downloads,
+}
+
+/// Memory usage in bytes per category
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsage {
+    /// Sectors that are being plotted
+    pub sector_buffers: u64,
+    /// Pieces stored in farmer piece cache
+    pub piece_cache: u64,
+    /// Pieces that are being downloaded
+    pub downloads: u64,
+}
+
+impl MemoryUsage {
+    /// Memory usage of specified category
+    pub fn get(&self, category: MemoryCategory) -> u64 {
+        match category {
+            MemoryCategory::SectorBuffers => self.sector_buffers,
+            MemoryCategory::PieceCache => self.piece_cache,
+            MemoryCategory::Downloads => self.downloads,
+        }
+    }
+
+    /// Total memory usage of all categories
+    pub fn total(&self) -> u64 {
+        self.sector_buffers + self.piece_cache + self.downloads
+    }
+
+    fn get_mut(&mut self, category: MemoryCategory) -> &mut u64 {
+        match category {
+            MemoryCategory::SectorBuffers => &mut self.sector_buffers,
+            MemoryCategory::PieceCache => &mut self.piece_cache,
+            MemoryCategory::Downloads => &mut self.downloads,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    limit: Option<NonZeroU64>,
+    usage: Mutex<MemoryUsage>,
+    released: Notify,
+}
+
+/// Global memory budget of the farmer.
+///
+/// Components register memory they use with reservations that are released on drop. Clones share
+/// the same accounting, so the budget is enforced across all plots and caches it is used with.
+///
+/// The budget is cooperative: plotting waits for memory to become available before starting a new
+/// sector, piece cache evicts pieces instead of growing beyond the budget and downloads are only
+/// accounted for, since they are bounded by plotting anyway.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    inner: Arc<Inner>,
+}
+
+impl MemoryBudget {
+    /// Create new memory budget, `None` means no limit (memory is still accounted for)
+    pub fn new(limit: Option<NonZeroU64>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                limit,
+                usage: Mutex::default(),
+                released: Notify::new(),
+            }),
+        }
+    }
+
+    /// Limit in bytes, `None` means no limit
+    pub fn limit(&self) -> Option<NonZeroU64> {
+        self.inner.limit
+    }
+
+    /// Current memory usage
+    pub fn usage(&self) -> MemoryUsage {
+        *self.inner.usage.lock()
+    }
+
+    /// Create empty reservation of specified category that can be grown later
+    pub fn empty_reservation(&self, category: MemoryCategory) -> MemoryReservation {
+        MemoryReservation {
+            budget: self.clone(),
+            category,
+            bytes: 0,
+        }
+    }
+
+    /// Register `bytes` of memory regardless of the limit, for memory that is already in use or
+    /// can't wait
+    pub fn register(&self, category: MemoryCategory, bytes: u64) -> MemoryReservation {
+        *self.inner.usage.lock().get_mut(category) += bytes;
+
+        MemoryReservation {
+            budget: self.clone(),
+            category,
+            bytes,
+        }
+    }
+
+    /// Try to reserve `bytes` of memory, returns `None` if this would exceed the limit
+    pub fn try_reserve(&self, category: MemoryCategory, bytes: u64) -> Option<MemoryReservation> {
+        self.try_add(category, bytes, false)
+            .then(|| MemoryReservation {
+                budget: self.clone(),
+                category,
+                bytes,
+            })
+    }
+
+    /// Reserve `bytes` of memory, waiting for other reservations to be released if this would
+    /// exceed the limit.
+    ///
+    /// Reservation is granted regardless of the limit when there are no other reservations of the
+    /// same category, so that progress is always possible even if other categories use the whole
+    /// budget or `bytes` alone is larger than the limit.
+    pub async fn reserve(&self, category: MemoryCategory, bytes: u64) -> MemoryReservation {
+        loop {
+            // Subscribe before checking to not miss release that happens in between
+            let released = self.inner.released.notified();
+
+            if self.try_add(category, bytes, true) {
+                return MemoryReservation {
+                    budget: self.clone(),
+                    category,
+                    bytes,
+                };
+            }
+
+            released.await;
+        }
+    }
+
+    fn try_add(&self, category: MemoryCategory, bytes: u64, allow_first: bool) -> bool {
+        let mut usage = self.inner.usage.lock();
+        let fits = match self.inner.limit {
+            Some(limit) => usage.total() + bytes <= limit.get(),
+            None => true,
+        };
+
+        if fits || (allow_first && usage.get(category) == 0) {
+            *usage.get_mut(category) += bytes;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn release(&self, category: MemoryCategory, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+
+        *self.inner.usage.lock().get_mut(category) -= bytes;
+        self.inner.released.notify_waiters();
+    }
+}
+
+/// Memory reserved in [`MemoryBudget`], released on drop
+pub struct MemoryReservation {
+    budget: MemoryBudget,
+    category: MemoryCategory,
+    bytes: u64,
+}
+
+impl fmt::Debug for MemoryReservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryReservation")
+            .field("category", &self.category)
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget.release(self.category, self.bytes);
+    }
+}
+
+impl MemoryReservation {
+    /// Category of this reservation
+    pub fn category(&self) -> MemoryCategory {
+        self.category
+    }
+
+    /// Number of bytes reserved
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Try to grow reservation by `bytes`, returns `false` if this would exceed the limit
+    pub fn try_grow(&mut self, bytes: u64) -> bool {
+        if self.budget.try_add(self.category, bytes, false) {
+            self.bytes += bytes;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Shrink reservation by `bytes`, releasing them back to the budget
+    pub fn shrink(&mut self, bytes: u64) {
+        let bytes = bytes.min(self.bytes);
+        self.bytes -= bytes;
+        self.budget.release(self.category, bytes);
+    }
+}
+
+/// Plotting configuration memory usage is estimated for with [`estimate_plotting_memory()`]
+#[derive(Debug, Copy, Clone)]
+pub struct PlottingMemoryOptions {
+    /// Number of sectors plotted concurrently, every plot plots one sector at a time
+    pub concurrency: NonZeroUsize,
+    /// Number of pieces of every sector being plotted that are retrieved ahead of encoding
+    pub prefetch_depth: usize,
+    /// Capacity of farmer piece cache in pieces, pieces pinned for upcoming sectors are stored in
+    /// it too
+    pub piece_cache_capacity: usize,
+    /// Size of KZG public parameters in bytes, see [`kzg_parameters_size()`]
+    pub kzg_parameters_size: usize,
+}
+
+/// Size of KZG public parameters in memory
+pub fn kzg_parameters_size(public_parameters: &PublicParameters) -> usize {
+    public_parameters.to_raw_var_bytes().len()
+}
+
+/// Estimate peak memory usage in bytes of plotting with `options` under `farmer_protocol_info`.
+///
+/// Sums the major buffers and caches: every sector being plotted is held in page cache until it is
+/// written to disk, together with its prefetched pieces, plotting arena and per-piece bookkeeping,
+/// while piece cache and KZG public parameters are shared. Allocator overhead and small
+/// allocations are not included, so the actual usage is somewhat higher.
+pub fn estimate_plotting_memory(
+    options: &PlottingMemoryOptions,
+    farmer_protocol_info: &FarmerProtocolInfo,
+) -> usize {
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l) as usize;
+    let pieces_in_sector = plot_sector_size / PIECE_SIZE;
+
+    let per_sector = plot_sector_size
+        + options.prefetch_depth * PIECE_SIZE
+        // Arena holds the piece being encoded and encoded sector metadata
+        + PIECE_SIZE
+        + SectorMetadata::encoded_size()
+        // Piece indexes of the sector and retrieval time of every piece
+        + pieces_in_sector * (mem::size_of::<PieceIndex>() + mem::size_of::<Duration>());
+
+    options.concurrency.get() * per_sector
+        + options.piece_cache_capacity * PIECE_SIZE
+        + options.kzg_parameters_size
+}
@@ -0,0 +1,163 @@
+use crate::memory_budget::{
+    estimate_plotting_memory, kzg_parameters_size, MemoryBudget, MemoryCategory, MemoryUsage,
+    PlottingMemoryOptions,
+};
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::time::Duration;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::{plot_sector_size, SegmentIndex, PIECE_SIZE};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tokio::time::timeout;
+
+#[test]
+fn accounting() {
+    let memory_budget = MemoryBudget::new(NonZeroU64::new(100));
+    assert_eq!(memory_budget.usage(), MemoryUsage::default());
+
+    let sector_reservation = memory_budget
+        .try_reserve(MemoryCategory::SectorBuffers, 60)
+        .unwrap();
+    let download_reservation = memory_budget.register(MemoryCategory::Downloads, 30);
+    assert_eq!(
+        memory_budget.usage(),
+        MemoryUsage {
+            sector_buffers: 60,
+            piece_cache: 0,
+            downloads: 30,
+        }
+    );
+
+    // Doesn't fit anymore
+    assert!(memory_budget
+        .try_reserve(MemoryCategory::SectorBuffers, 20)
+        .is_none());
+    let mut cache_reservation = memory_budget.empty_reservation(MemoryCategory::PieceCache);
+    assert!(cache_reservation.try_grow(10));
+    assert!(!cache_reservation.try_grow(1));
+    assert_eq!(memory_budget.usage().total(), 100);
+
+    // Registration ignores the limit
+    let extra_download_reservation = memory_budget.register(MemoryCategory::Downloads, 10);
+    assert_eq!(memory_budget.usage().downloads, 40);
+    assert_eq!(memory_budget.usage().total(), 110);
+
+    drop(sector_reservation);
+    drop(download_reservation);
+    drop(extra_download_reservation);
+    cache_reservation.shrink(5);
+    assert_eq!(memory_budget.usage().piece_cache, 5);
+
+    drop(cache_reservation);
+    assert_eq!(memory_budget.usage(), MemoryUsage::default());
+
+    // Without limit everything fits, but is still accounted for
+    let memory_budget = MemoryBudget::new(None);
+    let _reservation = memory_budget
+        .try_reserve(MemoryCategory::PieceCache, u64::MAX / 2)
+        .unwrap();
+    assert_eq!(memory_budget.usage().piece_cache, u64::MAX / 2);
+}
+
+#[tokio::test]
+async fn reserve_waits_for_release() {
+    let memory_budget = MemoryBudget::new(NonZeroU64::new(100));
+
+    // First reservation of the category is granted even if larger than the whole budget
+    let reservation = memory_budget
+        .reserve(MemoryCategory::SectorBuffers, 150)
+        .await;
+    assert_eq!(reservation.bytes(), 150);
+
+    // Second one has to wait
+    assert!(timeout(
+        Duration::from_millis(100),
+        memory_budget.reserve(MemoryCategory::SectorBuffers, 50)
+    )
+    .await
+    .is_err());
+
+    let mut waiting_reservation = tokio::spawn({
+        let memory_budget = memory_budget.clone();
+
+        async move {
+            memory_budget
+                .reserve(MemoryCategory::SectorBuffers, 50)
+                .await
+        }
+    });
+    assert!(
+        timeout(Duration::from_millis(100), &mut waiting_reservation)
+            .await
+            .is_err()
+    );
+
+    drop(reservation);
+    let reservation = timeout(Duration::from_secs(5), waiting_reservation)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(memory_budget.usage().sector_buffers, 50);
+
+    drop(reservation);
+    assert_eq!(memory_budget.usage(), MemoryUsage::default());
+}
+
+#[test]
+fn plotting_memory_estimate() {
+    let farmer_protocol_info = FarmerProtocolInfo {
+        genesis_hash: Default::default(),
+        record_size: NonZeroU32::new(3840).unwrap(),
+        recorded_history_segment_size: 3840 * 128,
+        total_pieces: NonZeroU64::new(1024).unwrap(),
+        space_l: NonZeroU16::new(20).unwrap(),
+        sector_expiration: SegmentIndex::ONE,
+        rotation: 0,
+    };
+    let options = PlottingMemoryOptions {
+        concurrency: NonZeroUsize::new(1).unwrap(),
+        prefetch_depth: 0,
+        piece_cache_capacity: 10,
+        kzg_parameters_size: kzg_parameters_size(&kzg::test_public_parameters()),
+    };
+    assert!(options.kzg_parameters_size > 0);
+
+    let estimate = |concurrency: usize, prefetch_depth: usize| {
+        estimate_plotting_memory(
+            &PlottingMemoryOptions {
+                concurrency: NonZeroUsize::new(concurrency).unwrap(),
+                prefetch_depth,
+                ..options
+            },
+            &farmer_protocol_info,
+        )
+    };
+
+    let base = estimate(1, 0);
+    // Sector buffer, piece cache and KZG parameters are always accounted for
+    assert!(
+        base >= plot_sector_size(farmer_protocol_info.space_l) as usize
+            + 10 * PIECE_SIZE
+            + options.kzg_parameters_size
+    );
+
+    // Linear in concurrency, shared parts are only accounted for once
+    let per_sector = estimate(2, 0) - base;
+    for concurrency in 1..=8 {
+        assert_eq!(
+            estimate(concurrency, 0),
+            base + (concurrency - 1) * per_sector
+        );
+    }
+
+    // Linear in prefetch depth, for every sector being plotted
+    for concurrency in 1..=4 {
+        let per_prefetched_piece = estimate(concurrency, 1) - estimate(concurrency, 0);
+        assert_eq!(per_prefetched_piece, concurrency * PIECE_SIZE);
+        for prefetch_depth in 0..=16 {
+            assert_eq!(
+                estimate(concurrency, prefetch_depth),
+                estimate(concurrency, 0) + prefetch_depth * per_prefetched_piece
+            );
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Pushing of farmer metrics to a collector.
+//!
+//! Farms behind NAT can't be scraped, so metrics are periodically pushed to statsd or graphite
+//! instead. Pushing happens in its own task with a timeout on every attempt and backs off when
+//! collector is unreachable, so it never holds up farming.
+
+#[cfg(test)]
+mod tests;
+
+use crate::repeated_errors::RepeatedErrors;
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use subspace_core_primitives::PublicKey;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tracing::{debug, warn};
+
+/// Timeout of a single push attempt
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Upper bound of delay added after repeated push failures
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+/// Size of statsd datagram that fits into Ethernet MTU together with IP and UDP headers
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+/// Protocol metrics are pushed with
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MetricsPushProtocol {
+    /// Statsd gauges over UDP with DogStatsD-style tags
+    Statsd,
+    /// Graphite plaintext protocol over TCP with tags
+    Graphite,
+}
+
+/// Options of [`push_metrics()`]
+#[derive(Debug, Clone)]
+pub struct MetricsPushOptions {
+    /// Protocol metrics are pushed with
+    pub protocol: MetricsPushProtocol,
+    /// Address of the collector in `host:port` format, resolved before every push
+    pub endpoint: String,
+    /// Interval between pushes
+    pub interval: Duration,
+    /// Host name added to labels of every metric
+    pub hostname: String,
+}
+
+/// Value of a metric of a single plot
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MetricSample {
+    /// Name of the metric
+    pub name: &'static str,
+    /// Public key of the plot, added to labels of the metric
+    pub public_key: PublicKey,
+    /// Value of the metric
+    pub value: u64,
+}
+
+/// Host name of this machine, `unknown` if it can't be determined
+pub fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: Buffer is valid for writes of its length
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return "unknown".to_string();
+    }
+    let length = buffer
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(buffer.len());
+
+    String::from_utf8_lossy(&buffer[..length]).into_owned()
+}
+
+/// Label value with characters that are separators in statsd or graphite replaced
+fn sanitize_label(value: &str) -> String {
+    value
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() || matches!(char, '.' | '-' | '_') {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Format `sample` as a line of `protocol`, `timestamp` is in seconds since Unix epoch and is only
+/// used by graphite
+pub(crate) fn format_sample(
+    protocol: MetricsPushProtocol,
+    sample: &MetricSample,
+    hostname: &str,
+    timestamp: u64,
+) -> String {
+    let MetricSample {
+        name,
+        public_key,
+        value,
+    } = sample;
+    let hostname = sanitize_label(hostname);
+
+    match protocol {
+        MetricsPushProtocol::Statsd => {
+            format!("{name}:{value}|g|#public_key:{public_key},hostname:{hostname}")
+        }
+        MetricsPushProtocol::Graphite => {
+            format!("{name};public_key={public_key};hostname={hostname} {value} {timestamp}")
+        }
+    }
+}
+
+/// Push `samples` to collector once
+async fn push(options: &MetricsPushOptions, samples: &[MetricSample]) -> io::Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let lines = samples
+        .iter()
+        .map(|sample| format_sample(options.protocol, sample, &options.hostname, timestamp));
+
+    match options.protocol {
+        MetricsPushProtocol::Statsd => {
+            let address = tokio::net::lookup_host(&options.endpoint)
+                .await?
+                .next()
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "endpoint resolved to no addresses")
+                })?;
+            let local_address = match address {
+                SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+                SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+            };
+            let socket = UdpSocket::bind(local_address).await?;
+            socket.connect(address).await?;
+
+            // Lines are packed into as few datagrams as possible
+            let mut datagram = String::new();
+            for line in lines {
+                if !datagram.is_empty() && datagram.len() + 1 + line.len() > MAX_DATAGRAM_SIZE {
+                    socket.send(datagram.as_bytes()).await?;
+                    datagram.clear();
+                }
+                if !datagram.is_empty() {
+                    datagram.push('\n');
+                }
+                datagram.push_str(&line);
+            }
+            if !datagram.is_empty() {
+                socket.send(datagram.as_bytes()).await?;
+            }
+        }
+        MetricsPushProtocol::Graphite => {
+            let mut stream = TcpStream::connect(&options.endpoint).await?;
+            let mut contents = String::new();
+            for line in lines {
+                contents.push_str(&line);
+                contents.push('\n');
+            }
+            stream.write_all(contents.as_bytes()).await?;
+            stream.shutdown().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Push metrics to collector with timeout
+async fn push_with_timeout(
+    options: &MetricsPushOptions,
+    samples: &[MetricSample],
+) -> io::Result<()> {
+    tokio::time::timeout(PUSH_TIMEOUT, push(options, samples))
+        .await
+        .map_err(|_elapsed| io::Error::new(io::ErrorKind::TimedOut, "push timed out"))?
+}
+
+/// Delay before the next push after `failures` consecutive failures
+pub(crate) fn backoff(interval: Duration, failures: u32) -> Duration {
+    if failures == 0 {
+        return Duration::ZERO;
+    }
+
+    interval
+        .checked_mul(1 << (failures - 1).min(16))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+/// Push metrics returned by `collect` every interval until `shutdown` resolves, after which metrics
+/// are collected and pushed one last time, so that final values land in time series.
+///
+/// Failures are logged and delay subsequent pushes, they never stop pushing.
+pub async fn push_metrics<C, S>(options: MetricsPushOptions, collect: C, shutdown: S)
+where
+    C: Fn() -> Vec<MetricSample>,
+    S: Future<Output = ()>,
+{
+    futures::pin_mut!(shutdown);
+    let mut failures = 0u32;
+    let mut push_errors = RepeatedErrors::<io::ErrorKind>::default();
+    let mut shutting_down = false;
+
+    while !shutting_down {
+        tokio::select! {
+            _ = tokio::time::sleep(options.interval + backoff(options.interval, failures)) => {}
+            _ = &mut shutdown => {
+                shutting_down = true;
+            }
+        }
+
+        match push_with_timeout(&options, &collect()).await {
+            Ok(()) => {
+                if let Some(suppressed) = push_errors.success() {
+                    debug!(%suppressed, "Metrics push recovered after repeated errors");
+                }
+                failures = 0;
+            }
+            Err(error) => {
+                failures = failures.saturating_add(1);
+                if let Some(repeats) = push_errors.error(error.kind()) {
+                    warn!(
+                        %error,
+                        %repeats,
+                        endpoint = %options.endpoint,
+                        "Failed to push metrics"
+                    );
+                }
+            }
+        }
+    }
+}
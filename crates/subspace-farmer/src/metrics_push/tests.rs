@@ -0,0 +1,163 @@
+use crate::metrics_push::{
+    backoff, format_sample, push_metrics, MetricSample, MetricsPushOptions, MetricsPushProtocol,
+    MAX_BACKOFF,
+};
+use futures::channel::oneshot;
+use std::time::Duration;
+use subspace_core_primitives::PublicKey;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, UdpSocket};
+
+fn samples() -> Vec<MetricSample> {
+    vec![
+        MetricSample {
+            name: "subspace_farmer_plotted_sectors",
+            public_key: PublicKey::from([1; 32]),
+            value: 10,
+        },
+        MetricSample {
+            name: "subspace_farmer_target_sectors",
+            public_key: PublicKey::from([1; 32]),
+            value: 20,
+        },
+    ]
+}
+
+fn options(
+    protocol: MetricsPushProtocol,
+    endpoint: String,
+    interval: Duration,
+) -> MetricsPushOptions {
+    MetricsPushOptions {
+        protocol,
+        endpoint,
+        interval,
+        hostname: "farm-1".to_string(),
+    }
+}
+
+#[test]
+fn sample_format() {
+    let sample = &samples()[0];
+    let public_key = "01".repeat(32);
+
+    assert_eq!(
+        format_sample(MetricsPushProtocol::Statsd, sample, "farm-1", 100),
+        format!("subspace_farmer_plotted_sectors:10|g|#public_key:{public_key},hostname:farm-1")
+    );
+    assert_eq!(
+        format_sample(MetricsPushProtocol::Graphite, sample, "farm-1", 100),
+        format!("subspace_farmer_plotted_sectors;public_key={public_key};hostname=farm-1 10 100")
+    );
+    // Separators of either protocol can't appear in labels
+    assert!(
+        format_sample(MetricsPushProtocol::Graphite, sample, "farm 1;x", 100)
+            .contains("hostname=farm_1_x ")
+    );
+}
+
+#[test]
+fn backoff_is_capped() {
+    let interval = Duration::from_secs(15);
+
+    assert_eq!(backoff(interval, 0), Duration::ZERO);
+    assert_eq!(backoff(interval, 1), interval);
+    assert_eq!(backoff(interval, 3), interval * 4);
+    assert_eq!(backoff(interval, 100), MAX_BACKOFF);
+}
+
+#[tokio::test]
+async fn statsd_flush_on_shutdown() {
+    let collector = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    // Interval is never reached, metrics are only pushed on shutdown
+    push_metrics(
+        options(
+            MetricsPushProtocol::Statsd,
+            collector.local_addr().unwrap().to_string(),
+            Duration::from_secs(3600),
+        ),
+        samples,
+        async {},
+    )
+    .await;
+
+    let mut buffer = [0u8; 1500];
+    let length = collector.recv(&mut buffer).await.unwrap();
+    let datagram = String::from_utf8(buffer[..length].to_vec()).unwrap();
+    let lines = datagram.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("subspace_farmer_plotted_sectors:10|g|"));
+    assert!(lines[1].starts_with("subspace_farmer_target_sectors:20|g|"));
+}
+
+#[tokio::test]
+async fn graphite_periodic_push() {
+    let collector = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+
+    let pusher = tokio::spawn(push_metrics(
+        options(
+            MetricsPushProtocol::Graphite,
+            collector.local_addr().unwrap().to_string(),
+            Duration::from_millis(10),
+        ),
+        samples,
+        async move {
+            let _ = shutdown_receiver.await;
+        },
+    ));
+
+    let receive = || async {
+        let (mut stream, _address) = collector.accept().await.unwrap();
+        let mut contents = String::new();
+        stream.read_to_string(&mut contents).await.unwrap();
+        contents
+    };
+
+    // Pushed periodically
+    let contents = receive().await;
+    assert_eq!(contents.lines().count(), 2);
+    assert!(contents.starts_with("subspace_farmer_plotted_sectors;"));
+
+    let contents = receive().await;
+    assert_eq!(contents.lines().count(), 2);
+
+    shutdown_sender.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(10), pusher)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn unreachable_collector() {
+    // Nothing listens on the port once listener is dropped
+    let endpoint = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+
+    let pusher = tokio::spawn(push_metrics(
+        options(
+            MetricsPushProtocol::Graphite,
+            endpoint,
+            Duration::from_millis(10),
+        ),
+        samples,
+        async move {
+            let _ = shutdown_receiver.await;
+        },
+    ));
+
+    // Failures back off, but pusher keeps running and exits promptly on shutdown
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    shutdown_sender.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(10), pusher)
+        .await
+        .unwrap()
+        .unwrap();
+}
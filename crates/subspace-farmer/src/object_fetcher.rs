@@ -0,0 +1,349 @@
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use parity_scale_codec::{Compact, CompactLen, Decode, Encode};
+use subspace_archiving::archiver::{Segment, SegmentItem};
+use subspace_core_primitives::objects::GlobalObject;
+use subspace_core_primitives::{Piece, PieceIndex, SegmentIndex};
+use thiserror::Error;
+use tracing::{debug, trace};
+
+/// Maximum expected size of one object in bytes
+pub const MAX_OBJECT_SIZE: usize = 5 * 1024 * 1024;
+
+/// Errors that happen during object fetching
+#[derive(Debug, Error)]
+pub enum ObjectFetcherError {
+    /// Object mapping points to a parity piece, objects are only stored in data pieces
+    #[error(
+        "Object mapping points to parity piece {piece_index}, objects are only stored in data \
+        pieces"
+    )]
+    NotDataPiece {
+        /// Piece index
+        piece_index: PieceIndex,
+    },
+    /// Object mapping offset is outside of the record
+    #[error("Object mapping offset {offset} is outside of the record of size {record_size}")]
+    OffsetOutsideRecord {
+        /// Offset within piece
+        offset: u32,
+        /// Record size
+        record_size: u32,
+    },
+    /// Piece not found
+    #[error("Piece {piece_index} not found")]
+    PieceNotFound {
+        /// Piece index
+        piece_index: PieceIndex,
+    },
+    /// Failed to retrieve piece
+    #[error("Failed to retrieve piece {piece_index}: {error}")]
+    FailedToRetrievePiece {
+        /// Piece index
+        piece_index: PieceIndex,
+        /// Lower-level error
+        error: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    /// Failed to decode segment
+    #[error("Failed to decode segment {segment_index}: {error}")]
+    FailedToDecodeSegment {
+        /// Segment index
+        segment_index: SegmentIndex,
+        /// Lower-level error
+        error: parity_scale_codec::Error,
+    },
+    /// Invalid object length prefix
+    #[error("Invalid object length prefix starting with 0x{first_byte:02x}")]
+    InvalidLengthPrefix {
+        /// First byte of the length prefix
+        first_byte: u8,
+    },
+    /// Object is larger than allowed
+    #[error("Object of size {size} is larger than allowed maximum of {MAX_OBJECT_SIZE} bytes")]
+    ObjectTooLarge {
+        /// Size of the object according to its length prefix
+        size: usize,
+    },
+    /// No segment item that could contain an object at specified offset
+    #[error("No data segment item found at offset {offset_in_segment} in segment {segment_index}")]
+    SegmentItemNotFound {
+        /// Segment index
+        segment_index: SegmentIndex,
+        /// Offset within segment
+        offset_in_segment: u64,
+    },
+    /// Block containing the object ended before the object did
+    #[error("Object is truncated, block containing it ended in segment {segment_index}")]
+    TruncatedObject {
+        /// Segment index where the next block was found
+        segment_index: SegmentIndex,
+    },
+}
+
+/// Retrieves objects stored in archived history using global object mappings.
+///
+/// Objects are stored as SCALE-encoded byte vectors in data records of pieces, they can span
+/// multiple pieces and even multiple segments. Fetcher retrieves necessary pieces, stitches object
+/// bytes together, skipping encoding overhead and padding of segments and returns original object
+/// bytes.
+pub struct ObjectFetcher<PR> {
+    piece_receiver: PR,
+    record_size: u32,
+    /// Number of data and parity pieces in a segment
+    pieces_in_segment: u32,
+}
+
+impl<PR> ObjectFetcher<PR>
+where
+    PR: PieceReceiver,
+{
+    /// Create new object fetcher that will retrieve pieces using `piece_receiver`
+    pub fn new(piece_receiver: PR, record_size: u32, recorded_history_segment_size: u32) -> Self {
+        Self {
+            piece_receiver,
+            record_size,
+            pieces_in_segment: recorded_history_segment_size / record_size * 2,
+        }
+    }
+
+    /// Fetch object that starts at piece index and offset specified in the mapping
+    pub async fn fetch_object(&self, mapping: GlobalObject) -> Result<Vec<u8>, ObjectFetcherError> {
+        let piece_index = mapping.piece_index();
+        let offset = mapping.offset();
+
+        let piece_position_in_segment = piece_index % u64::from(self.pieces_in_segment);
+        if piece_position_in_segment >= u64::from(self.pieces_in_segment / 2) {
+            return Err(ObjectFetcherError::NotDataPiece { piece_index });
+        }
+        if offset >= self.record_size {
+            return Err(ObjectFetcherError::OffsetOutsideRecord {
+                offset,
+                record_size: self.record_size,
+            });
+        }
+
+        if let Some(data) = self.fetch_object_fast(piece_index, offset).await? {
+            trace!(%piece_index, %offset, "Object fetched without decoding segment");
+
+            return Ok(data);
+        }
+
+        debug!(
+            %piece_index,
+            %offset,
+            "Object may cross segment boundary, decoding segments"
+        );
+
+        self.fetch_object_regular(piece_index, offset).await
+    }
+
+    /// Fast object fetching in case object doesn't cross segment boundary by just concatenating
+    /// records, returns `Ok(None)` if object is not guaranteed to fit into the segment.
+    async fn fetch_object_fast(
+        &self,
+        piece_index: PieceIndex,
+        offset: u32,
+    ) -> Result<Option<Vec<u8>>, ObjectFetcherError> {
+        let record_size = self.record_size as usize;
+        let data_pieces_in_segment = u64::from(self.pieces_in_segment / 2);
+        let piece_position_in_segment = piece_index % u64::from(self.pieces_in_segment);
+
+        // How many bytes belong to segment items starting at `offset` of `piece_index`. `-2` is
+        // because last 2 bytes of the segment might contain padding.
+        let bytes_in_segment = ((data_pieces_in_segment - piece_position_in_segment)
+            * u64::from(self.record_size)
+            - u64::from(offset))
+        .saturating_sub(2) as usize;
+
+        let mut records = Vec::<u8>::with_capacity(record_size * 2);
+        let mut next_piece_index = piece_index;
+
+        let (length_prefix_size, object_size) = loop {
+            let data = &records[(offset as usize).min(records.len())..];
+            if let Some(result) = decode_object_size(&data[..data.len().min(bytes_in_segment)])? {
+                break result;
+            }
+
+            if data.len() >= bytes_in_segment {
+                // Length prefix itself crosses segment boundary
+                return Ok(None);
+            }
+
+            let piece = self.read_piece(next_piece_index).await?;
+            next_piece_index += 1;
+            records.extend_from_slice(&piece[..record_size]);
+        };
+
+        let object_end = length_prefix_size + object_size;
+        if object_end > bytes_in_segment {
+            return Ok(None);
+        }
+
+        while records.len() < offset as usize + object_end {
+            let piece = self.read_piece(next_piece_index).await?;
+            next_piece_index += 1;
+            records.extend_from_slice(&piece[..record_size]);
+        }
+
+        Ok(Some(
+            records[offset as usize..][length_prefix_size..object_end].to_vec(),
+        ))
+    }
+
+    /// Object fetching that can cross segment boundary, which requires decoding of full segments
+    /// to skip encoding overhead and root block at the beginning of the next segment.
+    async fn fetch_object_regular(
+        &self,
+        piece_index: PieceIndex,
+        offset: u32,
+    ) -> Result<Vec<u8>, ObjectFetcherError> {
+        let segment_index = SegmentIndex::new(piece_index / u64::from(self.pieces_in_segment));
+        let piece_position_in_segment = piece_index % u64::from(self.pieces_in_segment);
+        let offset_in_segment =
+            piece_position_in_segment * u64::from(self.record_size) + u64::from(offset);
+
+        let mut data = {
+            let Segment::V0 { items } = self.read_segment(segment_index).await?;
+            // Unconditional progress is enum variant + compact encoding of number of elements
+            let mut progress = 1 + Compact::compact_len(&(items.len() as u64));
+            let segment_item = items
+                .into_iter()
+                .find(|item| {
+                    // Add number of bytes in encoded version of segment item
+                    progress += item.encoded_size();
+
+                    // Our data is within another segment item, which will have wrapping data
+                    // structure, hence strictly `>` here
+                    progress > offset_in_segment as usize
+                })
+                .ok_or(ObjectFetcherError::SegmentItemNotFound {
+                    segment_index,
+                    offset_in_segment,
+                })?;
+
+            match segment_item {
+                SegmentItem::Block { bytes, .. }
+                | SegmentItem::BlockStart { bytes, .. }
+                | SegmentItem::BlockContinuation { bytes, .. } => {
+                    // Rewind back progress to the beginning of the number of bytes
+                    progress -= bytes.len();
+                    // Get a chunk of the bytes starting at the position we care about
+                    Vec::from(&bytes[offset_in_segment as usize - progress..])
+                }
+                SegmentItem::RootBlock(_) | SegmentItem::Padding => {
+                    return Err(ObjectFetcherError::SegmentItemNotFound {
+                        segment_index,
+                        offset_in_segment,
+                    });
+                }
+            }
+        };
+
+        if let Some(object) = decode_object(&data)? {
+            return Ok(object);
+        }
+
+        for segment_index in (segment_index.get() + 1..).map(SegmentIndex::new) {
+            let Segment::V0 { items } = self.read_segment(segment_index).await?;
+            for segment_item in items {
+                match segment_item {
+                    SegmentItem::BlockContinuation { bytes, .. } => {
+                        data.extend_from_slice(&bytes);
+
+                        if let Some(object) = decode_object(&data)? {
+                            return Ok(object);
+                        }
+                    }
+                    SegmentItem::Block { .. } | SegmentItem::BlockStart { .. } => {
+                        // Block that contained the object has ended, but the object didn't
+                        return Err(ObjectFetcherError::TruncatedObject { segment_index });
+                    }
+                    SegmentItem::RootBlock(_) | SegmentItem::Padding => {
+                        // Not a part of the block
+                    }
+                }
+            }
+        }
+
+        unreachable!("Loop above is infinite and only exits with return; qed")
+    }
+
+    /// Read the whole segment by its index (just records, skipping witnesses)
+    async fn read_segment(
+        &self,
+        segment_index: SegmentIndex,
+    ) -> Result<Segment, ObjectFetcherError> {
+        let first_piece_in_segment = segment_index.get() * PieceIndex::from(self.pieces_in_segment);
+        let mut segment_bytes =
+            Vec::<u8>::with_capacity((self.pieces_in_segment / 2 * self.record_size) as usize);
+
+        for piece_index in (first_piece_in_segment..).take(self.pieces_in_segment as usize / 2) {
+            let piece = self.read_piece(piece_index).await?;
+            segment_bytes.extend_from_slice(&piece[..self.record_size as usize]);
+        }
+
+        Segment::decode(&mut segment_bytes.as_slice()).map_err(|error| {
+            ObjectFetcherError::FailedToDecodeSegment {
+                segment_index,
+                error,
+            }
+        })
+    }
+
+    async fn read_piece(&self, piece_index: PieceIndex) -> Result<Piece, ObjectFetcherError> {
+        self.piece_receiver
+            .get_piece(piece_index)
+            .await
+            .map_err(|error| ObjectFetcherError::FailedToRetrievePiece { piece_index, error })?
+            .ok_or(ObjectFetcherError::PieceNotFound { piece_index })
+    }
+}
+
+/// Decode size of the length prefix and object size from the beginning of `data`, returns
+/// `Ok(None)` if there is not enough bytes to decode length prefix
+fn decode_object_size(data: &[u8]) -> Result<Option<(usize, usize)>, ObjectFetcherError> {
+    let first_byte = match data.first() {
+        Some(&first_byte) => first_byte,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    // See https://docs.substrate.io/reference/scale-codec/#fn-1 for details about compact
+    // encoding, objects are never large enough to use big-integer mode
+    let length_prefix_size = match first_byte % 4 {
+        0 => 1,
+        1 => 2,
+        2 => 4,
+        _ => {
+            return Err(ObjectFetcherError::InvalidLengthPrefix { first_byte });
+        }
+    };
+
+    if data.len() < length_prefix_size {
+        return Ok(None);
+    }
+
+    let Compact(object_size) = Compact::<u32>::decode(&mut &data[..length_prefix_size])
+        .map_err(|_error| ObjectFetcherError::InvalidLengthPrefix { first_byte })?;
+    let object_size = object_size as usize;
+
+    if object_size > MAX_OBJECT_SIZE {
+        return Err(ObjectFetcherError::ObjectTooLarge { size: object_size });
+    }
+
+    Ok(Some((length_prefix_size, object_size)))
+}
+
+/// Decode object from the beginning of `data`, returns `Ok(None)` if there is not enough bytes yet
+fn decode_object(data: &[u8]) -> Result<Option<Vec<u8>>, ObjectFetcherError> {
+    Ok(
+        decode_object_size(data)?.and_then(|(length_prefix_size, object_size)| {
+            data.get(length_prefix_size..)?
+                .get(..object_size)
+                .map(<[u8]>::to_vec)
+        }),
+    )
+}
@@ -0,0 +1,172 @@
+use crate::object_fetcher::{ObjectFetcher, ObjectFetcherError};
+use crate::testing::MapPieceReceiver;
+use futures::executor::block_on;
+use parity_scale_codec::Encode;
+use std::collections::HashMap;
+use subspace_archiving::archiver::{ArchivedSegment, Archiver};
+use subspace_core_primitives::crypto::blake2b_256_hash;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::objects::{BlockObject, BlockObjectMapping, GlobalObject};
+use subspace_core_primitives::{Blake2b256Hash, RECORD_SIZE};
+
+// This is data + parity shards
+const PIECES_IN_SEGMENT: u32 = 8;
+// In terms of source data that can be stored in the segment, not the size after archiving
+const SEGMENT_SIZE: u32 = RECORD_SIZE * PIECES_IN_SEGMENT / 2;
+
+/// Create block of specified size with objects of specified sizes written at specified offsets
+fn create_block(
+    size: u32,
+    objects: &[(u32, usize)],
+) -> (Vec<u8>, BlockObjectMapping, Vec<Vec<u8>>) {
+    let mut block = vec![0u8; size as usize];
+    let mut object_mapping = BlockObjectMapping::default();
+    let mut contents = Vec::with_capacity(objects.len());
+
+    for &(offset, object_size) in objects {
+        let object = (0..object_size)
+            .map(|_| rand::random())
+            .collect::<Vec<u8>>();
+        let encoded_object = object.encode();
+        block[offset as usize..][..encoded_object.len()].copy_from_slice(&encoded_object);
+        object_mapping.objects.push(BlockObject::V0 {
+            hash: blake2b_256_hash(&object),
+            offset,
+        });
+        contents.push(object);
+    }
+
+    (block, object_mapping, contents)
+}
+
+fn global_objects(archived_segment: &ArchivedSegment) -> HashMap<Blake2b256Hash, GlobalObject> {
+    let first_piece_index =
+        archived_segment.root_block.segment_index().get() * u64::from(PIECES_IN_SEGMENT);
+
+    archived_segment
+        .object_mapping
+        .iter()
+        .enumerate()
+        .flat_map(|(position, piece_object_mapping)| {
+            piece_object_mapping
+                .objects
+                .iter()
+                .map(move |piece_object| {
+                    (
+                        piece_object.hash(),
+                        GlobalObject::V0 {
+                            piece_index: first_piece_index + position as u64,
+                            offset: piece_object.offset(),
+                        },
+                    )
+                })
+        })
+        .collect()
+}
+
+#[test]
+fn fetch_objects() {
+    let kzg = Kzg::random(PIECES_IN_SEGMENT).unwrap();
+    let mut archiver = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg).unwrap();
+    let mut piece_receiver = MapPieceReceiver::new();
+    let mut mappings = HashMap::new();
+
+    // First object fits into the first piece, second object crosses boundary between first and
+    // second pieces
+    let (block_0, block_0_object_mapping, block_0_objects) =
+        create_block(SEGMENT_SIZE / 4 * 3, &[(0, 100), (RECORD_SIZE - 100, 200)]);
+    assert!(archiver
+        .add_block(block_0, block_0_object_mapping)
+        .is_empty());
+
+    // Block will be split between first and second segments, object crosses segment boundary
+    let (block_1, block_1_object_mapping, block_1_objects) =
+        create_block(SEGMENT_SIZE / 2, &[(SEGMENT_SIZE / 4 - 1000, 4000)]);
+    let archived_segments = archiver.add_block(block_1, block_1_object_mapping);
+    assert_eq!(archived_segments.len(), 1);
+    for archived_segment in &archived_segments {
+        piece_receiver.insert_segment(archived_segment);
+        mappings.extend(global_objects(archived_segment));
+    }
+
+    // Object entirely in the second segment
+    let (block_2, block_2_object_mapping, block_2_objects) =
+        create_block(SEGMENT_SIZE, &[(10_000, 1000)]);
+    let archived_segments = archiver.add_block(block_2, block_2_object_mapping);
+    assert!(!archived_segments.is_empty());
+    for archived_segment in &archived_segments {
+        piece_receiver.insert_segment(archived_segment);
+        mappings.extend(global_objects(archived_segment));
+    }
+
+    let object_fetcher = ObjectFetcher::new(piece_receiver, RECORD_SIZE, SEGMENT_SIZE);
+
+    let fetch = |object: &[u8]| {
+        let mapping = mappings
+            .get(&blake2b_256_hash(object))
+            .copied()
+            .expect("Mapping for every object exists");
+        (
+            mapping,
+            block_on(object_fetcher.fetch_object(mapping)).unwrap(),
+        )
+    };
+
+    // Within single piece
+    {
+        let (mapping, fetched) = fetch(&block_0_objects[0]);
+        assert_eq!(mapping.piece_index(), 0);
+        assert_eq!(fetched, block_0_objects[0]);
+    }
+
+    // Across two pieces
+    {
+        let (mapping, fetched) = fetch(&block_0_objects[1]);
+        assert_eq!(mapping.piece_index(), 0);
+        assert!(
+            mapping.offset() as usize + block_0_objects[1].encoded_size() > RECORD_SIZE as usize
+        );
+        assert_eq!(fetched, block_0_objects[1]);
+    }
+
+    // Across two segments
+    {
+        let (mapping, fetched) = fetch(&block_1_objects[0]);
+        assert!(mapping.piece_index() < u64::from(PIECES_IN_SEGMENT / 2));
+        assert_eq!(fetched, block_1_objects[0]);
+    }
+
+    // Within second segment
+    {
+        let (mapping, fetched) = fetch(&block_2_objects[0]);
+        assert!(mapping.piece_index() >= u64::from(PIECES_IN_SEGMENT));
+        assert_eq!(fetched, block_2_objects[0]);
+    }
+
+    // Parity pieces do not contain objects
+    assert!(matches!(
+        block_on(object_fetcher.fetch_object(GlobalObject::V0 {
+            piece_index: u64::from(PIECES_IN_SEGMENT / 2),
+            offset: 0,
+        })),
+        Err(ObjectFetcherError::NotDataPiece { .. })
+    ));
+
+    // Offset must be within record
+    assert!(matches!(
+        block_on(object_fetcher.fetch_object(GlobalObject::V0 {
+            piece_index: 0,
+            offset: RECORD_SIZE,
+        })),
+        Err(ObjectFetcherError::OffsetOutsideRecord { .. })
+    ));
+
+    // Pieces that are not archived yet can't be retrieved
+    assert!(matches!(
+        block_on(object_fetcher.fetch_object(GlobalObject::V0 {
+            piece_index: u64::from(PIECES_IN_SEGMENT) * 100,
+            offset: 0,
+        })),
+        Err(ObjectFetcherError::PieceNotFound { .. })
+    ));
+}
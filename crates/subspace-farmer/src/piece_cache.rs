@@ -0,0 +1,702 @@
+#[cfg(test)]
+mod tests;
+
+use crate::memory_budget::{MemoryBudget, MemoryCategory, MemoryReservation};
+use crate::root_block_store::{RootBlockStore, RootBlockStoreError};
+use crate::rpc_client::{Error as RpcError, RpcClient};
+use crate::single_disk_plot::farming::PieceStore;
+use crate::utils::lower_thread_priority;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::future::Future;
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::{fmt, io, thread};
+use subspace_archiving::archiver::ArchivedSegment;
+use subspace_core_primitives::{Piece, PieceIndex, RootBlock, SegmentIndex, PIECE_SIZE};
+use subspace_rpc_primitives::MAX_SEGMENT_INDEXES_PER_REQUEST;
+use tracing::{debug, error, info, trace, warn};
+
+/// Number of archived segments that can be queued for insertion into the cache before we stop
+/// pulling new notifications from the node, this is what provides backpressure when node is
+/// archiving faster than farmer can process segments
+const ARCHIVED_SEGMENTS_BUFFER: usize = 2;
+/// At most this share of cache capacity can be pinned, so that pinning never prevents eviction
+/// entirely
+const MAX_PINNED_SHARE: (usize, usize) = (1, 2);
+/// Share of cache capacity that protected segment of [`EvictionPolicy::Segmented`] can occupy
+const PROTECTED_SHARE: (usize, usize) = (4, 5);
+
+/// Policy that decides which piece is evicted when cache is full, pinned pieces are never evicted
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum EvictionPolicy {
+    /// Least recently used piece is evicted first
+    #[default]
+    Lru,
+    /// Least frequently used piece is evicted first, least recently used one among pieces used the
+    /// same number of times
+    Lfu,
+    /// Segmented LRU: pieces that were used at least once after insertion are moved into protected
+    /// segment, least recently used pieces of probationary segment are evicted first
+    Segmented,
+}
+
+/// Statistics of piece cache usage
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct PieceCacheStats {
+    /// Number of requests that found pinned piece in cache
+    pub pinned_hits: u64,
+    /// Number of requests that found not pinned piece in cache
+    pub unpinned_hits: u64,
+    /// Number of requests for pieces that were not in cache
+    pub misses: u64,
+}
+
+impl PieceCacheStats {
+    /// Total number of requests that found piece in cache
+    pub fn hits(&self) -> u64 {
+        self.pinned_hits + self.unpinned_hits
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    piece: Piece,
+    /// Value of [`Pieces::clock`] when piece was used last time
+    last_used: u64,
+    /// Number of times piece was used, insertion included
+    uses: u64,
+    /// Whether piece is in protected segment of [`EvictionPolicy::Segmented`]
+    protected: bool,
+}
+
+impl Entry {
+    /// Pieces with smaller keys are evicted first
+    fn eviction_key(&self, eviction_policy: EvictionPolicy) -> (u64, u64) {
+        match eviction_policy {
+            EvictionPolicy::Lru => (0, self.last_used),
+            EvictionPolicy::Lfu => (self.uses, self.last_used),
+            EvictionPolicy::Segmented => (u64::from(self.protected), self.last_used),
+        }
+    }
+}
+
+struct Pieces {
+    eviction_policy: EvictionPolicy,
+    capacity: usize,
+    entries: HashMap<PieceIndex, Entry>,
+    /// Eviction keys of pieces in `entries` that are not pinned
+    evictable: BTreeSet<((u64, u64), PieceIndex)>,
+    /// Number of pins of pinned piece indexes, pieces don't need to be in cache to be pinned
+    pins: HashMap<PieceIndex, usize>,
+    /// Number of entries in protected segment
+    protected: usize,
+    /// Logical clock that increases with every use of the cache
+    clock: u64,
+    stats: PieceCacheStats,
+    /// Memory used by pieces in `entries`
+    memory_reservation: MemoryReservation,
+}
+
+impl Pieces {
+    fn max_pinned(&self) -> usize {
+        self.capacity * MAX_PINNED_SHARE.0 / MAX_PINNED_SHARE.1
+    }
+
+    fn max_protected(&self) -> usize {
+        self.capacity * PROTECTED_SHARE.0 / PROTECTED_SHARE.1
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Apply `update` to entry of `piece_index`, keeping eviction order consistent
+    fn update_entry<U>(&mut self, piece_index: PieceIndex, update: U) -> Option<&Entry>
+    where
+        U: FnOnce(&mut Entry),
+    {
+        let pinned = self.pins.contains_key(&piece_index);
+        let entry = self.entries.get_mut(&piece_index)?;
+        if !pinned {
+            self.evictable
+                .remove(&(entry.eviction_key(self.eviction_policy), piece_index));
+        }
+        let was_protected = entry.protected;
+        update(entry);
+        if !pinned {
+            self.evictable
+                .insert((entry.eviction_key(self.eviction_policy), piece_index));
+        }
+
+        if entry.protected && !was_protected {
+            self.protected += 1;
+            if self.protected > self.max_protected() {
+                self.demote_protected();
+            }
+        }
+
+        self.entries.get(&piece_index)
+    }
+
+    /// Move least recently used evictable piece of protected segment back to probationary segment
+    fn demote_protected(&mut self) {
+        let maybe_piece_index = self
+            .evictable
+            .range(((1, 0), 0)..)
+            .next()
+            .map(|(_key, piece_index)| *piece_index);
+        if let Some(piece_index) = maybe_piece_index {
+            self.update_entry(piece_index, |entry| {
+                entry.protected = false;
+            });
+            self.protected -= 1;
+        }
+    }
+
+    fn get(&mut self, piece_index: PieceIndex) -> Option<Piece> {
+        let last_used = self.tick();
+        let eviction_policy = self.eviction_policy;
+        let maybe_piece = self
+            .update_entry(piece_index, |entry| {
+                entry.last_used = last_used;
+                entry.uses += 1;
+                entry.protected |= eviction_policy == EvictionPolicy::Segmented;
+            })
+            .map(|entry| entry.piece.clone());
+
+        match maybe_piece {
+            Some(_) if self.pins.contains_key(&piece_index) => {
+                self.stats.pinned_hits += 1;
+            }
+            Some(_) => {
+                self.stats.unpinned_hits += 1;
+            }
+            None => {
+                self.stats.misses += 1;
+            }
+        }
+
+        maybe_piece
+    }
+
+    /// Remove piece that should be evicted first, returns `false` if all pieces are pinned
+    fn evict(&mut self) -> bool {
+        let (_key, piece_index) = match self.evictable.pop_first() {
+            Some(evictable) => evictable,
+            None => {
+                return false;
+            }
+        };
+        if let Some(entry) = self.entries.remove(&piece_index) {
+            if entry.protected {
+                self.protected -= 1;
+            }
+        }
+
+        true
+    }
+
+    fn remove(&mut self, piece_index: PieceIndex) {
+        let entry = match self.entries.remove(&piece_index) {
+            Some(entry) => entry,
+            None => {
+                return;
+            }
+        };
+        if !self.pins.contains_key(&piece_index) {
+            self.evictable
+                .remove(&(entry.eviction_key(self.eviction_policy), piece_index));
+        }
+        if entry.protected {
+            self.protected -= 1;
+        }
+        self.memory_reservation.shrink(PIECE_SIZE as u64);
+    }
+
+    fn insert(&mut self, piece_index: PieceIndex, piece: Piece) {
+        let last_used = self.tick();
+        if self.entries.contains_key(&piece_index) {
+            self.update_entry(piece_index, |entry| {
+                entry.piece = piece;
+                entry.last_used = last_used;
+            });
+            return;
+        }
+
+        if self.entries.len() >= self.capacity {
+            // Evicting one piece to make space for another doesn't change memory usage
+            if !self.evict() {
+                trace!(%piece_index, "All pieces are pinned, piece not cached");
+                return;
+            }
+        } else {
+            let mut reserved = self.memory_reservation.try_grow(PIECE_SIZE as u64);
+            while !reserved && self.evict() {
+                self.memory_reservation.shrink(PIECE_SIZE as u64);
+                reserved = self.memory_reservation.try_grow(PIECE_SIZE as u64);
+            }
+            if !reserved {
+                trace!(%piece_index, "Memory budget reached, piece not cached");
+                return;
+            }
+        }
+
+        let entry = Entry {
+            piece,
+            last_used,
+            uses: 1,
+            protected: false,
+        };
+        if !self.pins.contains_key(&piece_index) {
+            self.evictable
+                .insert((entry.eviction_key(self.eviction_policy), piece_index));
+        }
+        self.entries.insert(piece_index, entry);
+    }
+
+    /// Returns `false` if piece can't be pinned because too many pieces are pinned already
+    fn pin(&mut self, piece_index: PieceIndex) -> bool {
+        if let Some(pins) = self.pins.get_mut(&piece_index) {
+            *pins += 1;
+            return true;
+        }
+        if self.pins.len() >= self.max_pinned() {
+            return false;
+        }
+
+        if let Some(entry) = self.entries.get(&piece_index) {
+            self.evictable
+                .remove(&(entry.eviction_key(self.eviction_policy), piece_index));
+        }
+        self.pins.insert(piece_index, 1);
+
+        true
+    }
+
+    fn unpin(&mut self, piece_index: PieceIndex) {
+        let pins = match self.pins.get_mut(&piece_index) {
+            Some(pins) => pins,
+            None => {
+                return;
+            }
+        };
+        *pins -= 1;
+        if *pins > 0 {
+            return;
+        }
+
+        self.pins.remove(&piece_index);
+        if let Some(entry) = self.entries.get(&piece_index) {
+            self.evictable
+                .insert((entry.eviction_key(self.eviction_policy), piece_index));
+        }
+    }
+}
+
+struct Inner {
+    pieces: Mutex<Pieces>,
+    /// Total number of pieces according to the last archived segment seen, `0` if no segments were
+    /// seen yet
+    total_pieces: AtomicU64,
+    /// Root blocks of archived segments, new segments must extend their chain
+    root_block_store: RootBlockStore,
+}
+
+/// Farmer-local cache of pieces from recently archived segments.
+///
+/// Populated proactively from archived segment notifications so that plotting doesn't need to go
+/// to the network for fresh pieces, limited to a fixed number of pieces with pieces evicted
+/// according to [`EvictionPolicy`]. Pieces are also evicted when adding new ones would exceed the
+/// memory budget.
+///
+/// Pieces can be pinned with [`FarmerPieceCache::pin()`] to prevent their eviction, for instance
+/// pieces that upcoming sectors will need, at most half of the capacity can be pinned.
+#[derive(Clone)]
+pub struct FarmerPieceCache {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for FarmerPieceCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FarmerPieceCache")
+            .field("capacity", &self.capacity())
+            .field("total_pieces", &self.total_pieces())
+            .finish()
+    }
+}
+
+impl FarmerPieceCache {
+    /// Create new cache that will hold at most `capacity` pieces within `memory_budget` and evict
+    /// them according to `eviction_policy`, root blocks of added segments are stored in
+    /// `root_block_store`
+    pub fn new(
+        capacity: usize,
+        eviction_policy: EvictionPolicy,
+        root_block_store: RootBlockStore,
+        memory_budget: &MemoryBudget,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                pieces: Mutex::new(Pieces {
+                    eviction_policy,
+                    capacity,
+                    entries: HashMap::new(),
+                    evictable: BTreeSet::new(),
+                    pins: HashMap::new(),
+                    protected: 0,
+                    clock: 0,
+                    stats: PieceCacheStats::default(),
+                    memory_reservation: memory_budget.empty_reservation(MemoryCategory::PieceCache),
+                }),
+                total_pieces: AtomicU64::new(0),
+                root_block_store,
+            }),
+        }
+    }
+
+    /// Max number of pieces this cache can hold
+    pub fn capacity(&self) -> usize {
+        self.inner.pieces.lock().capacity
+    }
+
+    /// Number of pieces currently stored in cache
+    pub fn len(&self) -> usize {
+        self.inner.pieces.lock().entries.len()
+    }
+
+    /// Whether cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total number of pieces in archived history according to the last archived segment added to
+    /// this cache, `None` if no segments were added yet
+    pub fn total_pieces(&self) -> Option<NonZeroU64> {
+        NonZeroU64::new(self.inner.total_pieces.load(Ordering::Acquire))
+    }
+
+    /// Get piece from cache
+    pub fn get_piece(&self, piece_index: PieceIndex) -> Option<Piece> {
+        self.inner.pieces.lock().get(piece_index)
+    }
+
+    /// Pin pieces with `piece_indexes` so they are not evicted until returned guard is dropped,
+    /// pieces that are not in cache yet are pinned once added.
+    ///
+    /// The same piece can be pinned multiple times and stays pinned until all guards are dropped.
+    /// Pieces that would exceed the limit of pinned pieces are not pinned.
+    pub fn pin<I>(&self, piece_indexes: I) -> PinnedPieces
+    where
+        I: IntoIterator<Item = PieceIndex>,
+    {
+        let mut pieces = self.inner.pieces.lock();
+        let piece_indexes = piece_indexes
+            .into_iter()
+            .filter(|&piece_index| pieces.pin(piece_index))
+            .collect();
+
+        PinnedPieces {
+            piece_cache: self.clone(),
+            piece_indexes,
+        }
+    }
+
+    /// Statistics of cache usage so far
+    pub fn stats(&self) -> PieceCacheStats {
+        self.inner.pieces.lock().stats
+    }
+
+    /// Add pieces that were already verified by the caller to the cache, evicting pieces
+    /// according to eviction policy if cache is full or memory budget is reached.
+    ///
+    /// Unlike [`FarmerPieceCache::add_archived_segment()`], neither root block store nor total
+    /// number of pieces are updated.
+    pub fn add_pieces<I>(&self, pieces: I)
+    where
+        I: IntoIterator<Item = (PieceIndex, Piece)>,
+    {
+        let mut cached_pieces = self.inner.pieces.lock();
+        for (piece_index, piece) in pieces {
+            cached_pieces.insert(piece_index, piece);
+        }
+    }
+
+    /// Add all pieces of archived segment to the cache, evicting pieces according to eviction
+    /// policy if cache is full or memory budget is reached, and update total number of pieces.
+    ///
+    /// Segment is rejected if its root block doesn't extend the chain of root blocks in the root
+    /// block store, otherwise root block is added to the store.
+    pub fn add_archived_segment(
+        &self,
+        archived_segment: &ArchivedSegment,
+    ) -> Result<(), RootBlockStoreError> {
+        self.inner
+            .root_block_store
+            .add(&[archived_segment.root_block])?;
+
+        let pieces_in_segment = archived_segment.pieces.count() as u64;
+        let first_piece_index =
+            archived_segment.root_block.segment_index().get() * pieces_in_segment;
+
+        {
+            let mut pieces = self.inner.pieces.lock();
+            for (piece_index, piece) in
+                (first_piece_index..).zip(archived_segment.pieces.as_pieces())
+            {
+                let piece =
+                    Piece::try_from(piece).expect("Flat pieces always contain whole pieces; qed");
+                pieces.insert(piece_index, piece);
+            }
+        }
+
+        self.inner
+            .total_pieces
+            .fetch_max(first_piece_index + pieces_in_segment, Ordering::AcqRel);
+
+        Ok(())
+    }
+
+    /// Remove root blocks and cached pieces of specified segment and all segments after it, total
+    /// number of pieces is lowered accordingly
+    fn remove_segments(&self, first_segment_index: SegmentIndex) -> io::Result<()> {
+        self.inner.root_block_store.truncate(first_segment_index)?;
+
+        let first_piece_index = first_segment_index.first_piece_index();
+        {
+            let mut pieces = self.inner.pieces.lock();
+            let piece_indexes = pieces
+                .entries
+                .keys()
+                .copied()
+                .filter(|&piece_index| piece_index >= first_piece_index)
+                .collect::<Vec<_>>();
+            for piece_index in piece_indexes {
+                pieces.remove(piece_index);
+            }
+        }
+
+        self.inner
+            .total_pieces
+            .fetch_min(first_piece_index, Ordering::AcqRel);
+
+        Ok(())
+    }
+
+    /// Remove segments whose root blocks don't match records roots known to the node, so that
+    /// `rejected_root_block` can be stored afterwards.
+    ///
+    /// Nothing is removed if node doesn't know `rejected_root_block` itself. Otherwise stored root
+    /// blocks are compared with the node going back from the rejected segment until a batch of
+    /// segments fully matches and everything starting with the first mismatch is removed. Returns
+    /// `true` if any segments were removed.
+    async fn resync_root_blocks<RC>(
+        &self,
+        rpc_client: &RC,
+        rejected_root_block: &RootBlock,
+    ) -> Result<bool, RpcError>
+    where
+        RC: RpcClient,
+    {
+        let root_block_store = &self.inner.root_block_store;
+        let segment_index = rejected_root_block.segment_index();
+        let node_records_root = rpc_client
+            .records_roots(vec![segment_index])
+            .await?
+            .into_iter()
+            .next()
+            .flatten();
+        if !node_records_root
+            .map(|records_root| records_root.ct_eq(&rejected_root_block.records_root()))
+            .unwrap_or_default()
+        {
+            return Ok(false);
+        }
+
+        let max_segment_index = match root_block_store.max_segment_index() {
+            Some(max_segment_index) => max_segment_index,
+            None => {
+                return Ok(false);
+            }
+        };
+        // Next segment is included since it is also checked when root block is added
+        let mut batch_end = max_segment_index.min(segment_index + SegmentIndex::ONE);
+        let mut first_mismatch = None;
+        loop {
+            let batch_start = batch_end.saturating_sub(SegmentIndex::new(
+                MAX_SEGMENT_INDEXES_PER_REQUEST as u64 - 1,
+            ));
+            let segment_indexes = (batch_start.get()..=batch_end.get())
+                .map(SegmentIndex::new)
+                .collect::<Vec<_>>();
+            let records_roots = rpc_client.records_roots(segment_indexes.clone()).await?;
+
+            let batch_mismatch = segment_indexes.into_iter().zip(records_roots).find_map(
+                |(segment_index, records_root)| {
+                    let root_block = root_block_store.get(segment_index)?;
+                    let matches = records_root
+                        .map(|records_root| records_root.ct_eq(&root_block.records_root()))
+                        .unwrap_or_default();
+
+                    (!matches).then_some(segment_index)
+                },
+            );
+
+            match batch_mismatch {
+                Some(segment_index) => {
+                    first_mismatch.replace(segment_index);
+                }
+                None => {
+                    break;
+                }
+            }
+            if batch_start == SegmentIndex::ZERO {
+                break;
+            }
+            batch_end = batch_start - SegmentIndex::ONE;
+        }
+
+        match first_mismatch {
+            Some(segment_index) => {
+                info!(%segment_index, "Removing segments that don't match node");
+                self.remove_segments(segment_index)?;
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// Pieces pinned in [`FarmerPieceCache`], unpinned when dropped
+#[derive(Debug)]
+pub struct PinnedPieces {
+    piece_cache: FarmerPieceCache,
+    piece_indexes: Vec<PieceIndex>,
+}
+
+impl Drop for PinnedPieces {
+    fn drop(&mut self) {
+        let mut pieces = self.piece_cache.inner.pieces.lock();
+        for piece_index in self.piece_indexes.drain(..) {
+            pieces.unpin(piece_index);
+        }
+    }
+}
+
+impl PinnedPieces {
+    /// Indexes of pieces that were actually pinned
+    pub fn piece_indexes(&self) -> &[PieceIndex] {
+        &self.piece_indexes
+    }
+}
+
+impl PieceStore for FarmerPieceCache {
+    fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(FarmerPieceCache::get_piece(self, piece_index))
+    }
+}
+
+/// Subscribe to archived segments and keep populating provided piece cache with their pieces.
+///
+/// Insertion into the cache happens on a dedicated low priority thread, so it never competes with
+/// auditing, while limited buffer between subscription and that thread makes sure fast-syncing node
+/// doesn't flood the farmer with segments it is not able to process.
+///
+/// When root block of archived segment is rejected, stored root blocks are re-synced with records
+/// roots known to the node and the segment is added again if any stale ones were removed.
+pub async fn populate_piece_cache<RC>(
+    rpc_client: RC,
+    piece_cache: FarmerPieceCache,
+) -> Result<impl Future<Output = ()>, Box<dyn std::error::Error + Send + Sync>>
+where
+    RC: RpcClient,
+{
+    info!("Subscribing to archived segments for piece cache population");
+
+    let mut archived_segments = rpc_client.subscribe_archived_segments().await?;
+
+    let (mut archived_segments_sender, mut archived_segments_receiver) =
+        mpsc::channel::<ArchivedSegment>(ARCHIVED_SEGMENTS_BUFFER);
+
+    thread::Builder::new()
+        .name("piece-cache".to_string())
+        .spawn(move || {
+            lower_thread_priority();
+
+            while let Some(archived_segment) =
+                futures::executor::block_on(archived_segments_receiver.next())
+            {
+                let segment_index = archived_segment.root_block.segment_index();
+                let error = match piece_cache.add_archived_segment(&archived_segment) {
+                    Ok(()) => {
+                        trace!(%segment_index, "Archived segment added to piece cache");
+                        continue;
+                    }
+                    Err(error) => error,
+                };
+
+                // Stored root blocks might be from a history node doesn't follow anymore, in which
+                // case they'd reject all new segments, so they are checked against the node
+                warn!(
+                    %segment_index,
+                    %error,
+                    "Failed to store root block of archived segment, re-syncing with node"
+                );
+                match futures::executor::block_on(
+                    piece_cache.resync_root_blocks(&rpc_client, &archived_segment.root_block),
+                ) {
+                    Ok(true) => match piece_cache.add_archived_segment(&archived_segment) {
+                        Ok(()) => {
+                            debug!(%segment_index, "Archived segment added after re-sync");
+                        }
+                        Err(error) => {
+                            warn!(
+                                %segment_index,
+                                %error,
+                                "Failed to store root block of archived segment after re-sync, \
+                                ignoring"
+                            );
+                        }
+                    },
+                    Ok(false) => {
+                        warn!(
+                            %segment_index,
+                            "Stored root blocks match node, ignoring archived segment"
+                        );
+                    }
+                    Err(error) => {
+                        warn!(
+                            %segment_index,
+                            %error,
+                            "Failed to re-sync root blocks with node, ignoring archived segment"
+                        );
+                    }
+                }
+            }
+
+            debug!("Piece cache population finished");
+        })?;
+
+    Ok(async move {
+        while let Some(archived_segment) = archived_segments.next().await {
+            // Blocks here when cache thread is behind, which in turn stops pulling notifications
+            if archived_segments_sender
+                .send(archived_segment)
+                .await
+                .is_err()
+            {
+                error!("Piece cache population thread exited unexpectedly");
+                break;
+            }
+        }
+    })
+}
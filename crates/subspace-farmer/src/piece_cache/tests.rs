@@ -0,0 +1,343 @@
+use crate::memory_budget::MemoryBudget;
+use crate::piece_cache::{
+    populate_piece_cache, EvictionPolicy, FarmerPieceCache, PieceCacheStats,
+    ARCHIVED_SEGMENTS_BUFFER,
+};
+use crate::root_block_store::{RootBlockStore, RootBlockStoreError};
+use crate::testing::fixtures::{archived_segment, archived_segments, TestRpcClient};
+use std::num::NonZeroU64;
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{Piece, PieceIndex, SegmentIndex, PIECES_IN_SEGMENT, PIECE_SIZE};
+use tempfile::{tempdir, TempDir};
+
+fn piece_cache(
+    capacity: usize,
+    eviction_policy: EvictionPolicy,
+    memory_budget: &MemoryBudget,
+) -> (FarmerPieceCache, TempDir) {
+    let directory = tempdir().unwrap();
+    let root_block_store = RootBlockStore::open(directory.path()).unwrap();
+
+    (
+        FarmerPieceCache::new(capacity, eviction_policy, root_block_store, memory_budget),
+        directory,
+    )
+}
+
+fn insert(piece_cache: &FarmerPieceCache, piece_indexes: &[PieceIndex]) {
+    let mut pieces = piece_cache.inner.pieces.lock();
+    for &piece_index in piece_indexes {
+        pieces.insert(piece_index, Piece::default());
+    }
+}
+
+fn cached(piece_cache: &FarmerPieceCache) -> Vec<PieceIndex> {
+    let mut piece_indexes = piece_cache
+        .inner
+        .pieces
+        .lock()
+        .entries
+        .keys()
+        .copied()
+        .collect::<Vec<_>>();
+    piece_indexes.sort_unstable();
+    piece_indexes
+}
+
+#[test]
+fn lru_eviction() {
+    let (piece_cache, _directory) = piece_cache(3, EvictionPolicy::Lru, &MemoryBudget::new(None));
+
+    insert(&piece_cache, &[0, 1, 2]);
+    assert!(piece_cache.get_piece(0).is_some());
+    insert(&piece_cache, &[3]);
+    assert_eq!(cached(&piece_cache), vec![0, 2, 3]);
+}
+
+#[test]
+fn lfu_eviction() {
+    let (piece_cache, _directory) = piece_cache(3, EvictionPolicy::Lfu, &MemoryBudget::new(None));
+
+    insert(&piece_cache, &[0, 1, 2]);
+    for _ in 0..2 {
+        assert!(piece_cache.get_piece(0).is_some());
+    }
+    assert!(piece_cache.get_piece(1).is_some());
+    // Piece 2 is the least frequently used even though piece 0 was used before it
+    insert(&piece_cache, &[3]);
+    assert_eq!(cached(&piece_cache), vec![0, 1, 3]);
+    // Newly inserted piece is used the least
+    insert(&piece_cache, &[4]);
+    assert_eq!(cached(&piece_cache), vec![0, 1, 4]);
+}
+
+#[test]
+fn segmented_eviction() {
+    let (piece_cache, _directory) =
+        piece_cache(5, EvictionPolicy::Segmented, &MemoryBudget::new(None));
+
+    insert(&piece_cache, &[0, 1, 2, 3, 4]);
+    // Pieces 0 and 1 are protected
+    assert!(piece_cache.get_piece(0).is_some());
+    assert!(piece_cache.get_piece(1).is_some());
+    // Scan of new pieces only evicts probationary pieces
+    insert(&piece_cache, &[5, 6, 7, 8]);
+    assert_eq!(cached(&piece_cache), vec![0, 1, 6, 7, 8]);
+
+    // Protected segment is limited to 4 pieces, least recently used protected piece is demoted
+    for piece_index in [6, 7, 8] {
+        assert!(piece_cache.get_piece(piece_index).is_some());
+    }
+    insert(&piece_cache, &[9]);
+    assert_eq!(cached(&piece_cache), vec![1, 6, 7, 8, 9]);
+}
+
+#[test]
+fn pinned_pieces_not_evicted() {
+    let (piece_cache, _directory) = piece_cache(4, EvictionPolicy::Lru, &MemoryBudget::new(None));
+
+    insert(&piece_cache, &[0, 1, 2, 3]);
+    // Only half of the capacity can be pinned, piece 3 is not pinned, piece 10 is pinned even
+    // though it is not in cache yet
+    let pinned = piece_cache.pin([0, 10, 3]);
+    assert_eq!(pinned.piece_indexes(), &[0, 10]);
+    let pinned_again = piece_cache.pin([0]);
+    assert_eq!(pinned_again.piece_indexes(), &[0]);
+
+    insert(&piece_cache, &[4, 5, 6]);
+    assert_eq!(cached(&piece_cache), vec![0, 4, 5, 6]);
+    insert(&piece_cache, &[10, 11]);
+    assert_eq!(cached(&piece_cache), vec![0, 6, 10, 11]);
+
+    // Piece stays pinned until all pins are released
+    drop(pinned);
+    insert(&piece_cache, &[12, 13]);
+    assert_eq!(cached(&piece_cache), vec![0, 11, 12, 13]);
+    drop(pinned_again);
+    insert(&piece_cache, &[14]);
+    assert_eq!(cached(&piece_cache), vec![11, 12, 13, 14]);
+}
+
+#[test]
+fn pinning_limited() {
+    let (piece_cache, _directory) = piece_cache(2, EvictionPolicy::Lru, &MemoryBudget::new(None));
+
+    let _pinned = piece_cache.pin([0]);
+    insert(&piece_cache, &[0, 1]);
+    let _pinned_too = piece_cache.pin([1]);
+    // Limit of pinned pieces is 1, so piece 1 is still evictable
+    insert(&piece_cache, &[2]);
+    assert_eq!(cached(&piece_cache), vec![0, 2]);
+}
+
+#[test]
+fn memory_budget_eviction() {
+    let memory_budget = MemoryBudget::new(NonZeroU64::new(2 * PIECE_SIZE as u64));
+    let (piece_cache, _directory) = piece_cache(4, EvictionPolicy::Lfu, &memory_budget);
+
+    insert(&piece_cache, &[0, 1]);
+    assert!(piece_cache.get_piece(1).is_some());
+    insert(&piece_cache, &[2]);
+    assert_eq!(cached(&piece_cache), vec![1, 2]);
+    assert_eq!(memory_budget.usage().piece_cache, 2 * PIECE_SIZE as u64);
+
+    // Pinned pieces are not evicted to fit into memory budget
+    let _pinned = piece_cache.pin([1, 2]);
+    insert(&piece_cache, &[3]);
+    assert_eq!(cached(&piece_cache), vec![1, 2]);
+}
+
+#[test]
+fn stats() {
+    let (piece_cache, _directory) = piece_cache(4, EvictionPolicy::Lru, &MemoryBudget::new(None));
+
+    insert(&piece_cache, &[0, 1]);
+    let _pinned = piece_cache.pin([0]);
+    assert!(piece_cache.get_piece(0).is_some());
+    assert!(piece_cache.get_piece(1).is_some());
+    assert!(piece_cache.get_piece(1).is_some());
+    assert!(piece_cache.get_piece(2).is_none());
+
+    let stats = piece_cache.stats();
+    assert_eq!(
+        stats,
+        PieceCacheStats {
+            pinned_hits: 1,
+            unpinned_hits: 2,
+            misses: 1,
+        }
+    );
+    assert_eq!(stats.hits(), 3);
+}
+
+#[test]
+fn archived_segment_population() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let (piece_cache, _directory) = piece_cache(
+        PIECES_IN_SEGMENT as usize,
+        EvictionPolicy::Lru,
+        &MemoryBudget::new(None),
+    );
+    assert_eq!(piece_cache.total_pieces(), None);
+
+    // Segment with the same index, but of a different history
+    let other_archived_segment = archived_segment(&kzg);
+    let archived_segment = archived_segment(&kzg);
+    piece_cache.add_archived_segment(&archived_segment).unwrap();
+    assert_eq!(
+        piece_cache.total_pieces(),
+        NonZeroU64::new(u64::from(PIECES_IN_SEGMENT))
+    );
+    assert_eq!(piece_cache.len(), PIECES_IN_SEGMENT as usize);
+    for (piece_index, piece) in (0..).zip(archived_segment.pieces.as_pieces()) {
+        assert_eq!(piece_cache.get_piece(piece_index).unwrap().as_ref(), piece);
+    }
+
+    // Segment of a different history is rejected and doesn't change anything
+    assert!(matches!(
+        piece_cache.add_archived_segment(&other_archived_segment),
+        Err(RootBlockStoreError::ConflictingRootBlock { segment_index })
+            if segment_index == SegmentIndex::ZERO
+    ));
+    assert_eq!(
+        piece_cache.get_piece(0).unwrap().as_ref(),
+        archived_segment.pieces.as_pieces().next().unwrap()
+    );
+
+    // Pieces added directly don't change total number of pieces
+    piece_cache.add_pieces([(u64::from(PIECES_IN_SEGMENT) * 5, Piece::default())]);
+    assert_eq!(
+        piece_cache.total_pieces(),
+        NonZeroU64::new(u64::from(PIECES_IN_SEGMENT))
+    );
+}
+
+#[tokio::test]
+async fn population_backpressure() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let segment_count = ARCHIVED_SEGMENTS_BUFFER + 4;
+    let archived_segments = archived_segments(&kzg, segment_count);
+    let (piece_cache, _directory) = piece_cache(
+        PIECES_IN_SEGMENT as usize,
+        EvictionPolicy::Lru,
+        &MemoryBudget::new(None),
+    );
+
+    // Cache is locked, so population thread gets stuck on the first segment
+    let (locked_sender, locked_receiver) = mpsc::channel();
+    let (release_sender, release_receiver) = mpsc::channel::<()>();
+    let lock_thread = thread::spawn({
+        let piece_cache = piece_cache.clone();
+
+        move || {
+            let _pieces = piece_cache.inner.pieces.lock();
+            locked_sender.send(()).unwrap();
+            let _ = release_receiver.recv();
+        }
+    });
+    locked_receiver.recv().unwrap();
+
+    let rpc_client =
+        TestRpcClient::new(&archived_segments).with_subscription(archived_segments.clone());
+    let pulled = Arc::clone(&rpc_client.pulled);
+    let population = tokio::spawn(
+        populate_piece_cache(rpc_client, piece_cache.clone())
+            .await
+            .unwrap(),
+    );
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    // One segment is being added, the buffer is full (channel has an extra slot for its only
+    // sender) and one more segment is waiting for space in the buffer, nothing else is pulled from
+    // the node
+    let pulled_while_stuck = pulled.load(Ordering::SeqCst);
+    assert!(
+        pulled_while_stuck <= ARCHIVED_SEGMENTS_BUFFER + 3,
+        "Pulled {pulled_while_stuck} segments while cache thread was stuck"
+    );
+    assert!(pulled_while_stuck < segment_count);
+
+    drop(release_sender);
+    lock_thread.join().unwrap();
+    population.await.unwrap();
+    assert_eq!(pulled.load(Ordering::SeqCst), segment_count);
+
+    // The last segment may still be processed after all segments were pulled
+    let expected_total_pieces =
+        NonZeroU64::new(segment_count as u64 * u64::from(PIECES_IN_SEGMENT));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while piece_cache.total_pieces() != expected_total_pieces {
+        assert!(
+            Instant::now() < deadline,
+            "Population didn't finish in time"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn population_resyncs_root_blocks() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let stale_archived_segments = archived_segments(&kzg, 2);
+    let archived_segments = archived_segments(&kzg, 3);
+    let (piece_cache, _directory) = piece_cache(
+        PIECES_IN_SEGMENT as usize * 3,
+        EvictionPolicy::Lru,
+        &MemoryBudget::new(None),
+    );
+    for archived_segment in &stale_archived_segments {
+        piece_cache.add_archived_segment(archived_segment).unwrap();
+    }
+
+    // Nothing is removed if node doesn't know rejected segment
+    let rpc_client = TestRpcClient::new(&stale_archived_segments);
+    assert!(!piece_cache
+        .resync_root_blocks(&rpc_client, &archived_segments[1].root_block)
+        .await
+        .unwrap());
+    assert_eq!(
+        piece_cache.inner.root_block_store.get(SegmentIndex::ONE),
+        Some(stale_archived_segments[1].root_block)
+    );
+
+    // Node follows a different history, so stale segments are removed and new ones are added
+    let rpc_client =
+        TestRpcClient::new(&archived_segments).with_subscription(archived_segments[1..].to_vec());
+    populate_piece_cache(rpc_client, piece_cache.clone())
+        .await
+        .unwrap()
+        .await;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while piece_cache.inner.root_block_store.get(SegmentIndex::new(2))
+        != Some(archived_segments[2].root_block)
+    {
+        assert!(Instant::now() < deadline, "Re-sync didn't finish in time");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let root_block_store = &piece_cache.inner.root_block_store;
+    assert_eq!(root_block_store.get(SegmentIndex::ZERO), None);
+    assert_eq!(
+        root_block_store.get(SegmentIndex::ONE),
+        Some(archived_segments[1].root_block)
+    );
+    assert_eq!(
+        piece_cache.total_pieces(),
+        NonZeroU64::new(u64::from(PIECES_IN_SEGMENT) * 3)
+    );
+    // Pieces of stale history are not served anymore
+    assert_eq!(cached(&piece_cache).len(), PIECES_IN_SEGMENT as usize * 2);
+    assert!(piece_cache.get_piece(0).is_none());
+    assert_eq!(
+        piece_cache
+            .get_piece(u64::from(PIECES_IN_SEGMENT))
+            .unwrap()
+            .as_ref(),
+        archived_segments[1].pieces.as_pieces().next().unwrap()
+    );
+}
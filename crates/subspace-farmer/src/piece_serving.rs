@@ -0,0 +1,360 @@
+//! Serving of pieces to DSN peers with fair queuing.
+//!
+//! Every peer has its own queue of requests and queues are served in round-robin order, so an
+//! aggressive peer only ever delays its own requests. Serving bandwidth is shared by all peers and
+//! limited globally, number of requests of a single peer that are served concurrently or waiting
+//! in queue is limited as well, requests beyond that are rejected with a hint when to retry.
+//!
+//! All pieces have the same size, so round-robin over requests is the same as deficit round-robin
+//! over bytes.
+
+#[cfg(test)]
+mod tests;
+
+use crate::clock::{Clock, TokioClock};
+use crate::single_disk_plot::piece_receiver::BandwidthLimit;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::sync::Arc;
+use std::time::Duration;
+use subspace_core_primitives::{Piece, PIECE_SIZE};
+use subspace_networking::libp2p::PeerId;
+use thiserror::Error;
+use tokio::sync::{oneshot, Notify};
+use tracing::trace;
+
+/// Retry hint for rejected requests when serving bandwidth is not limited
+const MIN_RETRY_AFTER: Duration = Duration::from_secs(1);
+/// Number of peers whose statistics are kept, idle peers that were served the least are forgotten
+/// beyond that
+const MAX_TRACKED_PEERS: usize = 10_000;
+
+/// Limits of piece serving, can be changed at runtime with [`PieceServer::set_limits()`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PieceServingLimits {
+    /// Serving bandwidth in bytes per second shared by all peers, `None` means no limit
+    pub bandwidth: Option<NonZeroU64>,
+    /// Number of requests of a single peer that are served concurrently
+    pub max_concurrent_requests_per_peer: NonZeroUsize,
+    /// Number of requests of a single peer that wait for their turn, further requests are rejected
+    pub max_queued_requests_per_peer: usize,
+}
+
+impl Default for PieceServingLimits {
+    fn default() -> Self {
+        Self {
+            bandwidth: None,
+            max_concurrent_requests_per_peer: NonZeroUsize::new(4).expect("Not zero; qed"),
+            max_queued_requests_per_peer: 32,
+        }
+    }
+}
+
+/// Piece serving statistics of a single peer
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerServingStats {
+    /// Bytes of pieces served to the peer
+    pub served_bytes: u64,
+    /// Requests rejected because queue of the peer was full
+    pub rejected_requests: u64,
+    /// Requests waiting for their turn
+    pub queued_requests: usize,
+    /// Requests being served
+    pub in_flight_requests: usize,
+}
+
+/// Errors that happen during [`PieceServer::serve()`]
+#[derive(Debug, Error)]
+pub enum PieceServingError {
+    /// Too many requests of the peer are waiting already
+    #[error("Too many queued requests from peer, retry after {retry_after:?}")]
+    QueueFull {
+        /// Time after which request is likely to be accepted
+        retry_after: Duration,
+    },
+    /// Piece server was dropped before request was served
+    #[error("Piece server was dropped before request was served")]
+    Stopped,
+}
+
+#[derive(Debug, Default)]
+struct PeerState {
+    /// Requests waiting for their turn, cancelled requests are removed lazily
+    queue: VecDeque<oneshot::Sender<ServingPermit>>,
+    served_bytes: u64,
+    rejected_requests: u64,
+    in_flight_requests: usize,
+}
+
+impl PeerState {
+    fn is_idle(&self) -> bool {
+        self.queue.is_empty() && self.in_flight_requests == 0
+    }
+
+    fn stats(&self) -> PeerServingStats {
+        PeerServingStats {
+            served_bytes: self.served_bytes,
+            rejected_requests: self.rejected_requests,
+            queued_requests: self.queue.len(),
+            in_flight_requests: self.in_flight_requests,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    limits: PieceServingLimits,
+    peers: HashMap<PeerId, PeerState>,
+    /// Peers with queued requests in the order they will be served, peer is here if and only if
+    /// its queue is not empty
+    round_robin: VecDeque<PeerId>,
+}
+
+impl State {
+    /// Forget idle peer that was served the least to make space for a new one
+    fn forget_idle_peer(&mut self) {
+        let peer_id = self
+            .peers
+            .iter()
+            .filter(|(_peer_id, peer)| peer.is_idle())
+            .min_by_key(|(_peer_id, peer)| peer.served_bytes)
+            .map(|(peer_id, _peer)| *peer_id);
+
+        if let Some(peer_id) = peer_id {
+            self.peers.remove(&peer_id);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: Mutex<State>,
+    bandwidth_limit: BandwidthLimit,
+    /// Notified when request is queued or finished, or limits change
+    changed: Notify,
+}
+
+/// Permission to serve one request of a peer, peer's request is considered in flight until permit
+/// is dropped
+#[derive(Debug)]
+struct ServingPermit {
+    inner: Arc<Inner>,
+    peer_id: PeerId,
+}
+
+impl Drop for ServingPermit {
+    fn drop(&mut self) {
+        if let Some(peer) = self.inner.state.lock().peers.get_mut(&self.peer_id) {
+            peer.in_flight_requests -= 1;
+        }
+        self.inner.changed.notify_one();
+    }
+}
+
+/// Fair scheduler of piece requests from DSN peers.
+///
+/// Clones share the same queues and limits. Requests are only served while [`PieceServer::run()`]
+/// is running.
+#[derive(Debug, Clone)]
+pub struct PieceServer<C = TokioClock> {
+    inner: Arc<Inner>,
+    clock: C,
+}
+
+impl PieceServer {
+    /// Create new piece server with specified limits
+    pub fn new(limits: PieceServingLimits) -> Self {
+        Self::with_clock(limits, TokioClock)
+    }
+}
+
+impl<C> PieceServer<C>
+where
+    C: Clock,
+{
+    /// Same as [`PieceServer::new()`], but bandwidth limit waits according to `clock`
+    pub fn with_clock(limits: PieceServingLimits, clock: C) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    limits,
+                    peers: HashMap::new(),
+                    round_robin: VecDeque::new(),
+                }),
+                bandwidth_limit: BandwidthLimit::new(limits.bandwidth),
+                changed: Notify::new(),
+            }),
+            clock,
+        }
+    }
+
+    /// Current limits
+    pub fn limits(&self) -> PieceServingLimits {
+        self.inner.state.lock().limits
+    }
+
+    /// Change limits, new limits apply to requests that are not served yet
+    pub fn set_limits(&self, limits: PieceServingLimits) {
+        self.inner.state.lock().limits = limits;
+        self.inner
+            .bandwidth_limit
+            .set_bytes_per_second(limits.bandwidth);
+        // Peers might be allowed to have more requests in flight now
+        self.inner.changed.notify_one();
+    }
+
+    /// Serving statistics of peers that requested pieces
+    pub fn stats(&self) -> HashMap<PeerId, PeerServingStats> {
+        self.inner
+            .state
+            .lock()
+            .peers
+            .iter()
+            .map(|(peer_id, peer)| (*peer_id, peer.stats()))
+            .collect()
+    }
+
+    /// Wait for the turn of request of `peer_id` and serve it with `read_piece`, request is
+    /// rejected immediately if too many requests of the peer are waiting already
+    pub async fn serve<F, Fut>(
+        &self,
+        peer_id: PeerId,
+        read_piece: F,
+    ) -> Result<Option<Piece>, PieceServingError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Option<Piece>>,
+    {
+        let permit_receiver = {
+            let mut state = self.inner.state.lock();
+            let state = &mut *state;
+            let limits = state.limits;
+
+            if !state.peers.contains_key(&peer_id) && state.peers.len() >= MAX_TRACKED_PEERS {
+                state.forget_idle_peer();
+            }
+
+            let peer = state.peers.entry(peer_id).or_default();
+            if peer.queue.len() >= limits.max_queued_requests_per_peer {
+                peer.rejected_requests += 1;
+
+                let queued_requests = state
+                    .peers
+                    .values()
+                    .map(|peer| peer.queue.len())
+                    .sum::<usize>();
+
+                return Err(PieceServingError::QueueFull {
+                    retry_after: retry_after(limits.bandwidth, queued_requests),
+                });
+            }
+
+            let (permit_sender, permit_receiver) = oneshot::channel();
+            peer.queue.push_back(permit_sender);
+            if peer.queue.len() == 1 {
+                state.round_robin.push_back(peer_id);
+            }
+
+            permit_receiver
+        };
+        self.inner.changed.notify_one();
+
+        let _permit = permit_receiver
+            .await
+            .map_err(|_error| PieceServingError::Stopped)?;
+
+        let maybe_piece = read_piece().await;
+        if maybe_piece.is_some() {
+            if let Some(peer) = self.inner.state.lock().peers.get_mut(&peer_id) {
+                peer.served_bytes += PIECE_SIZE as u64;
+            }
+        }
+
+        Ok(maybe_piece)
+    }
+
+    /// Serve queued requests, never returns
+    pub async fn run(&self) {
+        loop {
+            let (permit_sender, permit) = match self.next_request() {
+                Some(next_request) => next_request,
+                None => {
+                    self.inner.changed.notified().await;
+                    continue;
+                }
+            };
+
+            self.inner
+                .bandwidth_limit
+                .acquire(PIECE_SIZE as u64, &self.clock)
+                .await;
+
+            if permit_sender.send(permit).is_err() {
+                trace!("Piece request was cancelled while waiting for bandwidth");
+            }
+        }
+    }
+
+    /// Take the next request in round-robin order from peers that have fewer than allowed requests
+    /// in flight
+    fn next_request(&self) -> Option<(oneshot::Sender<ServingPermit>, ServingPermit)> {
+        let mut state = self.inner.state.lock();
+        let state = &mut *state;
+        let max_concurrent_requests = state.limits.max_concurrent_requests_per_peer.get();
+
+        for _ in 0..state.round_robin.len() {
+            let peer_id = state.round_robin.pop_front()?;
+            let peer = state
+                .peers
+                .get_mut(&peer_id)
+                .expect("Peers in round-robin queue have queued requests; qed");
+
+            // Requests might have been cancelled while waiting
+            while matches!(peer.queue.front(), Some(permit_sender) if permit_sender.is_closed()) {
+                peer.queue.pop_front();
+            }
+            if peer.queue.is_empty() {
+                continue;
+            }
+            if peer.in_flight_requests >= max_concurrent_requests {
+                state.round_robin.push_back(peer_id);
+                continue;
+            }
+
+            let permit_sender = peer
+                .queue
+                .pop_front()
+                .expect("Checked that queue is not empty above; qed");
+            peer.in_flight_requests += 1;
+            if !peer.queue.is_empty() {
+                state.round_robin.push_back(peer_id);
+            }
+
+            let permit = ServingPermit {
+                inner: Arc::clone(&self.inner),
+                peer_id,
+            };
+
+            return Some((permit_sender, permit));
+        }
+
+        None
+    }
+}
+
+/// Time after which rejected request is likely to be accepted, given that `queued_requests` are
+/// waiting to be served
+fn retry_after(bandwidth: Option<NonZeroU64>, queued_requests: usize) -> Duration {
+    match bandwidth {
+        Some(bandwidth) => Duration::from_secs_f64(
+            queued_requests as f64 * PIECE_SIZE as f64 / bandwidth.get() as f64,
+        )
+        .max(MIN_RETRY_AFTER),
+        None => MIN_RETRY_AFTER,
+    }
+}
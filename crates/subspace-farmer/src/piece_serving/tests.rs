@@ -0,0 +1,210 @@
+use crate::piece_serving::{PeerServingStats, PieceServer, PieceServingError, PieceServingLimits};
+use parking_lot::Mutex;
+use std::iter;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::sync::Arc;
+use std::time::Duration;
+use subspace_core_primitives::{Piece, PIECE_SIZE};
+use subspace_networking::libp2p::PeerId;
+use tokio::sync::Semaphore;
+
+/// Wait until statistics of `peer_id` satisfy `predicate`
+async fn wait_for_stats<P>(piece_server: &PieceServer, peer_id: PeerId, predicate: P)
+where
+    P: Fn(PeerServingStats) -> bool,
+{
+    while !predicate(
+        piece_server
+            .stats()
+            .get(&peer_id)
+            .copied()
+            .unwrap_or_default(),
+    ) {
+        tokio::task::yield_now().await;
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn peers_are_served_in_turns() {
+    let piece_server = PieceServer::new(PieceServingLimits {
+        bandwidth: NonZeroU64::new(PIECE_SIZE as u64),
+        max_concurrent_requests_per_peer: NonZeroUsize::new(16).unwrap(),
+        max_queued_requests_per_peer: 16,
+    });
+    let aggressive_peer = PeerId::random();
+    let polite_peer = PeerId::random();
+    let served = Arc::new(Mutex::new(Vec::new()));
+
+    let requests = iter::repeat(aggressive_peer)
+        .take(6)
+        .chain(iter::repeat(polite_peer).take(2))
+        .map(|peer_id| {
+            let piece_server = piece_server.clone();
+            let served = Arc::clone(&served);
+
+            tokio::spawn(async move {
+                piece_server
+                    .serve(peer_id, || async move {
+                        served.lock().push(peer_id);
+                        Some(Piece::default())
+                    })
+                    .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Everything is queued before serving starts, aggressive peer is ahead of polite one
+    wait_for_stats(&piece_server, aggressive_peer, |stats| {
+        stats.queued_requests == 6
+    })
+    .await;
+    wait_for_stats(&piece_server, polite_peer, |stats| {
+        stats.queued_requests == 2
+    })
+    .await;
+
+    let server = tokio::spawn({
+        let piece_server = piece_server.clone();
+
+        async move { piece_server.run().await }
+    });
+
+    for request in requests {
+        assert!(request.await.unwrap().unwrap().is_some());
+    }
+    server.abort();
+
+    assert_eq!(
+        *served.lock(),
+        vec![
+            aggressive_peer,
+            polite_peer,
+            aggressive_peer,
+            polite_peer,
+            aggressive_peer,
+            aggressive_peer,
+            aggressive_peer,
+            aggressive_peer,
+        ]
+    );
+    let stats = piece_server.stats();
+    assert_eq!(stats[&aggressive_peer].served_bytes, 6 * PIECE_SIZE as u64);
+    assert_eq!(stats[&polite_peer].served_bytes, 2 * PIECE_SIZE as u64);
+}
+
+#[tokio::test]
+async fn full_queue_rejects_requests() {
+    let piece_server = PieceServer::new(PieceServingLimits {
+        bandwidth: NonZeroU64::new(PIECE_SIZE as u64),
+        max_concurrent_requests_per_peer: NonZeroUsize::new(1).unwrap(),
+        max_queued_requests_per_peer: 2,
+    });
+    let peer_id = PeerId::random();
+
+    // Server is not running, so requests stay in queue
+    let _requests = (0..2)
+        .map(|_| {
+            let piece_server = piece_server.clone();
+
+            tokio::spawn(async move {
+                piece_server
+                    .serve(peer_id, || async { Some(Piece::default()) })
+                    .await
+            })
+        })
+        .collect::<Vec<_>>();
+    wait_for_stats(&piece_server, peer_id, |stats| stats.queued_requests == 2).await;
+
+    match piece_server
+        .serve(peer_id, || async { Some(Piece::default()) })
+        .await
+    {
+        Err(PieceServingError::QueueFull { retry_after }) => {
+            // Two queued pieces take two seconds at configured bandwidth
+            assert_eq!(retry_after, Duration::from_secs(2));
+        }
+        result => {
+            panic!("Expected request to be rejected, got {result:?}");
+        }
+    }
+    assert_eq!(piece_server.stats()[&peer_id].rejected_requests, 1);
+
+    // Other peers are not affected
+    let other_peer_id = PeerId::random();
+    tokio::spawn({
+        let piece_server = piece_server.clone();
+
+        async move {
+            piece_server
+                .serve(other_peer_id, || async { Some(Piece::default()) })
+                .await
+        }
+    });
+    wait_for_stats(&piece_server, other_peer_id, |stats| {
+        stats.queued_requests == 1
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn concurrency_limit_is_adjustable() {
+    let piece_server = PieceServer::new(PieceServingLimits {
+        bandwidth: None,
+        max_concurrent_requests_per_peer: NonZeroUsize::new(1).unwrap(),
+        max_queued_requests_per_peer: 8,
+    });
+    let peer_id = PeerId::random();
+    let disk = Arc::new(Semaphore::new(0));
+
+    let server = tokio::spawn({
+        let piece_server = piece_server.clone();
+
+        async move { piece_server.run().await }
+    });
+
+    let requests = (0..3)
+        .map(|_| {
+            let piece_server = piece_server.clone();
+            let disk = Arc::clone(&disk);
+
+            tokio::spawn(async move {
+                piece_server
+                    .serve(peer_id, || async move {
+                        let _permit = disk.acquire().await.unwrap();
+                        Some(Piece::default())
+                    })
+                    .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    wait_for_stats(&piece_server, peer_id, |stats| {
+        stats.in_flight_requests == 1 && stats.queued_requests == 2
+    })
+    .await;
+
+    piece_server.set_limits(PieceServingLimits {
+        max_concurrent_requests_per_peer: NonZeroUsize::new(3).unwrap(),
+        ..piece_server.limits()
+    });
+    wait_for_stats(&piece_server, peer_id, |stats| {
+        stats.in_flight_requests == 3 && stats.queued_requests == 0
+    })
+    .await;
+
+    disk.add_permits(3);
+    for request in requests {
+        assert!(request.await.unwrap().unwrap().is_some());
+    }
+    server.abort();
+
+    assert_eq!(
+        piece_server.stats()[&peer_id],
+        PeerServingStats {
+            served_bytes: 3 * PIECE_SIZE as u64,
+            rejected_requests: 0,
+            queued_requests: 0,
+            in_flight_requests: 0,
+        }
+    );
+}
@@ -0,0 +1,122 @@
+//! Deduplication of errors that repeat on every slot.
+//!
+//! When node is down or disk is failing, the same error happens over and over again and logging
+//! all of them hides everything else. Repeats of the same kind of error are collapsed, only the
+//! first occurrence is logged right away and then a summary at most once per interval.
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Default interval between summaries of repeated errors
+pub(crate) const SUMMARY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Errors of the same kind that should be logged
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct Repeats {
+    /// Number of errors since previous log, including the one that is being logged
+    pub(crate) count: u64,
+    /// Time since previous log
+    pub(crate) interval: Duration,
+}
+
+impl fmt::Display for Repeats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count == 1 {
+            write!(f, "first occurrence")
+        } else {
+            write!(f, "×{} in last {:?}", self.count, self.interval)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ErrorKindState {
+    last_logged: Instant,
+    suppressed: u64,
+}
+
+/// Collapses repeats of the same kind of error into periodic summaries, kind is typically a
+/// discriminant of the error.
+#[derive(Debug)]
+pub(crate) struct RepeatedErrors<K> {
+    summary_interval: Duration,
+    kinds: HashMap<K, ErrorKindState>,
+}
+
+impl<K> Default for RepeatedErrors<K> {
+    fn default() -> Self {
+        Self::new(SUMMARY_INTERVAL)
+    }
+}
+
+impl<K> RepeatedErrors<K> {
+    pub(crate) fn new(summary_interval: Duration) -> Self {
+        Self {
+            summary_interval,
+            kinds: HashMap::new(),
+        }
+    }
+
+    /// Operation succeeded, forget about all errors. Returns number of errors that were suppressed
+    /// since they were logged last time, if there were any.
+    pub(crate) fn success(&mut self) -> Option<u64> {
+        if self.kinds.is_empty() {
+            return None;
+        }
+
+        let suppressed: u64 = self
+            .kinds
+            .drain()
+            .map(|(_kind, state)| state.suppressed)
+            .sum();
+        (suppressed > 0).then_some(suppressed)
+    }
+}
+
+impl<K> RepeatedErrors<K>
+where
+    K: Hash + Eq,
+{
+    /// Record error of `kind`, returns `Some` if it should be logged
+    pub(crate) fn error(&mut self, kind: K) -> Option<Repeats> {
+        self.error_at(kind, Instant::now())
+    }
+
+    fn error_at(&mut self, kind: K, now: Instant) -> Option<Repeats> {
+        let summary_interval = self.summary_interval;
+        match self.kinds.get_mut(&kind) {
+            Some(state) => {
+                let interval = now.saturating_duration_since(state.last_logged);
+                if interval < summary_interval {
+                    state.suppressed += 1;
+                    return None;
+                }
+
+                let count = state.suppressed + 1;
+                state.last_logged = now;
+                state.suppressed = 0;
+
+                Some(Repeats { count, interval })
+            }
+            None => {
+                self.kinds.insert(
+                    kind,
+                    ErrorKindState {
+                        last_logged: now,
+                        suppressed: 0,
+                    },
+                );
+
+                Some(Repeats {
+                    count: 1,
+                    interval: Duration::ZERO,
+                })
+            }
+        }
+    }
+}
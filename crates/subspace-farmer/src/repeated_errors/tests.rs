@@ -0,0 +1,93 @@
+use crate::repeated_errors::{RepeatedErrors, Repeats};
+use std::time::{Duration, Instant};
+
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Hash, Eq, PartialEq)]
+enum ErrorKind {
+    Read,
+    Submit,
+}
+
+#[test]
+fn repeats_are_summarized() {
+    let mut repeated_errors = RepeatedErrors::new(SUMMARY_INTERVAL);
+    let start = Instant::now();
+
+    // First occurrence is logged right away
+    assert_eq!(
+        repeated_errors.error_at(ErrorKind::Read, start),
+        Some(Repeats {
+            count: 1,
+            interval: Duration::ZERO
+        })
+    );
+    // Repeats within interval are counted, but not logged
+    for second in 1..60 {
+        assert_eq!(
+            repeated_errors.error_at(ErrorKind::Read, start + Duration::from_secs(second)),
+            None
+        );
+    }
+    // Other kinds of errors are independent
+    assert_eq!(
+        repeated_errors
+            .error_at(ErrorKind::Submit, start + Duration::from_secs(30))
+            .map(|repeats| repeats.count),
+        Some(1)
+    );
+
+    // Summary includes all repeats since the first occurrence
+    let repeats = repeated_errors
+        .error_at(ErrorKind::Read, start + SUMMARY_INTERVAL)
+        .unwrap();
+    assert_eq!(
+        repeats,
+        Repeats {
+            count: 60,
+            interval: SUMMARY_INTERVAL
+        }
+    );
+    assert_eq!(repeats.to_string(), "×60 in last 60s");
+
+    // Counting starts over after summary
+    assert_eq!(
+        repeated_errors.error_at(ErrorKind::Read, start + Duration::from_secs(61)),
+        None
+    );
+    assert_eq!(
+        repeated_errors
+            .error_at(ErrorKind::Read, start + Duration::from_secs(200))
+            .map(|repeats| repeats.count),
+        Some(2)
+    );
+}
+
+#[test]
+fn success_resets() {
+    let mut repeated_errors = RepeatedErrors::new(SUMMARY_INTERVAL);
+    let start = Instant::now();
+
+    // Nothing to flush without errors
+    assert_eq!(repeated_errors.success(), None);
+
+    // Errors that were logged already are not counted as suppressed
+    assert!(repeated_errors.error_at(ErrorKind::Read, start).is_some());
+    assert_eq!(repeated_errors.success(), None);
+
+    // Suppressed errors of all kinds are flushed
+    assert!(repeated_errors.error_at(ErrorKind::Read, start).is_some());
+    assert!(repeated_errors.error_at(ErrorKind::Read, start).is_none());
+    assert!(repeated_errors.error_at(ErrorKind::Submit, start).is_some());
+    assert!(repeated_errors.error_at(ErrorKind::Submit, start).is_none());
+    assert!(repeated_errors.error_at(ErrorKind::Submit, start).is_none());
+    assert_eq!(repeated_errors.success(), Some(3));
+
+    // After success the same error is logged right away again
+    assert_eq!(
+        repeated_errors
+            .error_at(ErrorKind::Read, start + Duration::from_secs(1))
+            .map(|repeats| repeats.to_string()),
+        Some("first occurrence".to_string())
+    );
+}
@@ -1,13 +1,18 @@
 use crate::identity::Identity;
+use crate::repeated_errors::RepeatedErrors;
 use crate::rpc_client::RpcClient;
+use crate::single_disk_plot::duplicate_plot::DuplicatePlotDetector;
 use futures::StreamExt;
 use std::future::Future;
+use std::time::Instant;
+use subspace_core_primitives::PublicKey;
 use subspace_rpc_primitives::{RewardSignatureResponse, RewardSigningInfo};
 use tracing::{info, warn};
 
 pub async fn reward_signing<RC>(
     rpc_client: RC,
     identity: Identity,
+    duplicate_plot_detector: Option<DuplicatePlotDetector>,
 ) -> Result<impl Future<Output = ()>, Box<dyn std::error::Error + Send + Sync>>
 where
     RC: RpcClient,
@@ -17,6 +22,8 @@ where
     let mut reward_signing_info_notifications = rpc_client.subscribe_reward_signing().await?;
 
     let reward_signing_fut = async move {
+        let mut submission_errors = RepeatedErrors::<()>::default();
+
         while let Some(RewardSigningInfo { hash, public_key }) =
             reward_signing_info_notifications.next().await
         {
@@ -25,6 +32,22 @@ where
                 continue;
             }
 
+            if let Some(duplicate_plot_detector) = &duplicate_plot_detector {
+                let expected = duplicate_plot_detector.reward_signing_requested(
+                    PublicKey::from(public_key),
+                    hash,
+                    Instant::now(),
+                );
+                // Don't help somebody else to win with a copy of this plot
+                if !expected && duplicate_plot_detector.submissions_paused() {
+                    warn!(
+                        "Not signing unexpected reward hash 0x{}, duplicate plot was detected",
+                        hex::encode(hash)
+                    );
+                    continue;
+                }
+            }
+
             let signature = identity.sign_reward_hash(&hash);
 
             match rpc_client
@@ -35,14 +58,23 @@ where
                 .await
             {
                 Ok(_) => {
+                    if let Some(suppressed) = submission_errors.success() {
+                        info!(
+                            %suppressed,
+                            "Reward signature submission recovered after repeated errors"
+                        );
+                    }
                     info!("Successfully signed reward hash 0x{}", hex::encode(hash));
                 }
                 Err(error) => {
-                    warn!(
-                        %error,
-                        "Failed to send signature for reward hash 0x{}",
-                        hex::encode(hash),
-                    );
+                    if let Some(repeats) = submission_errors.error(()) {
+                        warn!(
+                            %error,
+                            %repeats,
+                            "Failed to send signature for reward hash 0x{}",
+                            hex::encode(hash),
+                        );
+                    }
                 }
             }
         }
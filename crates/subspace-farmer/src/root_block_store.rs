@@ -0,0 +1,281 @@
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use parity_scale_codec::{Decode, Encode};
+use parking_lot::Mutex;
+use std::cmp::Ordering;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, fs, io};
+use subspace_archiving::archiver::{validate_root_block_chain, RootBlockChainError};
+use subspace_core_primitives::crypto::blake2b_256_hash;
+use subspace_core_primitives::{RootBlock, SegmentIndex, BLAKE2B_256_HASH_SIZE};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Size of one slot in the file, the first slot is occupied by the header, slot for each root block
+/// is at a fixed offset derived from its segment index.
+///
+/// Slot contains checksum of the rest of the slot followed by encoded root block padded with zeroes,
+/// there is plenty of space left for future root block versions.
+const SLOT_SIZE: u64 = 256;
+
+/// Errors happening when working with root block store
+#[derive(Debug, Error)]
+pub enum RootBlockStoreError {
+    /// I/O error occurred
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// Failed to decode root block store header
+    #[error("Failed to decode root block store header: {0}")]
+    FailedToDecodeHeader(parity_scale_codec::Error),
+    /// Root block store was created by newer version of the farmer
+    #[error(
+        "Root block store version {version} is newer than supported version {supported_version}"
+    )]
+    UnsupportedVersion {
+        /// Version found on disk
+        version: u8,
+        /// Latest version supported by this farmer
+        supported_version: u8,
+    },
+    /// Different root block was already stored for the same segment
+    #[error("Different root block was already stored for segment {segment_index}")]
+    ConflictingRootBlock {
+        /// Segment index
+        segment_index: SegmentIndex,
+    },
+    /// Root block doesn't form a valid chain with already stored root blocks
+    #[error("Root block doesn't form a valid chain with already stored root blocks: {0}")]
+    InvalidChain(#[from] RootBlockChainError),
+}
+
+#[derive(Debug, Encode, Decode)]
+struct RootBlockStoreHeader {
+    /// Version of the on-disk layout.
+    ///
+    /// Versions that are older than the latest one are upgraded on open.
+    version: u8,
+}
+
+impl RootBlockStoreHeader {
+    const LATEST_VERSION: u8 = 0;
+}
+
+struct Inner {
+    file: File,
+    /// Root blocks indexed by segment index, `None` for segments that were not seen yet
+    root_blocks: Mutex<Vec<Option<RootBlock>>>,
+}
+
+/// Persistent store of root blocks known to the farmer.
+///
+/// Stored in a flat file where each root block occupies a fixed size slot at offset derived from
+/// its segment index, which makes lookups O(1). Every slot is protected with a checksum, so slot
+/// that was partially written during crash is treated as missing rather than corrupting the store.
+#[derive(Clone)]
+pub struct RootBlockStore {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for RootBlockStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RootBlockStore")
+            .field("max_segment_index", &self.max_segment_index())
+            .finish()
+    }
+}
+
+impl RootBlockStore {
+    /// Name of the file in directory where root blocks are stored
+    pub const FILE_NAME: &'static str = "root_blocks.bin";
+
+    /// Open root block store in specified directory, creating it if necessary
+    pub fn open(directory: &Path) -> Result<Self, RootBlockStoreError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(directory.join(Self::FILE_NAME))?;
+
+        // Header is missing entirely or was not written completely
+        if file.metadata()?.len() < SLOT_SIZE {
+            Self::write_header(&file)?;
+        }
+
+        let mut header_bytes = vec![0; SLOT_SIZE as usize];
+        file.read_exact_at(&mut header_bytes, 0)?;
+        let header = RootBlockStoreHeader::decode(&mut header_bytes.as_slice())
+            .map_err(RootBlockStoreError::FailedToDecodeHeader)?;
+
+        match header.version.cmp(&RootBlockStoreHeader::LATEST_VERSION) {
+            Ordering::Less => {
+                // Root blocks are always available from the node, so older layouts are not
+                // converted, store is simply started from scratch
+                info!(
+                    version = header.version,
+                    "Root block store uses old layout, re-creating"
+                );
+                file.set_len(0)?;
+                Self::write_header(&file)?;
+            }
+            Ordering::Equal => {}
+            Ordering::Greater => {
+                return Err(RootBlockStoreError::UnsupportedVersion {
+                    version: header.version,
+                    supported_version: RootBlockStoreHeader::LATEST_VERSION,
+                });
+            }
+        }
+
+        let slots = file.metadata()?.len() / SLOT_SIZE - 1;
+        let mut root_blocks = Vec::with_capacity(slots as usize);
+        let mut slot = vec![0; SLOT_SIZE as usize];
+        for segment_index in (0..slots).map(SegmentIndex::new) {
+            file.read_exact_at(&mut slot, Self::slot_offset(segment_index))?;
+            let root_block = Self::decode_slot(&slot)
+                .filter(|root_block| root_block.segment_index() == segment_index);
+            if root_block.is_none() && slot.iter().any(|&byte| byte != 0) {
+                warn!(%segment_index, "Root block slot is corrupted, ignoring");
+            }
+            root_blocks.push(root_block);
+        }
+        while let Some(None) = root_blocks.last() {
+            root_blocks.pop();
+        }
+
+        debug!(
+            max_segment_index = ?root_blocks.len().checked_sub(1),
+            "Root block store opened"
+        );
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                file,
+                root_blocks: Mutex::new(root_blocks),
+            }),
+        })
+    }
+
+    /// Get root block of specified segment if known
+    pub fn get(&self, segment_index: SegmentIndex) -> Option<RootBlock> {
+        self.inner
+            .root_blocks
+            .lock()
+            .get(segment_index.get() as usize)
+            .copied()
+            .flatten()
+    }
+
+    /// Max segment index for which root block is known
+    pub fn max_segment_index(&self) -> Option<SegmentIndex> {
+        SegmentIndex::new(self.inner.root_blocks.lock().len() as u64).checked_sub(SegmentIndex::ONE)
+    }
+
+    /// Add root blocks to the store.
+    ///
+    /// Root blocks that are already stored are skipped, root blocks adjacent to already known ones
+    /// must form a valid chain with them. Root blocks before the first added one are not required,
+    /// the first root block after a gap is trusted.
+    pub fn add(&self, new_root_blocks: &[RootBlock]) -> Result<(), RootBlockStoreError> {
+        let mut root_blocks = self.inner.root_blocks.lock();
+
+        for &root_block in new_root_blocks {
+            let segment_index = root_block.segment_index();
+            let slot_index = segment_index.get() as usize;
+
+            if let Some(existing_root_block) = root_blocks.get(slot_index).copied().flatten() {
+                if existing_root_block == root_block {
+                    continue;
+                }
+                return Err(RootBlockStoreError::ConflictingRootBlock { segment_index });
+            }
+
+            let prev_root_block = slot_index
+                .checked_sub(1)
+                .and_then(|prev_slot_index| root_blocks.get(prev_slot_index).copied().flatten());
+            let next_root_block = root_blocks.get(slot_index + 1).copied().flatten();
+            let chain = prev_root_block
+                .into_iter()
+                .chain(Some(root_block))
+                .chain(next_root_block)
+                .collect::<Vec<_>>();
+            validate_root_block_chain(&chain)?;
+
+            let mut slot = vec![0; SLOT_SIZE as usize];
+            let encoded_root_block = root_block.encode();
+            slot[BLAKE2B_256_HASH_SIZE..][..encoded_root_block.len()]
+                .copy_from_slice(&encoded_root_block);
+            let checksum = blake2b_256_hash(&slot[BLAKE2B_256_HASH_SIZE..]);
+            slot[..BLAKE2B_256_HASH_SIZE].copy_from_slice(&checksum);
+            self.inner
+                .file
+                .write_all_at(&slot, Self::slot_offset(segment_index))?;
+
+            if root_blocks.len() <= slot_index {
+                root_blocks.resize(slot_index + 1, None);
+            }
+            root_blocks[slot_index].replace(root_block);
+        }
+
+        self.inner.file.sync_data()?;
+
+        Ok(())
+    }
+
+    /// Remove root blocks of specified segment and all segments after it, used when stored root
+    /// blocks turn out to belong to a different history than the one node follows
+    pub fn truncate(&self, segment_index: SegmentIndex) -> io::Result<()> {
+        let mut root_blocks = self.inner.root_blocks.lock();
+
+        if root_blocks.len() <= segment_index.get() as usize {
+            return Ok(());
+        }
+
+        self.inner.file.set_len(Self::slot_offset(segment_index))?;
+        self.inner.file.sync_data()?;
+
+        root_blocks.truncate(segment_index.get() as usize);
+        while let Some(None) = root_blocks.last() {
+            root_blocks.pop();
+        }
+
+        Ok(())
+    }
+
+    /// Wipe root block store in specified directory, does nothing if it doesn't exist
+    pub fn wipe(directory: &Path) -> io::Result<()> {
+        let path = directory.join(Self::FILE_NAME);
+        if path.exists() {
+            info!("Deleting root block store at {}", path.display());
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_header(file: &File) -> io::Result<()> {
+        let header = RootBlockStoreHeader {
+            version: RootBlockStoreHeader::LATEST_VERSION,
+        };
+        let mut header_bytes = header.encode();
+        header_bytes.resize(SLOT_SIZE as usize, 0);
+        file.write_all_at(&header_bytes, 0)
+    }
+
+    fn slot_offset(segment_index: SegmentIndex) -> u64 {
+        (segment_index.get() + 1) * SLOT_SIZE
+    }
+
+    /// Decode root block from slot, returns `None` if slot is empty or corrupted
+    fn decode_slot(slot: &[u8]) -> Option<RootBlock> {
+        let (checksum, contents) = slot.split_at(BLAKE2B_256_HASH_SIZE);
+        if checksum != blake2b_256_hash(contents) {
+            return None;
+        }
+
+        RootBlock::decode(&mut &*contents).ok()
+    }
+}
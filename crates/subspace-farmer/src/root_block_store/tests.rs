@@ -0,0 +1,232 @@
+use crate::file_ext::FileExt;
+use crate::root_block_store::{RootBlockStore, RootBlockStoreError, SLOT_SIZE};
+use std::fs::OpenOptions;
+use subspace_archiving::archiver::{Archiver, RootBlockChainError};
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::objects::BlockObjectMapping;
+use subspace_core_primitives::{RootBlock, SegmentIndex, RECORD_SIZE};
+use tempfile::TempDir;
+
+// This is data + parity shards
+const PIECES_IN_SEGMENT: u32 = 8;
+// In terms of source data that can be stored in the segment, not the size after archiving
+const SEGMENT_SIZE: u32 = RECORD_SIZE * PIECES_IN_SEGMENT / 2;
+
+fn root_blocks(count: usize) -> Vec<RootBlock> {
+    let kzg = Kzg::random(PIECES_IN_SEGMENT).unwrap();
+    let mut archiver = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg).unwrap();
+
+    let mut root_blocks = Vec::with_capacity(count);
+    while root_blocks.len() < count {
+        let block = (0..SEGMENT_SIZE).map(|_| rand::random()).collect();
+        root_blocks.extend(
+            archiver
+                .add_block(block, BlockObjectMapping::default())
+                .into_iter()
+                .map(|archived_segment| archived_segment.root_block),
+        );
+    }
+    root_blocks.truncate(count);
+
+    root_blocks
+}
+
+/// Same root block, but with different previous root block hash
+fn modified_root_block(root_block: RootBlock) -> RootBlock {
+    let RootBlock::V0 {
+        segment_index,
+        records_root,
+        last_archived_block,
+        ..
+    } = root_block;
+
+    RootBlock::V0 {
+        segment_index,
+        records_root,
+        prev_root_block_hash: rand::random(),
+        last_archived_block,
+    }
+}
+
+#[test]
+fn basic() {
+    let directory = TempDir::new().unwrap();
+    let root_blocks = root_blocks(4);
+
+    {
+        let store = RootBlockStore::open(directory.path()).unwrap();
+        assert_eq!(store.max_segment_index(), None);
+        assert_eq!(store.get(SegmentIndex::ZERO), None);
+
+        store.add(&root_blocks[..2]).unwrap();
+        assert_eq!(store.max_segment_index(), Some(SegmentIndex::ONE));
+        assert_eq!(store.get(SegmentIndex::ZERO), Some(root_blocks[0]));
+        assert_eq!(store.get(SegmentIndex::ONE), Some(root_blocks[1]));
+
+        // Adding the same root blocks again is fine
+        store.add(&root_blocks[1..2]).unwrap();
+
+        // Gaps are allowed
+        store.add(&root_blocks[3..]).unwrap();
+        assert_eq!(store.max_segment_index(), Some(SegmentIndex::new(3)));
+        assert_eq!(store.get(SegmentIndex::new(2)), None);
+        assert_eq!(store.get(SegmentIndex::new(3)), Some(root_blocks[3]));
+    }
+
+    // Everything is persisted
+    {
+        let store = RootBlockStore::open(directory.path()).unwrap();
+        assert_eq!(store.max_segment_index(), Some(SegmentIndex::new(3)));
+        assert_eq!(store.get(SegmentIndex::ZERO), Some(root_blocks[0]));
+        assert_eq!(store.get(SegmentIndex::ONE), Some(root_blocks[1]));
+        assert_eq!(store.get(SegmentIndex::new(2)), None);
+        assert_eq!(store.get(SegmentIndex::new(3)), Some(root_blocks[3]));
+
+        // Filling the gap must connect to root blocks on both sides
+        store.add(&root_blocks[2..3]).unwrap();
+        assert_eq!(store.get(SegmentIndex::new(2)), Some(root_blocks[2]));
+    }
+}
+
+#[test]
+fn invalid_root_blocks() {
+    let directory = TempDir::new().unwrap();
+    let root_blocks = root_blocks(3);
+
+    let store = RootBlockStore::open(directory.path()).unwrap();
+    store.add(&root_blocks[..1]).unwrap();
+
+    // Different root block for already known segment
+    assert!(matches!(
+        store.add(&[modified_root_block(root_blocks[0])]),
+        Err(RootBlockStoreError::ConflictingRootBlock { segment_index })
+            if segment_index == SegmentIndex::ZERO
+    ));
+
+    // Root block that doesn't extend previous one
+    assert!(matches!(
+        store.add(&[modified_root_block(root_blocks[1])]),
+        Err(RootBlockStoreError::InvalidChain(
+            RootBlockChainError::PrevHashMismatch { segment_index }
+        )) if segment_index == SegmentIndex::ONE
+    ));
+
+    // Root block that the next known one doesn't extend
+    store.add(&root_blocks[2..]).unwrap();
+    let RootBlock::V0 {
+        segment_index,
+        records_root,
+        prev_root_block_hash,
+        mut last_archived_block,
+    } = root_blocks[1];
+    last_archived_block.number += 1;
+    let root_block_with_other_last_archived_block = RootBlock::V0 {
+        segment_index,
+        records_root,
+        prev_root_block_hash,
+        last_archived_block,
+    };
+    assert!(matches!(
+        store.add(&[root_block_with_other_last_archived_block]),
+        Err(RootBlockStoreError::InvalidChain(
+            RootBlockChainError::PrevHashMismatch { segment_index }
+        )) if segment_index == SegmentIndex::new(2)
+    ));
+    assert_eq!(store.get(SegmentIndex::ONE), None);
+
+    store.add(&root_blocks[1..2]).unwrap();
+}
+
+#[test]
+fn partial_writes() {
+    let directory = TempDir::new().unwrap();
+    let root_blocks = root_blocks(3);
+
+    {
+        let store = RootBlockStore::open(directory.path()).unwrap();
+        store.add(&root_blocks).unwrap();
+    }
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(directory.path().join(RootBlockStore::FILE_NAME))
+        .unwrap();
+    // Simulate torn write in the middle of the second slot
+    file.write_all_at(&[0xff; 16], SLOT_SIZE * 2 + SLOT_SIZE / 2)
+        .unwrap();
+    // Simulate the last slot being only partially written
+    file.set_len(SLOT_SIZE * 4 - 1).unwrap();
+
+    {
+        let store = RootBlockStore::open(directory.path()).unwrap();
+        assert_eq!(store.max_segment_index(), Some(SegmentIndex::ZERO));
+        assert_eq!(store.get(SegmentIndex::ZERO), Some(root_blocks[0]));
+        assert_eq!(store.get(SegmentIndex::ONE), None);
+        assert_eq!(store.get(SegmentIndex::new(2)), None);
+
+        // Lost root blocks can be added again
+        store.add(&root_blocks[1..]).unwrap();
+    }
+
+    let store = RootBlockStore::open(directory.path()).unwrap();
+    assert_eq!(store.max_segment_index(), Some(SegmentIndex::new(2)));
+    assert_eq!(store.get(SegmentIndex::ONE), Some(root_blocks[1]));
+    assert_eq!(store.get(SegmentIndex::new(2)), Some(root_blocks[2]));
+}
+
+#[test]
+fn truncate() {
+    let directory = TempDir::new().unwrap();
+    let other_root_blocks = root_blocks(4);
+    let root_blocks = root_blocks(4);
+
+    {
+        let store = RootBlockStore::open(directory.path()).unwrap();
+        store.add(&root_blocks[..2]).unwrap();
+        store.add(&root_blocks[3..]).unwrap();
+
+        // Nothing to remove
+        store.truncate(SegmentIndex::new(4)).unwrap();
+        assert_eq!(store.max_segment_index(), Some(SegmentIndex::new(3)));
+
+        // Gap before removed root blocks is removed as well
+        store.truncate(SegmentIndex::new(3)).unwrap();
+        assert_eq!(store.max_segment_index(), Some(SegmentIndex::ONE));
+
+        store.truncate(SegmentIndex::ONE).unwrap();
+        assert_eq!(store.max_segment_index(), Some(SegmentIndex::ZERO));
+        assert_eq!(store.get(SegmentIndex::ONE), None);
+
+        // Root blocks of a different history can be added in place of removed ones
+        store.truncate(SegmentIndex::ZERO).unwrap();
+        assert_eq!(store.max_segment_index(), None);
+        store.add(&other_root_blocks[..2]).unwrap();
+    }
+
+    let store = RootBlockStore::open(directory.path()).unwrap();
+    assert_eq!(store.max_segment_index(), Some(SegmentIndex::ONE));
+    assert_eq!(store.get(SegmentIndex::ZERO), Some(other_root_blocks[0]));
+    assert_eq!(store.get(SegmentIndex::ONE), Some(other_root_blocks[1]));
+}
+
+#[test]
+fn unsupported_version() {
+    let directory = TempDir::new().unwrap();
+
+    drop(RootBlockStore::open(directory.path()).unwrap());
+
+    let file = OpenOptions::new()
+        .write(true)
+        .open(directory.path().join(RootBlockStore::FILE_NAME))
+        .unwrap();
+    file.write_all_at(&[u8::MAX], 0).unwrap();
+
+    assert!(matches!(
+        RootBlockStore::open(directory.path()),
+        Err(RootBlockStoreError::UnsupportedVersion {
+            version: u8::MAX,
+            ..
+        })
+    ));
+}
@@ -7,7 +7,8 @@ use std::pin::Pin;
 use subspace_archiving::archiver::ArchivedSegment;
 use subspace_core_primitives::{Piece, PieceIndex, RecordsRoot, SegmentIndex};
 use subspace_rpc_primitives::{
-    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SlotInfo, SolutionResponse,
+    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SegmentPieces, SlotInfo,
+    SolutionResponse,
 };
 
 /// To become error type agnostic
@@ -53,4 +54,13 @@ pub trait RpcClient: Clone + Send + Sync + 'static {
     ) -> Result<Vec<Option<RecordsRoot>>, Error>;
 
     async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, Error>;
+
+    /// Get up to `limit` consecutive pieces of archived segment starting at `first_position`
+    /// together with records root of the segment, `None` if segment is not archived yet
+    async fn segment_pieces(
+        &self,
+        segment_index: SegmentIndex,
+        first_position: u32,
+        limit: u32,
+    ) -> Result<Option<SegmentPieces>, Error>;
 }
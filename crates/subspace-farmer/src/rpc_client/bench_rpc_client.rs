@@ -9,7 +9,8 @@ use std::sync::Arc;
 use subspace_archiving::archiver::ArchivedSegment;
 use subspace_core_primitives::{Piece, PieceIndex, RecordsRoot, SegmentIndex};
 use subspace_rpc_primitives::{
-    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SlotInfo, SolutionResponse,
+    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SegmentPieces, SlotInfo,
+    SolutionResponse,
 };
 use tokio::sync::Mutex;
 
@@ -36,7 +37,8 @@ pub const BENCH_FARMER_PROTOCOL_INFO: FarmerProtocolInfo = FarmerProtocolInfo {
     // Doesn't matter, as we don't start sync
     total_pieces: NonZeroU64::new(1).unwrap(),
     space_l: NonZeroU16::new(20).unwrap(),
-    sector_expiration: 100,
+    sector_expiration: SegmentIndex::new(100),
+    rotation: 0,
 };
 
 impl BenchRpcClient {
@@ -137,4 +139,13 @@ impl RpcClient for BenchRpcClient {
     async fn get_piece(&self, _piece_index: PieceIndex) -> Result<Option<Piece>, Error> {
         unimplemented!()
     }
+
+    async fn segment_pieces(
+        &self,
+        _segment_index: SegmentIndex,
+        _first_position: u32,
+        _limit: u32,
+    ) -> Result<Option<SegmentPieces>, Error> {
+        Ok(None)
+    }
 }
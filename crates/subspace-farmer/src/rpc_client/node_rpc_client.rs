@@ -10,7 +10,8 @@ use std::sync::Arc;
 use subspace_archiving::archiver::ArchivedSegment;
 use subspace_core_primitives::{Piece, PieceIndex, RecordsRoot, SegmentIndex};
 use subspace_rpc_primitives::{
-    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SlotInfo, SolutionResponse,
+    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SegmentPieces, SlotInfo,
+    SolutionResponse,
 };
 
 // Defines max_concurrent_requests constant in the node rpc client.
@@ -140,4 +141,19 @@ impl RpcClient for NodeRpcClient {
             .request("subspace_getPiece", rpc_params![&piece_index])
             .await?)
     }
+
+    async fn segment_pieces(
+        &self,
+        segment_index: SegmentIndex,
+        first_position: u32,
+        limit: u32,
+    ) -> Result<Option<SegmentPieces>, RpcError> {
+        Ok(self
+            .client
+            .request(
+                "subspace_segmentPieces",
+                rpc_params![&segment_index, &first_position, &limit],
+            )
+            .await?)
+    }
 }
@@ -1,52 +1,145 @@
+pub mod attestation;
+pub mod audit_cache;
+pub mod audit_coordinator;
+pub mod audit_order;
+pub mod audit_replay;
+pub mod compressibility;
+pub mod diagnostics;
+pub mod dry_run;
+pub mod duplicate_plot;
 pub mod farming;
+pub mod format_migration;
+pub mod full_verification;
+pub mod idle_verification;
+pub mod legacy_plot;
+pub mod migration;
+pub mod overlay;
 pub mod piece_publisher;
 pub mod piece_reader;
 pub mod piece_receiver;
+pub mod plot_auditor;
+pub mod plot_layout_calculator;
+pub mod plot_writer;
 pub mod plotting;
+pub mod plotting_progress;
+pub mod plotting_stats;
+pub mod prefault;
+pub mod read_only;
+pub mod read_planner;
+pub mod remote;
+pub mod remote_farming;
+pub mod sector_locks;
+pub mod sector_metadata;
+pub mod sector_timings;
+pub mod segment_receiver;
+pub mod self_test;
+pub mod sharded_plot_file;
+pub mod simulation;
+pub mod solution_outlook;
+pub mod startup_check;
+pub mod storage_backend;
+#[cfg(test)]
+mod tests;
+pub mod witness_cache;
 
 use crate::file_ext::FileExt;
 use crate::identity::Identity;
+use crate::io_priority::{set_current_thread_io_priority, IoPriority};
+use crate::memory_budget::{MemoryBudget, MemoryCategory};
+use crate::piece_cache::{FarmerPieceCache, PinnedPieces};
+use crate::repeated_errors::RepeatedErrors;
 use crate::reward_signing::reward_signing;
+use crate::root_block_store::RootBlockStore;
 use crate::rpc_client;
 use crate::rpc_client::RpcClient;
-use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::attestation::{create_attestation, AttestationProof};
+use crate::single_disk_plot::audit_cache::{AuditCache, AuditCacheStats};
+use crate::single_disk_plot::audit_coordinator::{
+    AuditCoordinator, PausingPieceReceiver, PausingWriter,
+};
+use crate::single_disk_plot::audit_order::{audit_coverage, AuditOrder, SectorAuditOrder};
+use crate::single_disk_plot::audit_replay::{AuditRecord, AuditRecorder};
+use crate::single_disk_plot::dry_run::{DryRunOptions, DryRunReport, PlotPlan};
+use crate::single_disk_plot::duplicate_plot::{DuplicatePlotDetector, PlotIncarnation};
+use crate::single_disk_plot::farming::{audit_sector_cached, BestMiss};
+use crate::single_disk_plot::idle_verification::{
+    ChecksummingWriter, IdleVerification, IdleVerificationOptions, IdleVerificationOutcome,
+    SlotTimer, VerificationCoverage,
+};
+use crate::single_disk_plot::overlay::PlotOverlay;
 use crate::single_disk_plot::piece_publisher::PieceSectorPublisher;
 use crate::single_disk_plot::piece_reader::{read_piece, PieceReader, ReadPieceRequest};
-use crate::single_disk_plot::plotting::{plot_sector, PlotSectorError, PlottedSector};
+use crate::single_disk_plot::plotting::{
+    plot_sector_resumable, plot_sector_with_arena, replot_sector_in_place, sector_expires_at,
+    PlotSectorError, PlottedSector, SectorDestination,
+};
+use crate::single_disk_plot::plotting_progress::ProgressStore;
+use crate::single_disk_plot::plotting_stats::{slow_pieces, PlottingStats};
+use crate::single_disk_plot::read_planner::{
+    prefetch_audited_pieces, PrefetchedPiece, PrefetchedSectorReader,
+};
+use crate::single_disk_plot::sector_locks::SectorLocks;
+use crate::single_disk_plot::sector_metadata::{
+    open_sector_metadata_file, read_sector_metadata, read_sector_metadata_record,
+    remove_abandoned_files, sector_infos, sector_metadata_file_size, sector_metadata_record_offset,
+    SectorInfo, SectorMetadataRecord, SectorMetadataRecordError, SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::sector_timings::SectorTimings;
+use crate::single_disk_plot::sharded_plot_file::{PlotFile, PlotShards, ShardedPlotFile};
+use crate::single_disk_plot::solution_outlook::SolutionOutlook;
+use crate::single_disk_plot::startup_check::{
+    startup_check, StartupCheckError, StartupCheckReport,
+};
+use crate::single_disk_plot::storage_backend::{
+    plot_mmap_len, sector_start, MetadataFile, MetadataFileMut, PlotData, SectorFileWriter,
+    StorageBackend,
+};
+use crate::single_disk_plot::witness_cache::WitnessCache;
 use crate::utils::JoinOnDrop;
+use bumpalo::Bump;
 use bytesize::ByteSize;
 use derive_more::{Display, From};
 use event_listener_primitives::{Bag, HandlerId};
 use futures::channel::oneshot;
 use futures::stream::FuturesUnordered;
-use futures::StreamExt;
-use memmap2::{Mmap, MmapMut, MmapOptions};
+use futures::{FutureExt, StreamExt};
+use memmap2::{MmapMut, MmapOptions};
 use parity_db::const_assert;
 use parity_scale_codec::{Decode, Encode};
 use parking_lot::Mutex;
-use piece_receiver::MultiChannelPieceReceiver;
+use piece_receiver::{
+    BandwidthLimit, BandwidthLimitedPieceReceiver, CachedPieceReceiver, CoalescingPieceReceiver,
+    FallbackPieceReceiver, MemoryAccountedPieceReceiver, MultiChannelPieceReceiver, PieceDownloads,
+    PieceReceiver, RetryingPieceReceiver, TimeoutPieceReceiver, VerifyingPieceReceiver,
+};
+use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
 use std::fs::OpenOptions;
 use std::future::Future;
 use std::io::{Seek, SeekFrom};
-use std::num::{NonZeroU16, NonZeroU64};
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
-use std::{fmt, fs, io, thread};
+use std::time::{Duration, Instant};
+use std::{fmt, fs, io, panic, thread};
 use std_semaphore::{Semaphore, SemaphoreGuard};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
 use subspace_core_primitives::{
-    plot_sector_size, PieceIndex, PublicKey, SectorId, SectorIndex, SegmentIndex, Solution,
-    PIECE_SIZE,
+    solution_range_to_space, Blake2b256Hash, PieceIndex, PublicKey, SectorId, SectorIndex,
+    SegmentIndex, SlotNumber, Solution, SolutionRange, PIECE_SIZE,
 };
 use subspace_networking::Node;
-use subspace_rpc_primitives::SolutionResponse;
+use subspace_rpc_primitives::{FarmerProtocolInfo, SolutionResponse};
 use thiserror::Error;
 use tokio::runtime::Handle;
 use tokio::sync::broadcast;
-use tracing::{debug, error, info, info_span, trace, Instrument, Span};
+use tracing::{debug, error, info, info_span, trace, warn, Instrument, Span};
 use ulid::Ulid;
 
 // Refuse to compile on non-64-bit platforms, offsets may fail on those when converting from u64 to
@@ -55,6 +148,48 @@ const_assert!(std::mem::size_of::<usize>() >= std::mem::size_of::<u64>());
 
 /// Reserve 1M of space for plot metadata (for potential future expansion)
 const RESERVED_PLOT_METADATA: u64 = 1024 * 1024;
+/// How often plotting checks whether node started signaling new rotation or idle verification found
+/// corrupted sectors once all sectors are plotted under the current rotation
+const STALE_ROTATION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Granularity of sleeping in plotting thread at which shutdown is noticed
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How often solution outlook is logged and stored in plot directory
+const SOLUTION_OUTLOOK_REPORT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Incarnation of the plot is checked against plot directory this often
+const PLOT_INCARNATION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Progress of resumable plotting is saved after every this many pieces (2 MiB)
+const PLOTTING_PROGRESS_SAVE_INTERVAL: NonZeroU64 = match NonZeroU64::new(64) {
+    Some(interval) => interval,
+    None => unreachable!(),
+};
+/// How many times plotting of a sector is attempted before plotting fails
+const SECTOR_PLOTTING_ATTEMPTS: usize = 3;
+
+/// Files of a plot that are synced to disk by [`SingleDiskPlot::sync_all()`]
+#[derive(Debug)]
+struct PlotFiles {
+    plot_file: Arc<PlotFile>,
+    plot_overlay: Option<PlotOverlay>,
+    sector_metadata_file: fs::File,
+    metadata_file: fs::File,
+}
+
+impl PlotFiles {
+    /// Sync contents of all files to disk.
+    ///
+    /// Plot file is synced first, then sector metadata and metadata header with sector count last,
+    /// so that sector counted as plotted never points to contents that didn't reach the disk.
+    /// Pages written through shared memory mappings belong to the same files and are written as
+    /// well.
+    fn sync_all(&self) -> io::Result<()> {
+        self.plot_file.sync_all()?;
+        if let Some(plot_overlay) = &self.plot_overlay {
+            plot_overlay.sync_all()?;
+        }
+        self.sector_metadata_file.sync_all()?;
+        self.metadata_file.sync_all()
+    }
+}
 
 /// Semaphore that limits disk access concurrency in strategic places to the number specified during
 /// initialization
@@ -103,7 +238,9 @@ impl SingleDiskPlotId {
     }
 }
 
-/// Important information about the contents of the `SingleDiskPlot`
+/// Important information about the contents of the `SingleDiskPlot`.
+///
+/// Stored on disk as JSON, encoding is pinned by golden test vectors.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum SingleDiskPlotInfo {
@@ -125,6 +262,9 @@ pub enum SingleDiskPlotInfo {
         first_sector_index: SectorIndex,
         /// How much space in bytes is allocated for this plot
         allocated_space: u64,
+        /// Layout of plot file split into shards, plot is stored in a single file if not present
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        plot_shards: Option<PlotShards>,
     },
 }
 
@@ -144,6 +284,28 @@ impl SingleDiskPlotInfo {
             public_key,
             first_sector_index,
             allocated_space,
+            plot_shards: None,
+        }
+    }
+
+    /// Same info, but for plot whose plot file is split into shards with `plot_shards` layout
+    pub fn with_plot_shards(self, plot_shards: PlotShards) -> Self {
+        let Self::V0 {
+            id,
+            genesis_hash,
+            public_key,
+            first_sector_index,
+            allocated_space,
+            ..
+        } = self;
+
+        Self::V0 {
+            id,
+            genesis_hash,
+            public_key,
+            first_sector_index,
+            allocated_space,
+            plot_shards: Some(plot_shards),
         }
     }
 
@@ -211,6 +373,12 @@ impl SingleDiskPlotInfo {
         } = self;
         *allocated_space
     }
+
+    /// Layout of plot file split into shards, `None` if plot is stored in a single file
+    pub fn plot_shards(&self) -> Option<PlotShards> {
+        let Self::V0 { plot_shards, .. } = self;
+        *plot_shards
+    }
 }
 
 /// Summary of single disk plot for presentational purposes
@@ -236,6 +404,7 @@ pub enum SingleDiskPlotSummary {
     },
 }
 
+/// Header of plot metadata file, encoding is pinned by golden test vectors
 #[derive(Debug, Encode, Decode)]
 struct PlotMetadataHeader {
     version: u8,
@@ -243,6 +412,11 @@ struct PlotMetadataHeader {
 }
 
 impl PlotMetadataHeader {
+    /// Version 0 has no farmer protocol info stored, version 1 stores [`PlotProtocolInfo`] right
+    /// after the header, version 2 additionally stores [`PlotSectorAlignment`] right after protocol
+    /// info
+    const LATEST_VERSION: u8 = 2;
+
     fn encoded_size() -> usize {
         let default = PlotMetadataHeader {
             version: 0,
@@ -253,9 +427,322 @@ impl PlotMetadataHeader {
     }
 }
 
-/// Metadata of the plotted sector
+/// Farmer protocol info the plot was created with, stored in plot metadata.
+///
+/// When new fields are added to [`FarmerProtocolInfo`], new variant needs to be added here and
+/// conversion of older variants must fill new fields with values that were implicitly used before.
+/// Encoding of existing variants is pinned by golden test vectors.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Encode, Decode)]
+enum PlotProtocolInfo {
+    /// V0 of the protocol info
+    #[codec(index = 0)]
+    V0 {
+        /// Genesis hash of the chain
+        genesis_hash: [u8; 32],
+        /// The size of data in one piece (in bytes)
+        record_size: NonZeroU32,
+        /// Recorded history is encoded and plotted in segments of this size (in bytes)
+        recorded_history_segment_size: u32,
+        /// Total number of pieces stored on the network as of plot creation
+        total_pieces: NonZeroU64,
+        /// Space parameter for proof-of-replication in bits
+        space_l: NonZeroU16,
+        /// Number of segments after which sector expires
+        sector_expiration: SegmentIndex,
+    },
+    /// V1 of the protocol info, adds identifier of KZG parameters
+    #[codec(index = 1)]
+    V1 {
+        /// Genesis hash of the chain
+        genesis_hash: [u8; 32],
+        /// The size of data in one piece (in bytes)
+        record_size: NonZeroU32,
+        /// Recorded history is encoded and plotted in segments of this size (in bytes)
+        recorded_history_segment_size: u32,
+        /// Total number of pieces stored on the network as of plot creation
+        total_pieces: NonZeroU64,
+        /// Space parameter for proof-of-replication in bits
+        space_l: NonZeroU16,
+        /// Number of segments after which sector expires
+        sector_expiration: SegmentIndex,
+        /// Identifier of KZG parameters plot was created with
+        kzg_parameters_id: KzgParametersId,
+    },
+}
+
+impl PlotProtocolInfo {
+    fn new(farmer_protocol_info: FarmerProtocolInfo, kzg_parameters_id: KzgParametersId) -> Self {
+        let FarmerProtocolInfo {
+            genesis_hash,
+            record_size,
+            recorded_history_segment_size,
+            total_pieces,
+            space_l,
+            sector_expiration,
+            rotation: _,
+        } = farmer_protocol_info;
+
+        Self::V1 {
+            genesis_hash,
+            record_size,
+            recorded_history_segment_size,
+            total_pieces,
+            space_l,
+            sector_expiration,
+            kzg_parameters_id,
+        }
+    }
+
+    /// The same protocol info with `sector_expiration` instead of the stored one, version is kept
+    /// so encoded size doesn't change
+    fn with_sector_expiration(self, sector_expiration: SegmentIndex) -> Self {
+        match self {
+            Self::V0 {
+                genesis_hash,
+                record_size,
+                recorded_history_segment_size,
+                total_pieces,
+                space_l,
+                ..
+            } => Self::V0 {
+                genesis_hash,
+                record_size,
+                recorded_history_segment_size,
+                total_pieces,
+                space_l,
+                sector_expiration,
+            },
+            Self::V1 {
+                genesis_hash,
+                record_size,
+                recorded_history_segment_size,
+                total_pieces,
+                space_l,
+                kzg_parameters_id,
+                ..
+            } => Self::V1 {
+                genesis_hash,
+                record_size,
+                recorded_history_segment_size,
+                total_pieces,
+                space_l,
+                sector_expiration,
+                kzg_parameters_id,
+            },
+        }
+    }
+
+    /// Identifier of KZG parameters plot was created with
+    fn kzg_parameters_id(&self) -> KzgParametersId {
+        match self {
+            // Test parameters were the only ones in use before identifier was stored
+            Self::V0 { .. } => KzgParametersId::TEST,
+            Self::V1 {
+                kzg_parameters_id, ..
+            } => *kzg_parameters_id,
+        }
+    }
+
+    /// Load protocol info stored in plot metadata, stores provided `farmer_protocol_info` and
+    /// `kzg_parameters_id` if metadata doesn't have it yet (new plot or plot created before
+    /// protocol info was stored) and upgrades metadata header version accordingly.
+    ///
+    /// Returns farmer protocol info and identifier of KZG parameters plot was created with, plot
+    /// created on a different chain is refused.
+    ///
+    /// Sector expiration is the only parameter that follows the node: it is not part of sector
+    /// encoding, so stored protocol info is updated when it changes and already plotted sectors are
+    /// rebound to the new expiration in place by plotting.
+    fn load_or_store(
+        id: SingleDiskPlotId,
+        metadata_file: &fs::File,
+        metadata_header: &mut PlotMetadataHeader,
+        farmer_protocol_info: FarmerProtocolInfo,
+        kzg_parameters_id: KzgParametersId,
+    ) -> Result<(FarmerProtocolInfo, KzgParametersId), SingleDiskPlotError> {
+        if metadata_header.version == 0 {
+            metadata_file.write_all_at(
+                &Self::new(farmer_protocol_info, kzg_parameters_id).encode(),
+                PlotMetadataHeader::encoded_size() as u64,
+            )?;
+            metadata_header.version = 1;
+            metadata_file.write_all_at(&metadata_header.encode(), 0)?;
+
+            return Ok((farmer_protocol_info, kzg_parameters_id));
+        }
+
+        let mut plot_protocol_info = Self::read(metadata_file)?;
+        let stored_protocol_info = FarmerProtocolInfo::from(plot_protocol_info);
+
+        if stored_protocol_info.genesis_hash != farmer_protocol_info.genesis_hash {
+            return Err(SingleDiskPlotError::WrongChain {
+                id,
+                correct_chain: hex::encode(stored_protocol_info.genesis_hash),
+                wrong_chain: hex::encode(farmer_protocol_info.genesis_hash),
+            });
+        }
+
+        if stored_protocol_info.sector_expiration != farmer_protocol_info.sector_expiration {
+            info!(
+                %id,
+                old_sector_expiration = %stored_protocol_info.sector_expiration,
+                new_sector_expiration = %farmer_protocol_info.sector_expiration,
+                "Sector expiration changed, plotted sectors will be rebound to it"
+            );
+            plot_protocol_info =
+                plot_protocol_info.with_sector_expiration(farmer_protocol_info.sector_expiration);
+            metadata_file.write_all_at(
+                &plot_protocol_info.encode(),
+                PlotMetadataHeader::encoded_size() as u64,
+            )?;
+        }
+
+        Ok((
+            FarmerProtocolInfo::from(plot_protocol_info),
+            plot_protocol_info.kzg_parameters_id(),
+        ))
+    }
+
+    /// Load protocol info and identifier of KZG parameters stored in plot metadata, metadata
+    /// header version must be at least `1`
+    fn load(
+        metadata_file: &fs::File,
+    ) -> Result<(FarmerProtocolInfo, KzgParametersId), SingleDiskPlotError> {
+        let plot_protocol_info = Self::read(metadata_file)?;
+
+        Ok((
+            FarmerProtocolInfo::from(plot_protocol_info),
+            plot_protocol_info.kzg_parameters_id(),
+        ))
+    }
+
+    /// Read protocol info stored in plot metadata as is
+    fn read(metadata_file: &fs::File) -> Result<Self, SingleDiskPlotError> {
+        let offset = PlotMetadataHeader::encoded_size() as u64;
+
+        let mut bytes = vec![0; (RESERVED_PLOT_METADATA - offset) as usize];
+        metadata_file.read_exact_at(&mut bytes, offset)?;
+
+        Self::decode(&mut bytes.as_slice()).map_err(SingleDiskPlotError::FailedToDecodeProtocolInfo)
+    }
+}
+
+/// Alignment of sector starts within plot file, stored in plot metadata right after
+/// [`PlotProtocolInfo`] as little-endian `u64`.
+///
+/// Sectors of plots created before alignment was stored are located back to back, which is the
+/// same as alignment of `1` byte.
+struct PlotSectorAlignment;
+
+impl PlotSectorAlignment {
+    /// Metadata header version since which sector alignment is stored
+    const VERSION: u8 = 2;
+
+    /// Alignment that plot with `metadata_header` that doesn't have alignment stored yet will use:
+    /// `sector_alignment` for new plots and `1` for plots that were created before alignment was
+    /// stored
+    fn initial(metadata_header: &PlotMetadataHeader, sector_alignment: NonZeroU64) -> u64 {
+        if metadata_header.version == 0 && metadata_header.sector_count == 0 {
+            sector_alignment.get()
+        } else {
+            1
+        }
+    }
+
+    /// Load sector alignment stored in plot metadata, stores `initial_sector_alignment` if metadata
+    /// doesn't have it yet and upgrades metadata header version accordingly, protocol info must be
+    /// already stored.
+    fn load_or_store(
+        metadata_file: &fs::File,
+        metadata_header: &mut PlotMetadataHeader,
+        initial_sector_alignment: u64,
+    ) -> Result<u64, SingleDiskPlotError> {
+        if metadata_header.version >= Self::VERSION {
+            return Self::load(metadata_file, metadata_header);
+        }
+
+        metadata_file.write_all_at(
+            &initial_sector_alignment.to_le_bytes(),
+            Self::offset(metadata_file)?,
+        )?;
+        metadata_header.version = Self::VERSION;
+        metadata_file.write_all_at(&metadata_header.encode(), 0)?;
+
+        Ok(initial_sector_alignment)
+    }
+
+    /// Load sector alignment stored in plot metadata, metadata header version must be at least `1`.
+    ///
+    /// Alignment is not validated here, it is validated when sector stride is computed.
+    fn load(
+        metadata_file: &fs::File,
+        metadata_header: &PlotMetadataHeader,
+    ) -> Result<u64, SingleDiskPlotError> {
+        if metadata_header.version < Self::VERSION {
+            return Ok(1);
+        }
+
+        let mut sector_alignment_bytes = [0; std::mem::size_of::<u64>()];
+        metadata_file.read_exact_at(&mut sector_alignment_bytes, Self::offset(metadata_file)?)?;
+
+        Ok(u64::from_le_bytes(sector_alignment_bytes))
+    }
+
+    /// Offset of sector alignment within plot metadata, which depends on the size of stored
+    /// protocol info
+    fn offset(metadata_file: &fs::File) -> Result<u64, SingleDiskPlotError> {
+        let protocol_info_offset = PlotMetadataHeader::encoded_size() as u64;
+
+        let mut bytes = vec![0; (RESERVED_PLOT_METADATA - protocol_info_offset) as usize];
+        metadata_file.read_exact_at(&mut bytes, protocol_info_offset)?;
+
+        let mut input = bytes.as_slice();
+        PlotProtocolInfo::decode(&mut input)
+            .map_err(SingleDiskPlotError::FailedToDecodeProtocolInfo)?;
+
+        Ok(protocol_info_offset + (bytes.len() - input.len()) as u64)
+    }
+}
+
+impl From<PlotProtocolInfo> for FarmerProtocolInfo {
+    fn from(plot_protocol_info: PlotProtocolInfo) -> Self {
+        match plot_protocol_info {
+            PlotProtocolInfo::V0 {
+                genesis_hash,
+                record_size,
+                recorded_history_segment_size,
+                total_pieces,
+                space_l,
+                sector_expiration,
+            }
+            | PlotProtocolInfo::V1 {
+                genesis_hash,
+                record_size,
+                recorded_history_segment_size,
+                total_pieces,
+                space_l,
+                sector_expiration,
+                ..
+            } => Self {
+                genesis_hash,
+                record_size,
+                recorded_history_segment_size,
+                total_pieces,
+                space_l,
+                sector_expiration,
+                // Rotation changes during lifetime of the plot and is not stored
+                rotation: 0,
+            },
+        }
+    }
+}
+
+/// Metadata of the plotted sector.
+///
+/// Stored on disk for every sector, encoding is pinned by golden test vectors, so fields must never
+/// be reordered or changed.
 #[doc(hidden)]
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Encode, Decode)]
 pub struct SectorMetadata {
     /// Total number of pieces in archived history of the blockchain as of sector creation
     pub total_pieces: NonZeroU64,
@@ -268,13 +755,83 @@ impl SectorMetadata {
     pub fn encoded_size() -> usize {
         let default = SectorMetadata {
             total_pieces: NonZeroU64::new(1).expect("1 is not 0; qed"),
-            expires_at: 0,
+            expires_at: SegmentIndex::ZERO,
         };
 
         default.encoded_size()
     }
 }
 
+/// Layout of plot files on disk
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PlotLayout {
+    /// Directory where compact sector metadata is stored separately from sector data, for instance
+    /// on faster storage, sector metadata is stored in plot directory if not specified
+    pub metadata_directory: Option<PathBuf>,
+    /// Directory of writable overlay on top of plot directory, which is then treated as read-only
+    /// snapshot of existing plot (for instance ZFS or LVM snapshot). Metadata of the plot and
+    /// sectors plotted or replotted after snapshot was taken are stored in overlay, see [`overlay`]
+    /// module for details.
+    pub overlay_directory: Option<PathBuf>,
+    /// Sectors start at offsets within plot file that are multiples of this many bytes, with
+    /// padding between sectors as necessary, which helps `O_DIRECT` access and some SSDs. Must be a
+    /// power of two.
+    ///
+    /// Alignment is recorded in plot metadata when plot is created, existing plots keep their
+    /// alignment regardless of this setting.
+    pub sector_alignment: NonZeroU64,
+    /// Split plot file into shards of at most this many bytes for file systems that limit the size
+    /// of a single file, see [`sharded_plot_file`] module for details. `None` stores plot in a
+    /// single file.
+    ///
+    /// Shard layout is recorded in plot info when plot is created, existing plots keep their
+    /// layout regardless of this setting.
+    pub plot_shard_size: Option<NonZeroU64>,
+}
+
+impl Default for PlotLayout {
+    fn default() -> Self {
+        Self {
+            metadata_directory: None,
+            overlay_directory: None,
+            sector_alignment: NonZeroU64::new(1).expect("Not zero; qed"),
+            plot_shard_size: None,
+        }
+    }
+}
+
+impl PlotLayout {
+    /// Directory where sector metadata of the plot stored in `directory` is located, which is
+    /// overlay directory if plot has overlay
+    pub fn metadata_directory<'a>(&'a self, directory: &'a Path) -> &'a Path {
+        self.overlay_directory
+            .as_deref()
+            .unwrap_or_else(|| self.base_metadata_directory(directory))
+    }
+
+    /// Directory where files of the plot stored in `directory` are written, which is overlay
+    /// directory if plot has overlay
+    pub fn writable_directory<'a>(&'a self, directory: &'a Path) -> &'a Path {
+        self.overlay_directory.as_deref().unwrap_or(directory)
+    }
+
+    /// Directory where sector metadata of the plot stored in `directory` is located, ignoring
+    /// overlay
+    fn base_metadata_directory<'a>(&'a self, directory: &'a Path) -> &'a Path {
+        self.metadata_directory.as_deref().unwrap_or(directory)
+    }
+
+    fn metadata_file(&self, directory: &Path) -> PathBuf {
+        self.metadata_directory(directory)
+            .join(SingleDiskPlot::METADATA_FILE)
+    }
+
+    fn sector_metadata_file(&self, directory: &Path) -> PathBuf {
+        self.metadata_directory(directory)
+            .join(SingleDiskPlot::SECTOR_METADATA_FILE)
+    }
+}
+
 /// Options used to open single dis plot
 pub struct SingleDiskPlotOptions<RC> {
     /// Path to directory where plot are stored.
@@ -287,6 +844,90 @@ pub struct SingleDiskPlotOptions<RC> {
     pub reward_address: PublicKey,
     /// Optional DSN Node.
     pub dsn_node: Option<Node>,
+    /// Local cache of recently archived pieces, checked before going to the network
+    pub piece_cache: FarmerPieceCache,
+    /// Number of upcoming sectors, including the one being plotted, whose pieces are pinned in
+    /// piece cache during plotting, `0` disables pinning
+    pub piece_cache_pin_ahead: u64,
+    /// Storage backend to use for plot files, auto-detected from the file system of `directory`
+    /// if not specified
+    pub storage_backend: Option<StorageBackend>,
+    /// Download bandwidth limit for pieces retrieved during plotting, can be shared between plots
+    pub bandwidth_limit: BandwidthLimit,
+    /// Downloads of pieces retrieved during plotting, sharing it between plots makes concurrent
+    /// requests for the same piece result in a single download
+    pub piece_downloads: PieceDownloads,
+    /// Timeout of a single attempt to retrieve piece during plotting, attempts that time out are
+    /// retried
+    pub piece_fetch_timeout: Duration,
+    /// Pieces whose retrieval takes longer than this during plotting are logged
+    pub slow_piece_threshold: Duration,
+    /// Root blocks known to the farmer, used for piece verification and history size
+    pub root_block_store: RootBlockStore,
+    /// Pause plotting disk writes while audit is in progress to reduce audit latency
+    pub pause_plotting_during_audit: bool,
+    /// Also pause encoding of pieces while audit is in progress, only used together with
+    /// `pause_plotting_during_audit`
+    pub pause_encoding_during_audit: bool,
+    /// Order in which sectors are audited
+    pub audit_order: AuditOrder,
+    /// Audit at most this many sectors every slot, selected from the challenge such that every
+    /// sector is audited in the same fraction of slots, for disks that can't audit the whole plot
+    /// before slot deadline. Fraction of rewards plot gets is reduced accordingly. `None` audits
+    /// all sectors.
+    pub max_sectors_per_slot: Option<NonZeroU64>,
+    /// Memory budget plotting waits for before starting a new sector, can be shared between plots
+    pub memory_budget: MemoryBudget,
+    /// Record inputs of audits that resulted in solutions into audit replay log in plot directory
+    /// for debugging, log will not grow beyond specified size in bytes. `None` disables recording.
+    pub audit_replay_log_size: Option<u64>,
+    /// Where sector data and sector metadata are stored
+    pub plot_layout: PlotLayout,
+    /// Set OS-level I/O priority of audit reads above plotting writes, requires `io-priority`
+    /// feature and only supported on Linux
+    pub io_priority: bool,
+    /// Local source of pieces that is tried before the network during plotting, for instance a
+    /// legacy plot that is being converted. Pieces are verified and those that fail verification
+    /// are retrieved from the network instead.
+    pub local_pieces: Option<Arc<dyn PieceReceiver + Send + Sync>>,
+    /// Source of pieces downloaded from the node segment by segment (see
+    /// [`segment_receiver::SegmentPieceReceiver`]), tried before all other sources during plotting.
+    /// Pieces are verified by the source itself.
+    pub node_segments: Option<Arc<dyn PieceReceiver + Send + Sync>>,
+    /// Size in bytes of the cache of pieces read during recent audits, `0` disables the cache
+    pub audit_cache_size: u64,
+    /// Coalesce audit reads of sectors whose audited pieces are at most this many bytes apart into
+    /// a single read, reducing number of syscalls and round-trips. Only used for plots that are
+    /// read with positional reads ([`StorageBackend::Network`]), have no overlay and are not split
+    /// into shards. `None` disables coalescing.
+    pub audit_read_coalescing_gap: Option<u64>,
+    /// Maximum number of entries in the cache of piece witnesses verified during plotting, the
+    /// same pieces are plotted into many sectors when `total_pieces` is small and don't need to be
+    /// verified again. `0` disables the cache.
+    pub witness_cache_entries: usize,
+    /// Where progress of plotting within a sector is saved, so that sector interrupted by restart
+    /// continues where it stopped instead of being plotted from scratch. `None` disables resumable
+    /// plotting.
+    pub progress_store: Option<Arc<dyn ProgressStore + Send + Sync>>,
+    /// Slot probability of the network as `(numerator, denominator)`, used to estimate space
+    /// pledged to the network from solution range
+    pub slot_probability: (u64, u64),
+    /// Read and audit one random plotted sector and verify resulting solution before farming
+    /// starts, plot fails to open if that doesn't succeed
+    pub startup_check: bool,
+    /// Verify checksums of plotted sectors one by one when disk is idle after audit, sectors that
+    /// fail verification are replotted. `None` disables verification.
+    pub idle_verification: Option<IdleVerificationOptions>,
+    /// Detector of the same plot being farmed by another farmer, shared by all plots of the
+    /// farmer. `None` disables detection.
+    pub duplicate_plot_detector: Option<DuplicatePlotDetector>,
+    /// Exporter of timings of every plotted and audited sector for offline analysis. `None`
+    /// disables export.
+    pub sector_timings: Option<SectorTimings>,
+    /// Identity derived from a mnemonic with [`Identity::from_mnemonic()`] to use instead of
+    /// generating random identity, only its derivation is stored in plot directory. Existing plot
+    /// must have been created with the same identity. `None` uses random identity.
+    pub derived_identity: Option<Identity>,
 }
 
 /// Errors happening when trying to create/open single disk plot
@@ -342,9 +983,167 @@ pub enum SingleDiskPlotError {
     /// Unexpected metadata version
     #[error("Unexpected metadata version {0}")]
     UnexpectedMetadataVersion(u8),
+    /// Failed to decode sector metadata file header
+    #[error("Failed to decode sector metadata file header: {0}")]
+    FailedToDecodeSectorMetadataHeader(parity_scale_codec::Error),
+    /// Unexpected sector metadata file version
+    #[error("Unexpected sector metadata file version {0}")]
+    UnexpectedSectorMetadataVersion(u8),
+    /// Unexpected record size of sector metadata file
+    #[error("Unexpected record size of sector metadata file {0}")]
+    UnexpectedSectorMetadataRecordSize(u32),
+    /// Failed to decode farmer protocol info stored in metadata
+    #[error("Failed to decode farmer protocol info stored in metadata: {0}")]
+    FailedToDecodeProtocolInfo(parity_scale_codec::Error),
+    /// Plot info not found
+    #[error("Plot info not found in {}", directory.display())]
+    PlotInfoNotFound {
+        /// Directory where plot was expected
+        directory: PathBuf,
+    },
+    /// Farmer protocol info is not stored in metadata yet, plot needs to be opened by the farmer
+    /// connected to the node first
+    #[error(
+        "Farmer protocol info of plot {id} is not stored in metadata yet, plot needs to be opened \
+        by the farmer connected to the node first"
+    )]
+    ProtocolInfoNotStored {
+        /// Plot ID
+        id: SingleDiskPlotId,
+    },
     /// Node RPC error
     #[error("Node RPC error: {0}")]
     NodeRpcError(Box<dyn std::error::Error + Send + Sync + 'static>),
+    /// Failed to open or store identity
+    #[error("Failed to open or store identity: {error}")]
+    FailedToOpenIdentity {
+        /// Lower-level error
+        error: anyhow::Error,
+    },
+    /// Directory is not writable
+    #[error("Directory {} is not writable", directory.display())]
+    DirectoryNotWritable {
+        /// Directory
+        directory: PathBuf,
+    },
+    /// Not enough space for plot files
+    #[error(
+        "Not enough space in {} for plot files, {required} is required, but only {available} is \
+        available",
+        directory.display()
+    )]
+    InsufficientSpace {
+        /// Directory plot files are stored in
+        directory: PathBuf,
+        /// Space required for plot files
+        required: ByteSize,
+        /// Space available on the file system
+        available: ByteSize,
+    },
+    /// Allocated space, sector count and sector size don't match each other or plot file size
+    #[error(
+        "Layout of plot {id} is inconsistent: {sector_count} sectors of {plot_sector_size} bytes \
+        with {allocated_space} bytes allocated don't match plot file of {plot_file_size} bytes, \
+        plot was likely created with different protocol parameters"
+    )]
+    InconsistentPlotLayout {
+        /// Plot ID
+        id: SingleDiskPlotId,
+        /// Space allocated during plot creation
+        allocated_space: u64,
+        /// Number of sectors recorded in plot metadata
+        sector_count: u64,
+        /// Size of one sector in bytes computed from recorded protocol parameters
+        plot_sector_size: u64,
+        /// Actual size of plot file in bytes
+        plot_file_size: u64,
+    },
+    /// Sector is so far into the plot that its location can't be represented on this platform
+    #[error(
+        "Sector at offset {sector_offset} is out of range, its location in plot with sectors of \
+        {plot_sector_size} bytes overflows"
+    )]
+    SectorIndexOutOfRange {
+        /// Offset of the sector within plot
+        sector_offset: u64,
+        /// Size of one sector in bytes
+        plot_sector_size: u64,
+    },
+    /// Sector alignment is not a power of two or sector padded to it is too large
+    #[error(
+        "Sector alignment of {sector_alignment} bytes is invalid for sectors of \
+        {plot_sector_size} bytes, must be a power of two"
+    )]
+    InvalidSectorAlignment {
+        /// Sector alignment in bytes
+        sector_alignment: u64,
+        /// Size of one sector in bytes
+        plot_sector_size: u64,
+    },
+    /// Startup check of plot failed
+    #[error("Startup check of plot {id} failed: {error}")]
+    StartupCheckFailed {
+        /// Plot ID
+        id: SingleDiskPlotId,
+        /// Lower-level error
+        error: StartupCheckError,
+    },
+    /// Plot can't be created because directory already contains a plot
+    #[error("Plot already exists in {}", directory.display())]
+    PlotAlreadyExists {
+        /// Plot directory
+        directory: PathBuf,
+    },
+    /// Plot is locked by another reader or writer
+    #[error("Plot in {} is locked by another process", directory.display())]
+    PlotLocked {
+        /// Plot directory
+        directory: PathBuf,
+    },
+    /// Overlay can only be used on top of existing plot
+    #[error(
+        "Plot not found in {}, overlay can only be used on top of snapshot of existing plot",
+        directory.display()
+    )]
+    OverlayWithoutPlot {
+        /// Plot directory
+        directory: PathBuf,
+    },
+    /// Overlay can't be used on top of plot split into shards
+    #[error(
+        "Plot in {} is split into shards, overlay can only be used on top of plot stored in a \
+        single file",
+        directory.display()
+    )]
+    OverlayOfShardedPlot {
+        /// Plot directory
+        directory: PathBuf,
+    },
+    /// Offline tools and remote plot server can't be used with plot split into shards
+    #[error(
+        "Plot in {} is split into shards, offline tools and remote plot server only support plot \
+        stored in a single file",
+        directory.display()
+    )]
+    OfflineAccessOfShardedPlot {
+        /// Plot directory
+        directory: PathBuf,
+    },
+    /// Shard layout recorded in plot info doesn't match plot size
+    #[error(
+        "Shard layout of plot {id} is inconsistent: {} shards of {} bytes don't match plot file of \
+        {plot_file_size} bytes",
+        plot_shards.shard_count,
+        plot_shards.shard_size
+    )]
+    InconsistentPlotShards {
+        /// Plot ID
+        id: SingleDiskPlotId,
+        /// Shard layout recorded in plot info
+        plot_shards: PlotShards,
+        /// Size of plot file in bytes
+        plot_file_size: u64,
+    },
 }
 
 /// Errors that happen during plotting
@@ -370,6 +1169,17 @@ pub enum PlottingError {
         /// Lower-level error
         error: Box<dyn std::error::Error + Send + Sync + 'static>,
     },
+    /// Failed to save or load plotting progress
+    #[error("Failed to access plotting progress of sector {sector_index}: {error}")]
+    ProgressStore {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Lower-level error
+        error: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    /// Plot error
+    #[error("Plot error: {0}")]
+    Plot(#[from] SingleDiskPlotError),
     /// I/O error occurred
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
@@ -390,8 +1200,8 @@ pub enum FarmingError {
         /// Lower-level error
         error: io::Error,
     },
-    /// Failed to create memory mapping for metadata
-    #[error("Failed to create memory mapping for metadata: {error}")]
+    /// Failed to memory map or open metadata
+    #[error("Failed to access metadata: {error}")]
     FailedToMapMetadata {
         /// Lower-level error
         error: io::Error,
@@ -408,6 +1218,53 @@ pub enum FarmingError {
         /// Lower-level error
         error: rpc_client::Error,
     },
+    /// Plot was created with different KZG parameters than supplied for audit
+    #[error(
+        "Plot was created with KZG parameters {plot}, but audit was attempted with KZG parameters \
+        {supplied}"
+    )]
+    KzgParametersMismatch {
+        /// KZG parameters plot was created with
+        plot: KzgParametersId,
+        /// KZG parameters supplied for audit
+        supplied: KzgParametersId,
+    },
+    /// Failed to retrieve piece for audit
+    #[error("Failed to retrieve piece {piece_index} for audit: {error}")]
+    FailedToGetPiece {
+        /// Piece index
+        piece_index: PieceIndex,
+        /// Lower-level error
+        error: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    /// Piece for audit was not found
+    #[error("Piece {piece_index} for audit was not found")]
+    PieceNotFound {
+        /// Piece index
+        piece_index: PieceIndex,
+    },
+    /// Plot served by plot server was created with different identity than used for farming
+    #[error(
+        "Remote plot was created with public key {plot}, but farming was attempted with public key \
+        {identity}"
+    )]
+    RemoteIdentityMismatch {
+        /// Public key plot was created with
+        plot: PublicKey,
+        /// Public key of identity used for farming
+        identity: PublicKey,
+    },
+    /// Plotting or audit of a sector panicked
+    #[error("Processing of sector {sector_index} panicked: {message}")]
+    Panicked {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Panic message
+        message: String,
+    },
+    /// Plot error
+    #[error("Plot error: {0}")]
+    Plot(#[from] SingleDiskPlotError),
     /// I/O error occurred
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
@@ -432,6 +1289,7 @@ type Handler<A> = Bag<HandlerFn<A>, A>;
 #[derive(Default, Debug)]
 struct Handlers {
     sector_plotted: Handler<PlottedSector>,
+    farming_error: Handler<FarmingError>,
 }
 
 /// Single disk plot abstraction is a container for everything necessary to plot/farm with a single
@@ -441,14 +1299,32 @@ struct Handlers {
 #[must_use = "Plot does not function properly unless run() method is called"]
 pub struct SingleDiskPlot {
     single_disk_plot_info: SingleDiskPlotInfo,
-    /// All sector metadata file region is mapped, not just plotted sectors!
-    sector_metadata_mmap: Mmap,
+    /// All sector metadata file region is accessible, not just plotted sectors!
+    sector_metadata: MetadataFile,
+    sector_metadata_path: PathBuf,
     metadata_header: Arc<Mutex<PlotMetadataHeader>>,
+    plot_file: Arc<PlotFile>,
+    plot_overlay: Option<PlotOverlay>,
     plot_sector_size: u64,
+    sector_stride: u64,
+    target_sector_count: u64,
     span: Span,
     tasks: FuturesUnordered<BackgroundTask>,
     handlers: Arc<Handlers>,
+    plotting_stats: Arc<Mutex<PlottingStats>>,
+    audit_coordinator: AuditCoordinator,
+    audit_cache: AuditCache,
+    witness_cache: WitnessCache,
+    slot_probability: (u64, u64),
+    space_l: NonZeroU16,
+    /// Solution range of the latest slot received by farming
+    solution_range: Arc<Mutex<Option<SolutionRange>>>,
     piece_reader: PieceReader,
+    startup_check_report: Option<StartupCheckReport>,
+    verification_coverage: VerificationCoverage,
+    solution_outlook: SolutionOutlook,
+    max_sectors_per_slot: Option<NonZeroU64>,
+    plot_files: PlotFiles,
     _plotting_join_handle: JoinOnDrop,
     _farming_join_handle: JoinOnDrop,
     _reading_join_handle: JoinOnDrop,
@@ -470,6 +1346,20 @@ impl Drop for SingleDiskPlot {
 impl SingleDiskPlot {
     const PLOT_FILE: &'static str = "plot.bin";
     const METADATA_FILE: &'static str = "metadata.bin";
+    const SECTOR_METADATA_FILE: &'static str = "sector_metadata.bin";
+    const AUDIT_REPLAY_LOG_FILE: &'static str = "audit_replay.bin";
+
+    /// Validate everything [`SingleDiskPlot::new()`] would validate without writing anything to
+    /// disk and return description of the plot that would result from it.
+    ///
+    /// Identity is only generated in memory if it doesn't exist yet, so public key in the report
+    /// of a new plot changes between dry runs.
+    pub fn dry_run<RC>(options: DryRunOptions<'_, RC>) -> Result<DryRunReport, SingleDiskPlotError>
+    where
+        RC: RpcClient,
+    {
+        dry_run::plan(options).map(|plot_plan| plot_plan.report)
+    }
 
     /// Create new single disk plot instance
     pub fn new<RC>(options: SingleDiskPlotOptions<RC>) -> Result<Self, SingleDiskPlotError>
@@ -484,157 +1374,284 @@ impl SingleDiskPlot {
             rpc_client,
             reward_address,
             dsn_node,
+            piece_cache,
+            piece_cache_pin_ahead,
+            storage_backend,
+            bandwidth_limit,
+            piece_downloads,
+            piece_fetch_timeout,
+            slow_piece_threshold,
+            root_block_store,
+            pause_plotting_during_audit,
+            pause_encoding_during_audit,
+            audit_order,
+            max_sectors_per_slot,
+            memory_budget,
+            audit_replay_log_size,
+            plot_layout,
+            io_priority,
+            local_pieces,
+            node_segments,
+            audit_cache_size,
+            audit_read_coalescing_gap,
+            witness_cache_entries,
+            progress_store,
+            slot_probability,
+            startup_check: run_startup_check,
+            idle_verification,
+            duplicate_plot_detector,
+            sector_timings,
+            derived_identity,
         } = options;
 
+        // Everything is validated before anything is written to disk, the same way as during dry
+        // run
+        let PlotPlan {
+            report,
+            identity,
+            node_farmer_protocol_info,
+        } = dry_run::plan(DryRunOptions {
+            directory: &directory,
+            allocated_space,
+            rpc_client: &rpc_client,
+            storage_backend,
+            plot_layout: &plot_layout,
+            derived_identity: derived_identity.as_ref(),
+        })?;
+        let storage_backend = report.storage_backend;
+        if storage_backend == StorageBackend::Network {
+            info!(
+                "Plot is located on network file system, memory mapping of plot is disabled, \
+                expect lower performance"
+            );
+        }
+
         fs::create_dir_all(&directory)?;
+        fs::create_dir_all(plot_layout.metadata_directory(&directory))?;
+        if plot_layout.overlay_directory.is_some() {
+            overlay::merge_metadata(&directory, &plot_layout)?;
+        }
 
         // TODO: Parametrize concurrency, much higher default due to SSD focus
         // TODO: Use this or remove
         let _single_disk_semaphore =
             SingleDiskSemaphore::new(NonZeroU16::new(10).expect("Not a zero; qed"));
 
-        // TODO: Update `Identity` to use more specific error type and remove this `.unwrap()`
-        let identity = Identity::open_or_create(&directory).unwrap();
+        if report.new_identity {
+            identity
+                .store(&directory)
+                .map_err(|error| SingleDiskPlotError::FailedToOpenIdentity { error })?;
+        }
         let public_key = identity.public_key().to_bytes().into();
 
-        let farmer_protocol_info = tokio::task::block_in_place(|| {
-            Handle::current()
-                .block_on(rpc_client.farmer_protocol_info())
-                .map_err(SingleDiskPlotError::NodeRpcError)
-        })?;
-        let record_size = farmer_protocol_info.record_size;
-        // TODO: In case `space_l` changes on the fly, code below will break horribly
-        let space_l = farmer_protocol_info.space_l;
-        let plot_sector_size = plot_sector_size(space_l);
-
-        assert_eq!(
-            plot_sector_size % PIECE_SIZE as u64,
-            0,
-            "Sector size must be multiple of piece size"
-        );
-
-        let single_disk_plot_info = match SingleDiskPlotInfo::load_from(&directory)? {
-            Some(single_disk_plot_info) => {
-                if allocated_space != single_disk_plot_info.allocated_space() {
-                    return Err(SingleDiskPlotError::CantResize {
-                        id: *single_disk_plot_info.id(),
-                        old_space: ByteSize::b(single_disk_plot_info.allocated_space()),
-                        new_space: ByteSize::b(allocated_space),
-                    });
-                }
-
-                if &farmer_protocol_info.genesis_hash != single_disk_plot_info.genesis_hash() {
-                    return Err(SingleDiskPlotError::WrongChain {
-                        id: *single_disk_plot_info.id(),
-                        correct_chain: hex::encode(single_disk_plot_info.genesis_hash()),
-                        wrong_chain: hex::encode(farmer_protocol_info.genesis_hash),
-                    });
-                }
-
-                if &public_key != single_disk_plot_info.public_key() {
-                    return Err(SingleDiskPlotError::IdentityMismatch {
-                        id: *single_disk_plot_info.id(),
-                        correct_public_key: *single_disk_plot_info.public_key(),
-                        wrong_public_key: public_key,
-                    });
-                }
-
-                single_disk_plot_info
-            }
-            None => {
-                // TODO: Global generator that makes sure to avoid returning the same sector index
-                //  for multiple disks
-                let first_sector_index = SystemTime::UNIX_EPOCH
-                    .elapsed()
-                    .expect("Unix epoch is always in the past; qed")
-                    .as_secs()
-                    .wrapping_mul(u64::from(u32::MAX));
-
-                let single_disk_plot_info = SingleDiskPlotInfo::new(
-                    SingleDiskPlotId::new(),
-                    farmer_protocol_info.genesis_hash,
-                    public_key,
-                    first_sector_index,
-                    allocated_space,
-                );
-
-                single_disk_plot_info.store_to(&directory)?;
-
-                single_disk_plot_info
-            }
-        };
+        let single_disk_plot_info = report.info;
+        if report.new_plot {
+            single_disk_plot_info.store_to(&directory)?;
+        }
 
         let single_disk_plot_id = *single_disk_plot_info.id();
         let first_sector_index = single_disk_plot_info.first_sector_index();
 
-        // TODO: Account for plot overhead
-        let target_sector_count = allocated_space / plot_sector_size;
-
         // TODO: Consider file locking to prevent other apps from modifying it
         let mut metadata_file = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
-            .open(directory.join(Self::METADATA_FILE))?;
+            .open(plot_layout.metadata_file(&directory))?;
 
-        let (metadata_header, mut metadata_header_mmap) = if metadata_file.seek(SeekFrom::End(0))?
-            == 0
-        {
+        let new_metadata = metadata_file.seek(SeekFrom::End(0))? == 0;
+
+        let mut metadata_header = if new_metadata {
+            // Farmer protocol info will be stored below, which will upgrade the version
             let metadata_header = PlotMetadataHeader {
                 version: 0,
                 sector_count: 0,
             };
 
-            metadata_file.preallocate(
-                RESERVED_PLOT_METADATA
-                    + SectorMetadata::encoded_size() as u64 * target_sector_count,
-            )?;
             metadata_file.write_all_at(metadata_header.encode().as_slice(), 0)?;
 
-            let metadata_header_mmap = unsafe {
-                MmapOptions::new()
-                    .len(PlotMetadataHeader::encoded_size())
-                    .map_mut(&metadata_file)?
-            };
-
-            (metadata_header, metadata_header_mmap)
+            metadata_header
         } else {
-            let metadata_header_mmap = unsafe {
-                MmapOptions::new()
-                    .len(PlotMetadataHeader::encoded_size())
-                    .map_mut(&metadata_file)?
-            };
+            let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+            metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
 
-            let metadata_header = PlotMetadataHeader::decode(&mut metadata_header_mmap.as_ref())
+            let metadata_header = PlotMetadataHeader::decode(&mut metadata_header_bytes.as_slice())
                 .map_err(SingleDiskPlotError::FailedToDecodeMetadataHeader)?;
 
-            if metadata_header.version != 0 {
+            if metadata_header.version > PlotMetadataHeader::LATEST_VERSION {
                 return Err(SingleDiskPlotError::UnexpectedMetadataVersion(
                     metadata_header.version,
                 ));
             }
 
-            (metadata_header, metadata_header_mmap)
+            metadata_header
         };
 
+        let kzg = Kzg::new(kzg::test_public_parameters());
+
+        // Must be determined before protocol info upgrades metadata header version
+        let initial_sector_alignment =
+            PlotSectorAlignment::initial(&metadata_header, plot_layout.sector_alignment);
+        // Plot must be audited with the same parameters it was created with, so stored protocol
+        // info takes precedence over what node reports, except for sector expiration
+        let (farmer_protocol_info, plot_kzg_parameters_id) = PlotProtocolInfo::load_or_store(
+            single_disk_plot_id,
+            &metadata_file,
+            &mut metadata_header,
+            node_farmer_protocol_info,
+            kzg.id(),
+        )?;
+        PlotSectorAlignment::load_or_store(
+            &metadata_file,
+            &mut metadata_header,
+            initial_sector_alignment,
+        )?;
+        let record_size = farmer_protocol_info.record_size;
+        let space_l = farmer_protocol_info.space_l;
+        let plot_sector_size = report.plot_sector_size;
+        let sector_stride = report.sector_stride;
+        let target_sector_count = report.target_sector_count;
+        let target_plot_size = report.plot_file_size;
+
+        if new_metadata {
+            storage_backend.preallocate(&metadata_file, report.metadata_file_size)?;
+        }
+
+        let sector_metadata_file = open_sector_metadata_file(
+            &plot_layout.sector_metadata_file(&directory),
+            &metadata_file,
+            metadata_header.sector_count,
+        )?;
+        let sector_metadata_file_size = report.sector_metadata_file_size;
+        storage_backend.preallocate(&sector_metadata_file, sector_metadata_file_size)?;
+
+        // Plotting is the only writer of metadata files
+        let mut metadata_header_mut = MetadataFileMut::open(
+            storage_backend,
+            &metadata_file,
+            PlotMetadataHeader::encoded_size(),
+        )?;
+
         let metadata_header = Arc::new(Mutex::new(metadata_header));
 
-        let mut metadata_mmap_mut = unsafe {
-            MmapOptions::new()
-                .offset(RESERVED_PLOT_METADATA)
-                .len(SectorMetadata::encoded_size() * target_sector_count as usize)
-                .map_mut(&metadata_file)?
+        let mut sector_metadata_mut = MetadataFileMut::open(
+            storage_backend,
+            &sector_metadata_file,
+            sector_metadata_file_size as usize,
+        )?;
+
+        let (plot_file, plot_overlay) = match single_disk_plot_info.plot_shards() {
+            // Overlay of sharded plot is refused during validation
+            Some(plot_shards) => {
+                let plot_file = ShardedPlotFile::open(&directory, plot_shards)?;
+                plot_file.allocate(storage_backend, target_plot_size)?;
+
+                (PlotFile::Sharded(plot_file), None)
+            }
+            None => {
+                // Sectors are plotted into overlay plot file if plot has overlay
+                let plot_file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .open(
+                        plot_layout
+                            .writable_directory(&directory)
+                            .join(Self::PLOT_FILE),
+                    )?;
+
+                let plot_overlay = if plot_layout.overlay_directory.is_some() {
+                    // Overlay only receives some of the sectors, the rest of the file is left
+                    // sparse
+                    if plot_file.metadata()?.len() < target_plot_size {
+                        plot_file.set_len(target_plot_size)?;
+                    }
+
+                    Some(PlotOverlay::open(
+                        &directory,
+                        &plot_layout,
+                        storage_backend,
+                        sector_stride,
+                    )?)
+                } else {
+                    storage_backend.preallocate(&plot_file, target_plot_size)?;
+
+                    None
+                };
+
+                (PlotFile::Single(plot_file), plot_overlay)
+            }
         };
 
-        let plot_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(directory.join(Self::PLOT_FILE))?;
+        let plot_file = Arc::new(plot_file);
+        let plot_files = PlotFiles {
+            plot_file: Arc::clone(&plot_file),
+            plot_overlay: plot_overlay.clone(),
+            sector_metadata_file: sector_metadata_file.try_clone()?,
+            metadata_file: metadata_file.try_clone()?,
+        };
 
-        plot_file.preallocate(plot_sector_size * target_sector_count)?;
+        let sector_count = metadata_header.lock().sector_count;
+        let startup_check_report = if !run_startup_check {
+            None
+        } else if sector_count == 0 {
+            debug!("No sectors plotted yet, skipping startup check");
+            None
+        } else {
+            let sector_offset = thread_rng().gen_range(0..sector_count);
+            let sector_index = first_sector_index.offset(sector_offset);
+            info!(%sector_index, "Running startup check");
+
+            let report = tokio::task::block_in_place(|| {
+                startup_check(
+                    &identity,
+                    reward_address,
+                    &farmer_protocol_info,
+                    plot_kzg_parameters_id,
+                    &kzg,
+                    sector_index,
+                    // Positional reads report disk errors as such, while memory mapping would
+                    // crash the process instead
+                    plot_file
+                        .plot_data()
+                        .layered(plot_overlay.as_ref())
+                        .sector(sector_offset, sector_stride)?,
+                    &sector_metadata_mut[sector_metadata_record_offset(sector_offset) as usize..]
+                        [..SECTOR_METADATA_RECORD_SIZE],
+                    |segment_index| {
+                        root_block_store
+                            .get(segment_index)
+                            .map(|root_block| root_block.records_root())
+                            .or_else(|| {
+                                handle
+                                    .block_on(rpc_client.records_roots(vec![segment_index]))
+                                    .ok()?
+                                    .into_iter()
+                                    .next()
+                                    .flatten()
+                            })
+                    },
+                )
+                .map_err(|error| SingleDiskPlotError::StartupCheckFailed {
+                    id: single_disk_plot_id,
+                    error,
+                })
+            })?;
+            info!(
+                read_time = ?report.read_time,
+                audit_time = ?report.audit_time,
+                proving_time = ?report.proving_time,
+                verification_time = ?report.verification_time,
+                "Startup check succeeded"
+            );
+
+            Some(report)
+        };
 
-        let mut plot_mmap_mut = unsafe { MmapMut::map_mut(&plot_file)? };
+        let mut plot_mmap_mut = match plot_file.mmap_file(storage_backend) {
+            Some(plot_file) => Some(unsafe { MmapMut::map_mut(plot_file)? }),
+            None => None,
+        };
 
         let (error_sender, error_receiver) = oneshot::channel();
         let error_sender = Arc::new(Mutex::new(Some(error_sender)));
@@ -649,26 +1666,62 @@ impl SingleDiskPlot {
             Ok(())
         }));
 
+        let pieces_in_segment =
+            u64::from(farmer_protocol_info.recorded_history_segment_size / record_size.get() * 2);
+
+        let audit_coordinator =
+            AuditCoordinator::new(pause_plotting_during_audit, pause_encoding_during_audit);
+        let sector_locks = SectorLocks::default();
+
         let handlers = Arc::<Handlers>::default();
+        let plotting_stats = Arc::<Mutex<PlottingStats>>::default();
+        let audit_cache = AuditCache::new(audit_cache_size);
+        let witness_cache = WitnessCache::new(witness_cache_entries);
+        // Latest slot received by farming, recorded in metadata of sectors as they are plotted
+        let current_slot = Arc::<Mutex<Option<SlotNumber>>>::default();
+        let solution_range = Arc::<Mutex<Option<SolutionRange>>>::default();
         let (start_sender, mut start_receiver) = broadcast::channel::<()>(1);
         let shutting_down = Arc::new(AtomicBool::new(false));
+        // Offsets of sectors that failed idle verification and wait for replotting
+        let corrupted_sectors = Arc::<Mutex<VecDeque<u64>>>::default();
+        let verification_coverage = VerificationCoverage::new(
+            idle_verification
+                .map(|idle_verification| idle_verification.coverage_window)
+                .unwrap_or_default(),
+        );
+        let solution_outlook = SolutionOutlook::new(SOLUTION_OUTLOOK_REPORT_INTERVAL);
+        let plot_incarnation = duplicate_plot_detector
+            .is_some()
+            .then(|| PlotIncarnation::claim(plot_layout.writable_directory(&directory)))
+            .transpose()?;
 
         let plotting_join_handle = thread::Builder::new()
-            .name(format!("p-{single_disk_plot_id}"))
+            .name(thread_name(&public_key, "plotting"))
             .spawn({
                 let handle = handle.clone();
                 let metadata_header = Arc::clone(&metadata_header);
                 let handlers = Arc::clone(&handlers);
+                let plotting_stats = Arc::clone(&plotting_stats);
+                let sector_timings = sector_timings.clone();
+                let audit_cache = audit_cache.clone();
+                let witness_cache = witness_cache.clone();
+                let current_slot = Arc::clone(&current_slot);
                 let shutting_down = Arc::clone(&shutting_down);
                 let rpc_client = rpc_client.clone();
                 let error_sender = Arc::clone(&error_sender);
                 let piece_publisher = dsn_node.as_ref().map(|dsn_node| {
                     PieceSectorPublisher::new(dsn_node.clone(), shutting_down.clone())
                 });
+                let plot_file = Arc::clone(&plot_file);
+                let plot_overlay = plot_overlay.clone();
+                let audit_coordinator = audit_coordinator.clone();
+                let sector_locks = sector_locks.clone();
+                let kzg = kzg.clone();
+                let corrupted_sectors = Arc::clone(&corrupted_sectors);
 
                 move || {
                     let _tokio_handle_guard = handle.enter();
-                    let span = info_span!("single_disk_plot", %single_disk_plot_id);
+                    let span = info_span!("single_disk_plot", %single_disk_plot_id, %public_key);
                     let _span_guard = span.enter();
 
                     if handle.block_on(start_receiver.recv()).is_err() {
@@ -676,66 +1729,364 @@ impl SingleDiskPlot {
                         return;
                     }
 
-                    // Initial plotting
+                    if io_priority {
+                        set_thread_io_priority(IoPriority::Idle);
+                    }
+
+                    // Transient allocations of this plotting thread, reset after every sector
+                    let mut arena = Bump::new();
+
+                    // Initial plotting, followed by replotting of corrupted sectors and sectors
+                    // plotted under stale rotation if node signals rotation
                     let initial_plotting_result = try {
-                        let chunked_sectors = plot_mmap_mut
-                            .as_mut()
-                            .chunks_exact_mut(plot_sector_size as usize);
-                        let chunked_metadata = metadata_mmap_mut
-                            .as_mut()
-                            .chunks_exact_mut(SectorMetadata::encoded_size());
-                        let plot_initial_sector = chunked_sectors
-                            .zip(chunked_metadata)
-                            .enumerate()
-                            .skip(
-                                // Some sectors may already be plotted, skip them
-                                metadata_header.lock().sector_count as usize,
-                            )
-                            .map(|(sector_index, (sector, metadata))| {
-                                (sector_index as u64 + first_sector_index, sector, metadata)
-                            });
+                        // Pieces of upcoming sectors pinned in piece cache, front is the sector
+                        // being plotted
+                        let mut pinned_sectors = VecDeque::<PinnedPieces>::new();
+                        // Some sectors may already be plotted, skip them
+                        let mut next_pinned_sector_offset = metadata_header.lock().sector_count;
+                        // Sector expiration can only change on open, so sectors are rebound to it
+                        // once per instance
+                        let mut sector_expirations_rebound = false;
 
                         // TODO: Concurrency
-                        for (sector_index, sector, sector_metadata) in plot_initial_sector {
+                        loop {
                             if shutting_down.load(Ordering::Acquire) {
-                                debug!(
-                                    %sector_index,
-                                    "Instance is shutting down, interrupting plotting"
-                                );
+                                debug!("Instance is shutting down, interrupting plotting");
                                 return;
                             }
 
-                            let farmer_protocol_info =
+                            let node_farmer_protocol_info =
                                 handle.block_on(rpc_client.farmer_protocol_info()).map_err(
                                     |error| PlottingError::FailedToGetFarmerProtocolInfo { error },
                                 )?;
 
-                            let piece_receiver = MultiChannelPieceReceiver::new(
+                            let sector_count = metadata_header.lock().sector_count;
+                            // Sectors plotted before sector expiration changed are rebound to it
+                            // in place once initial plotting is done, which requires current slot
+                            if !sector_expirations_rebound && sector_count >= target_sector_count {
+                                let maybe_current_slot = *current_slot.lock();
+                                if let Some(current_slot) = maybe_current_slot {
+                                    let rebound_sectors = rebind_sector_expirations(
+                                        &mut sector_metadata_mut,
+                                        sector_count,
+                                        &sector_locks,
+                                        current_slot,
+                                        &FarmerProtocolInfo {
+                                            rotation: node_farmer_protocol_info.rotation,
+                                            ..farmer_protocol_info
+                                        },
+                                    )?;
+                                    if rebound_sectors > 0 {
+                                        info!(
+                                            %rebound_sectors,
+                                            "Rebound sectors to new sector expiration"
+                                        );
+                                    }
+                                    sector_expirations_rebound = true;
+                                }
+                            }
+                            // Corrupted sectors are replotted once initial plotting is done
+                            let maybe_corrupted_sector_offset = if sector_count < target_sector_count
+                            {
+                                None
+                            } else {
+                                corrupted_sectors.lock().pop_front()
+                            };
+                            let (sector_offset, replotting) = if sector_count < target_sector_count
+                            {
+                                (sector_count, false)
+                            } else if let Some(sector_offset) = maybe_corrupted_sector_offset {
+                                info!(
+                                    sector_index = %first_sector_index.offset(sector_offset),
+                                    "Replotting sector that failed verification"
+                                );
+                                (sector_offset, true)
+                            } else if node_farmer_protocol_info.rotation == 0
+                                && idle_verification.is_none()
+                                && sector_expirations_rebound
+                            {
+                                // Without rotation plot never becomes stale and without idle
+                                // verification corruption is never detected
+                                break;
+                            } else {
+                                // Without rotation plot never becomes stale
+                                let maybe_stale_sector_offset =
+                                    if node_farmer_protocol_info.rotation == 0 {
+                                        None
+                                    } else {
+                                        stale_rotation_sector_offset(
+                                            &sector_metadata_mut,
+                                            sector_count,
+                                            node_farmer_protocol_info.rotation,
+                                        )
+                                    };
+                                match maybe_stale_sector_offset {
+                                    Some(sector_offset) => {
+                                        info!(
+                                            sector_index = %first_sector_index.offset(sector_offset),
+                                            "Replotting sector plotted under stale rotation"
+                                        );
+                                        (sector_offset, true)
+                                    }
+                                    None => {
+                                        let mut waited = Duration::ZERO;
+                                        while waited < STALE_ROTATION_CHECK_INTERVAL {
+                                            if shutting_down.load(Ordering::Acquire) {
+                                                return;
+                                            }
+                                            thread::sleep(SHUTDOWN_CHECK_INTERVAL);
+                                            waited += SHUTDOWN_CHECK_INTERVAL;
+                                        }
+                                        continue;
+                                    }
+                                }
+                            };
+                            let sector_metadata_offset =
+                                sector_metadata_record_offset(sector_offset) as usize;
+                            let sector_index = first_sector_index.offset(sector_offset);
+                            let _sector_span_guard =
+                                info_span!("plot_sector", %sector_index).entered();
+                            // Farming skips the sector until it is plotted completely
+                            let _sector_write_guard = sector_locks.write(sector_offset);
+                            if replotting {
+                                // Cleared record excludes sector from farming until it is replotted
+                                sector_metadata_mut.write_at(
+                                    &[0; SECTOR_METADATA_RECORD_SIZE],
+                                    sector_metadata_offset,
+                                )?;
+                            }
+                            // History size according to stored root blocks and to archived
+                            // segments piece cache has seen is used as lower bound, so node that
+                            // is still syncing can't make us plot only the beginning of the
+                            // history
+                            let stored_total_pieces = root_block_store
+                                .max_segment_index()
+                                .and_then(|segment_index| {
+                                    NonZeroU64::new((segment_index.get() + 1) * pieces_in_segment)
+                                });
+                            // Only history size and rotation are taken from the node,
+                            // everything else must stay the same as during plot creation
+                            let farmer_protocol_info = FarmerProtocolInfo {
+                                total_pieces: stored_total_pieces
+                                    .into_iter()
+                                    .chain(piece_cache.total_pieces())
+                                    .fold(node_farmer_protocol_info.total_pieces, NonZeroU64::max),
+                                rotation: node_farmer_protocol_info.rotation,
+                                ..farmer_protocol_info
+                            };
+
+                            // Pieces are only pinned ahead during initial plotting, stale sectors
+                            // are not known in advance
+                            if !replotting {
+                                let pin_until = (sector_offset + piece_cache_pin_ahead)
+                                    .min(target_sector_count);
+                                while next_pinned_sector_offset < pin_until {
+                                    pinned_sectors.push_back(pin_sector_pieces(
+                                        &piece_cache,
+                                        &public_key,
+                                        first_sector_index.offset(next_pinned_sector_offset),
+                                        plot_sector_size,
+                                        &farmer_protocol_info,
+                                    ));
+                                    next_pinned_sector_offset += 1;
+                                }
+                            }
+
+                            let local_piece_receiver = local_pieces.clone().map(|local_pieces| {
+                                VerifyingPieceReceiver::new(
+                                    local_pieces,
+                                    rpc_client.clone(),
+                                    root_block_store.clone(),
+                                    kzg.clone(),
+                                    witness_cache.clone(),
+                                    farmer_protocol_info.record_size.get(),
+                                    farmer_protocol_info.recorded_history_segment_size,
+                                )
+                            });
+                            let network_piece_receiver = VerifyingPieceReceiver::new(
+                                MemoryAccountedPieceReceiver::new(
+                                    BandwidthLimitedPieceReceiver::new(
+                                        RetryingPieceReceiver::new(TimeoutPieceReceiver::new(
+                                            MultiChannelPieceReceiver::new(
+                                                rpc_client.clone(),
+                                                dsn_node.clone(),
+                                                Arc::clone(&shutting_down),
+                                            ),
+                                            piece_fetch_timeout,
+                                        )),
+                                        bandwidth_limit.clone(),
+                                    ),
+                                    memory_budget.clone(),
+                                ),
                                 rpc_client.clone(),
-                                dsn_node.clone(),
-                                &shutting_down,
+                                root_block_store.clone(),
+                                kzg.clone(),
+                                witness_cache.clone(),
+                                farmer_protocol_info.record_size.get(),
+                                farmer_protocol_info.recorded_history_segment_size,
                             );
+                            // Node segments are tried first, then local pieces and network last
+                            let piece_receiver = PausingPieceReceiver::new(
+                                CoalescingPieceReceiver::new(
+                                    CachedPieceReceiver::new(
+                                        piece_cache.clone(),
+                                        FallbackPieceReceiver::new(
+                                            node_segments.clone(),
+                                            FallbackPieceReceiver::new(
+                                                local_piece_receiver,
+                                                network_piece_receiver,
+                                            ),
+                                        ),
+                                    ),
+                                    piece_downloads.clone(),
+                                ),
+                                &audit_coordinator,
+                            );
+
+                            let sector: Box<dyn SectorDestination + '_> =
+                                match plot_mmap_mut.as_mut() {
+                                    Some(plot_mmap_mut) => Box::new(
+                                        // Plot size was checked to fit into `usize` during
+                                        // creation
+                                        &mut plot_mmap_mut
+                                            [sector_start(sector_offset, sector_stride)? as usize..]
+                                            [..plot_sector_size as usize],
+                                    ),
+                                    None => Box::new(SectorFileWriter::new(
+                                        &*plot_file,
+                                        sector_offset,
+                                        sector_stride,
+                                    )?),
+                                };
 
-                            let plotted_sector = match handle.block_on(plot_sector(
-                                &public_key,
-                                sector_index,
-                                &piece_receiver,
-                                &shutting_down,
-                                &farmer_protocol_info,
-                                io::Cursor::new(sector),
-                                io::Cursor::new(sector_metadata),
-                            )) {
-                                Ok(plotted_sector) => plotted_sector,
-                                Err(PlotSectorError::Cancelled) => {
+                            // Sector contents are held in page cache (or buffers of the file
+                            // system) until written to disk, account for the whole sector
+                            let _sector_memory_reservation = handle.block_on(
+                                memory_budget
+                                    .reserve(MemoryCategory::SectorBuffers, plot_sector_size),
+                            );
+
+                            let mut sector_output = ChecksummingWriter::new(
+                                PausingWriter::new(sector, &audit_coordinator),
+                                plot_sector_size,
+                            );
+                            // Sector output is aborted on failure, so plotting can be retried
+                            // right away
+                            let mut attempt = 1;
+                            let maybe_plotting_result = loop {
+                                let maybe_plotting_result =
+                                    catch_sector_panic(sector_index, &handlers, || {
+                                        // Complete record is written below once sector is plotted
+                                        match &progress_store {
+                                            Some(progress_store) => {
+                                                handle.block_on(plot_sector_resumable(
+                                                    &public_key,
+                                                    sector_index,
+                                                    &piece_receiver,
+                                                    &shutting_down,
+                                                    &farmer_protocol_info,
+                                                    &mut sector_output,
+                                                    io::sink(),
+                                                    progress_store.as_ref(),
+                                                    PLOTTING_PROGRESS_SAVE_INTERVAL,
+                                                    Some(&arena),
+                                                ))
+                                            }
+                                            None => handle.block_on(plot_sector_with_arena(
+                                                &public_key,
+                                                sector_index,
+                                                &piece_receiver,
+                                                &shutting_down,
+                                                &farmer_protocol_info,
+                                                &mut sector_output,
+                                                io::sink(),
+                                                &arena,
+                                            )),
+                                        }
+                                    });
+                                arena.reset();
+
+                                match maybe_plotting_result {
+                                    Some(Err(PlotSectorError::Plotting(error)))
+                                        if attempt < SECTOR_PLOTTING_ATTEMPTS =>
+                                    {
+                                        warn!(%error, %attempt, "Failed to plot sector, retrying");
+                                        attempt += 1;
+                                    }
+                                    maybe_plotting_result => {
+                                        break maybe_plotting_result;
+                                    }
+                                }
+                            };
+                            let plotted_sector = match maybe_plotting_result {
+                                Some(Ok(plotted_sector)) => plotted_sector,
+                                Some(Err(PlotSectorError::Cancelled)) => {
+                                    return;
+                                }
+                                Some(Err(PlotSectorError::Plotting(error))) => Err(error)?,
+                                None => {
+                                    // The same sector would be plotted again and likely panic
+                                    // again, so plotting of this plot stops, while farming of
+                                    // already plotted sectors and other plots carry on
+                                    warn!("Plotting stopped after panic");
                                     return;
                                 }
-                                Err(PlotSectorError::Plotting(error)) => Err(error)?,
                             };
 
-                            let mut metadata_header = metadata_header.lock();
-                            metadata_header.sector_count += 1;
-                            metadata_header_mmap
-                                .copy_from_slice(metadata_header.encode().as_slice());
+                            // Pieces of previous contents of the sector must not be audited anymore
+                            audit_cache.invalidate_sector(sector_index);
+                            // Sector must be read from overlay before it becomes visible to farming
+                            if let Some(plot_overlay) = &plot_overlay {
+                                plot_overlay.add_sector(sector_offset)?;
+                            }
+                            if witness_cache.is_enabled() {
+                                let stats = witness_cache.stats();
+                                trace!(
+                                    hits = stats.hits,
+                                    misses = stats.misses,
+                                    hit_rate = ?stats.hit_rate(),
+                                    "Witness cache usage"
+                                );
+                            }
+                            if !replotting {
+                                pinned_sectors.pop_front();
+                            }
+                            sector_metadata_mut.write_at(
+                                &SectorMetadataRecord {
+                                    sector_metadata: plotted_sector.sector_metadata,
+                                    plotted_at_slot: *current_slot.lock(),
+                                    rotation: plotted_sector.rotation,
+                                    checksum: sector_output.checksum(),
+                                }
+                                .encode(),
+                                sector_metadata_offset,
+                            )?;
+
+                            // Sector metadata record was written above, only now sector becomes
+                            // visible to farming
+                            if !replotting {
+                                let mut metadata_header = metadata_header.lock();
+                                metadata_header.sector_count += 1;
+                                metadata_header_mut
+                                    .write_at(metadata_header.encode().as_slice(), 0)?;
+                            }
+
+                            if let Some(stats) = &plotted_sector.stats {
+                                plotting_stats.lock().add(stats);
+                                if let Some(sector_timings) = &sector_timings {
+                                    sector_timings.sector_plotted(sector_index, stats.total());
+                                }
+                            }
+                            let slow_pieces = slow_pieces(&plotted_sector, slow_piece_threshold)
+                                .collect::<Vec<_>>();
+                            if !slow_pieces.is_empty() {
+                                warn!(
+                                    %sector_index,
+                                    ?slow_pieces,
+                                    "Retrieval of some pieces took longer than {:?}",
+                                    slow_piece_threshold
+                                );
+                            }
 
                             handlers.sector_plotted.call_simple(&plotted_sector);
 
@@ -764,24 +2115,49 @@ impl SingleDiskPlot {
                 }
             })?;
 
-        let global_plot_mmap = unsafe {
-            MmapOptions::new()
-                .len((plot_sector_size * target_sector_count) as usize)
-                .map(&plot_file)?
-        };
-        #[cfg(unix)]
-        {
-            global_plot_mmap.advise(memmap2::Advice::Random)?;
-        }
-        let global_sector_metadata_mmap = unsafe {
-            MmapOptions::new()
-                .offset(RESERVED_PLOT_METADATA)
-                .len(SectorMetadata::encoded_size() * target_sector_count as usize)
-                .map(&metadata_file)?
+        let global_plot_mmap = if let Some(plot_file) = plot_file.mmap_file(storage_backend) {
+            let global_plot_mmap = unsafe {
+                MmapOptions::new()
+                    .len(target_plot_size as usize)
+                    .map(plot_file)?
+            };
+            #[cfg(unix)]
+            {
+                global_plot_mmap.advise(memmap2::Advice::Random)?;
+            }
+
+            Some(global_plot_mmap)
+        } else {
+            None
         };
+        let global_sector_metadata = MetadataFile::open(
+            storage_backend,
+            &sector_metadata_file,
+            sector_metadata_file_size as usize,
+        )?;
+        // Piece reading needs rotation from sector metadata records, global access is owned by the
+        // plot
+        let reading_sector_metadata = MetadataFile::open(
+            storage_backend,
+            &sector_metadata_file,
+            sector_metadata_file_size as usize,
+        )?;
+
+        let mut audit_recorder = audit_replay_log_size
+            .map(|max_size| {
+                let path = plot_layout
+                    .writable_directory(&directory)
+                    .join(Self::AUDIT_REPLAY_LOG_FILE);
+                info!(
+                    path = %path.display(),
+                    "Recording audits that result in solutions into audit replay log"
+                );
+                AuditRecorder::open(&path, max_size)
+            })
+            .transpose()?;
 
         let farming_join_handle = thread::Builder::new()
-            .name(format!("f-{single_disk_plot_id}"))
+            .name(thread_name(&public_key, "farming"))
             .spawn({
                 let handle = handle.clone();
                 let metadata_header = Arc::clone(&metadata_header);
@@ -789,10 +2165,23 @@ impl SingleDiskPlot {
                 let shutting_down = Arc::clone(&shutting_down);
                 let identity = identity.clone();
                 let rpc_client = rpc_client.clone();
+                let plot_file = Arc::clone(&plot_file);
+                let audit_cache = audit_cache.clone();
+                let current_slot = Arc::clone(&current_slot);
+                let solution_range = Arc::clone(&solution_range);
+                let handlers = Arc::clone(&handlers);
+                let corrupted_sectors = Arc::clone(&corrupted_sectors);
+                let verification_coverage = verification_coverage.clone();
+                let solution_outlook = solution_outlook.clone();
+                let plot_overlay = plot_overlay.clone();
+                let directory = plot_layout.writable_directory(&directory).to_path_buf();
+                let duplicate_plot_detector = duplicate_plot_detector.clone();
+                let sector_timings = sector_timings.clone();
+                let audit_coordinator = audit_coordinator.clone();
 
                 move || {
                     let _tokio_handle_guard = handle.enter();
-                    let span = info_span!("single_disk_plot", %single_disk_plot_id);
+                    let span = info_span!("single_disk_plot", %single_disk_plot_id, %public_key);
                     let _span_guard = span.enter();
 
                     if handle.block_on(start_receiver.recv()).is_err() {
@@ -800,6 +2189,24 @@ impl SingleDiskPlot {
                         return;
                     }
 
+                    if io_priority {
+                        set_thread_io_priority(IoPriority::High);
+                    }
+
+                    // Incarnation is checked until it is superseded once
+                    let mut plot_incarnation = plot_incarnation;
+                    let mut plot_incarnation_checked_at = Instant::now();
+                    let mut sector_audit_order =
+                        SectorAuditOrder::new(audit_order, max_sectors_per_slot);
+                    // Sector count audit coverage was last logged for
+                    let mut audit_coverage_logged_for = None;
+                    let mut audit_errors = RepeatedErrors::<io::ErrorKind>::default();
+                    let mut submission_errors = RepeatedErrors::<()>::default();
+                    let mut idle_verification = idle_verification
+                        .map(|idle_verification| (idle_verification, IdleVerification::new()));
+                    let mut slot_timer = SlotTimer::default();
+                    let mut verification_errors = RepeatedErrors::<io::ErrorKind>::default();
+
                     let farming_result = try {
                         info!("Subscribing to slot info notifications");
                         let mut slot_info_notifications = handle
@@ -808,48 +2215,139 @@ impl SingleDiskPlot {
                                 error,
                             })?;
 
-                        while let Some(slot_info) = handle.block_on(slot_info_notifications.next())
+                        // Slot that arrived while idle verification was in progress
+                        let mut pending_slot_info = None;
+
+                        while let Some(slot_info) = pending_slot_info
+                            .take()
+                            .unwrap_or_else(|| handle.block_on(slot_info_notifications.next()))
                         {
+                            let _slot_span_guard =
+                                info_span!("audit", %slot_info.slot_number).entered();
                             debug!(?slot_info, "New slot");
+                            slot_timer.slot_arrived(Instant::now());
+                            current_slot.lock().replace(slot_info.slot_number);
+                            solution_range.lock().replace(slot_info.solution_range);
 
-                            let sector_count = metadata_header.lock().sector_count;
-                            let plot_mmap = unsafe {
-                                MmapOptions::new()
-                                    .len((plot_sector_size * sector_count) as usize)
-                                    .map(&plot_file)
-                                    .map_err(|error| FarmingError::FailedToMapPlot { error })?
-                            };
-                            #[cfg(unix)]
+                            if let (Some(incarnation), Some(duplicate_plot_detector)) =
+                                (plot_incarnation, &duplicate_plot_detector)
                             {
-                                plot_mmap
-                                    .advise(memmap2::Advice::Random)
-                                    .map_err(FarmingError::Io)?;
+                                if plot_incarnation_checked_at.elapsed()
+                                    >= PLOT_INCARNATION_CHECK_INTERVAL
+                                {
+                                    plot_incarnation_checked_at = Instant::now();
+                                    match incarnation.is_current(&directory) {
+                                        Ok(true) => {}
+                                        Ok(false) => {
+                                            plot_incarnation.take();
+                                            duplicate_plot_detector
+                                                .incarnation_superseded(public_key);
+                                        }
+                                        Err(error) => {
+                                            warn!(%error, "Failed to check plot incarnation");
+                                        }
+                                    }
+                                }
                             }
-                            let metadata_mmap = unsafe {
-                                MmapOptions::new()
-                                    .offset(RESERVED_PLOT_METADATA)
-                                    .len(SectorMetadata::encoded_size() * sector_count as usize)
-                                    .map(&metadata_file)
-                                    .map_err(|error| FarmingError::FailedToMapMetadata { error })?
+                            debug!(
+                                estimated_network_space = solution_range_to_space(
+                                    slot_info.solution_range,
+                                    slot_probability,
+                                    space_l,
+                                ),
+                                "Estimated space pledged to the network"
+                            );
+
+                            let sector_count = metadata_header.lock().sector_count;
+                            let plot_mmap = if let Some(plot_file) =
+                                plot_file.mmap_file(storage_backend)
+                            {
+                                let plot_mmap = unsafe {
+                                    MmapOptions::new()
+                                        .len(plot_mmap_len(sector_count, sector_stride)?)
+                                        .map(plot_file)
+                                        .map_err(|error| FarmingError::FailedToMapPlot { error })?
+                                };
+                                #[cfg(unix)]
+                                {
+                                    plot_mmap
+                                        .advise(memmap2::Advice::Random)
+                                        .map_err(FarmingError::Io)?;
+                                }
+
+                                Some(plot_mmap)
+                            } else {
+                                None
+                            };
+                            let plot_data = match &plot_mmap {
+                                Some(plot_mmap) => PlotData::Mmap(plot_mmap),
+                                None => plot_file.plot_data(),
                             };
-                            #[cfg(unix)]
+                            let plot_data = plot_data.layered(plot_overlay.as_ref());
+                            let metadata = MetadataFile::open(
+                                storage_backend,
+                                &sector_metadata_file,
+                                sector_metadata_file_size(sector_count) as usize,
+                            )
+                            .map_err(|error| FarmingError::FailedToMapMetadata { error })?;
+                            metadata.advise_random_access().map_err(FarmingError::Io)?;
+                            let shutting_down = Arc::clone(&shutting_down);
+
+                            let audit_coverage = audit_coverage(sector_count, max_sectors_per_slot);
+                            if audit_coverage < 1.0
+                                && audit_coverage_logged_for.replace(sector_count)
+                                    != Some(sector_count)
                             {
-                                metadata_mmap
-                                    .advise(memmap2::Advice::Random)
-                                    .map_err(FarmingError::Io)?;
+                                info!(
+                                    %sector_count,
+                                    ?max_sectors_per_slot,
+                                    %audit_coverage,
+                                    "Only a subset of sectors is audited every slot"
+                                );
                             }
-                            let shutting_down = Arc::clone(&shutting_down);
 
                             let mut solutions = Vec::<Solution<PublicKey, PublicKey>>::new();
-
-                            for (sector_index, sector, sector_metadata) in plot_mmap
-                                .chunks_exact(plot_sector_size as usize)
-                                .zip(metadata_mmap.chunks_exact(SectorMetadata::encoded_size()))
-                                .enumerate()
-                                .map(|(sector_index, (sector, metadata))| {
-                                    (sector_index as u64 + first_sector_index, sector, metadata)
-                                })
+                            let mut best_miss = BestMiss::default();
+                            let mut audited_sectors = 0;
+                            let audit_guard = audit_coordinator.start_audit();
+                            let sector_offsets = sector_audit_order
+                                .sector_offsets(sector_count, &slot_info.global_challenge);
+
+                            // Audited pieces of all sectors are read ahead with coalesced reads,
+                            // sectors that weren't prefetched are read individually below
+                            let mut prefetched_pieces = match (audit_read_coalescing_gap, plot_data)
                             {
+                                (Some(max_gap), PlotData::File(plot_file)) => metadata
+                                    .contents()
+                                    .and_then(|metadata_contents| {
+                                        prefetch_audited_pieces(
+                                            plot_file,
+                                            &sector_locks,
+                                            &audit_cache,
+                                            &metadata_contents,
+                                            &sector_offsets,
+                                            sector_stride,
+                                            first_sector_index,
+                                            &public_key,
+                                            &farmer_protocol_info,
+                                            &slot_info.global_challenge,
+                                            max_gap,
+                                        )
+                                    })
+                                    .unwrap_or_else(|error| {
+                                        warn!(
+                                            %error,
+                                            "Failed to prefetch audited pieces, reading them \
+                                            individually"
+                                        );
+                                        HashMap::new()
+                                    }),
+                                _ => HashMap::new(),
+                            };
+
+                            for sector_offset in sector_offsets {
+                                let sector_index = first_sector_index.offset(sector_offset);
+
                                 if shutting_down.load(Ordering::Acquire) {
                                     debug!(
                                         %sector_index,
@@ -858,46 +2356,376 @@ impl SingleDiskPlot {
                                     return;
                                 }
 
-                                let eligible_sector = match audit_sector(
-                                    &public_key,
-                                    sector_index,
-                                    &farmer_protocol_info,
-                                    &slot_info.global_challenge,
-                                    slot_info.voting_solution_range,
-                                    io::Cursor::new(sector),
-                                )? {
-                                    Some(eligible_sector) => eligible_sector,
-                                    None => {
+                                // Sector that is being replotted contains a mix of old and new
+                                // contents, it is skipped until replotting is finished
+                                let (_sector_read_guard, prefetched_piece) =
+                                    match prefetched_pieces.remove(&sector_offset) {
+                                        Some(PrefetchedPiece {
+                                            sector_read_guard,
+                                            offset,
+                                            piece,
+                                        }) => (sector_read_guard, Some((offset, piece))),
+                                        None => match sector_locks.try_read(sector_offset) {
+                                            Some(sector_read_guard) => (sector_read_guard, None),
+                                            None => {
+                                                trace!(
+                                                    %sector_index,
+                                                    "Skipping audit of sector that is being \
+                                                    replotted"
+                                                );
+                                                continue;
+                                            }
+                                        },
+                                    };
+
+                                // Record is read only once sector is locked, so it matches sector
+                                // contents
+                                let sector_metadata_record = match metadata.read_at(
+                                    sector_metadata_record_offset(sector_offset) as usize,
+                                    SECTOR_METADATA_RECORD_SIZE,
+                                ) {
+                                    Ok(sector_metadata_record) => sector_metadata_record,
+                                    Err(error) => {
+                                        warn!(
+                                            %sector_index,
+                                            %error,
+                                            "Failed to read sector metadata record, skipping \
+                                            sector audit"
+                                        );
                                         continue;
                                     }
                                 };
+                                let sector_metadata = sector_metadata_record.as_ref();
 
-                                let solution = match eligible_sector.try_into_solution(
-                                    &identity,
-                                    reward_address,
-                                    &farmer_protocol_info,
+                                // Sectors are audited with rotation they were plotted under,
+                                // sectors being replotted have their records cleared and are
+                                // skipped
+                                let rotation = match SectorMetadataRecord::decode(
                                     sector_metadata,
-                                )? {
-                                    Some(solution) => solution,
+                                    SectorMetadataRecord::LATEST_VERSION,
+                                ) {
+                                    Ok(record) => record.rotation,
+                                    Err(error) => {
+                                        trace!(%sector_index, %error, "Skipping sector audit");
+                                        continue;
+                                    }
+                                };
+                                let sector_farmer_protocol_info = FarmerProtocolInfo {
+                                    rotation,
+                                    ..farmer_protocol_info
+                                };
+
+                                let audit_started_at = Instant::now();
+                                let sector_audited = |solutions| {
+                                    if let Some(sector_timings) = &sector_timings {
+                                        sector_timings.sector_audited(
+                                            sector_index,
+                                            audit_started_at.elapsed(),
+                                            solutions,
+                                        );
+                                    }
+                                };
+
+                                let sector = PrefetchedSectorReader::new(
+                                    plot_data.sector(sector_offset, sector_stride)?,
+                                    prefetched_piece
+                                        .as_ref()
+                                        .map(|(offset, piece)| (*offset, &piece[..])),
+                                );
+                                // Panic during audit of one sector skips it, the rest of the plot
+                                // is still farmed
+                                let audit_result =
+                                    match catch_sector_panic(sector_index, &handlers, || {
+                                        audit_sector_cached(
+                                            &public_key,
+                                            sector_index,
+                                            &sector_farmer_protocol_info,
+                                            plot_kzg_parameters_id,
+                                            &kzg,
+                                            &slot_info.global_challenge,
+                                            slot_info.voting_solution_range,
+                                            sector,
+                                            &audit_cache,
+                                            Some(&mut best_miss),
+                                        )
+                                    }) {
+                                        Some(audit_result) => audit_result,
+                                        None => {
+                                            continue;
+                                        }
+                                    };
+                                let eligible_sector = match audit_result {
+                                    Ok(maybe_eligible_sector) => {
+                                        audited_sectors += 1;
+                                        if let Some(suppressed) = audit_errors.success() {
+                                            info!(
+                                                %suppressed,
+                                                "Sector audit recovered after repeated errors"
+                                            );
+                                        }
+                                        match maybe_eligible_sector {
+                                            Some(eligible_sector) => eligible_sector,
+                                            None => {
+                                                sector_audited(0);
+                                                continue;
+                                            }
+                                        }
+                                    }
+                                    // Failing reads of one sector shouldn't stop farming of
+                                    // the rest of the plot
+                                    Err(FarmingError::Io(error)) => {
+                                        if let Some(repeats) = audit_errors.error(error.kind()) {
+                                            warn!(
+                                                %sector_index,
+                                                %error,
+                                                %repeats,
+                                                "Failed to audit sector"
+                                            );
+                                        }
+                                        continue;
+                                    }
+                                    Err(error) => Err(error)?,
+                                };
+
+                                // Audited piece is consumed during solution creation, so it
+                                // is stored for recording beforehand
+                                let maybe_encoded_piece = audit_recorder
+                                    .is_some()
+                                    .then(|| eligible_sector.encoded_piece.clone());
+                                let audit_piece_offset = eligible_sector.audit_piece_offset;
+
+                                let maybe_proving_result =
+                                    catch_sector_panic(sector_index, &handlers, || {
+                                        eligible_sector.try_into_solution(
+                                            &identity,
+                                            reward_address,
+                                            &farmer_protocol_info,
+                                            sector_metadata,
+                                        )
+                                    });
+                                let solution = match maybe_proving_result {
+                                    Some(proving_result) => match proving_result? {
+                                        Some(solution) => solution,
+                                        None => {
+                                            sector_audited(0);
+                                            continue;
+                                        }
+                                    },
                                     None => {
                                         continue;
                                     }
                                 };
 
+                                sector_audited(1);
                                 debug!("Solution found");
                                 trace!(?solution, "Solution found");
 
+                                if let (Some(audit_recorder), Some(encoded_piece)) =
+                                    (&mut audit_recorder, maybe_encoded_piece)
+                                {
+                                    let audit_record = AuditRecord {
+                                        slot_number: slot_info.slot_number,
+                                        global_challenge: slot_info.global_challenge,
+                                        solution_range: slot_info.voting_solution_range,
+                                        public_key,
+                                        reward_address,
+                                        sector_index,
+                                        genesis_hash: farmer_protocol_info.genesis_hash,
+                                        record_size: farmer_protocol_info.record_size,
+                                        recorded_history_segment_size: farmer_protocol_info
+                                            .recorded_history_segment_size,
+                                        total_pieces: farmer_protocol_info.total_pieces,
+                                        space_l: farmer_protocol_info.space_l,
+                                        sector_expiration: farmer_protocol_info.sector_expiration,
+                                        kzg_parameters_id: plot_kzg_parameters_id,
+                                        audit_piece_offset,
+                                        encoded_piece,
+                                        sector_metadata: SectorMetadata::decode(
+                                            &mut &*sector_metadata,
+                                        )
+                                        .map_err(|error| FarmingError::FailedToDecodeMetadata {
+                                            error,
+                                        })?,
+                                        rotation: sector_farmer_protocol_info.rotation,
+                                    };
+                                    if let Err(error) = audit_recorder.record(&audit_record) {
+                                        warn!(%error, "Failed to record audit into replay log");
+                                    }
+                                }
+
+                                sector_audit_order.sector_won(sector_offset);
                                 solutions.push(solution);
                             }
+                            drop(audit_guard);
+                            if let Some(sector_timings) = &sector_timings {
+                                sector_timings.flush();
+                            }
+
+                            if let Some(report) = solution_outlook.slot_audited(
+                                slot_info.voting_solution_range,
+                                audited_sectors,
+                                best_miss,
+                                slot_timer.interval(),
+                                Instant::now(),
+                            ) {
+                                if report.is_hopeless() {
+                                    warn!(
+                                        "Solutions are not expected more often than once a week \
+                                        at current solution range, plot may need more space to \
+                                        win rewards: {report}"
+                                    );
+                                } else {
+                                    info!("Solution outlook: {report}");
+                                }
+                                if let Err(error) = report.store_to(&directory) {
+                                    warn!(%error, "Failed to store solution outlook");
+                                }
+                            }
 
-                            handle
-                                .block_on(rpc_client.submit_solution_response(SolutionResponse {
+                            if audit_cache.is_enabled() {
+                                let stats = audit_cache.stats();
+                                trace!(
+                                    hits = stats.hits,
+                                    misses = stats.misses,
+                                    hit_rate = ?stats.hit_rate(),
+                                    "Audit cache usage"
+                                );
+                            }
+
+                            if let Some(duplicate_plot_detector) = &duplicate_plot_detector {
+                                if duplicate_plot_detector.submissions_paused() {
+                                    if !solutions.is_empty() {
+                                        warn!(
+                                            solutions = %solutions.len(),
+                                            "Solutions are not submitted, duplicate plot was \
+                                            detected"
+                                        );
+                                        solutions.clear();
+                                    }
+                                } else if !solutions.is_empty() {
+                                    duplicate_plot_detector.solutions_submitted(
+                                        public_key,
+                                        solutions.len(),
+                                        Instant::now(),
+                                    );
+                                }
+                            }
+
+                            let submission_result = handle.block_on(
+                                rpc_client.submit_solution_response(SolutionResponse {
                                     slot_number: slot_info.slot_number,
                                     solutions,
-                                }))
-                                .map_err(|error| FarmingError::FailedToSubmitSolutionsResponse {
-                                    error,
-                                })?;
+                                }),
+                            );
+                            match submission_result {
+                                Ok(()) => {
+                                    if let Some(suppressed) = submission_errors.success() {
+                                        info!(
+                                            %suppressed,
+                                            "Solutions submission recovered after repeated errors"
+                                        );
+                                    }
+                                }
+                                Err(error) => {
+                                    // Node may be temporarily unavailable, next slot will be
+                                    // submitted again
+                                    if let Some(repeats) = submission_errors.error(()) {
+                                        warn!(
+                                            %error,
+                                            %repeats,
+                                            "Failed to submit solutions response"
+                                        );
+                                    }
+                                }
+                            }
+
+                            // Disk is idle until the next slot, use remaining time to verify one
+                            // sector
+                            let maybe_verification_deadline =
+                                idle_verification.as_ref().and_then(|(options, _)| {
+                                    slot_timer
+                                        .next_slot_expected_at()?
+                                        .checked_sub(options.margin)
+                                });
+                            if let (Some((_, idle_verification)), Some(verification_deadline)) =
+                                (&mut idle_verification, maybe_verification_deadline)
+                            {
+                                verification_coverage.set_sector_count(sector_count);
+                                let verification_result =
+                                    metadata.contents().and_then(|metadata_contents| {
+                                        idle_verification.verify_next(
+                                            plot_data,
+                                            &metadata_contents,
+                                            sector_count,
+                                            plot_sector_size,
+                                            sector_stride,
+                                            || {
+                                                if Instant::now() >= verification_deadline
+                                                    || shutting_down.load(Ordering::Acquire)
+                                                {
+                                                    return true;
+                                                }
+                                                // Next slot pauses verification immediately
+                                                match slot_info_notifications.next().now_or_never()
+                                                {
+                                                    Some(maybe_slot_info) => {
+                                                        pending_slot_info.replace(maybe_slot_info);
+                                                        true
+                                                    }
+                                                    None => false,
+                                                }
+                                            },
+                                        )
+                                    });
+                                match verification_result {
+                                    Ok(outcome) => {
+                                        if let Some(suppressed) = verification_errors.success() {
+                                            info!(
+                                                %suppressed,
+                                                "Idle verification recovered after repeated errors"
+                                            );
+                                        }
+                                        verification_coverage.record(outcome, Instant::now());
+                                        match outcome {
+                                            IdleVerificationOutcome::Verified { sector_offset } => {
+                                                let sector_index =
+                                                    first_sector_index.offset(sector_offset);
+                                                debug!(%sector_index, "Sector verified");
+                                            }
+                                            IdleVerificationOutcome::Corrupted {
+                                                sector_offset,
+                                            } => {
+                                                let sector_index =
+                                                    first_sector_index.offset(sector_offset);
+                                                warn!(
+                                                    %sector_index,
+                                                    "Sector contents don't match checksum, sector \
+                                                    will be replotted"
+                                                );
+                                                let mut corrupted_sectors =
+                                                    corrupted_sectors.lock();
+                                                if !corrupted_sectors.contains(&sector_offset) {
+                                                    corrupted_sectors.push_back(sector_offset);
+                                                }
+                                            }
+                                            IdleVerificationOutcome::Changed { .. }
+                                            | IdleVerificationOutcome::Paused
+                                            | IdleVerificationOutcome::NothingToVerify => {}
+                                        }
+                                    }
+                                    Err(error) => {
+                                        if let Some(repeats) =
+                                            verification_errors.error(error.kind())
+                                        {
+                                            warn!(
+                                                %error,
+                                                %repeats,
+                                                "Failed to read sector during idle verification"
+                                            );
+                                        }
+                                    }
+                                }
+                            }
                         }
                     };
 
@@ -914,16 +2742,23 @@ impl SingleDiskPlot {
         let (piece_reader, mut read_piece_receiver) = PieceReader::new();
 
         let reading_join_handle = thread::Builder::new()
-            .name(format!("r-{single_disk_plot_id}"))
+            .name(thread_name(&public_key, "reading"))
             .spawn({
                 let metadata_header = Arc::clone(&metadata_header);
                 let shutting_down = Arc::clone(&shutting_down);
+                let plot_overlay = plot_overlay.clone();
 
                 move || {
                     let _tokio_handle_guard = handle.enter();
-                    let span = info_span!("single_disk_plot", %single_disk_plot_id);
+                    let span = info_span!("single_disk_plot", %single_disk_plot_id, %public_key);
                     let _span_guard = span.enter();
 
+                    let plot_data = match &global_plot_mmap {
+                        Some(global_plot_mmap) => PlotData::Mmap(global_plot_mmap),
+                        None => plot_file.plot_data(),
+                    };
+                    let plot_data = plot_data.layered(plot_overlay.as_ref());
+
                     while let Some(read_piece_request) = handle.block_on(read_piece_receiver.next())
                     {
                         let ReadPieceRequest {
@@ -952,9 +2787,11 @@ impl SingleDiskPlot {
                             &public_key,
                             first_sector_index,
                             plot_sector_size,
+                            sector_stride,
                             record_size,
                             space_l,
-                            &global_plot_mmap,
+                            plot_data,
+                            &reading_sector_metadata,
                         );
 
                         // Doesn't matter if receiver still cares about it
@@ -965,20 +2802,40 @@ impl SingleDiskPlot {
 
         tasks.push(Box::pin(async move {
             // TODO: Error handling here
-            reward_signing(rpc_client, identity).await.unwrap().await;
+            reward_signing(rpc_client, identity, duplicate_plot_detector)
+                .await
+                .unwrap()
+                .await;
 
             Ok(())
         }));
 
         let farm = Self {
             single_disk_plot_info,
-            sector_metadata_mmap: global_sector_metadata_mmap,
+            sector_metadata: global_sector_metadata,
+            sector_metadata_path: plot_layout.sector_metadata_file(&directory),
             metadata_header,
+            plot_file,
+            plot_overlay,
             plot_sector_size,
+            sector_stride,
+            target_sector_count,
             span: Span::current(),
             tasks,
             handlers,
+            plotting_stats,
+            audit_coordinator,
+            audit_cache,
+            witness_cache,
+            slot_probability,
+            space_l,
+            solution_range,
             piece_reader,
+            startup_check_report,
+            verification_coverage,
+            solution_outlook,
+            max_sectors_per_slot,
+            plot_files,
             _plotting_join_handle: JoinOnDrop::new(plotting_join_handle),
             _farming_join_handle: JoinOnDrop::new(farming_join_handle),
             _reading_join_handle: JoinOnDrop::new(reading_join_handle),
@@ -1012,48 +2869,110 @@ impl SingleDiskPlot {
         self.single_disk_plot_info.id()
     }
 
+    /// Public key of this farm
+    pub fn public_key(&self) -> &PublicKey {
+        self.single_disk_plot_info.public_key()
+    }
+
     /// Number of sectors successfully plotted so far
     pub fn plotted_sectors_count(&self) -> u64 {
         self.metadata_header.lock().sector_count
     }
 
+    /// Number of sectors plot will have once initial plotting is complete
+    pub fn target_sectors_count(&self) -> u64 {
+        self.target_sector_count
+    }
+
+    /// Maximum number of sectors audited every slot, `None` if all sectors are audited
+    pub fn max_sectors_per_slot(&self) -> Option<NonZeroU64> {
+        self.max_sectors_per_slot
+    }
+
+    /// Fraction of plotted sectors audited every slot, which is also the fraction of rewards plot
+    /// gets compared to auditing all sectors
+    pub fn audit_coverage(&self) -> f64 {
+        audit_coverage(self.plotted_sectors_count(), self.max_sectors_per_slot)
+    }
+
+    /// Indices of sectors plotted thus far with slots they were plotted at and their expiration
+    pub fn sectors(&self) -> impl Iterator<Item = SectorInfo> + '_ {
+        sector_infos(
+            self.sector_metadata_contents(),
+            self.single_disk_plot_info.first_sector_index(),
+            self.metadata_header.lock().sector_count,
+        )
+    }
+
     /// Read information about sectors plotted thus far
     pub fn plotted_sectors(
         &self,
-    ) -> impl Iterator<Item = Result<PlottedSector, parity_scale_codec::Error>> + '_ {
+    ) -> impl Iterator<Item = Result<PlottedSector, SectorMetadataRecordError>> + '_ {
         let public_key = self.single_disk_plot_info.public_key();
         let first_sector_index = self.single_disk_plot_info.first_sector_index();
         let sector_count = self.metadata_header.lock().sector_count;
         let pieces_in_sector = self.plot_sector_size as usize / PIECE_SIZE;
-
-        (first_sector_index..)
-            .into_iter()
-            .zip(
-                self.sector_metadata_mmap
-                    .chunks_exact(SectorMetadata::encoded_size()),
-            )
-            .take(sector_count as usize)
-            .map(move |(sector_index, mut sector_metadata)| {
-                let sector_metadata = SectorMetadata::decode(&mut sector_metadata)?;
-                let sector_id = SectorId::new(public_key, sector_index);
-
-                let piece_indexes = (0u64..)
-                    .take(pieces_in_sector)
-                    .map(|piece_offset| {
-                        sector_id.derive_piece_index(
-                            piece_offset as PieceIndex,
-                            sector_metadata.total_pieces,
-                        )
-                    })
-                    .collect();
-
-                Ok(PlottedSector {
-                    sector_id,
-                    sector_index,
-                    sector_metadata,
-                    piece_indexes,
+        let sector_metadata_contents = self.sector_metadata_contents();
+
+        (0..sector_count).map(move |sector_offset| {
+            let sector_index = first_sector_index.offset(sector_offset);
+            let SectorMetadataRecord {
+                sector_metadata,
+                rotation,
+                ..
+            } = read_sector_metadata_record(&sector_metadata_contents, sector_offset)?;
+            let sector_id = SectorId::new_rotated(public_key, sector_index, rotation);
+
+            let piece_indexes = (0u64..)
+                .take(pieces_in_sector)
+                .map(|piece_offset| {
+                    sector_id.derive_piece_index(
+                        piece_offset as PieceIndex,
+                        sector_metadata.total_pieces,
+                    )
                 })
+                .collect();
+
+            Ok(PlottedSector {
+                sector_id,
+                sector_index,
+                sector_metadata,
+                rotation,
+                piece_indexes,
+                stats: None,
             })
+        })
+    }
+
+    /// Contents of sector metadata file, empty if file can't be read, in which case records of all
+    /// sectors are invalid
+    fn sector_metadata_contents(&self) -> Cow<'_, [u8]> {
+        self.sector_metadata.contents().unwrap_or_else(|error| {
+            warn!(%error, "Failed to read sector metadata file");
+            Cow::Borrowed(&[])
+        })
+    }
+
+    /// Attest that plot is held by reading `sample_count` records selected by `challenge`, proof
+    /// can be checked with [`verify_attestation()`](attestation::verify_attestation)
+    pub fn attest(
+        &self,
+        challenge: &Blake2b256Hash,
+        sample_count: usize,
+    ) -> Result<AttestationProof, SingleDiskPlotError> {
+        create_attestation(
+            self.plot_file
+                .plot_data()
+                .layered(self.plot_overlay.as_ref()),
+            &self.sector_metadata.contents()?,
+            self.single_disk_plot_info.public_key(),
+            self.single_disk_plot_info.first_sector_index(),
+            self.metadata_header.lock().sector_count,
+            self.plot_sector_size,
+            self.sector_stride,
+            challenge,
+            sample_count,
+        )
     }
 
     /// Get piece reader to read plot pieces later
@@ -1061,11 +2980,93 @@ impl SingleDiskPlot {
         self.piece_reader.clone()
     }
 
+    /// Report of startup check, `None` if it was disabled or plot had no sectors yet
+    pub fn startup_check_report(&self) -> Option<&StartupCheckReport> {
+        self.startup_check_report.as_ref()
+    }
+
+    /// Statistics of sectors plotted since plot was opened
+    pub fn plotting_stats(&self) -> PlottingStats {
+        self.plotting_stats.lock().clone()
+    }
+
+    /// Space in bytes pledged to the network estimated from solution range of the latest slot,
+    /// `None` until the first slot is received
+    pub fn estimated_network_space(&self) -> Option<u64> {
+        self.solution_range.lock().map(|solution_range| {
+            solution_range_to_space(solution_range, self.slot_probability, self.space_l)
+        })
+    }
+
+    /// Reclaim space occupied by files that are no longer used by the plot, returns number of bytes
+    /// reclaimed.
+    ///
+    /// Can be called while plot is farming and interrupted at any point, only files that are never
+    /// read again are removed. Sector metadata is stored in fixed-size records indexed by sector
+    /// offset and is never fragmented, while sectors can't be moved within the plot since their
+    /// encoding depends on sector index derived from their position.
+    pub fn compact(&self) -> io::Result<u64> {
+        let reclaimed = remove_abandoned_files(&self.sector_metadata_path)?;
+        if reclaimed > 0 {
+            info!(reclaimed, "Removed abandoned files of the plot");
+        }
+
+        Ok(reclaimed)
+    }
+
+    /// Force everything plotted so far to disk.
+    ///
+    /// Plotting doesn't sync files after every sector and leaves writing data back to the OS, this
+    /// is the commit point for callers that need sectors reported as plotted to survive a crash.
+    /// Sectors that are being plotted concurrently may or may not be included.
+    ///
+    /// NOTE: This function does blocking I/O, it must be running in a separate thread in order to
+    /// prevent blocking an executor.
+    pub fn sync_all(&self) -> io::Result<()> {
+        self.plot_files.sync_all()
+    }
+
+    /// Coverage of idle verification, always zero if it is disabled
+    pub fn verification_coverage(&self) -> VerificationCoverage {
+        self.verification_coverage.clone()
+    }
+
+    /// Outlook of solutions produced by this plot at current solution range
+    pub fn solution_outlook(&self) -> SolutionOutlook {
+        self.solution_outlook.clone()
+    }
+
+    /// Usage statistics of the cache of pieces read during recent audits
+    pub fn audit_cache_stats(&self) -> AuditCacheStats {
+        self.audit_cache.stats()
+    }
+
+    /// Cache of pieces read during recent audits, for tracking its hit rate
+    pub fn audit_cache(&self) -> AuditCache {
+        self.audit_cache.clone()
+    }
+
+    /// Coordinator of plotting and audits, for tracking time plotting was paused during audits
+    pub fn audit_coordinator(&self) -> AuditCoordinator {
+        self.audit_coordinator.clone()
+    }
+
+    /// Cache of piece witnesses verified during plotting, for tracking its hit rate
+    pub fn witness_cache(&self) -> WitnessCache {
+        self.witness_cache.clone()
+    }
+
     /// Subscribe to sector plotting notification
     pub fn on_sector_plotted(&self, callback: HandlerFn<PlottedSector>) -> HandlerId {
         self.handlers.sector_plotted.add(callback)
     }
 
+    /// Subscribe to errors that don't stop farming, like panics during plotting or audit of a
+    /// sector, the rest of the plot and other plots keep running after those
+    pub fn on_farming_error(&self, callback: HandlerFn<FarmingError>) -> HandlerId {
+        self.handlers.farming_error.add(callback)
+    }
+
     /// Run and wait for background threads to exit or return an error
     pub async fn run(mut self) -> anyhow::Result<()> {
         if let Some(start_sender) = self.start_sender.take() {
@@ -1080,8 +3081,14 @@ impl SingleDiskPlot {
         Ok(())
     }
 
-    /// Wipe everything that belongs to this single disk plot
-    pub fn wipe(directory: &Path) -> io::Result<()> {
+    /// Wipe everything that belongs to this single disk plot.
+    ///
+    /// Only overlay is wiped if plot has overlay, read-only snapshot is left untouched.
+    pub fn wipe(directory: &Path, plot_layout: &PlotLayout) -> io::Result<()> {
+        if let Some(overlay_directory) = &plot_layout.overlay_directory {
+            return PlotOverlay::wipe(overlay_directory);
+        }
+
         let single_disk_plot_info_path = directory.join(SingleDiskPlotInfo::FILE_NAME);
         let single_disk_plot_info = SingleDiskPlotInfo::load_from(directory)?.ok_or_else(|| {
             io::Error::new(
@@ -1095,23 +3102,34 @@ impl SingleDiskPlot {
 
         info!("Found single disk plot {}", single_disk_plot_info.id());
 
-        {
-            let plot = directory.join(Self::PLOT_FILE);
-            info!("Deleting plot file at {}", plot.display());
-            fs::remove_file(plot)?;
+        match single_disk_plot_info.plot_shards() {
+            Some(plot_shards) => {
+                ShardedPlotFile::wipe(directory, plot_shards)?;
+            }
+            None => {
+                let plot = directory.join(Self::PLOT_FILE);
+                info!("Deleting plot file at {}", plot.display());
+                fs::remove_file(plot)?;
+            }
         }
         {
-            let metadata = directory.join(Self::METADATA_FILE);
+            let metadata = plot_layout.metadata_file(directory);
             info!("Deleting metadata file at {}", metadata.display());
             fs::remove_file(metadata)?;
         }
-        // TODO: Identity should be able to wipe itself instead of assuming a specific file name
-        //  here
         {
-            let identity = directory.join("identity.bin");
-            info!("Deleting identity file at {}", identity.display());
-            fs::remove_file(identity)?;
+            let sector_metadata = plot_layout.sector_metadata_file(directory);
+            // Plots that were never opened after sector metadata file was introduced don't have it
+            if sector_metadata.exists() {
+                info!(
+                    "Deleting sector metadata file at {}",
+                    sector_metadata.display()
+                );
+                fs::remove_file(sector_metadata)?;
+            }
         }
+        info!("Deleting identity files in {}", directory.display());
+        Identity::wipe(directory)?;
 
         info!(
             "Deleting info file at {}",
@@ -1120,3 +3138,122 @@ impl SingleDiskPlot {
         fs::remove_file(single_disk_plot_info_path)
     }
 }
+
+fn set_thread_io_priority(io_priority: IoPriority) {
+    match set_current_thread_io_priority(io_priority) {
+        Ok(true) => {
+            debug!(?io_priority, "Set I/O priority of the thread");
+        }
+        Ok(false) => {
+            warn!("I/O priority is not supported on this platform or `io-priority` feature is off");
+        }
+        Err(error) => {
+            warn!(%error, ?io_priority, "Failed to set I/O priority of the thread");
+        }
+    }
+}
+
+/// Pin pieces sector will be plotted from in piece cache. Piece indexes depend on history size, so
+/// for sectors plotted later they are only approximate if history grows in the meantime.
+fn pin_sector_pieces(
+    piece_cache: &FarmerPieceCache,
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    plot_sector_size: u64,
+    farmer_protocol_info: &FarmerProtocolInfo,
+) -> PinnedPieces {
+    let sector_id = SectorId::new_rotated(public_key, sector_index, farmer_protocol_info.rotation);
+    piece_cache.pin(
+        (0..plot_sector_size / PIECE_SIZE as u64).map(|piece_offset| {
+            sector_id.derive_piece_index(piece_offset, farmer_protocol_info.total_pieces)
+        }),
+    )
+}
+
+/// Offset of the first of `sector_count` plotted sectors that was plotted under rotation other
+/// than `rotation`, sectors with invalid records are replotted too
+fn stale_rotation_sector_offset(
+    sector_metadata_file_contents: &[u8],
+    sector_count: u64,
+    rotation: u64,
+) -> Option<u64> {
+    (0..sector_count).find(|&sector_offset| {
+        read_sector_metadata_record(sector_metadata_file_contents, sector_offset)
+            .map(|record| record.rotation != rotation)
+            .unwrap_or(true)
+    })
+}
+
+/// Rebind the first `sector_count` sectors whose expiration doesn't match `farmer_protocol_info`
+/// to `new_slot` with [`replot_sector_in_place()`], returns the number of rebound sectors.
+///
+/// This only happens when sector expiration parameter changes after sectors were plotted. Sectors
+/// still expire as history grows regardless and need to be fully replotted with pieces selected out
+/// of the larger history then. Sectors under a different rotation and sectors whose record can't
+/// be decoded are skipped, those are fully replotted instead.
+fn rebind_sector_expirations(
+    sector_metadata_file: &mut MetadataFileMut,
+    sector_count: u64,
+    sector_locks: &SectorLocks,
+    new_slot: SlotNumber,
+    farmer_protocol_info: &FarmerProtocolInfo,
+) -> io::Result<u64> {
+    let mut rebound_sectors = 0;
+
+    for sector_offset in 0..sector_count {
+        let sector_metadata = match read_sector_metadata(sector_metadata_file, sector_offset) {
+            Ok(sector_metadata) => sector_metadata,
+            Err(_) => continue,
+        };
+        if sector_metadata.expires_at
+            == sector_expires_at(sector_metadata.total_pieces, farmer_protocol_info)
+        {
+            continue;
+        }
+
+        // Farming must not see partially written record
+        let _sector_write_guard = sector_locks.write(sector_offset);
+        let record_offset = sector_metadata_record_offset(sector_offset) as usize;
+        let mut record = [0; SECTOR_METADATA_RECORD_SIZE];
+        record.copy_from_slice(&sector_metadata_file[record_offset..][..record.len()]);
+        if let Ok(Some(_)) = replot_sector_in_place(&mut record, new_slot, farmer_protocol_info) {
+            sector_metadata_file.write_at(&record, record_offset)?;
+            rebound_sectors += 1;
+        }
+    }
+
+    Ok(rebound_sectors)
+}
+
+/// Name of background thread with `role` of the plot, short public key makes it possible to tell
+/// plots apart in panic messages and debuggers
+fn thread_name(public_key: &PublicKey, role: &str) -> String {
+    let public_key = public_key.to_string();
+    format!("plot-{}-{role}", &public_key[..4])
+}
+
+/// Run `f` that processes sector with `sector_index`, catching panic and reporting it to farming
+/// error handlers as [`FarmingError::Panicked`], in which case `None` is returned
+fn catch_sector_panic<F, T>(sector_index: SectorIndex, handlers: &Handlers, f: F) -> Option<T>
+where
+    F: FnOnce() -> T,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Unknown panic".to_string());
+            let error = FarmingError::Panicked {
+                sector_index,
+                message,
+            };
+            error!(%error, "Sector processing panicked");
+            handlers.farming_error.call_simple(&error);
+
+            None
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Spot check attesting that farmer holds specific plot.
+//!
+//! Records to sample are selected by the challenge, so attestation can't be precomputed, and all
+//! of their contents are bound to the challenge by a single hash. Verifier checks that samples
+//! were selected correctly and that every sampled piece decodes into a valid piece of archived
+//! history, without having access to the plot itself.
+
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::piece_reader::decode_piece;
+use crate::single_disk_plot::sector_metadata::read_sector_metadata_record;
+use crate::single_disk_plot::storage_backend::{sector_start, PlotData};
+use crate::single_disk_plot::{SectorMetadata, SingleDiskPlotError};
+use parity_scale_codec::{Decode, Encode};
+use std::io;
+use subspace_archiving::archiver::is_piece_valid;
+use subspace_core_primitives::crypto::blake2b_256_hash;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, Piece, PieceIndex, PublicKey, RecordsRoot, SectorId,
+    SectorIndex, SegmentIndex, BLAKE2B_256_HASH_SIZE, PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use thiserror::Error;
+
+/// Errors that happen during attestation verification
+#[derive(Debug, Error)]
+pub enum AttestationError {
+    /// Attestation was created for a different challenge
+    #[error("Attestation was created for a different challenge")]
+    ChallengeMismatch,
+    /// Attestation was created for a plot of a different farmer
+    #[error("Attestation was created for plot of {attested}, but {expected} was expected")]
+    PublicKeyMismatch {
+        /// Public key in attestation
+        attested: PublicKey,
+        /// Expected public key
+        expected: PublicKey,
+    },
+    /// Plot has no sectors to attest
+    #[error("Plot has no sectors to attest")]
+    NoSectors,
+    /// Unexpected number of samples
+    #[error("Attestation contains {actual} samples, but {expected} samples were expected")]
+    WrongSampleCount {
+        /// Expected number of samples
+        expected: usize,
+        /// Number of samples in attestation
+        actual: usize,
+    },
+    /// Sample doesn't correspond to the challenge
+    #[error(
+        "Sample {sample_index} is for sector {sector_index} piece offset {piece_offset}, which \
+        doesn't correspond to the challenge"
+    )]
+    WrongSamplePosition {
+        /// Index of the sample in attestation
+        sample_index: usize,
+        /// Sector index of the sample
+        sector_index: SectorIndex,
+        /// Piece offset of the sample
+        piece_offset: u64,
+    },
+    /// Hash doesn't match attestation contents
+    #[error("Hash doesn't match attestation contents")]
+    HashMismatch,
+    /// Records root of the segment is unknown
+    #[error("Records root of segment {segment_index} is unknown, can't verify piece")]
+    UnknownRecordsRoot {
+        /// Segment index
+        segment_index: SegmentIndex,
+    },
+    /// Sampled piece is not a valid piece of archived history
+    #[error("Piece at sector {sector_index} piece offset {piece_offset} is invalid")]
+    InvalidPiece {
+        /// Sector index of the sample
+        sector_index: SectorIndex,
+        /// Piece offset of the sample
+        piece_offset: u64,
+    },
+}
+
+/// Single sampled record of the plot
+#[derive(Debug, Encode, Decode)]
+pub struct AttestationSample {
+    /// Sector index
+    pub sector_index: SectorIndex,
+    /// Offset of the piece in sector
+    pub piece_offset: u64,
+    /// Piece as stored in the plot
+    pub encoded_piece: Piece,
+    /// Metadata of the sector piece belongs to
+    pub sector_metadata: SectorMetadata,
+    /// Rotation of the global salt sector was encoded with
+    pub rotation: u64,
+}
+
+/// Proof that farmer holds plot at the time challenge was issued, created with
+/// [`SingleDiskPlot::attest()`](crate::single_disk_plot::SingleDiskPlot::attest) and checked with
+/// [`verify_attestation()`]
+#[derive(Debug, Encode, Decode)]
+pub struct AttestationProof {
+    /// Challenge attestation was created for
+    pub challenge: Blake2b256Hash,
+    /// Public key plot belongs to
+    pub public_key: PublicKey,
+    /// Index of the first sector of the plot
+    pub first_sector_index: SectorIndex,
+    /// Number of plotted sectors
+    pub sector_count: u64,
+    /// Records selected by the challenge
+    pub samples: Vec<AttestationSample>,
+    /// Hash binding all of the above together
+    pub hash: Blake2b256Hash,
+}
+
+/// Position of the sample number `sample_index` within plot as `(sector_offset, piece_offset)`
+fn sample_position(
+    challenge: &Blake2b256Hash,
+    sample_index: u64,
+    sector_count: u64,
+    pieces_in_sector: u64,
+) -> (u64, u64) {
+    let sample_hash =
+        blake2b_256_hash(&[challenge.as_slice(), &sample_index.to_le_bytes()].concat());
+    let sector_offset = u64::from_le_bytes(
+        sample_hash[..8]
+            .try_into()
+            .expect("Hash is longer than 8 bytes; qed"),
+    ) % sector_count;
+    let piece_offset = u64::from_le_bytes(
+        sample_hash[8..16]
+            .try_into()
+            .expect("Hash is longer than 16 bytes; qed"),
+    ) % pieces_in_sector;
+
+    (sector_offset, piece_offset)
+}
+
+fn attestation_hash(
+    challenge: &Blake2b256Hash,
+    public_key: &PublicKey,
+    first_sector_index: SectorIndex,
+    sector_count: u64,
+    samples: &[AttestationSample],
+) -> Blake2b256Hash {
+    let mut hasher = blake2_rfc::blake2b::Blake2b::new(BLAKE2B_256_HASH_SIZE);
+    hasher.update(challenge);
+    hasher.update(public_key.as_ref());
+    hasher.update(&first_sector_index.get().to_le_bytes());
+    hasher.update(&sector_count.to_le_bytes());
+    for sample in samples {
+        hasher.update(&sample.encode());
+    }
+
+    hasher
+        .finalize()
+        .as_bytes()
+        .try_into()
+        .expect("Initialized with correct length; qed")
+}
+
+/// Create attestation of the plot by reading `sample_count` records selected by `challenge`,
+/// `sectors_metadata` are contents of sector metadata file
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_attestation(
+    plot: PlotData<'_>,
+    sectors_metadata: &[u8],
+    public_key: &PublicKey,
+    first_sector_index: SectorIndex,
+    sector_count: u64,
+    plot_sector_size: u64,
+    sector_stride: u64,
+    challenge: &Blake2b256Hash,
+    sample_count: usize,
+) -> Result<AttestationProof, SingleDiskPlotError> {
+    let pieces_in_sector = plot_sector_size / PIECE_SIZE as u64;
+
+    let samples = if sector_count == 0 {
+        Vec::new()
+    } else {
+        (0..sample_count as u64)
+            .map(|sample_index| {
+                let (sector_offset, piece_offset) =
+                    sample_position(challenge, sample_index, sector_count, pieces_in_sector);
+
+                let mut encoded_piece = Piece::default();
+                plot.read_exact_at(
+                    &mut encoded_piece,
+                    sector_start(sector_offset, sector_stride)? + piece_offset * PIECE_SIZE as u64,
+                )?;
+
+                let record = read_sector_metadata_record(sectors_metadata, sector_offset).map_err(
+                    |error| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Failed to read metadata of sector {sector_offset}: {error}"),
+                        )
+                    },
+                )?;
+
+                Ok(AttestationSample {
+                    sector_index: first_sector_index.offset(sector_offset),
+                    piece_offset,
+                    encoded_piece,
+                    sector_metadata: record.sector_metadata,
+                    rotation: record.rotation,
+                })
+            })
+            .collect::<Result<Vec<_>, SingleDiskPlotError>>()?
+    };
+
+    Ok(AttestationProof {
+        challenge: *challenge,
+        public_key: *public_key,
+        first_sector_index,
+        sector_count,
+        hash: attestation_hash(
+            challenge,
+            public_key,
+            first_sector_index,
+            sector_count,
+            &samples,
+        ),
+        samples,
+    })
+}
+
+/// Verify attestation created for `challenge` by farmer with `public_key` that contains
+/// `sample_count` samples.
+///
+/// `records_root` returns records root of the segment, which is used to check that every sampled
+/// piece is a valid piece of archived history.
+pub fn verify_attestation<RR>(
+    proof: &AttestationProof,
+    challenge: &Blake2b256Hash,
+    public_key: &PublicKey,
+    sample_count: usize,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    kzg: &Kzg,
+    records_root: RR,
+) -> Result<(), AttestationError>
+where
+    RR: Fn(SegmentIndex) -> Option<RecordsRoot>,
+{
+    if &proof.challenge != challenge {
+        return Err(AttestationError::ChallengeMismatch);
+    }
+    if &proof.public_key != public_key {
+        return Err(AttestationError::PublicKeyMismatch {
+            attested: proof.public_key,
+            expected: *public_key,
+        });
+    }
+    if proof.sector_count == 0 {
+        return Err(AttestationError::NoSectors);
+    }
+    if proof.samples.len() != sample_count {
+        return Err(AttestationError::WrongSampleCount {
+            expected: sample_count,
+            actual: proof.samples.len(),
+        });
+    }
+
+    let pieces_in_sector = plot_sector_size(farmer_protocol_info.space_l) / PIECE_SIZE as u64;
+    for (sample_index, sample) in proof.samples.iter().enumerate() {
+        let (sector_offset, piece_offset) = sample_position(
+            challenge,
+            sample_index as u64,
+            proof.sector_count,
+            pieces_in_sector,
+        );
+        if sample.sector_index != proof.first_sector_index.offset(sector_offset)
+            || sample.piece_offset != piece_offset
+        {
+            return Err(AttestationError::WrongSamplePosition {
+                sample_index,
+                sector_index: sample.sector_index,
+                piece_offset: sample.piece_offset,
+            });
+        }
+    }
+
+    if proof.hash
+        != attestation_hash(
+            &proof.challenge,
+            &proof.public_key,
+            proof.first_sector_index,
+            proof.sector_count,
+            &proof.samples,
+        )
+    {
+        return Err(AttestationError::HashMismatch);
+    }
+
+    let pieces_in_segment = farmer_protocol_info.recorded_history_segment_size
+        / farmer_protocol_info.record_size.get()
+        * 2;
+    for sample in &proof.samples {
+        let invalid_piece = || AttestationError::InvalidPiece {
+            sector_index: sample.sector_index,
+            piece_offset: sample.piece_offset,
+        };
+
+        // Sector was encoded with rotation it was plotted under, wrong rotation results in piece
+        // that doesn't decode correctly
+        let sector_id =
+            SectorId::new_rotated(&proof.public_key, sample.sector_index, sample.rotation);
+        let piece_index = sector_id.derive_piece_index(
+            sample.piece_offset as PieceIndex,
+            sample.sector_metadata.total_pieces,
+        );
+        let segment_index = SegmentIndex::new(piece_index / PieceIndex::from(pieces_in_segment));
+        let position = (piece_index % PieceIndex::from(pieces_in_segment)) as u32;
+
+        let records_root = records_root(segment_index)
+            .ok_or(AttestationError::UnknownRecordsRoot { segment_index })?;
+
+        if sample.encoded_piece.len() != PIECE_SIZE {
+            return Err(invalid_piece());
+        }
+        let mut piece = sample.encoded_piece.clone();
+        decode_piece(
+            &mut piece,
+            &sector_id,
+            farmer_protocol_info.record_size,
+            farmer_protocol_info.space_l,
+        );
+
+        if !is_piece_valid(
+            kzg,
+            pieces_in_segment,
+            &piece,
+            records_root,
+            position,
+            farmer_protocol_info.record_size.get(),
+        ) {
+            return Err(invalid_piece());
+        }
+    }
+
+    Ok(())
+}
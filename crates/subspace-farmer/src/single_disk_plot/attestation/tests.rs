@@ -0,0 +1,207 @@
+use crate::single_disk_plot::attestation::{
+    attestation_hash, create_attestation, verify_attestation, AttestationError, AttestationProof,
+};
+use crate::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use crate::single_disk_plot::sector_metadata::{SectorMetadataRecord, SECTOR_METADATA_RECORD_SIZE};
+use crate::single_disk_plot::storage_backend::PlotData;
+use crate::single_disk_plot::SectorMetadata;
+use crate::testing::fixtures::{archived_segment, farmer_protocol_info};
+use crate::testing::MapPieceReceiver;
+use futures::executor::block_on;
+use parity_scale_codec::Decode;
+use std::num::NonZeroU64;
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, PublicKey, RecordsRoot, SectorIndex, SegmentIndex, PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+const SAMPLE_COUNT: usize = 8;
+
+struct TestPlot {
+    public_key: PublicKey,
+    first_sector_index: SectorIndex,
+    sector_count: u64,
+    farmer_protocol_info: FarmerProtocolInfo,
+    records_root: RecordsRoot,
+    plot: Vec<u8>,
+    sectors_metadata: Vec<u8>,
+}
+
+impl TestPlot {
+    /// Plot a few sectors with a valid piece of archived history, the last sector is plotted under
+    /// rotation of the global salt
+    fn create(kzg: &Kzg) -> Self {
+        let archived_segment = archived_segment(kzg);
+        let piece_receiver = MapPieceReceiver::from_archived_segments([&archived_segment]);
+
+        let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+        let first_sector_index = SectorIndex::new(100);
+        // With a single piece in history every sector consists of the same piece
+        let farmer_protocol_info = FarmerProtocolInfo {
+            total_pieces: NonZeroU64::new(1).unwrap(),
+            ..farmer_protocol_info()
+        };
+        let sector_count = 2;
+
+        let mut plot = Vec::new();
+        // Header is not checked during attestation, so it is left zeroed
+        let mut sectors_metadata = vec![0; SECTOR_METADATA_RECORD_SIZE];
+        for sector_offset in 0..sector_count {
+            let rotation = if sector_offset == sector_count - 1 {
+                3
+            } else {
+                0
+            };
+            let mut sector_metadata = Vec::new();
+            block_on(plot_sector(
+                &public_key,
+                first_sector_index.offset(sector_offset),
+                &piece_receiver,
+                &AtomicBool::new(false),
+                &FarmerProtocolInfo {
+                    rotation,
+                    ..farmer_protocol_info
+                },
+                SectorBuffer::new(&mut plot),
+                &mut sector_metadata,
+            ))
+            .unwrap();
+            let record = SectorMetadataRecord {
+                sector_metadata: SectorMetadata::decode(&mut sector_metadata.as_slice()).unwrap(),
+                plotted_at_slot: None,
+                rotation,
+                checksum: None,
+            };
+            sectors_metadata.extend_from_slice(&record.encode());
+        }
+
+        Self {
+            public_key,
+            first_sector_index,
+            sector_count,
+            farmer_protocol_info,
+            records_root: archived_segment.root_block.records_root(),
+            plot,
+            sectors_metadata,
+        }
+    }
+
+    fn attest(&self, challenge: &Blake2b256Hash) -> AttestationProof {
+        create_attestation(
+            PlotData::Mmap(&self.plot),
+            &self.sectors_metadata,
+            &self.public_key,
+            self.first_sector_index,
+            self.sector_count,
+            plot_sector_size(self.farmer_protocol_info.space_l),
+            plot_sector_size(self.farmer_protocol_info.space_l),
+            challenge,
+            SAMPLE_COUNT,
+        )
+        .unwrap()
+    }
+
+    fn verify(
+        &self,
+        proof: &AttestationProof,
+        challenge: &Blake2b256Hash,
+        kzg: &Kzg,
+    ) -> Result<(), AttestationError> {
+        verify_attestation(
+            proof,
+            challenge,
+            &self.public_key,
+            SAMPLE_COUNT,
+            &self.farmer_protocol_info,
+            kzg,
+            |segment_index| (segment_index == SegmentIndex::ZERO).then_some(self.records_root),
+        )
+    }
+}
+
+#[test]
+fn valid_plot() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let test_plot = TestPlot::create(&kzg);
+    let challenge = rand::random();
+
+    let proof = test_plot.attest(&challenge);
+    assert_eq!(proof.samples.len(), SAMPLE_COUNT);
+    test_plot.verify(&proof, &challenge, &kzg).unwrap();
+
+    // Samples of rotated sector only decode with rotation sector was plotted under
+    if let Some(sample_index) = proof.samples.iter().position(|sample| sample.rotation != 0) {
+        let mut proof = test_plot.attest(&challenge);
+        proof.samples[sample_index].rotation = 0;
+        proof.hash = attestation_hash(
+            &proof.challenge,
+            &proof.public_key,
+            proof.first_sector_index,
+            proof.sector_count,
+            &proof.samples,
+        );
+        assert!(matches!(
+            test_plot.verify(&proof, &challenge, &kzg),
+            Err(AttestationError::InvalidPiece { .. })
+        ));
+    }
+
+    // The same challenge always results in the same proof
+    assert_eq!(test_plot.attest(&challenge).hash, proof.hash);
+
+    // Proof can't be reused for a different challenge
+    let other_challenge = rand::random();
+    assert!(matches!(
+        test_plot.verify(&proof, &other_challenge, &kzg),
+        Err(AttestationError::ChallengeMismatch)
+    ));
+    assert_ne!(test_plot.attest(&other_challenge).hash, proof.hash);
+}
+
+#[test]
+fn tampered_plot() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let mut test_plot = TestPlot::create(&kzg);
+    let challenge = rand::random();
+
+    let proof = test_plot.attest(&challenge);
+    let sample = &proof.samples[0];
+
+    // Corrupt sampled record in the plot
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+    let sampled_piece_start = (sample
+        .sector_index
+        .offset_from(test_plot.first_sector_index)
+        .unwrap()
+        * plot_sector_size
+        + sample.piece_offset * PIECE_SIZE as u64) as usize;
+    test_plot.plot[sampled_piece_start] ^= 0xff;
+
+    let tampered_proof = test_plot.attest(&challenge);
+    assert!(matches!(
+        test_plot.verify(&tampered_proof, &challenge, &kzg),
+        Err(AttestationError::InvalidPiece { .. })
+    ));
+
+    // Samples can't be replaced without updating the hash
+    let mut tampered_proof = test_plot.attest(&challenge);
+    tampered_proof.samples[0].encoded_piece = proof.samples[0].encoded_piece.clone();
+    assert!(matches!(
+        test_plot.verify(&tampered_proof, &challenge, &kzg),
+        Err(AttestationError::HashMismatch)
+    ));
+
+    // Samples must be located where challenge says
+    let mut tampered_proof = test_plot.attest(&challenge);
+    tampered_proof.samples[0].piece_offset += 1;
+    assert!(matches!(
+        test_plot.verify(&tampered_proof, &challenge, &kzg),
+        Err(AttestationError::WrongSamplePosition {
+            sample_index: 0,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,130 @@
+//! Cache of pieces read during recent audits.
+//!
+//! Consecutive slots may result in audit of the same piece of the same sector, in which case it is
+//! not necessary to read it from disk again. Cache is limited in size and hit rate is tracked, so
+//! it is possible to tell whether it is actually useful with real challenges.
+
+#[cfg(test)]
+mod tests;
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use subspace_core_primitives::{Piece, SectorIndex, PIECE_SIZE};
+
+/// Statistics of audit cache usage
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct AuditCacheStats {
+    /// Number of audits that found piece in cache
+    pub hits: u64,
+    /// Number of audits that had to read piece from disk
+    pub misses: u64,
+}
+
+impl AuditCacheStats {
+    /// Share of audits that found piece in cache, `None` if cache wasn't used yet
+    pub fn hit_rate(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        (total > 0).then(|| self.hits as f64 / total as f64)
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// Encoded pieces by sector index and offset of the piece within sector, `None` when cache is
+    /// disabled
+    pieces: Option<LruCache<(SectorIndex, u64), Piece>>,
+    stats: AuditCacheStats,
+}
+
+/// LRU cache of encoded pieces read during audits, can be cheaply cloned and shared between
+/// plotting and farming
+#[derive(Debug, Clone)]
+pub struct AuditCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl AuditCache {
+    /// Create cache that will hold at most `size` bytes of pieces, cache that can't fit a single
+    /// piece is disabled
+    pub fn new(size: u64) -> Self {
+        let capacity = (size / PIECE_SIZE as u64) as usize;
+
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                pieces: (capacity > 0).then(|| LruCache::new(capacity)),
+                stats: AuditCacheStats::default(),
+            })),
+        }
+    }
+
+    /// Whether cache is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.inner.lock().pieces.is_some()
+    }
+
+    /// Get piece at `piece_offset` of sector `sector_index` from cache or read it with `read` and
+    /// store in cache
+    pub fn get_or_read<E, R>(
+        &self,
+        sector_index: SectorIndex,
+        piece_offset: u64,
+        read: R,
+    ) -> Result<Piece, E>
+    where
+        R: FnOnce() -> Result<Piece, E>,
+    {
+        {
+            let mut inner = self.inner.lock();
+            let inner = &mut *inner;
+            let pieces = match &mut inner.pieces {
+                Some(pieces) => pieces,
+                None => {
+                    return read();
+                }
+            };
+
+            if let Some(piece) = pieces.get(&(sector_index, piece_offset)) {
+                let piece = piece.clone();
+                inner.stats.hits += 1;
+                return Ok(piece);
+            }
+            inner.stats.misses += 1;
+        }
+
+        // Lock is not held during reading, concurrent audits of the same piece will both read it
+        let piece = read()?;
+        if let Some(pieces) = &mut self.inner.lock().pieces {
+            pieces.put((sector_index, piece_offset), piece.clone());
+        }
+
+        Ok(piece)
+    }
+
+    /// Whether piece at `piece_offset` of sector `sector_index` is in cache, doesn't affect
+    /// statistics or eviction order
+    pub fn contains(&self, sector_index: SectorIndex, piece_offset: u64) -> bool {
+        self.inner.lock().pieces.as_ref().map_or(false, |pieces| {
+            pieces.contains(&(sector_index, piece_offset))
+        })
+    }
+
+    /// Remove all pieces of sector `sector_index`, must be called when sector is (re)plotted
+    pub fn invalidate_sector(&self, sector_index: SectorIndex) {
+        if let Some(pieces) = &mut self.inner.lock().pieces {
+            let keys = pieces
+                .iter()
+                .map(|(key, _piece)| *key)
+                .filter(|(cached_sector_index, _piece_offset)| *cached_sector_index == sector_index)
+                .collect::<Vec<_>>();
+            for key in keys {
+                pieces.pop(&key);
+            }
+        }
+    }
+
+    /// Statistics of cache usage so far
+    pub fn stats(&self) -> AuditCacheStats {
+        self.inner.lock().stats
+    }
+}
@@ -0,0 +1,62 @@
+use crate::single_disk_plot::audit_cache::{AuditCache, AuditCacheStats};
+use std::convert::Infallible;
+use subspace_core_primitives::{Piece, PIECE_SIZE};
+
+fn piece(byte: u8) -> Piece {
+    Piece::try_from(vec![byte; PIECE_SIZE].as_slice()).unwrap()
+}
+
+#[test]
+fn audit_cache() {
+    let audit_cache = AuditCache::new(PIECE_SIZE as u64 * 2);
+    assert!(audit_cache.is_enabled());
+    assert_eq!(audit_cache.stats().hit_rate(), None);
+
+    let get = |sector_index, piece_offset, byte| {
+        audit_cache
+            .get_or_read(sector_index, piece_offset, || {
+                Ok::<_, Infallible>(piece(byte))
+            })
+            .unwrap()
+    };
+
+    // Read and cached
+    assert_eq!(get(0, 1, 1), piece(1));
+    assert_eq!(get(1, 1, 2), piece(2));
+    // Cached version is returned without reading
+    assert_eq!(get(0, 1, 3), piece(1));
+    assert_eq!(audit_cache.stats(), AuditCacheStats { hits: 1, misses: 2 });
+
+    // Least recently used piece is evicted
+    assert_eq!(get(2, 0, 4), piece(4));
+    assert_eq!(get(0, 1, 5), piece(1));
+    assert_eq!(get(1, 1, 6), piece(6));
+
+    // Replotted sector is read again
+    audit_cache.invalidate_sector(0);
+    assert_eq!(get(0, 1, 7), piece(7));
+    assert_eq!(audit_cache.stats(), AuditCacheStats { hits: 2, misses: 5 });
+    assert_eq!(audit_cache.stats().hit_rate(), Some(2.0 / 7.0));
+
+    // Read errors are returned and not cached
+    assert!(audit_cache
+        .get_or_read(3, 0, || Err::<Piece, _>("failed"))
+        .is_err());
+    assert_eq!(get(3, 0, 8), piece(8));
+}
+
+#[test]
+fn disabled_audit_cache() {
+    let audit_cache = AuditCache::new(PIECE_SIZE as u64 - 1);
+    assert!(!audit_cache.is_enabled());
+
+    for byte in 0..2 {
+        assert_eq!(
+            audit_cache
+                .get_or_read(0, 0, || Ok::<_, Infallible>(piece(byte)))
+                .unwrap(),
+            piece(byte)
+        );
+    }
+    assert_eq!(audit_cache.stats(), AuditCacheStats::default());
+}
@@ -0,0 +1,239 @@
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use crate::single_disk_plot::plotting::SectorDestination;
+use async_trait::async_trait;
+use parking_lot::{Condvar, Mutex};
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::{Piece, PieceIndex};
+use tracing::info;
+
+/// Max time plotting can be paused waiting for a single audit to finish, makes sure plotting isn't
+/// starved on large plots where audit takes most of the slot
+const MAX_PLOTTING_PAUSE: Duration = Duration::from_millis(500);
+/// Interval at which total time plotting was paused is reported
+const PAUSED_TIME_REPORT_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+struct State {
+    audit_in_progress: bool,
+    /// Incremented every time audit starts
+    audit_generation: u64,
+    /// Generation of the last audit plotting was paused for, plotting is paused at most once per
+    /// audit
+    paused_generation: u64,
+    /// Total time plotting was paused since `report_interval_start`
+    paused_time: Duration,
+    /// Total time plotting was paused since coordinator was created, never reset
+    total_paused_time: Duration,
+    report_interval_start: Instant,
+}
+
+impl State {
+    fn maybe_report(&mut self) {
+        if self.report_interval_start.elapsed() < PAUSED_TIME_REPORT_INTERVAL {
+            return;
+        }
+
+        info!(
+            paused_time = ?self.paused_time,
+            interval = ?self.report_interval_start.elapsed(),
+            "Plotting was paused during audits"
+        );
+        self.paused_time = Duration::ZERO;
+        self.report_interval_start = Instant::now();
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    enabled: bool,
+    /// Whether encoding of pieces is paused in addition to disk writes
+    pause_encoding: bool,
+    state: Mutex<State>,
+    audit_finished: Condvar,
+}
+
+/// Coordinates plotting and farming of the same plot, such that plotting can pause disk writes (and
+/// optionally encoding) while audit is in progress and audit latency is not affected by plotting.
+///
+/// Does nothing unless enabled.
+#[derive(Debug, Clone)]
+pub struct AuditCoordinator {
+    inner: Arc<Inner>,
+}
+
+impl AuditCoordinator {
+    /// Create new instance, plotting will only be paused if `enabled` is `true`, encoding is paused
+    /// in addition to disk writes if `pause_encoding` is `true` as well
+    pub fn new(enabled: bool, pause_encoding: bool) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                enabled,
+                pause_encoding: enabled && pause_encoding,
+                state: Mutex::new(State {
+                    audit_in_progress: false,
+                    audit_generation: 0,
+                    paused_generation: 0,
+                    paused_time: Duration::ZERO,
+                    total_paused_time: Duration::ZERO,
+                    report_interval_start: Instant::now(),
+                }),
+                audit_finished: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Signal that audit has started, it is considered to be in progress until returned guard is
+    /// dropped
+    pub fn start_audit(&self) -> AuditGuard<'_> {
+        if self.inner.enabled {
+            let mut state = self.inner.state.lock();
+            state.audit_in_progress = true;
+            state.audit_generation += 1;
+            state.maybe_report();
+        }
+
+        AuditGuard { coordinator: self }
+    }
+
+    /// Wait for audit in progress to finish, but no longer than [`MAX_PLOTTING_PAUSE`] and only
+    /// once per audit
+    pub fn pause_plotting(&self) {
+        if !self.inner.enabled {
+            return;
+        }
+
+        let mut state = self.inner.state.lock();
+        if !state.audit_in_progress || state.paused_generation == state.audit_generation {
+            return;
+        }
+
+        let audit_generation = state.audit_generation;
+        state.paused_generation = audit_generation;
+
+        let pause_start = Instant::now();
+        let deadline = pause_start + MAX_PLOTTING_PAUSE;
+        while state.audit_in_progress && state.audit_generation == audit_generation {
+            if self
+                .inner
+                .audit_finished
+                .wait_until(&mut state, deadline)
+                .timed_out()
+            {
+                break;
+            }
+        }
+
+        let paused_time = pause_start.elapsed();
+        state.paused_time += paused_time;
+        state.total_paused_time += paused_time;
+        state.maybe_report();
+    }
+
+    /// Same as [`Self::pause_plotting()`], but only if encoding pause is enabled
+    pub fn pause_encoding(&self) {
+        if self.inner.pause_encoding {
+            self.pause_plotting();
+        }
+    }
+
+    /// Total time plotting was paused during the current reporting interval
+    pub fn paused_time(&self) -> Duration {
+        self.inner.state.lock().paused_time
+    }
+
+    /// Total time plotting was paused since coordinator was created, unlike
+    /// [`Self::paused_time()`] it is not reset at the end of reporting interval
+    pub fn total_paused_time(&self) -> Duration {
+        self.inner.state.lock().total_paused_time
+    }
+
+    fn finish_audit(&self) {
+        if self.inner.enabled {
+            self.inner.state.lock().audit_in_progress = false;
+            self.inner.audit_finished.notify_all();
+        }
+    }
+}
+
+/// Audit is considered in progress while this guard is alive
+#[derive(Debug)]
+pub struct AuditGuard<'a> {
+    coordinator: &'a AuditCoordinator,
+}
+
+impl Drop for AuditGuard<'_> {
+    fn drop(&mut self) {
+        self.coordinator.finish_audit();
+    }
+}
+
+/// Writer that pauses plotting before each write while audit is in progress
+pub(crate) struct PausingWriter<'a, W> {
+    inner: W,
+    audit_coordinator: &'a AuditCoordinator,
+}
+
+impl<'a, W> PausingWriter<'a, W> {
+    pub(crate) fn new(inner: W, audit_coordinator: &'a AuditCoordinator) -> Self {
+        Self {
+            inner,
+            audit_coordinator,
+        }
+    }
+}
+
+impl<W> SectorDestination for PausingWriter<'_, W>
+where
+    W: SectorDestination,
+{
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        self.audit_coordinator.pause_plotting();
+        self.inner.write_sector_chunk(offset, bytes)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        self.inner.commit()
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        self.inner.abort()
+    }
+}
+
+/// Piece receiver that pauses plotting after each received piece while audit is in progress, so
+/// that piece is not encoded during audit, only pauses if encoding pause is enabled
+pub(crate) struct PausingPieceReceiver<'a, PR> {
+    inner: PR,
+    audit_coordinator: &'a AuditCoordinator,
+}
+
+impl<'a, PR> PausingPieceReceiver<'a, PR> {
+    pub(crate) fn new(inner: PR, audit_coordinator: &'a AuditCoordinator) -> Self {
+        Self {
+            inner,
+            audit_coordinator,
+        }
+    }
+}
+
+#[async_trait]
+impl<PR> PieceReceiver for PausingPieceReceiver<'_, PR>
+where
+    PR: PieceReceiver + Send + Sync,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let maybe_piece = self.inner.get_piece(piece_index).await?;
+        self.audit_coordinator.pause_encoding();
+
+        Ok(maybe_piece)
+    }
+}
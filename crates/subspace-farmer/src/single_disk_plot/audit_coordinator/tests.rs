@@ -0,0 +1,88 @@
+use crate::single_disk_plot::audit_coordinator::{AuditCoordinator, MAX_PLOTTING_PAUSE};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn disabled() {
+    let audit_coordinator = AuditCoordinator::new(false, false);
+
+    let _audit_guard = audit_coordinator.start_audit();
+    let start = Instant::now();
+    audit_coordinator.pause_plotting();
+    assert!(start.elapsed() < MAX_PLOTTING_PAUSE / 2);
+    assert_eq!(audit_coordinator.paused_time(), Duration::ZERO);
+    assert_eq!(audit_coordinator.total_paused_time(), Duration::ZERO);
+}
+
+#[test]
+fn pause_until_audit_finished() {
+    let audit_coordinator = AuditCoordinator::new(true, false);
+
+    // No audit in progress
+    let start = Instant::now();
+    audit_coordinator.pause_plotting();
+    assert!(start.elapsed() < MAX_PLOTTING_PAUSE / 2);
+
+    let audit_duration = MAX_PLOTTING_PAUSE / 5;
+    thread::scope(|scope| {
+        let audit_guard = audit_coordinator.start_audit();
+        scope.spawn(move || {
+            thread::sleep(audit_duration);
+            drop(audit_guard);
+        });
+
+        let start = Instant::now();
+        audit_coordinator.pause_plotting();
+        let paused = start.elapsed();
+        assert!(paused >= audit_duration);
+        assert!(paused < MAX_PLOTTING_PAUSE);
+    });
+
+    assert!(audit_coordinator.paused_time() >= audit_duration);
+    // Nothing was reported yet, so totals match
+    assert_eq!(
+        audit_coordinator.total_paused_time(),
+        audit_coordinator.paused_time()
+    );
+}
+
+#[test]
+fn pause_is_bounded() {
+    let audit_coordinator = AuditCoordinator::new(true, false);
+
+    let _audit_guard = audit_coordinator.start_audit();
+
+    // Audit that takes too long doesn't block plotting forever
+    let start = Instant::now();
+    audit_coordinator.pause_plotting();
+    assert!(start.elapsed() >= MAX_PLOTTING_PAUSE);
+
+    // Plotting is only paused once per audit
+    let start = Instant::now();
+    audit_coordinator.pause_plotting();
+    assert!(start.elapsed() < MAX_PLOTTING_PAUSE / 2);
+}
+
+#[test]
+fn encoding_pause() {
+    let audit_coordinator = AuditCoordinator::new(true, false);
+    let _audit_guard = audit_coordinator.start_audit();
+
+    // Only disk writes are paused
+    let start = Instant::now();
+    audit_coordinator.pause_encoding();
+    assert!(start.elapsed() < MAX_PLOTTING_PAUSE / 2);
+    assert_eq!(audit_coordinator.paused_time(), Duration::ZERO);
+
+    let audit_coordinator = AuditCoordinator::new(true, true);
+    let _audit_guard = audit_coordinator.start_audit();
+
+    let start = Instant::now();
+    audit_coordinator.pause_encoding();
+    assert!(start.elapsed() >= MAX_PLOTTING_PAUSE);
+
+    // Encoding and writes share the pause, so plotting is still only paused once per audit
+    let start = Instant::now();
+    audit_coordinator.pause_plotting();
+    assert!(start.elapsed() < MAX_PLOTTING_PAUSE / 2);
+}
@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests;
+
+use lru::LruCache;
+use rand::prelude::*;
+use rand::seq::index;
+use std::num::NonZeroU64;
+use subspace_core_primitives::crypto::blake2b_256_hash;
+use subspace_core_primitives::Blake2b256Hash;
+
+/// Number of sectors that produced solutions recently to remember for
+/// [`AuditOrder::RecentWinnersFirst`]
+const RECENT_WINNERS: usize = 32;
+/// Domain separator of the seed of sector subset selection, keeps subset independent of
+/// [`AuditOrder::RandomPerSlot`] order derived from the same challenge
+const SECTOR_SUBSET_SEED_DOMAIN: &[u8] = b"sector_subset";
+
+/// Order in which sectors of the plot are audited.
+///
+/// Order never affects which sectors are audited, every plotted sector is audited exactly once per
+/// slot (unless number of sectors audited per slot is limited, see [`SectorAuditOrder::new()`]),
+/// but with large plots it affects which sectors are audited before slot deadline.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AuditOrder {
+    /// Sectors are audited in order they are stored in the plot
+    Sequential,
+    /// Sectors are audited in random order, derived from global challenge of the slot, such that
+    /// order is reproducible
+    RandomPerSlot,
+    /// Sectors that produced solutions recently are audited first, the rest sequentially
+    RecentWinnersFirst,
+}
+
+impl Default for AuditOrder {
+    fn default() -> Self {
+        Self::Sequential
+    }
+}
+
+/// Produces order of sector offsets for auditing according to [`AuditOrder`]
+pub(crate) struct SectorAuditOrder {
+    audit_order: AuditOrder,
+    max_sectors_per_slot: Option<NonZeroU64>,
+    /// Offsets of sectors that produced solutions recently
+    recent_winners: LruCache<u64, ()>,
+}
+
+impl SectorAuditOrder {
+    /// Create new instance, if `max_sectors_per_slot` is specified and there are more plotted
+    /// sectors than that, only a subset of sectors of that size is audited every slot
+    pub(crate) fn new(audit_order: AuditOrder, max_sectors_per_slot: Option<NonZeroU64>) -> Self {
+        Self {
+            audit_order,
+            max_sectors_per_slot,
+            recent_winners: LruCache::new(RECENT_WINNERS),
+        }
+    }
+
+    /// Offsets of `sector_count` plotted sectors in order they should be audited.
+    ///
+    /// If number of sectors audited per slot is limited, sectors are selected uniformly at random
+    /// with randomness derived from `global_challenge`, so every sector is audited in the same
+    /// fraction of slots and selection is reproducible. Selected sectors are audited in the same
+    /// relative order they would be audited without the limit.
+    pub(crate) fn sector_offsets(
+        &self,
+        sector_count: u64,
+        global_challenge: &Blake2b256Hash,
+    ) -> Vec<u64> {
+        let mut sector_offsets = (0..sector_count).collect::<Vec<_>>();
+
+        match self.audit_order {
+            AuditOrder::Sequential => {}
+            AuditOrder::RandomPerSlot => {
+                sector_offsets.shuffle(&mut StdRng::from_seed(*global_challenge));
+            }
+            AuditOrder::RecentWinnersFirst => {
+                // Most recent winners first
+                let recent_winners = self
+                    .recent_winners
+                    .iter()
+                    .map(|(&sector_offset, _)| sector_offset)
+                    .filter(|&sector_offset| sector_offset < sector_count)
+                    .collect::<Vec<_>>();
+                sector_offsets.retain(|sector_offset| !recent_winners.contains(sector_offset));
+                sector_offsets.splice(0..0, recent_winners);
+            }
+        }
+
+        if let Some(max_sectors_per_slot) = self.max_sectors_per_slot {
+            if max_sectors_per_slot.get() < sector_count {
+                let seed = blake2b_256_hash(
+                    &[global_challenge.as_slice(), SECTOR_SUBSET_SEED_DOMAIN].concat(),
+                );
+                let mut selected = vec![false; sector_count as usize];
+                for sector_offset in index::sample(
+                    &mut StdRng::from_seed(seed),
+                    sector_count as usize,
+                    max_sectors_per_slot.get() as usize,
+                ) {
+                    selected[sector_offset] = true;
+                }
+                sector_offsets.retain(|&sector_offset| selected[sector_offset as usize]);
+            }
+        }
+
+        sector_offsets
+    }
+
+    /// Remember that sector at `sector_offset` has produced a solution
+    pub(crate) fn sector_won(&mut self, sector_offset: u64) {
+        if self.audit_order == AuditOrder::RecentWinnersFirst {
+            self.recent_winners.put(sector_offset, ());
+        }
+    }
+}
+
+/// Fraction of `sector_count` plotted sectors audited every slot when at most
+/// `max_sectors_per_slot` sectors are audited per slot
+pub fn audit_coverage(sector_count: u64, max_sectors_per_slot: Option<NonZeroU64>) -> f64 {
+    match max_sectors_per_slot {
+        Some(max_sectors_per_slot) if max_sectors_per_slot.get() < sector_count => {
+            max_sectors_per_slot.get() as f64 / sector_count as f64
+        }
+        _ => 1.0,
+    }
+}
@@ -0,0 +1,189 @@
+use crate::single_disk_plot::audit_order::{
+    audit_coverage, AuditOrder, SectorAuditOrder, RECENT_WINNERS,
+};
+use std::num::NonZeroU64;
+
+const SECTOR_COUNT: u64 = 100;
+
+fn assert_audited_once(sector_offsets: &[u64]) {
+    let mut sorted_sector_offsets = sector_offsets.to_vec();
+    sorted_sector_offsets.sort_unstable();
+    assert_eq!(
+        sorted_sector_offsets,
+        (0..SECTOR_COUNT).collect::<Vec<_>>(),
+        "Every sector must be audited exactly once"
+    );
+}
+
+#[test]
+fn every_sector_audited_once() {
+    for audit_order in [
+        AuditOrder::Sequential,
+        AuditOrder::RandomPerSlot,
+        AuditOrder::RecentWinnersFirst,
+    ] {
+        let mut sector_audit_order = SectorAuditOrder::new(audit_order, None);
+        for slot in 0..10u8 {
+            let global_challenge = [slot; 32];
+            let sector_offsets = sector_audit_order.sector_offsets(SECTOR_COUNT, &global_challenge);
+            assert_audited_once(&sector_offsets);
+
+            // Also sectors that are not plotted (yet) must be ignored
+            sector_audit_order.sector_won(u64::from(slot) * 7);
+            sector_audit_order.sector_won(SECTOR_COUNT + u64::from(slot));
+        }
+    }
+}
+
+#[test]
+fn sequential() {
+    let sector_audit_order = SectorAuditOrder::new(AuditOrder::Sequential, None);
+
+    assert_eq!(
+        sector_audit_order.sector_offsets(SECTOR_COUNT, &rand::random()),
+        (0..SECTOR_COUNT).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn random_per_slot() {
+    let sector_audit_order = SectorAuditOrder::new(AuditOrder::RandomPerSlot, None);
+    let global_challenge = rand::random();
+
+    let sector_offsets = sector_audit_order.sector_offsets(SECTOR_COUNT, &global_challenge);
+    // Reproducible for the same challenge
+    assert_eq!(
+        sector_audit_order.sector_offsets(SECTOR_COUNT, &global_challenge),
+        sector_offsets
+    );
+    // Different for different challenge
+    assert_ne!(
+        sector_audit_order.sector_offsets(SECTOR_COUNT, &rand::random()),
+        sector_offsets
+    );
+}
+
+#[test]
+fn recent_winners_first() {
+    let mut sector_audit_order = SectorAuditOrder::new(AuditOrder::RecentWinnersFirst, None);
+
+    sector_audit_order.sector_won(42);
+    sector_audit_order.sector_won(7);
+    let sector_offsets = sector_audit_order.sector_offsets(SECTOR_COUNT, &rand::random());
+    assert_eq!(&sector_offsets[..2], &[7, 42]);
+    assert_eq!(
+        &sector_offsets[2..],
+        (0..SECTOR_COUNT)
+            .filter(|&sector_offset| sector_offset != 7 && sector_offset != 42)
+            .collect::<Vec<_>>()
+            .as_slice()
+    );
+
+    // Only limited number of recent winners is remembered
+    for sector_offset in 0..SECTOR_COUNT {
+        sector_audit_order.sector_won(sector_offset);
+    }
+    let sector_offsets = sector_audit_order.sector_offsets(SECTOR_COUNT, &rand::random());
+    assert_eq!(
+        &sector_offsets[..RECENT_WINNERS],
+        (SECTOR_COUNT - RECENT_WINNERS as u64..SECTOR_COUNT)
+            .rev()
+            .collect::<Vec<_>>()
+            .as_slice()
+    );
+    assert_audited_once(&sector_offsets);
+}
+
+#[test]
+fn limited_sectors_per_slot() {
+    let max_sectors_per_slot = NonZeroU64::new(10);
+
+    for audit_order in [
+        AuditOrder::Sequential,
+        AuditOrder::RandomPerSlot,
+        AuditOrder::RecentWinnersFirst,
+    ] {
+        let mut sector_audit_order = SectorAuditOrder::new(audit_order, max_sectors_per_slot);
+        sector_audit_order.sector_won(42);
+        let mut unlimited_sector_audit_order = SectorAuditOrder::new(audit_order, None);
+        unlimited_sector_audit_order.sector_won(42);
+        let global_challenge = rand::random();
+
+        let sector_offsets = sector_audit_order.sector_offsets(SECTOR_COUNT, &global_challenge);
+        assert_eq!(sector_offsets.len(), 10);
+        let mut unique_sector_offsets = sector_offsets.clone();
+        unique_sector_offsets.sort_unstable();
+        unique_sector_offsets.dedup();
+        assert_eq!(unique_sector_offsets.len(), 10);
+        // Reproducible for the same challenge
+        assert_eq!(
+            sector_audit_order.sector_offsets(SECTOR_COUNT, &global_challenge),
+            sector_offsets
+        );
+        // Different for different challenge
+        assert_ne!(
+            sector_audit_order.sector_offsets(SECTOR_COUNT, &rand::random()),
+            sector_offsets
+        );
+        // Subset doesn't depend on audit order and is audited in the same relative order
+        assert_eq!(
+            unlimited_sector_audit_order
+                .sector_offsets(SECTOR_COUNT, &global_challenge)
+                .into_iter()
+                .filter(|sector_offset| sector_offsets.contains(sector_offset))
+                .collect::<Vec<_>>(),
+            sector_offsets
+        );
+        assert_eq!(
+            SectorAuditOrder::new(AuditOrder::Sequential, max_sectors_per_slot)
+                .sector_offsets(SECTOR_COUNT, &global_challenge),
+            unique_sector_offsets
+        );
+
+        // Limit that is not below sector count behaves the same as no limit
+        for max_sectors_per_slot in [SECTOR_COUNT, SECTOR_COUNT + 1, u64::MAX] {
+            let mut sector_audit_order =
+                SectorAuditOrder::new(audit_order, NonZeroU64::new(max_sectors_per_slot));
+            sector_audit_order.sector_won(42);
+            let mut unlimited_sector_audit_order = SectorAuditOrder::new(audit_order, None);
+            unlimited_sector_audit_order.sector_won(42);
+            assert_eq!(
+                sector_audit_order.sector_offsets(SECTOR_COUNT, &global_challenge),
+                unlimited_sector_audit_order.sector_offsets(SECTOR_COUNT, &global_challenge)
+            );
+        }
+    }
+}
+
+#[test]
+fn limited_sectors_per_slot_unbiased() {
+    let sector_audit_order = SectorAuditOrder::new(AuditOrder::Sequential, NonZeroU64::new(10));
+    let slots = 2_000;
+
+    let mut audits = vec![0u32; SECTOR_COUNT as usize];
+    for slot in 0..slots {
+        let mut global_challenge = [0; 32];
+        global_challenge[..4].copy_from_slice(&u32::to_le_bytes(slot));
+        for sector_offset in sector_audit_order.sector_offsets(SECTOR_COUNT, &global_challenge) {
+            audits[sector_offset as usize] += 1;
+        }
+    }
+
+    // Every sector is expected to be audited in 10% of slots, 200 times, allow for random
+    // deviation
+    for (sector_offset, audits) in audits.into_iter().enumerate() {
+        assert!(
+            (120..=280).contains(&audits),
+            "Sector {sector_offset} audited {audits} times"
+        );
+    }
+}
+
+#[test]
+fn coverage() {
+    assert_eq!(audit_coverage(100, None), 1.0);
+    assert_eq!(audit_coverage(100, NonZeroU64::new(100)), 1.0);
+    assert_eq!(audit_coverage(100, NonZeroU64::new(1000)), 1.0);
+    assert_eq!(audit_coverage(100, NonZeroU64::new(25)), 0.25);
+    assert_eq!(audit_coverage(0, NonZeroU64::new(25)), 1.0);
+}
@@ -0,0 +1,297 @@
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::farming::{audit_sector, EligibleSector};
+use crate::single_disk_plot::storage_backend::PieceSector;
+use crate::single_disk_plot::{FarmingError, SectorMetadata};
+use parity_scale_codec::{Decode, Encode};
+use schnorrkel::{ExpansionMode, MiniSecretKey};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, Piece, PublicKey, SectorIndex, SegmentIndex, SlotNumber,
+    Solution, SolutionRange,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tracing::{info, warn};
+
+/// Version of the replay log format, stored in the first byte of the file
+const AUDIT_REPLAY_LOG_VERSION: u8 = 1;
+/// Version of the replay log format that didn't record rotation, sectors were plotted without it
+const AUDIT_REPLAY_LOG_VERSION_WITHOUT_ROTATION: u8 = 0;
+
+/// Audit that resulted in a solution, recorded with all inputs necessary to replay it.
+///
+/// Only contains data that is either public or revealed on-chain with the solution, farmer's
+/// secret key is never recorded.
+#[derive(Debug, Encode, Decode)]
+pub struct AuditRecord {
+    /// Slot number
+    pub slot_number: SlotNumber,
+    /// Global slot challenge
+    pub global_challenge: Blake2b256Hash,
+    /// Solution range used for audit
+    pub solution_range: SolutionRange,
+    /// Public key of the farmer
+    pub public_key: PublicKey,
+    /// Address where farming rewards should go
+    pub reward_address: PublicKey,
+    /// Sector index
+    pub sector_index: SectorIndex,
+    /// Genesis hash of the chain
+    pub genesis_hash: [u8; 32],
+    /// The size of data in one piece (in bytes)
+    pub record_size: NonZeroU32,
+    /// Recorded history is encoded and plotted in segments of this size (in bytes)
+    pub recorded_history_segment_size: u32,
+    /// Total number of pieces stored on the network
+    pub total_pieces: NonZeroU64,
+    /// Space parameter for proof-of-replication in bits
+    pub space_l: NonZeroU16,
+    /// Number of segments after which sector expires
+    pub sector_expiration: SegmentIndex,
+    /// Identifier of KZG parameters plot was created with
+    pub kzg_parameters_id: KzgParametersId,
+    /// Offset of the audited piece in sector
+    pub audit_piece_offset: u64,
+    /// Audited piece as stored in the sector, the only part of the sector read during audit
+    pub encoded_piece: Piece,
+    /// Metadata of the audited sector
+    pub sector_metadata: SectorMetadata,
+    /// Rotation of the global salt audited sector was encoded with
+    pub rotation: u64,
+}
+
+/// Audit record of [`AUDIT_REPLAY_LOG_VERSION_WITHOUT_ROTATION`], the same as [`AuditRecord`]
+/// without rotation
+#[derive(Decode)]
+struct AuditRecordWithoutRotation {
+    slot_number: SlotNumber,
+    global_challenge: Blake2b256Hash,
+    solution_range: SolutionRange,
+    public_key: PublicKey,
+    reward_address: PublicKey,
+    sector_index: SectorIndex,
+    genesis_hash: [u8; 32],
+    record_size: NonZeroU32,
+    recorded_history_segment_size: u32,
+    total_pieces: NonZeroU64,
+    space_l: NonZeroU16,
+    sector_expiration: SegmentIndex,
+    kzg_parameters_id: KzgParametersId,
+    audit_piece_offset: u64,
+    encoded_piece: Piece,
+    sector_metadata: SectorMetadata,
+}
+
+impl From<AuditRecordWithoutRotation> for AuditRecord {
+    fn from(audit_record: AuditRecordWithoutRotation) -> Self {
+        Self {
+            slot_number: audit_record.slot_number,
+            global_challenge: audit_record.global_challenge,
+            solution_range: audit_record.solution_range,
+            public_key: audit_record.public_key,
+            reward_address: audit_record.reward_address,
+            sector_index: audit_record.sector_index,
+            genesis_hash: audit_record.genesis_hash,
+            record_size: audit_record.record_size,
+            recorded_history_segment_size: audit_record.recorded_history_segment_size,
+            total_pieces: audit_record.total_pieces,
+            space_l: audit_record.space_l,
+            sector_expiration: audit_record.sector_expiration,
+            kzg_parameters_id: audit_record.kzg_parameters_id,
+            audit_piece_offset: audit_record.audit_piece_offset,
+            encoded_piece: audit_record.encoded_piece,
+            sector_metadata: audit_record.sector_metadata,
+            rotation: 0,
+        }
+    }
+}
+
+impl AuditRecord {
+    /// Farmer protocol info audit was done with
+    pub fn farmer_protocol_info(&self) -> FarmerProtocolInfo {
+        FarmerProtocolInfo {
+            genesis_hash: self.genesis_hash,
+            record_size: self.record_size,
+            recorded_history_segment_size: self.recorded_history_segment_size,
+            total_pieces: self.total_pieces,
+            space_l: self.space_l,
+            sector_expiration: self.sector_expiration,
+            rotation: self.rotation,
+        }
+    }
+}
+
+/// Appends audit records to the replay log file until its size limit is reached
+#[derive(Debug)]
+pub(crate) struct AuditRecorder {
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl AuditRecorder {
+    /// Open replay log at `path` for appending, creating it if necessary, file will not grow beyond
+    /// `max_size` bytes.
+    ///
+    /// Log of older version is moved aside to [`older_version_path()`] and a new log is started.
+    pub(crate) fn open(path: &Path, max_size: u64) -> io::Result<Self> {
+        let mut file = Self::open_file(path)?;
+
+        let mut size = file.metadata()?.len();
+        if size > 0 {
+            let mut version = [0];
+            file.seek(SeekFrom::Start(0))?;
+            file.read_exact(&mut version)?;
+            let version = version[0];
+            check_version(version)?;
+
+            if version != AUDIT_REPLAY_LOG_VERSION {
+                let older_version_path = older_version_path(path, version);
+                info!(
+                    path = %older_version_path.display(),
+                    %version,
+                    "Audit replay log has older version, moving it aside and starting a new one"
+                );
+                drop(file);
+                fs::rename(path, older_version_path)?;
+                file = Self::open_file(path)?;
+                size = 0;
+            }
+        }
+        if size == 0 {
+            file.write_all(&[AUDIT_REPLAY_LOG_VERSION])?;
+            size = 1;
+        }
+
+        Ok(Self {
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+    }
+
+    /// Append record to the log, record is skipped if it doesn't fit into the size limit
+    pub(crate) fn record(&mut self, audit_record: &AuditRecord) -> io::Result<()> {
+        let encoded_audit_record = audit_record.encode();
+        if self.size + encoded_audit_record.len() as u64 > self.max_size {
+            warn!(
+                slot_number = %audit_record.slot_number,
+                sector_index = %audit_record.sector_index,
+                max_size = %self.max_size,
+                "Audit replay log is full, audit is not recorded"
+            );
+            return Ok(());
+        }
+
+        self.file.write_all(&encoded_audit_record)?;
+        self.size += encoded_audit_record.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Read all audit records from replay log file at `path`
+pub fn read_audit_records(path: &Path) -> io::Result<Vec<AuditRecord>> {
+    let contents = fs::read(path)?;
+    let (&version, mut input) = contents
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "Audit replay log is empty"))?;
+    check_version(version)?;
+
+    let mut audit_records = Vec::new();
+    while !input.is_empty() {
+        let audit_record = if version == AUDIT_REPLAY_LOG_VERSION_WITHOUT_ROTATION {
+            AuditRecordWithoutRotation::decode(&mut input).map(AuditRecord::from)
+        } else {
+            AuditRecord::decode(&mut input)
+        };
+        audit_records
+            .push(audit_record.map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?);
+    }
+
+    Ok(audit_records)
+}
+
+/// Path replay log of older `version` at `path` is moved to before a new log is started
+fn older_version_path(path: &Path, version: u8) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".v{version}"));
+    path.with_file_name(file_name)
+}
+
+fn check_version(version: u8) -> io::Result<()> {
+    if version != AUDIT_REPLAY_LOG_VERSION && version != AUDIT_REPLAY_LOG_VERSION_WITHOUT_ROTATION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported audit replay log version {version}"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Result of replaying recorded audit
+#[derive(Debug)]
+pub struct ReplayedAudit {
+    /// Eligible sector produced by audit, `None` if audit didn't find sector eligible
+    pub eligible_sector: Option<EligibleSector>,
+    /// Solution created from eligible sector.
+    ///
+    /// NOTE: Secret key of the farmer is not recorded, so `public_key` and `chunk_signature` of the
+    /// solution are created with a throwaway key and don't match the original solution.
+    pub solution: Option<Solution<PublicKey, PublicKey>>,
+}
+
+/// Re-run audit and solution creation using recorded inputs
+pub fn replay_audit(audit_record: &AuditRecord, kzg: &Kzg) -> Result<ReplayedAudit, FarmingError> {
+    let farmer_protocol_info = audit_record.farmer_protocol_info();
+
+    let eligible_sector = audit_sector(
+        &audit_record.public_key,
+        audit_record.sector_index,
+        &farmer_protocol_info,
+        audit_record.kzg_parameters_id,
+        kzg,
+        &audit_record.global_challenge,
+        audit_record.solution_range,
+        PieceSector::new(
+            plot_sector_size(audit_record.space_l),
+            audit_record.audit_piece_offset,
+            &audit_record.encoded_piece,
+        ),
+    )?;
+
+    let solution = match &eligible_sector {
+        Some(eligible_sector) => {
+            let keypair = MiniSecretKey::from_bytes(&rand::random::<[u8; 32]>())
+                .expect("32 bytes can always build a key; qed")
+                .expand_to_keypair(ExpansionMode::Ed25519);
+            eligible_sector.clone().try_into_solution(
+                &keypair,
+                audit_record.reward_address,
+                &farmer_protocol_info,
+                audit_record.sector_metadata.encode().as_slice(),
+            )?
+        }
+        None => None,
+    };
+
+    Ok(ReplayedAudit {
+        eligible_sector,
+        solution,
+    })
+}
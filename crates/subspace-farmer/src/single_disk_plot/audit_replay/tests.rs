@@ -0,0 +1,222 @@
+use crate::single_disk_plot::audit_replay::{
+    older_version_path, read_audit_records, replay_audit, AuditRecord, AuditRecorder,
+};
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use crate::single_disk_plot::SectorMetadata;
+use crate::testing::fixtures::{farmer_protocol_info, DerivedPieceReceiver};
+use futures::executor::block_on;
+use parity_scale_codec::{Decode, Encode};
+use schnorrkel::{ExpansionMode, MiniSecretKey};
+use std::fs;
+use std::io::Cursor;
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{PublicKey, SectorId, SectorIndex, SlotNumber};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tempfile::TempDir;
+
+/// Plot a sector under `rotation`, audit it and create audit record the same way farmer does
+fn create_audit_record(kzg: &Kzg, rotation: u64) -> AuditRecord {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::new(5);
+    let farmer_protocol_info = FarmerProtocolInfo {
+        genesis_hash: rand::random(),
+        rotation,
+        ..farmer_protocol_info()
+    };
+
+    let mut sector = Vec::new();
+    let mut sector_metadata = Vec::new();
+    block_on(plot_sector(
+        &public_key,
+        sector_index,
+        &DerivedPieceReceiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut sector),
+        &mut sector_metadata,
+    ))
+    .unwrap();
+
+    let global_challenge = rand::random();
+    // Maximum solution range makes sure every sector is eligible
+    let solution_range = u64::MAX;
+    let eligible_sector = audit_sector(
+        &public_key,
+        sector_index,
+        &farmer_protocol_info,
+        KzgParametersId::TEST,
+        kzg,
+        &global_challenge,
+        solution_range,
+        Cursor::new(&sector),
+    )
+    .unwrap()
+    .unwrap();
+
+    AuditRecord {
+        slot_number: SlotNumber::new(42),
+        global_challenge,
+        solution_range,
+        public_key,
+        reward_address: public_key,
+        sector_index,
+        genesis_hash: farmer_protocol_info.genesis_hash,
+        record_size: farmer_protocol_info.record_size,
+        recorded_history_segment_size: farmer_protocol_info.recorded_history_segment_size,
+        total_pieces: farmer_protocol_info.total_pieces,
+        space_l: farmer_protocol_info.space_l,
+        sector_expiration: farmer_protocol_info.sector_expiration,
+        kzg_parameters_id: KzgParametersId::TEST,
+        audit_piece_offset: eligible_sector.audit_piece_offset,
+        encoded_piece: eligible_sector.encoded_piece,
+        sector_metadata: SectorMetadata::decode(&mut sector_metadata.as_slice()).unwrap(),
+        rotation: farmer_protocol_info.rotation,
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().join("audit_replay.bin");
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let audit_record = create_audit_record(&kzg, 0);
+    let mut recorder = AuditRecorder::open(&path, u64::MAX).unwrap();
+    recorder.record(&audit_record).unwrap();
+    drop(recorder);
+
+    // Reopening appends to existing log
+    let mut recorder = AuditRecorder::open(&path, u64::MAX).unwrap();
+    recorder.record(&audit_record).unwrap();
+    drop(recorder);
+
+    let audit_records = read_audit_records(&path).unwrap();
+    assert_eq!(audit_records.len(), 2);
+    for read_audit_record in &audit_records {
+        assert_eq!(read_audit_record.encode(), audit_record.encode());
+    }
+
+    let replayed_audit = replay_audit(&audit_records[0], &kzg).unwrap();
+    let eligible_sector = replayed_audit.eligible_sector.unwrap();
+    assert_eq!(eligible_sector.sector_index, audit_record.sector_index);
+    assert_eq!(
+        eligible_sector.audit_piece_offset,
+        audit_record.audit_piece_offset
+    );
+    assert_eq!(eligible_sector.encoded_piece, audit_record.encoded_piece);
+
+    // Solution created from original eligible sector must match the replayed one, except parts
+    // that depend on secret key
+    let keypair = MiniSecretKey::from_bytes(&rand::random::<[u8; 32]>())
+        .unwrap()
+        .expand_to_keypair(ExpansionMode::Ed25519);
+    let expected_solution = eligible_sector
+        .clone()
+        .try_into_solution(
+            &keypair,
+            audit_record.reward_address,
+            &audit_record.farmer_protocol_info(),
+            audit_record.sector_metadata.encode().as_slice(),
+        )
+        .unwrap()
+        .unwrap();
+    let solution = replayed_audit.solution.unwrap();
+    assert_eq!(solution.sector_index, expected_solution.sector_index);
+    assert_eq!(solution.total_pieces, expected_solution.total_pieces);
+    assert_eq!(solution.piece_offset, expected_solution.piece_offset);
+    assert_eq!(
+        solution.piece_record_hash,
+        expected_solution.piece_record_hash
+    );
+    assert_eq!(solution.chunk, expected_solution.chunk);
+}
+
+#[test]
+fn size_limit() {
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().join("audit_replay.bin");
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let audit_record = create_audit_record(&kzg, 0);
+    let encoded_len = audit_record.encode().len() as u64;
+    // Version byte and a single record fit, second record doesn't
+    let max_size = 1 + encoded_len * 2 - 1;
+
+    let mut recorder = AuditRecorder::open(&path, max_size).unwrap();
+    recorder.record(&audit_record).unwrap();
+    recorder.record(&audit_record).unwrap();
+    drop(recorder);
+
+    assert_eq!(fs::metadata(&path).unwrap().len(), 1 + encoded_len);
+    assert_eq!(read_audit_records(&path).unwrap().len(), 1);
+
+    // Limit is respected across reopening
+    let mut recorder = AuditRecorder::open(&path, max_size).unwrap();
+    recorder.record(&audit_record).unwrap();
+    drop(recorder);
+
+    assert_eq!(read_audit_records(&path).unwrap().len(), 1);
+}
+
+#[test]
+fn unsupported_version() {
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().join("audit_replay.bin");
+
+    fs::write(&path, [2]).unwrap();
+    assert!(read_audit_records(&path).is_err());
+    assert!(AuditRecorder::open(&path, u64::MAX).is_err());
+}
+
+#[test]
+fn replay_rotated_sector() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let mut audit_record = create_audit_record(&kzg, 5);
+    let replayed_audit = replay_audit(&audit_record, &kzg).unwrap();
+    let sector_id = SectorId::new_rotated(&audit_record.public_key, audit_record.sector_index, 5);
+    assert_eq!(
+        replayed_audit.eligible_sector.unwrap().local_challenge,
+        sector_id.derive_local_challenge(&audit_record.global_challenge)
+    );
+    let solution = replayed_audit.solution.unwrap();
+
+    // Sector ID depends on rotation, so audit of rotated sector can't be reproduced without it
+    audit_record.rotation = 0;
+    let reproduced = replay_audit(&audit_record, &kzg).map_or(false, |replayed_audit| {
+        replayed_audit.solution.map_or(false, |replayed_solution| {
+            replayed_solution.piece_record_hash == solution.piece_record_hash
+        })
+    });
+    assert!(!reproduced);
+}
+
+#[test]
+fn older_version() {
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().join("audit_replay.bin");
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    // Log of version `0` is the same, but records don't end with rotation
+    let audit_record = create_audit_record(&kzg, 0);
+    let encoded_audit_record = audit_record.encode();
+    let mut contents = vec![0];
+    contents.extend_from_slice(&encoded_audit_record[..encoded_audit_record.len() - 8]);
+    fs::write(&path, &contents).unwrap();
+
+    let audit_records = read_audit_records(&path).unwrap();
+    assert_eq!(audit_records.len(), 1);
+    assert_eq!(audit_records[0].encode(), encoded_audit_record);
+
+    // Recording moves older log aside instead of appending records of a different version to it
+    let mut recorder = AuditRecorder::open(&path, u64::MAX).unwrap();
+    recorder.record(&audit_record).unwrap();
+    drop(recorder);
+
+    assert_eq!(fs::read(older_version_path(&path, 0)).unwrap(), contents);
+    assert_eq!(fs::read(&path).unwrap()[0], 1);
+    assert_eq!(read_audit_records(&path).unwrap().len(), 1);
+}
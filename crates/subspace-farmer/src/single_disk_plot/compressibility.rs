@@ -0,0 +1,61 @@
+//! Estimation of how well plotted sectors compress.
+//!
+//! Compressing a plot costs CPU time on every audit, so before enabling it plot-wide operators can
+//! check on a sample of sectors how much space it would actually save.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::storage_backend::sector_start;
+use crate::single_disk_plot::SingleDiskPlotError;
+use std::io;
+use tracing::debug;
+
+/// Default number of sectors compressed by [`sample_compressibility()`]
+pub const DEFAULT_SAMPLE_SECTORS: u64 = 8;
+/// Default zstd compression level used by [`sample_compressibility()`]
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// Compress up to `sample_sectors` sectors of the plot with zstd at `compression_level` and return
+/// average ratio of compressed size to original size.
+///
+/// Sampled sectors are spread evenly across the plot. Sector that doesn't shrink would be stored
+/// uncompressed, so ratio of every sector is capped at `1.0` and the result is always within
+/// `0.0..=1.0`, lower is better. Nothing is written to the plot.
+///
+/// NOTE: This function does blocking I/O, it must be running in a separate thread in order to
+/// prevent blocking an executor.
+pub fn sample_compressibility(
+    plot: &PlotReader,
+    sample_sectors: u64,
+    compression_level: i32,
+) -> Result<f64, SingleDiskPlotError> {
+    let sample_sectors = sample_sectors.min(plot.sector_count());
+    if sample_sectors == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Plot has no sectors or no sectors were requested for sampling",
+        )
+        .into());
+    }
+
+    let mut sector = vec![0; plot.plot_sector_size as usize];
+    let mut ratio_sum = 0.0;
+    for sample in 0..sample_sectors {
+        let sector_offset = sample * plot.sector_count() / sample_sectors;
+        let offset = sector_start(sector_offset, plot.sector_stride)?;
+        plot.plot_file.read_exact_at(&mut sector, offset)?;
+        // Sampled sectors are only read once, don't push useful pages out of page cache
+        plot.plot_file.drop_cache(offset, plot.plot_sector_size)?;
+
+        let compressed_size = zstd::bulk::compress(&sector, compression_level)?.len();
+        let ratio = (compressed_size as f64 / sector.len() as f64).min(1.0);
+        debug!(%sector_offset, %ratio, "Sector compressed");
+
+        ratio_sum += ratio;
+    }
+
+    Ok(ratio_sum / sample_sectors as f64)
+}
@@ -0,0 +1,66 @@
+use crate::single_disk_plot::compressibility::{
+    sample_compressibility, DEFAULT_COMPRESSION_LEVEL, DEFAULT_SAMPLE_SECTORS,
+};
+use crate::single_disk_plot::read_only::tests::TestPlot;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::SingleDiskPlot;
+use rand::prelude::*;
+use std::fs;
+use std::path::Path;
+use subspace_core_primitives::crypto::kzg::KzgParametersId;
+use tempfile::TempDir;
+
+fn create_plot(directory: &Path) -> TestPlot {
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory);
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    test_plot
+}
+
+#[test]
+fn plotted_data() {
+    let directory = TempDir::new().unwrap();
+    create_plot(directory.path());
+    let plot = PlotReader::open(directory.path()).unwrap();
+
+    for compression_level in [1, DEFAULT_COMPRESSION_LEVEL, 19] {
+        let ratio =
+            sample_compressibility(&plot, DEFAULT_SAMPLE_SECTORS, compression_level).unwrap();
+        assert!((0.0..=1.0).contains(&ratio), "{ratio}");
+    }
+}
+
+#[test]
+fn high_entropy_sectors() {
+    let directory = TempDir::new().unwrap();
+    let test_plot = create_plot(directory.path());
+    let mut plot_contents = vec![0u8; test_plot.plot.len()];
+    thread_rng().fill(plot_contents.as_mut_slice());
+    fs::write(
+        directory.path().join(SingleDiskPlot::PLOT_FILE),
+        &plot_contents,
+    )
+    .unwrap();
+    let plot = PlotReader::open(directory.path()).unwrap();
+
+    let ratio = sample_compressibility(&plot, 1, DEFAULT_COMPRESSION_LEVEL).unwrap();
+    assert!(ratio > 0.99, "{ratio}");
+}
+
+#[test]
+fn low_entropy_sectors() {
+    let directory = TempDir::new().unwrap();
+    let test_plot = create_plot(directory.path());
+    fs::write(
+        directory.path().join(SingleDiskPlot::PLOT_FILE),
+        vec![0u8; test_plot.plot.len()],
+    )
+    .unwrap();
+    let plot = PlotReader::open(directory.path()).unwrap();
+
+    let ratio =
+        sample_compressibility(&plot, DEFAULT_SAMPLE_SECTORS, DEFAULT_COMPRESSION_LEVEL).unwrap();
+    assert!(ratio < 0.01, "{ratio}");
+
+    // Nothing to sample
+    assert!(sample_compressibility(&plot, 0, DEFAULT_COMPRESSION_LEVEL).is_err());
+}
@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+use std::io;
+use std::io::Read;
+use subspace_core_primitives::{Blake2b256Hash, BLAKE2B_256_HASH_SIZE};
+
+/// Size of the chunk sectors are read and hashed in
+const READ_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Find sectors with identical contents among the first `sector_count` sectors of the plot.
+///
+/// Legitimately plotted sectors are never byte-identical, so any match indicates a plotting bug or
+/// corrupted plot. Returns pairs of sector offsets within the plot, where the first element is the
+/// offset of the first sector with such contents and the second is the offset of its duplicate.
+pub fn find_duplicate_sectors<P>(
+    mut plot: P,
+    sector_count: u64,
+    plot_sector_size: u64,
+) -> io::Result<Vec<(u64, u64)>>
+where
+    P: Read,
+{
+    let mut first_sector_offsets = HashMap::<Blake2b256Hash, u64>::new();
+    let mut duplicates = Vec::new();
+    let mut buffer = vec![0; READ_CHUNK_SIZE.min(plot_sector_size) as usize];
+
+    for sector_offset in 0..sector_count {
+        let mut hasher = blake2_rfc::blake2b::Blake2b::new(BLAKE2B_256_HASH_SIZE);
+        let mut remaining = plot_sector_size;
+        while remaining > 0 {
+            let chunk = &mut buffer[..remaining.min(READ_CHUNK_SIZE) as usize];
+            plot.read_exact(chunk)?;
+            hasher.update(chunk);
+            remaining -= chunk.len() as u64;
+        }
+
+        let sector_hash = hasher
+            .finalize()
+            .as_bytes()
+            .try_into()
+            .expect("Initialized with correct length; qed");
+
+        match first_sector_offsets.get(&sector_hash) {
+            Some(&first_sector_offset) => {
+                duplicates.push((first_sector_offset, sector_offset));
+            }
+            None => {
+                first_sector_offsets.insert(sector_hash, sector_offset);
+            }
+        }
+    }
+
+    Ok(duplicates)
+}
@@ -0,0 +1,33 @@
+use crate::single_disk_plot::diagnostics::find_duplicate_sectors;
+use rand::prelude::*;
+use std::io::Cursor;
+
+#[test]
+fn duplicate_sectors() {
+    // Not a multiple of read chunk size to make sure partial chunks are handled
+    let plot_sector_size = 1024 * 1024 + 1024;
+    let sector_count = 5;
+    let mut plot = vec![0u8; plot_sector_size * (sector_count + 2)];
+    thread_rng().fill(&mut plot[..plot_sector_size * sector_count]);
+
+    // Sectors are distinct, unplotted sectors at the end of the plot are identical, but ignored
+    assert!(find_duplicate_sectors(
+        Cursor::new(&plot),
+        sector_count as u64,
+        plot_sector_size as u64
+    )
+    .unwrap()
+    .is_empty());
+
+    // Duplicate sector 1 into sector 3
+    plot.copy_within(plot_sector_size..plot_sector_size * 2, plot_sector_size * 3);
+    assert_eq!(
+        find_duplicate_sectors(
+            Cursor::new(&plot),
+            sector_count as u64,
+            plot_sector_size as u64
+        )
+        .unwrap(),
+        vec![(1, 3)]
+    );
+}
@@ -0,0 +1,489 @@
+//! Validation of plot creation without side effects.
+//!
+//! The same validation is the first phase of [`SingleDiskPlot::new()`], so dry run can't accept a
+//! plot that would be refused by the farmer or the other way around.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::identity::Identity;
+use crate::rpc_client::RpcClient;
+use crate::single_disk_plot::sector_metadata::sector_metadata_file_size;
+use crate::single_disk_plot::sharded_plot_file::{PlotShards, ShardedPlotFile};
+use crate::single_disk_plot::storage_backend::{
+    check_plot_layout, plot_mmap_len, plot_size, sector_stride, target_sector_counts,
+    StorageBackend,
+};
+use crate::single_disk_plot::{
+    PlotLayout, PlotMetadataHeader, PlotProtocolInfo, PlotSectorAlignment, SingleDiskPlot,
+    SingleDiskPlotError, SingleDiskPlotId, SingleDiskPlotInfo, RESERVED_PLOT_METADATA,
+};
+use bytesize::ByteSize;
+use parity_scale_codec::Decode;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use subspace_core_primitives::{plot_sector_size, PublicKey, SectorIndex, PIECE_SIZE};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tokio::runtime::Handle;
+
+/// Options for [`SingleDiskPlot::dry_run()`], subset of
+/// [`SingleDiskPlotOptions`](crate::single_disk_plot::SingleDiskPlotOptions) that affects plot
+/// creation
+pub struct DryRunOptions<'a, RC> {
+    /// Path to directory where plot is stored.
+    pub directory: &'a Path,
+    /// How much space in bytes can plot use for plot
+    pub allocated_space: u64,
+    /// RPC client connected to Subspace node
+    pub rpc_client: &'a RC,
+    /// Storage backend to use for plot files, auto-detected from the file system of `directory`
+    /// if not specified
+    pub storage_backend: Option<StorageBackend>,
+    /// Where sector data and sector metadata are stored
+    pub plot_layout: &'a PlotLayout,
+    /// Identity derived from a mnemonic to use instead of identity generated randomly, must match
+    /// identity of existing plot
+    pub derived_identity: Option<&'a Identity>,
+}
+
+/// Space on the file system of a directory
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectorySpace {
+    /// Directory
+    pub directory: PathBuf,
+    /// Space in bytes that still needs to be allocated for plot files in this directory
+    pub required: u64,
+    /// Space in bytes available on the file system
+    pub available: u64,
+}
+
+/// Report of [`SingleDiskPlot::dry_run()`] describing the plot that would result from opening it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunReport {
+    /// Plot info, ID and first sector index are generated anew for plots that don't exist yet
+    pub info: SingleDiskPlotInfo,
+    /// Plot doesn't exist yet and would be created
+    pub new_plot: bool,
+    /// Identity doesn't exist yet and would be created
+    pub new_identity: bool,
+    /// Farmer protocol info plot is created with, stored in plot metadata for existing plots
+    pub farmer_protocol_info: FarmerProtocolInfo,
+    /// Storage backend used for plot files
+    pub storage_backend: StorageBackend,
+    /// Path to plot file, shard index is appended to it for every shard of plot split into shards
+    /// (see [`sharded_plot_file`](crate::single_disk_plot::sharded_plot_file))
+    pub plot_file: PathBuf,
+    /// Path to plot metadata file
+    pub metadata_file: PathBuf,
+    /// Path to sector metadata file
+    pub sector_metadata_file: PathBuf,
+    /// Size of one sector in bytes
+    pub plot_sector_size: u64,
+    /// Sectors start at offsets within plot file that are multiples of this many bytes, stored in
+    /// plot metadata for existing plots
+    pub sector_alignment: u64,
+    /// Distance in bytes between starts of consecutive sectors, sector size padded to alignment
+    pub sector_stride: u64,
+    /// Number of sectors plot will have when fully plotted
+    pub target_sector_count: u64,
+    /// Number of sectors already plotted
+    pub plotted_sector_count: u64,
+    /// Size of plot file in bytes, combined size of all shards for plot split into shards
+    pub plot_file_size: u64,
+    /// Size of plot metadata file in bytes
+    pub metadata_file_size: u64,
+    /// Size of sector metadata file in bytes
+    pub sector_metadata_file_size: u64,
+    /// Space of every directory plot files are stored in
+    pub space: Vec<DirectorySpace>,
+}
+
+/// Result of validation, everything plot creation needs to proceed
+pub(super) struct PlotPlan {
+    pub(super) report: DryRunReport,
+    pub(super) identity: Identity,
+    /// Farmer protocol info as reported by the node
+    pub(super) node_farmer_protocol_info: FarmerProtocolInfo,
+}
+
+/// Validate creation of the plot without writing anything to disk
+pub(super) fn plan<RC>(options: DryRunOptions<'_, RC>) -> Result<PlotPlan, SingleDiskPlotError>
+where
+    RC: RpcClient,
+{
+    let DryRunOptions {
+        directory,
+        allocated_space,
+        rpc_client,
+        storage_backend,
+        plot_layout,
+        derived_identity,
+    } = options;
+    let metadata_directory = plot_layout.metadata_directory(directory);
+
+    // Directories are only created after validation, so checks are done for the closest ancestor
+    // that exists already
+    let existing_directory = existing_ancestor(directory)?;
+    let existing_metadata_directory = existing_ancestor(metadata_directory)?;
+    // Plot directory with overlay is a read-only snapshot, everything is written to overlay
+    if plot_layout.overlay_directory.is_none() {
+        check_writable(existing_directory)?;
+    }
+    check_writable(existing_metadata_directory)?;
+
+    let storage_backend = match storage_backend {
+        Some(storage_backend) => storage_backend,
+        None => StorageBackend::detect(existing_directory)?,
+    };
+
+    let (identity, new_identity) = match Identity::open_with(directory, derived_identity)
+        .map_err(|error| SingleDiskPlotError::FailedToOpenIdentity { error })?
+    {
+        Some(identity) => (identity, false),
+        None => (
+            derived_identity.cloned().unwrap_or_else(Identity::generate),
+            true,
+        ),
+    };
+    let public_key = PublicKey::from(identity.public_key().to_bytes());
+
+    let node_farmer_protocol_info = tokio::task::block_in_place(|| {
+        Handle::current()
+            .block_on(rpc_client.farmer_protocol_info())
+            .map_err(SingleDiskPlotError::NodeRpcError)
+    })?;
+
+    let (info, new_plot) = match SingleDiskPlotInfo::load_from(directory)? {
+        Some(info) => {
+            if allocated_space != info.allocated_space() {
+                return Err(SingleDiskPlotError::CantResize {
+                    id: *info.id(),
+                    old_space: ByteSize::b(info.allocated_space()),
+                    new_space: ByteSize::b(allocated_space),
+                });
+            }
+
+            if &node_farmer_protocol_info.genesis_hash != info.genesis_hash() {
+                return Err(SingleDiskPlotError::WrongChain {
+                    id: *info.id(),
+                    correct_chain: hex::encode(info.genesis_hash()),
+                    wrong_chain: hex::encode(node_farmer_protocol_info.genesis_hash),
+                });
+            }
+
+            if &public_key != info.public_key() {
+                return Err(SingleDiskPlotError::IdentityMismatch {
+                    id: *info.id(),
+                    correct_public_key: *info.public_key(),
+                    wrong_public_key: public_key,
+                });
+            }
+
+            if info.plot_shards().is_some() && plot_layout.overlay_directory.is_some() {
+                return Err(SingleDiskPlotError::OverlayOfShardedPlot {
+                    directory: directory.to_path_buf(),
+                });
+            }
+
+            (info, false)
+        }
+        None if plot_layout.overlay_directory.is_some() => {
+            return Err(SingleDiskPlotError::OverlayWithoutPlot {
+                directory: directory.to_path_buf(),
+            });
+        }
+        None => {
+            // TODO: Global generator that makes sure to avoid returning the same sector index
+            //  for multiple disks
+            let first_sector_index = SectorIndex::new(
+                SystemTime::UNIX_EPOCH
+                    .elapsed()
+                    .expect("Unix epoch is always in the past; qed")
+                    .as_secs()
+                    .wrapping_mul(u64::from(u32::MAX)),
+            );
+
+            let info = SingleDiskPlotInfo::new(
+                SingleDiskPlotId::new(),
+                node_farmer_protocol_info.genesis_hash,
+                public_key,
+                first_sector_index,
+                allocated_space,
+            );
+
+            (info, true)
+        }
+    };
+
+    let mut metadata_file_path = plot_layout.metadata_file(directory);
+    // Metadata is only copied into overlay when plot is opened with it for the first time
+    if plot_layout.overlay_directory.is_some() && !metadata_file_path.exists() {
+        metadata_file_path = plot_layout
+            .base_metadata_directory(directory)
+            .join(SingleDiskPlot::METADATA_FILE);
+    }
+    let metadata_file = match open_existing(&metadata_file_path)? {
+        // Empty metadata file is treated the same way as missing one during plot creation
+        Some(metadata_file) if metadata_file.metadata()?.len() > 0 => Some(metadata_file),
+        _ => None,
+    };
+    let (farmer_protocol_info, plotted_sector_count, sector_alignment) = match metadata_file {
+        Some(metadata_file) => {
+            let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+            metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
+            let metadata_header = PlotMetadataHeader::decode(&mut metadata_header_bytes.as_slice())
+                .map_err(SingleDiskPlotError::FailedToDecodeMetadataHeader)?;
+
+            if metadata_header.version > PlotMetadataHeader::LATEST_VERSION {
+                return Err(SingleDiskPlotError::UnexpectedMetadataVersion(
+                    metadata_header.version,
+                ));
+            }
+
+            // Plot must be audited with the same parameters it was created with, so stored
+            // protocol info takes precedence over what node reports
+            let farmer_protocol_info = if metadata_header.version == 0 {
+                node_farmer_protocol_info
+            } else {
+                let (stored_protocol_info, _stored_kzg_parameters_id) =
+                    PlotProtocolInfo::load(&metadata_file)?;
+
+                if stored_protocol_info.genesis_hash != node_farmer_protocol_info.genesis_hash {
+                    return Err(SingleDiskPlotError::WrongChain {
+                        id: *info.id(),
+                        correct_chain: hex::encode(stored_protocol_info.genesis_hash),
+                        wrong_chain: hex::encode(node_farmer_protocol_info.genesis_hash),
+                    });
+                }
+
+                stored_protocol_info
+            };
+
+            // Sectors of existing plots stay where they are regardless of requested alignment
+            let sector_alignment = if metadata_header.version >= PlotSectorAlignment::VERSION {
+                PlotSectorAlignment::load(&metadata_file, &metadata_header)?
+            } else {
+                PlotSectorAlignment::initial(&metadata_header, plot_layout.sector_alignment)
+            };
+
+            (
+                farmer_protocol_info,
+                metadata_header.sector_count,
+                sector_alignment,
+            )
+        }
+        None => (
+            node_farmer_protocol_info,
+            0,
+            plot_layout.sector_alignment.get(),
+        ),
+    };
+
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+    assert_eq!(
+        plot_sector_size % PIECE_SIZE as u64,
+        0,
+        "Sector size must be multiple of piece size"
+    );
+
+    let sector_stride = sector_stride(plot_sector_size, sector_alignment)?;
+
+    // Existing plots keep their shard layout regardless of requested shard size
+    let plot_shard_size = match info.plot_shards() {
+        Some(plot_shards) => Some(plot_shards.shard_size),
+        None if new_plot => plot_layout.plot_shard_size,
+        None => None,
+    };
+
+    let plot_file = directory.join(SingleDiskPlot::PLOT_FILE);
+    let existing_plot_file_size = if new_plot {
+        0
+    } else if let Some(plot_shards) = info.plot_shards() {
+        ShardedPlotFile::existing_len(directory, plot_shards)?
+    } else {
+        match open_existing(&plot_file)? {
+            Some(file) => file.metadata()?.len(),
+            None => 0,
+        }
+    };
+
+    let [target_sector_count, legacy_target_sector_count] =
+        target_sector_counts(allocated_space, plot_sector_size, sector_stride);
+    // Plots created before metadata overhead was accounted for keep the number of sectors they were
+    // created with
+    let target_sector_count = if existing_plot_file_size > 0
+        && plot_size(legacy_target_sector_count, sector_stride).ok()
+            == Some(existing_plot_file_size)
+    {
+        legacy_target_sector_count
+    } else {
+        target_sector_count
+    };
+    // Whole plot must be addressable, including memory mapping on 32-bit platforms, sharded plots
+    // are never memory mapped
+    let plot_file_size = if storage_backend.use_mmap() && plot_shard_size.is_none() {
+        plot_mmap_len(target_sector_count, sector_stride)? as u64
+    } else {
+        plot_size(target_sector_count, sector_stride)?
+    };
+    let info = match plot_shard_size {
+        Some(plot_shard_size) => {
+            let plot_shards = PlotShards::new(plot_file_size, plot_shard_size);
+            match info.plot_shards() {
+                Some(stored_plot_shards) if stored_plot_shards != plot_shards => {
+                    return Err(SingleDiskPlotError::InconsistentPlotShards {
+                        id: *info.id(),
+                        plot_shards: stored_plot_shards,
+                        plot_file_size,
+                    });
+                }
+                Some(_) => info,
+                None => info.with_plot_shards(plot_shards),
+            }
+        }
+        None => info,
+    };
+    let metadata_file_size = RESERVED_PLOT_METADATA;
+    let sector_metadata_file_size = sector_metadata_file_size(target_sector_count);
+
+    let sector_metadata_file = plot_layout.sector_metadata_file(directory);
+
+    if !new_plot {
+        // Plot file doesn't exist yet if plot creation was interrupted before any sector was
+        // plotted, there is nothing to misinterpret in that case
+        if plotted_sector_count > 0 || existing_plot_file_size > 0 {
+            check_plot_layout(
+                *info.id(),
+                info.allocated_space(),
+                plotted_sector_count,
+                sector_stride,
+                existing_plot_file_size,
+            )?;
+        }
+    }
+
+    // Files that exist already only need to grow to the target size
+    let remaining = |path: &Path, size: u64| -> io::Result<u64> {
+        let existing_size = match open_existing(path)? {
+            Some(file) => file.metadata()?.len(),
+            None => 0,
+        };
+        Ok(size.saturating_sub(existing_size))
+    };
+    let required_for_plot = if info.plot_shards().is_some() {
+        plot_file_size.saturating_sub(existing_plot_file_size)
+    } else {
+        remaining(&plot_file, plot_file_size)?
+    };
+    let required_for_metadata = remaining(&metadata_file_path, metadata_file_size)?
+        + remaining(&sector_metadata_file, sector_metadata_file_size)?;
+
+    let space = if existing_directory == existing_metadata_directory {
+        vec![DirectorySpace {
+            directory: directory.to_path_buf(),
+            required: required_for_plot + required_for_metadata,
+            available: fs2::available_space(existing_directory)?,
+        }]
+    } else {
+        vec![
+            DirectorySpace {
+                directory: directory.to_path_buf(),
+                required: required_for_plot,
+                available: fs2::available_space(existing_directory)?,
+            },
+            DirectorySpace {
+                directory: metadata_directory.to_path_buf(),
+                required: required_for_metadata,
+                available: fs2::available_space(existing_metadata_directory)?,
+            },
+        ]
+    };
+    if let Some(directory_space) = space
+        .iter()
+        .find(|directory_space| directory_space.required > directory_space.available)
+    {
+        return Err(SingleDiskPlotError::InsufficientSpace {
+            directory: directory_space.directory.clone(),
+            required: ByteSize::b(directory_space.required),
+            available: ByteSize::b(directory_space.available),
+        });
+    }
+
+    Ok(PlotPlan {
+        report: DryRunReport {
+            info,
+            new_plot,
+            new_identity,
+            farmer_protocol_info,
+            storage_backend,
+            plot_file,
+            metadata_file: metadata_file_path,
+            sector_metadata_file,
+            plot_sector_size,
+            sector_alignment,
+            sector_stride,
+            target_sector_count,
+            plotted_sector_count,
+            plot_file_size,
+            metadata_file_size,
+            sector_metadata_file_size,
+            space,
+        },
+        identity,
+        node_farmer_protocol_info,
+    })
+}
+
+/// Closest ancestor of `path` (including `path` itself) that exists
+fn existing_ancestor(path: &Path) -> Result<&Path, SingleDiskPlotError> {
+    path.ancestors()
+        // Relative path in current directory has empty parent
+        .map(|path| {
+            if path.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                path
+            }
+        })
+        .find(|path| path.exists())
+        .ok_or_else(|| SingleDiskPlotError::DirectoryNotWritable {
+            directory: path.to_path_buf(),
+        })
+}
+
+fn check_writable(directory: &Path) -> Result<(), SingleDiskPlotError> {
+    #[cfg(unix)]
+    let writable = {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let directory = CString::new(directory.as_os_str().as_bytes())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        // SAFETY: Path is a valid nul-terminated string
+        unsafe { libc::access(directory.as_ptr(), libc::W_OK) == 0 }
+    };
+    #[cfg(not(unix))]
+    let writable = !directory.metadata()?.permissions().readonly();
+
+    if writable {
+        Ok(())
+    } else {
+        Err(SingleDiskPlotError::DirectoryNotWritable {
+            directory: directory.to_path_buf(),
+        })
+    }
+}
+
+fn open_existing(path: &Path) -> io::Result<Option<File>> {
+    match OpenOptions::new().read(true).open(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
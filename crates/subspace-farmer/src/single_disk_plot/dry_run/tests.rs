@@ -0,0 +1,367 @@
+use crate::file_ext::FileExt;
+use crate::identity::Identity;
+use crate::rpc_client::bench_rpc_client::{BenchRpcClient, BENCH_FARMER_PROTOCOL_INFO};
+use crate::single_disk_plot::dry_run::DryRunOptions;
+use crate::single_disk_plot::plot_layout_calculator::PlotLayoutCalculator;
+use crate::single_disk_plot::sharded_plot_file::PlotShards;
+use crate::single_disk_plot::{
+    PlotLayout, PlotMetadataHeader, SingleDiskPlot, SingleDiskPlotError, SingleDiskPlotId,
+    SingleDiskPlotInfo, RESERVED_PLOT_METADATA,
+};
+use futures::channel::mpsc;
+use parity_scale_codec::Encode;
+use std::fs;
+use std::num::NonZeroU64;
+use std::path::Path;
+use subspace_core_primitives::{plot_sector_size, PublicKey};
+use tempfile::tempdir;
+
+fn rpc_client() -> BenchRpcClient {
+    BenchRpcClient::new(
+        BENCH_FARMER_PROTOCOL_INFO,
+        mpsc::channel(0).1,
+        mpsc::channel(0).1,
+    )
+}
+
+fn options<'a>(
+    directory: &'a Path,
+    allocated_space: u64,
+    rpc_client: &'a BenchRpcClient,
+    plot_layout: &'a PlotLayout,
+) -> DryRunOptions<'a, BenchRpcClient> {
+    DryRunOptions {
+        directory,
+        allocated_space,
+        rpc_client,
+        storage_backend: None,
+        plot_layout,
+        derived_identity: None,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn new_plot() {
+    let base_directory = tempdir().unwrap();
+    let directory = base_directory.path().join("plot");
+    let rpc_client = rpc_client();
+    let plot_layout = PlotLayout::default();
+    let plot_sector_size = plot_sector_size(BENCH_FARMER_PROTOCOL_INFO.space_l);
+    let calculator = PlotLayoutCalculator::new(&BENCH_FARMER_PROTOCOL_INFO);
+
+    let report = SingleDiskPlot::dry_run(options(
+        &directory,
+        calculator.space_for_sectors(10),
+        &rpc_client,
+        &plot_layout,
+    ))
+    .unwrap();
+
+    assert!(report.new_plot);
+    assert!(report.new_identity);
+    assert_eq!(report.farmer_protocol_info, BENCH_FARMER_PROTOCOL_INFO);
+    assert_eq!(report.plot_sector_size, plot_sector_size);
+    assert_eq!(report.target_sector_count, 10);
+    assert_eq!(report.plotted_sector_count, 0);
+    assert_eq!(report.plot_file, directory.join(SingleDiskPlot::PLOT_FILE));
+    assert_eq!(report.space.len(), 1);
+    assert!(report.space[0].required >= report.plot_file_size);
+
+    // Nothing was written to disk
+    assert!(!directory.exists());
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["targetSectorCount"], 10);
+    assert_eq!(json["newPlot"], true);
+
+    // Metadata doesn't fit next to 10 sectors anymore
+    let report = SingleDiskPlot::dry_run(options(
+        &directory,
+        calculator.space_for_sectors(10) - 1,
+        &rpc_client,
+        &plot_layout,
+    ))
+    .unwrap();
+    assert_eq!(report.target_sector_count, 9);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn existing_plot_checks() {
+    let directory = tempdir().unwrap();
+    let rpc_client = rpc_client();
+    let plot_layout = PlotLayout::default();
+    let plot_sector_size = plot_sector_size(BENCH_FARMER_PROTOCOL_INFO.space_l);
+
+    let identity = Identity::create(directory.path()).unwrap();
+    let info = SingleDiskPlotInfo::new(
+        SingleDiskPlotId::new(),
+        BENCH_FARMER_PROTOCOL_INFO.genesis_hash,
+        PublicKey::from(identity.public_key().to_bytes()),
+        100,
+        plot_sector_size * 10,
+    );
+    info.store_to(directory.path()).unwrap();
+
+    let report = SingleDiskPlot::dry_run(options(
+        directory.path(),
+        plot_sector_size * 10,
+        &rpc_client,
+        &plot_layout,
+    ))
+    .unwrap();
+    assert!(!report.new_plot);
+    assert!(!report.new_identity);
+    assert_eq!(report.info.id(), info.id());
+    assert_eq!(report.info.first_sector_index(), 100);
+
+    // Same checks as during plot opening apply
+    assert!(matches!(
+        SingleDiskPlot::dry_run(options(
+            directory.path(),
+            plot_sector_size * 20,
+            &rpc_client,
+            &plot_layout,
+        )),
+        Err(SingleDiskPlotError::CantResize { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn derived_identity() {
+    let directory = tempdir().unwrap();
+    let rpc_client = rpc_client();
+    let plot_layout = PlotLayout::default();
+    let plot_sector_size = plot_sector_size(BENCH_FARMER_PROTOCOL_INFO.space_l);
+    let derive = |derivation_path: &str| {
+        Identity::from_mnemonic(
+            "bottom drive obey lake curtain smoke basket hold race lonely fit walk",
+            &derivation_path.parse().unwrap(),
+        )
+        .unwrap()
+    };
+    let identity = derive("//farmer");
+    let same_identity = derive("//farmer");
+    let other_identity = derive("//farmer//1");
+    let derived_options = |derived_identity| DryRunOptions {
+        derived_identity: Some(derived_identity),
+        ..options(
+            directory.path(),
+            plot_sector_size * 10,
+            &rpc_client,
+            &plot_layout,
+        )
+    };
+
+    let report = SingleDiskPlot::dry_run(derived_options(&identity)).unwrap();
+    assert!(report.new_identity);
+    assert_eq!(
+        report.info.public_key(),
+        &PublicKey::from(identity.public_key().to_bytes())
+    );
+
+    identity.store(directory.path()).unwrap();
+    report.info.store_to(directory.path()).unwrap();
+
+    let report = SingleDiskPlot::dry_run(derived_options(&same_identity)).unwrap();
+    assert!(!report.new_identity);
+
+    // Plot can't be opened without mnemonic or with a different one
+    assert!(matches!(
+        SingleDiskPlot::dry_run(options(
+            directory.path(),
+            plot_sector_size * 10,
+            &rpc_client,
+            &plot_layout,
+        )),
+        Err(SingleDiskPlotError::FailedToOpenIdentity { .. })
+    ));
+    assert!(matches!(
+        SingleDiskPlot::dry_run(derived_options(&other_identity)),
+        Err(SingleDiskPlotError::FailedToOpenIdentity { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn insufficient_space() {
+    let directory = tempdir().unwrap();
+    let rpc_client = rpc_client();
+    let plot_layout = PlotLayout::default();
+
+    assert!(matches!(
+        SingleDiskPlot::dry_run(options(
+            directory.path(),
+            1u64 << 60,
+            &rpc_client,
+            &plot_layout,
+        )),
+        Err(SingleDiskPlotError::InsufficientSpace { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn inconsistent_plot_layout() {
+    let directory = tempdir().unwrap();
+    let rpc_client = rpc_client();
+    let plot_layout = PlotLayout::default();
+    let plot_sector_size = plot_sector_size(BENCH_FARMER_PROTOCOL_INFO.space_l);
+    let allocated_space = plot_sector_size * 10;
+
+    let identity = Identity::create(directory.path()).unwrap();
+    let info = SingleDiskPlotInfo::new(
+        SingleDiskPlotId::new(),
+        BENCH_FARMER_PROTOCOL_INFO.genesis_hash,
+        PublicKey::from(identity.public_key().to_bytes()),
+        100,
+        allocated_space,
+    );
+    info.store_to(directory.path()).unwrap();
+
+    let metadata_file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(directory.path().join(SingleDiskPlot::METADATA_FILE))
+        .unwrap();
+    metadata_file.set_len(RESERVED_PLOT_METADATA).unwrap();
+    let write_sector_count = |sector_count: u64| {
+        metadata_file
+            .write_all_at(
+                &PlotMetadataHeader {
+                    version: 0,
+                    sector_count,
+                }
+                .encode(),
+                0,
+            )
+            .unwrap();
+    };
+    let plot_file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(directory.path().join(SingleDiskPlot::PLOT_FILE))
+        .unwrap();
+
+    // Consistent plot opens fine
+    write_sector_count(5);
+    plot_file.set_len(allocated_space).unwrap();
+    SingleDiskPlot::dry_run(options(
+        directory.path(),
+        allocated_space,
+        &rpc_client,
+        &plot_layout,
+    ))
+    .unwrap();
+
+    // More sectors recorded than fit into allocated space
+    write_sector_count(11);
+    assert!(matches!(
+        SingleDiskPlot::dry_run(options(
+            directory.path(),
+            allocated_space,
+            &rpc_client,
+            &plot_layout,
+        )),
+        Err(SingleDiskPlotError::InconsistentPlotLayout {
+            id,
+            allocated_space: error_allocated_space,
+            sector_count: 11,
+            plot_sector_size: error_plot_sector_size,
+            plot_file_size,
+        }) if id == *info.id()
+            && error_allocated_space == allocated_space
+            && error_plot_sector_size == plot_sector_size
+            && plot_file_size == allocated_space
+    ));
+
+    // Plot file doesn't match allocated space
+    write_sector_count(5);
+    plot_file.set_len(plot_sector_size * 5).unwrap();
+    assert!(matches!(
+        SingleDiskPlot::dry_run(options(
+            directory.path(),
+            allocated_space,
+            &rpc_client,
+            &plot_layout,
+        )),
+        Err(SingleDiskPlotError::InconsistentPlotLayout {
+            sector_count: 5,
+            plot_file_size,
+            ..
+        }) if plot_file_size == plot_sector_size * 5
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sharded_plot() {
+    let directory = tempdir().unwrap();
+    let rpc_client = rpc_client();
+    // Identity must exist for plot info stored below to be accepted
+    Identity::create(directory.path()).unwrap();
+    let plot_sector_size = plot_sector_size(BENCH_FARMER_PROTOCOL_INFO.space_l);
+    let calculator = PlotLayoutCalculator::new(&BENCH_FARMER_PROTOCOL_INFO);
+    let plot_layout = PlotLayout {
+        plot_shard_size: Some(NonZeroU64::new(plot_sector_size * 3).unwrap()),
+        ..PlotLayout::default()
+    };
+
+    let report = SingleDiskPlot::dry_run(options(
+        directory.path(),
+        calculator.space_for_sectors(10),
+        &rpc_client,
+        &plot_layout,
+    ))
+    .unwrap();
+    assert!(report.new_plot);
+    assert_eq!(report.plot_file_size, plot_sector_size * 10);
+    let plot_shards = report.info.plot_shards().unwrap();
+    assert_eq!(plot_shards.shard_size.get(), plot_sector_size * 3);
+    assert_eq!(plot_shards.shard_count, 4);
+
+    // Existing plot keeps its layout regardless of requested shard size
+    report.info.store_to(directory.path()).unwrap();
+    let report = SingleDiskPlot::dry_run(options(
+        directory.path(),
+        calculator.space_for_sectors(10),
+        &rpc_client,
+        &PlotLayout::default(),
+    ))
+    .unwrap();
+    assert!(!report.new_plot);
+    assert_eq!(report.info.plot_shards(), Some(plot_shards));
+
+    // Shard layout that doesn't match plot size is refused
+    report
+        .info
+        .clone()
+        .with_plot_shards(PlotShards {
+            shard_count: 3,
+            ..plot_shards
+        })
+        .store_to(directory.path())
+        .unwrap();
+    assert!(matches!(
+        SingleDiskPlot::dry_run(options(
+            directory.path(),
+            calculator.space_for_sectors(10),
+            &rpc_client,
+            &PlotLayout::default(),
+        )),
+        Err(SingleDiskPlotError::InconsistentPlotShards { .. })
+    ));
+
+    // Overlay can't be used on top of sharded plot
+    let overlay_directory = tempdir().unwrap();
+    report.info.store_to(directory.path()).unwrap();
+    assert!(matches!(
+        SingleDiskPlot::dry_run(options(
+            directory.path(),
+            calculator.space_for_sectors(10),
+            &rpc_client,
+            &PlotLayout {
+                overlay_directory: Some(overlay_directory.path().to_path_buf()),
+                ..PlotLayout::default()
+            },
+        )),
+        Err(SingleDiskPlotError::OverlayOfShardedPlot { .. })
+    ));
+}
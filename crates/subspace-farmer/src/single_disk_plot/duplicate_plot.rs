@@ -0,0 +1,253 @@
+//! Best-effort detection of the same plot being farmed by more than one farmer.
+//!
+//! Plot copied to another machine and farmed there under the same identity results in competing
+//! solutions from the same public key, which node treats as equivocation. Farmer can't see
+//! solutions of other farmers directly, but node asks every connected farmer to sign rewards of
+//! blocks and votes produced with its public key. Every such request must correspond to a
+//! solution that was recently submitted by this farmer, request that doesn't means somebody else
+//! farms the same identity. Requests that arrive shortly after start are never treated as
+//! evidence, since they may correspond to solutions submitted before restart.
+//!
+//! Additionally every opened plot writes a random incarnation nonce into its directory, process
+//! that finds its nonce replaced knows that the same plot files were opened by someone else (for
+//! instance on network storage).
+//!
+//! Multiple farmers using the same identity with different sector ranges legitimately look the
+//! same as a copied plot, so detection only emits an event and pausing of submissions is opt-in.
+
+#[cfg(test)]
+mod tests;
+
+use event_listener_primitives::{Bag, HandlerId};
+use parking_lot::Mutex;
+use rand::random;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fs, io, iter};
+use subspace_core_primitives::{Blake2b256Hash, PublicKey};
+use tracing::error;
+
+/// Node requests reward signature within this time after solution was submitted
+const REWARD_SIGNING_WINDOW: Duration = Duration::from_secs(60);
+/// Reward signing requests are not treated as evidence for this long after start, solutions
+/// submitted before restart may still be signed during that time
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(2 * 60);
+
+type HandlerFn<A> = Arc<dyn Fn(&A) + Send + Sync + 'static>;
+type Handler<A> = Bag<HandlerFn<A>, A>;
+
+/// Evidence of the same plot being farmed by someone else
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DuplicatePlotEvidence {
+    /// Node requested reward signature while no solutions were submitted recently
+    UnexpectedRewardSigning {
+        /// Hash that node requested to sign
+        hash: Blake2b256Hash,
+    },
+    /// Incarnation nonce in plot directory was replaced by another process
+    IncarnationSuperseded,
+}
+
+/// Event emitted when the same plot appears to be farmed by another farmer
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DuplicatePlotDetected {
+    /// Public key of the affected identity
+    pub public_key: PublicKey,
+    /// What the detection is based on
+    pub evidence: DuplicatePlotEvidence,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Times of recently submitted solutions that were not matched with reward signing requests
+    /// yet, by public key
+    submitted: HashMap<PublicKey, VecDeque<Instant>>,
+    /// Recent reward signing requests and whether they were expected, every plot that shares
+    /// identity receives the same request
+    requested: VecDeque<(Blake2b256Hash, Instant, bool)>,
+    detected: bool,
+}
+
+struct Inner {
+    started_at: Instant,
+    pause_submissions: bool,
+    state: Mutex<State>,
+    handlers: Handler<DuplicatePlotDetected>,
+}
+
+/// Detector of duplicate plots shared by all plots of the farmer, plots that share identity must
+/// share the detector.
+///
+/// Cheap to clone, all clones share the same state.
+#[derive(Clone)]
+pub struct DuplicatePlotDetector {
+    inner: Arc<Inner>,
+}
+
+impl DuplicatePlotDetector {
+    /// Create new detector started at `now`, submission of solutions is paused after detection if
+    /// `pause_submissions` is `true`
+    pub fn new(pause_submissions: bool, now: Instant) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                started_at: now,
+                pause_submissions,
+                state: Mutex::default(),
+                handlers: Handler::default(),
+            }),
+        }
+    }
+
+    /// Subscribe to duplicate plot detection
+    pub fn on_duplicate_plot_detected(
+        &self,
+        callback: HandlerFn<DuplicatePlotDetected>,
+    ) -> HandlerId {
+        self.inner.handlers.add(callback)
+    }
+
+    /// Whether duplicate plot was detected since start
+    pub fn is_detected(&self) -> bool {
+        self.inner.state.lock().detected
+    }
+
+    /// Whether solutions must not be submitted anymore
+    pub fn submissions_paused(&self) -> bool {
+        self.inner.pause_submissions && self.is_detected()
+    }
+
+    /// Record `count` solutions submitted with `public_key` at `now`
+    pub(crate) fn solutions_submitted(&self, public_key: PublicKey, count: usize, now: Instant) {
+        let mut state = self.inner.state.lock();
+        let submitted = state.submitted.entry(public_key).or_default();
+        submitted.extend(iter::repeat(now).take(count));
+        prune(submitted, now);
+    }
+
+    /// Check reward signing request for `hash` with `public_key` that arrived at `now` against
+    /// recently submitted solutions, returns `false` if request is evidence of duplicate plot
+    pub(crate) fn reward_signing_requested(
+        &self,
+        public_key: PublicKey,
+        hash: Blake2b256Hash,
+        now: Instant,
+    ) -> bool {
+        let expected = {
+            let mut state = self.inner.state.lock();
+            while let Some((_hash, requested_at, _expected)) = state.requested.front() {
+                if now.saturating_duration_since(*requested_at) <= REWARD_SIGNING_WINDOW {
+                    break;
+                }
+                state.requested.pop_front();
+            }
+            if let Some((_hash, _requested_at, expected)) = state
+                .requested
+                .iter()
+                .find(|(requested_hash, _requested_at, _expected)| *requested_hash == hash)
+            {
+                return *expected;
+            }
+
+            let expected = match state.submitted.get_mut(&public_key) {
+                Some(submitted) => {
+                    prune(submitted, now);
+                    submitted.pop_front().is_some()
+                }
+                None => false,
+            } || now.saturating_duration_since(self.inner.started_at)
+                < STARTUP_GRACE_PERIOD;
+            state.requested.push_back((hash, now, expected));
+
+            expected
+        };
+
+        if expected {
+            return true;
+        }
+
+        self.detected(DuplicatePlotDetected {
+            public_key,
+            evidence: DuplicatePlotEvidence::UnexpectedRewardSigning { hash },
+        });
+
+        false
+    }
+
+    /// Incarnation of plot with `public_key` was superseded by another process
+    pub(crate) fn incarnation_superseded(&self, public_key: PublicKey) {
+        self.detected(DuplicatePlotDetected {
+            public_key,
+            evidence: DuplicatePlotEvidence::IncarnationSuperseded,
+        });
+    }
+
+    fn detected(&self, event: DuplicatePlotDetected) {
+        self.inner.state.lock().detected = true;
+
+        error!(
+            public_key = %event.public_key,
+            evidence = ?event.evidence,
+            pause_submissions = %self.inner.pause_submissions,
+            "Duplicate plot detected, the same identity appears to be farmed by another farmer, \
+            make sure plot was not copied to another machine"
+        );
+        self.inner.handlers.call_simple(&event);
+    }
+}
+
+fn prune(submitted: &mut VecDeque<Instant>, now: Instant) {
+    while let Some(submitted_at) = submitted.front() {
+        if now.saturating_duration_since(*submitted_at) <= REWARD_SIGNING_WINDOW {
+            break;
+        }
+        submitted.pop_front();
+    }
+}
+
+/// Random nonce written into plot directory every time plot is opened
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlotIncarnation {
+    nonce: u64,
+}
+
+impl PlotIncarnation {
+    const FILE_NAME: &'static str = "incarnation.json";
+
+    /// Create new incarnation and store it in plot `directory`, replacing previous one
+    pub(crate) fn claim(directory: &Path) -> io::Result<Self> {
+        let incarnation = Self { nonce: random() };
+
+        let temporary_path = directory.join(format!("{}.tmp", Self::FILE_NAME));
+        fs::write(
+            &temporary_path,
+            serde_json::to_vec(&incarnation).expect("Incarnation serialization never fails; qed"),
+        )?;
+        fs::rename(temporary_path, directory.join(Self::FILE_NAME))?;
+
+        Ok(incarnation)
+    }
+
+    /// Whether incarnation stored in plot `directory` is still this one, missing incarnation is
+    /// treated as current
+    pub(crate) fn is_current(&self, directory: &Path) -> io::Result<bool> {
+        let bytes = match fs::read(directory.join(Self::FILE_NAME)) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                return if error.kind() == io::ErrorKind::NotFound {
+                    Ok(true)
+                } else {
+                    Err(error)
+                };
+            }
+        };
+
+        let stored = serde_json::from_slice::<Self>(&bytes)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        Ok(stored == *self)
+    }
+}
@@ -0,0 +1,89 @@
+use crate::single_disk_plot::duplicate_plot::{
+    DuplicatePlotDetected, DuplicatePlotDetector, DuplicatePlotEvidence, PlotIncarnation,
+    REWARD_SIGNING_WINDOW, STARTUP_GRACE_PERIOD,
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::PublicKey;
+use tempfile::TempDir;
+
+#[test]
+fn reward_signing_matches_submitted_solutions() {
+    let start = Instant::now();
+    let detector = DuplicatePlotDetector::new(false, start);
+    let public_key = PublicKey::from([1; 32]);
+    let events = Arc::new(Mutex::new(Vec::<DuplicatePlotDetected>::new()));
+    let _handler_id = detector.on_duplicate_plot_detected(Arc::new({
+        let events = Arc::clone(&events);
+        move |event| events.lock().push(*event)
+    }));
+
+    // Solutions submitted by previous run right before restart are signed during grace period
+    assert!(detector.reward_signing_requested(public_key, [0; 32], start));
+
+    let now = start + STARTUP_GRACE_PERIOD;
+    detector.solutions_submitted(public_key, 2, now);
+    assert!(detector.reward_signing_requested(public_key, [1; 32], now));
+    assert!(detector.reward_signing_requested(public_key, [2; 32], now));
+    // Plots sharing identity receive the same request
+    assert!(detector.reward_signing_requested(public_key, [2; 32], now));
+    assert!(!detector.is_detected());
+
+    // Every solution is signed at most once
+    assert!(!detector.reward_signing_requested(public_key, [3; 32], now));
+    assert!(detector.is_detected());
+    // Detection doesn't pause submissions unless requested
+    assert!(!detector.submissions_paused());
+    assert_eq!(
+        events.lock().as_slice(),
+        &[DuplicatePlotDetected {
+            public_key,
+            evidence: DuplicatePlotEvidence::UnexpectedRewardSigning { hash: [3; 32] },
+        }]
+    );
+}
+
+#[test]
+fn stale_solutions_are_not_matched() {
+    let start = Instant::now();
+    let detector = DuplicatePlotDetector::new(true, start);
+    let public_key = PublicKey::from([1; 32]);
+    let other_public_key = PublicKey::from([2; 32]);
+
+    let now = start + STARTUP_GRACE_PERIOD;
+    detector.solutions_submitted(public_key, 1, now);
+    // Solutions of other identities don't count
+    assert!(!detector.reward_signing_requested(
+        other_public_key,
+        [0; 32],
+        now + Duration::from_secs(1)
+    ));
+    assert!(detector.submissions_paused());
+
+    let detector = DuplicatePlotDetector::new(true, start);
+    detector.solutions_submitted(public_key, 1, now);
+    assert!(!detector.reward_signing_requested(
+        public_key,
+        [0; 32],
+        now + REWARD_SIGNING_WINDOW + Duration::from_secs(1)
+    ));
+    assert!(detector.submissions_paused());
+}
+
+#[test]
+fn plot_incarnation() {
+    let directory = TempDir::new().unwrap();
+
+    let first = PlotIncarnation::claim(directory.path()).unwrap();
+    assert!(first.is_current(directory.path()).unwrap());
+
+    // Restart of the same farmer claims new incarnation, previous process is gone by then
+    let second = PlotIncarnation::claim(directory.path()).unwrap();
+    assert!(second.is_current(directory.path()).unwrap());
+    assert!(!first.is_current(directory.path()).unwrap());
+
+    let detector = DuplicatePlotDetector::new(false, Instant::now());
+    detector.incarnation_superseded(PublicKey::from([1; 32]));
+    assert!(detector.is_detected());
+}
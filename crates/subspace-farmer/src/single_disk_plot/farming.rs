@@ -1,20 +1,63 @@
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::audit_cache::AuditCache;
+use crate::single_disk_plot::plotting::encode_piece;
 use crate::single_disk_plot::{FarmingError, SectorMetadata};
 use bitvec::prelude::*;
 use parity_scale_codec::{Decode, IoReader};
 use schnorrkel::Keypair;
-use std::io;
+use std::error::Error;
 use std::io::SeekFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fmt, io, iter};
 use subspace_core_primitives::crypto::blake2b_256_254_hash;
-use subspace_core_primitives::crypto::kzg::Witness;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId, Witness};
 use subspace_core_primitives::{
-    Blake2b256Hash, Chunk, Piece, PublicKey, SectorId, SectorIndex, Solution, SolutionRange,
-    PIECE_SIZE,
+    bidirectional_distance, plot_sector_size, Blake2b256Hash, Chunk, Piece, PieceIndex, PublicKey,
+    SectorId, SectorIndex, Solution, SolutionRange, PIECE_SIZE,
 };
 use subspace_rpc_primitives::FarmerProtocolInfo;
 use subspace_solving::{create_chunk_signature, derive_chunk_otp};
 use subspace_verification::is_within_solution_range;
 use tracing::error;
 
+/// Closest chunk to local challenge among audited chunks that were not within solution range,
+/// tells how far the plot is from producing a solution when it doesn't produce any
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct BestMiss {
+    distance: Option<SolutionRange>,
+}
+
+impl BestMiss {
+    /// Record bidirectional distance between local challenge and expanded chunk of a miss
+    pub fn record(&mut self, distance: SolutionRange) {
+        self.distance = Some(
+            self.distance
+                .map_or(distance, |best_distance| best_distance.min(distance)),
+        );
+    }
+
+    /// Combine with misses recorded elsewhere
+    pub fn merge(&mut self, other: BestMiss) {
+        if let Some(distance) = other.distance {
+            self.record(distance);
+        }
+    }
+
+    /// Distance of the closest miss, `None` if there were no misses
+    pub fn distance(&self) -> Option<SolutionRange> {
+        self.distance
+    }
+
+    /// How many times the closest miss was further from local challenge than allowed by
+    /// `solution_range` (always above `1.0`), `None` if there were no misses
+    pub fn outside_ratio(&self, solution_range: SolutionRange) -> Option<f64> {
+        self.distance
+            .map(|distance| distance as f64 / (solution_range / 2).max(1) as f64)
+    }
+}
+
 /// Sector that can be used to create a solution that is within desired solution range
 #[derive(Debug, Clone)]
 pub struct EligibleSector {
@@ -74,22 +117,7 @@ impl EligibleSector {
                 return Ok(None);
             }
         };
-        // TODO: Extract encoding into separate function reusable in
-        //  farmer and otherwise
-        record
-            .view_bits_mut::<Lsb0>()
-            .chunks_mut(farmer_protocol_info.space_l.get() as usize)
-            .enumerate()
-            .for_each(|(chunk_index, bits)| {
-                // Derive one-time pad
-                let mut otp = derive_chunk_otp(&self.sector_id, witness_bytes, chunk_index as u32);
-                // XOR chunk bit by bit with one-time pad
-                bits.iter_mut()
-                    .zip(otp.view_bits_mut::<Lsb0>().iter())
-                    .for_each(|(mut a, b)| {
-                        *a ^= *b;
-                    });
-            });
+        decode_record(&self.sector_id, farmer_protocol_info, record, witness_bytes);
 
         Ok(Some(Solution {
             public_key: PublicKey::from(keypair.public.to_bytes()),
@@ -105,35 +133,256 @@ impl EligibleSector {
     }
 }
 
+/// Reverse sector encoding of the record of a piece in place, `witness_bytes` are the witness part
+/// of the same piece, which is stored unencoded
+fn decode_record(
+    sector_id: &SectorId,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    record: &mut [u8],
+    witness_bytes: &[u8],
+) {
+    // TODO: Extract encoding into separate function reusable in
+    //  farmer and otherwise
+    record
+        .view_bits_mut::<Lsb0>()
+        .chunks_mut(farmer_protocol_info.space_l.get() as usize)
+        .enumerate()
+        .for_each(|(chunk_index, bits)| {
+            // Derive one-time pad
+            let mut otp = derive_chunk_otp(sector_id, witness_bytes, chunk_index as u32);
+            // XOR chunk bit by bit with one-time pad
+            bits.iter_mut()
+                .zip(otp.view_bits_mut::<Lsb0>().iter())
+                .for_each(|(mut a, b)| {
+                    *a ^= *b;
+                });
+        });
+}
+
+/// Offset of the piece in sector that contains chunk at `audit_index`
+fn audit_index_piece_offset(audit_index: u64) -> u64 {
+    (audit_index / u64::from(u8::BITS)) / PIECE_SIZE as u64
+}
+
+/// Read piece that contains winning chunk at `chunk_offset` (audit index of
+/// [`EligibleSector`]) from the sector and decode it, piece is returned along with its global
+/// index that can be used for witness lookup.
+///
+/// Note: reading expects cursor to be set to the beginning of the sector and will move the cursor
+/// during its operation, same as [`audit_sector()`].
+pub fn read_winning_piece<S>(
+    sector_id: &SectorId,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    mut sector: S,
+    sector_metadata: &SectorMetadata,
+    chunk_offset: u64,
+) -> Result<(Piece, PieceIndex), FarmingError>
+where
+    S: io::Read + io::Seek,
+{
+    let piece_offset = audit_index_piece_offset(chunk_offset);
+    let mut piece = Piece::default();
+    sector.seek(SeekFrom::Current((piece_offset * PIECE_SIZE as u64) as i64))?;
+    sector.read_exact(&mut piece)?;
+
+    let (record, witness_bytes) =
+        piece.split_at_mut(farmer_protocol_info.record_size.get() as usize);
+    decode_record(sector_id, farmer_protocol_info, record, witness_bytes);
+
+    let piece_index = sector_id.derive_piece_index(piece_offset, sector_metadata.total_pieces);
+
+    Ok((piece, piece_index))
+}
+
+/// Position of the audited chunk within sector
+struct AuditPosition {
+    local_challenge: SolutionRange,
+    /// Audit index (chunk) within sector
+    audit_index: u64,
+    /// Offset of the audited piece in sector
+    audit_piece_offset: u64,
+}
+
+impl AuditPosition {
+    fn new(
+        sector_id: &SectorId,
+        farmer_protocol_info: &FarmerProtocolInfo,
+        global_challenge: &Blake2b256Hash,
+    ) -> Self {
+        let chunks_in_sector = u64::from(farmer_protocol_info.record_size.get())
+            * u64::from(u8::BITS)
+            / u64::from(farmer_protocol_info.space_l.get());
+
+        let local_challenge = sector_id.derive_local_challenge(global_challenge);
+        let audit_index: u64 = local_challenge % chunks_in_sector;
+        let audit_piece_offset = audit_index_piece_offset(audit_index);
+
+        Self {
+            local_challenge,
+            audit_index,
+            audit_piece_offset,
+        }
+    }
+}
+
+/// Offset of the piece within sector that will be read by [`audit_sector()`] for specified global
+/// challenge, allows fetching data for auditing ahead of time
+pub fn audit_piece_offset(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    global_challenge: &Blake2b256Hash,
+) -> u64 {
+    AuditPosition::new(
+        &SectorId::new_rotated(public_key, sector_index, farmer_protocol_info.rotation),
+        farmer_protocol_info,
+        global_challenge,
+    )
+    .audit_piece_offset
+}
+
+/// Offsets of records within sector that are audited for specified global challenge.
+///
+/// Mapping is deterministic and only depends on its arguments, so anyone can check independently
+/// of scanning implementation that record offset claimed by a solution is legitimate for the
+/// challenge. Currently exactly one record is audited per sector.
+pub fn challenge_to_record_offsets(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    global_challenge: &Blake2b256Hash,
+    farmer_protocol_info: &FarmerProtocolInfo,
+) -> impl Iterator<Item = u32> {
+    let audit_piece_offset = audit_piece_offset(
+        public_key,
+        sector_index,
+        farmer_protocol_info,
+        global_challenge,
+    );
+
+    iter::once(
+        u32::try_from(audit_piece_offset)
+            .expect("Offset is within sector, number of records in sector fits into u32; qed"),
+    )
+}
+
 /// Audit a single sector
 ///
+/// `plot_kzg_parameters_id` is identifier of KZG parameters plot was created with, it must match
+/// parameters of supplied `kzg` or else witnesses of the sector can't be verified with it.
+///
 /// Note: auditing expects cursor to be set to the beginning of the sector and will move the cursor
 /// during its operation. Make sure to return it back to the beginning of the sector if necessary.
+#[allow(clippy::too_many_arguments)]
 pub fn audit_sector<S>(
     public_key: &PublicKey,
-    sector_index: u64,
+    sector_index: SectorIndex,
     farmer_protocol_info: &FarmerProtocolInfo,
+    plot_kzg_parameters_id: KzgParametersId,
+    kzg: &Kzg,
     global_challenge: &Blake2b256Hash,
     solution_range: SolutionRange,
-    mut sector: S,
+    sector: S,
+) -> Result<Option<EligibleSector>, FarmingError>
+where
+    S: io::Read + io::Seek,
+{
+    audit_sector_with(
+        public_key,
+        sector_index,
+        farmer_protocol_info,
+        plot_kzg_parameters_id,
+        kzg,
+        global_challenge,
+        solution_range,
+        |audit_piece_offset| read_audit_piece(sector, audit_piece_offset),
+        None,
+    )
+}
+
+/// Same as [`audit_sector()`], but audited piece is taken from `audit_cache` when possible and
+/// stored there after reading from `sector` otherwise.
+///
+/// When audited chunk is not within solution range its distance is recorded into `best_miss` if
+/// provided.
+#[allow(clippy::too_many_arguments)]
+pub fn audit_sector_cached<S>(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    plot_kzg_parameters_id: KzgParametersId,
+    kzg: &Kzg,
+    global_challenge: &Blake2b256Hash,
+    solution_range: SolutionRange,
+    sector: S,
+    audit_cache: &AuditCache,
+    best_miss: Option<&mut BestMiss>,
 ) -> Result<Option<EligibleSector>, FarmingError>
 where
     S: io::Read + io::Seek,
 {
-    let sector_id = SectorId::new(public_key, sector_index);
-    let chunks_in_sector = u64::from(farmer_protocol_info.record_size.get()) * u64::from(u8::BITS)
-        / u64::from(farmer_protocol_info.space_l.get());
+    audit_sector_with(
+        public_key,
+        sector_index,
+        farmer_protocol_info,
+        plot_kzg_parameters_id,
+        kzg,
+        global_challenge,
+        solution_range,
+        |audit_piece_offset| {
+            audit_cache.get_or_read(sector_index, audit_piece_offset, || {
+                read_audit_piece(sector, audit_piece_offset)
+            })
+        },
+        best_miss,
+    )
+}
 
-    let local_challenge = sector_id.derive_local_challenge(global_challenge);
-    let audit_index: u64 = local_challenge % chunks_in_sector;
-    let audit_piece_offset = (audit_index / u64::from(u8::BITS)) / PIECE_SIZE as u64;
+fn read_audit_piece<S>(mut sector: S, audit_piece_offset: u64) -> Result<Piece, FarmingError>
+where
+    S: io::Read + io::Seek,
+{
+    let mut piece = Piece::default();
+    sector.seek(SeekFrom::Current(
+        (audit_piece_offset * PIECE_SIZE as u64) as i64,
+    ))?;
+    sector.read_exact(&mut piece)?;
+
+    Ok(piece)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn audit_sector_with<R>(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    plot_kzg_parameters_id: KzgParametersId,
+    kzg: &Kzg,
+    global_challenge: &Blake2b256Hash,
+    solution_range: SolutionRange,
+    read_piece: R,
+    best_miss: Option<&mut BestMiss>,
+) -> Result<Option<EligibleSector>, FarmingError>
+where
+    R: FnOnce(u64) -> Result<Piece, FarmingError>,
+{
+    if plot_kzg_parameters_id != kzg.id() {
+        return Err(FarmingError::KzgParametersMismatch {
+            plot: plot_kzg_parameters_id,
+            supplied: kzg.id(),
+        });
+    }
+
+    let sector_id = SectorId::new_rotated(public_key, sector_index, farmer_protocol_info.rotation);
+    let AuditPosition {
+        local_challenge,
+        audit_index,
+        audit_piece_offset,
+    } = AuditPosition::new(&sector_id, farmer_protocol_info, global_challenge);
     // Offset of the piece in sector (in bytes)
     let audit_piece_bytes_offset = audit_piece_offset * PIECE_SIZE as u64;
     // Audit index (chunk) within corresponding piece
     let audit_index_within_piece = audit_index - audit_piece_bytes_offset * u64::from(u8::BITS);
-    let mut piece = Piece::default();
-    sector.seek(SeekFrom::Current(audit_piece_bytes_offset as i64))?;
-    sector.read_exact(&mut piece)?;
+    let piece = read_piece(audit_piece_offset)?;
 
     // TODO: We are skipping witness part of the piece or else it is not
     //  decodable
@@ -155,18 +404,221 @@ where
     //  something else?
     let expanded_chunk = chunk.expand(local_challenge);
 
-    Ok(
-        is_within_solution_range(local_challenge, expanded_chunk, solution_range).then_some(
-            EligibleSector {
-                sector_id,
-                sector_index,
-                local_challenge,
-                audit_index,
-                chunk,
-                expanded_chunk,
-                encoded_piece: piece,
-                audit_piece_offset,
-            },
-        ),
-    )
+    if !is_within_solution_range(local_challenge, expanded_chunk, solution_range) {
+        if let Some(best_miss) = best_miss {
+            best_miss.record(bidirectional_distance(&local_challenge, &expanded_chunk));
+        }
+
+        return Ok(None);
+    }
+
+    Ok(Some(EligibleSector {
+        sector_id,
+        sector_index,
+        local_challenge,
+        audit_index,
+        chunk,
+        expanded_chunk,
+        encoded_piece: piece,
+        audit_piece_offset,
+    }))
+}
+
+/// Source of pieces of archived history for auditing without a plot, see [`audit_from_pieces()`]
+pub trait PieceStore {
+    /// Get piece by its index, `None` is returned if piece is not available
+    fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>>;
+}
+
+/// Audit a single sector without a plot, results are the same as with [`audit_sector()`] on a
+/// plotted sector.
+///
+/// Instead of reading encoded piece from the sector, original piece is retrieved from
+/// `piece_store` and encoded on the fly, which trades CPU time for disk space. Only the audited
+/// chunk is encoded during audit, the rest of the piece is only encoded if sector turns out to be
+/// eligible.
+pub fn audit_from_pieces(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    global_challenge: &Blake2b256Hash,
+    solution_range: SolutionRange,
+    piece_store: &dyn PieceStore,
+) -> Result<Option<EligibleSector>, FarmingError> {
+    let sector_id = SectorId::new_rotated(public_key, sector_index, farmer_protocol_info.rotation);
+    let AuditPosition {
+        local_challenge,
+        audit_index,
+        audit_piece_offset,
+    } = AuditPosition::new(&sector_id, farmer_protocol_info, global_challenge);
+    // Audit index (chunk) within corresponding piece
+    let audit_index_within_piece =
+        audit_index - audit_piece_offset * PIECE_SIZE as u64 * u64::from(u8::BITS);
+
+    let piece_index =
+        sector_id.derive_piece_index(audit_piece_offset, farmer_protocol_info.total_pieces);
+    let mut piece = piece_store
+        .get_piece(piece_index)
+        .map_err(|error| FarmingError::FailedToGetPiece { piece_index, error })?
+        .ok_or(FarmingError::PieceNotFound { piece_index })?;
+
+    let (record, witness_bytes) = piece.split_at(farmer_protocol_info.record_size.get() as usize);
+    let maybe_chunk_bits = record
+        .view_bits::<Lsb0>()
+        .chunks_exact(farmer_protocol_info.space_l.get() as usize)
+        .nth(audit_index_within_piece as usize);
+
+    let mut chunk_bits = match maybe_chunk_bits {
+        Some(chunk_bits) => chunk_bits.to_bitvec(),
+        None => {
+            // TODO: Record size is not multiple of `space_l`, last bits
+            //  were not encoded and should not be used for solving
+            return Ok(None);
+        }
+    };
+    // Encode just the audited chunk the same way as during plotting
+    let otp = derive_chunk_otp(&sector_id, witness_bytes, audit_index_within_piece as u32);
+    chunk_bits
+        .iter_mut()
+        .zip(otp.view_bits::<Lsb0>().iter())
+        .for_each(|(mut a, b)| {
+            *a ^= *b;
+        });
+    let chunk = Chunk::from(chunk_bits.as_bitslice());
+
+    let expanded_chunk = chunk.expand(local_challenge);
+
+    if !is_within_solution_range(local_challenge, expanded_chunk, solution_range) {
+        return Ok(None);
+    }
+
+    encode_piece(&sector_id, &mut piece, farmer_protocol_info);
+
+    Ok(Some(EligibleSector {
+        sector_id,
+        sector_index,
+        local_challenge,
+        audit_index,
+        chunk,
+        expanded_chunk,
+        encoded_piece: piece,
+        audit_piece_offset,
+    }))
+}
+
+/// Iterator that audits sectors of a plot lazily one by one, yields sector index along with
+/// eligible sector if audit resulted in one.
+///
+/// Sectors are only read when corresponding item is requested, so it is cheap to stop early, for
+/// instance on the first eligible sector with [`Iterator::find()`]. Iteration stops once
+/// `cancelled` is set.
+pub struct AuditIter<'a> {
+    plot: &'a [u8],
+    plot_sector_size: u64,
+    public_key: &'a PublicKey,
+    first_sector_index: SectorIndex,
+    farmer_protocol_info: &'a FarmerProtocolInfo,
+    plot_kzg_parameters_id: KzgParametersId,
+    kzg: &'a Kzg,
+    global_challenge: &'a Blake2b256Hash,
+    solution_range: SolutionRange,
+    cancelled: &'a AtomicBool,
+    sector_offset: u64,
+}
+
+impl fmt::Debug for AuditIter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditIter")
+            .field("plot_sector_size", &self.plot_sector_size)
+            .field("first_sector_index", &self.first_sector_index)
+            .field("solution_range", &self.solution_range)
+            .field("sector_offset", &self.sector_offset)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> AuditIter<'a> {
+    /// Create new iterator over sectors of `plot` (memory-mapped plot file or any other slice that
+    /// contains plotted sectors one after another), trailing bytes that don't form a whole sector
+    /// are ignored.
+    ///
+    /// `plot_kzg_parameters_id` must match parameters of supplied `kzg`, see [`audit_sector()`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        plot: &'a [u8],
+        public_key: &'a PublicKey,
+        first_sector_index: SectorIndex,
+        farmer_protocol_info: &'a FarmerProtocolInfo,
+        plot_kzg_parameters_id: KzgParametersId,
+        kzg: &'a Kzg,
+        global_challenge: &'a Blake2b256Hash,
+        solution_range: SolutionRange,
+        cancelled: &'a AtomicBool,
+    ) -> Result<Self, FarmingError> {
+        if plot_kzg_parameters_id != kzg.id() {
+            return Err(FarmingError::KzgParametersMismatch {
+                plot: plot_kzg_parameters_id,
+                supplied: kzg.id(),
+            });
+        }
+
+        Ok(Self {
+            plot,
+            plot_sector_size: plot_sector_size(farmer_protocol_info.space_l),
+            public_key,
+            first_sector_index,
+            farmer_protocol_info,
+            plot_kzg_parameters_id,
+            kzg,
+            global_challenge,
+            solution_range,
+            cancelled,
+            sector_offset: 0,
+        })
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.plot.len() as u64 / self.plot_sector_size
+    }
+}
+
+impl Iterator for AuditIter<'_> {
+    type Item = (SectorIndex, Option<EligibleSector>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.sector_offset >= self.sector_count() || self.cancelled.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let sector_offset = self.sector_offset;
+        self.sector_offset += 1;
+        let sector_index = self.first_sector_index.offset(sector_offset);
+        let sector = &self.plot[(sector_offset * self.plot_sector_size) as usize..]
+            [..self.plot_sector_size as usize];
+
+        let maybe_eligible_sector = audit_sector(
+            self.public_key,
+            sector_index,
+            self.farmer_protocol_info,
+            self.plot_kzg_parameters_id,
+            self.kzg,
+            self.global_challenge,
+            self.solution_range,
+            io::Cursor::new(sector),
+        )
+        .expect(
+            "KZG parameters are checked in constructor and sector is fully within the plot, \
+            reading from memory can't fail; qed",
+        );
+
+        Some((sector_index, maybe_eligible_sector))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.sector_count().saturating_sub(self.sector_offset) as usize;
+        (0, Some(remaining))
+    }
 }
@@ -0,0 +1,498 @@
+use crate::single_disk_plot::audit_cache::{AuditCache, AuditCacheStats};
+use crate::single_disk_plot::farming::{
+    audit_from_pieces, audit_sector, audit_sector_cached, challenge_to_record_offsets,
+    read_winning_piece, AuditIter, BestMiss,
+};
+use crate::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use crate::single_disk_plot::{FarmingError, SectorMetadata};
+use crate::testing::fixtures::{farmer_protocol_info, piece, DerivedPieceReceiver};
+use crate::testing::MapPieceReceiver;
+use futures::executor::block_on;
+use parity_scale_codec::Decode;
+use std::io;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicBool, Ordering};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    bidirectional_distance, PublicKey, SectorId, SectorIndex, SolutionRange, PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+/// Sector that remembers offsets of all reads
+struct RecordingSector<'a> {
+    cursor: Cursor<&'a [u8]>,
+    read_offsets: Vec<u64>,
+}
+
+impl Read for RecordingSector<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_offsets.push(self.cursor.position());
+        self.cursor.read(buf)
+    }
+}
+
+impl Seek for RecordingSector<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.cursor.seek(pos)
+    }
+}
+
+fn plot(public_key: &PublicKey, sector_index: SectorIndex) -> (Vec<u8>, SectorMetadata) {
+    plot_with(public_key, sector_index, &farmer_protocol_info())
+}
+
+fn plot_with(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    farmer_protocol_info: &FarmerProtocolInfo,
+) -> (Vec<u8>, SectorMetadata) {
+    let mut sector = Vec::new();
+    let mut sector_metadata = Vec::new();
+    block_on(plot_sector(
+        public_key,
+        sector_index,
+        &DerivedPieceReceiver,
+        &AtomicBool::new(false),
+        farmer_protocol_info,
+        SectorBuffer::new(&mut sector),
+        &mut sector_metadata,
+    ))
+    .unwrap();
+
+    (
+        sector,
+        SectorMetadata::decode(&mut sector_metadata.as_slice()).unwrap(),
+    )
+}
+
+#[test]
+fn read_winning_piece_round_trip() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::ZERO;
+    let farmer_protocol_info = farmer_protocol_info();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let (sector, sector_metadata) = plot(&public_key, sector_index);
+    let sector_id = SectorId::new(&public_key, sector_index);
+
+    let mut audited = 0;
+    for _ in 0..10 {
+        let maybe_eligible_sector = audit_sector(
+            &public_key,
+            sector_index,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &rand::random(),
+            SolutionRange::MAX,
+            Cursor::new(&sector),
+        )
+        .unwrap();
+        let eligible_sector = match maybe_eligible_sector {
+            Some(eligible_sector) => eligible_sector,
+            // Last bits of the record that don't form a whole chunk are never eligible
+            None => continue,
+        };
+        audited += 1;
+
+        let (winning_piece, piece_index) = read_winning_piece(
+            &sector_id,
+            &farmer_protocol_info,
+            Cursor::new(&sector),
+            &sector_metadata,
+            eligible_sector.audit_index,
+        )
+        .unwrap();
+
+        assert_eq!(
+            piece_index,
+            sector_id.derive_piece_index(
+                eligible_sector.audit_piece_offset,
+                sector_metadata.total_pieces
+            )
+        );
+        assert_eq!(winning_piece, piece(piece_index));
+    }
+    assert!(audited > 0);
+}
+
+#[test]
+fn tiny_history_produces_auditable_sectors() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::ZERO;
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    for total_pieces in [1, 2, 3, 5] {
+        let farmer_protocol_info = FarmerProtocolInfo {
+            total_pieces: NonZeroU64::new(total_pieces).unwrap(),
+            ..farmer_protocol_info()
+        };
+        let (sector, sector_metadata) = plot_with(&public_key, sector_index, &farmer_protocol_info);
+        let sector_id = SectorId::new(&public_key, sector_index);
+        assert_eq!(sector_metadata.total_pieces.get(), total_pieces);
+
+        let mut audited = 0;
+        for _ in 0..10 {
+            let maybe_eligible_sector = audit_sector(
+                &public_key,
+                sector_index,
+                &farmer_protocol_info,
+                KzgParametersId::TEST,
+                &kzg,
+                &rand::random(),
+                SolutionRange::MAX,
+                Cursor::new(&sector),
+            )
+            .unwrap();
+            let eligible_sector = match maybe_eligible_sector {
+                Some(eligible_sector) => eligible_sector,
+                None => continue,
+            };
+            audited += 1;
+
+            let (winning_piece, piece_index) = read_winning_piece(
+                &sector_id,
+                &farmer_protocol_info,
+                Cursor::new(&sector),
+                &sector_metadata,
+                eligible_sector.audit_index,
+            )
+            .unwrap();
+
+            assert!(piece_index < total_pieces);
+            assert_eq!(winning_piece, piece(piece_index));
+        }
+        assert!(
+            audited > 0,
+            "No audits succeeded with {total_pieces} total pieces"
+        );
+    }
+}
+
+#[test]
+fn solution_range_changes_between_slots() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::ZERO;
+    let farmer_protocol_info = farmer_protocol_info();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let (sector, _sector_metadata) = plot(&public_key, sector_index);
+
+    // Auditing keeps no state between calls, so solution range that network adjusts over time is
+    // simply passed with every slot
+    let audit = |global_challenge, solution_range| {
+        audit_sector(
+            &public_key,
+            sector_index,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            global_challenge,
+            solution_range,
+            Cursor::new(&sector),
+        )
+        .unwrap()
+        .is_some()
+    };
+
+    let mut wide_solutions = 0;
+    let mut narrow_solutions = 0;
+    for _ in 0..100 {
+        let global_challenge = rand::random();
+
+        // Same sector audited in succession with different ranges, narrowing the range never
+        // leaves stale eligibility from the wider one
+        let wide = audit(&global_challenge, SolutionRange::MAX);
+        let narrow = audit(&global_challenge, SolutionRange::MAX / 8);
+        assert!(wide || !narrow);
+        // And widening it back works just as well
+        assert_eq!(audit(&global_challenge, SolutionRange::MAX), wide);
+        assert!(!audit(&global_challenge, 0) || narrow);
+
+        wide_solutions += usize::from(wide);
+        narrow_solutions += usize::from(narrow);
+    }
+
+    assert!(wide_solutions > narrow_solutions);
+}
+
+#[test]
+fn audit_iter_stops_early() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let first_sector_index = SectorIndex::new(10);
+    let sector_count = 4;
+    let farmer_protocol_info = farmer_protocol_info();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let cancelled = AtomicBool::new(false);
+    let plotted_sectors = (0..sector_count)
+        .flat_map(|sector_offset| plot(&public_key, first_sector_index.offset(sector_offset)).0)
+        .collect::<Vec<u8>>();
+
+    // Find challenge for which one of the sectors is closer to local challenge than all others
+    let (global_challenge, winner, solution_range) = loop {
+        let global_challenge = rand::random();
+        let mut distances = AuditIter::new(
+            &plotted_sectors,
+            &public_key,
+            first_sector_index,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            SolutionRange::MAX,
+            &cancelled,
+        )
+        .unwrap()
+        .filter_map(|(sector_index, maybe_eligible_sector)| {
+            let eligible_sector = maybe_eligible_sector?;
+            Some((
+                bidirectional_distance(
+                    &eligible_sector.local_challenge,
+                    &eligible_sector.expanded_chunk,
+                ),
+                sector_index,
+            ))
+        })
+        .collect::<Vec<_>>();
+        distances.sort_unstable();
+        if let [(distance, winner), (next_distance, _), ..] = distances.as_slice() {
+            if distance < next_distance {
+                break (global_challenge, *winner, distance.saturating_mul(2));
+            }
+        }
+    };
+
+    let new_audit_iter = |solution_range| {
+        AuditIter::new(
+            &plotted_sectors,
+            &public_key,
+            first_sector_index,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            solution_range,
+            &cancelled,
+        )
+        .unwrap()
+    };
+
+    let mut audit_iter = new_audit_iter(solution_range);
+    let (sector_index, eligible_sector) = audit_iter
+        .find(|(_sector_index, maybe_eligible_sector)| maybe_eligible_sector.is_some())
+        .unwrap();
+    assert_eq!(sector_index, winner);
+    assert_eq!(eligible_sector.unwrap().sector_index, winner);
+
+    // Sectors after the winning one were not audited yet
+    let remaining_sectors = first_sector_index
+        .offset(sector_count)
+        .offset_from(winner)
+        .unwrap()
+        - 1;
+    assert_eq!(audit_iter.size_hint().1, Some(remaining_sectors as usize));
+    assert_eq!(
+        audit_iter.next().map(|(sector_index, _)| sector_index),
+        (remaining_sectors > 0).then_some(winner.offset(1))
+    );
+
+    // Cancellation stops iteration between items
+    let mut audit_iter = new_audit_iter(SolutionRange::MAX);
+    assert_eq!(audit_iter.next().unwrap().0, first_sector_index);
+    cancelled.store(true, Ordering::Release);
+    assert!(audit_iter.next().is_none());
+}
+
+#[test]
+fn audit_from_pieces_matches_plot() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::new(5);
+    let farmer_protocol_info = farmer_protocol_info();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let (sector, _sector_metadata) = plot(&public_key, sector_index);
+
+    for solution_range in [SolutionRange::MAX, SolutionRange::MAX / 4] {
+        for _ in 0..20 {
+            let global_challenge = rand::random();
+
+            let from_plot = audit_sector(
+                &public_key,
+                sector_index,
+                &farmer_protocol_info,
+                KzgParametersId::TEST,
+                &kzg,
+                &global_challenge,
+                solution_range,
+                Cursor::new(&sector),
+            )
+            .unwrap();
+            let from_pieces = audit_from_pieces(
+                &public_key,
+                sector_index,
+                &farmer_protocol_info,
+                &global_challenge,
+                solution_range,
+                &DerivedPieceReceiver,
+            )
+            .unwrap();
+
+            assert_eq!(
+                from_pieces.map(|eligible_sector| (
+                    eligible_sector.audit_index,
+                    eligible_sector.audit_piece_offset,
+                    eligible_sector.chunk,
+                    eligible_sector.expanded_chunk,
+                    eligible_sector.encoded_piece,
+                )),
+                from_plot.map(|eligible_sector| (
+                    eligible_sector.audit_index,
+                    eligible_sector.audit_piece_offset,
+                    eligible_sector.chunk,
+                    eligible_sector.expanded_chunk,
+                    eligible_sector.encoded_piece,
+                )),
+            );
+        }
+    }
+
+    // Missing piece is an error rather than lack of solution
+    assert!(matches!(
+        audit_from_pieces(
+            &public_key,
+            sector_index,
+            &farmer_protocol_info,
+            &rand::random(),
+            SolutionRange::MAX,
+            &MapPieceReceiver::new(),
+        ),
+        Err(FarmingError::PieceNotFound { .. })
+    ));
+}
+
+#[test]
+fn audit_sector_cached_matches_uncached() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::new(3);
+    let farmer_protocol_info = farmer_protocol_info();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let audit_cache = AuditCache::new(PIECE_SIZE as u64 * 4);
+
+    let (sector, _sector_metadata) = plot(&public_key, sector_index);
+    // Second audit of the same piece must not touch the sector at all
+    let empty_sector = vec![0u8; sector.len()];
+    let global_challenge = rand::random();
+
+    let uncached = audit_sector(
+        &public_key,
+        sector_index,
+        &farmer_protocol_info,
+        KzgParametersId::TEST,
+        &kzg,
+        &global_challenge,
+        SolutionRange::MAX,
+        Cursor::new(&sector),
+    )
+    .unwrap()
+    .unwrap();
+
+    for sector in [&sector, &empty_sector] {
+        let cached = audit_sector_cached(
+            &public_key,
+            sector_index,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            SolutionRange::MAX,
+            Cursor::new(sector),
+            &audit_cache,
+            None,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(cached.audit_index, uncached.audit_index);
+        assert_eq!(cached.chunk, uncached.chunk);
+        assert_eq!(cached.encoded_piece, uncached.encoded_piece);
+    }
+
+    assert_eq!(audit_cache.stats(), AuditCacheStats { hits: 1, misses: 1 });
+
+    // Chunk that misses solution range is recorded as the best miss
+    let mut best_miss = BestMiss::default();
+    let eligible_sector = audit_sector_cached(
+        &public_key,
+        sector_index,
+        &farmer_protocol_info,
+        KzgParametersId::TEST,
+        &kzg,
+        &global_challenge,
+        0,
+        Cursor::new(&sector),
+        &audit_cache,
+        Some(&mut best_miss),
+    )
+    .unwrap();
+    let distance = bidirectional_distance(&uncached.local_challenge, &uncached.expanded_chunk);
+    if distance == 0 {
+        assert!(eligible_sector.is_some());
+        assert_eq!(best_miss.distance(), None);
+    } else {
+        assert!(eligible_sector.is_none());
+        assert_eq!(best_miss.distance(), Some(distance));
+    }
+}
+
+#[test]
+fn audited_records_match_challenge_mapping() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::new(5);
+    let farmer_protocol_info = farmer_protocol_info();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let (sector, _sector_metadata) = plot(&public_key, sector_index);
+
+    for _ in 0..10 {
+        let global_challenge = rand::random();
+        let mut recording_sector = RecordingSector {
+            cursor: Cursor::new(sector.as_slice()),
+            read_offsets: Vec::new(),
+        };
+
+        let eligible_sector = audit_sector(
+            &public_key,
+            sector_index,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            SolutionRange::MAX,
+            &mut recording_sector,
+        )
+        .unwrap()
+        .unwrap();
+
+        let record_offsets = challenge_to_record_offsets(
+            &public_key,
+            sector_index,
+            &global_challenge,
+            &farmer_protocol_info,
+        )
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            record_offsets,
+            vec![u32::try_from(eligible_sector.audit_piece_offset).unwrap()]
+        );
+        // Auditor reads exactly the records mapping returns and nothing else
+        assert_eq!(
+            recording_sector.read_offsets,
+            record_offsets
+                .iter()
+                .map(|&record_offset| u64::from(record_offset) * PIECE_SIZE as u64)
+                .collect::<Vec<_>>()
+        );
+    }
+}
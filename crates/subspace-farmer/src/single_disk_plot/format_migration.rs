@@ -0,0 +1,250 @@
+//! Upgrade of plot format to a newer version in place.
+//!
+//! Farmer upgrades plot metadata header on open as far as it can, but some fields can only be
+//! derived from sector contents, which is too slow to do during startup. Format migration upgrades
+//! plot metadata to requested version offline, moves sector metadata into sector metadata file if
+//! plot still stores it in plot metadata file and backfills checksums of sectors that were plotted
+//! before checksums were recorded by reading sector contents.
+//!
+//! Every step is idempotent and writes are ordered so that interrupted migration leaves plot in a
+//! valid state, calling migration again continues where it stopped.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::idle_verification::sector_checksum;
+use crate::single_disk_plot::sector_metadata::{
+    open_sector_metadata_file, sector_metadata_record_offset, SectorMetadataRecord,
+    SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::storage_backend::{sector_start, sector_stride};
+use crate::single_disk_plot::{
+    PlotLayout, PlotMetadataHeader, PlotProtocolInfo, PlotSectorAlignment, SingleDiskPlot,
+    SingleDiskPlotError, SingleDiskPlotId, SingleDiskPlotInfo,
+};
+use parity_scale_codec::Decode;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use subspace_core_primitives::plot_sector_size;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Errors that happen during [`migrate_plot_format()`]
+#[derive(Debug, Error)]
+pub enum FormatMigrationError {
+    /// Failed to open plot
+    #[error("Failed to open plot: {0}")]
+    Open(#[from] SingleDiskPlotError),
+    /// I/O error occurred
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// Target version is not supported by this version of the farmer
+    #[error(
+        "Plot format version {version} is not supported, latest supported version is {latest}"
+    )]
+    UnsupportedVersion {
+        /// Requested version
+        version: u8,
+        /// Latest supported version
+        latest: u8,
+    },
+    /// Plot already has newer format than requested, plots are never downgraded
+    #[error("Plot has format version {current}, can't downgrade to {target}")]
+    Downgrade {
+        /// Current version of the plot
+        current: u8,
+        /// Requested version
+        target: u8,
+    },
+}
+
+/// Result of [`migrate_plot_format()`]
+#[derive(Debug, Clone)]
+pub struct FormatMigrationReport {
+    /// ID of migrated plot
+    pub id: SingleDiskPlotId,
+    /// Format version plot had before this call
+    pub previous_version: u8,
+    /// Format version plot has after this call
+    pub version: u8,
+    /// Sectors whose checksums were computed and stored by this call
+    pub checksummed_sectors: u64,
+    /// Sectors that already had checksum stored, by farmer or by previous call
+    pub skipped_sectors: u64,
+    /// Sectors whose metadata records failed to decode, such sectors are replotted by farmer and
+    /// are left untouched
+    pub invalid_sectors: u64,
+    /// Whether migration completed, `false` if it was cancelled
+    pub completed: bool,
+    /// Time spent in this call
+    pub elapsed: Duration,
+}
+
+/// Migrate format of plot in `directory` to `target_version` of plot metadata header.
+///
+/// Plot metadata header is upgraded first (sector alignment is stored as `1` byte, which is how
+/// sectors of plots created before alignment was stored are located), then sector metadata is
+/// moved into sector metadata file if plot doesn't have one yet. After that every plotted sector
+/// without checksum is read and its checksum is stored in sector metadata record. Checksums are
+/// backfilled from contents as they are on disk, so sectors that are already corrupted will verify
+/// successfully until they are replotted.
+///
+/// Plot must have been opened by the farmer at least once, since farmer protocol info can't be
+/// derived from plot contents. Migration is idempotent, migration that was cancelled with
+/// `cancelled` or interrupted continues where it stopped when called again. Farmer must not be
+/// running on the plot during migration.
+///
+/// Only plots with metadata stored in plot directory are supported.
+///
+/// NOTE: This function does blocking I/O, it must be running in a separate thread in order to
+/// prevent blocking an executor.
+pub fn migrate_plot_format(
+    directory: &Path,
+    target_version: u8,
+    cancelled: &AtomicBool,
+) -> Result<FormatMigrationReport, FormatMigrationError> {
+    let start = Instant::now();
+    let plot_layout = PlotLayout::default();
+
+    if target_version > PlotMetadataHeader::LATEST_VERSION {
+        return Err(FormatMigrationError::UnsupportedVersion {
+            version: target_version,
+            latest: PlotMetadataHeader::LATEST_VERSION,
+        });
+    }
+
+    let info = SingleDiskPlotInfo::load_from(directory)?.ok_or_else(|| {
+        SingleDiskPlotError::PlotInfoNotFound {
+            directory: directory.to_path_buf(),
+        }
+    })?;
+    let id = *info.id();
+
+    let metadata_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(plot_layout.metadata_file(directory))?;
+
+    let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+    metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
+    let mut metadata_header = PlotMetadataHeader::decode(&mut metadata_header_bytes.as_slice())
+        .map_err(SingleDiskPlotError::FailedToDecodeMetadataHeader)?;
+
+    let previous_version = metadata_header.version;
+    if previous_version > PlotMetadataHeader::LATEST_VERSION {
+        return Err(SingleDiskPlotError::UnexpectedMetadataVersion(previous_version).into());
+    }
+    if previous_version > target_version {
+        return Err(FormatMigrationError::Downgrade {
+            current: previous_version,
+            target: target_version,
+        });
+    }
+    if previous_version == 0 {
+        return Err(SingleDiskPlotError::ProtocolInfoNotStored { id }.into());
+    }
+
+    info!(
+        %id,
+        %previous_version,
+        %target_version,
+        "Migrating plot format in {}",
+        directory.display()
+    );
+
+    if metadata_header.version < PlotSectorAlignment::VERSION
+        && target_version >= PlotSectorAlignment::VERSION
+    {
+        // Plots that don't have alignment stored have sectors located back to back
+        PlotSectorAlignment::load_or_store(&metadata_file, &mut metadata_header, 1)?;
+        metadata_file.sync_all()?;
+        debug!(version = %metadata_header.version, "Plot metadata header upgraded");
+    }
+
+    let (farmer_protocol_info, _kzg_parameters_id) = PlotProtocolInfo::load(&metadata_file)?;
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+    let sector_stride = sector_stride(
+        plot_sector_size,
+        PlotSectorAlignment::load(&metadata_file, &metadata_header)?,
+    )?;
+
+    // Migrates sector metadata out of plot metadata file if necessary
+    let sector_metadata_file = open_sector_metadata_file(
+        &plot_layout.sector_metadata_file(directory),
+        &metadata_file,
+        metadata_header.sector_count,
+    )?;
+
+    let plot_file = OpenOptions::new()
+        .read(true)
+        .open(directory.join(SingleDiskPlot::PLOT_FILE))?;
+
+    let mut report = FormatMigrationReport {
+        id,
+        previous_version,
+        version: metadata_header.version,
+        checksummed_sectors: 0,
+        skipped_sectors: 0,
+        invalid_sectors: 0,
+        completed: false,
+        elapsed: Duration::ZERO,
+    };
+
+    let mut record_bytes = [0; SECTOR_METADATA_RECORD_SIZE];
+    let mut sector = vec![0; plot_sector_size as usize];
+    for sector_offset in 0..metadata_header.sector_count {
+        if cancelled.load(Ordering::Acquire) {
+            debug!(%sector_offset, "Format migration cancelled");
+            sector_metadata_file.sync_all()?;
+            report.elapsed = start.elapsed();
+            return Ok(report);
+        }
+
+        let record_offset = sector_metadata_record_offset(sector_offset);
+        sector_metadata_file.read_exact_at(&mut record_bytes, record_offset)?;
+        let mut record =
+            match SectorMetadataRecord::decode(&record_bytes, SectorMetadataRecord::LATEST_VERSION)
+            {
+                Ok(record) => record,
+                Err(error) => {
+                    warn!(%sector_offset, %error, "Skipping sector with invalid metadata record");
+                    report.invalid_sectors += 1;
+                    continue;
+                }
+            };
+        if record.checksum.is_some() {
+            report.skipped_sectors += 1;
+            continue;
+        }
+
+        let offset = sector_start(sector_offset, sector_stride)?;
+        plot_file.read_exact_at(&mut sector, offset)?;
+        // Sectors are only read once, don't push useful pages out of page cache
+        plot_file.drop_cache(offset, plot_sector_size)?;
+
+        record.checksum.replace(sector_checksum(&sector));
+        // Record is written with a single write, so interruption leaves either old or new record
+        sector_metadata_file.write_all_at(&record.encode(), record_offset)?;
+        report.checksummed_sectors += 1;
+        debug!(%sector_offset, "Sector checksum backfilled");
+    }
+
+    sector_metadata_file.sync_all()?;
+    report.completed = true;
+    report.elapsed = start.elapsed();
+
+    info!(
+        %id,
+        version = %report.version,
+        checksummed_sectors = %report.checksummed_sectors,
+        invalid_sectors = %report.invalid_sectors,
+        elapsed = ?report.elapsed,
+        "Plot format migration completed"
+    );
+
+    Ok(report)
+}
@@ -0,0 +1,166 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::format_migration::{
+    migrate_plot_format, FormatMigrationError, FormatMigrationReport,
+};
+use crate::single_disk_plot::idle_verification::sector_checksum;
+use crate::single_disk_plot::read_only::tests::TestPlot;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::sector_metadata::{
+    read_sector_metadata_record, sector_metadata_record_offset, SectorMetadataRecord,
+};
+use crate::single_disk_plot::{
+    PlotMetadataHeader, PlotProtocolInfo, SingleDiskPlot, RESERVED_PLOT_METADATA,
+};
+use parity_scale_codec::Encode;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{plot_sector_size, SolutionRange};
+use tempfile::TempDir;
+
+/// Create plot the way farmer that stored protocol info, but neither sector alignment nor sector
+/// metadata file did
+fn create_v1_plot(directory: &Path) -> TestPlot {
+    let (test_plot, metadata_file, mut metadata_header) = TestPlot::create(directory);
+    PlotProtocolInfo::load_or_store(
+        test_plot.id,
+        &metadata_file,
+        &mut metadata_header,
+        test_plot.farmer_protocol_info,
+        KzgParametersId::TEST,
+    )
+    .unwrap();
+    assert_eq!(metadata_header.version, 1);
+    metadata_header.sector_count = test_plot.sector_count;
+    metadata_file
+        .write_all_at(&metadata_header.encode(), 0)
+        .unwrap();
+    metadata_file
+        .write_all_at(&test_plot.sectors_metadata, RESERVED_PLOT_METADATA)
+        .unwrap();
+
+    test_plot
+}
+
+fn sector_metadata_records(directory: &Path, sector_count: u64) -> Vec<SectorMetadataRecord> {
+    let contents = fs::read(directory.join(SingleDiskPlot::SECTOR_METADATA_FILE)).unwrap();
+    (0..sector_count)
+        .map(|sector_offset| read_sector_metadata_record(&contents, sector_offset).unwrap())
+        .collect()
+}
+
+#[test]
+fn migrate_v1_to_v2() {
+    let directory = TempDir::new().unwrap();
+    let test_plot = create_v1_plot(directory.path());
+
+    let FormatMigrationReport {
+        id,
+        previous_version,
+        version,
+        checksummed_sectors,
+        skipped_sectors,
+        invalid_sectors,
+        completed,
+        ..
+    } = migrate_plot_format(directory.path(), 2, &AtomicBool::new(false)).unwrap();
+    assert_eq!(id, test_plot.id);
+    assert_eq!(previous_version, 1);
+    assert_eq!(version, 2);
+    assert_eq!(checksummed_sectors, test_plot.sector_count);
+    assert_eq!(skipped_sectors, 0);
+    assert_eq!(invalid_sectors, 0);
+    assert!(completed);
+
+    // Sector metadata moved out of plot metadata file and has checksums of sector contents
+    assert_eq!(
+        fs::metadata(directory.path().join(SingleDiskPlot::METADATA_FILE))
+            .unwrap()
+            .len(),
+        RESERVED_PLOT_METADATA
+    );
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l) as usize;
+    for (record, sector) in sector_metadata_records(directory.path(), test_plot.sector_count)
+        .into_iter()
+        .zip(test_plot.plot.chunks_exact(plot_sector_size))
+    {
+        assert_eq!(record.checksum, Some(sector_checksum(sector)));
+    }
+
+    // Plot is readable and auditable with the latest reader
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let plot = PlotReader::open(directory.path()).unwrap();
+    assert_eq!(plot.sector_count(), test_plot.sector_count);
+    for sector_offset in 0..plot.sector_count() {
+        assert!(plot
+            .audit_sector(sector_offset, &kzg, &rand::random(), SolutionRange::MAX)
+            .unwrap()
+            .is_some());
+    }
+
+    // Migration is idempotent
+    let report = migrate_plot_format(directory.path(), 2, &AtomicBool::new(false)).unwrap();
+    assert_eq!(report.previous_version, 2);
+    assert_eq!(report.checksummed_sectors, 0);
+    assert_eq!(report.skipped_sectors, test_plot.sector_count);
+    assert!(report.completed);
+
+    // Plots are never downgraded
+    assert!(matches!(
+        migrate_plot_format(directory.path(), 1, &AtomicBool::new(false)),
+        Err(FormatMigrationError::Downgrade {
+            current: 2,
+            target: 1
+        })
+    ));
+    assert!(matches!(
+        migrate_plot_format(
+            directory.path(),
+            PlotMetadataHeader::LATEST_VERSION + 1,
+            &AtomicBool::new(false)
+        ),
+        Err(FormatMigrationError::UnsupportedVersion { .. })
+    ));
+}
+
+#[test]
+fn cancel_and_resume() {
+    let directory = TempDir::new().unwrap();
+    let test_plot = create_v1_plot(directory.path());
+
+    let report = migrate_plot_format(directory.path(), 2, &AtomicBool::new(true)).unwrap();
+    assert!(!report.completed);
+    assert_eq!(report.version, 2);
+    assert_eq!(report.checksummed_sectors, 0);
+    // Plot is valid at every step
+    assert!(PlotReader::open(directory.path()).is_ok());
+    assert!(
+        sector_metadata_records(directory.path(), test_plot.sector_count)
+            .iter()
+            .all(|record| record.checksum.is_none())
+    );
+
+    // Pretend that the first sector was checksummed before interruption
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l) as usize;
+    let mut record = sector_metadata_records(directory.path(), 1)[0];
+    let checksum = sector_checksum(&test_plot.plot[..plot_sector_size]);
+    record.checksum.replace(checksum);
+    fs::OpenOptions::new()
+        .write(true)
+        .open(directory.path().join(SingleDiskPlot::SECTOR_METADATA_FILE))
+        .unwrap()
+        .write_all_at(&record.encode(), sector_metadata_record_offset(0))
+        .unwrap();
+
+    let report = migrate_plot_format(directory.path(), 2, &AtomicBool::new(false)).unwrap();
+    assert!(report.completed);
+    assert_eq!(report.previous_version, 2);
+    assert_eq!(report.skipped_sectors, 1);
+    assert_eq!(report.checksummed_sectors, test_plot.sector_count - 1);
+    assert_eq!(
+        sector_metadata_records(directory.path(), 1)[0].checksum,
+        Some(checksum)
+    );
+}
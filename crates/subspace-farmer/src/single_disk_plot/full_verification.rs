@@ -0,0 +1,218 @@
+//! Full verification of plot contents against original pieces.
+//!
+//! Every piece of every sector is retrieved from a piece store, encoded the same way as during
+//! plotting and compared with bytes stored in the plot. This is slow, but unlike audits it checks
+//! every byte of the plot.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::farming::PieceStore;
+use crate::single_disk_plot::plotting::encode_piece;
+use crate::single_disk_plot::read_only::{in_thread_pool, PlotReader};
+use crate::single_disk_plot::storage_backend::sector_start;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use subspace_core_primitives::{PieceIndex, SectorId, SectorIndex, PIECE_SIZE};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use thiserror::Error;
+use tracing::debug;
+
+/// First byte of a sector that differs from encoded original piece
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SectorDivergence {
+    /// Sector index
+    pub sector_index: SectorIndex,
+    /// Offset of the piece in sector
+    pub piece_offset: u64,
+    /// Index of the piece in archived history
+    pub piece_index: PieceIndex,
+    /// Offset of the first divergent byte from the start of the sector
+    pub byte_offset: u64,
+}
+
+/// Errors that prevent verification of a single sector
+#[derive(Debug, Error)]
+pub enum SectorVerificationError {
+    /// Failed to retrieve piece from piece store
+    #[error("Failed to retrieve piece {piece_index} of sector {sector_index}: {error}")]
+    FailedToGetPiece {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Piece index
+        piece_index: PieceIndex,
+        /// Lower-level error
+        error: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    /// Piece was not found in piece store
+    #[error("Piece {piece_index} of sector {sector_index} was not found in piece store")]
+    PieceNotFound {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Piece index
+        piece_index: PieceIndex,
+    },
+    /// Failed to read sector or its metadata
+    #[error("Failed to read sector {sector_index}: {error}")]
+    Io {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Lower-level error
+        error: io::Error,
+    },
+}
+
+/// Result of [`verify_full_plot()`]
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Number of sectors whose contents match encoded original pieces
+    pub verified_sectors: u64,
+    /// Sectors whose contents don't match encoded original pieces, in order of sector indexes
+    pub divergences: Vec<SectorDivergence>,
+    /// Sectors that could not be verified, in order of sector indexes
+    pub errors: Vec<SectorVerificationError>,
+    /// Verification was cancelled before all sectors were verified
+    pub cancelled: bool,
+}
+
+enum SectorVerification {
+    Verified,
+    Diverged(SectorDivergence),
+    Cancelled,
+}
+
+/// Verify every sector of `plot` by encoding original pieces from `piece_store` and comparing them
+/// with bytes stored in the plot, reporting the first divergent byte of every sector.
+///
+/// `farmer_protocol_info` must be the one plot was created with, see
+/// [`PlotReader::farmer_protocol_info()`]. Sectors are verified in parallel on
+/// `thread_pool`, global rayon thread pool is used if `None`. Verification stops early once
+/// `cancelled` is set, sectors that were not verified fully by then are not reported.
+///
+/// NOTE: This function does blocking I/O, it must be running in a separate thread in order to
+/// prevent blocking an executor.
+pub fn verify_full_plot(
+    plot: &PlotReader,
+    piece_store: &(dyn PieceStore + Sync),
+    farmer_protocol_info: &FarmerProtocolInfo,
+    cancelled: &AtomicBool,
+    thread_pool: Option<&ThreadPool>,
+) -> VerifyReport {
+    let verification_results = in_thread_pool(thread_pool, || {
+        (0..plot.sector_count())
+            .into_par_iter()
+            .map(|sector_offset| {
+                verify_sector(
+                    plot,
+                    sector_offset,
+                    piece_store,
+                    farmer_protocol_info,
+                    cancelled,
+                )
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut report = VerifyReport::default();
+    for verification_result in verification_results {
+        match verification_result {
+            Ok(SectorVerification::Verified) => {
+                report.verified_sectors += 1;
+            }
+            Ok(SectorVerification::Diverged(divergence)) => {
+                report.divergences.push(divergence);
+            }
+            Ok(SectorVerification::Cancelled) => {
+                report.cancelled = true;
+            }
+            Err(error) => {
+                report.errors.push(error);
+            }
+        }
+    }
+
+    report
+}
+
+fn verify_sector(
+    plot: &PlotReader,
+    sector_offset: u64,
+    piece_store: &(dyn PieceStore + Sync),
+    farmer_protocol_info: &FarmerProtocolInfo,
+    cancelled: &AtomicBool,
+) -> Result<SectorVerification, SectorVerificationError> {
+    if cancelled.load(Ordering::Acquire) {
+        return Ok(SectorVerification::Cancelled);
+    }
+
+    let sector_index = plot.info().first_sector_index().offset(sector_offset);
+    let io_error = |error| SectorVerificationError::Io {
+        sector_index,
+        error,
+    };
+
+    // Pieces were selected according to history size and encoded with rotation at the time sector
+    // was plotted
+    let record = plot
+        .read_sector_metadata_record(sector_offset)
+        .map_err(io_error)?;
+    let total_pieces = record.sector_metadata.total_pieces;
+    let farmer_protocol_info = &FarmerProtocolInfo {
+        rotation: record.rotation,
+        ..*farmer_protocol_info
+    };
+
+    let mut sector = vec![0; plot.plot_sector_size as usize];
+    let offset = sector_start(sector_offset, plot.sector_stride)
+        .map_err(|error| io_error(io::Error::new(io::ErrorKind::InvalidInput, error)))?;
+    plot.plot_file
+        .read_exact_at(&mut sector, offset)
+        .map_err(io_error)?;
+    // Every sector is only read once, don't push useful pages out of page cache
+    plot.plot_file
+        .drop_cache(offset, plot.plot_sector_size)
+        .map_err(io_error)?;
+
+    let sector_id = SectorId::new_rotated(plot.info().public_key(), sector_index, record.rotation);
+
+    for (piece_offset, encoded_piece) in (0..).zip(sector.chunks_exact(PIECE_SIZE)) {
+        if cancelled.load(Ordering::Acquire) {
+            return Ok(SectorVerification::Cancelled);
+        }
+
+        let piece_index = sector_id.derive_piece_index(piece_offset, total_pieces);
+        let mut piece = piece_store
+            .get_piece(piece_index)
+            .map_err(|error| SectorVerificationError::FailedToGetPiece {
+                sector_index,
+                piece_index,
+                error,
+            })?
+            .ok_or(SectorVerificationError::PieceNotFound {
+                sector_index,
+                piece_index,
+            })?;
+        encode_piece(&sector_id, &mut piece, farmer_protocol_info);
+
+        let maybe_divergent_byte = piece
+            .iter()
+            .zip(encoded_piece)
+            .position(|(expected, actual)| expected != actual);
+        if let Some(divergent_byte) = maybe_divergent_byte {
+            let divergence = SectorDivergence {
+                sector_index,
+                piece_offset,
+                piece_index,
+                byte_offset: piece_offset * PIECE_SIZE as u64 + divergent_byte as u64,
+            };
+            debug!(?divergence, "Sector diverged from original pieces");
+
+            return Ok(SectorVerification::Diverged(divergence));
+        }
+    }
+
+    Ok(SectorVerification::Verified)
+}
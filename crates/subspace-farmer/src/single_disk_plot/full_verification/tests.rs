@@ -0,0 +1,105 @@
+use crate::single_disk_plot::full_verification::{verify_full_plot, SectorVerificationError};
+use crate::single_disk_plot::read_only::tests::TestPlot;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::SingleDiskPlot;
+use crate::testing::fixtures::DerivedPieceReceiver;
+use crate::testing::MapPieceReceiver;
+use rand::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg::KzgParametersId;
+use subspace_core_primitives::{plot_sector_size, PIECE_SIZE};
+use tempfile::TempDir;
+
+fn create_plot(directory: &Path) -> TestPlot {
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory);
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    test_plot
+}
+
+#[test]
+fn correct_plot() {
+    let directory = TempDir::new().unwrap();
+    let test_plot = create_plot(directory.path());
+    let plot = PlotReader::open(directory.path()).unwrap();
+
+    let report = verify_full_plot(
+        &plot,
+        &DerivedPieceReceiver,
+        &test_plot.farmer_protocol_info,
+        &AtomicBool::new(false),
+        None,
+    );
+    assert_eq!(report.verified_sectors, test_plot.sector_count);
+    assert!(report.divergences.is_empty());
+    assert!(report.errors.is_empty());
+    assert!(!report.cancelled);
+}
+
+#[test]
+fn corrupted_byte() {
+    let directory = TempDir::new().unwrap();
+    let test_plot = create_plot(directory.path());
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+
+    let corrupted_sector_offset = 1;
+    let corrupted_byte_offset = thread_rng().gen_range(0..plot_sector_size);
+    let mut plot_bytes = test_plot.plot.clone();
+    plot_bytes[(corrupted_sector_offset * plot_sector_size + corrupted_byte_offset) as usize] ^= 1;
+    fs::write(directory.path().join(SingleDiskPlot::PLOT_FILE), plot_bytes).unwrap();
+
+    let plot = PlotReader::open(directory.path()).unwrap();
+    let report = verify_full_plot(
+        &plot,
+        &DerivedPieceReceiver,
+        &test_plot.farmer_protocol_info,
+        &AtomicBool::new(false),
+        None,
+    );
+    assert_eq!(report.verified_sectors, test_plot.sector_count - 1);
+    assert!(report.errors.is_empty());
+    assert_eq!(report.divergences.len(), 1);
+    let divergence = report.divergences[0];
+    assert_eq!(
+        divergence.sector_index,
+        test_plot.first_sector_index.offset(corrupted_sector_offset)
+    );
+    assert_eq!(divergence.byte_offset, corrupted_byte_offset);
+    assert_eq!(
+        divergence.piece_offset,
+        corrupted_byte_offset / PIECE_SIZE as u64
+    );
+}
+
+#[test]
+fn missing_pieces_and_cancellation() {
+    let directory = TempDir::new().unwrap();
+    let test_plot = create_plot(directory.path());
+    let plot = PlotReader::open(directory.path()).unwrap();
+
+    let report = verify_full_plot(
+        &plot,
+        &MapPieceReceiver::new(),
+        &test_plot.farmer_protocol_info,
+        &AtomicBool::new(false),
+        None,
+    );
+    assert_eq!(report.verified_sectors, 0);
+    assert_eq!(report.errors.len() as u64, test_plot.sector_count);
+    assert!(report
+        .errors
+        .iter()
+        .all(|error| matches!(error, SectorVerificationError::PieceNotFound { .. })));
+
+    let report = verify_full_plot(
+        &plot,
+        &DerivedPieceReceiver,
+        &test_plot.farmer_protocol_info,
+        &AtomicBool::new(true),
+        None,
+    );
+    assert_eq!(report.verified_sectors, 0);
+    assert!(report.divergences.is_empty());
+    assert!(report.cancelled);
+}
@@ -0,0 +1,343 @@
+//! Opportunistic verification of plotted sectors during idle slots.
+//!
+//! Most slots produce no solution and disk sits idle after the audit finishes. Farming uses that
+//! time to verify checksum of one sector per slot, going through the plot round-robin, but only if
+//! there is enough time left before the next slot is expected. Sector is read in small chunks and
+//! verification pauses as soon as the next slot arrives, it resumes from the same place during the
+//! next idle period. Sectors whose contents don't match checksum are queued for replotting.
+
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::plotting::SectorDestination;
+use crate::single_disk_plot::sector_metadata::{
+    read_sector_metadata_record, SectorChecksum, SECTOR_CHECKSUM_SIZE,
+};
+use crate::single_disk_plot::storage_backend::{sector_start, PlotData};
+use blake2_rfc::blake2b::Blake2b;
+use parking_lot::Mutex;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::{BLAKE2B_256_HASH_SIZE, PIECE_SIZE};
+
+/// Size of a single read during verification, verification can only pause between reads
+const VERIFICATION_READ_SIZE: u64 = PIECE_SIZE as u64;
+
+/// Options of opportunistic verification of plotted sectors during idle slots
+#[derive(Debug, Copy, Clone)]
+pub struct IdleVerificationOptions {
+    /// Verification only starts and continues while at least this much time is left before the
+    /// next slot is expected
+    pub margin: Duration,
+    /// Verification coverage is the fraction of plotted sectors that were verified within this
+    /// window
+    pub coverage_window: Duration,
+}
+
+/// Checksum of `sector` contents
+pub fn sector_checksum(sector: &[u8]) -> SectorChecksum {
+    let mut hasher = Blake2b::new(BLAKE2B_256_HASH_SIZE);
+    hasher.update(sector);
+    finalize_checksum(hasher)
+}
+
+fn finalize_checksum(hasher: Blake2b) -> SectorChecksum {
+    let mut checksum = SectorChecksum::default();
+    checksum.copy_from_slice(&hasher.finalize().as_bytes()[..SECTOR_CHECKSUM_SIZE]);
+    checksum
+}
+
+/// Writer that computes checksum of sector contents written through it
+pub(crate) struct ChecksummingWriter<W> {
+    inner: W,
+    hasher: Blake2b,
+    sector_size: u64,
+    written: u64,
+    /// Chunk was written somewhere other than right after written contents, checksum doesn't cover
+    /// the sector
+    out_of_order: bool,
+}
+
+impl<W> ChecksummingWriter<W> {
+    pub(crate) fn new(inner: W, sector_size: u64) -> Self {
+        Self {
+            inner,
+            hasher: Blake2b::new(BLAKE2B_256_HASH_SIZE),
+            sector_size,
+            written: 0,
+            out_of_order: false,
+        }
+    }
+
+    /// Checksum of written contents, `None` unless exactly `sector_size` bytes were written
+    /// sequentially from the beginning of the sector
+    pub(crate) fn checksum(&self) -> Option<SectorChecksum> {
+        (!self.out_of_order && self.written == self.sector_size)
+            .then(|| finalize_checksum(self.hasher.clone()))
+    }
+}
+
+impl<W> SectorDestination for ChecksummingWriter<W>
+where
+    W: SectorDestination,
+{
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_sector_chunk(offset, bytes)?;
+        if offset != self.written {
+            self.out_of_order = true;
+        }
+        self.hasher.update(bytes);
+        self.written = offset + bytes.len() as u64;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        self.inner.commit()
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        // Sector will be written from scratch (or resumed) after abort
+        self.hasher = Blake2b::new(BLAKE2B_256_HASH_SIZE);
+        self.written = 0;
+        self.out_of_order = false;
+        self.inner.abort()
+    }
+}
+
+/// Estimates when the next slot arrives from arrival times of previous slots
+#[derive(Debug, Default)]
+pub(crate) struct SlotTimer {
+    last_arrival: Option<Instant>,
+    interval: Option<Duration>,
+}
+
+impl SlotTimer {
+    /// Record arrival of a new slot at `now`
+    pub(crate) fn slot_arrived(&mut self, now: Instant) {
+        if let Some(last_arrival) = self.last_arrival {
+            self.interval
+                .replace(now.saturating_duration_since(last_arrival));
+        }
+        self.last_arrival.replace(now);
+    }
+
+    /// Interval between the two latest slots, `None` until at least two slots have arrived
+    pub(crate) fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Time at which the next slot is expected, `None` until at least two slots have arrived
+    pub(crate) fn next_slot_expected_at(&self) -> Option<Instant> {
+        Some(self.last_arrival? + self.interval?)
+    }
+}
+
+/// Outcome of [`IdleVerification::verify_next()`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum IdleVerificationOutcome {
+    /// Sector contents match its checksum
+    Verified {
+        /// Offset of the sector in plot
+        sector_offset: u64,
+    },
+    /// Sector contents don't match its checksum, sector needs to be replotted
+    Corrupted {
+        /// Offset of the sector in plot
+        sector_offset: u64,
+    },
+    /// Sector was replotted while its verification was paused, it will be verified again on the
+    /// next round
+    Changed {
+        /// Offset of the sector in plot
+        sector_offset: u64,
+    },
+    /// Verification paused before sector was read completely, next call resumes it
+    Paused,
+    /// None of the plotted sectors has checksum
+    NothingToVerify,
+}
+
+struct SectorInProgress {
+    sector_offset: u64,
+    checksum: SectorChecksum,
+    hasher: Blake2b,
+    position: u64,
+}
+
+/// State of opportunistic verification of a single plot
+pub(crate) struct IdleVerification {
+    /// Sector that will be verified after the one in progress
+    next_sector_offset: u64,
+    in_progress: Option<SectorInProgress>,
+}
+
+impl IdleVerification {
+    pub(crate) fn new() -> Self {
+        Self {
+            next_sector_offset: 0,
+            in_progress: None,
+        }
+    }
+
+    /// Verify checksum of the next one of the first `sector_count` sectors in `plot_data`,
+    /// continuing verification that was paused before. Sectors without checksum are skipped.
+    ///
+    /// `should_pause` is called before every read, verification pauses once it returns `true`.
+    pub(crate) fn verify_next<P>(
+        &mut self,
+        plot_data: PlotData<'_>,
+        sector_metadata_file_contents: &[u8],
+        sector_count: u64,
+        plot_sector_size: u64,
+        sector_stride: u64,
+        mut should_pause: P,
+    ) -> io::Result<IdleVerificationOutcome>
+    where
+        P: FnMut() -> bool,
+    {
+        let in_progress = match self.in_progress.take() {
+            Some(in_progress) if in_progress.sector_offset < sector_count => Some(in_progress),
+            _ => self.next_sector(sector_metadata_file_contents, sector_count),
+        };
+        let mut in_progress = match in_progress {
+            Some(in_progress) => in_progress,
+            None => {
+                return Ok(IdleVerificationOutcome::NothingToVerify);
+            }
+        };
+        let sector_offset = in_progress.sector_offset;
+
+        let sector_start = sector_start(sector_offset, sector_stride)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        let mut buffer = vec![0; VERIFICATION_READ_SIZE as usize];
+        while in_progress.position < plot_sector_size {
+            if should_pause() {
+                self.in_progress.replace(in_progress);
+                return Ok(IdleVerificationOutcome::Paused);
+            }
+
+            let read_size = (plot_sector_size - in_progress.position).min(VERIFICATION_READ_SIZE);
+            let buffer = &mut buffer[..read_size as usize];
+            plot_data.read_exact_at(buffer, sector_start + in_progress.position)?;
+            in_progress.hasher.update(buffer);
+            in_progress.position += read_size;
+        }
+
+        // Replotting clears the record first and writes new checksum last, so different checksum
+        // means sector contents were (partially) replaced while verification was paused
+        let current_checksum =
+            read_sector_metadata_record(sector_metadata_file_contents, sector_offset)
+                .ok()
+                .and_then(|record| record.checksum);
+        if current_checksum != Some(in_progress.checksum) {
+            return Ok(IdleVerificationOutcome::Changed { sector_offset });
+        }
+
+        if finalize_checksum(in_progress.hasher) == in_progress.checksum {
+            Ok(IdleVerificationOutcome::Verified { sector_offset })
+        } else {
+            Ok(IdleVerificationOutcome::Corrupted { sector_offset })
+        }
+    }
+
+    /// Start verification of the next sector with checksum in round-robin order
+    fn next_sector(
+        &mut self,
+        sector_metadata_file_contents: &[u8],
+        sector_count: u64,
+    ) -> Option<SectorInProgress> {
+        for _ in 0..sector_count {
+            let sector_offset = self.next_sector_offset % sector_count;
+            self.next_sector_offset = sector_offset + 1;
+
+            let maybe_checksum =
+                read_sector_metadata_record(sector_metadata_file_contents, sector_offset)
+                    .ok()
+                    .and_then(|record| record.checksum);
+            if let Some(checksum) = maybe_checksum {
+                return Some(SectorInProgress {
+                    sector_offset,
+                    checksum,
+                    hasher: Blake2b::new(BLAKE2B_256_HASH_SIZE),
+                    position: 0,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Debug)]
+struct VerificationCoverageInner {
+    window: Duration,
+    /// Time of the last successful verification of every plotted sector, indexed by sector offset
+    verified_at: Mutex<Vec<Option<Instant>>>,
+}
+
+/// Verification coverage of a plot, fraction of plotted sectors that were verified successfully
+/// during idle slots within coverage window.
+///
+/// Cheap to clone, all clones share the same state.
+#[derive(Debug, Clone)]
+pub struct VerificationCoverage {
+    inner: Arc<VerificationCoverageInner>,
+}
+
+impl VerificationCoverage {
+    /// Create new instance, only verifications that happened within `window` are counted
+    pub fn new(window: Duration) -> Self {
+        Self {
+            inner: Arc::new(VerificationCoverageInner {
+                window,
+                verified_at: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Fraction of plotted sectors that were verified within coverage window as of `now`
+    pub fn fraction(&self, now: Instant) -> f64 {
+        let verified_at = self.inner.verified_at.lock();
+        if verified_at.is_empty() {
+            return 0.0;
+        }
+
+        let verified_sectors = verified_at
+            .iter()
+            .flatten()
+            .filter(|verified_at| now.saturating_duration_since(**verified_at) <= self.inner.window)
+            .count();
+
+        verified_sectors as f64 / verified_at.len() as f64
+    }
+
+    /// Update number of plotted sectors
+    pub(crate) fn set_sector_count(&self, sector_count: u64) {
+        self.inner
+            .verified_at
+            .lock()
+            .resize(sector_count as usize, None);
+    }
+
+    /// Record outcome of sector verification at `now`
+    pub(crate) fn record(&self, outcome: IdleVerificationOutcome, now: Instant) {
+        let (sector_offset, verified) = match outcome {
+            IdleVerificationOutcome::Verified { sector_offset } => (sector_offset, true),
+            IdleVerificationOutcome::Corrupted { sector_offset }
+            | IdleVerificationOutcome::Changed { sector_offset } => (sector_offset, false),
+            IdleVerificationOutcome::Paused | IdleVerificationOutcome::NothingToVerify => {
+                return;
+            }
+        };
+
+        if let Some(verified_at) = self
+            .inner
+            .verified_at
+            .lock()
+            .get_mut(sector_offset as usize)
+        {
+            *verified_at = verified.then_some(now);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use crate::single_disk_plot::idle_verification::{
+    sector_checksum, ChecksummingWriter, IdleVerification, IdleVerificationOutcome, SlotTimer,
+    VerificationCoverage,
+};
+use crate::single_disk_plot::plotting::{SectorBuffer, SectorDestination};
+use crate::single_disk_plot::sector_metadata::{
+    sector_metadata_file_size, sector_metadata_record_offset, SectorChecksum, SectorMetadataRecord,
+    SECTOR_CHECKSUM_SIZE, SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::storage_backend::PlotData;
+use crate::single_disk_plot::SectorMetadata;
+use std::num::NonZeroU64;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::{SegmentIndex, PIECE_SIZE};
+
+/// Sector is a few reads long, last read is partial
+const SECTOR_SIZE: u64 = PIECE_SIZE as u64 * 3 + 100;
+
+/// Plot with `sector_count` random sectors and sector metadata file contents with checksums of
+/// sectors for which `with_checksum` returns `true`
+fn test_plot(sector_count: u64, with_checksum: impl Fn(u64) -> bool) -> (Vec<u8>, Vec<u8>) {
+    let plot = (0..SECTOR_SIZE * sector_count)
+        .map(|_| rand::random())
+        .collect::<Vec<u8>>();
+    let mut sector_metadata_file_contents =
+        vec![0; sector_metadata_file_size(sector_count) as usize];
+    for sector_offset in 0..sector_count {
+        let sector = &plot[(sector_offset * SECTOR_SIZE) as usize..][..SECTOR_SIZE as usize];
+        write_record(
+            &mut sector_metadata_file_contents,
+            sector_offset,
+            with_checksum(sector_offset).then(|| sector_checksum(sector)),
+        );
+    }
+
+    (plot, sector_metadata_file_contents)
+}
+
+fn write_record(
+    sector_metadata_file_contents: &mut [u8],
+    sector_offset: u64,
+    checksum: Option<SectorChecksum>,
+) {
+    sector_metadata_file_contents[sector_metadata_record_offset(sector_offset) as usize..]
+        [..SECTOR_METADATA_RECORD_SIZE]
+        .copy_from_slice(
+            &SectorMetadataRecord {
+                sector_metadata: SectorMetadata {
+                    total_pieces: NonZeroU64::new(1).unwrap(),
+                    expires_at: SegmentIndex::ONE,
+                },
+                plotted_at_slot: None,
+                rotation: 0,
+                checksum,
+            }
+            .encode(),
+        );
+}
+
+fn verify_next(
+    idle_verification: &mut IdleVerification,
+    plot: &[u8],
+    sector_metadata_file_contents: &[u8],
+    sector_count: u64,
+    should_pause: impl FnMut() -> bool,
+) -> IdleVerificationOutcome {
+    idle_verification
+        .verify_next(
+            PlotData::Mmap(plot),
+            sector_metadata_file_contents,
+            sector_count,
+            SECTOR_SIZE,
+            SECTOR_SIZE,
+            should_pause,
+        )
+        .unwrap()
+}
+
+#[test]
+fn checksumming_writer() {
+    let sector = (0..SECTOR_SIZE)
+        .map(|_| rand::random())
+        .collect::<Vec<u8>>();
+
+    let mut written = Vec::new();
+    let mut writer = ChecksummingWriter::new(SectorBuffer::new(&mut written), SECTOR_SIZE);
+    for (offset, chunk) in (0..).step_by(1000).zip(sector.chunks(1000)) {
+        writer.write_sector_chunk(offset, chunk).unwrap();
+    }
+    assert_eq!(writer.checksum(), Some(sector_checksum(&sector)));
+
+    // Aborted sector is checksummed from scratch when written again
+    writer.abort().unwrap();
+    assert_eq!(writer.checksum(), None);
+    writer.write_sector_chunk(0, &sector).unwrap();
+    assert_eq!(writer.checksum(), Some(sector_checksum(&sector)));
+    drop(writer);
+    assert_eq!(written, sector);
+
+    // Partially written sector has no checksum
+    let mut written = Vec::new();
+    let mut writer = ChecksummingWriter::new(SectorBuffer::new(&mut written), SECTOR_SIZE);
+    writer.write_sector_chunk(0, &sector[..1000]).unwrap();
+    assert_eq!(writer.checksum(), None);
+
+    // Sector written out of order has no checksum
+    let mut written = Vec::new();
+    let mut writer = ChecksummingWriter::new(SectorBuffer::new(&mut written), SECTOR_SIZE);
+    writer.write_sector_chunk(1000, &sector[1000..]).unwrap();
+    writer.write_sector_chunk(0, &sector[..1000]).unwrap();
+    assert_eq!(writer.checksum(), None);
+}
+
+#[test]
+fn round_robin() {
+    let sector_count = 3;
+    // Sector in the middle was plotted before checksums were stored
+    let (mut plot, sector_metadata_file_contents) =
+        test_plot(sector_count, |sector_offset| sector_offset != 1);
+    plot[(2 * SECTOR_SIZE + SECTOR_SIZE / 2) as usize] ^= 1;
+    let mut idle_verification = IdleVerification::new();
+
+    let mut verify = || {
+        verify_next(
+            &mut idle_verification,
+            &plot,
+            &sector_metadata_file_contents,
+            sector_count,
+            || false,
+        )
+    };
+    assert_eq!(
+        verify(),
+        IdleVerificationOutcome::Verified { sector_offset: 0 }
+    );
+    assert_eq!(
+        verify(),
+        IdleVerificationOutcome::Corrupted { sector_offset: 2 }
+    );
+    assert_eq!(
+        verify(),
+        IdleVerificationOutcome::Verified { sector_offset: 0 }
+    );
+
+    // Nothing to verify without checksums
+    let (plot, sector_metadata_file_contents) = test_plot(sector_count, |_| false);
+    assert_eq!(
+        verify_next(
+            &mut IdleVerification::new(),
+            &plot,
+            &sector_metadata_file_contents,
+            sector_count,
+            || false,
+        ),
+        IdleVerificationOutcome::NothingToVerify
+    );
+}
+
+#[test]
+fn pause_and_resume() {
+    let sector_count = 2;
+    let (plot, mut sector_metadata_file_contents) = test_plot(sector_count, |_| true);
+    let mut idle_verification = IdleVerification::new();
+
+    // Pause after the first read and then after nothing was read at all
+    let mut reads = 0;
+    let outcome = verify_next(
+        &mut idle_verification,
+        &plot,
+        &sector_metadata_file_contents,
+        sector_count,
+        || {
+            reads += 1;
+            reads > 1
+        },
+    );
+    assert_eq!(outcome, IdleVerificationOutcome::Paused);
+    let outcome = verify_next(
+        &mut idle_verification,
+        &plot,
+        &sector_metadata_file_contents,
+        sector_count,
+        || true,
+    );
+    assert_eq!(outcome, IdleVerificationOutcome::Paused);
+
+    // Verification resumes where it paused and produces the same result as without pauses
+    let outcome = verify_next(
+        &mut idle_verification,
+        &plot,
+        &sector_metadata_file_contents,
+        sector_count,
+        || false,
+    );
+    assert_eq!(
+        outcome,
+        IdleVerificationOutcome::Verified { sector_offset: 0 }
+    );
+
+    // Sector that is replotted while verification is paused is not reported as corrupted
+    let outcome = verify_next(
+        &mut idle_verification,
+        &plot,
+        &sector_metadata_file_contents,
+        sector_count,
+        || true,
+    );
+    assert_eq!(outcome, IdleVerificationOutcome::Paused);
+    write_record(
+        &mut sector_metadata_file_contents,
+        1,
+        Some([1; SECTOR_CHECKSUM_SIZE]),
+    );
+    let outcome = verify_next(
+        &mut idle_verification,
+        &plot,
+        &sector_metadata_file_contents,
+        sector_count,
+        || false,
+    );
+    assert_eq!(
+        outcome,
+        IdleVerificationOutcome::Changed { sector_offset: 1 }
+    );
+}
+
+#[test]
+fn coverage() {
+    let coverage = VerificationCoverage::new(Duration::from_secs(60));
+    let now = Instant::now();
+    assert_eq!(coverage.fraction(now), 0.0);
+
+    coverage.set_sector_count(4);
+    coverage.record(IdleVerificationOutcome::Verified { sector_offset: 0 }, now);
+    coverage.record(IdleVerificationOutcome::Verified { sector_offset: 1 }, now);
+    coverage.record(IdleVerificationOutcome::Verified { sector_offset: 2 }, now);
+    coverage.record(IdleVerificationOutcome::Paused, now);
+    assert_eq!(coverage.fraction(now), 0.75);
+
+    // Corrupted sector doesn't count as verified anymore
+    coverage.record(IdleVerificationOutcome::Corrupted { sector_offset: 2 }, now);
+    assert_eq!(coverage.fraction(now), 0.5);
+
+    // Verifications that happened before coverage window are not counted
+    let later = now + Duration::from_secs(90);
+    coverage.record(
+        IdleVerificationOutcome::Verified { sector_offset: 3 },
+        later,
+    );
+    assert_eq!(coverage.fraction(later), 0.25);
+
+    // Clones share the same state
+    assert_eq!(coverage.clone().fraction(later), 0.25);
+}
+
+#[test]
+fn slot_timer() {
+    let mut slot_timer = SlotTimer::default();
+    let now = Instant::now();
+    assert_eq!(slot_timer.next_slot_expected_at(), None);
+
+    slot_timer.slot_arrived(now);
+    assert_eq!(slot_timer.next_slot_expected_at(), None);
+
+    slot_timer.slot_arrived(now + Duration::from_secs(1));
+    assert_eq!(
+        slot_timer.next_slot_expected_at(),
+        Some(now + Duration::from_secs(2))
+    );
+}
@@ -0,0 +1,125 @@
+//! Read-only access to plots of the previous format for one-time conversion.
+//!
+//! Before sectors, plot consisted of whole pieces stored one after another in `plot.bin`, with
+//! index of the piece at every offset stored in `plot-offset-to-index.bin` as little-endian `u64`.
+//! Commitments databases that were stored alongside are not needed and ignored. Pieces of such
+//! plot can be used as a local source of pieces while plotting a new plot instead of retrieving
+//! them from the network again, they must be verified before use.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use subspace_core_primitives::{Piece, PieceIndex, PIECE_SIZE};
+use thiserror::Error;
+use tracing::debug;
+
+/// Errors that happen when opening legacy plot
+#[derive(Debug, Error)]
+pub enum LegacyPlotError {
+    /// Legacy plot not found
+    #[error("Legacy plot not found in {}", directory.display())]
+    NotFound {
+        /// Directory where legacy plot was expected
+        directory: PathBuf,
+    },
+    /// I/O error occurred
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Plot of the previous format, opened read-only and never modified
+#[derive(Debug)]
+pub struct LegacyPlot {
+    plot_file: File,
+    /// Offset (in pieces) of every piece within plot file
+    piece_offsets: HashMap<PieceIndex, u64>,
+}
+
+#[async_trait]
+impl PieceReceiver for LegacyPlot {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(self.read_piece(piece_index)?)
+    }
+}
+
+impl LegacyPlot {
+    /// File with pieces
+    pub const PLOT_FILE: &'static str = "plot.bin";
+    /// File with piece index of every piece in plot file
+    pub const OFFSET_TO_INDEX_FILE: &'static str = "plot-offset-to-index.bin";
+
+    /// Open legacy plot in `directory`, pieces that were not completely written (for instance due
+    /// to interrupted plotting) are ignored
+    pub fn open(directory: &Path) -> Result<Self, LegacyPlotError> {
+        let plot_file_path = directory.join(Self::PLOT_FILE);
+        let offset_to_index_file_path = directory.join(Self::OFFSET_TO_INDEX_FILE);
+
+        if !plot_file_path.is_file() || !offset_to_index_file_path.is_file() {
+            return Err(LegacyPlotError::NotFound {
+                directory: directory.to_path_buf(),
+            });
+        }
+
+        let plot_file = OpenOptions::new().read(true).open(plot_file_path)?;
+        let offset_to_index = std::fs::read(offset_to_index_file_path)?;
+
+        let piece_count = plot_file.metadata()?.len() / PIECE_SIZE as u64;
+        let mut piece_offsets = HashMap::new();
+        for (piece_offset, piece_index_bytes) in
+            (0..piece_count).zip(offset_to_index.chunks_exact(std::mem::size_of::<PieceIndex>()))
+        {
+            let piece_index = PieceIndex::from_le_bytes(
+                piece_index_bytes
+                    .try_into()
+                    .expect("Chunk has exactly the size of piece index; qed"),
+            );
+            // The same piece might have been plotted more than once, the first copy is enough
+            piece_offsets.entry(piece_index).or_insert(piece_offset);
+        }
+
+        debug!(
+            directory = %directory.display(),
+            pieces = piece_offsets.len(),
+            "Opened legacy plot"
+        );
+
+        Ok(Self {
+            plot_file,
+            piece_offsets,
+        })
+    }
+
+    /// Number of distinct pieces in the plot
+    pub fn piece_count(&self) -> usize {
+        self.piece_offsets.len()
+    }
+
+    /// Read piece as it is stored in the plot, `None` is returned if plot doesn't contain it.
+    ///
+    /// Piece is not verified in any way.
+    pub fn read_piece(&self, piece_index: PieceIndex) -> io::Result<Option<Piece>> {
+        let piece_offset = match self.piece_offsets.get(&piece_index) {
+            Some(piece_offset) => *piece_offset,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let mut piece = Piece::default();
+        self.plot_file
+            .read_exact_at(&mut piece, piece_offset * PIECE_SIZE as u64)?;
+
+        Ok(Some(piece))
+    }
+}
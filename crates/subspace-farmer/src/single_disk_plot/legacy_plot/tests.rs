@@ -0,0 +1,57 @@
+use crate::single_disk_plot::legacy_plot::{LegacyPlot, LegacyPlotError};
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use crate::testing::fixtures::piece;
+use futures::executor::block_on;
+use std::fs;
+use subspace_core_primitives::{PieceIndex, PIECE_SIZE};
+use tempfile::tempdir;
+
+#[test]
+fn read_legacy_plot() {
+    let directory = tempdir().unwrap();
+
+    assert!(matches!(
+        LegacyPlot::open(directory.path()),
+        Err(LegacyPlotError::NotFound { .. })
+    ));
+
+    // Piece 7 is plotted twice, the last piece was not completely written
+    let piece_indexes: [PieceIndex; 4] = [3, 7, 100, 7];
+    let mut plot = Vec::new();
+    let mut offset_to_index = Vec::new();
+    for piece_index in piece_indexes {
+        plot.extend_from_slice(&piece(piece_index));
+        offset_to_index.extend_from_slice(&piece_index.to_le_bytes());
+    }
+    plot.extend_from_slice(&piece(42)[..PIECE_SIZE / 2]);
+    offset_to_index.extend_from_slice(&42u64.to_le_bytes());
+    fs::write(directory.path().join(LegacyPlot::PLOT_FILE), &plot).unwrap();
+    fs::write(
+        directory.path().join(LegacyPlot::OFFSET_TO_INDEX_FILE),
+        &offset_to_index,
+    )
+    .unwrap();
+
+    let legacy_plot = LegacyPlot::open(directory.path()).unwrap();
+    assert_eq!(legacy_plot.piece_count(), 3);
+
+    for piece_index in [3, 7, 100] {
+        assert_eq!(
+            block_on(legacy_plot.get_piece(piece_index)).unwrap(),
+            Some(piece(piece_index))
+        );
+    }
+    assert_eq!(legacy_plot.read_piece(42).unwrap(), None);
+    assert_eq!(legacy_plot.read_piece(0).unwrap(), None);
+
+    // Legacy plot is never modified
+    drop(legacy_plot);
+    assert_eq!(
+        fs::read(directory.path().join(LegacyPlot::PLOT_FILE)).unwrap(),
+        plot
+    );
+    assert_eq!(
+        fs::read(directory.path().join(LegacyPlot::OFFSET_TO_INDEX_FILE)).unwrap(),
+        offset_to_index
+    );
+}
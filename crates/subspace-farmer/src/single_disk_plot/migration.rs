@@ -0,0 +1,319 @@
+//! Migration of a plot to a different directory, usually on a different disk.
+//!
+//! Copying plot with plain `cp` creates a sparse or fragmented plot file and nothing checks that
+//! the data reached the disk intact. Migration preallocates destination plot file the same way
+//! farmer does, copies it sector by sector and reads every sector back from the destination to
+//! make sure it matches the source.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::identity::Identity;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::storage_backend::{sector_start, StorageBackend};
+use crate::single_disk_plot::{
+    SingleDiskPlot, SingleDiskPlotError, SingleDiskPlotId, SingleDiskPlotInfo,
+};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::{fs, io};
+use subspace_core_primitives::crypto::{blake2b_256_hash, Blake2b256Hasher};
+use subspace_core_primitives::Blake2b256Hash;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// How many times sector is written before migration gives up on it
+const SECTOR_WRITE_ATTEMPTS: usize = 3;
+/// Files of the plot other than plot file, copied after all sectors, plot info is copied last, so
+/// that incomplete destination is never recognized as a plot
+const PLOT_FILES: &[&str] = &[
+    SingleDiskPlot::METADATA_FILE,
+    SingleDiskPlot::SECTOR_METADATA_FILE,
+    SingleDiskPlot::AUDIT_REPLAY_LOG_FILE,
+    Identity::FILE_NAME,
+    Identity::DERIVATION_FILE_NAME,
+    SingleDiskPlotInfo::FILE_NAME,
+];
+
+/// Errors that happen during [`migrate_plot()`]
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    /// Failed to open source plot
+    #[error("Failed to open source plot: {0}")]
+    Open(#[from] SingleDiskPlotError),
+    /// I/O error occurred
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// Destination directory already contains a plot
+    #[error("Destination directory {directory} already contains a plot")]
+    DestinationContainsPlot {
+        /// Destination directory
+        directory: PathBuf,
+    },
+    /// Destination directory contains progress of migration of a different plot
+    #[error("Destination directory contains migration of plot {found} instead of {expected}")]
+    DifferentPlotInProgress {
+        /// ID of the plot being migrated
+        expected: SingleDiskPlotId,
+        /// ID of the plot whose migration was found in destination directory
+        found: SingleDiskPlotId,
+    },
+    /// Sector read back from destination didn't match the source after every attempt
+    #[error("Sector at offset {sector_offset} doesn't match source after {attempts} attempts")]
+    SectorMismatch {
+        /// Offset of the sector within plot
+        sector_offset: u64,
+        /// Number of attempts to write sector
+        attempts: usize,
+    },
+    /// File copied to destination doesn't match the source
+    #[error("File {path} doesn't match source after copying")]
+    FileMismatch {
+        /// Path to the file in destination directory
+        path: PathBuf,
+    },
+}
+
+/// Result of [`migrate_plot()`]
+#[derive(Debug, Clone)]
+pub struct MigrationReport {
+    /// ID of migrated plot
+    pub id: SingleDiskPlotId,
+    /// Number of sectors in the plot
+    pub sector_count: u64,
+    /// Sectors copied and verified by this call
+    pub copied_sectors: u64,
+    /// Sectors that were already migrated by previous interrupted or cancelled call
+    pub resumed_sectors: u64,
+    /// Number of times sector read back from destination didn't match the source and had to be
+    /// written again
+    pub mismatches: u64,
+    /// Bytes copied by this call
+    pub bytes_copied: u64,
+    /// Whether migration completed, `false` if it was cancelled
+    pub completed: bool,
+    /// Time spent in this call
+    pub elapsed: Duration,
+}
+
+/// Progress of migration, stored in destination directory until migration completes
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MigrationProgress {
+    /// ID of the plot being migrated
+    id: SingleDiskPlotId,
+    /// Number of sectors that are copied and verified
+    migrated_sectors: u64,
+}
+
+impl MigrationProgress {
+    const FILE_NAME: &'static str = "migration.json";
+
+    fn load_from(directory: &Path) -> io::Result<Option<Self>> {
+        let bytes = match fs::read(directory.join(Self::FILE_NAME)) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                return if error.kind() == io::ErrorKind::NotFound {
+                    Ok(None)
+                } else {
+                    Err(error)
+                };
+            }
+        };
+
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Store progress atomically, so that interruption never leaves progress file half-written
+    fn store_to(&self, directory: &Path) -> io::Result<()> {
+        let temporary_path = directory.join(format!("{}.tmp", Self::FILE_NAME));
+        fs::write(
+            &temporary_path,
+            serde_json::to_vec(self).expect("Progress serialization never fails; qed"),
+        )?;
+        fs::rename(temporary_path, directory.join(Self::FILE_NAME))
+    }
+}
+
+/// Migrate plot from `source` directory to `destination` directory.
+///
+/// Destination plot file is preallocated according to storage backend of the destination, then
+/// plotted sectors are copied one by one, every sector is flushed to disk, dropped from page cache
+/// and read back to check that it matches the source. Mismatching sector is written again a few
+/// times before migration fails. Remaining plot files are copied and verified once all sectors are
+/// copied, plot info is copied last.
+///
+/// Progress is stored in destination directory after every sector, so migration that was cancelled
+/// with `cancelled` or interrupted continues where it stopped when called again with the same
+/// directories. Source is never modified, but farmer must not be running on it during migration.
+///
+/// Only plots with sector metadata stored in plot directory are supported.
+///
+/// NOTE: This function does blocking I/O, it must be running in a separate thread in order to
+/// prevent blocking an executor.
+pub fn migrate_plot(
+    source: &Path,
+    destination: &Path,
+    cancelled: &AtomicBool,
+) -> Result<MigrationReport, MigrationError> {
+    let start = Instant::now();
+    let plot = PlotReader::open(source)?;
+    let id = *plot.info().id();
+
+    fs::create_dir_all(destination)?;
+    if SingleDiskPlotInfo::load_from(destination)?.is_some() {
+        return Err(MigrationError::DestinationContainsPlot {
+            directory: destination.to_path_buf(),
+        });
+    }
+    let resumed_sectors = match MigrationProgress::load_from(destination)? {
+        Some(progress) => {
+            if progress.id != id {
+                return Err(MigrationError::DifferentPlotInProgress {
+                    expected: id,
+                    found: progress.id,
+                });
+            }
+            progress.migrated_sectors.min(plot.sector_count())
+        }
+        None => 0,
+    };
+
+    let mut report = MigrationReport {
+        id,
+        sector_count: plot.sector_count(),
+        copied_sectors: 0,
+        resumed_sectors,
+        mismatches: 0,
+        bytes_copied: 0,
+        completed: false,
+        elapsed: Duration::ZERO,
+    };
+
+    let destination_plot_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(destination.join(SingleDiskPlot::PLOT_FILE))?;
+    StorageBackend::detect(destination)?
+        .preallocate(&destination_plot_file, plot.plot_file.metadata()?.len())?;
+
+    info!(
+        %id,
+        sector_count = %plot.sector_count(),
+        %resumed_sectors,
+        "Migrating plot from {} to {}",
+        source.display(),
+        destination.display()
+    );
+
+    let mut source_sector = vec![0; plot.plot_sector_size as usize];
+    let mut destination_sector = vec![0; plot.plot_sector_size as usize];
+    for sector_offset in resumed_sectors..plot.sector_count() {
+        if cancelled.load(Ordering::Acquire) {
+            debug!(%sector_offset, "Migration cancelled");
+            report.elapsed = start.elapsed();
+            return Ok(report);
+        }
+
+        let offset = sector_start(sector_offset, plot.sector_stride)?;
+        plot.plot_file.read_exact_at(&mut source_sector, offset)?;
+        // Source sectors are only read once, don't push useful pages out of page cache
+        plot.plot_file.drop_cache(offset, plot.plot_sector_size)?;
+        let source_hash = blake2b_256_hash(&source_sector);
+
+        let mut attempt = 1;
+        loop {
+            write_sector(
+                &destination_plot_file,
+                offset,
+                &source_sector,
+                &mut destination_sector,
+            )?;
+            report.bytes_copied += source_sector.len() as u64;
+
+            if blake2b_256_hash(&destination_sector) == source_hash {
+                break;
+            }
+
+            report.mismatches += 1;
+            warn!(%sector_offset, %attempt, "Sector doesn't match source after copying");
+            if attempt == SECTOR_WRITE_ATTEMPTS {
+                return Err(MigrationError::SectorMismatch {
+                    sector_offset,
+                    attempts: attempt,
+                });
+            }
+            attempt += 1;
+        }
+
+        report.copied_sectors += 1;
+        MigrationProgress {
+            id,
+            migrated_sectors: sector_offset + 1,
+        }
+        .store_to(destination)?;
+        debug!(%sector_offset, "Sector migrated");
+    }
+
+    for file_name in PLOT_FILES {
+        let source_path = source.join(file_name);
+        // Optional files that plot may not have yet
+        if !source_path.exists() {
+            continue;
+        }
+        report.bytes_copied += copy_file(&source_path, &destination.join(file_name))?;
+    }
+
+    fs::remove_file(destination.join(MigrationProgress::FILE_NAME))?;
+    report.completed = true;
+    report.elapsed = start.elapsed();
+
+    info!(
+        %id,
+        copied_sectors = %report.copied_sectors,
+        mismatches = %report.mismatches,
+        elapsed = ?report.elapsed,
+        "Plot migration completed"
+    );
+
+    Ok(report)
+}
+
+/// Write `sector` to `file` at `offset`, flush it to disk and read it back into `read_back`
+fn write_sector(file: &File, offset: u64, sector: &[u8], read_back: &mut [u8]) -> io::Result<()> {
+    file.write_all_at(sector, offset)?;
+    file.sync_data()?;
+    // Make sure sector is read from disk rather than from page cache
+    file.drop_cache(offset, sector.len() as u64)?;
+    file.read_exact_at(read_back, offset)
+}
+
+/// Copy file from `source` to `destination`, flush it to disk and check that contents match,
+/// returns number of bytes copied
+fn copy_file(source: &Path, destination: &Path) -> Result<u64, MigrationError> {
+    let bytes_copied = fs::copy(source, destination)?;
+    File::open(destination)?.sync_all()?;
+
+    if file_hash(source)? != file_hash(destination)? {
+        return Err(MigrationError::FileMismatch {
+            path: destination.to_path_buf(),
+        });
+    }
+
+    Ok(bytes_copied)
+}
+
+/// Hash of file contents, file is streamed rather than read into memory
+fn file_hash(path: &Path) -> io::Result<Blake2b256Hash> {
+    let mut hasher = Blake2b256Hasher::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hasher.finalize())
+}
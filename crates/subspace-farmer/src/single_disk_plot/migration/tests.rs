@@ -0,0 +1,148 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::migration::{
+    migrate_plot, MigrationError, MigrationProgress, MigrationReport,
+};
+use crate::single_disk_plot::read_only::tests::TestPlot;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::{SingleDiskPlot, SingleDiskPlotId, SingleDiskPlotInfo};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{plot_sector_size, SolutionRange};
+use tempfile::TempDir;
+
+fn create_plot(directory: &Path) -> TestPlot {
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory);
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    test_plot
+}
+
+/// Check that both plots produce the same audit results
+fn assert_audits_identically(source: &Path, destination: &Path) {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let source = PlotReader::open(source).unwrap();
+    let destination = PlotReader::open(destination).unwrap();
+
+    assert_eq!(destination.info().id(), source.info().id());
+    assert_eq!(destination.sector_count(), source.sector_count());
+
+    for sector_offset in 0..source.sector_count() {
+        let global_challenge = rand::random();
+        let audit = |plot: &PlotReader| {
+            plot.audit_sector(sector_offset, &kzg, &global_challenge, SolutionRange::MAX)
+                .unwrap()
+                .map(|eligible_sector| {
+                    (
+                        eligible_sector.audit_index,
+                        eligible_sector.chunk,
+                        eligible_sector.encoded_piece,
+                    )
+                })
+        };
+
+        let expected = audit(&source);
+        assert!(expected.is_some());
+        assert_eq!(audit(&destination), expected);
+    }
+}
+
+#[test]
+fn migrate() {
+    let source = TempDir::new().unwrap();
+    let destination = TempDir::new().unwrap();
+    let test_plot = create_plot(source.path());
+    fs::write(source.path().join("identity.bin"), [1; 32]).unwrap();
+
+    let MigrationReport {
+        id,
+        sector_count,
+        copied_sectors,
+        resumed_sectors,
+        mismatches,
+        completed,
+        ..
+    } = migrate_plot(source.path(), destination.path(), &AtomicBool::new(false)).unwrap();
+    assert_eq!(id, test_plot.id);
+    assert_eq!(sector_count, test_plot.sector_count);
+    assert_eq!(copied_sectors, test_plot.sector_count);
+    assert_eq!(resumed_sectors, 0);
+    assert_eq!(mismatches, 0);
+    assert!(completed);
+
+    assert_audits_identically(source.path(), destination.path());
+    assert_eq!(
+        fs::read(destination.path().join("identity.bin")).unwrap(),
+        [1; 32]
+    );
+    assert!(!destination
+        .path()
+        .join(MigrationProgress::FILE_NAME)
+        .exists());
+
+    // Plot is never migrated over another plot
+    assert!(matches!(
+        migrate_plot(source.path(), destination.path(), &AtomicBool::new(false)),
+        Err(MigrationError::DestinationContainsPlot { .. })
+    ));
+}
+
+#[test]
+fn cancel_and_resume() {
+    let source = TempDir::new().unwrap();
+    let destination = TempDir::new().unwrap();
+    let test_plot = create_plot(source.path());
+
+    let report = migrate_plot(source.path(), destination.path(), &AtomicBool::new(true)).unwrap();
+    assert!(!report.completed);
+    assert_eq!(report.copied_sectors, 0);
+    // Incomplete destination is not a plot
+    assert!(SingleDiskPlotInfo::load_from(destination.path())
+        .unwrap()
+        .is_none());
+
+    // Pretend that the first sector was migrated before interruption
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l) as usize;
+    fs::OpenOptions::new()
+        .write(true)
+        .open(destination.path().join(SingleDiskPlot::PLOT_FILE))
+        .unwrap()
+        .write_all_at(&test_plot.plot[..plot_sector_size], 0)
+        .unwrap();
+    MigrationProgress {
+        id: test_plot.id,
+        migrated_sectors: 1,
+    }
+    .store_to(destination.path())
+    .unwrap();
+
+    let report = migrate_plot(source.path(), destination.path(), &AtomicBool::new(false)).unwrap();
+    assert!(report.completed);
+    assert_eq!(report.resumed_sectors, 1);
+    assert_eq!(report.copied_sectors, test_plot.sector_count - 1);
+    assert_eq!(report.mismatches, 0);
+
+    assert_audits_identically(source.path(), destination.path());
+}
+
+#[test]
+fn different_plot_in_progress() {
+    let source = TempDir::new().unwrap();
+    let destination = TempDir::new().unwrap();
+    let test_plot = create_plot(source.path());
+
+    let other_id = SingleDiskPlotId::new();
+    MigrationProgress {
+        id: other_id,
+        migrated_sectors: 1,
+    }
+    .store_to(destination.path())
+    .unwrap();
+
+    assert!(matches!(
+        migrate_plot(source.path(), destination.path(), &AtomicBool::new(false)),
+        Err(MigrationError::DifferentPlotInProgress { expected, found })
+            if expected == test_plot.id && found == other_id
+    ));
+}
@@ -0,0 +1,337 @@
+//! Farming of a plot stored in read-only snapshot (for instance ZFS or LVM snapshot) with writable
+//! overlay on top of it.
+//!
+//! Live plot can't be backed up since it changes underneath the backup, but its snapshot can be.
+//! Plot directory of such plot is treated as read-only base layer, while overlay directory
+//! receives metadata updates and sectors that are plotted or replotted after snapshot was taken.
+//! Plot metadata of the base is copied into overlay when plot is opened with overlay for the first
+//! time, from then on metadata in overlay is authoritative and views of both layers are merged at
+//! open time with [`SectorResolver`].
+//!
+//! Overlay plot file has the same layout as base plot file, sectors are located at the same
+//! offsets in both, only sectors that were written to overlay occupy space in it.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::storage_backend::{plot_mmap_len, PlotData, StorageBackend};
+use crate::single_disk_plot::{
+    PlotLayout, PlotMetadataHeader, SingleDiskPlot, SingleDiskPlotError,
+};
+use bitvec::prelude::*;
+use memmap2::{Mmap, MmapOptions};
+use parity_scale_codec::Decode;
+use parking_lot::RwLock;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::sync::Arc;
+use std::{fs, io};
+use tracing::{debug, info};
+
+/// Layer that contents of a sector are located in
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SectorLayer {
+    /// Read-only snapshot of the plot
+    Base,
+    /// Writable overlay on top of the snapshot
+    Overlay,
+}
+
+/// Resolves which layer contents of a sector are located in.
+///
+/// Sectors that were plotted when snapshot was taken are located in the base layer unless they
+/// were written to overlay since, overlay always wins over the base.
+#[derive(Debug, Clone)]
+pub struct SectorResolver {
+    base_sector_count: u64,
+    overlay_sectors: BitVec<u8, Lsb0>,
+}
+
+impl SectorResolver {
+    /// Create resolver for base layer with `base_sector_count` plotted sectors and overlay that
+    /// has no sectors written to it yet
+    pub fn new(base_sector_count: u64) -> Self {
+        Self {
+            base_sector_count,
+            overlay_sectors: BitVec::new(),
+        }
+    }
+
+    /// Create resolver for base layer with `base_sector_count` plotted sectors and overlay whose
+    /// sectors are recorded in `overlay_sectors` bitmap, one bit per sector offset
+    pub fn from_overlay_sectors(base_sector_count: u64, overlay_sectors: Vec<u8>) -> Self {
+        Self {
+            base_sector_count,
+            overlay_sectors: BitVec::from_vec(overlay_sectors),
+        }
+    }
+
+    /// Number of sectors plotted in the base layer
+    pub fn base_sector_count(&self) -> u64 {
+        self.base_sector_count
+    }
+
+    /// Number of sectors written to overlay
+    pub fn overlay_sector_count(&self) -> u64 {
+        self.overlay_sectors.count_ones() as u64
+    }
+
+    /// Layer contents of the sector at `sector_offset` are located in, `None` if sector is in
+    /// neither layer, meaning it is not plotted yet
+    pub fn resolve(&self, sector_offset: u64) -> Option<SectorLayer> {
+        let in_overlay = usize::try_from(sector_offset)
+            .ok()
+            .and_then(|sector_offset| self.overlay_sectors.get(sector_offset))
+            .map(|in_overlay| *in_overlay)
+            .unwrap_or_default();
+
+        if in_overlay {
+            Some(SectorLayer::Overlay)
+        } else if sector_offset < self.base_sector_count {
+            Some(SectorLayer::Base)
+        } else {
+            None
+        }
+    }
+
+    /// Record that sector at `sector_offset` was written to overlay, returns `false` if it was
+    /// recorded already
+    pub fn add_overlay_sector(&mut self, sector_offset: u64) -> bool {
+        let sector_offset =
+            usize::try_from(sector_offset).expect("Sector offset was checked to fit plot; qed");
+        if sector_offset >= self.overlay_sectors.len() {
+            self.overlay_sectors.resize(sector_offset + 1, false);
+        }
+
+        !self.overlay_sectors.replace(sector_offset, true)
+    }
+
+    /// Bitmap of sectors written to overlay, one bit per sector offset
+    pub fn overlay_sectors(&self) -> &[u8] {
+        self.overlay_sectors.as_raw_slice()
+    }
+}
+
+/// Copy plot metadata of the base layer into overlay unless it was copied already.
+///
+/// Metadata header is copied last, so copying interrupted half-way is repeated on next open.
+pub(super) fn merge_metadata(
+    directory: &Path,
+    plot_layout: &PlotLayout,
+) -> Result<(), SingleDiskPlotError> {
+    let metadata_file = plot_layout.metadata_file(directory);
+    if metadata_file.exists() {
+        return Ok(());
+    }
+
+    info!(
+        "Copying plot metadata into overlay {}",
+        plot_layout.writable_directory(directory).display()
+    );
+
+    // Plots that were never opened after sector metadata file was introduced don't have it, sector
+    // metadata is moved out of metadata file in overlay in that case
+    let base_sector_metadata_file = plot_layout
+        .base_metadata_directory(directory)
+        .join(SingleDiskPlot::SECTOR_METADATA_FILE);
+    if base_sector_metadata_file.exists() {
+        copy_file(
+            &base_sector_metadata_file,
+            &plot_layout.sector_metadata_file(directory),
+        )?;
+    }
+    copy_file(
+        &plot_layout
+            .base_metadata_directory(directory)
+            .join(SingleDiskPlot::METADATA_FILE),
+        &metadata_file,
+    )?;
+
+    Ok(())
+}
+
+/// Copy file through temporary file, so that `to` either doesn't exist or has complete contents.
+///
+/// Contents are copied rather than the file itself, since permissions of files in read-only
+/// snapshot would make the copy read-only too.
+fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    let mut temporary_path = to.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+
+    let mut temporary_file = File::create(&temporary_path)?;
+    io::copy(&mut File::open(from)?, &mut temporary_file)?;
+    temporary_file.sync_all()?;
+    fs::rename(temporary_path, to)
+}
+
+#[derive(Debug)]
+struct Inner {
+    base_plot_file: File,
+    base_plot_mmap: Option<Mmap>,
+    overlay_sectors_file: File,
+    sector_stride: u64,
+    resolver: RwLock<SectorResolver>,
+}
+
+/// Read-only base layer of the plot together with the record of sectors written to overlay.
+///
+/// Cheap to clone, all clones share the same state.
+#[derive(Debug, Clone)]
+pub(crate) struct PlotOverlay {
+    inner: Arc<Inner>,
+}
+
+impl PlotOverlay {
+    const OVERLAY_SECTORS_FILE: &'static str = "overlay_sectors.bin";
+
+    /// Open base layer of the plot in `directory` and overlay specified in `plot_layout`, which
+    /// must have metadata merged already.
+    ///
+    /// Base plot is memory mapped if storage backend allows it.
+    pub(super) fn open(
+        directory: &Path,
+        plot_layout: &PlotLayout,
+        storage_backend: StorageBackend,
+        sector_stride: u64,
+    ) -> Result<Self, SingleDiskPlotError> {
+        let overlay_directory = plot_layout.writable_directory(directory);
+
+        let base_metadata_file = File::open(
+            plot_layout
+                .base_metadata_directory(directory)
+                .join(SingleDiskPlot::METADATA_FILE),
+        )?;
+        let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+        base_metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
+        let base_sector_count = PlotMetadataHeader::decode(&mut metadata_header_bytes.as_slice())
+            .map_err(SingleDiskPlotError::FailedToDecodeMetadataHeader)?
+            .sector_count;
+
+        let base_plot_file = File::open(directory.join(SingleDiskPlot::PLOT_FILE))?;
+        base_plot_file.advise_random_access()?;
+        let base_plot_mmap = if storage_backend.use_mmap() && base_sector_count > 0 {
+            let base_plot_mmap = unsafe {
+                MmapOptions::new()
+                    .len(plot_mmap_len(base_sector_count, sector_stride)?)
+                    .map(&base_plot_file)?
+            };
+            #[cfg(unix)]
+            {
+                base_plot_mmap.advise(memmap2::Advice::Random)?;
+            }
+
+            Some(base_plot_mmap)
+        } else {
+            None
+        };
+
+        let overlay_sectors_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(overlay_directory.join(Self::OVERLAY_SECTORS_FILE))?;
+        let mut overlay_sectors = vec![0; overlay_sectors_file.metadata()?.len() as usize];
+        overlay_sectors_file.read_exact_at(&mut overlay_sectors, 0)?;
+
+        let resolver = SectorResolver::from_overlay_sectors(base_sector_count, overlay_sectors);
+        debug!(
+            %base_sector_count,
+            overlay_sector_count = %resolver.overlay_sector_count(),
+            "Opened plot overlay"
+        );
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                base_plot_file,
+                base_plot_mmap,
+                overlay_sectors_file,
+                sector_stride,
+                resolver: RwLock::new(resolver),
+            }),
+        })
+    }
+
+    /// Plot data that reads every sector from the layer it is located in, `overlay` is plot data
+    /// of overlay plot file
+    pub(crate) fn plot_data<'a>(&'a self, overlay: &'a PlotData<'a>) -> PlotData<'a> {
+        PlotData::Layered {
+            plot_overlay: self,
+            overlay,
+        }
+    }
+
+    /// Record that sector at `sector_offset` was written to overlay, must be called before sector
+    /// becomes visible to farming
+    pub(crate) fn add_sector(&self, sector_offset: u64) -> io::Result<()> {
+        let mut resolver = self.inner.resolver.write();
+        if resolver.add_overlay_sector(sector_offset) {
+            let byte_offset = sector_offset / u8::BITS as u64;
+            self.inner.overlay_sectors_file.write_all_at(
+                &resolver.overlay_sectors()[byte_offset as usize..][..1],
+                byte_offset,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Sync record of sectors written to overlay to disk
+    pub(crate) fn sync_all(&self) -> io::Result<()> {
+        self.inner.overlay_sectors_file.sync_all()
+    }
+
+    /// Read exact number of bytes at a specific offset within plot, `overlay` is plot data of
+    /// overlay plot file.
+    ///
+    /// Sectors that are not plotted yet are read from overlay, where they will be plotted.
+    pub(crate) fn read_exact_at(
+        &self,
+        overlay: PlotData<'_>,
+        mut buf: &mut [u8],
+        mut offset: u64,
+    ) -> io::Result<()> {
+        let sector_stride = self.inner.sector_stride;
+
+        // Reads that cross sector boundary are split, since neighbouring sectors may be located in
+        // different layers
+        while !buf.is_empty() {
+            let sector_offset = offset / sector_stride;
+            let len = ((sector_offset + 1) * sector_stride - offset).min(buf.len() as u64) as usize;
+            let (chunk, rest) = buf.split_at_mut(len);
+
+            let layer = self.inner.resolver.read().resolve(sector_offset);
+            match layer {
+                Some(SectorLayer::Base) => match &self.inner.base_plot_mmap {
+                    Some(base_plot_mmap) => PlotData::Mmap(base_plot_mmap),
+                    None => PlotData::File(&self.inner.base_plot_file),
+                }
+                .read_exact_at(chunk, offset)?,
+                Some(SectorLayer::Overlay) | None => overlay.read_exact_at(chunk, offset)?,
+            }
+
+            buf = rest;
+            offset += len as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Wipe overlay in `overlay_directory`, base layer is left untouched
+    pub(super) fn wipe(overlay_directory: &Path) -> io::Result<()> {
+        for file_name in [
+            SingleDiskPlot::PLOT_FILE,
+            SingleDiskPlot::METADATA_FILE,
+            SingleDiskPlot::SECTOR_METADATA_FILE,
+            Self::OVERLAY_SECTORS_FILE,
+        ] {
+            let path = overlay_directory.join(file_name);
+            if path.exists() {
+                info!("Deleting overlay file at {}", path.display());
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
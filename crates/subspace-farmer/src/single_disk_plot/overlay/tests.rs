@@ -0,0 +1,143 @@
+use crate::single_disk_plot::overlay::{merge_metadata, PlotOverlay, SectorLayer, SectorResolver};
+use crate::single_disk_plot::read_only::tests::TestPlot;
+use crate::single_disk_plot::storage_backend::{PlotData, StorageBackend};
+use crate::single_disk_plot::{PlotLayout, SingleDiskPlot};
+use std::fs;
+use subspace_core_primitives::crypto::kzg::KzgParametersId;
+use subspace_core_primitives::plot_sector_size;
+use tempfile::TempDir;
+
+#[test]
+fn resolve_sector_layers() {
+    let mut resolver = SectorResolver::new(2);
+
+    assert_eq!(resolver.resolve(0), Some(SectorLayer::Base));
+    assert_eq!(resolver.resolve(1), Some(SectorLayer::Base));
+    // Not plotted in either layer
+    assert_eq!(resolver.resolve(2), None);
+
+    // Sector present in both layers is read from overlay
+    assert!(resolver.add_overlay_sector(1));
+    assert!(!resolver.add_overlay_sector(1));
+    assert_eq!(resolver.resolve(0), Some(SectorLayer::Base));
+    assert_eq!(resolver.resolve(1), Some(SectorLayer::Overlay));
+
+    // Sector plotted after snapshot was taken
+    assert!(resolver.add_overlay_sector(10));
+    assert_eq!(resolver.resolve(10), Some(SectorLayer::Overlay));
+    assert_eq!(resolver.resolve(9), None);
+    assert_eq!(resolver.resolve(11), None);
+    assert_eq!(resolver.resolve(u64::MAX), None);
+    assert_eq!(resolver.overlay_sector_count(), 2);
+
+    let restored = SectorResolver::from_overlay_sectors(
+        resolver.base_sector_count(),
+        resolver.overlay_sectors().to_vec(),
+    );
+    for sector_offset in 0..20 {
+        assert_eq!(
+            restored.resolve(sector_offset),
+            resolver.resolve(sector_offset)
+        );
+    }
+}
+
+#[test]
+fn layered_plot_data() {
+    let directory = TempDir::new().unwrap();
+    let overlay_directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    let plot_layout = PlotLayout {
+        overlay_directory: Some(overlay_directory.path().to_path_buf()),
+        ..PlotLayout::default()
+    };
+    let sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l) as usize;
+
+    merge_metadata(directory.path(), &plot_layout).unwrap();
+    let base_metadata = fs::read(directory.path().join(SingleDiskPlot::METADATA_FILE)).unwrap();
+    assert_eq!(
+        fs::read(overlay_directory.path().join(SingleDiskPlot::METADATA_FILE)).unwrap(),
+        base_metadata
+    );
+    // Metadata in overlay is authoritative once copied
+    fs::write(
+        overlay_directory.path().join(SingleDiskPlot::METADATA_FILE),
+        [1, 2, 3],
+    )
+    .unwrap();
+    merge_metadata(directory.path(), &plot_layout).unwrap();
+    assert_eq!(
+        fs::read(overlay_directory.path().join(SingleDiskPlot::METADATA_FILE)).unwrap(),
+        [1, 2, 3]
+    );
+    fs::write(
+        overlay_directory.path().join(SingleDiskPlot::METADATA_FILE),
+        &base_metadata,
+    )
+    .unwrap();
+
+    // Overlay has one more sector than the base
+    let overlay_plot = vec![0xAA; sector_size * 3];
+    let overlay_plot_data = PlotData::Mmap(&overlay_plot);
+
+    for storage_backend in [StorageBackend::Local, StorageBackend::Network] {
+        let _ = fs::remove_file(
+            overlay_directory
+                .path()
+                .join(PlotOverlay::OVERLAY_SECTORS_FILE),
+        );
+        let plot_overlay = PlotOverlay::open(
+            directory.path(),
+            &plot_layout,
+            storage_backend,
+            sector_size as u64,
+        )
+        .unwrap();
+        let plot_data = overlay_plot_data.layered(Some(&plot_overlay));
+
+        let mut plot = vec![0; sector_size * 3];
+        plot_data.read_exact_at(&mut plot, 0).unwrap();
+        assert_eq!(plot[..sector_size * 2], test_plot.plot);
+        // Unplotted sector is read from overlay
+        assert_eq!(plot[sector_size * 2..], overlay_plot[sector_size * 2..]);
+
+        plot_overlay.add_sector(1).unwrap();
+
+        // Read that crosses sector boundary is split between layers
+        let mut bytes = vec![0; 20];
+        plot_data
+            .read_exact_at(&mut bytes, sector_size as u64 - 10)
+            .unwrap();
+        assert_eq!(bytes[..10], test_plot.plot[sector_size - 10..sector_size]);
+        assert_eq!(bytes[10..], [0xAA; 10]);
+
+        // Sectors written to overlay are remembered between opens
+        drop(plot_overlay);
+        let plot_overlay = PlotOverlay::open(
+            directory.path(),
+            &plot_layout,
+            storage_backend,
+            sector_size as u64,
+        )
+        .unwrap();
+        let plot_data = overlay_plot_data.layered(Some(&plot_overlay));
+        plot_data.read_exact_at(&mut plot, 0).unwrap();
+        assert_eq!(plot[..sector_size], test_plot.plot[..sector_size]);
+        assert_eq!(plot[sector_size..], overlay_plot[sector_size..]);
+    }
+
+    SingleDiskPlot::wipe(directory.path(), &plot_layout).unwrap();
+    assert!(!overlay_directory
+        .path()
+        .join(SingleDiskPlot::METADATA_FILE)
+        .exists());
+    assert_eq!(
+        fs::read(directory.path().join(SingleDiskPlot::PLOT_FILE)).unwrap(),
+        test_plot.plot
+    );
+    assert_eq!(
+        fs::read(directory.path().join(SingleDiskPlot::METADATA_FILE)).unwrap(),
+        base_metadata
+    );
+}
@@ -1,3 +1,7 @@
+use crate::single_disk_plot::sector_metadata::{
+    sector_metadata_record_offset, SectorMetadataRecord, SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::storage_backend::{sector_start, MetadataFile, PlotData};
 use bitvec::prelude::*;
 use futures::channel::{mpsc, oneshot};
 use futures::SinkExt;
@@ -58,21 +62,25 @@ pub(super) fn read_piece(
     public_key: &PublicKey,
     first_sector_index: SectorIndex,
     plot_sector_size: u64,
+    sector_stride: u64,
     record_size: NonZeroU32,
     space_l: NonZeroU16,
-    global_plot: &[u8],
+    global_plot: PlotData<'_>,
+    sector_metadata_file: &MetadataFile,
 ) -> Option<Piece> {
-    if sector_index < first_sector_index {
-        warn!(
-            %sector_index,
-            %piece_offset,
-            %sector_count,
-            %first_sector_index,
-            "Incorrect first sector index"
-        );
-        return None;
-    }
-    let sector_offset = sector_index - first_sector_index;
+    let sector_offset = match sector_index.offset_from(first_sector_index) {
+        Some(sector_offset) => sector_offset,
+        None => {
+            warn!(
+                %sector_index,
+                %piece_offset,
+                %sector_count,
+                %first_sector_index,
+                "Incorrect first sector index"
+            );
+            return None;
+        }
+    };
     // Sector must be plotted
     if sector_offset >= sector_count {
         warn!(
@@ -95,13 +103,78 @@ pub(super) fn read_piece(
         );
         return None;
     }
-    let sector_bytes = &global_plot[(sector_offset * plot_sector_size) as usize..];
-    let piece_bytes = &sector_bytes[piece_offset as usize * PIECE_SIZE..][..PIECE_SIZE];
-    let mut piece = Piece::try_from(piece_bytes).ok()?;
+    let sector_start = match sector_start(sector_offset, sector_stride) {
+        Ok(sector_start) => sector_start,
+        Err(error) => {
+            warn!(
+                %error,
+                %sector_index,
+                %piece_offset,
+                "Incorrect sector offset"
+            );
+            return None;
+        }
+    };
+    // Sector is decoded with rotation it was plotted under, sector being replotted has no valid
+    // record
+    let record = match sector_metadata_file.read_at(
+        sector_metadata_record_offset(sector_offset) as usize,
+        SECTOR_METADATA_RECORD_SIZE,
+    ) {
+        Ok(record) => record,
+        Err(error) => {
+            warn!(
+                %error,
+                %sector_index,
+                %piece_offset,
+                "Failed to read sector metadata record"
+            );
+            return None;
+        }
+    };
+    let rotation = match SectorMetadataRecord::decode(&record, SectorMetadataRecord::LATEST_VERSION)
+    {
+        Ok(record) => record.rotation,
+        Err(error) => {
+            warn!(
+                %error,
+                %sector_index,
+                %piece_offset,
+                "Failed to decode sector metadata record"
+            );
+            return None;
+        }
+    };
+    let mut piece = Piece::default();
+    if let Err(error) =
+        global_plot.read_exact_at(&mut piece, sector_start + piece_offset * PIECE_SIZE as u64)
+    {
+        warn!(
+            %error,
+            %sector_index,
+            %piece_offset,
+            "Failed to read piece from plot"
+        );
+        return None;
+    }
 
-    let sector_id = SectorId::new(public_key, sector_index);
+    decode_piece(
+        &mut piece,
+        &SectorId::new_rotated(public_key, sector_index, rotation),
+        record_size,
+        space_l,
+    );
+
+    Some(piece)
+}
 
-    // Decode piece
+/// Decode piece read from sector with `sector_id` in place
+pub(crate) fn decode_piece(
+    piece: &mut Piece,
+    sector_id: &SectorId,
+    record_size: NonZeroU32,
+    space_l: NonZeroU16,
+) {
     let (record, witness_bytes) = piece.split_at_mut(record_size.get() as usize);
     // TODO: Extract encoding into separate function reusable in farmer and
     //  otherwise
@@ -111,7 +184,7 @@ pub(super) fn read_piece(
         .enumerate()
         .for_each(|(chunk_index, bits)| {
             // Derive one-time pad
-            let mut otp = derive_chunk_otp(&sector_id, witness_bytes, chunk_index as u32);
+            let mut otp = derive_chunk_otp(sector_id, witness_bytes, chunk_index as u32);
             // XOR chunk bit by bit with one-time pad
             bits.iter_mut()
                 .zip(otp.view_bits_mut::<Lsb0>().iter())
@@ -119,6 +192,4 @@ pub(super) fn read_piece(
                     *a ^= *b;
                 });
         });
-
-    Some(piece)
 }
@@ -1,17 +1,55 @@
+#[cfg(test)]
+mod tests;
+
+use crate::clock::{Clock, TokioClock};
+use crate::memory_budget::{MemoryBudget, MemoryCategory};
+use crate::piece_cache::FarmerPieceCache;
+use crate::root_block_store::RootBlockStore;
+use crate::single_disk_plot::witness_cache::WitnessCache;
 use crate::RpcClient;
 use async_trait::async_trait;
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use futures::future::{BoxFuture, Shared, WeakShared};
+use futures::FutureExt;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::num::NonZeroU64;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
-use subspace_core_primitives::{Piece, PieceIndex, PieceIndexHash};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    Piece, PieceIndex, PieceIndexHash, RecordsRoot, SegmentIndex, PIECE_SIZE,
+};
 use subspace_networking::libp2p::PeerId;
 use subspace_networking::utils::multihash::MultihashCode;
 use subspace_networking::{Node, PieceByHashRequest, PieceKey, ToMultihash};
+use thiserror::Error;
 use tokio::time::sleep;
 use tracing::{debug, error, info, trace, warn};
 
 /// Defines a duration between get_piece calls.
 const GET_PIECE_WAITING_DURATION_IN_SECS: u64 = 1;
+/// Maximum delay between retries of failed piece requests
+pub const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Errors produced by piece receivers themselves rather than by the source of pieces, returned
+/// boxed like any other piece receiver error
+#[derive(Debug, Error)]
+pub enum PieceError {
+    /// Piece wasn't retrieved before deadline
+    #[error("Piece {piece_index} wasn't retrieved within {timeout:?}")]
+    Timeout {
+        /// Piece index
+        piece_index: PieceIndex,
+        /// Timeout that was exceeded
+        timeout: Duration,
+    },
+}
 
 #[async_trait]
 pub trait PieceReceiver {
@@ -21,15 +59,28 @@ pub trait PieceReceiver {
     ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>>;
 }
 
+#[async_trait]
+impl<PR> PieceReceiver for Arc<PR>
+where
+    PR: PieceReceiver + Send + Sync + ?Sized,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        self.as_ref().get_piece(piece_index).await
+    }
+}
+
 // Temporary struct serving pieces from different providers using configuration arguments.
-pub(crate) struct MultiChannelPieceReceiver<'a, RC: RpcClient> {
+pub(crate) struct MultiChannelPieceReceiver<RC: RpcClient> {
     rpc_client: RC,
     dsn_node: Option<Node>,
-    cancelled: &'a AtomicBool,
+    cancelled: Arc<AtomicBool>,
 }
 
-impl<'a, RC: RpcClient> MultiChannelPieceReceiver<'a, RC> {
-    pub(crate) fn new(rpc_client: RC, dsn_node: Option<Node>, cancelled: &'a AtomicBool) -> Self {
+impl<RC: RpcClient> MultiChannelPieceReceiver<RC> {
+    pub(crate) fn new(rpc_client: RC, dsn_node: Option<Node>, cancelled: Arc<AtomicBool>) -> Self {
         Self {
             rpc_client,
             dsn_node,
@@ -143,6 +194,14 @@ impl<'a, RC: RpcClient> MultiChannelPieceReceiver<'a, RC> {
                                 if let Some(piece) = request.piece {
                                     return Some(piece);
                                 }
+                                if let Some(retry_after_ms) = request.retry_after_ms {
+                                    debug!(
+                                        %piece_index,
+                                        ?peer_id,
+                                        %retry_after_ms,
+                                        "Piece provider is busy"
+                                    );
+                                }
                             }
                             Err(error) => {
                                 error!(%piece_index,?peer_id, ?key, ?error, "Error on piece-by-hash request.");
@@ -171,7 +230,7 @@ impl<'a, RC: RpcClient> MultiChannelPieceReceiver<'a, RC> {
 }
 
 #[async_trait]
-impl<'a, RC: RpcClient> PieceReceiver for MultiChannelPieceReceiver<'a, RC> {
+impl<RC: RpcClient> PieceReceiver for MultiChannelPieceReceiver<RC> {
     async fn get_piece(
         &self,
         piece_index: PieceIndex,
@@ -203,3 +262,714 @@ impl<'a, RC: RpcClient> PieceReceiver for MultiChannelPieceReceiver<'a, RC> {
         }
     }
 }
+
+/// Piece receiver that checks local piece cache first and only goes to wrapped piece receiver for
+/// pieces that are not in the cache
+pub struct CachedPieceReceiver<PR> {
+    piece_cache: FarmerPieceCache,
+    piece_receiver: PR,
+}
+
+impl<PR> CachedPieceReceiver<PR> {
+    pub fn new(piece_cache: FarmerPieceCache, piece_receiver: PR) -> Self {
+        Self {
+            piece_cache,
+            piece_receiver,
+        }
+    }
+}
+
+#[async_trait]
+impl<PR> PieceReceiver for CachedPieceReceiver<PR>
+where
+    PR: PieceReceiver + Send + Sync,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        if let Some(piece) = self.piece_cache.get_piece(piece_index) {
+            trace!(%piece_index, "Piece found in local piece cache");
+
+            return Ok(Some(piece));
+        }
+
+        self.piece_receiver.get_piece(piece_index).await
+    }
+}
+
+/// Piece receiver that verifies pieces retrieved by wrapped piece receiver against records root of
+/// the segment they belong to.
+///
+/// Records roots are taken from root block store, node is only asked about segments whose root
+/// blocks are not in the store. Witnesses that were verified before are taken from witness cache.
+pub struct VerifyingPieceReceiver<PR, RC> {
+    piece_receiver: PR,
+    rpc_client: RC,
+    root_block_store: RootBlockStore,
+    kzg: Kzg,
+    witness_cache: WitnessCache,
+    record_size: u32,
+    /// Number of data and parity pieces in a segment
+    pieces_in_segment: u32,
+}
+
+impl<PR, RC> VerifyingPieceReceiver<PR, RC> {
+    pub fn new(
+        piece_receiver: PR,
+        rpc_client: RC,
+        root_block_store: RootBlockStore,
+        kzg: Kzg,
+        witness_cache: WitnessCache,
+        record_size: u32,
+        recorded_history_segment_size: u32,
+    ) -> Self {
+        Self {
+            piece_receiver,
+            rpc_client,
+            root_block_store,
+            kzg,
+            witness_cache,
+            record_size,
+            pieces_in_segment: recorded_history_segment_size / record_size * 2,
+        }
+    }
+}
+
+impl<PR, RC> VerifyingPieceReceiver<PR, RC>
+where
+    RC: RpcClient,
+{
+    async fn records_root(
+        &self,
+        segment_index: SegmentIndex,
+    ) -> Result<Option<RecordsRoot>, Box<dyn Error + Send + Sync + 'static>> {
+        if let Some(root_block) = self.root_block_store.get(segment_index) {
+            return Ok(Some(root_block.records_root()));
+        }
+
+        trace!(%segment_index, "Root block not found in store, requesting records root from node");
+
+        Ok(self
+            .rpc_client
+            .records_roots(vec![segment_index])
+            .await?
+            .into_iter()
+            .next()
+            .flatten())
+    }
+}
+
+#[async_trait]
+impl<PR, RC> PieceReceiver for VerifyingPieceReceiver<PR, RC>
+where
+    PR: PieceReceiver + Send + Sync,
+    RC: RpcClient,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let piece = match self.piece_receiver.get_piece(piece_index).await? {
+            Some(piece) => piece,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let segment_index =
+            SegmentIndex::new(piece_index / PieceIndex::from(self.pieces_in_segment));
+        let position = (piece_index % PieceIndex::from(self.pieces_in_segment)) as u32;
+
+        let records_root = self.records_root(segment_index).await?.ok_or_else(|| {
+            format!("Records root of segment {segment_index} is unknown, can't verify piece")
+        })?;
+
+        if !self.witness_cache.is_piece_valid(
+            &self.kzg,
+            self.pieces_in_segment,
+            &piece,
+            records_root,
+            position,
+            self.record_size,
+        ) {
+            warn!(%piece_index, %segment_index, "Received invalid piece");
+
+            return Err(format!("Piece {piece_index} failed verification").into());
+        }
+
+        Ok(Some(piece))
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    /// `None` means there is no limit
+    bytes_per_second: Option<NonZeroU64>,
+    /// Negative when bytes were reserved by fetches that are waiting for their turn
+    available_bytes: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, now: Instant) {
+        if let Some(bytes_per_second) = self.bytes_per_second {
+            let bytes_per_second = bytes_per_second.get() as f64;
+            let elapsed = now.saturating_duration_since(self.last_refill);
+            // Accumulate at most one second worth of bytes to limit bursts after idle periods
+            self.available_bytes = (self.available_bytes
+                + elapsed.as_secs_f64() * bytes_per_second)
+                .min(bytes_per_second);
+        }
+        self.last_refill = now;
+    }
+}
+
+/// Download bandwidth limit in bytes per second, implemented as a token bucket.
+///
+/// Clones share the same bucket, so the limit is enforced across all piece receivers it is used
+/// with. The limit can be adjusted at runtime, new value applies to fetches started after the
+/// change.
+#[derive(Debug, Clone)]
+pub struct BandwidthLimit {
+    inner: Arc<Mutex<TokenBucket>>,
+}
+
+impl BandwidthLimit {
+    /// Create new bandwidth limit, `None` means no limit
+    pub fn new(bytes_per_second: Option<NonZeroU64>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(TokenBucket {
+                bytes_per_second,
+                available_bytes: 0.0,
+                last_refill: Instant::now(),
+            })),
+        }
+    }
+
+    /// Current limit in bytes per second, `None` means no limit
+    pub fn bytes_per_second(&self) -> Option<NonZeroU64> {
+        self.inner.lock().bytes_per_second
+    }
+
+    /// Change limit, `None` means no limit
+    pub fn set_bytes_per_second(&self, bytes_per_second: Option<NonZeroU64>) {
+        let mut token_bucket = self.inner.lock();
+        token_bucket.refill(Instant::now());
+        token_bucket.bytes_per_second = bytes_per_second;
+        token_bucket.available_bytes = match bytes_per_second {
+            Some(bytes_per_second) => token_bucket
+                .available_bytes
+                .min(bytes_per_second.get() as f64),
+            None => 0.0,
+        };
+    }
+
+    /// Reserve `bytes` from the bucket and wait until they are available according to `clock`
+    pub(crate) async fn acquire<C>(&self, bytes: u64, clock: &C)
+    where
+        C: Clock,
+    {
+        let delay = {
+            let mut token_bucket = self.inner.lock();
+            let bytes_per_second = match token_bucket.bytes_per_second {
+                Some(bytes_per_second) => bytes_per_second,
+                None => {
+                    return;
+                }
+            };
+
+            token_bucket.refill(Instant::now());
+            // Reserve bytes even if not available yet, this way concurrent fetches queue up behind
+            // each other instead of competing for the same bytes
+            token_bucket.available_bytes -= bytes as f64;
+
+            if token_bucket.available_bytes >= 0.0 {
+                return;
+            }
+
+            Duration::from_secs_f64(-token_bucket.available_bytes / bytes_per_second.get() as f64)
+        };
+
+        clock.sleep_until(clock.now() + delay).await;
+    }
+}
+
+/// Piece receiver that limits download bandwidth of wrapped piece receiver.
+///
+/// Bandwidth for a piece is reserved before the piece is requested, so when limit is reached
+/// requests are delayed rather than issued and throttled afterwards.
+pub struct BandwidthLimitedPieceReceiver<PR, C = TokioClock> {
+    piece_receiver: PR,
+    bandwidth_limit: BandwidthLimit,
+    clock: C,
+}
+
+impl<PR> BandwidthLimitedPieceReceiver<PR> {
+    pub fn new(piece_receiver: PR, bandwidth_limit: BandwidthLimit) -> Self {
+        Self::with_clock(piece_receiver, bandwidth_limit, TokioClock)
+    }
+}
+
+impl<PR, C> BandwidthLimitedPieceReceiver<PR, C> {
+    pub fn with_clock(piece_receiver: PR, bandwidth_limit: BandwidthLimit, clock: C) -> Self {
+        Self {
+            piece_receiver,
+            bandwidth_limit,
+            clock,
+        }
+    }
+}
+
+#[async_trait]
+impl<PR, C> PieceReceiver for BandwidthLimitedPieceReceiver<PR, C>
+where
+    PR: PieceReceiver + Send + Sync,
+    C: Clock,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        self.bandwidth_limit
+            .acquire(PIECE_SIZE as u64, &self.clock)
+            .await;
+
+        self.piece_receiver.get_piece(piece_index).await
+    }
+}
+
+/// Piece receiver that accounts pieces being downloaded by wrapped piece receiver in memory budget
+pub struct MemoryAccountedPieceReceiver<PR> {
+    piece_receiver: PR,
+    memory_budget: MemoryBudget,
+}
+
+impl<PR> MemoryAccountedPieceReceiver<PR> {
+    pub fn new(piece_receiver: PR, memory_budget: MemoryBudget) -> Self {
+        Self {
+            piece_receiver,
+            memory_budget,
+        }
+    }
+}
+
+#[async_trait]
+impl<PR> PieceReceiver for MemoryAccountedPieceReceiver<PR>
+where
+    PR: PieceReceiver + Send + Sync,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        // Downloads are bounded by plotting that waits for memory budget, so they are only
+        // accounted for here and never wait themselves
+        let _memory_reservation = self
+            .memory_budget
+            .register(MemoryCategory::Downloads, PIECE_SIZE as u64);
+
+        self.piece_receiver.get_piece(piece_index).await
+    }
+}
+
+/// Piece receiver that fails every request of wrapped piece receiver that takes longer than
+/// specified timeout with [`PieceError::Timeout`], so a hung peer can't stall plotting
+/// indefinitely.
+///
+/// Timeout applies to each request individually, use [`RetryingPieceReceiver`] on top to retry
+/// requests that timed out.
+pub struct TimeoutPieceReceiver<PR, C = TokioClock> {
+    piece_receiver: PR,
+    timeout: Duration,
+    clock: C,
+}
+
+impl<PR> TimeoutPieceReceiver<PR> {
+    pub fn new(piece_receiver: PR, timeout: Duration) -> Self {
+        Self::with_clock(piece_receiver, timeout, TokioClock)
+    }
+}
+
+impl<PR, C> TimeoutPieceReceiver<PR, C> {
+    pub fn with_clock(piece_receiver: PR, timeout: Duration, clock: C) -> Self {
+        Self {
+            piece_receiver,
+            timeout,
+            clock,
+        }
+    }
+}
+
+#[async_trait]
+impl<PR, C> PieceReceiver for TimeoutPieceReceiver<PR, C>
+where
+    PR: PieceReceiver + Send + Sync,
+    C: Clock,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let deadline = self.clock.now() + self.timeout;
+
+        tokio::select! {
+            result = self.piece_receiver.get_piece(piece_index) => result,
+            () = self.clock.sleep_until(deadline) => {
+                debug!(%piece_index, timeout = ?self.timeout, "Piece request timed out");
+
+                Err(PieceError::Timeout {
+                    piece_index,
+                    timeout: self.timeout,
+                }
+                .into())
+            }
+        }
+    }
+}
+
+/// Piece receiver that retries requests of wrapped piece receiver that failed with
+/// [`PieceError`] using exponential backoff, all other errors are returned immediately.
+///
+/// Delay between retries never exceeds [`MAX_RETRY_INTERVAL`].
+pub struct RetryingPieceReceiver<PR, C = TokioClock> {
+    piece_receiver: PR,
+    clock: C,
+}
+
+impl<PR> RetryingPieceReceiver<PR> {
+    pub fn new(piece_receiver: PR) -> Self {
+        Self::with_clock(piece_receiver, TokioClock)
+    }
+}
+
+impl<PR, C> RetryingPieceReceiver<PR, C> {
+    pub fn with_clock(piece_receiver: PR, clock: C) -> Self {
+        Self {
+            piece_receiver,
+            clock,
+        }
+    }
+}
+
+#[async_trait]
+impl<PR, C> PieceReceiver for RetryingPieceReceiver<PR, C>
+where
+    PR: PieceReceiver + Send + Sync,
+    C: Clock,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let mut backoff = ExponentialBackoff {
+            max_interval: MAX_RETRY_INTERVAL,
+            max_elapsed_time: None,
+            ..ExponentialBackoff::default()
+        };
+
+        loop {
+            match self.piece_receiver.get_piece(piece_index).await {
+                Err(error) if error.is::<PieceError>() => {
+                    // Randomization may take delay above maximum interval otherwise
+                    let delay = backoff
+                        .next_backoff()
+                        .expect("Backoff without maximum elapsed time never ends; qed")
+                        .min(MAX_RETRY_INTERVAL);
+                    warn!(%piece_index, %error, ?delay, "Retrying piece request");
+
+                    self.clock.sleep_until(self.clock.now() + delay).await;
+                }
+                result => {
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+/// Piece receiver that tries local source of pieces first (if there is one) and retrieves pieces
+/// that local source doesn't have or failed to provide from `fallback`
+pub struct FallbackPieceReceiver<PR, F> {
+    local: Option<PR>,
+    fallback: F,
+}
+
+impl<PR, F> FallbackPieceReceiver<PR, F> {
+    pub fn new(local: Option<PR>, fallback: F) -> Self {
+        Self { local, fallback }
+    }
+}
+
+#[async_trait]
+impl<PR, F> PieceReceiver for FallbackPieceReceiver<PR, F>
+where
+    PR: PieceReceiver + Send + Sync,
+    F: PieceReceiver + Send + Sync,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        if let Some(local) = &self.local {
+            match local.get_piece(piece_index).await {
+                Ok(Some(piece)) => {
+                    trace!(%piece_index, "Piece found locally");
+
+                    return Ok(Some(piece));
+                }
+                Ok(None) => {
+                    trace!(%piece_index, "Piece not found locally");
+                }
+                Err(error) => {
+                    debug!(%piece_index, %error, "Failed to get piece locally, falling back");
+                }
+            }
+        }
+
+        self.fallback.get_piece(piece_index).await
+    }
+}
+
+pub(super) type PieceDownloadResult<T = Piece> =
+    Result<Option<T>, Arc<dyn Error + Send + Sync + 'static>>;
+
+pub(super) enum PieceDownload<T = Piece> {
+    /// Piece was downloaded recently
+    Recent(T),
+    /// Piece is being downloaded
+    InFlight {
+        shared_download: Shared<BoxFuture<'static, PieceDownloadResult<T>>>,
+        /// Download was started by this request
+        started_here: bool,
+    },
+}
+
+struct PieceDownloadsInner<K, T> {
+    in_flight: HashMap<K, WeakShared<BoxFuture<'static, PieceDownloadResult<T>>>>,
+    recent_pieces: HashMap<K, (T, Instant)>,
+    /// Recent pieces in the order they expire
+    recent_pieces_order: VecDeque<(K, Instant)>,
+}
+
+impl<K, T> Default for PieceDownloadsInner<K, T> {
+    fn default() -> Self {
+        Self {
+            in_flight: HashMap::new(),
+            recent_pieces: HashMap::new(),
+            recent_pieces_order: VecDeque::new(),
+        }
+    }
+}
+
+impl<K, T> PieceDownloadsInner<K, T>
+where
+    K: Copy + Eq + Hash,
+{
+    fn remove_expired(&mut self, now: Instant) {
+        while let Some((key, expires_at)) = self.recent_pieces_order.front().copied() {
+            if expires_at > now {
+                break;
+            }
+            self.recent_pieces_order.pop_front();
+            // Piece might have been downloaded again since then
+            if self
+                .recent_pieces
+                .get(&key)
+                .map(|(_piece, recent_expires_at)| *recent_expires_at == expires_at)
+                .unwrap_or_default()
+            {
+                self.recent_pieces.remove(&key);
+            }
+        }
+    }
+}
+
+/// Piece downloads shared by all [`CoalescingPieceReceiver`]s it is used with, so that concurrent
+/// requests for the same piece (for example, from different plots) result in a single download.
+///
+/// Pieces downloaded within the last `recent_pieces_ttl` are kept in memory and returned without
+/// downloading them again. Memory used by them grows with download rate, so TTL should be short.
+///
+/// Downloads are keyed by piece index by default, but anything that is downloaded as a whole can be
+/// coalesced the same way, for instance pieces of the whole segment keyed by segment index.
+pub struct PieceDownloads<K = PieceIndex, T = Piece, C = TokioClock> {
+    recent_pieces_ttl: Duration,
+    clock: C,
+    inner: Arc<Mutex<PieceDownloadsInner<K, T>>>,
+}
+
+impl<K, T, C> Clone for PieceDownloads<K, T, C>
+where
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            recent_pieces_ttl: self.recent_pieces_ttl,
+            clock: self.clock.clone(),
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<K, T, C> fmt::Debug for PieceDownloads<K, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PieceDownloads")
+            .field("recent_pieces_ttl", &self.recent_pieces_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, T> PieceDownloads<K, T> {
+    /// Create new shared piece downloads, zero `recent_pieces_ttl` disables keeping of recent
+    /// pieces in memory
+    pub fn new(recent_pieces_ttl: Duration) -> Self {
+        Self::with_clock(recent_pieces_ttl, TokioClock)
+    }
+}
+
+impl<K, T, C> PieceDownloads<K, T, C> {
+    /// Same as [`PieceDownloads::new()`], but recent pieces expire according to `clock`
+    pub fn with_clock(recent_pieces_ttl: Duration, clock: C) -> Self {
+        Self {
+            recent_pieces_ttl,
+            clock,
+            inner: Arc::default(),
+        }
+    }
+}
+
+impl<K, T, C> PieceDownloads<K, T, C>
+where
+    K: Copy + Eq + Hash + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+    C: Clock + Clone + 'static,
+{
+    /// Recently downloaded piece or download that is in progress, new download is created with
+    /// `download` if there is neither
+    pub(super) fn get_or_start<F>(&self, key: K, download: F) -> PieceDownload<T>
+    where
+        F: FnOnce() -> BoxFuture<'static, PieceDownloadResult<T>>,
+    {
+        let mut inner = self.inner.lock();
+        inner.remove_expired(self.clock.now());
+
+        if let Some((piece, _expires_at)) = inner.recent_pieces.get(&key) {
+            return PieceDownload::Recent(piece.clone());
+        }
+
+        // Download is only alive while someone is waiting for it
+        if let Some(shared_download) = inner.in_flight.get(&key).and_then(WeakShared::upgrade) {
+            return PieceDownload::InFlight {
+                shared_download,
+                started_here: false,
+            };
+        }
+
+        let piece_downloads = self.clone();
+        let shared_download = async move {
+            let result = download().await;
+            piece_downloads.finish(key, &result);
+            result
+        }
+        .boxed()
+        .shared();
+
+        inner
+            .in_flight
+            .retain(|_key, weak_download| weak_download.upgrade().is_some());
+        inner.in_flight.insert(
+            key,
+            shared_download
+                .downgrade()
+                .expect("Download was just created and not polled yet; qed"),
+        );
+
+        PieceDownload::InFlight {
+            shared_download,
+            started_here: true,
+        }
+    }
+
+    fn finish(&self, key: K, result: &PieceDownloadResult<T>) {
+        let mut inner = self.inner.lock();
+        inner.in_flight.remove(&key);
+
+        if let Ok(Some(piece)) = result {
+            if !self.recent_pieces_ttl.is_zero() {
+                let expires_at = self.clock.now() + self.recent_pieces_ttl;
+                inner.recent_pieces.insert(key, (piece.clone(), expires_at));
+                inner.recent_pieces_order.push_back((key, expires_at));
+            }
+        }
+    }
+}
+
+/// Piece receiver that coalesces concurrent requests for the same piece with other piece receivers
+/// sharing the same [`PieceDownloads`], only one of them downloads the piece using its wrapped
+/// piece receiver and the result is shared with all the others.
+///
+/// Download continues as long as at least one request for the piece is waiting for it. If shared
+/// download fails (for instance, because plot that started it is shutting down), other requests
+/// retry with their own wrapped piece receivers.
+pub struct CoalescingPieceReceiver<PR, C = TokioClock> {
+    piece_receiver: Arc<PR>,
+    piece_downloads: PieceDownloads<PieceIndex, Piece, C>,
+}
+
+impl<PR, C> CoalescingPieceReceiver<PR, C> {
+    pub fn new(piece_receiver: PR, piece_downloads: PieceDownloads<PieceIndex, Piece, C>) -> Self {
+        Self {
+            piece_receiver: Arc::new(piece_receiver),
+            piece_downloads,
+        }
+    }
+}
+
+#[async_trait]
+impl<PR, C> PieceReceiver for CoalescingPieceReceiver<PR, C>
+where
+    PR: PieceReceiver + Send + Sync + 'static,
+    C: Clock + Clone + 'static,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let (shared_download, started_here) =
+            match self.piece_downloads.get_or_start(piece_index, || {
+                let piece_receiver = Arc::clone(&self.piece_receiver);
+                async move {
+                    piece_receiver
+                        .get_piece(piece_index)
+                        .await
+                        .map_err(Arc::from)
+                }
+                .boxed()
+            }) {
+                PieceDownload::Recent(piece) => {
+                    trace!(%piece_index, "Piece was downloaded recently");
+
+                    return Ok(Some(piece));
+                }
+                PieceDownload::InFlight {
+                    shared_download,
+                    started_here,
+                } => (shared_download, started_here),
+            };
+
+        if !started_here {
+            trace!(%piece_index, "Joining piece download that is already in progress");
+        }
+
+        match shared_download.await {
+            Ok(maybe_piece) => Ok(maybe_piece),
+            Err(error) if started_here => Err(error.into()),
+            Err(error) => {
+                debug!(%piece_index, %error, "Shared piece download failed, downloading separately");
+
+                self.piece_receiver.get_piece(piece_index).await
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use crate::clock::{Clock, TestClock};
+use crate::single_disk_plot::piece_receiver::{
+    BandwidthLimit, BandwidthLimitedPieceReceiver, CoalescingPieceReceiver, FallbackPieceReceiver,
+    PieceDownloads, PieceError, PieceReceiver, RetryingPieceReceiver, TimeoutPieceReceiver,
+    MAX_RETRY_INTERVAL,
+};
+use crate::testing::fixtures::DerivedPieceReceiver;
+use async_trait::async_trait;
+use futures::future::join_all;
+use futures::FutureExt;
+use std::error::Error;
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::{Piece, PieceIndex, PIECE_SIZE};
+
+const PIECE_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Piece receiver that hangs on the first request and responds immediately afterwards
+#[derive(Default)]
+struct HangingPieceReceiver {
+    requests: AtomicUsize,
+}
+
+#[async_trait]
+impl PieceReceiver for HangingPieceReceiver {
+    async fn get_piece(
+        &self,
+        _piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        if self.requests.fetch_add(1, Ordering::SeqCst) == 0 {
+            tokio::time::sleep(PIECE_FETCH_TIMEOUT * 2).await;
+        }
+
+        Ok(Some(Piece::default()))
+    }
+}
+
+/// Piece receiver that times out a number of times before responding
+struct FailingPieceReceiver {
+    failures: usize,
+    requests: AtomicUsize,
+}
+
+#[async_trait]
+impl PieceReceiver for FailingPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        if self.requests.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err(PieceError::Timeout {
+                piece_index,
+                timeout: PIECE_FETCH_TIMEOUT,
+            }
+            .into());
+        }
+
+        Ok(Some(Piece::default()))
+    }
+}
+
+/// Piece receiver that counts requests and takes a second to respond
+struct SlowPieceReceiver {
+    requests: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl PieceReceiver for SlowPieceReceiver {
+    async fn get_piece(
+        &self,
+        _piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        Ok(Some(Piece::default()))
+    }
+}
+
+fn coalescing_piece_receivers<C>(
+    piece_downloads: &PieceDownloads<PieceIndex, Piece, C>,
+    requests: &Arc<AtomicUsize>,
+) -> Vec<Arc<CoalescingPieceReceiver<SlowPieceReceiver, C>>>
+where
+    C: Clone,
+{
+    (0..2)
+        .map(|_| {
+            Arc::new(CoalescingPieceReceiver::new(
+                SlowPieceReceiver {
+                    requests: Arc::clone(requests),
+                },
+                piece_downloads.clone(),
+            ))
+        })
+        .collect()
+}
+
+/// Fetch pieces concurrently using all receivers and return achieved rate in bytes per second
+async fn fetch_rate(
+    piece_receivers: &[BandwidthLimitedPieceReceiver<DerivedPieceReceiver>],
+    pieces_per_receiver: u64,
+) -> f64 {
+    let start = Instant::now();
+
+    join_all(piece_receivers.iter().map(|piece_receiver| async move {
+        for piece_index in 0..pieces_per_receiver {
+            piece_receiver
+                .get_piece(piece_index)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    }))
+    .await;
+
+    let bytes = piece_receivers.len() as u64 * pieces_per_receiver * PIECE_SIZE as u64;
+    bytes as f64 / start.elapsed().as_secs_f64()
+}
+
+#[tokio::test]
+async fn bandwidth_limit() {
+    let limit = NonZeroU64::new(PIECE_SIZE as u64 * 4).unwrap();
+    let bandwidth_limit = BandwidthLimit::new(Some(limit));
+
+    // Limit is shared between receivers
+    let piece_receivers = (0..2)
+        .map(|_| BandwidthLimitedPieceReceiver::new(DerivedPieceReceiver, bandwidth_limit.clone()))
+        .collect::<Vec<_>>();
+
+    let rate = fetch_rate(&piece_receivers, 4).await;
+    assert!(
+        rate <= limit.get() as f64,
+        "Rate {rate} bytes/s must not exceed limit {limit} bytes/s"
+    );
+
+    // Limit can be changed at runtime
+    let new_limit = NonZeroU64::new(PIECE_SIZE as u64 * 16).unwrap();
+    bandwidth_limit.set_bytes_per_second(Some(new_limit));
+    assert_eq!(bandwidth_limit.bytes_per_second(), Some(new_limit));
+
+    let new_rate = fetch_rate(&piece_receivers, 4).await;
+    assert!(
+        new_rate <= new_limit.get() as f64,
+        "Rate {new_rate} bytes/s must not exceed limit {new_limit} bytes/s"
+    );
+    assert!(
+        new_rate > limit.get() as f64,
+        "Rate {new_rate} bytes/s must exceed old limit {limit} bytes/s after increase"
+    );
+
+    // Without limit pieces are fetched immediately
+    bandwidth_limit.set_bytes_per_second(None);
+    let start = Instant::now();
+    fetch_rate(&piece_receivers, 4).await;
+    assert!(start.elapsed().as_secs_f64() < 1.0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn piece_fetch_timeout() {
+    let piece_receiver =
+        TimeoutPieceReceiver::new(HangingPieceReceiver::default(), PIECE_FETCH_TIMEOUT);
+
+    let error = piece_receiver.get_piece(0).await.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<PieceError>(),
+        Some(PieceError::Timeout {
+            piece_index: 0,
+            timeout: PIECE_FETCH_TIMEOUT,
+        })
+    ));
+
+    // The next attempt succeeds
+    piece_receiver.get_piece(0).await.unwrap().unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn piece_fetch_timeout_retry() {
+    let piece_receiver = RetryingPieceReceiver::new(TimeoutPieceReceiver::new(
+        HangingPieceReceiver::default(),
+        PIECE_FETCH_TIMEOUT,
+    ));
+
+    piece_receiver.get_piece(0).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn piece_fetch_deadline() {
+    let clock = TestClock::new();
+    let piece_receiver = TimeoutPieceReceiver::with_clock(
+        HangingPieceReceiver::default(),
+        PIECE_FETCH_TIMEOUT,
+        clock.clone(),
+    );
+
+    let mut get_piece = piece_receiver.get_piece(0);
+    assert!((&mut get_piece).now_or_never().is_none());
+
+    // Deadline is not reached yet
+    clock.advance(PIECE_FETCH_TIMEOUT - Duration::from_secs(1));
+    assert!((&mut get_piece).now_or_never().is_none());
+
+    clock.advance(Duration::from_secs(1));
+    let error = get_piece.now_or_never().unwrap().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<PieceError>(),
+        Some(PieceError::Timeout {
+            piece_index: 0,
+            timeout: PIECE_FETCH_TIMEOUT,
+        })
+    ));
+}
+
+#[test]
+fn piece_fetch_retry_backoff_caps_at_max() {
+    let failures = 40;
+    let clock = TestClock::new();
+    let piece_receiver = RetryingPieceReceiver::with_clock(
+        FailingPieceReceiver {
+            failures,
+            requests: AtomicUsize::new(0),
+        },
+        clock.clone(),
+    );
+
+    let mut get_piece = piece_receiver.get_piece(0);
+    let mut delays = Vec::new();
+    let piece = loop {
+        if let Some(result) = (&mut get_piece).now_or_never() {
+            break result.unwrap();
+        }
+
+        let delay = clock.next_deadline().unwrap() - clock.now();
+        delays.push(delay);
+        clock.advance(delay);
+    };
+
+    assert!(piece.is_some());
+    assert_eq!(delays.len(), failures);
+    assert!(delays.iter().all(|delay| *delay <= MAX_RETRY_INTERVAL));
+    // Delay grows until it hits the cap
+    assert_eq!(delays.iter().max(), Some(&MAX_RETRY_INTERVAL));
+    assert!(delays[0] < MAX_RETRY_INTERVAL / 10);
+}
+
+#[tokio::test(start_paused = true)]
+async fn piece_fallback() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let fallback = || SlowPieceReceiver {
+        requests: Arc::clone(&requests),
+    };
+
+    // Without local source everything goes to fallback
+    let piece_receiver = FallbackPieceReceiver::new(None::<DerivedPieceReceiver>, fallback());
+    piece_receiver.get_piece(0).await.unwrap().unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // Failed local request falls back, successful doesn't
+    let piece_receiver = FallbackPieceReceiver::new(
+        Some(FailingPieceReceiver {
+            failures: 1,
+            requests: AtomicUsize::new(0),
+        }),
+        fallback(),
+    );
+    piece_receiver.get_piece(0).await.unwrap().unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    piece_receiver.get_piece(0).await.unwrap().unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn piece_download_coalescing() {
+    let recent_pieces_ttl = Duration::from_secs(5);
+    let clock = TestClock::new();
+    let piece_downloads = PieceDownloads::with_clock(recent_pieces_ttl, clock.clone());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let piece_receivers = coalescing_piece_receivers(&piece_downloads, &requests);
+
+    // Concurrent requests for the same piece from different receivers result in a single download
+    let pieces = join_all(
+        piece_receivers
+            .iter()
+            .map(|piece_receiver| piece_receiver.get_piece(0)),
+    )
+    .await;
+    assert!(pieces
+        .iter()
+        .all(|result| matches!(result, Ok(Some(piece)) if piece == &Piece::default())));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // Other pieces are downloaded separately
+    piece_receivers[0].get_piece(1).await.unwrap().unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    // Recently downloaded piece is served from memory
+    piece_receivers[1].get_piece(0).await.unwrap().unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    // Until it expires
+    clock.advance(recent_pieces_ttl - Duration::from_secs(1));
+    piece_receivers[1].get_piece(0).await.unwrap().unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    clock.advance(Duration::from_secs(1));
+    piece_receivers[1].get_piece(0).await.unwrap().unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn piece_download_coalescing_cancellation() {
+    let piece_downloads = PieceDownloads::new(Duration::ZERO);
+    let requests = Arc::new(AtomicUsize::new(0));
+    let piece_receivers = coalescing_piece_receivers(&piece_downloads, &requests);
+
+    let first_request = tokio::spawn({
+        let piece_receiver = Arc::clone(&piece_receivers[0]);
+        async move { piece_receiver.get_piece(0).await.map(|_| ()) }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let second_request = tokio::spawn({
+        let piece_receiver = Arc::clone(&piece_receivers[1]);
+        async move {
+            piece_receiver
+                .get_piece(0)
+                .await
+                .map(|maybe_piece| maybe_piece.is_some())
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Request that started download is cancelled, but download continues for the other one
+    first_request.abort();
+    assert!(second_request.await.unwrap().unwrap());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
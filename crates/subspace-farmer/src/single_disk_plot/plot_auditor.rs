@@ -0,0 +1,122 @@
+//! Stable public API for external tools that audit plots.
+//!
+//! [`PlotAuditor`] opens plot in read-only mode without connection to the node and audits its
+//! sectors against arbitrary challenges. Sectors are read lazily with positional reads, so plot
+//! doesn't need to be memory mapped, and audit is done by the same code the farmer uses, results
+//! are guaranteed to be the same.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use subspace_core_primitives::crypto::kzg::{test_public_parameters, Kzg};
+//! use subspace_core_primitives::SolutionRange;
+//! use subspace_farmer::single_disk_plot::plot_auditor::PlotAuditor;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let auditor = PlotAuditor::open(
+//!     Path::new("/path/to/plot"),
+//!     Kzg::new(test_public_parameters()),
+//! )?;
+//! // Every chunk is within the largest solution range, so every sector is a hit and this
+//! // enumerates audited chunks of all sectors
+//! for hit in auditor.audit([0; 32], SolutionRange::MAX) {
+//!     let hit = hit?;
+//!     println!("Sector {}: chunk {:?}", hit.sector_index, hit.chunk);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::farming::EligibleSector;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::{FarmingError, SingleDiskPlotError};
+use std::path::Path;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{Blake2b256Hash, SectorIndex, SolutionRange};
+use thiserror::Error;
+
+/// Sector that is eligible for solution creation, contains audited chunk and encoded piece it
+/// belongs to
+pub type AuditHit = EligibleSector;
+
+/// Errors that happen when creating [`PlotAuditor`]
+#[derive(Debug, Error)]
+pub enum PlotAuditorError {
+    /// Failed to open plot
+    #[error("Failed to open plot: {0}")]
+    Open(#[from] SingleDiskPlotError),
+    /// Plot was created with KZG parameters different from supplied
+    #[error("Plot was created with KZG parameters {plot}, but {supplied} were supplied")]
+    KzgParametersMismatch {
+        /// KZG parameters plot was created with
+        plot: KzgParametersId,
+        /// Supplied KZG parameters
+        supplied: KzgParametersId,
+    },
+}
+
+/// Failed to audit a single sector
+#[derive(Debug, Error)]
+#[error("Failed to audit sector {sector_index}: {error}")]
+pub struct AuditError {
+    /// Sector index
+    pub sector_index: SectorIndex,
+    /// Lower-level error
+    pub error: FarmingError,
+}
+
+/// Audits sectors of a plot against arbitrary challenges, see [module-level documentation](self)
+#[derive(Debug)]
+pub struct PlotAuditor {
+    plot: PlotReader,
+    kzg: Kzg,
+}
+
+impl PlotAuditor {
+    /// Open plot stored in `directory`, `kzg` must use the same parameters plot was created with
+    pub fn open(directory: &Path, kzg: Kzg) -> Result<Self, PlotAuditorError> {
+        Self::from_read_only(PlotReader::open(directory)?, kzg)
+    }
+
+    /// Create auditor for already opened plot, `kzg` must use the same parameters plot was created
+    /// with
+    pub fn from_read_only(plot: PlotReader, kzg: Kzg) -> Result<Self, PlotAuditorError> {
+        if plot.kzg_parameters_id() != kzg.id() {
+            return Err(PlotAuditorError::KzgParametersMismatch {
+                plot: plot.kzg_parameters_id(),
+                supplied: kzg.id(),
+            });
+        }
+
+        Ok(Self { plot, kzg })
+    }
+
+    /// Plot being audited
+    pub fn plot(&self) -> &PlotReader {
+        &self.plot
+    }
+
+    /// Audit sectors plotted by the time plot was opened one by one, yields sectors that are within
+    /// `solution_range` for `global_challenge` and errors of sectors that couldn't be audited.
+    ///
+    /// Sectors are only read when the next item is requested, so it is cheap to stop early.
+    pub fn audit(
+        &self,
+        global_challenge: Blake2b256Hash,
+        solution_range: SolutionRange,
+    ) -> impl Iterator<Item = Result<AuditHit, AuditError>> + '_ {
+        let first_sector_index = self.plot.info().first_sector_index();
+
+        (0..self.plot.sector_count()).filter_map(move |sector_offset| {
+            self.plot
+                .audit_sector(sector_offset, &self.kzg, &global_challenge, solution_range)
+                .map_err(|error| AuditError {
+                    sector_index: first_sector_index.offset(sector_offset),
+                    error,
+                })
+                .transpose()
+        })
+    }
+}
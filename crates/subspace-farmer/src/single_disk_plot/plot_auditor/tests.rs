@@ -0,0 +1,73 @@
+use crate::single_disk_plot::plot_auditor::{AuditError, PlotAuditor, PlotAuditorError};
+use crate::single_disk_plot::read_only::tests::TestPlot;
+use crate::single_disk_plot::SingleDiskPlot;
+use std::fs::OpenOptions;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{plot_sector_size, SolutionRange};
+use tempfile::TempDir;
+
+#[test]
+fn plot_auditor() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    let global_challenge = rand::random();
+
+    assert!(matches!(
+        PlotAuditor::open(
+            directory.path(),
+            Kzg::with_id(kzg::test_public_parameters(), KzgParametersId(1)),
+        ),
+        Err(PlotAuditorError::KzgParametersMismatch {
+            plot: KzgParametersId::TEST,
+            supplied: KzgParametersId(1),
+        })
+    ));
+
+    let auditor =
+        PlotAuditor::open(directory.path(), Kzg::new(kzg::test_public_parameters())).unwrap();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    // Every sector is a hit with the largest solution range and results are the same as with
+    // auditing sectors individually
+    let hits = auditor
+        .audit(global_challenge, SolutionRange::MAX)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(hits.len() as u64, test_plot.sector_count);
+    for (sector_offset, hit) in (0..).zip(&hits) {
+        let expected = auditor
+            .plot()
+            .audit_sector(sector_offset, &kzg, &global_challenge, SolutionRange::MAX)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            hit.sector_index,
+            test_plot.first_sector_index.offset(sector_offset)
+        );
+        assert_eq!(hit.audit_index, expected.audit_index);
+        assert_eq!(hit.chunk, expected.chunk);
+        assert_eq!(hit.encoded_piece, expected.encoded_piece);
+    }
+
+    // Nothing is within zero solution range
+    assert_eq!(auditor.audit(global_challenge, 0).count(), 0);
+
+    // Sectors that can't be read are reported as errors without stopping iteration
+    OpenOptions::new()
+        .write(true)
+        .open(directory.path().join(SingleDiskPlot::PLOT_FILE))
+        .unwrap()
+        .set_len(plot_sector_size(test_plot.farmer_protocol_info.space_l))
+        .unwrap();
+    let results = auditor
+        .audit(global_challenge, SolutionRange::MAX)
+        .collect::<Vec<_>>();
+    assert_eq!(results.len() as u64, test_plot.sector_count);
+    assert!(results[0].is_ok());
+    assert!(matches!(
+        results[1],
+        Err(AuditError { sector_index, .. }) if sector_index == test_plot.first_sector_index.offset(1)
+    ));
+}
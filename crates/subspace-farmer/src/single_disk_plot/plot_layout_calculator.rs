@@ -0,0 +1,114 @@
+//! Relationship between disk space and number of sectors of a plot.
+//!
+//! Plot creation, dry run, `info` and `simulate` commands all answer "how many sectors fit into
+//! this much space" with [`PlotLayoutCalculator`], so they never disagree.
+
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::sector_metadata::{
+    sector_metadata_file_size, SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::storage_backend::sector_stride;
+use crate::single_disk_plot::{SingleDiskPlotError, RESERVED_PLOT_METADATA};
+use subspace_core_primitives::plot_sector_size;
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+/// Space taken by each of the small files of the plot (identity and plot info), they take at least
+/// one file system block regardless of actual size
+const SMALL_FILE_SIZE: u64 = 4096;
+/// Number of small files in plot directory
+const SMALL_FILES: u64 = 2;
+
+/// Calculator of plot size and number of sectors for specific protocol parameters and sector
+/// alignment.
+///
+/// Space includes plot file with sectors and padding between them as well as plot metadata, sector
+/// metadata, identity and plot info files. Audit replay log is optional and sized explicitly, so it
+/// is not included.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PlotLayoutCalculator {
+    plot_sector_size: u64,
+    sector_stride: u64,
+}
+
+impl PlotLayoutCalculator {
+    /// Calculator for plots with sectors that are not aligned
+    pub fn new(farmer_protocol_info: &FarmerProtocolInfo) -> Self {
+        let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+        Self {
+            plot_sector_size,
+            sector_stride: plot_sector_size,
+        }
+    }
+
+    /// Calculator for plots with sectors aligned to `sector_alignment` bytes, alignment must be a
+    /// power of two
+    pub fn with_sector_alignment(
+        farmer_protocol_info: &FarmerProtocolInfo,
+        sector_alignment: u64,
+    ) -> Result<Self, SingleDiskPlotError> {
+        let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+        Ok(Self {
+            plot_sector_size,
+            sector_stride: sector_stride(plot_sector_size, sector_alignment)?,
+        })
+    }
+
+    /// Calculator for plots with already known sector stride
+    pub(crate) fn with_sector_stride(plot_sector_size: u64, sector_stride: u64) -> Self {
+        Self {
+            plot_sector_size,
+            sector_stride,
+        }
+    }
+
+    /// Size of one sector in bytes
+    pub fn plot_sector_size(&self) -> u64 {
+        self.plot_sector_size
+    }
+
+    /// Distance in bytes between starts of consecutive sectors, sector size padded to alignment
+    pub fn sector_stride(&self) -> u64 {
+        self.sector_stride
+    }
+
+    /// The largest number of sectors plot that takes at most `space` bytes can have
+    pub fn sectors_for_space(&self, space: u64) -> u64 {
+        // Metadata overhead is linear in number of sectors: fixed part plus one record per sector
+        let fixed_overhead = self.metadata_overhead(0);
+        let per_sector_space = self.sector_stride + SECTOR_METADATA_RECORD_SIZE as u64;
+
+        space.saturating_sub(fixed_overhead) / per_sector_space
+    }
+
+    /// Space in bytes taken by plot with `sector_count` sectors, saturates at [`u64::MAX`]
+    pub fn space_for_sectors(&self, sector_count: u64) -> u64 {
+        sector_count
+            .saturating_mul(self.sector_stride)
+            .saturating_add(self.metadata_overhead(sector_count))
+    }
+
+    /// Space in bytes taken by everything except sectors in plot with `sector_count` sectors,
+    /// saturates at [`u64::MAX`]
+    pub fn metadata_overhead(&self, sector_count: u64) -> u64 {
+        let sector_metadata_size = if sector_count < u64::MAX / SECTOR_METADATA_RECORD_SIZE as u64 {
+            sector_metadata_file_size(sector_count)
+        } else {
+            u64::MAX
+        };
+
+        RESERVED_PLOT_METADATA
+            .saturating_add(sector_metadata_size)
+            .saturating_add(SMALL_FILE_SIZE * SMALL_FILES)
+    }
+
+    /// Fraction of space that is occupied by sector contents in large plots, where fixed overhead
+    /// is negligible
+    pub fn usable_fraction(&self) -> f64 {
+        self.plot_sector_size as f64
+            / (self.sector_stride + SECTOR_METADATA_RECORD_SIZE as u64) as f64
+    }
+}
@@ -0,0 +1,90 @@
+use crate::single_disk_plot::plot_layout_calculator::PlotLayoutCalculator;
+use crate::single_disk_plot::SingleDiskPlotError;
+use crate::testing::fixtures;
+use std::num::NonZeroU16;
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+const TIB: u64 = 1024 * GIB;
+
+fn farmer_protocol_info(space_l: u16) -> FarmerProtocolInfo {
+    FarmerProtocolInfo {
+        space_l: NonZeroU16::new(space_l).unwrap(),
+        ..fixtures::farmer_protocol_info()
+    }
+}
+
+#[test]
+fn known_values() {
+    // (space_l, sector size, sectors in 1 GiB, sectors in 1 TiB)
+    let known_values = [
+        (16, 131_072, 8_179, 8_384_505),
+        (18, 589_824, 1_818, 1_863_931),
+        (20, 2_621_440, 409, 419_419),
+    ];
+
+    for (space_l, plot_sector_size, sectors_in_gib, sectors_in_tib) in known_values {
+        let calculator = PlotLayoutCalculator::new(&farmer_protocol_info(space_l));
+
+        assert_eq!(calculator.plot_sector_size(), plot_sector_size);
+        assert_eq!(calculator.sector_stride(), plot_sector_size);
+        assert_eq!(calculator.sectors_for_space(GIB), sectors_in_gib);
+        assert_eq!(calculator.sectors_for_space(TIB), sectors_in_tib);
+
+        // Space for sectors is the smallest space that fits them
+        for sector_count in [0, 1, sectors_in_gib, sectors_in_tib] {
+            let space = calculator.space_for_sectors(sector_count);
+            assert_eq!(calculator.sectors_for_space(space), sector_count);
+            assert_eq!(
+                calculator.sectors_for_space(space - 1),
+                sector_count.saturating_sub(1)
+            );
+        }
+    }
+}
+
+#[test]
+fn metadata_overhead() {
+    let calculator = PlotLayoutCalculator::new(&farmer_protocol_info(16));
+
+    // Reserved plot metadata, sector metadata header and small files
+    assert_eq!(calculator.metadata_overhead(0), 1_056_832);
+    // One sector metadata record per sector
+    assert_eq!(calculator.metadata_overhead(1_000), 1_056_832 + 64_000);
+    assert_eq!(
+        calculator.space_for_sectors(1_000),
+        1_000 * 131_072 + 1_056_832 + 64_000
+    );
+    assert_eq!(calculator.sectors_for_space(1_056_832), 0);
+    assert_eq!(calculator.sectors_for_space(0), 0);
+
+    // Never overflows
+    assert_eq!(calculator.space_for_sectors(u64::MAX), u64::MAX);
+    assert_eq!(calculator.metadata_overhead(u64::MAX), u64::MAX);
+
+    assert!((calculator.usable_fraction() - 131_072.0 / 131_136.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn sector_alignment() {
+    let farmer_protocol_info = farmer_protocol_info(16);
+
+    // Sector size is a multiple of alignment already
+    assert_eq!(
+        PlotLayoutCalculator::with_sector_alignment(&farmer_protocol_info, 4096).unwrap(),
+        PlotLayoutCalculator::new(&farmer_protocol_info)
+    );
+
+    // Padding after every sector is as large as sector itself
+    let calculator =
+        PlotLayoutCalculator::with_sector_alignment(&farmer_protocol_info, 262_144).unwrap();
+    assert_eq!(calculator.plot_sector_size(), 131_072);
+    assert_eq!(calculator.sector_stride(), 262_144);
+    assert_eq!(calculator.sectors_for_space(GIB), 4_090);
+    assert!(calculator.usable_fraction() < 0.5);
+
+    assert!(matches!(
+        PlotLayoutCalculator::with_sector_alignment(&farmer_protocol_info, 3),
+        Err(SingleDiskPlotError::InvalidSectorAlignment { .. })
+    ));
+}
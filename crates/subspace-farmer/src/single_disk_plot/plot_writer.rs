@@ -0,0 +1,278 @@
+//! Writable handle of the plot for offline tools.
+//!
+//! [`PlotReader`] has no plotting APIs at all, so verifiers and auditors can't modify plots they
+//! only need to read by accident. Tools that do need to write sectors (for instance to plot on a
+//! different machine or repair damaged sectors) open plot with [`PlotWriter`] instead, which
+//! provides the same reading APIs through [`PlotWriter::reader()`].
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::idle_verification::ChecksummingWriter;
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use crate::single_disk_plot::plot_layout_calculator::PlotLayoutCalculator;
+use crate::single_disk_plot::plotting::{plot_sector, PlotSectorError, PlottedSector};
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::sector_metadata::{
+    sector_metadata_record_offset, SectorMetadataRecord,
+};
+use crate::single_disk_plot::storage_backend::{plot_size, SectorFileWriter};
+use crate::single_disk_plot::{
+    PlotLayout, PlotMetadataHeader, PlotProtocolInfo, PlotSectorAlignment, SingleDiskPlot,
+    SingleDiskPlotError, SingleDiskPlotId, SingleDiskPlotInfo, RESERVED_PLOT_METADATA,
+};
+use parity_scale_codec::Encode;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg::KzgParametersId;
+use subspace_core_primitives::{PublicKey, SectorIndex};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use thiserror::Error;
+
+/// Errors that happen during [`PlotWriter`] operations
+#[derive(Debug, Error)]
+pub enum PlotWriterError {
+    /// Failed to open plot
+    #[error("Failed to open plot: {0}")]
+    Open(#[from] SingleDiskPlotError),
+    /// I/O error occurred
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// Failed to plot sector
+    #[error("Failed to plot sector: {0}")]
+    Plotting(#[from] PlotSectorError),
+    /// All sectors of the plot are plotted already
+    #[error("Plot is fully plotted, it has {sector_count} sectors")]
+    PlotIsFull {
+        /// Number of sectors plot has
+        sector_count: u64,
+    },
+    /// Sector is not plotted yet, so it can't be replotted
+    #[error("Sector offset {sector_offset} is out of range, plot has {sector_count} sectors")]
+    SectorNotPlotted {
+        /// Offset of the sector within plot
+        sector_offset: u64,
+        /// Number of sectors plotted
+        sector_count: u64,
+    },
+}
+
+/// Writable handle of the plot that doesn't need connection to the node, meant for external tools
+/// that plot sectors offline.
+///
+/// Plot is locked in exclusive mode while writer exists, neither [`PlotReader`] nor another writer
+/// can open it at the same time. Farmer must not be running on the plot.
+#[derive(Debug)]
+pub struct PlotWriter {
+    reader: PlotReader,
+    metadata_header: PlotMetadataHeader,
+    sector_metadata_file: File,
+}
+
+impl PlotWriter {
+    /// Create new empty plot with space for `sector_count` sectors in `directory`, which is useful
+    /// for plotting sectors offline without connection to the node.
+    ///
+    /// Plot is created the same way farmer creates it, with sectors located back to back, and can
+    /// be farmed by the farmer connected to the node with the same genesis hash afterwards.
+    pub fn create(
+        directory: &Path,
+        public_key: PublicKey,
+        first_sector_index: SectorIndex,
+        sector_count: u64,
+        farmer_protocol_info: FarmerProtocolInfo,
+        kzg_parameters_id: KzgParametersId,
+    ) -> Result<Self, SingleDiskPlotError> {
+        if SingleDiskPlotInfo::load_from(directory)?.is_some() {
+            return Err(SingleDiskPlotError::PlotAlreadyExists {
+                directory: directory.to_path_buf(),
+            });
+        }
+
+        let layout_calculator = PlotLayoutCalculator::new(&farmer_protocol_info);
+        let id = SingleDiskPlotId::new();
+        let plot_file_size = plot_size(sector_count, layout_calculator.sector_stride())?;
+
+        let plot_file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(directory.join(SingleDiskPlot::PLOT_FILE))?;
+        plot_file.preallocate(plot_file_size)?;
+
+        let metadata_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(directory.join(SingleDiskPlot::METADATA_FILE))?;
+        metadata_file.preallocate(RESERVED_PLOT_METADATA)?;
+        let mut metadata_header = PlotMetadataHeader {
+            version: 0,
+            sector_count: 0,
+        };
+        metadata_file.write_all_at(&metadata_header.encode(), 0)?;
+        PlotProtocolInfo::load_or_store(
+            id,
+            &metadata_file,
+            &mut metadata_header,
+            farmer_protocol_info,
+            kzg_parameters_id,
+        )?;
+        PlotSectorAlignment::load_or_store(&metadata_file, &mut metadata_header, 1)?;
+
+        // Plot info is stored last, so that plot that failed to be created is not mistaken for
+        // the real one
+        SingleDiskPlotInfo::new(
+            id,
+            farmer_protocol_info.genesis_hash,
+            public_key,
+            first_sector_index,
+            layout_calculator.space_for_sectors(sector_count),
+        )
+        .store_to(directory)?;
+
+        Self::open(directory)
+    }
+
+    /// Open plot stored in `directory`, plot must have been opened by the farmer at least once
+    pub fn open(directory: &Path) -> Result<Self, SingleDiskPlotError> {
+        Self::open_with_layout(directory, &PlotLayout::default())
+    }
+
+    /// Open plot stored in `directory` with specified layout, plot must have been opened by the
+    /// farmer at least once.
+    ///
+    /// Sector metadata is moved into sector metadata file if plot still stores it in plot metadata
+    /// file.
+    pub fn open_with_layout(
+        directory: &Path,
+        plot_layout: &PlotLayout,
+    ) -> Result<Self, SingleDiskPlotError> {
+        let (reader, metadata_header) = PlotReader::open_internal(directory, plot_layout, true)?;
+        let sector_metadata_file = reader
+            .sector_metadata_file
+            .as_ref()
+            .expect("Writable plot always has sector metadata file; qed")
+            .try_clone()?;
+
+        Ok(Self {
+            reader,
+            metadata_header,
+            sector_metadata_file,
+        })
+    }
+
+    /// Reading APIs of the plot, sectors plotted by this writer are visible
+    pub fn reader(&self) -> &PlotReader {
+        &self.reader
+    }
+
+    /// Plot the next sector of the plot with pieces from `piece_receiver`, sector becomes visible
+    /// once it is fully written.
+    ///
+    /// NOTE: This function does blocking I/O.
+    pub async fn plot_sector<PR>(
+        &mut self,
+        piece_receiver: &PR,
+        cancelled: &AtomicBool,
+    ) -> Result<PlottedSector, PlotWriterError>
+    where
+        PR: PieceReceiver,
+    {
+        let sector_offset = self.reader.sector_count();
+        if sector_offset >= self.reader.target_sector_count() {
+            return Err(PlotWriterError::PlotIsFull {
+                sector_count: sector_offset,
+            });
+        }
+
+        let plotted_sector = self
+            .write_sector(sector_offset, piece_receiver, cancelled)
+            .await?;
+
+        // Sector metadata record was written above, only now sector becomes visible
+        self.metadata_header.sector_count += 1;
+        self.reader
+            .metadata_file
+            .write_all_at(&self.metadata_header.encode(), 0)?;
+        self.reader.sector_count = self.metadata_header.sector_count;
+
+        Ok(plotted_sector)
+    }
+
+    /// Plot sector at `sector_offset` that is plotted already again with pieces from
+    /// `piece_receiver`, for instance to repair sector that failed verification.
+    ///
+    /// Sector contents are overwritten in place, sector is invalid if replotting fails or is
+    /// cancelled and needs to be replotted again.
+    ///
+    /// NOTE: This function does blocking I/O.
+    pub async fn replot_sector<PR>(
+        &mut self,
+        sector_offset: u64,
+        piece_receiver: &PR,
+        cancelled: &AtomicBool,
+    ) -> Result<PlottedSector, PlotWriterError>
+    where
+        PR: PieceReceiver,
+    {
+        if sector_offset >= self.reader.sector_count() {
+            return Err(PlotWriterError::SectorNotPlotted {
+                sector_offset,
+                sector_count: self.reader.sector_count(),
+            });
+        }
+
+        self.write_sector(sector_offset, piece_receiver, cancelled)
+            .await
+    }
+
+    /// Plot sector at `sector_offset` and write its sector metadata record
+    async fn write_sector<PR>(
+        &self,
+        sector_offset: u64,
+        piece_receiver: &PR,
+        cancelled: &AtomicBool,
+    ) -> Result<PlottedSector, PlotWriterError>
+    where
+        PR: PieceReceiver,
+    {
+        let mut sector_output = ChecksummingWriter::new(
+            SectorFileWriter::new(
+                &self.reader.plot_file,
+                sector_offset,
+                self.reader.sector_stride,
+            )?,
+            self.reader.plot_sector_size,
+        );
+
+        let plotted_sector = plot_sector(
+            self.reader.info().public_key(),
+            self.reader
+                .info()
+                .first_sector_index()
+                .offset(sector_offset),
+            piece_receiver,
+            cancelled,
+            self.reader.farmer_protocol_info(),
+            &mut sector_output,
+            io::sink(),
+        )
+        .await?;
+
+        self.sector_metadata_file.write_all_at(
+            &SectorMetadataRecord {
+                sector_metadata: plotted_sector.sector_metadata,
+                plotted_at_slot: None,
+                rotation: plotted_sector.rotation,
+                checksum: sector_output.checksum(),
+            }
+            .encode(),
+            sector_metadata_record_offset(sector_offset),
+        )?;
+
+        Ok(plotted_sector)
+    }
+}
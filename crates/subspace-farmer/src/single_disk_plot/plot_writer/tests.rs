@@ -0,0 +1,139 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::idle_verification::sector_checksum;
+use crate::single_disk_plot::plot_writer::{PlotWriter, PlotWriterError};
+use crate::single_disk_plot::read_only::tests::TestPlot;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::sector_metadata::read_sector_metadata_record;
+use crate::single_disk_plot::{SingleDiskPlot, SingleDiskPlotError};
+use crate::testing::fixtures::{farmer_protocol_info, DerivedPieceReceiver};
+use futures::executor::block_on;
+use std::fs;
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg::KzgParametersId;
+use subspace_core_primitives::{plot_sector_size, PublicKey, SectorIndex, PIECE_SIZE};
+use tempfile::TempDir;
+
+#[test]
+fn plot_and_replot_sectors() {
+    let directory = TempDir::new().unwrap();
+    let (mut test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    let sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l) as usize;
+    let plot_path = directory.path().join(SingleDiskPlot::PLOT_FILE);
+
+    // Only the first sector is plotted, the second one is left for the writer
+    test_plot.sector_count = 1;
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    drop(metadata_file);
+    fs::File::options()
+        .write(true)
+        .open(&plot_path)
+        .unwrap()
+        .write_all_at(&vec![0; sector_size * 2], 0)
+        .unwrap();
+
+    let mut plot_writer = PlotWriter::open(directory.path()).unwrap();
+    let cancelled = AtomicBool::new(false);
+
+    assert!(matches!(
+        block_on(plot_writer.replot_sector(1, &DerivedPieceReceiver, &cancelled)),
+        Err(PlotWriterError::SectorNotPlotted {
+            sector_offset: 1,
+            sector_count: 1
+        })
+    ));
+
+    let replotted_sector =
+        block_on(plot_writer.replot_sector(0, &DerivedPieceReceiver, &cancelled)).unwrap();
+    assert_eq!(replotted_sector.sector_index, test_plot.first_sector_index);
+    assert_eq!(plot_writer.reader().sector_count(), 1);
+
+    let plotted_sector =
+        block_on(plot_writer.plot_sector(&DerivedPieceReceiver, &cancelled)).unwrap();
+    assert_eq!(
+        plotted_sector.sector_index,
+        test_plot.first_sector_index.offset(1)
+    );
+    assert_eq!(plot_writer.reader().sector_count(), 2);
+
+    assert!(matches!(
+        block_on(plot_writer.plot_sector(&DerivedPieceReceiver, &cancelled)),
+        Err(PlotWriterError::PlotIsFull { sector_count: 2 })
+    ));
+    drop(plot_writer);
+
+    assert_eq!(fs::read(&plot_path).unwrap(), test_plot.plot);
+
+    let sector_metadata_file =
+        fs::read(directory.path().join(SingleDiskPlot::SECTOR_METADATA_FILE)).unwrap();
+    for sector_offset in 0..2 {
+        let record = read_sector_metadata_record(&sector_metadata_file, sector_offset).unwrap();
+        assert_eq!(
+            record.checksum,
+            Some(sector_checksum(
+                &test_plot.plot[sector_offset as usize * sector_size..][..sector_size]
+            ))
+        );
+    }
+
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+    assert_eq!(read_only_plot.sector_count(), 2);
+    let record = read_only_plot
+        .read_record(test_plot.first_sector_index.offset(1), 0)
+        .unwrap();
+    assert_eq!(
+        record.encoded_piece.as_ref(),
+        &test_plot.plot[sector_size..][..PIECE_SIZE]
+    );
+}
+
+#[test]
+fn create_and_plot() {
+    let directory = TempDir::new().unwrap();
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let first_sector_index = SectorIndex::new(10);
+    let farmer_protocol_info = farmer_protocol_info();
+    let cancelled = AtomicBool::new(false);
+
+    let mut plot_writer = PlotWriter::create(
+        directory.path(),
+        public_key,
+        first_sector_index,
+        2,
+        farmer_protocol_info,
+        KzgParametersId::TEST,
+    )
+    .unwrap();
+    assert_eq!(plot_writer.reader().sector_count(), 0);
+    assert_eq!(plot_writer.reader().target_sector_count(), 2);
+
+    for _ in 0..2 {
+        block_on(plot_writer.plot_sector(&DerivedPieceReceiver, &cancelled)).unwrap();
+    }
+    drop(plot_writer);
+
+    // Existing plot is never overwritten
+    assert!(matches!(
+        PlotWriter::create(
+            directory.path(),
+            public_key,
+            first_sector_index,
+            2,
+            farmer_protocol_info,
+            KzgParametersId::TEST,
+        ),
+        Err(SingleDiskPlotError::PlotAlreadyExists { .. })
+    ));
+
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+    assert_eq!(read_only_plot.sector_count(), 2);
+    assert_eq!(read_only_plot.info().public_key(), &public_key);
+    assert_eq!(
+        read_only_plot.info().first_sector_index(),
+        first_sector_index
+    );
+    assert_eq!(read_only_plot.kzg_parameters_id(), KzgParametersId::TEST);
+    assert_eq!(
+        read_only_plot.farmer_protocol_info().genesis_hash,
+        farmer_protocol_info.genesis_hash
+    );
+}
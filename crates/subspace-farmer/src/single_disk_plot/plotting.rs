@@ -1,17 +1,27 @@
+#[cfg(test)]
+mod tests;
+
 use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use crate::single_disk_plot::plotting_progress::{PlotProgress, ProgressStore};
+use crate::single_disk_plot::sector_metadata::{SectorMetadataRecord, SectorMetadataRecordError};
 use crate::single_disk_plot::{PlottingError, SectorMetadata};
 use bitvec::order::Lsb0;
 use bitvec::prelude::*;
+use bumpalo::Bump;
 use parity_scale_codec::Encode;
+use std::collections::VecDeque;
 use std::io;
+use std::num::NonZeroU64;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use subspace_core_primitives::{
-    plot_sector_size, PieceIndex, PublicKey, SectorId, SectorIndex, PIECE_SIZE,
+    plot_sector_size, Piece, PieceIndex, PublicKey, SectorId, SectorIndex, SegmentIndex,
+    SlotNumber, PIECE_SIZE,
 };
 use subspace_rpc_primitives::FarmerProtocolInfo;
 use subspace_solving::derive_chunk_otp;
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, warn};
 
 /// Information about sector that was plotted
 pub struct PlottedSector {
@@ -21,8 +31,35 @@ pub struct PlottedSector {
     pub sector_index: SectorIndex,
     /// Sector metadata
     pub sector_metadata: SectorMetadata,
+    /// Rotation of the global salt sector was encoded with
+    pub rotation: u64,
     /// Indexes of pieces that were plotted
     pub piece_indexes: Vec<PieceIndex>,
+    /// Breakdown of time spent plotting the sector, `None` for sectors that were plotted before
+    /// and read back from the plot
+    pub stats: Option<PlottedSectorStats>,
+}
+
+/// Breakdown of time spent plotting a sector
+#[derive(Debug, Default, Clone)]
+pub struct PlottedSectorStats {
+    /// Total time spent retrieving pieces
+    pub piece_retrieval: Duration,
+    /// Total time spent encoding pieces
+    pub encoding: Duration,
+    /// Total time spent writing sector and sector metadata
+    pub writing: Duration,
+    /// Time spent retrieving every piece, in the same order as
+    /// [`PlottedSector::piece_indexes`], pieces that were plotted before resumed plotting started
+    /// are not included
+    pub piece_retrieval_times: Vec<Duration>,
+}
+
+impl PlottedSectorStats {
+    /// Total time spent plotting the sector
+    pub fn total(&self) -> Duration {
+        self.piece_retrieval + self.encoding + self.writing
+    }
 }
 
 /// Plotting status
@@ -36,35 +73,420 @@ pub enum PlotSectorError {
     Plotting(#[from] PlottingError),
 }
 
-/// Plot a single sector, where `sector` and `sector_metadata` must be positioned correctly (seek to
-/// desired offset before calling this function if necessary)
+/// When encoding of a sector starts relative to retrieval of its pieces
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum PlottingStart {
+    /// Pieces are retrieved one at a time while sector is encoded and written
+    #[default]
+    Lazy,
+    /// All pieces of the sector are retrieved before anything is written, if any piece is
+    /// unavailable (after retries of the piece receiver) plotting fails without writing anything.
+    ///
+    /// The whole sector is held in memory while pieces are retrieved.
+    GatherAll,
+    /// At least this many pieces at the beginning of the sector are retrieved before anything is
+    /// written, the rest are retrieved lazily, if any of them is unavailable plotting fails without
+    /// writing anything
+    Quorum(NonZeroU64),
+}
+
+/// Destination sector is written to during plotting.
+///
+/// Sector is written in chunks at offsets within the sector, after which plotting either commits
+/// the sector or aborts it if plotting failed or was cancelled partway. Aborted destination can be
+/// used to plot the same sector again.
+pub trait SectorDestination {
+    /// Write `bytes` at `offset` within the sector, chunk must be fully written by the time this
+    /// method returns successfully
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()>;
+
+    /// Sector was fully written
+    fn commit(&mut self) -> io::Result<()>;
+
+    /// Sector was only partially written and must not be used.
+    ///
+    /// Resumable plotting reuses chunks covered by saved progress, so destinations used with
+    /// [`plot_sector_resumable()`] must keep written chunks in place.
+    fn abort(&mut self) -> io::Result<()>;
+}
+
+impl<D> SectorDestination for &mut D
+where
+    D: SectorDestination + ?Sized,
+{
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        (**self).write_sector_chunk(offset, bytes)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        (**self).commit()
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        (**self).abort()
+    }
+}
+
+impl<D> SectorDestination for Box<D>
+where
+    D: SectorDestination + ?Sized,
+{
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        (**self).write_sector_chunk(offset, bytes)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        (**self).commit()
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        (**self).abort()
+    }
+}
+
+/// Buffer of exactly one sector (for instance memory mapped sector of a plot), partially written
+/// contents are left in place on abort
+impl SectorDestination for [u8] {
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.get_mut(offset..)?.get_mut(..bytes.len()))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    format!(
+                        "Chunk of {} bytes at offset {offset} doesn't fit into sector of {} bytes",
+                        bytes.len(),
+                        self.len()
+                    ),
+                )
+            })?
+            .copy_from_slice(bytes);
+
+        Ok(())
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sector appended to the end of a vector, vector is truncated back to its original length on
+/// abort
+#[derive(Debug)]
+pub struct SectorBuffer<'a> {
+    buffer: &'a mut Vec<u8>,
+    sector_start: usize,
+}
+
+impl<'a> SectorBuffer<'a> {
+    /// Create destination that appends sector to `buffer`
+    pub fn new(buffer: &'a mut Vec<u8>) -> Self {
+        let sector_start = buffer.len();
+
+        Self {
+            buffer,
+            sector_start,
+        }
+    }
+}
+
+impl SectorDestination for SectorBuffer<'_> {
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        let start = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| offset.checked_add(self.sector_start))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Offset is too large"))?;
+        let end = start + bytes.len();
+        if self.buffer.len() < end {
+            self.buffer.resize(end, 0);
+        }
+        self.buffer[start..end].copy_from_slice(bytes);
+
+        Ok(())
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        self.buffer.truncate(self.sector_start);
+        Ok(())
+    }
+}
+
+/// Adapter for [`io::Write`] destinations (like [`io::sink()`]) that can only be written
+/// sequentially, chunks written out of order result in error, nothing is done on abort
+#[derive(Debug)]
+pub struct SequentialSectorDestination<W> {
+    inner: W,
+    position: u64,
+}
+
+impl<W> SequentialSectorDestination<W> {
+    /// Create new adapter that starts at the beginning of the sector
+    pub fn new(inner: W) -> Self {
+        Self { inner, position: 0 }
+    }
+}
+
+impl<W> SectorDestination for SequentialSectorDestination<W>
+where
+    W: io::Write,
+{
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        if offset != self.position {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "Chunk at offset {offset} written out of order, expected offset {}",
+                    self.position
+                ),
+            ));
+        }
+
+        self.inner.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+
+        Ok(())
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        self.position = 0;
+        self.inner.flush()
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        self.position = 0;
+        Ok(())
+    }
+}
+
+/// Plot a single sector into `sector_output`, `sector_metadata_output` must be positioned correctly
+/// (seek to desired offset before calling this function if necessary).
+///
+/// Sector output is committed once all pieces are written and aborted if plotting fails or is
+/// cancelled partway, in which case sector can be plotted into the same output again.
+///
+/// Sector contents are fully determined by public key, sector index and pieces returned by
+/// `piece_receiver`, there is no other source of entropy, so plotting is reproducible.
+///
+/// Plotting doesn't spawn tasks or use timers, so it runs under any executor, including
+/// `futures::executor::block_on()`. Only `piece_receiver` may depend on a specific runtime, piece
+/// receivers that wait for deadlines take a [`Clock`](crate::clock::Clock) for this reason.
 ///
 /// NOTE: Even though this function is async, it has blocking code inside and must be running in a
 /// separate thread in order to prevent blocking an executor.
 pub async fn plot_sector<PR, S, SM>(
     public_key: &PublicKey,
-    sector_index: u64,
+    sector_index: SectorIndex,
+    piece_receiver: &PR,
+    cancelled: &AtomicBool,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    sector_output: S,
+    sector_metadata_output: SM,
+) -> Result<PlottedSector, PlotSectorError>
+where
+    PR: PieceReceiver,
+    S: SectorDestination,
+    SM: io::Write,
+{
+    plot_sector_internal(
+        public_key,
+        sector_index,
+        piece_receiver,
+        cancelled,
+        farmer_protocol_info,
+        sector_output,
+        sector_metadata_output,
+        PlottingStart::Lazy,
+        None,
+        None,
+    )
+    .await
+}
+
+/// Same as [`plot_sector()`], but pieces are retrieved according to `plotting_start` policy, so
+/// that unavailable pieces are discovered before anything is written.
+///
+/// NOTE: Even though this function is async, it has blocking code inside and must be running in a
+/// separate thread in order to prevent blocking an executor.
+#[allow(clippy::too_many_arguments)]
+pub async fn plot_sector_with_start<PR, S, SM>(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    piece_receiver: &PR,
+    cancelled: &AtomicBool,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    sector_output: S,
+    sector_metadata_output: SM,
+    plotting_start: PlottingStart,
+) -> Result<PlottedSector, PlotSectorError>
+where
+    PR: PieceReceiver,
+    S: SectorDestination,
+    SM: io::Write,
+{
+    plot_sector_internal(
+        public_key,
+        sector_index,
+        piece_receiver,
+        cancelled,
+        farmer_protocol_info,
+        sector_output,
+        sector_metadata_output,
+        plotting_start,
+        None,
+        None,
+    )
+    .await
+}
+
+/// Same as [`plot_sector()`], but transient allocations are made in `arena` instead of global
+/// allocator.
+///
+/// Allocations are not freed until arena is reset, so each plotting thread is expected to have its
+/// own arena and reset it after every sector.
+#[allow(clippy::too_many_arguments)]
+pub async fn plot_sector_with_arena<PR, S, SM>(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    piece_receiver: &PR,
+    cancelled: &AtomicBool,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    sector_output: S,
+    sector_metadata_output: SM,
+    arena: &Bump,
+) -> Result<PlottedSector, PlotSectorError>
+where
+    PR: PieceReceiver,
+    S: SectorDestination,
+    SM: io::Write,
+{
+    plot_sector_internal(
+        public_key,
+        sector_index,
+        piece_receiver,
+        cancelled,
+        farmer_protocol_info,
+        sector_output,
+        sector_metadata_output,
+        PlottingStart::Lazy,
+        Some(arena),
+        None,
+    )
+    .await
+}
+
+/// Progress of resumable plotting, see [`plot_sector_resumable()`]
+struct Resumable<'a> {
+    progress_store: &'a dyn ProgressStore,
+    /// Pieces at the beginning of the sector that were plotted before
+    resumed_pieces: u64,
+    /// Progress is saved after every this many pieces
+    save_interval: NonZeroU64,
+}
+
+/// Same as [`plot_sector()`], but progress is saved to `progress_store` after every
+/// `progress_save_interval` pieces, and if progress of the same sector was saved before (for
+/// instance by plotting that was interrupted by restart) plotting continues after the last saved
+/// piece instead of starting from scratch. Progress is marked complete once sector is plotted.
+///
+/// Pieces that were plotted before are not written to `sector_output` again, so output must keep
+/// written chunks on abort, see [`SectorDestination::abort()`]. Progress is saved after pieces
+/// were written, so it never covers pieces that were not written yet. Saved progress is ignored if
+/// sector would be encoded differently now, which is the case when rotation or total pieces are
+/// different.
+///
+/// Transient allocations are made in `arena` if provided, see [`plot_sector_with_arena()`].
+///
+/// NOTE: Even though this function is async, it has blocking code inside and must be running in a
+/// separate thread in order to prevent blocking an executor.
+#[allow(clippy::too_many_arguments)]
+pub async fn plot_sector_resumable<PR, S, SM>(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+    piece_receiver: &PR,
+    cancelled: &AtomicBool,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    sector_output: S,
+    sector_metadata_output: SM,
+    progress_store: &dyn ProgressStore,
+    progress_save_interval: NonZeroU64,
+    arena: Option<&Bump>,
+) -> Result<PlottedSector, PlotSectorError>
+where
+    PR: PieceReceiver,
+    S: SectorDestination,
+    SM: io::Write,
+{
+    let pieces_in_sector = plot_sector_size(farmer_protocol_info.space_l) / PIECE_SIZE as u64;
+    let maybe_progress =
+        progress_store
+            .load(sector_index)
+            .map_err(|error| PlottingError::ProgressStore {
+                sector_index,
+                error,
+            })?;
+    let resumed_pieces = match maybe_progress {
+        Some(progress)
+            if progress.total_pieces == farmer_protocol_info.total_pieces
+                && progress.rotation == farmer_protocol_info.rotation =>
+        {
+            progress.plotted_pieces.min(pieces_in_sector)
+        }
+        _ => 0,
+    };
+
+    if resumed_pieces > 0 {
+        debug!(%sector_index, %resumed_pieces, "Resuming plotting of sector");
+    }
+
+    plot_sector_internal(
+        public_key,
+        sector_index,
+        piece_receiver,
+        cancelled,
+        farmer_protocol_info,
+        sector_output,
+        sector_metadata_output,
+        PlottingStart::Lazy,
+        arena,
+        Some(Resumable {
+            progress_store,
+            resumed_pieces,
+            save_interval: progress_save_interval,
+        }),
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn plot_sector_internal<PR, S, SM>(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
     piece_receiver: &PR,
     cancelled: &AtomicBool,
     farmer_protocol_info: &FarmerProtocolInfo,
     mut sector_output: S,
     mut sector_metadata_output: SM,
+    plotting_start: PlottingStart,
+    arena: Option<&Bump>,
+    resumable: Option<Resumable<'_>>,
 ) -> Result<PlottedSector, PlotSectorError>
 where
     PR: PieceReceiver,
-    S: io::Write,
+    S: SectorDestination,
     SM: io::Write,
 {
-    let sector_id = SectorId::new(public_key, sector_index);
+    let sector_id = SectorId::new_rotated(public_key, sector_index, farmer_protocol_info.rotation);
     let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
-    // TODO: Consider adding number of pieces in a sector to protocol info
-    //  explicitly and, ideally, we need to remove 2x replication
-    //  expectation from other places too
-    let current_segment_index = farmer_protocol_info.total_pieces.get()
-        / u64::from(farmer_protocol_info.recorded_history_segment_size)
-        / u64::from(farmer_protocol_info.record_size.get())
-        * 2;
-    let expires_at = current_segment_index + farmer_protocol_info.sector_expiration;
+    let expires_at = sector_expires_at(farmer_protocol_info.total_pieces, farmer_protocol_info);
 
     let piece_indexes: Vec<PieceIndex> = (0u64..)
         .take(plot_sector_size as usize / PIECE_SIZE)
@@ -76,46 +498,134 @@ where
         })
         .collect();
 
-    for piece_index in piece_indexes.iter().copied() {
-        if cancelled.load(Ordering::Acquire) {
+    // Young network may have fewer pieces than a sector holds, sector is still valid and
+    // auditable, it just contains the same pieces many times
+    if farmer_protocol_info.total_pieces.get() < piece_indexes.len() as u64 {
+        debug!(
+            %sector_index,
+            total_pieces = %farmer_protocol_info.total_pieces,
+            pieces_in_sector = %piece_indexes.len(),
+            "History is smaller than a sector, pieces will be repeated within sector"
+        );
+    }
+
+    // With arena pieces are encoded in a single buffer allocated once per sector and received
+    // pieces are released right away
+    let mut arena_piece = arena.map(|arena| arena.alloc_slice_fill_copy(PIECE_SIZE, 0u8));
+
+    let mut stats = PlottedSectorStats {
+        piece_retrieval_times: Vec::with_capacity(piece_indexes.len()),
+        ..PlottedSectorStats::default()
+    };
+
+    let progress_store_error = |error| PlottingError::ProgressStore {
+        sector_index,
+        error,
+    };
+    let resumed_pieces = resumable
+        .as_ref()
+        .map(|resumable| resumable.resumed_pieces)
+        .unwrap_or_default();
+
+    let pieces_to_plot = (0u64..)
+        .zip(piece_indexes.iter().copied())
+        .skip(resumed_pieces as usize);
+    let gathered_pieces_count = match plotting_start {
+        PlottingStart::Lazy => 0,
+        PlottingStart::GatherAll => piece_indexes.len(),
+        PlottingStart::Quorum(quorum) => usize::try_from(quorum.get()).unwrap_or(usize::MAX),
+    }
+    .min(piece_indexes.len().saturating_sub(resumed_pieces as usize));
+
+    let plotting_result = async {
+        // Pieces gathered up front are used in order before the rest are retrieved lazily
+        let mut gathered_pieces = VecDeque::with_capacity(gathered_pieces_count);
+        for (_piece_offset, piece_index) in pieces_to_plot.clone().take(gathered_pieces_count) {
+            if cancelled.load(Ordering::Acquire) {
+                debug!(
+                    %sector_index,
+                    "Plotting was cancelled, interrupting gathering of pieces"
+                );
+                return Err(PlotSectorError::Cancelled);
+            }
+
+            let piece = retrieve_piece(piece_receiver, piece_index, &mut stats).await?;
+            gathered_pieces.push_back(piece);
+        }
+        if gathered_pieces_count > 0 {
             debug!(
                 %sector_index,
-                "Plotting was cancelled, interrupting plotting"
+                %gathered_pieces_count,
+                "Pieces gathered, starting encoding"
             );
-            return Err(PlotSectorError::Cancelled);
         }
 
-        let mut piece = piece_receiver
-            .get_piece(piece_index)
-            .await
-            .map_err(|error| PlottingError::FailedToRetrievePiece { piece_index, error })?
-            .ok_or(PlottingError::PieceNotFound { piece_index })?;
-
-        // TODO: We are skipping witness part of the piece or else it is not
-        //  decodable
-        // TODO: Last bits may not be encoded if record size is not multiple
-        //  of `space_l`
-        // Encode piece
-        let (record, witness_bytes) =
-            piece.split_at_mut(farmer_protocol_info.record_size.get() as usize);
-        // TODO: Extract encoding into separate function reusable in
-        //  farmer and otherwise
-        record
-            .view_bits_mut::<Lsb0>()
-            .chunks_mut(farmer_protocol_info.space_l.get() as usize)
-            .enumerate()
-            .for_each(|(chunk_index, bits)| {
-                // Derive one-time pad
-                let mut otp = derive_chunk_otp(&sector_id, witness_bytes, chunk_index as u32);
-                // XOR chunk bit by bit with one-time pad
-                bits.iter_mut()
-                    .zip(otp.view_bits_mut::<Lsb0>().iter())
-                    .for_each(|(mut a, b)| {
-                        *a ^= *b;
-                    });
-            });
-
-        sector_output.write_all(&piece).map_err(PlottingError::Io)?;
+        for (piece_offset, piece_index) in pieces_to_plot {
+            if cancelled.load(Ordering::Acquire) {
+                debug!(
+                    %sector_index,
+                    "Plotting was cancelled, interrupting plotting"
+                );
+                return Err(PlotSectorError::Cancelled);
+            }
+
+            let mut received_piece = match gathered_pieces.pop_front() {
+                Some(piece) => piece,
+                None => retrieve_piece(piece_receiver, piece_index, &mut stats).await?,
+            };
+
+            let encoding_start = Instant::now();
+            let piece: &mut [u8] = match arena_piece.as_deref_mut() {
+                Some(arena_piece) => {
+                    arena_piece.copy_from_slice(&received_piece);
+                    drop(received_piece);
+                    arena_piece
+                }
+                None => &mut received_piece,
+            };
+
+            encode_piece(&sector_id, piece, farmer_protocol_info);
+            stats.encoding += encoding_start.elapsed();
+
+            let writing_start = Instant::now();
+            sector_output
+                .write_sector_chunk(piece_offset * PIECE_SIZE as u64, piece)
+                .map_err(PlottingError::Io)?;
+            let plotted_pieces = piece_offset + 1;
+            if let Some(resumable) = &resumable {
+                if plotted_pieces % resumable.save_interval.get() == 0
+                    && plotted_pieces < piece_indexes.len() as u64
+                {
+                    resumable
+                        .progress_store
+                        .save(
+                            sector_index,
+                            PlotProgress {
+                                plotted_pieces,
+                                total_pieces: farmer_protocol_info.total_pieces,
+                                rotation: farmer_protocol_info.rotation,
+                            },
+                        )
+                        .map_err(progress_store_error)?;
+                }
+            }
+            stats.writing += writing_start.elapsed();
+        }
+
+        let writing_start = Instant::now();
+        sector_output.commit().map_err(PlottingError::Io)?;
+        stats.writing += writing_start.elapsed();
+
+        Ok::<_, PlotSectorError>(())
+    }
+    .await;
+
+    if let Err(error) = plotting_result {
+        // Partially written sector must not be used, but error that caused it is more important
+        if let Err(abort_error) = sector_output.abort() {
+            warn!(%sector_index, %abort_error, "Failed to abort sector output");
+        }
+        return Err(error);
     }
 
     let sector_metadata = SectorMetadata {
@@ -123,14 +633,138 @@ where
         expires_at,
     };
 
-    sector_metadata_output
-        .write_all(&sector_metadata.encode())
-        .map_err(PlottingError::Io)?;
+    let writing_start = Instant::now();
+    match arena {
+        Some(arena) => {
+            let encoded_sector_metadata =
+                arena.alloc_slice_fill_copy(SectorMetadata::encoded_size(), 0u8);
+            sector_metadata.encode_to(&mut &mut *encoded_sector_metadata);
+            sector_metadata_output.write_all(encoded_sector_metadata)
+        }
+        None => sector_metadata_output.write_all(&sector_metadata.encode()),
+    }
+    .map_err(PlottingError::Io)?;
+    stats.writing += writing_start.elapsed();
+
+    if let Some(resumable) = &resumable {
+        resumable
+            .progress_store
+            .mark_complete(sector_index)
+            .map_err(progress_store_error)?;
+    }
 
     Ok(PlottedSector {
         sector_id,
         sector_index,
         sector_metadata,
+        rotation: farmer_protocol_info.rotation,
         piece_indexes,
+        stats: Some(stats),
     })
 }
+
+/// Retrieve piece for plotting, accounting time spent in `stats`
+async fn retrieve_piece<PR>(
+    piece_receiver: &PR,
+    piece_index: PieceIndex,
+    stats: &mut PlottedSectorStats,
+) -> Result<Piece, PlottingError>
+where
+    PR: PieceReceiver,
+{
+    let piece_retrieval_start = Instant::now();
+    let piece = piece_receiver
+        .get_piece(piece_index)
+        .await
+        .map_err(|error| PlottingError::FailedToRetrievePiece { piece_index, error })?
+        .ok_or(PlottingError::PieceNotFound { piece_index })?;
+    let piece_retrieval_time = piece_retrieval_start.elapsed();
+    stats.piece_retrieval += piece_retrieval_time;
+    stats.piece_retrieval_times.push(piece_retrieval_time);
+
+    Ok(piece)
+}
+
+/// Replot sector with `sector_metadata_record` in place, binding it to `new_slot` and expiration
+/// according to `farmer_protocol_info` without retrieving any pieces.
+///
+/// Encoding of the sector only depends on sector ID and contents of pieces, which in turn only
+/// depend on public key, sector index, rotation and total pieces stored in the record. Neither
+/// slot sector was plotted at nor its expiration are part of the encoding, so sector contents stay
+/// valid as is and only the record needs to be rewritten, which this function does.
+///
+/// Returns `Ok(None)` and leaves the record untouched if sector must be fully replotted with
+/// [`plot_sector()`] instead, which is the case when rotation of the sector differs from the one
+/// in `farmer_protocol_info`.
+pub fn replot_sector_in_place(
+    sector_metadata_record: &mut [u8],
+    new_slot: SlotNumber,
+    farmer_protocol_info: &FarmerProtocolInfo,
+) -> Result<Option<SectorMetadataRecord>, SectorMetadataRecordError> {
+    let record =
+        SectorMetadataRecord::decode(sector_metadata_record, SectorMetadataRecord::LATEST_VERSION)?;
+
+    if record.rotation != farmer_protocol_info.rotation {
+        return Ok(None);
+    }
+
+    let total_pieces = record.sector_metadata.total_pieces;
+    let record = SectorMetadataRecord {
+        sector_metadata: SectorMetadata {
+            total_pieces,
+            expires_at: sector_expires_at(total_pieces, farmer_protocol_info),
+        },
+        plotted_at_slot: Some(new_slot),
+        rotation: record.rotation,
+        // Sector contents are not touched
+        checksum: record.checksum,
+    };
+    sector_metadata_record.copy_from_slice(&record.encode());
+
+    Ok(Some(record))
+}
+
+/// Segment index at which sector with pieces selected out of `total_pieces` expires
+pub(crate) fn sector_expires_at(
+    total_pieces: NonZeroU64,
+    farmer_protocol_info: &FarmerProtocolInfo,
+) -> SegmentIndex {
+    // TODO: Consider adding number of pieces in a sector to protocol info
+    //  explicitly and, ideally, we need to remove 2x replication
+    //  expectation from other places too
+    let current_segment_index = SegmentIndex::new(
+        total_pieces.get()
+            / u64::from(farmer_protocol_info.recorded_history_segment_size)
+            / u64::from(farmer_protocol_info.record_size.get())
+            * 2,
+    );
+    current_segment_index + farmer_protocol_info.sector_expiration
+}
+
+/// Encode piece in place for storing in sector with `sector_id`
+pub(crate) fn encode_piece(
+    sector_id: &SectorId,
+    piece: &mut [u8],
+    farmer_protocol_info: &FarmerProtocolInfo,
+) {
+    // TODO: We are skipping witness part of the piece or else it is not
+    //  decodable
+    // TODO: Last bits may not be encoded if record size is not multiple
+    //  of `space_l`
+    let (record, witness_bytes) =
+        piece.split_at_mut(farmer_protocol_info.record_size.get() as usize);
+    record
+        .view_bits_mut::<Lsb0>()
+        .chunks_mut(farmer_protocol_info.space_l.get() as usize)
+        .enumerate()
+        .for_each(|(chunk_index, bits)| {
+            // Derive one-time pad
+            let mut otp = derive_chunk_otp(sector_id, witness_bytes, chunk_index as u32);
+            // XOR chunk bit by bit with one-time pad
+            bits.iter_mut()
+                .zip(otp.view_bits_mut::<Lsb0>().iter())
+                .for_each(|(mut a, b)| {
+                    *a ^= *b;
+                });
+        });
+}
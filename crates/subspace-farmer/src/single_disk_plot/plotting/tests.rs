@@ -0,0 +1,654 @@
+use crate::single_disk_plot::farming::{audit_sector, read_winning_piece};
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use crate::single_disk_plot::plotting::{
+    plot_sector, plot_sector_resumable, plot_sector_with_arena, plot_sector_with_start,
+    replot_sector_in_place, PlotSectorError, PlottingStart, SectorBuffer, SectorDestination,
+    SequentialSectorDestination,
+};
+use crate::single_disk_plot::plotting_progress::{PlotProgress, ProgressStore};
+use crate::single_disk_plot::sector_metadata::{SectorMetadataRecord, SECTOR_CHECKSUM_SIZE};
+use crate::single_disk_plot::{PlottingError, SectorMetadata};
+use crate::testing::fixtures::{farmer_protocol_info, piece, DerivedPieceReceiver};
+use async_trait::async_trait;
+use bumpalo::Bump;
+use futures::channel::oneshot;
+use futures::executor::{block_on, LocalPool};
+use parity_scale_codec::Decode;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Cursor;
+use std::num::{NonZeroU16, NonZeroU64};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::{io, thread};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Piece, PieceIndex, PublicKey, SectorId, SectorIndex, SegmentIndex,
+    SlotNumber, SolutionRange, PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+/// Returns the same pieces as [`DerivedPieceReceiver`], but from another thread, so plotting has to
+/// be woken up by the executor it runs under
+struct ThreadPieceReceiver;
+
+#[async_trait]
+impl PieceReceiver for ThreadPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let (piece_sender, piece_receiver) = oneshot::channel();
+        thread::spawn(move || {
+            let _ = piece_sender.send(piece(piece_index));
+        });
+
+        Ok(Some(piece_receiver.await?))
+    }
+}
+
+/// Returns the same pieces as [`DerivedPieceReceiver`], counts retrieved pieces and cancels plotting
+/// after `cancel_after` pieces were retrieved
+struct CancellingPieceReceiver<'a> {
+    retrieved: AtomicU64,
+    cancel_after: u64,
+    cancelled: &'a AtomicBool,
+}
+
+#[async_trait]
+impl PieceReceiver for CancellingPieceReceiver<'_> {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        if self.retrieved.fetch_add(1, Ordering::SeqCst) + 1 == self.cancel_after {
+            self.cancelled.store(true, Ordering::Release);
+        }
+
+        Ok(Some(piece(piece_index)))
+    }
+}
+
+/// Fails to retrieve piece after `fail_after` pieces were retrieved
+struct FailingPieceReceiver {
+    retrieved: AtomicU64,
+    fail_after: u64,
+}
+
+#[async_trait]
+impl PieceReceiver for FailingPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        if self.retrieved.fetch_add(1, Ordering::SeqCst) == self.fail_after {
+            return Err("Piece retrieval failed".into());
+        }
+
+        Ok(Some(piece(piece_index)))
+    }
+}
+
+/// Doesn't have the piece after `missing_after` pieces were retrieved
+struct MissingPieceReceiver {
+    retrieved: AtomicU64,
+    missing_after: u64,
+}
+
+#[async_trait]
+impl PieceReceiver for MissingPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        if self.retrieved.fetch_add(1, Ordering::SeqCst) == self.missing_after {
+            return Ok(None);
+        }
+
+        Ok(Some(piece(piece_index)))
+    }
+}
+
+/// Counts bytes written to sector and whether it was committed or aborted
+#[derive(Default)]
+struct CountingSectorDestination {
+    written: u64,
+    committed: bool,
+    aborted: bool,
+}
+
+impl SectorDestination for CountingSectorDestination {
+    fn write_sector_chunk(&mut self, _offset: u64, bytes: &[u8]) -> io::Result<()> {
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        self.committed = true;
+        Ok(())
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        self.aborted = true;
+        Ok(())
+    }
+}
+
+/// Progress store that keeps progress in memory
+#[derive(Default)]
+struct InMemoryProgressStore {
+    progress: Mutex<HashMap<SectorIndex, PlotProgress>>,
+}
+
+impl ProgressStore for InMemoryProgressStore {
+    fn save(
+        &self,
+        sector_index: SectorIndex,
+        progress: PlotProgress,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        self.progress.lock().insert(sector_index, progress);
+        Ok(())
+    }
+
+    fn load(
+        &self,
+        sector_index: SectorIndex,
+    ) -> Result<Option<PlotProgress>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(self.progress.lock().get(&sector_index).copied())
+    }
+
+    fn mark_complete(
+        &self,
+        sector_index: SectorIndex,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        self.progress.lock().remove(&sector_index);
+        Ok(())
+    }
+}
+
+fn plot(public_key: &PublicKey, sector_index: SectorIndex) -> (Vec<u8>, Vec<u8>) {
+    let farmer_protocol_info = farmer_protocol_info();
+
+    let mut sector = Vec::with_capacity(plot_sector_size(farmer_protocol_info.space_l) as usize);
+    let mut sector_metadata = Vec::new();
+    block_on(plot_sector(
+        public_key,
+        sector_index,
+        &DerivedPieceReceiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut sector),
+        &mut sector_metadata,
+    ))
+    .unwrap();
+
+    (sector, sector_metadata)
+}
+
+#[test]
+fn plotting_is_deterministic() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+
+    // Sector contents only depend on public key, sector index and pieces, there is no other source
+    // of entropy
+    let (sector, sector_metadata) = plot(&public_key, 0);
+    assert_eq!(
+        sector.len() as u64,
+        plot_sector_size(NonZeroU16::new(16).unwrap())
+    );
+    assert_eq!(plot(&public_key, 0), (sector.clone(), sector_metadata));
+
+    assert_ne!(plot(&public_key, 1).0, sector);
+    assert_ne!(
+        plot(&PublicKey::from(rand::random::<[u8; 32]>()), 0).0,
+        sector
+    );
+}
+
+async fn plot_with_thread_piece_receiver(
+    public_key: &PublicKey,
+    sector_index: SectorIndex,
+) -> (Vec<u8>, Vec<u8>) {
+    let mut sector = Vec::new();
+    let mut sector_metadata = Vec::new();
+    plot_sector(
+        public_key,
+        sector_index,
+        &ThreadPieceReceiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info(),
+        SectorBuffer::new(&mut sector),
+        &mut sector_metadata,
+    )
+    .await
+    .unwrap();
+
+    (sector, sector_metadata)
+}
+
+#[test]
+fn plotting_is_executor_agnostic() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let expected = plot(&public_key, 0);
+
+    assert_eq!(
+        block_on(plot_with_thread_piece_receiver(&public_key, 0)),
+        expected
+    );
+    assert_eq!(
+        LocalPool::new().run_until(plot_with_thread_piece_receiver(&public_key, 0)),
+        expected
+    );
+    assert_eq!(
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(plot_with_thread_piece_receiver(&public_key, 0)),
+        expected
+    );
+    assert_eq!(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap()
+            .block_on(plot_with_thread_piece_receiver(&public_key, 0)),
+        expected
+    );
+}
+
+#[test]
+fn plotting_with_arena() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let farmer_protocol_info = farmer_protocol_info();
+    let mut arena = Bump::new();
+
+    let mut allocated_bytes = None;
+    for sector_index in (0..3).map(SectorIndex::new) {
+        let mut sector = Vec::new();
+        let mut sector_metadata = Vec::new();
+        block_on(plot_sector_with_arena(
+            &public_key,
+            sector_index,
+            &DerivedPieceReceiver,
+            &AtomicBool::new(false),
+            &farmer_protocol_info,
+            SectorBuffer::new(&mut sector),
+            &mut sector_metadata,
+            &arena,
+        ))
+        .unwrap();
+
+        // Arena must only change where transient data lives, not the output
+        assert_eq!((sector, sector_metadata), plot(&public_key, sector_index));
+
+        // Memory of the arena is reused after reset instead of growing with every sector
+        assert!(arena.allocated_bytes() > 0);
+        match allocated_bytes {
+            Some(allocated_bytes) => assert_eq!(arena.allocated_bytes(), allocated_bytes),
+            None => {
+                allocated_bytes.replace(arena.allocated_bytes());
+            }
+        }
+        arena.reset();
+    }
+}
+
+#[test]
+fn plotting_records_stats() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let farmer_protocol_info = farmer_protocol_info();
+
+    let plotted_sector = block_on(plot_sector(
+        &public_key,
+        0,
+        &DerivedPieceReceiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut Vec::new()),
+        &mut Vec::new(),
+    ))
+    .unwrap();
+
+    let stats = plotted_sector.stats.unwrap();
+    // One retrieval time per piece, in the same order as piece indexes
+    assert_eq!(
+        stats.piece_retrieval_times.len(),
+        plotted_sector.piece_indexes.len()
+    );
+    assert_eq!(
+        stats.total(),
+        stats.piece_retrieval + stats.encoding + stats.writing
+    );
+}
+
+#[test]
+fn replotting_in_place() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::ZERO;
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let (sector, sector_metadata) = plot(&public_key, sector_index);
+    let sector_metadata = SectorMetadata::decode(&mut sector_metadata.as_slice()).unwrap();
+    let mut record = SectorMetadataRecord {
+        sector_metadata,
+        plotted_at_slot: Some(SlotNumber::new(1)),
+        rotation: 0,
+        checksum: Some([1; SECTOR_CHECKSUM_SIZE]),
+    }
+    .encode();
+
+    // Sector expiration changed since sector was plotted
+    let farmer_protocol_info = FarmerProtocolInfo {
+        sector_expiration: SegmentIndex::new(5),
+        ..farmer_protocol_info()
+    };
+    let replotted = replot_sector_in_place(&mut record, SlotNumber::new(10), &farmer_protocol_info)
+        .unwrap()
+        .unwrap();
+    assert_eq!(replotted.plotted_at_slot, Some(SlotNumber::new(10)));
+    assert_eq!(replotted.checksum, Some([1; SECTOR_CHECKSUM_SIZE]));
+    assert_eq!(
+        replotted.sector_metadata.expires_at,
+        sector_metadata.expires_at + SegmentIndex::new(4)
+    );
+    assert_eq!(
+        SectorMetadataRecord::decode(&record, SectorMetadataRecord::LATEST_VERSION).unwrap(),
+        replotted
+    );
+
+    // Full replot under new expiration results in the same sector and sector metadata
+    let mut full_sector = Vec::new();
+    let mut full_sector_metadata = Vec::new();
+    block_on(plot_sector(
+        &public_key,
+        sector_index,
+        &DerivedPieceReceiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut full_sector),
+        &mut full_sector_metadata,
+    ))
+    .unwrap();
+    assert_eq!(full_sector, sector);
+    assert_eq!(
+        SectorMetadata::decode(&mut full_sector_metadata.as_slice()).unwrap(),
+        replotted.sector_metadata
+    );
+
+    // Sector that was left as is audits correctly with replotted sector metadata
+    let sector_id = SectorId::new(&public_key, sector_index);
+    let mut audited = 0;
+    for _ in 0..10 {
+        let eligible_sector = match audit_sector(
+            &public_key,
+            sector_index,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &rand::random(),
+            SolutionRange::MAX,
+            Cursor::new(&sector),
+        )
+        .unwrap()
+        {
+            Some(eligible_sector) => eligible_sector,
+            // Last bits of the record that don't form a whole chunk are never eligible
+            None => continue,
+        };
+        audited += 1;
+
+        let (winning_piece, piece_index) = read_winning_piece(
+            &sector_id,
+            &farmer_protocol_info,
+            Cursor::new(&sector),
+            &replotted.sector_metadata,
+            eligible_sector.audit_index,
+        )
+        .unwrap();
+        assert_eq!(winning_piece, piece(piece_index));
+    }
+    assert!(audited > 0);
+
+    // Sector plotted under different rotation must be fully replotted
+    let rotated_farmer_protocol_info = FarmerProtocolInfo {
+        rotation: 1,
+        ..farmer_protocol_info
+    };
+    let before = record;
+    assert!(replot_sector_in_place(
+        &mut record,
+        SlotNumber::new(20),
+        &rotated_farmer_protocol_info
+    )
+    .unwrap()
+    .is_none());
+    assert_eq!(record, before);
+}
+
+#[test]
+fn failed_plotting_aborts_sector_output() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::ZERO;
+    let farmer_protocol_info = farmer_protocol_info();
+    let (expected_sector, _expected_sector_metadata) = plot(&public_key, sector_index);
+    let failing_piece_receiver = || FailingPieceReceiver {
+        retrieved: AtomicU64::new(0),
+        fail_after: 3,
+    };
+
+    // Partially plotted sector is removed from buffer, contents before it are preserved
+    let mut buffer = vec![1, 2, 3];
+    let result = block_on(plot_sector(
+        &public_key,
+        sector_index,
+        &failing_piece_receiver(),
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut buffer),
+        io::sink(),
+    ));
+    assert!(matches!(
+        result,
+        Err(PlotSectorError::Plotting(
+            PlottingError::FailedToRetrievePiece { .. }
+        ))
+    ));
+    assert_eq!(buffer, [1, 2, 3]);
+
+    let cancelled = AtomicBool::new(false);
+    let result = block_on(plot_sector(
+        &public_key,
+        sector_index,
+        &CancellingPieceReceiver {
+            retrieved: AtomicU64::new(0),
+            cancel_after: 2,
+            cancelled: &cancelled,
+        },
+        &cancelled,
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut buffer),
+        io::sink(),
+    ));
+    assert!(matches!(result, Err(PlotSectorError::Cancelled)));
+    assert_eq!(buffer, [1, 2, 3]);
+
+    // The same output is used to plot sector again after failure
+    let mut sector_output = SectorBuffer::new(&mut buffer);
+    assert!(block_on(plot_sector(
+        &public_key,
+        sector_index,
+        &failing_piece_receiver(),
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        &mut sector_output,
+        io::sink(),
+    ))
+    .is_err());
+    block_on(plot_sector(
+        &public_key,
+        sector_index,
+        &DerivedPieceReceiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        &mut sector_output,
+        io::sink(),
+    ))
+    .unwrap();
+    assert_eq!(buffer[..3], [1, 2, 3]);
+    assert_eq!(buffer[3..], expected_sector);
+
+    // Sequential adapter refuses chunks written out of order
+    let mut sequential = SequentialSectorDestination::new(io::sink());
+    sequential.write_sector_chunk(0, &[0; 10]).unwrap();
+    assert!(sequential.write_sector_chunk(20, &[0; 10]).is_err());
+    sequential.abort().unwrap();
+    sequential.write_sector_chunk(0, &[0; 10]).unwrap();
+}
+
+#[test]
+fn plotting_start_gathers_pieces_before_writing() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::ZERO;
+    let farmer_protocol_info = farmer_protocol_info();
+    let pieces_in_sector = plot_sector_size(farmer_protocol_info.space_l) / PIECE_SIZE as u64;
+    let (expected_sector, expected_sector_metadata) = plot(&public_key, sector_index);
+    let plot_with_missing_piece = |plotting_start| {
+        let mut sector_output = CountingSectorDestination::default();
+        let result = block_on(plot_sector_with_start(
+            &public_key,
+            sector_index,
+            &MissingPieceReceiver {
+                retrieved: AtomicU64::new(0),
+                missing_after: pieces_in_sector - 1,
+            },
+            &AtomicBool::new(false),
+            &farmer_protocol_info,
+            &mut sector_output,
+            io::sink(),
+            plotting_start,
+        ));
+        assert!(matches!(
+            result,
+            Err(PlotSectorError::Plotting(
+                PlottingError::PieceNotFound { .. }
+            ))
+        ));
+        assert!(!sector_output.committed);
+        assert!(sector_output.aborted);
+
+        sector_output.written
+    };
+
+    // Last piece is missing, lazy plotting writes everything before it
+    assert_eq!(
+        plot_with_missing_piece(PlottingStart::Lazy),
+        (pieces_in_sector - 1) * PIECE_SIZE as u64
+    );
+    // Nothing is written when missing piece is discovered while gathering
+    assert_eq!(plot_with_missing_piece(PlottingStart::GatherAll), 0);
+    assert_eq!(
+        plot_with_missing_piece(PlottingStart::Quorum(
+            NonZeroU64::new(pieces_in_sector).unwrap()
+        )),
+        0
+    );
+    // Quorum smaller than the sector only protects pieces within it
+    assert_eq!(
+        plot_with_missing_piece(PlottingStart::Quorum(NonZeroU64::new(1).unwrap())),
+        (pieces_in_sector - 1) * PIECE_SIZE as u64
+    );
+
+    // Gathering doesn't change sector contents
+    for plotting_start in [
+        PlottingStart::GatherAll,
+        PlottingStart::Quorum(NonZeroU64::new(2).unwrap()),
+        PlottingStart::Quorum(NonZeroU64::new(u64::MAX).unwrap()),
+    ] {
+        let mut sector = Vec::new();
+        let mut sector_metadata = Vec::new();
+        let plotted_sector = block_on(plot_sector_with_start(
+            &public_key,
+            sector_index,
+            &DerivedPieceReceiver,
+            &AtomicBool::new(false),
+            &farmer_protocol_info,
+            SectorBuffer::new(&mut sector),
+            &mut sector_metadata,
+            plotting_start,
+        ))
+        .unwrap();
+        assert_eq!(sector, expected_sector);
+        assert_eq!(sector_metadata, expected_sector_metadata);
+        assert_eq!(
+            plotted_sector.stats.unwrap().piece_retrieval_times.len() as u64,
+            pieces_in_sector
+        );
+    }
+}
+
+#[test]
+fn plotting_resumes_through_progress_store() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::ZERO;
+    let farmer_protocol_info = farmer_protocol_info();
+    let progress_store = InMemoryProgressStore::default();
+    let progress_save_interval = NonZeroU64::new(2).unwrap();
+    let (expected_sector, expected_sector_metadata) = plot(&public_key, sector_index);
+    let pieces_in_sector = expected_sector.len() / PIECE_SIZE;
+    let mut sector = vec![0u8; expected_sector.len()];
+
+    let mut plot_resumable = |cancel_after| {
+        let cancelled = AtomicBool::new(false);
+        let piece_receiver = CancellingPieceReceiver {
+            retrieved: AtomicU64::new(0),
+            cancel_after,
+            cancelled: &cancelled,
+        };
+        let mut sector_metadata = Vec::new();
+        let result = block_on(plot_sector_resumable(
+            &public_key,
+            sector_index,
+            &piece_receiver,
+            &cancelled,
+            &farmer_protocol_info,
+            sector.as_mut_slice(),
+            &mut sector_metadata,
+            &progress_store,
+            progress_save_interval,
+            None,
+        ));
+
+        (
+            result,
+            piece_receiver.retrieved.load(Ordering::SeqCst),
+            sector_metadata,
+        )
+    };
+
+    // Plotting is interrupted after 3 pieces, progress was last saved after 2 pieces
+    let (result, retrieved, _sector_metadata) = plot_resumable(3);
+    assert!(matches!(result, Err(PlotSectorError::Cancelled)));
+    assert_eq!(retrieved, 3);
+    assert_eq!(
+        progress_store.load(sector_index).unwrap(),
+        Some(PlotProgress {
+            plotted_pieces: 2,
+            total_pieces: farmer_protocol_info.total_pieces,
+            rotation: farmer_protocol_info.rotation,
+        })
+    );
+
+    // Plotting continues after the last saved piece and results in the same sector as plotting
+    // from scratch, progress is removed once sector is plotted
+    let (result, retrieved, sector_metadata) = plot_resumable(0);
+    let plotted_sector = result.unwrap();
+    assert_eq!(retrieved, pieces_in_sector as u64 - 2);
+    assert_eq!(
+        plotted_sector.stats.unwrap().piece_retrieval_times.len(),
+        pieces_in_sector - 2
+    );
+    assert_eq!(sector, expected_sector);
+    assert_eq!(sector_metadata, expected_sector_metadata);
+    assert_eq!(progress_store.load(sector_index).unwrap(), None);
+}
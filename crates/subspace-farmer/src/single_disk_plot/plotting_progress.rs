@@ -0,0 +1,117 @@
+//! Persistence of plotting progress within a sector.
+//!
+//! Sector takes a while to plot and without saved progress sector that was interrupted by restart
+//! is plotted from scratch. Resumable plotting saves progress through [`ProgressStore`] every few
+//! pieces and continues after the last saved piece instead. Where progress is stored is up to the
+//! implementation: [`FilesystemProgressStore`] keeps it next to the plot, cluster deployments may
+//! keep progress of all farmers in a database or remote key-value store.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+use std::{fs, io};
+use subspace_core_primitives::SectorIndex;
+
+/// Progress of plotting of a single sector
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlotProgress {
+    /// Number of pieces at the beginning of the sector that are encoded and written to the plot
+    pub plotted_pieces: u64,
+    /// Total pieces sector pieces were selected from, progress is only valid for the same value
+    pub total_pieces: NonZeroU64,
+    /// Rotation of the global salt pieces were encoded with, progress is only valid for the same
+    /// value
+    pub rotation: u64,
+}
+
+/// Storage of plotting progress of sectors, used by
+/// [`plot_sector_resumable()`](crate::single_disk_plot::plotting::plot_sector_resumable)
+pub trait ProgressStore {
+    /// Save progress of sector `sector_index`, replacing previously saved progress
+    fn save(
+        &self,
+        sector_index: SectorIndex,
+        progress: PlotProgress,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>>;
+
+    /// Load previously saved progress of sector `sector_index`, `None` if there is none
+    fn load(
+        &self,
+        sector_index: SectorIndex,
+    ) -> Result<Option<PlotProgress>, Box<dyn Error + Send + Sync + 'static>>;
+
+    /// Sector `sector_index` is plotted completely, its progress is no longer needed
+    fn mark_complete(
+        &self,
+        sector_index: SectorIndex,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>>;
+}
+
+/// Progress store that keeps progress of every sector in a separate file in a directory
+#[derive(Debug, Clone)]
+pub struct FilesystemProgressStore {
+    directory: PathBuf,
+}
+
+impl FilesystemProgressStore {
+    /// Create progress store in `directory`, directory is created when progress is saved for the
+    /// first time
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    fn progress_path(&self, sector_index: SectorIndex) -> PathBuf {
+        self.directory.join(format!("sector-{sector_index}.json"))
+    }
+}
+
+impl ProgressStore for FilesystemProgressStore {
+    /// Progress is stored atomically, so that interruption never leaves progress file half-written
+    fn save(
+        &self,
+        sector_index: SectorIndex,
+        progress: PlotProgress,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        fs::create_dir_all(&self.directory)?;
+        let path = self.progress_path(sector_index);
+        let temporary_path = path.with_extension("json.tmp");
+        fs::write(
+            &temporary_path,
+            serde_json::to_vec(&progress).expect("Progress serialization never fails; qed"),
+        )?;
+        fs::rename(temporary_path, path)?;
+
+        Ok(())
+    }
+
+    fn load(
+        &self,
+        sector_index: SectorIndex,
+    ) -> Result<Option<PlotProgress>, Box<dyn Error + Send + Sync + 'static>> {
+        let bytes = match fs::read(self.progress_path(sector_index)) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                return if error.kind() == io::ErrorKind::NotFound {
+                    Ok(None)
+                } else {
+                    Err(error.into())
+                };
+            }
+        };
+
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+
+    fn mark_complete(
+        &self,
+        sector_index: SectorIndex,
+    ) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+        match fs::remove_file(self.progress_path(sector_index)) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
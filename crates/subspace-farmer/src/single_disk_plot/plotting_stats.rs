@@ -0,0 +1,120 @@
+//! Aggregated statistics of sector plotting.
+//!
+//! Every plotted sector contributes time spent on piece retrieval, encoding and writing to separate
+//! histograms, so it is possible to tell which of them makes some sectors plot much slower than
+//! others.
+
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::plotting::{PlottedSector, PlottedSectorStats};
+use std::time::Duration;
+use subspace_core_primitives::PieceIndex;
+
+/// Number of histogram buckets, the last bucket covers everything above 2^30 ms (~12 days)
+const BUCKETS: usize = 32;
+
+/// Histogram of durations with exponential buckets, bucket `i` counts durations below `2^i`
+/// milliseconds that didn't fit into previous buckets
+#[derive(Debug, Default, Clone)]
+pub struct DurationHistogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    sum: Duration,
+}
+
+impl DurationHistogram {
+    /// Record a single duration
+    pub fn record(&mut self, duration: Duration) {
+        let millis = duration.as_millis();
+        let bucket = if millis == 0 {
+            0
+        } else {
+            (u128::BITS - millis.leading_zeros()) as usize
+        };
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(duration);
+    }
+
+    /// Number of recorded durations
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Sum of recorded durations
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Non-empty buckets as `(upper_bound, count)`, upper bound is `None` for the last bucket
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_bucket, &count)| count > 0)
+            .map(|(bucket, &count)| {
+                let upper_bound =
+                    (bucket < BUCKETS - 1).then(|| Duration::from_millis(1 << bucket));
+                (upper_bound, count)
+            })
+    }
+
+    /// Upper bound of the bucket `percentile` (0..=100) of recorded durations fall into, `None` if
+    /// nothing was recorded or it is the last bucket
+    pub fn percentile(&self, percentile: u8) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let target = ((self.count * u64::from(percentile.min(100)) + 99) / 100).max(1);
+        let mut seen = 0;
+        self.buckets().find_map(|(upper_bound, count)| {
+            seen += count;
+            (seen >= target).then_some(upper_bound).flatten()
+        })
+    }
+}
+
+/// Statistics of sectors plotted by a single disk plot since it was opened
+#[derive(Debug, Default, Clone)]
+pub struct PlottingStats {
+    /// Time spent retrieving pieces of a sector
+    pub piece_retrieval: DurationHistogram,
+    /// Time spent encoding pieces of a sector
+    pub encoding: DurationHistogram,
+    /// Time spent writing a sector
+    pub writing: DurationHistogram,
+    /// Total time spent plotting a sector
+    pub total: DurationHistogram,
+}
+
+impl PlottingStats {
+    /// Add stats of a plotted sector
+    pub fn add(&mut self, stats: &PlottedSectorStats) {
+        self.piece_retrieval.record(stats.piece_retrieval);
+        self.encoding.record(stats.encoding);
+        self.writing.record(stats.writing);
+        self.total.record(stats.total());
+    }
+}
+
+/// Pieces of plotted sector whose retrieval took longer than `threshold` along with their
+/// retrieval time
+pub fn slow_pieces(
+    plotted_sector: &PlottedSector,
+    threshold: Duration,
+) -> impl Iterator<Item = (PieceIndex, Duration)> + '_ {
+    let piece_retrieval_times = plotted_sector
+        .stats
+        .as_ref()
+        .map(|stats| stats.piece_retrieval_times.as_slice())
+        .unwrap_or_default();
+
+    plotted_sector
+        .piece_indexes
+        .iter()
+        .copied()
+        .zip(piece_retrieval_times.iter().copied())
+        .filter(move |(_piece_index, retrieval_time)| *retrieval_time > threshold)
+}
@@ -0,0 +1,79 @@
+use crate::single_disk_plot::plotting::{PlottedSector, PlottedSectorStats};
+use crate::single_disk_plot::plotting_stats::{slow_pieces, DurationHistogram, PlottingStats};
+use crate::single_disk_plot::SectorMetadata;
+use std::num::NonZeroU64;
+use std::time::Duration;
+use subspace_core_primitives::{PublicKey, SectorId, SectorIndex, SegmentIndex};
+
+#[test]
+fn duration_histogram() {
+    let mut histogram = DurationHistogram::default();
+    assert_eq!(histogram.percentile(50), None);
+
+    for millis in [0, 1, 3, 3, 100] {
+        histogram.record(Duration::from_millis(millis));
+    }
+
+    assert_eq!(histogram.count(), 5);
+    assert_eq!(histogram.sum(), Duration::from_millis(107));
+    assert_eq!(
+        histogram.buckets().collect::<Vec<_>>(),
+        vec![
+            (Some(Duration::from_millis(1)), 1),
+            (Some(Duration::from_millis(2)), 1),
+            (Some(Duration::from_millis(4)), 2),
+            (Some(Duration::from_millis(128)), 1),
+        ]
+    );
+    assert_eq!(histogram.percentile(0), Some(Duration::from_millis(1)));
+    assert_eq!(histogram.percentile(50), Some(Duration::from_millis(4)));
+    assert_eq!(histogram.percentile(100), Some(Duration::from_millis(128)));
+
+    // Very long durations end up in the last bucket without upper bound
+    histogram.record(Duration::from_secs(u64::MAX));
+    assert_eq!(histogram.buckets().last(), Some((None, 1)));
+    assert_eq!(histogram.percentile(100), None);
+}
+
+#[test]
+fn plotting_stats_and_slow_pieces() {
+    let stats = PlottedSectorStats {
+        piece_retrieval: Duration::from_millis(30),
+        encoding: Duration::from_millis(20),
+        writing: Duration::from_millis(10),
+        piece_retrieval_times: vec![
+            Duration::from_millis(5),
+            Duration::from_millis(20),
+            Duration::from_millis(5),
+        ],
+    };
+    let plotted_sector = PlottedSector {
+        sector_id: SectorId::new(&PublicKey::default(), SectorIndex::ZERO),
+        sector_index: SectorIndex::ZERO,
+        sector_metadata: SectorMetadata {
+            total_pieces: NonZeroU64::new(1).unwrap(),
+            expires_at: SegmentIndex::ZERO,
+        },
+        rotation: 0,
+        piece_indexes: vec![10, 11, 12],
+        stats: Some(stats.clone()),
+    };
+
+    let mut plotting_stats = PlottingStats::default();
+    plotting_stats.add(&stats);
+    assert_eq!(plotting_stats.total.count(), 1);
+    assert_eq!(plotting_stats.total.sum(), Duration::from_millis(60));
+    assert_eq!(
+        plotting_stats.piece_retrieval.sum(),
+        Duration::from_millis(30)
+    );
+
+    assert_eq!(
+        slow_pieces(&plotted_sector, Duration::from_millis(10)).collect::<Vec<_>>(),
+        vec![(11, Duration::from_millis(20))]
+    );
+    assert_eq!(
+        slow_pieces(&plotted_sector, Duration::from_millis(20)).count(),
+        0
+    );
+}
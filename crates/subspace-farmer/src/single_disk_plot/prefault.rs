@@ -0,0 +1,90 @@
+//! Warm-up of memory-mapped plot ahead of audits.
+//!
+//! Pages of a freshly opened plot are not in page cache yet, so the first slot after opening pays
+//! for a storm of page faults, which shows up as audit latency spike. Prefaulting asks the kernel
+//! to start reading sectors before they are audited.
+
+#[cfg(test)]
+mod tests;
+
+use memmap2::Mmap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::{io, thread};
+use tracing::debug;
+
+/// Prefault pages of `sectors` (offsets within the plot) of memory-mapped plot, sectors that are
+/// outside of the mapping are ignored.
+///
+/// On Unix kernel is advised with `MADV_WILLNEED` to read pages in the background, so this returns
+/// quickly, on other platforms every page is touched. Prefaulting stops between sectors once
+/// `cancelled` is set.
+pub fn prefault(
+    plot_mmap: &Mmap,
+    plot_sector_size: u64,
+    sectors: Range<u64>,
+    cancelled: &AtomicBool,
+) -> io::Result<()> {
+    let sector_count = plot_mmap.len() as u64 / plot_sector_size;
+
+    for sector_offset in sectors.start.min(sector_count)..sectors.end.min(sector_count) {
+        if cancelled.load(Ordering::Acquire) {
+            debug!(%sector_offset, "Prefaulting cancelled");
+            break;
+        }
+
+        prefault_memory(
+            &plot_mmap[(sector_offset * plot_sector_size) as usize..][..plot_sector_size as usize],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Same as [`prefault()`], but runs in a background thread
+pub fn prefault_in_background(
+    plot_mmap: Arc<Mmap>,
+    plot_sector_size: u64,
+    sectors: Range<u64>,
+    cancelled: Arc<AtomicBool>,
+) -> io::Result<JoinHandle<io::Result<()>>> {
+    thread::Builder::new()
+        .name("prefault".to_string())
+        .spawn(move || prefault(&plot_mmap, plot_sector_size, sectors, &cancelled))
+}
+
+#[cfg(unix)]
+fn prefault_memory(memory: &[u8]) -> io::Result<()> {
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let address = memory.as_ptr() as usize;
+    // Memory mapping itself is always page-aligned, so aligned address is still within it
+    let aligned_address = address - address % page_size;
+    let len = memory.len() + (address - aligned_address);
+
+    let result = unsafe {
+        libc::madvise(
+            aligned_address as *mut libc::c_void,
+            len,
+            libc::MADV_WILLNEED,
+        )
+    };
+    if result != 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+fn prefault_memory(memory: &[u8]) -> io::Result<()> {
+    // Pages are at least 4 KiB on all supported platforms
+    for byte in memory.iter().step_by(4096) {
+        unsafe {
+            std::ptr::read_volatile(byte);
+        }
+    }
+
+    Ok(())
+}
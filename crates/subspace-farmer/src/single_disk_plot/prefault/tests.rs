@@ -0,0 +1,114 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use crate::single_disk_plot::prefault::{prefault, prefault_in_background};
+use crate::testing::fixtures::{farmer_protocol_info, DerivedPieceReceiver};
+use futures::executor::block_on;
+use memmap2::Mmap;
+use std::io::{Cursor, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{plot_sector_size, PublicKey, SectorIndex, SolutionRange};
+
+const SECTOR_COUNT: u64 = 4;
+
+/// Plot sectors into a file that is not in page cache and map it
+fn plot(public_key: &PublicKey) -> (Vec<u8>, Mmap) {
+    let farmer_protocol_info = farmer_protocol_info();
+    let mut plot = Vec::new();
+    for sector_offset in 0..SECTOR_COUNT {
+        block_on(plot_sector(
+            public_key,
+            SectorIndex::new(sector_offset),
+            &DerivedPieceReceiver,
+            &AtomicBool::new(false),
+            &farmer_protocol_info,
+            SectorBuffer::new(&mut plot),
+            std::io::sink(),
+        ))
+        .unwrap();
+    }
+
+    let mut plot_file = tempfile::tempfile().unwrap();
+    plot_file.write_all(&plot).unwrap();
+    // Dirty pages can't be dropped from page cache, make sure everything is on disk
+    plot_file.sync_all().unwrap();
+    plot_file.drop_cache(0, 0).unwrap();
+    let plot_mmap = unsafe { Mmap::map(&plot_file).unwrap() };
+
+    (plot, plot_mmap)
+}
+
+#[test]
+fn prefaulted_plot_audits() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let farmer_protocol_info = farmer_protocol_info();
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let (plot, plot_mmap) = plot(&public_key);
+
+    let start = Instant::now();
+    // Sectors beyond the end of the plot are ignored
+    prefault(
+        &plot_mmap,
+        plot_sector_size,
+        1..SECTOR_COUNT * 2,
+        &AtomicBool::new(false),
+    )
+    .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // Audits of prefaulted plot are the same as audits of the same sectors in memory
+    for _ in 0..10 {
+        let global_challenge = rand::random();
+        for sector_offset in 0..SECTOR_COUNT {
+            let audit = |plot: &[u8]| {
+                audit_sector(
+                    &public_key,
+                    SectorIndex::new(sector_offset),
+                    &farmer_protocol_info,
+                    KzgParametersId::TEST,
+                    &kzg,
+                    &global_challenge,
+                    SolutionRange::MAX,
+                    Cursor::new(
+                        &plot[(sector_offset * plot_sector_size) as usize..]
+                            [..plot_sector_size as usize],
+                    ),
+                )
+                .unwrap()
+                .map(|eligible_sector| (eligible_sector.audit_index, eligible_sector.encoded_piece))
+            };
+
+            assert_eq!(audit(&plot_mmap), audit(&plot));
+        }
+    }
+}
+
+#[test]
+fn prefault_cancellation() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let plot_sector_size = plot_sector_size(farmer_protocol_info().space_l);
+    let (_plot, plot_mmap) = plot(&public_key);
+    let plot_mmap = Arc::new(plot_mmap);
+
+    // Cancelled prefaulting stops without error
+    let cancelled = Arc::new(AtomicBool::new(true));
+    prefault(&plot_mmap, plot_sector_size, 0..SECTOR_COUNT, &cancelled).unwrap();
+
+    // Background prefaulting completes
+    cancelled.store(false, Ordering::Release);
+    prefault_in_background(
+        Arc::clone(&plot_mmap),
+        plot_sector_size,
+        0..SECTOR_COUNT,
+        cancelled,
+    )
+    .unwrap()
+    .join()
+    .unwrap()
+    .unwrap();
+}
@@ -0,0 +1,677 @@
+#[cfg(test)]
+pub(crate) mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::farming::{audit_sector, EligibleSector, PieceStore};
+use crate::single_disk_plot::full_verification::{verify_full_plot, VerifyReport};
+use crate::single_disk_plot::plot_auditor::AuditError;
+use crate::single_disk_plot::plot_layout_calculator::PlotLayoutCalculator;
+use crate::single_disk_plot::sector_metadata::{
+    open_sector_metadata_file, open_sector_metadata_file_read_only, sector_metadata_record_offset,
+    SectorMetadataRecord, SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::storage_backend::{
+    check_plot_layout, plot_mmap_len, plot_size, sector_start, sector_stride, PlotData,
+    StorageBackend,
+};
+use crate::single_disk_plot::{
+    FarmingError, PlotLayout, PlotMetadataHeader, PlotProtocolInfo, PlotSectorAlignment,
+    SectorMetadata, SingleDiskPlot, SingleDiskPlotError, SingleDiskPlotInfo,
+    RESERVED_PLOT_METADATA,
+};
+use memmap2::{Mmap, MmapOptions};
+use parity_scale_codec::Decode;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, Piece, PublicKey, SectorIndex, SolutionRange, PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use thiserror::Error;
+
+/// Errors that happen during [`audit_plot_file()`]
+#[derive(Debug, Error)]
+pub enum AuditPlotFileError {
+    /// Plot file is not located in plot directory
+    #[error("Plot file {path} is not located in plot directory")]
+    NotInPlotDirectory {
+        /// Path to plot file
+        path: PathBuf,
+    },
+    /// Failed to open plot
+    #[error("Failed to open plot: {0}")]
+    Open(#[from] SingleDiskPlotError),
+    /// Plot belongs to a different public key
+    #[error("Plot belongs to public key {plot}, but audit was requested for {supplied}")]
+    PublicKeyMismatch {
+        /// Public key plot belongs to
+        plot: PublicKey,
+        /// Public key supplied for audit
+        supplied: PublicKey,
+    },
+    /// Plot was created with different farmer protocol info
+    #[error("Plot was created with farmer protocol info different from supplied for audit")]
+    FarmerProtocolInfoMismatch,
+}
+
+/// Result of [`audit_plot_file()`] and [`audit_plot_first()`]
+#[derive(Debug, Default)]
+pub struct AuditPlotFileReport {
+    /// Sectors that are eligible for solution creation, in order of sector indexes
+    pub eligible_sectors: Vec<EligibleSector>,
+    /// Sectors that failed to be audited, in order of sector indexes
+    pub errors: Vec<AuditError>,
+    /// Number of sectors that were audited, successfully or not
+    pub audited_sectors: u64,
+}
+
+/// Audit all sectors of the plot file located at `path` and return those that are eligible for
+/// solution creation.
+///
+/// Sectors that fail to be audited (for instance due to read errors) don't fail the whole audit,
+/// they are returned in [`AuditPlotFileReport::errors`] alongside eligible sectors of the rest of
+/// the plot.
+///
+/// Plot file must be located in plot directory next to plot metadata and plot info, they are used
+/// to determine number of plotted sectors, their indexes and KZG parameters used. Depending on
+/// storage backend, plot is either memory mapped or read with positional reads.
+///
+/// Sectors are audited in parallel on `thread_pool`, global rayon thread pool is used if `None`.
+pub fn audit_plot_file(
+    path: &Path,
+    public_key: &PublicKey,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    kzg: &Kzg,
+    global_challenge: &Blake2b256Hash,
+    solution_range: SolutionRange,
+    thread_pool: Option<&ThreadPool>,
+) -> Result<AuditPlotFileReport, AuditPlotFileError> {
+    let (plot, plot_mmap) = open_plot_file_for_audit(path, public_key, farmer_protocol_info)?;
+    let plot_data = match &plot_mmap {
+        Some(plot_mmap) => PlotData::Mmap(plot_mmap),
+        None => PlotData::File(&plot.plot_file),
+    };
+
+    Ok(audit_plot_data(
+        &plot,
+        plot_data,
+        farmer_protocol_info,
+        kzg,
+        global_challenge,
+        solution_range,
+        thread_pool,
+    ))
+}
+
+/// Same as [`audit_plot_file()`], but stops once `solutions_needed` eligible sectors are found.
+///
+/// Sectors are still audited in parallel, sectors that were not audited by the time enough
+/// eligible sectors were found are skipped, which saves I/O on large plots when farmer only needs
+/// one solution per slot. Which of the eligible sectors are returned depends on the order in which
+/// parallel audits complete, see [`AuditPlotFileReport::audited_sectors`] for number of sectors
+/// that were actually audited.
+#[allow(clippy::too_many_arguments)]
+pub fn audit_plot_first(
+    path: &Path,
+    public_key: &PublicKey,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    kzg: &Kzg,
+    global_challenge: &Blake2b256Hash,
+    solution_range: SolutionRange,
+    solutions_needed: NonZeroUsize,
+    thread_pool: Option<&ThreadPool>,
+) -> Result<AuditPlotFileReport, AuditPlotFileError> {
+    let (plot, plot_mmap) = open_plot_file_for_audit(path, public_key, farmer_protocol_info)?;
+    let plot_data = match &plot_mmap {
+        Some(plot_mmap) => PlotData::Mmap(plot_mmap),
+        None => PlotData::File(&plot.plot_file),
+    };
+
+    Ok(audit_plot_data_until(
+        &plot,
+        plot_data,
+        farmer_protocol_info,
+        kzg,
+        global_challenge,
+        solution_range,
+        Some(solutions_needed),
+        thread_pool,
+    ))
+}
+
+/// Open plot that plot file at `path` belongs to and check that it can be audited with supplied
+/// parameters, plot is memory mapped if storage backend allows it
+fn open_plot_file_for_audit(
+    path: &Path,
+    public_key: &PublicKey,
+    farmer_protocol_info: &FarmerProtocolInfo,
+) -> Result<(PlotReader, Option<Mmap>), AuditPlotFileError> {
+    let directory = path
+        .parent()
+        .filter(|directory| directory.join(SingleDiskPlot::PLOT_FILE) == path)
+        .ok_or_else(|| AuditPlotFileError::NotInPlotDirectory {
+            path: path.to_path_buf(),
+        })?;
+    // Relative path to plot file in current directory has empty parent
+    let directory = if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    };
+
+    let plot = PlotReader::open(directory)?;
+
+    if plot.info.public_key() != public_key {
+        return Err(AuditPlotFileError::PublicKeyMismatch {
+            plot: *plot.info.public_key(),
+            supplied: *public_key,
+        });
+    }
+    if PlotProtocolInfo::new(plot.farmer_protocol_info, plot.kzg_parameters_id)
+        != PlotProtocolInfo::new(*farmer_protocol_info, plot.kzg_parameters_id)
+    {
+        return Err(AuditPlotFileError::FarmerProtocolInfoMismatch);
+    }
+
+    plot.plot_file
+        .advise_random_access()
+        .map_err(SingleDiskPlotError::Io)?;
+
+    let plot_mmap = if StorageBackend::detect(directory)
+        .map_err(SingleDiskPlotError::Io)?
+        .use_mmap()
+        && plot.sector_count > 0
+    {
+        let plot_mmap = unsafe {
+            MmapOptions::new()
+                .len(plot_mmap_len(plot.sector_count, plot.sector_stride)?)
+                .map(&plot.plot_file)
+                .map_err(SingleDiskPlotError::Io)?
+        };
+        #[cfg(unix)]
+        {
+            plot_mmap
+                .advise(memmap2::Advice::Random)
+                .map_err(SingleDiskPlotError::Io)?;
+        }
+
+        Some(plot_mmap)
+    } else {
+        None
+    };
+
+    Ok((plot, plot_mmap))
+}
+
+/// Audit all sectors of `plot` stored in `plot_data` in parallel, collecting eligible sectors and
+/// errors of individual sectors
+fn audit_plot_data(
+    plot: &PlotReader,
+    plot_data: PlotData<'_>,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    kzg: &Kzg,
+    global_challenge: &Blake2b256Hash,
+    solution_range: SolutionRange,
+    thread_pool: Option<&ThreadPool>,
+) -> AuditPlotFileReport {
+    audit_plot_data_until(
+        plot,
+        plot_data,
+        farmer_protocol_info,
+        kzg,
+        global_challenge,
+        solution_range,
+        None,
+        thread_pool,
+    )
+}
+
+/// Same as [`audit_plot_data()`], but outstanding sectors are skipped once `solutions_needed`
+/// eligible sectors are found, all sectors are audited if `None`
+#[allow(clippy::too_many_arguments)]
+fn audit_plot_data_until(
+    plot: &PlotReader,
+    plot_data: PlotData<'_>,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    kzg: &Kzg,
+    global_challenge: &Blake2b256Hash,
+    solution_range: SolutionRange,
+    solutions_needed: Option<NonZeroUsize>,
+    thread_pool: Option<&ThreadPool>,
+) -> AuditPlotFileReport {
+    let solutions_found = AtomicUsize::new(0);
+    let enough_solutions = || {
+        solutions_needed.map_or(false, |solutions_needed| {
+            solutions_found.load(Ordering::Acquire) >= solutions_needed.get()
+        })
+    };
+
+    let audit_results = in_thread_pool(thread_pool, || {
+        (0..plot.sector_count)
+            .into_par_iter()
+            .map(|sector_offset| {
+                // `None` stops the rest of parallel iteration
+                if enough_solutions() {
+                    return None;
+                }
+
+                let sector_index = plot.info.first_sector_index().offset(sector_offset);
+
+                let audit_result: Result<_, FarmingError> = try {
+                    // Sectors are audited with rotation they were plotted under
+                    let rotation = plot.read_sector_metadata_record(sector_offset)?.rotation;
+                    audit_sector(
+                        plot.info.public_key(),
+                        sector_index,
+                        &FarmerProtocolInfo {
+                            rotation,
+                            ..*farmer_protocol_info
+                        },
+                        plot.kzg_parameters_id,
+                        kzg,
+                        global_challenge,
+                        solution_range,
+                        plot_data.sector(sector_offset, plot.sector_stride)?,
+                    )?
+                };
+
+                match audit_result {
+                    Ok(Some(eligible_sector)) => {
+                        // Sectors that were being audited concurrently may find more solutions
+                        // than needed, those are not returned
+                        let found_before = solutions_found.fetch_add(1, Ordering::AcqRel);
+                        if solutions_needed.map_or(false, |solutions_needed| {
+                            found_before >= solutions_needed.get()
+                        }) {
+                            Some(Ok(None))
+                        } else {
+                            Some(Ok(Some(eligible_sector)))
+                        }
+                    }
+                    Ok(None) => Some(Ok(None)),
+                    Err(error) => Some(Err(AuditError {
+                        sector_index,
+                        error,
+                    })),
+                }
+            })
+            .while_some()
+            .collect::<Vec<_>>()
+    });
+
+    let mut report = AuditPlotFileReport {
+        audited_sectors: audit_results.len() as u64,
+        ..AuditPlotFileReport::default()
+    };
+    for audit_result in audit_results {
+        match audit_result {
+            Ok(Some(eligible_sector)) => {
+                report.eligible_sectors.push(eligible_sector);
+            }
+            Ok(None) => {}
+            Err(error) => {
+                report.errors.push(error);
+            }
+        }
+    }
+
+    report
+}
+
+/// Run `op` in `thread_pool` so that parallel iterators inside of it use that pool, global thread
+/// pool is used if `None`
+pub(super) fn in_thread_pool<OP, R>(thread_pool: Option<&ThreadPool>, op: OP) -> R
+where
+    OP: FnOnce() -> R + Send,
+    R: Send,
+{
+    match thread_pool {
+        Some(thread_pool) => thread_pool.install(op),
+        None => op(),
+    }
+}
+
+/// Single record read from the plot together with metadata necessary to verify it
+#[derive(Debug)]
+pub struct Record {
+    /// Sector index
+    pub sector_index: SectorIndex,
+    /// Offset of the record in sector
+    pub record_offset: u64,
+    /// Encoded piece as stored in the plot: encoded record followed by its witness
+    pub encoded_piece: Piece,
+    /// Metadata of the sector record belongs to
+    pub sector_metadata: SectorMetadata,
+    /// Rotation of the global salt sector was encoded with, needed to decode the record
+    pub rotation: u64,
+}
+
+/// Read-only view of the single disk plot that doesn't need connection to the node, meant for
+/// external tools that inspect and audit plots.
+///
+/// Plot files are opened read-only, so plots on read-only media can be opened as well. Plot is
+/// locked in shared mode while reader exists, [`PlotWriter`](super::plot_writer::PlotWriter) can't
+/// open it at the same time. Sectors plotted after opening are not visible.
+///
+/// Reader has no plotting APIs, which is enforced at compile time:
+/// ```compile_fail
+/// # use std::path::Path;
+/// # use std::sync::atomic::AtomicBool;
+/// # use subspace_farmer::single_disk_plot::piece_receiver::PieceReceiver;
+/// # use subspace_farmer::single_disk_plot::read_only::PlotReader;
+/// # async fn f(piece_receiver: &impl PieceReceiver) {
+/// let mut plot = PlotReader::open(Path::new("plot")).unwrap();
+/// plot.plot_sector(piece_receiver, &AtomicBool::new(false)).await.unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PlotReader {
+    info: SingleDiskPlotInfo,
+    farmer_protocol_info: FarmerProtocolInfo,
+    kzg_parameters_id: KzgParametersId,
+    pub(super) sector_count: u64,
+    target_sector_count: u64,
+    pub(super) plot_sector_size: u64,
+    sector_alignment: u64,
+    /// Distance between starts of consecutive sectors, includes padding for alignment
+    pub(super) sector_stride: u64,
+    pub(super) plot_file: File,
+    pub(super) metadata_file: File,
+    /// `None` for plots that were not migrated to sector metadata file yet
+    pub(super) sector_metadata_file: Option<File>,
+}
+
+impl PlotReader {
+    /// Open plot stored in `directory`, plot must have been opened by the farmer at least once
+    pub fn open(directory: &Path) -> Result<Self, SingleDiskPlotError> {
+        Self::open_with_layout(directory, &PlotLayout::default())
+    }
+
+    /// Open plot stored in `directory` with specified layout, plot must have been opened by the
+    /// farmer at least once.
+    ///
+    /// Sector alignment of `plot_layout` is ignored, alignment recorded in plot metadata is used.
+    pub fn open_with_layout(
+        directory: &Path,
+        plot_layout: &PlotLayout,
+    ) -> Result<Self, SingleDiskPlotError> {
+        Self::open_internal(directory, plot_layout, false).map(|(plot, _metadata_header)| plot)
+    }
+
+    /// Open plot for reading and also for writing if `writable` is `true`, plot is locked in
+    /// exclusive mode in that case instead of shared. Sector metadata is migrated to sector
+    /// metadata file if plot is writable.
+    pub(super) fn open_internal(
+        directory: &Path,
+        plot_layout: &PlotLayout,
+        writable: bool,
+    ) -> Result<(Self, PlotMetadataHeader), SingleDiskPlotError> {
+        let info = SingleDiskPlotInfo::load_from(directory)?.ok_or_else(|| {
+            SingleDiskPlotError::PlotInfoNotFound {
+                directory: directory.to_path_buf(),
+            }
+        })?;
+        if info.plot_shards().is_some() {
+            return Err(SingleDiskPlotError::OfflineAccessOfShardedPlot {
+                directory: directory.to_path_buf(),
+            });
+        }
+
+        let plot_file = OpenOptions::new()
+            .read(true)
+            .write(writable)
+            .open(directory.join(SingleDiskPlot::PLOT_FILE))?;
+        let lock_result = if writable {
+            fs2::FileExt::try_lock_exclusive(&plot_file)
+        } else {
+            fs2::FileExt::try_lock_shared(&plot_file)
+        };
+        if let Err(error) = lock_result {
+            return Err(
+                if error.raw_os_error() == fs2::lock_contended_error().raw_os_error() {
+                    SingleDiskPlotError::PlotLocked {
+                        directory: directory.to_path_buf(),
+                    }
+                } else {
+                    error.into()
+                },
+            );
+        }
+
+        let metadata_file = OpenOptions::new()
+            .read(true)
+            .write(writable)
+            .open(plot_layout.metadata_file(directory))?;
+
+        let mut metadata_header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+        metadata_file.read_exact_at(&mut metadata_header_bytes, 0)?;
+        let metadata_header = PlotMetadataHeader::decode(&mut metadata_header_bytes.as_slice())
+            .map_err(SingleDiskPlotError::FailedToDecodeMetadataHeader)?;
+
+        if metadata_header.version > PlotMetadataHeader::LATEST_VERSION {
+            return Err(SingleDiskPlotError::UnexpectedMetadataVersion(
+                metadata_header.version,
+            ));
+        }
+        if metadata_header.version == 0 {
+            return Err(SingleDiskPlotError::ProtocolInfoNotStored { id: *info.id() });
+        }
+
+        let (farmer_protocol_info, kzg_parameters_id) = PlotProtocolInfo::load(&metadata_file)?;
+
+        let sector_metadata_file = if writable {
+            Some(open_sector_metadata_file(
+                &plot_layout.sector_metadata_file(directory),
+                &metadata_file,
+                metadata_header.sector_count,
+            )?)
+        } else {
+            open_sector_metadata_file_read_only(&plot_layout.sector_metadata_file(directory))?
+        };
+
+        // Sector count comes from disk, make sure all sectors are addressable
+        let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+        let sector_alignment = PlotSectorAlignment::load(&metadata_file, &metadata_header)?;
+        let sector_stride = sector_stride(plot_sector_size, sector_alignment)?;
+        plot_size(metadata_header.sector_count, sector_stride)?;
+        let plot_file_size = plot_file.metadata()?.len();
+        check_plot_layout(
+            *info.id(),
+            info.allocated_space(),
+            metadata_header.sector_count,
+            sector_stride,
+            plot_file_size,
+        )?;
+
+        let plot = Self {
+            info,
+            farmer_protocol_info,
+            kzg_parameters_id,
+            sector_count: metadata_header.sector_count,
+            // Plot file is allocated for all sectors upfront, which was checked above
+            target_sector_count: plot_file_size / sector_stride,
+            plot_sector_size,
+            sector_alignment,
+            sector_stride,
+            plot_file,
+            metadata_file,
+            sector_metadata_file,
+        };
+
+        Ok((plot, metadata_header))
+    }
+
+    /// Plot info
+    pub fn info(&self) -> &SingleDiskPlotInfo {
+        &self.info
+    }
+
+    /// Farmer protocol info the plot was created with
+    pub fn farmer_protocol_info(&self) -> &FarmerProtocolInfo {
+        &self.farmer_protocol_info
+    }
+
+    /// Identifier of KZG parameters the plot was created with
+    pub fn kzg_parameters_id(&self) -> KzgParametersId {
+        self.kzg_parameters_id
+    }
+
+    /// Number of sectors plotted
+    pub fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    /// Number of sectors plot will have when fully plotted
+    pub fn target_sector_count(&self) -> u64 {
+        self.target_sector_count
+    }
+
+    /// Alignment of sectors within plot file the plot was created with
+    pub fn sector_alignment(&self) -> u64 {
+        self.sector_alignment
+    }
+
+    /// Calculator of plot layout for protocol parameters and sector alignment of this plot
+    pub fn layout_calculator(&self) -> PlotLayoutCalculator {
+        PlotLayoutCalculator::with_sector_stride(self.plot_sector_size, self.sector_stride)
+    }
+
+    /// Read a single record with `record_offset` from sector with `sector_index` along with sector
+    /// metadata, without reading the rest of the sector
+    pub fn read_record(&self, sector_index: SectorIndex, record_offset: u64) -> io::Result<Record> {
+        let sector_offset = sector_index
+            .offset_from(self.info.first_sector_index())
+            .filter(|&sector_offset| sector_offset < self.sector_count)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Sector index {sector_index} is out of range, plot has {} sectors \
+                        starting at {}",
+                        self.sector_count,
+                        self.info.first_sector_index()
+                    ),
+                )
+            })?;
+        let records_in_sector = self.plot_sector_size / PIECE_SIZE as u64;
+        if record_offset >= records_in_sector {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Record offset {record_offset} is out of range, sector has \
+                    {records_in_sector} records"
+                ),
+            ));
+        }
+
+        let mut encoded_piece = Piece::default();
+        self.plot_file.read_exact_at(
+            &mut encoded_piece,
+            sector_start(sector_offset, self.sector_stride)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?
+                + record_offset * PIECE_SIZE as u64,
+        )?;
+
+        let record = self.read_sector_metadata_record(sector_offset)?;
+
+        Ok(Record {
+            sector_index,
+            record_offset,
+            encoded_piece,
+            sector_metadata: record.sector_metadata,
+            rotation: record.rotation,
+        })
+    }
+
+    /// Read metadata record of sector at `sector_offset` within plot, plots that were not migrated
+    /// to sector metadata file yet only store sector metadata and were plotted without rotation
+    pub(super) fn read_sector_metadata_record(
+        &self,
+        sector_offset: u64,
+    ) -> io::Result<SectorMetadataRecord> {
+        match &self.sector_metadata_file {
+            Some(sector_metadata_file) => {
+                let mut record = [0; SECTOR_METADATA_RECORD_SIZE];
+                sector_metadata_file
+                    .read_exact_at(&mut record, sector_metadata_record_offset(sector_offset))?;
+                SectorMetadataRecord::decode(&record, SectorMetadataRecord::LATEST_VERSION)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+            }
+            None => {
+                let mut sector_metadata_bytes = vec![0; SectorMetadata::encoded_size()];
+                self.metadata_file.read_exact_at(
+                    &mut sector_metadata_bytes,
+                    RESERVED_PLOT_METADATA + sector_offset * SectorMetadata::encoded_size() as u64,
+                )?;
+                let sector_metadata = SectorMetadata::decode(&mut sector_metadata_bytes.as_slice())
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+                Ok(SectorMetadataRecord {
+                    sector_metadata,
+                    plotted_at_slot: None,
+                    rotation: 0,
+                    checksum: None,
+                })
+            }
+        }
+    }
+
+    /// Verify every sector of the plot against original pieces from `piece_store`, see
+    /// [`verify_full_plot()`] for details
+    pub fn scrub(
+        &self,
+        piece_store: &(dyn PieceStore + Sync),
+        cancelled: &AtomicBool,
+        thread_pool: Option<&ThreadPool>,
+    ) -> VerifyReport {
+        verify_full_plot(
+            self,
+            piece_store,
+            &self.farmer_protocol_info,
+            cancelled,
+            thread_pool,
+        )
+    }
+
+    /// Audit sector at `sector_offset` within plot, `kzg` must use the same parameters the plot was
+    /// created with
+    pub fn audit_sector(
+        &self,
+        sector_offset: u64,
+        kzg: &Kzg,
+        global_challenge: &Blake2b256Hash,
+        solution_range: SolutionRange,
+    ) -> Result<Option<EligibleSector>, FarmingError> {
+        if sector_offset >= self.sector_count {
+            return Err(FarmingError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Sector offset {sector_offset} is out of range, plot has {} sectors",
+                    self.sector_count
+                ),
+            )));
+        }
+
+        // Sector is audited with rotation it was plotted under
+        let rotation = self.read_sector_metadata_record(sector_offset)?.rotation;
+        audit_sector(
+            self.info.public_key(),
+            self.info.first_sector_index().offset(sector_offset),
+            &FarmerProtocolInfo {
+                rotation,
+                ..self.farmer_protocol_info
+            },
+            self.kzg_parameters_id,
+            kzg,
+            global_challenge,
+            solution_range,
+            PlotData::File(&self.plot_file).sector(sector_offset, self.sector_stride)?,
+        )
+    }
+}
@@ -0,0 +1,936 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::plot_writer::PlotWriter;
+use crate::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use crate::single_disk_plot::read_only::{
+    audit_plot_data, audit_plot_file, audit_plot_first, in_thread_pool, AuditPlotFileError,
+    PlotReader,
+};
+use crate::single_disk_plot::sharded_plot_file::PlotShards;
+use crate::single_disk_plot::storage_backend::PlotData;
+use crate::single_disk_plot::{
+    FarmingError, PlotLayout, PlotMetadataHeader, PlotProtocolInfo, PlotSectorAlignment,
+    SectorMetadata, SingleDiskPlot, SingleDiskPlotError, SingleDiskPlotId, SingleDiskPlotInfo,
+    RESERVED_PLOT_METADATA,
+};
+use crate::testing::fixtures::{farmer_protocol_info, DerivedPieceReceiver};
+use futures::executor::block_on;
+use parity_scale_codec::Encode;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::io::Cursor;
+use std::num::{NonZeroU16, NonZeroU64, NonZeroUsize};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::{fs, io, thread};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{plot_sector_size, PublicKey, SectorIndex, PIECE_SIZE};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tempfile::TempDir;
+
+/// Plot created with pieces from [`piece()`](crate::testing::fixtures::piece), also used by tests
+/// of other modules that work on top of read-only plot
+pub(crate) struct TestPlot {
+    pub(crate) id: SingleDiskPlotId,
+    pub(crate) public_key: PublicKey,
+    pub(crate) first_sector_index: SectorIndex,
+    pub(crate) sector_count: u64,
+    pub(crate) farmer_protocol_info: FarmerProtocolInfo,
+    pub(crate) plot: Vec<u8>,
+    pub(crate) sectors_metadata: Vec<u8>,
+}
+
+impl TestPlot {
+    /// Plot a few sectors and write everything except sector metadata to `directory`, the same way
+    /// farmer does
+    pub(crate) fn create(directory: &Path) -> (Self, fs::File, PlotMetadataHeader) {
+        let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+        let first_sector_index = SectorIndex::new(100);
+        let farmer_protocol_info = FarmerProtocolInfo {
+            genesis_hash: rand::random(),
+            ..farmer_protocol_info()
+        };
+        let sector_count = 2;
+        let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+        let id = SingleDiskPlotId::new();
+        SingleDiskPlotInfo::new(
+            id,
+            farmer_protocol_info.genesis_hash,
+            public_key,
+            first_sector_index,
+            plot_sector_size * sector_count,
+        )
+        .store_to(directory)
+        .unwrap();
+
+        let mut plot = Vec::new();
+        let mut sectors_metadata = Vec::new();
+        for sector_offset in 0..sector_count {
+            block_on(plot_sector(
+                &public_key,
+                first_sector_index.offset(sector_offset),
+                &DerivedPieceReceiver,
+                &AtomicBool::new(false),
+                &farmer_protocol_info,
+                SectorBuffer::new(&mut plot),
+                &mut sectors_metadata,
+            ))
+            .unwrap();
+        }
+        fs::write(directory.join(SingleDiskPlot::PLOT_FILE), &plot).unwrap();
+
+        let metadata_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(directory.join(SingleDiskPlot::METADATA_FILE))
+            .unwrap();
+        metadata_file.set_len(RESERVED_PLOT_METADATA).unwrap();
+        let metadata_header = PlotMetadataHeader {
+            version: 0,
+            sector_count: 0,
+        };
+        metadata_file
+            .write_all_at(&metadata_header.encode(), 0)
+            .unwrap();
+
+        let test_plot = Self {
+            id,
+            public_key,
+            first_sector_index,
+            sector_count,
+            farmer_protocol_info,
+            plot,
+            sectors_metadata,
+        };
+
+        (test_plot, metadata_file, metadata_header)
+    }
+
+    /// Store protocol info and sector metadata, completing the plot
+    pub(crate) fn finish(
+        &self,
+        metadata_file: &fs::File,
+        metadata_header: PlotMetadataHeader,
+        kzg_parameters_id: KzgParametersId,
+    ) {
+        self.finish_aligned(metadata_file, metadata_header, kzg_parameters_id, 1);
+    }
+
+    /// Same as [`Self::finish()`], but records specified sector alignment in plot metadata
+    pub(crate) fn finish_aligned(
+        &self,
+        metadata_file: &fs::File,
+        mut metadata_header: PlotMetadataHeader,
+        kzg_parameters_id: KzgParametersId,
+        sector_alignment: u64,
+    ) {
+        PlotProtocolInfo::load_or_store(
+            self.id,
+            metadata_file,
+            &mut metadata_header,
+            self.farmer_protocol_info,
+            kzg_parameters_id,
+        )
+        .unwrap();
+        PlotSectorAlignment::load_or_store(metadata_file, &mut metadata_header, sector_alignment)
+            .unwrap();
+        metadata_header.sector_count = self.sector_count;
+        metadata_file
+            .write_all_at(&metadata_header.encode(), 0)
+            .unwrap();
+        metadata_file
+            .write_all_at(&self.sectors_metadata, RESERVED_PLOT_METADATA)
+            .unwrap();
+    }
+}
+
+#[test]
+fn open_and_audit() {
+    let directory = TempDir::new().unwrap();
+
+    assert!(matches!(
+        PlotReader::open(directory.path()),
+        Err(SingleDiskPlotError::PlotInfoNotFound { .. })
+    ));
+
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    let TestPlot {
+        id,
+        public_key,
+        first_sector_index,
+        sector_count,
+        farmer_protocol_info,
+        ..
+    } = test_plot;
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+    // Protocol info is not stored yet
+    assert!(matches!(
+        PlotReader::open(directory.path()),
+        Err(SingleDiskPlotError::ProtocolInfoNotStored { id: error_id }) if error_id == id
+    ));
+
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+    assert_eq!(read_only_plot.sector_count(), sector_count);
+    assert_eq!(read_only_plot.kzg_parameters_id(), KzgParametersId::TEST);
+    assert_eq!(read_only_plot.info().public_key(), &public_key);
+    assert_eq!(
+        read_only_plot.farmer_protocol_info().genesis_hash,
+        farmer_protocol_info.genesis_hash
+    );
+
+    for sector_offset in 0..sector_count {
+        let global_challenge = rand::random();
+        // Large solution range makes sure audit is very likely to find eligible sector
+        let solution_range = u64::MAX / 2;
+
+        let eligible_sector = read_only_plot
+            .audit_sector(sector_offset, &kzg, &global_challenge, solution_range)
+            .unwrap();
+        let expected_eligible_sector = audit_sector(
+            &public_key,
+            first_sector_index.offset(sector_offset),
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            solution_range,
+            Cursor::new(
+                &test_plot.plot[(sector_offset * plot_sector_size) as usize..]
+                    [..plot_sector_size as usize],
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            eligible_sector.map(|eligible_sector| (
+                eligible_sector.sector_index,
+                eligible_sector.audit_index,
+                eligible_sector.chunk
+            )),
+            expected_eligible_sector.map(|eligible_sector| (
+                eligible_sector.sector_index,
+                eligible_sector.audit_index,
+                eligible_sector.chunk
+            ))
+        );
+    }
+
+    assert!(read_only_plot
+        .audit_sector(sector_count, &kzg, &rand::random(), u64::MAX)
+        .is_err());
+}
+
+#[test]
+fn read_only_files() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    drop(metadata_file);
+
+    // Writer locks plot exclusively
+    let plot_writer = PlotWriter::open(directory.path()).unwrap();
+    assert!(matches!(
+        PlotReader::open(directory.path()),
+        Err(SingleDiskPlotError::PlotLocked { .. })
+    ));
+    drop(plot_writer);
+
+    for file_name in [SingleDiskPlot::PLOT_FILE, SingleDiskPlot::METADATA_FILE] {
+        let path = directory.path().join(file_name);
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+    }
+
+    // Any number of readers can open the same plot
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+    let another_read_only_plot = PlotReader::open(directory.path()).unwrap();
+    assert!(PlotWriter::open(directory.path()).is_err());
+
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let global_challenge = rand::random();
+    for sector_offset in 0..test_plot.sector_count {
+        let eligible_sector = read_only_plot
+            .audit_sector(sector_offset, &kzg, &global_challenge, u64::MAX)
+            .unwrap()
+            .unwrap();
+        let another_eligible_sector = another_read_only_plot
+            .audit_sector(sector_offset, &kzg, &global_challenge, u64::MAX)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            eligible_sector.sector_index,
+            test_plot.first_sector_index.offset(sector_offset)
+        );
+        assert_eq!(eligible_sector.chunk, another_eligible_sector.chunk);
+    }
+}
+
+#[test]
+fn sharded_plot() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    drop(metadata_file);
+
+    // Plot is split into shards, there is no plot file
+    fs::remove_file(directory.path().join(SingleDiskPlot::PLOT_FILE)).unwrap();
+    let plot_shards = PlotShards::new(
+        test_plot.plot.len() as u64,
+        NonZeroU64::new(test_plot.plot.len() as u64 / 2).unwrap(),
+    );
+    SingleDiskPlotInfo::load_from(directory.path())
+        .unwrap()
+        .unwrap()
+        .with_plot_shards(plot_shards)
+        .store_to(directory.path())
+        .unwrap();
+
+    assert!(matches!(
+        PlotReader::open(directory.path()),
+        Err(SingleDiskPlotError::OfflineAccessOfShardedPlot { .. })
+    ));
+    assert!(matches!(
+        PlotWriter::open(directory.path()),
+        Err(SingleDiskPlotError::OfflineAccessOfShardedPlot { .. })
+    ));
+}
+
+#[test]
+fn aligned_sectors() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+    // Alignment larger than sector results in padding of the same size as sector after every
+    // sector
+    let sector_alignment = plot_sector_size * 2;
+    let sector_stride = sector_alignment;
+
+    SingleDiskPlotInfo::new(
+        test_plot.id,
+        test_plot.farmer_protocol_info.genesis_hash,
+        test_plot.public_key,
+        test_plot.first_sector_index,
+        sector_stride * test_plot.sector_count,
+    )
+    .store_to(directory.path())
+    .unwrap();
+    // Padding is filled with garbage that would be audited if offsets were computed incorrectly
+    let mut aligned_plot = (0..sector_stride * test_plot.sector_count)
+        .map(|_| rand::random::<u8>())
+        .collect::<Vec<_>>();
+    for (sector, aligned_sector) in test_plot
+        .plot
+        .chunks_exact(plot_sector_size as usize)
+        .zip(aligned_plot.chunks_exact_mut(sector_stride as usize))
+    {
+        aligned_sector[..plot_sector_size as usize].copy_from_slice(sector);
+    }
+    fs::write(
+        directory.path().join(SingleDiskPlot::PLOT_FILE),
+        &aligned_plot,
+    )
+    .unwrap();
+
+    test_plot.finish_aligned(
+        &metadata_file,
+        metadata_header,
+        KzgParametersId::TEST,
+        sector_alignment,
+    );
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+    assert_eq!(read_only_plot.sector_count(), test_plot.sector_count);
+
+    for sector_offset in 0..test_plot.sector_count {
+        let sector = &test_plot.plot[(sector_offset * plot_sector_size) as usize..]
+            [..plot_sector_size as usize];
+
+        for record_offset in [0, plot_sector_size / PIECE_SIZE as u64 - 1] {
+            let record = read_only_plot
+                .read_record(
+                    test_plot.first_sector_index.offset(sector_offset),
+                    record_offset,
+                )
+                .unwrap();
+            assert_eq!(
+                record.encoded_piece.as_ref(),
+                &sector[(record_offset * PIECE_SIZE as u64) as usize..][..PIECE_SIZE]
+            );
+        }
+
+        for _ in 0..10 {
+            let global_challenge = rand::random();
+            let solution_range = u64::MAX / 2;
+
+            let eligible_sector = read_only_plot
+                .audit_sector(sector_offset, &kzg, &global_challenge, solution_range)
+                .unwrap();
+            let expected_eligible_sector = audit_sector(
+                &test_plot.public_key,
+                test_plot.first_sector_index.offset(sector_offset),
+                &test_plot.farmer_protocol_info,
+                KzgParametersId::TEST,
+                &kzg,
+                &global_challenge,
+                solution_range,
+                Cursor::new(sector),
+            )
+            .unwrap();
+
+            assert_eq!(
+                eligible_sector.map(|eligible_sector| eligible_sector.encoded_piece),
+                expected_eligible_sector.map(|eligible_sector| eligible_sector.encoded_piece)
+            );
+        }
+    }
+}
+
+#[test]
+fn split_layout() {
+    let directory = TempDir::new().unwrap();
+    let metadata_directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    drop(metadata_file);
+
+    // Only sector data stays in plot directory
+    fs::rename(
+        directory.path().join(SingleDiskPlot::METADATA_FILE),
+        metadata_directory
+            .path()
+            .join(SingleDiskPlot::METADATA_FILE),
+    )
+    .unwrap();
+    let plot_layout = PlotLayout {
+        metadata_directory: Some(metadata_directory.path().to_path_buf()),
+        ..PlotLayout::default()
+    };
+
+    assert!(matches!(
+        PlotReader::open(directory.path()),
+        Err(SingleDiskPlotError::Io(_))
+    ));
+
+    let read_only_plot = PlotReader::open_with_layout(directory.path(), &plot_layout).unwrap();
+    assert_eq!(read_only_plot.sector_count(), test_plot.sector_count);
+
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+    for sector_offset in 0..test_plot.sector_count {
+        let sector_index = test_plot.first_sector_index.offset(sector_offset);
+        let global_challenge = rand::random();
+        // Maximum solution range makes sure every sector is eligible
+        let solution_range = u64::MAX;
+
+        let eligible_sector = read_only_plot
+            .audit_sector(sector_offset, &kzg, &global_challenge, solution_range)
+            .unwrap()
+            .unwrap();
+        let expected_eligible_sector = audit_sector(
+            &test_plot.public_key,
+            sector_index,
+            &test_plot.farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            solution_range,
+            Cursor::new(
+                &test_plot.plot[(sector_offset * plot_sector_size) as usize..]
+                    [..plot_sector_size as usize],
+            ),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(eligible_sector.sector_index, sector_index);
+        assert_eq!(eligible_sector.chunk, expected_eligible_sector.chunk);
+
+        // Sector metadata is read from metadata directory
+        let record = read_only_plot
+            .read_record(sector_index, eligible_sector.audit_piece_offset)
+            .unwrap();
+        assert_eq!(record.encoded_piece, eligible_sector.encoded_piece);
+        assert_eq!(
+            record.sector_metadata.encode(),
+            test_plot.sectors_metadata[sector_offset as usize * SectorMetadata::encoded_size()..]
+                [..SectorMetadata::encoded_size()]
+        );
+    }
+}
+
+#[test]
+fn kzg_parameters_mismatch() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    let plot_kzg_parameters_id = KzgParametersId(1);
+    test_plot.finish(&metadata_file, metadata_header, plot_kzg_parameters_id);
+
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+    assert_eq!(read_only_plot.kzg_parameters_id(), plot_kzg_parameters_id);
+
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    assert!(matches!(
+        read_only_plot.audit_sector(0, &kzg, &rand::random(), u64::MAX),
+        Err(FarmingError::KzgParametersMismatch { plot, supplied })
+            if plot == plot_kzg_parameters_id && supplied == KzgParametersId::TEST
+    ));
+
+    let kzg = Kzg::with_id(kzg::test_public_parameters(), plot_kzg_parameters_id);
+    assert!(read_only_plot
+        .audit_sector(0, &kzg, &rand::random(), u64::MAX)
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn read_record() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+    let records_in_sector = plot_sector_size / PIECE_SIZE as u64;
+
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+
+    for sector_offset in 0..test_plot.sector_count {
+        let sector_index = test_plot.first_sector_index.offset(sector_offset);
+        let expected_sector_metadata = &test_plot.sectors_metadata
+            [sector_offset as usize * SectorMetadata::encoded_size()..]
+            [..SectorMetadata::encoded_size()];
+
+        for record_offset in 0..records_in_sector {
+            let record = read_only_plot
+                .read_record(sector_index, record_offset)
+                .unwrap();
+
+            assert_eq!(record.sector_index, sector_index);
+            assert_eq!(record.record_offset, record_offset);
+            assert_eq!(
+                record.encoded_piece.as_ref(),
+                &test_plot.plot[(sector_offset * plot_sector_size
+                    + record_offset * PIECE_SIZE as u64)
+                    as usize..][..PIECE_SIZE]
+            );
+            assert_eq!(record.sector_metadata.encode(), expected_sector_metadata);
+        }
+    }
+
+    // Out of range
+    assert!(read_only_plot
+        .read_record(SectorIndex::new(test_plot.first_sector_index.get() - 1), 0)
+        .is_err());
+    assert!(read_only_plot
+        .read_record(
+            test_plot.first_sector_index.offset(test_plot.sector_count),
+            0
+        )
+        .is_err());
+    assert!(read_only_plot
+        .read_record(test_plot.first_sector_index, records_in_sector)
+        .is_err());
+}
+
+#[test]
+fn audit_plot_file_by_path() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    let plot_file_path = directory.path().join(SingleDiskPlot::PLOT_FILE);
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let global_challenge = rand::random();
+
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+
+    // Max solution range results in every sector being eligible
+    let eligible_sectors = audit_plot_file(
+        &plot_file_path,
+        &test_plot.public_key,
+        &test_plot.farmer_protocol_info,
+        &kzg,
+        &global_challenge,
+        u64::MAX,
+        None,
+    )
+    .unwrap()
+    .eligible_sectors;
+    assert_eq!(eligible_sectors.len() as u64, test_plot.sector_count);
+    for (sector_offset, eligible_sector) in (0..).zip(&eligible_sectors) {
+        let expected_eligible_sector = read_only_plot
+            .audit_sector(sector_offset, &kzg, &global_challenge, u64::MAX)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            eligible_sector.sector_index,
+            test_plot.first_sector_index.offset(sector_offset)
+        );
+        assert_eq!(
+            eligible_sector.audit_index,
+            expected_eligible_sector.audit_index
+        );
+        assert_eq!(eligible_sector.chunk, expected_eligible_sector.chunk);
+        assert_eq!(
+            eligible_sector.encoded_piece,
+            expected_eligible_sector.encoded_piece
+        );
+    }
+
+    // Nothing is eligible with zero solution range
+    assert!(audit_plot_file(
+        &plot_file_path,
+        &test_plot.public_key,
+        &test_plot.farmer_protocol_info,
+        &kzg,
+        &global_challenge,
+        0,
+        None,
+    )
+    .unwrap()
+    .eligible_sectors
+    .is_empty());
+
+    assert!(matches!(
+        audit_plot_file(
+            &directory.path().join(SingleDiskPlot::METADATA_FILE),
+            &test_plot.public_key,
+            &test_plot.farmer_protocol_info,
+            &kzg,
+            &global_challenge,
+            u64::MAX,
+            None,
+        ),
+        Err(AuditPlotFileError::NotInPlotDirectory { .. })
+    ));
+
+    let other_public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    assert!(matches!(
+        audit_plot_file(
+            &plot_file_path,
+            &other_public_key,
+            &test_plot.farmer_protocol_info,
+            &kzg,
+            &global_challenge,
+            u64::MAX,
+            None,
+        ),
+        Err(AuditPlotFileError::PublicKeyMismatch { plot, supplied })
+            if plot == test_plot.public_key && supplied == other_public_key
+    ));
+
+    let other_farmer_protocol_info = FarmerProtocolInfo {
+        space_l: NonZeroU16::new(20).unwrap(),
+        ..test_plot.farmer_protocol_info
+    };
+    assert!(matches!(
+        audit_plot_file(
+            &plot_file_path,
+            &test_plot.public_key,
+            &other_farmer_protocol_info,
+            &kzg,
+            &global_challenge,
+            u64::MAX,
+            None,
+        ),
+        Err(AuditPlotFileError::FarmerProtocolInfoMismatch)
+    ));
+}
+
+#[test]
+fn audit_plot_file_custom_thread_pool() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    let plot_file_path = directory.path().join(SingleDiskPlot::PLOT_FILE);
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let global_challenge = rand::random();
+    let thread_pool = ThreadPoolBuilder::new()
+        .num_threads(2)
+        .thread_name(|index| format!("custom-audit-{index}"))
+        .build()
+        .unwrap();
+
+    let audit = |thread_pool| {
+        audit_plot_file(
+            &plot_file_path,
+            &test_plot.public_key,
+            &test_plot.farmer_protocol_info,
+            &kzg,
+            &global_challenge,
+            u64::MAX,
+            thread_pool,
+        )
+        .unwrap()
+        .eligible_sectors
+        .into_iter()
+        .map(|eligible_sector| (eligible_sector.sector_index, eligible_sector.audit_index))
+        .collect::<Vec<_>>()
+    };
+    let eligible_sectors = audit(Some(&thread_pool));
+    assert_eq!(eligible_sectors.len() as u64, test_plot.sector_count);
+    assert_eq!(eligible_sectors, audit(None));
+
+    // Parallel work is done by threads of the custom pool only
+    let thread_names = in_thread_pool(Some(&thread_pool), || {
+        (0..100)
+            .into_par_iter()
+            .map(|_| thread::current().name().map(String::from))
+            .collect::<Vec<_>>()
+    });
+    assert!(thread_names.iter().all(|thread_name| thread_name
+        .as_deref()
+        .map_or(false, |thread_name| thread_name
+            .starts_with("custom-audit-"))));
+
+    // And not by them when no pool is specified
+    let thread_names = in_thread_pool(None, || {
+        (0..100)
+            .into_par_iter()
+            .map(|_| thread::current().name().map(String::from))
+            .collect::<Vec<_>>()
+    });
+    assert!(!thread_names.iter().any(|thread_name| thread_name
+        .as_deref()
+        .map_or(false, |thread_name| thread_name
+            .starts_with("custom-audit-"))));
+}
+
+#[test]
+fn audit_plot_first_stops_early() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    let plot_file_path = directory.path().join(SingleDiskPlot::PLOT_FILE);
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let global_challenge = rand::random();
+    // Single thread audits sectors one after another, so number of audited sectors is predictable
+    let thread_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    assert!(test_plot.sector_count > 1);
+
+    // Maximum solution range makes every sector eligible
+    let audit = |solutions_needed: usize, thread_pool| {
+        audit_plot_first(
+            &plot_file_path,
+            &test_plot.public_key,
+            &test_plot.farmer_protocol_info,
+            &kzg,
+            &global_challenge,
+            u64::MAX,
+            NonZeroUsize::new(solutions_needed).unwrap(),
+            thread_pool,
+        )
+        .unwrap()
+    };
+
+    let report = audit(1, Some(&thread_pool));
+    assert_eq!(report.eligible_sectors.len(), 1);
+    assert_eq!(report.audited_sectors, 1);
+    assert!(report.errors.is_empty());
+
+    // Asking for more solutions than there are sectors audits the whole plot
+    let report = audit(test_plot.sector_count as usize + 1, Some(&thread_pool));
+    assert_eq!(report.eligible_sectors.len() as u64, test_plot.sector_count);
+    assert_eq!(report.audited_sectors, test_plot.sector_count);
+
+    // Concurrent audits never return more solutions than requested
+    let report = audit(1, None);
+    assert_eq!(report.eligible_sectors.len(), 1);
+    assert!(report.audited_sectors >= 1);
+
+    // Regular audit is not limited
+    let report = audit_plot_file(
+        &plot_file_path,
+        &test_plot.public_key,
+        &test_plot.farmer_protocol_info,
+        &kzg,
+        &global_challenge,
+        u64::MAX,
+        None,
+    )
+    .unwrap();
+    assert_eq!(report.eligible_sectors.len() as u64, test_plot.sector_count);
+    assert_eq!(report.audited_sectors, test_plot.sector_count);
+}
+
+#[test]
+fn inconsistent_plot_layout() {
+    let assert_inconsistent =
+        |directory: &Path, id: SingleDiskPlotId, expected: (u64, u64, u64, u64)| {
+            let result = PlotReader::open(directory);
+            assert!(
+                matches!(
+                    result,
+                    Err(SingleDiskPlotError::InconsistentPlotLayout {
+                        id: error_id,
+                        allocated_space,
+                        sector_count,
+                        plot_sector_size,
+                        plot_file_size,
+                    }) if error_id == id
+                        && (allocated_space, sector_count, plot_sector_size, plot_file_size)
+                            == expected
+                ),
+                "{result:?}"
+            );
+        };
+
+    // Allocated space
+    {
+        let directory = TempDir::new().unwrap();
+        let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+        test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+        let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+
+        SingleDiskPlotInfo::new(
+            test_plot.id,
+            test_plot.farmer_protocol_info.genesis_hash,
+            test_plot.public_key,
+            test_plot.first_sector_index,
+            plot_sector_size,
+        )
+        .store_to(directory.path())
+        .unwrap();
+
+        assert_inconsistent(
+            directory.path(),
+            test_plot.id,
+            (
+                plot_sector_size,
+                test_plot.sector_count,
+                plot_sector_size,
+                test_plot.plot.len() as u64,
+            ),
+        );
+    }
+
+    // Sector count
+    {
+        let directory = TempDir::new().unwrap();
+        let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+        test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+        let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+
+        metadata_file
+            .write_all_at(
+                &PlotMetadataHeader {
+                    version: PlotMetadataHeader::LATEST_VERSION,
+                    sector_count: test_plot.sector_count + 1,
+                }
+                .encode(),
+                0,
+            )
+            .unwrap();
+
+        assert_inconsistent(
+            directory.path(),
+            test_plot.id,
+            (
+                plot_sector_size * test_plot.sector_count,
+                test_plot.sector_count + 1,
+                plot_sector_size,
+                test_plot.plot.len() as u64,
+            ),
+        );
+    }
+
+    // Sector size recomputed from recorded protocol parameters
+    {
+        let directory = TempDir::new().unwrap();
+        let (mut test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+        let original_plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+        test_plot.farmer_protocol_info.space_l = NonZeroU16::new(15).unwrap();
+        test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+
+        assert_inconsistent(
+            directory.path(),
+            test_plot.id,
+            (
+                original_plot_sector_size * test_plot.sector_count,
+                test_plot.sector_count,
+                plot_sector_size(test_plot.farmer_protocol_info.space_l),
+                test_plot.plot.len() as u64,
+            ),
+        );
+    }
+
+    // Plot file size
+    {
+        let directory = TempDir::new().unwrap();
+        let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+        test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+        let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+
+        fs::OpenOptions::new()
+            .write(true)
+            .open(directory.path().join(SingleDiskPlot::PLOT_FILE))
+            .unwrap()
+            .set_len(test_plot.plot.len() as u64 - PIECE_SIZE as u64)
+            .unwrap();
+
+        assert_inconsistent(
+            directory.path(),
+            test_plot.id,
+            (
+                plot_sector_size * test_plot.sector_count,
+                test_plot.sector_count,
+                plot_sector_size,
+                test_plot.plot.len() as u64 - PIECE_SIZE as u64,
+            ),
+        );
+    }
+}
+
+#[test]
+fn audit_plot_data_partial_failure() {
+    let directory = TempDir::new().unwrap();
+    let (test_plot, metadata_file, metadata_header) = TestPlot::create(directory.path());
+    test_plot.finish(&metadata_file, metadata_header, KzgParametersId::TEST);
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let global_challenge = rand::random();
+    let plot_sector_size = plot_sector_size(test_plot.farmer_protocol_info.space_l);
+
+    let read_only_plot = PlotReader::open(directory.path()).unwrap();
+
+    // The last sector is cut off and can't be read
+    let report = audit_plot_data(
+        &read_only_plot,
+        PlotData::Mmap(
+            &test_plot.plot[..(plot_sector_size * (test_plot.sector_count - 1)) as usize],
+        ),
+        &test_plot.farmer_protocol_info,
+        &kzg,
+        &global_challenge,
+        u64::MAX,
+        None,
+    );
+
+    // The rest of the sectors are still audited
+    assert_eq!(
+        report
+            .eligible_sectors
+            .iter()
+            .map(|eligible_sector| eligible_sector.sector_index)
+            .collect::<Vec<_>>(),
+        (0..test_plot.sector_count - 1)
+            .map(|sector_offset| test_plot.first_sector_index.offset(sector_offset))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(
+        report.errors[0].sector_index,
+        test_plot
+            .first_sector_index
+            .offset(test_plot.sector_count - 1)
+    );
+    assert!(matches!(
+        &report.errors[0].error,
+        FarmingError::Io(error) if error.kind() == io::ErrorKind::UnexpectedEof
+    ));
+}
@@ -0,0 +1,459 @@
+//! Coalescing of positional reads issued during audit.
+//!
+//! Audit of every sector reads a piece at challenge-derived offset, which with positional reads
+//! (see [`StorageBackend::Network`](crate::single_disk_plot::storage_backend::StorageBackend))
+//! means at least one syscall (and network round-trip) per sector. [`ReadPlanner`] collects reads of
+//! all sectors audited in a slot, merges those that are at most a configurable gap apart and issues
+//! one read per merged range, after which results are sliced back out per request.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::audit_cache::AuditCache;
+use crate::single_disk_plot::farming::audit_piece_offset;
+use crate::single_disk_plot::sector_locks::{SectorLocks, SectorReadGuard};
+use crate::single_disk_plot::sector_metadata::{
+    sector_metadata_record_offset, SectorMetadataRecord, SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::storage_backend::sector_start;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use subspace_core_primitives::{Blake2b256Hash, Piece, PublicKey, SectorIndex, PIECE_SIZE};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tracing::debug;
+
+/// Maximum number of requests served by a single coalesced read, keeps number of buffers of a
+/// vectored read (requests and gaps between them) under `IOV_MAX` of Linux
+const MAX_REQUESTS_PER_READ: usize = 512;
+
+#[derive(Debug, Copy, Clone)]
+struct ReadRequest {
+    offset: u64,
+    len: usize,
+}
+
+/// Single read that serves one or more requests
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CoalescedRead {
+    /// Offset of the first byte read
+    pub offset: u64,
+    /// Number of bytes read, including gaps between requests
+    pub length: u64,
+    /// Indexes of served requests sorted by their offsets
+    pub requests: Vec<usize>,
+}
+
+/// Planner of reads that coalesces requested ranges at most `max_gap` bytes apart into single
+/// reads
+#[derive(Debug, Clone)]
+pub struct ReadPlanner {
+    max_gap: u64,
+    requests: Vec<ReadRequest>,
+}
+
+impl ReadPlanner {
+    /// Create planner that merges ranges with at most `max_gap` bytes between them, `0` only
+    /// merges adjacent and overlapping ranges
+    pub fn new(max_gap: u64) -> Self {
+        Self {
+            max_gap,
+            requests: Vec::new(),
+        }
+    }
+
+    /// Request `len` bytes at `offset`, returns index of the request in [`ReadResults`]
+    pub fn add(&mut self, offset: u64, len: usize) -> usize {
+        self.requests.push(ReadRequest { offset, len });
+        self.requests.len() - 1
+    }
+
+    /// Number of requests added so far
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Whether no requests were added
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Reads that will be issued to serve all requests, sorted by offset
+    pub fn plan(&self) -> Vec<CoalescedRead> {
+        let mut sorted_requests = (0..self.requests.len()).collect::<Vec<_>>();
+        sorted_requests.sort_by_key(|&index| {
+            let request = self.requests[index];
+            (request.offset, request.len)
+        });
+
+        let mut reads = Vec::<CoalescedRead>::new();
+        for index in sorted_requests {
+            let request = self.requests[index];
+            let request_end = request.offset + request.len as u64;
+
+            match reads.last_mut() {
+                Some(read)
+                    if request.offset
+                        <= (read.offset + read.length).saturating_add(self.max_gap)
+                        && read.requests.len() < MAX_REQUESTS_PER_READ =>
+                {
+                    read.length = read.length.max(request_end - read.offset);
+                    read.requests.push(index);
+                }
+                _ => {
+                    reads.push(CoalescedRead {
+                        offset: request.offset,
+                        length: request.len as u64,
+                        requests: vec![index],
+                    });
+                }
+            }
+        }
+
+        reads
+    }
+
+    /// Issue planned reads to `file` and return results of all requests.
+    ///
+    /// On Linux reads are vectored (`preadv`), such that requested bytes are read directly into
+    /// results and gaps between requests into a scratch buffer, on other platforms (or when
+    /// requests overlap) the whole coalesced range is read and requested bytes are copied out.
+    pub fn read(&self, file: &File) -> io::Result<ReadResults> {
+        // Results are stored one after another in the same order requests were added
+        let mut ranges = Vec::with_capacity(self.requests.len());
+        let mut total_len = 0;
+        for request in &self.requests {
+            ranges.push(total_len..total_len + request.len);
+            total_len += request.len;
+        }
+        let mut data = vec![0u8; total_len];
+
+        let reads = self.plan();
+        for read in &reads {
+            self.issue_read(file, read, &ranges, &mut data)?;
+        }
+
+        Ok(ReadResults {
+            data,
+            ranges,
+            reads: reads.len(),
+        })
+    }
+
+    fn issue_read(
+        &self,
+        file: &File,
+        read: &CoalescedRead,
+        ranges: &[Range<usize>],
+        data: &mut [u8],
+    ) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            let overlapping = read.requests.windows(2).any(|pair| {
+                let previous = self.requests[pair[0]];
+                previous.offset + previous.len as u64 > self.requests[pair[1]].offset
+            });
+
+            if !overlapping {
+                return self.issue_vectored_read(file, read, ranges, data);
+            }
+        }
+
+        let mut buffer = vec![0u8; read.length as usize];
+        file.read_exact_at(&mut buffer, read.offset)?;
+        for &index in &read.requests {
+            let request = self.requests[index];
+            let start = (request.offset - read.offset) as usize;
+            data[ranges[index].clone()].copy_from_slice(&buffer[start..][..request.len]);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn issue_vectored_read(
+        &self,
+        file: &File,
+        read: &CoalescedRead,
+        ranges: &[Range<usize>],
+        data: &mut [u8],
+    ) -> io::Result<()> {
+        let largest_gap = read
+            .requests
+            .windows(2)
+            .map(|pair| {
+                let previous = self.requests[pair[0]];
+                self.requests[pair[1]].offset - (previous.offset + previous.len as u64)
+            })
+            .max()
+            .unwrap_or_default();
+        // The same scratch buffer receives every gap, its contents are never used
+        let mut scratch = vec![0u8; largest_gap as usize];
+
+        let data_ptr = data.as_mut_ptr();
+        let mut iovecs = Vec::with_capacity(read.requests.len() * 2);
+        let mut position = read.offset;
+        for &index in &read.requests {
+            let request = self.requests[index];
+            if request.offset > position {
+                iovecs.push(libc::iovec {
+                    iov_base: scratch.as_mut_ptr().cast(),
+                    iov_len: (request.offset - position) as usize,
+                });
+            }
+            iovecs.push(libc::iovec {
+                // SAFETY: Range of every request is within `data`
+                iov_base: unsafe { data_ptr.add(ranges[index].start) }.cast(),
+                iov_len: request.len,
+            });
+            position = request.offset + request.len as u64;
+        }
+
+        preadv_exact(file, iovecs, read.offset)
+    }
+}
+
+/// Read into all buffers described by `iovecs` starting at `offset` of `file`, retrying on short
+/// reads.
+///
+/// Buffers must stay valid for the duration of the call and must not be accessed otherwise.
+#[cfg(target_os = "linux")]
+fn preadv_exact(file: &File, mut iovecs: Vec<libc::iovec>, mut offset: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    iovecs.retain(|iovec| iovec.iov_len > 0);
+    let mut first = 0;
+    while first < iovecs.len() {
+        let remaining = &iovecs[first..];
+        let file_offset = libc::off_t::try_from(offset)
+            .map_err(|_error| io::Error::new(io::ErrorKind::InvalidInput, "Offset is too large"))?;
+        // SAFETY: Buffers are valid for writes of their length as required from the caller
+        let read = unsafe {
+            libc::preadv(
+                file.as_raw_fd(),
+                remaining.as_ptr(),
+                remaining.len() as libc::c_int,
+                file_offset,
+            )
+        };
+        if read < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(error);
+        }
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+
+        let mut read = read as usize;
+        offset += read as u64;
+        // Skip buffers that were filled and advance the one that was filled partially
+        while read > 0 {
+            let iovec = &mut iovecs[first];
+            if read >= iovec.iov_len {
+                read -= iovec.iov_len;
+                first += 1;
+            } else {
+                // SAFETY: Still within the same buffer
+                iovec.iov_base = unsafe { iovec.iov_base.cast::<u8>().add(read) }.cast();
+                iovec.iov_len -= read;
+                read = 0;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Results of requests of [`ReadPlanner`]
+#[derive(Debug)]
+pub struct ReadResults {
+    data: Vec<u8>,
+    ranges: Vec<Range<usize>>,
+    reads: usize,
+}
+
+impl ReadResults {
+    /// Bytes of request with `index` returned by [`ReadPlanner::add()`]
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        self.data.get(self.ranges.get(index)?.clone())
+    }
+
+    /// Number of reads that were issued to serve all requests
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+}
+
+/// Piece read ahead of the audit of its sector.
+///
+/// Read lock of the sector is held until sector is audited, so that prefetched contents can't be
+/// replaced by replotting in the meantime.
+pub(crate) struct PrefetchedPiece<'a> {
+    pub(crate) sector_read_guard: SectorReadGuard<'a>,
+    /// Offset of the piece within sector in bytes
+    pub(crate) offset: u64,
+    pub(crate) piece: Piece,
+}
+
+/// Read pieces that will be audited in sectors at `sector_offsets` with coalesced reads, returns
+/// prefetched pieces by sector offset.
+///
+/// Sectors that are being written, don't have valid metadata record or whose audited piece is
+/// already in `audit_cache` are not prefetched.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn prefetch_audited_pieces<'a>(
+    plot_file: &File,
+    sector_locks: &'a SectorLocks,
+    audit_cache: &AuditCache,
+    sector_metadata: &[u8],
+    sector_offsets: &[u64],
+    sector_stride: u64,
+    first_sector_index: SectorIndex,
+    public_key: &PublicKey,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    global_challenge: &Blake2b256Hash,
+    max_gap: u64,
+) -> io::Result<HashMap<u64, PrefetchedPiece<'a>>> {
+    let mut planner = ReadPlanner::new(max_gap);
+    let mut planned = Vec::new();
+
+    for &sector_offset in sector_offsets {
+        let sector_index = first_sector_index.offset(sector_offset);
+        let sector_read_guard = match sector_locks.try_read(sector_offset) {
+            Some(sector_read_guard) => sector_read_guard,
+            None => {
+                continue;
+            }
+        };
+        let rotation = match SectorMetadataRecord::decode(
+            &sector_metadata[sector_metadata_record_offset(sector_offset) as usize..]
+                [..SECTOR_METADATA_RECORD_SIZE],
+            SectorMetadataRecord::LATEST_VERSION,
+        ) {
+            Ok(record) => record.rotation,
+            Err(_error) => {
+                continue;
+            }
+        };
+        let sector_start = match sector_start(sector_offset, sector_stride) {
+            Ok(sector_start) => sector_start,
+            Err(_error) => {
+                continue;
+            }
+        };
+
+        let piece_offset = audit_piece_offset(
+            public_key,
+            sector_index,
+            &FarmerProtocolInfo {
+                rotation,
+                ..*farmer_protocol_info
+            },
+            global_challenge,
+        );
+        if audit_cache.contains(sector_index, piece_offset) {
+            continue;
+        }
+
+        let offset = piece_offset * PIECE_SIZE as u64;
+        let request = planner.add(sector_start + offset, PIECE_SIZE);
+        planned.push((sector_offset, sector_read_guard, offset, request));
+    }
+
+    if planner.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let results = planner.read(plot_file)?;
+    debug!(
+        requested = %planner.len(),
+        reads = %results.reads(),
+        "Prefetched audited pieces"
+    );
+
+    Ok(planned
+        .into_iter()
+        .map(|(sector_offset, sector_read_guard, offset, request)| {
+            let piece = Piece::try_from(
+                results
+                    .get(request)
+                    .expect("Request was added to the planner above; qed"),
+            )
+            .expect("Exactly one piece was requested; qed");
+
+            (
+                sector_offset,
+                PrefetchedPiece {
+                    sector_read_guard,
+                    offset,
+                    piece,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Sector reader that serves reads of prefetched bytes from memory and delegates everything else
+/// to the wrapped sector reader, which must be positioned at the beginning of the sector
+pub(crate) struct PrefetchedSectorReader<'a, S> {
+    inner: S,
+    position: u64,
+    /// Offset within sector and prefetched bytes
+    prefetched: Option<(u64, &'a [u8])>,
+}
+
+impl<'a, S> PrefetchedSectorReader<'a, S> {
+    pub(crate) fn new(inner: S, prefetched: Option<(u64, &'a [u8])>) -> Self {
+        Self {
+            inner,
+            position: 0,
+            prefetched,
+        }
+    }
+}
+
+impl<S> Read for PrefetchedSectorReader<'_, S>
+where
+    S: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let maybe_prefetched = self.prefetched.and_then(|(offset, bytes)| {
+            let start = usize::try_from(self.position.checked_sub(offset)?).ok()?;
+            bytes.get(start..).filter(|bytes| !bytes.is_empty())
+        });
+
+        match maybe_prefetched {
+            Some(prefetched) => {
+                let len = prefetched.len().min(buf.len());
+                buf[..len].copy_from_slice(&prefetched[..len]);
+                self.position += len as u64;
+                self.inner.seek(SeekFrom::Start(self.position))?;
+                Ok(len)
+            }
+            None => {
+                let len = self.inner.read(buf)?;
+                self.position += len as u64;
+                Ok(len)
+            }
+        }
+    }
+}
+
+impl<S> Seek for PrefetchedSectorReader<'_, S>
+where
+    S: Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
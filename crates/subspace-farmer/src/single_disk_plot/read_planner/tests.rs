@@ -0,0 +1,150 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::read_planner::{
+    CoalescedRead, PrefetchedSectorReader, ReadPlanner, MAX_REQUESTS_PER_READ,
+};
+use rand::prelude::*;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use tempfile::tempfile;
+
+#[test]
+fn plan_merges_ranges_within_gap() {
+    let mut planner = ReadPlanner::new(10);
+    let far = planner.add(1000, 10);
+    let first = planner.add(0, 10);
+    let within_gap = planner.add(20, 10);
+    let overlapping = planner.add(25, 10);
+    let beyond_gap = planner.add(46, 4);
+    let adjacent = planner.add(50, 5);
+
+    assert_eq!(
+        planner.plan(),
+        vec![
+            CoalescedRead {
+                offset: 0,
+                length: 35,
+                requests: vec![first, within_gap, overlapping],
+            },
+            CoalescedRead {
+                offset: 46,
+                length: 9,
+                requests: vec![beyond_gap, adjacent],
+            },
+            CoalescedRead {
+                offset: 1000,
+                length: 10,
+                requests: vec![far],
+            },
+        ]
+    );
+
+    // Only adjacent and overlapping ranges are merged without gap
+    let mut planner = ReadPlanner::new(0);
+    planner.add(0, 10);
+    planner.add(10, 10);
+    planner.add(21, 10);
+    assert_eq!(planner.plan().len(), 2);
+
+    // Number of requests served by one read is limited
+    let mut planner = ReadPlanner::new(0);
+    for offset in 0..MAX_REQUESTS_PER_READ as u64 + 1 {
+        planner.add(offset * 10, 10);
+    }
+    let plan = planner.plan();
+    assert_eq!(plan.len(), 2);
+    assert_eq!(plan[0].requests.len(), MAX_REQUESTS_PER_READ);
+    assert_eq!(plan[1].requests.len(), 1);
+
+    assert!(ReadPlanner::new(0).plan().is_empty());
+}
+
+#[test]
+fn coalesced_reads_match_individual_reads() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut contents = vec![0u8; 1024 * 1024];
+    rng.fill(contents.as_mut_slice());
+    let mut file = tempfile().unwrap();
+    file.write_all(&contents).unwrap();
+
+    for max_gap in [0, 100, 4096, u64::MAX] {
+        let mut planner = ReadPlanner::new(max_gap);
+        let requests = (0..200)
+            .map(|_| {
+                let len = rng.gen_range(0..2000);
+                let offset = rng.gen_range(0..contents.len() - len) as u64;
+                (planner.add(offset, len), offset, len)
+            })
+            .collect::<Vec<_>>();
+        // Overlapping requests and requests for the same bytes are served too
+        let (_index, offset, len) = requests[0];
+        let requests = requests
+            .into_iter()
+            .chain([
+                (planner.add(offset, len), offset, len),
+                (planner.add(offset + 1, len), offset + 1, len),
+            ])
+            .collect::<Vec<_>>();
+
+        let results = planner.read(&file).unwrap();
+        assert_eq!(results.reads(), planner.plan().len());
+        if max_gap == u64::MAX {
+            assert_eq!(results.reads(), 1);
+        }
+
+        for (index, offset, len) in requests {
+            let mut expected = vec![0u8; len];
+            file.read_exact_at(&mut expected, offset).unwrap();
+            assert_eq!(results.get(index).unwrap(), expected.as_slice());
+        }
+    }
+
+    // Reads past the end of file fail
+    let mut planner = ReadPlanner::new(0);
+    planner.add(contents.len() as u64 - 10, 20);
+    assert!(planner.read(&file).is_err());
+}
+
+#[test]
+fn prefetched_sector_reader_matches_sector() {
+    let mut sector = vec![0u8; 1000];
+    StdRng::seed_from_u64(1).fill(sector.as_mut_slice());
+    let prefetched_offset = 300;
+    let prefetched = sector[prefetched_offset..][..200].to_vec();
+
+    for (position, len) in [
+        (300, 200),
+        (250, 100),
+        (350, 300),
+        (0, 1000),
+        (500, 100),
+        (450, 10),
+    ] {
+        let mut reader = PrefetchedSectorReader::new(
+            Cursor::new(sector.as_slice()),
+            Some((prefetched_offset as u64, &prefetched[..])),
+        );
+        reader.seek(SeekFrom::Start(position)).unwrap();
+        let mut buffer = vec![0u8; len];
+        reader.read_exact(&mut buffer).unwrap();
+
+        assert_eq!(buffer, sector[position as usize..][..len]);
+        assert_eq!(reader.stream_position().unwrap(), position + len as u64);
+    }
+
+    // Prefetched bytes are served from memory and reading continues in the sector after them
+    let mut sector_without_prefetched = sector.clone();
+    sector_without_prefetched[prefetched_offset..][..200].fill(0);
+    let mut reader = PrefetchedSectorReader::new(
+        Cursor::new(sector_without_prefetched.as_slice()),
+        Some((prefetched_offset as u64, &prefetched[..])),
+    );
+    reader.seek(SeekFrom::Start(400)).unwrap();
+    let mut buffer = vec![0u8; 300];
+    reader.read_exact(&mut buffer).unwrap();
+    assert_eq!(buffer, sector[400..700]);
+
+    // Without prefetched bytes everything is read from the sector
+    let mut reader = PrefetchedSectorReader::new(Cursor::new(sector.as_slice()), None);
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer).unwrap();
+    assert_eq!(buffer, sector);
+}
@@ -0,0 +1,803 @@
+//! Thin protocol for reading plot remotely.
+//!
+//! Plot server exposes positional reads of the plot file over TCP (optionally with TLS), so that
+//! plot can be stored on one machine and audited by the farmer on another. Every message is a
+//! frame prefixed with its length as little-endian `u32`, followed by SCALE-encoded payload. Reads
+//! necessary to audit plot for one slot are sent in as few requests as possible (a single one for
+//! plots of up to [`MAX_RANGES_PER_REQUEST`] sectors), [`PlotMetadata`] describes plot layout and
+//! sectors, so that farmer doesn't need any files of the plot locally, see
+//! [`remote_farming`](crate::single_disk_plot::remote_farming).
+//!
+//! Plot server is meant to be reachable by farmers only, but still doesn't trust its peers: request
+//! frames are limited to a small size, number of concurrent connections is limited and with TLS
+//! clients can be required to present certificate issued by a specific certificate authority.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::repeated_errors::RepeatedErrors;
+use crate::single_disk_plot::farming::{audit_piece_offset, audit_sector, EligibleSector};
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::storage_backend::{sector_start, sector_stride, PieceSector};
+use crate::single_disk_plot::{
+    FarmingError, SectorMetadata, SingleDiskPlot, SingleDiskPlotError, SingleDiskPlotInfo,
+};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use parity_scale_codec::{Decode, Encode};
+use parking_lot::Mutex;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, io};
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, PublicKey, SectorIndex, SolutionRange, PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{
+    Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig, ServerName,
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tracing::{debug, info};
+
+/// Version of the protocol, exchanged by both sides right after connection is established
+const PROTOCOL_VERSION: u8 = 1;
+/// Maximum size of request frame accepted by plot server, requests only contain ranges to read, so
+/// peers can't make server allocate much memory before request is even decoded
+const MAX_REQUEST_FRAME_SIZE: u32 = 64 * 1024;
+/// Maximum size of response frame accepted by [`RemoteReadAt`], fits [`MAX_READ_SIZE`] bytes of
+/// contents
+const MAX_RESPONSE_FRAME_SIZE: u32 = 256 * 1024 * 1024;
+/// Time new connection has to complete TLS handshake and protocol version exchange, so that
+/// connections that never start talking don't occupy connection slots of plot server forever
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum number of bytes that can be read with a single request
+const MAX_READ_SIZE: u64 = 128 * 1024 * 1024;
+/// Maximum number of ranges [`audit_sectors()`] reads with a single request, keeps requests below
+/// [`MAX_REQUEST_FRAME_SIZE`] and pieces of all ranges within [`MAX_READ_SIZE`]
+pub const MAX_RANGES_PER_REQUEST: usize = 4096;
+/// Maximum number of idle connections kept open by [`RemoteReadAt`]
+const MAX_IDLE_CONNECTIONS: usize = 4;
+
+/// Range of bytes within plot file
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Encode, Decode)]
+pub struct ReadRange {
+    /// Offset in bytes
+    pub offset: u64,
+    /// Length in bytes
+    pub length: u32,
+}
+
+impl ReadRange {
+    fn end(&self) -> u64 {
+        self.offset.saturating_add(u64::from(self.length))
+    }
+}
+
+/// Metadata of a plotted sector necessary to audit it and create solutions
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Encode, Decode)]
+pub struct RemoteSectorMetadata {
+    /// Rotation sector was plotted under
+    pub rotation: u64,
+    /// Sector metadata
+    pub sector_metadata: SectorMetadata,
+}
+
+/// Metadata of plot served by plot server, everything necessary to audit it and create solutions
+/// except identity
+#[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+pub struct PlotMetadata {
+    /// Public key of identity plot was created with
+    pub public_key: PublicKey,
+    /// Index of the first sector of the plot
+    pub first_sector_index: SectorIndex,
+    /// Farmer protocol info plot was created with
+    pub farmer_protocol_info: FarmerProtocolInfo,
+    /// Identifier of KZG parameters plot was created with
+    pub kzg_parameters_id: KzgParametersId,
+    /// Alignment of sectors within plot file
+    pub sector_alignment: u64,
+    /// Metadata of every plotted sector, `None` for sectors that are being replotted
+    pub sectors: Vec<Option<RemoteSectorMetadata>>,
+}
+
+impl PlotMetadata {
+    /// Number of plotted sectors
+    pub fn sector_count(&self) -> u64 {
+        self.sectors.len() as u64
+    }
+
+    fn read_from(directory: &Path) -> Result<Self, SingleDiskPlotError> {
+        // Plot is opened for every request, so that sectors plotted in the meantime are included
+        let plot = PlotReader::open(directory)?;
+
+        let sectors = (0..plot.sector_count())
+            .map(
+                |sector_offset| match plot.read_sector_metadata_record(sector_offset) {
+                    Ok(record) => Ok(Some(RemoteSectorMetadata {
+                        rotation: record.rotation,
+                        sector_metadata: record.sector_metadata,
+                    })),
+                    // Records of sectors that are being replotted are cleared
+                    Err(error) if error.kind() == io::ErrorKind::InvalidData => Ok(None),
+                    Err(error) => Err(error),
+                },
+            )
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            public_key: *plot.info().public_key(),
+            first_sector_index: plot.info().first_sector_index(),
+            farmer_protocol_info: *plot.farmer_protocol_info(),
+            kzg_parameters_id: plot.kzg_parameters_id(),
+            sector_alignment: plot.sector_alignment(),
+            sectors,
+        })
+    }
+}
+
+#[derive(Debug, Encode, Decode)]
+enum Request {
+    /// Read ranges of plot file
+    #[codec(index = 0)]
+    Read(Vec<ReadRange>),
+    /// Get [`PlotMetadata`]
+    #[codec(index = 1)]
+    Metadata,
+}
+
+#[derive(Debug, Encode, Decode)]
+enum Response {
+    /// Contents of requested ranges in the same order
+    #[codec(index = 0)]
+    Read(Vec<Vec<u8>>),
+    /// Metadata of the plot
+    #[codec(index = 1)]
+    Metadata(PlotMetadata),
+    /// Request failed
+    #[codec(index = 2)]
+    Err(String),
+}
+
+/// Source of plot contents for batched auditing, all reads necessary for one slot are issued at
+/// once
+#[async_trait]
+pub trait AuditReader: Send + Sync {
+    /// Read `ranges` of plot file, contents are returned in the same order as `ranges`
+    async fn read_ranges(&self, ranges: &[ReadRange]) -> io::Result<Vec<Vec<u8>>>;
+}
+
+/// Local plot file, reads are blocking
+#[async_trait]
+impl AuditReader for File {
+    async fn read_ranges(&self, ranges: &[ReadRange]) -> io::Result<Vec<Vec<u8>>> {
+        read_file_ranges(self, ranges)
+    }
+}
+
+fn read_file_ranges(file: &File, ranges: &[ReadRange]) -> io::Result<Vec<Vec<u8>>> {
+    let read_size = ranges
+        .iter()
+        .map(|range| u64::from(range.length))
+        .sum::<u64>();
+    if read_size > MAX_READ_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Requested {read_size} bytes, but at most {MAX_READ_SIZE} bytes can be read"),
+        ));
+    }
+
+    ranges
+        .iter()
+        .map(|range| {
+            let mut contents = vec![0; range.length as usize];
+            file.read_exact_at(&mut contents, range.offset)?;
+            Ok(contents)
+        })
+        .collect()
+}
+
+/// Audit sectors at `sector_offsets` within plot accessible through `reader`.
+///
+/// Audited pieces are read with one [`AuditReader::read_ranges()`] call per
+/// [`MAX_RANGES_PER_REQUEST`] sectors, calls are issued concurrently, which for [`RemoteReadAt`]
+/// means a single round trip per call. `sector_alignment` must be the alignment plot was created
+/// with, see [`PlotLayout::sector_alignment`](crate::single_disk_plot::PlotLayout).
+/// Sectors are audited with `farmer_protocol_info.rotation`, sectors plotted under different
+/// rotations need to be audited with separate calls.
+#[allow(clippy::too_many_arguments)]
+pub async fn audit_sectors<R>(
+    reader: &R,
+    public_key: &PublicKey,
+    first_sector_index: SectorIndex,
+    sector_offsets: &[u64],
+    farmer_protocol_info: &FarmerProtocolInfo,
+    sector_alignment: u64,
+    plot_kzg_parameters_id: KzgParametersId,
+    kzg: &Kzg,
+    global_challenge: &Blake2b256Hash,
+    solution_range: SolutionRange,
+) -> Result<Vec<EligibleSector>, FarmingError>
+where
+    R: AuditReader + ?Sized,
+{
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+    let sector_stride = sector_stride(plot_sector_size, sector_alignment)?;
+
+    let piece_offsets = sector_offsets
+        .iter()
+        .map(|&sector_offset| {
+            audit_piece_offset(
+                public_key,
+                first_sector_index.offset(sector_offset),
+                farmer_protocol_info,
+                global_challenge,
+            )
+        })
+        .collect::<Vec<_>>();
+    let ranges = sector_offsets
+        .iter()
+        .zip(&piece_offsets)
+        .map(|(&sector_offset, &piece_offset)| {
+            Ok(ReadRange {
+                offset: sector_start(sector_offset, sector_stride)?
+                    + piece_offset * PIECE_SIZE as u64,
+                length: PIECE_SIZE as u32,
+            })
+        })
+        .collect::<Result<Vec<_>, SingleDiskPlotError>>()?;
+
+    let pieces = try_join_all(
+        ranges
+            .chunks(MAX_RANGES_PER_REQUEST)
+            .map(|ranges| reader.read_ranges(ranges)),
+    )
+    .await?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    let mut eligible_sectors = Vec::new();
+    for ((&sector_offset, &piece_offset), piece) in
+        sector_offsets.iter().zip(&piece_offsets).zip(&pieces)
+    {
+        eligible_sectors.extend(audit_sector(
+            public_key,
+            first_sector_index.offset(sector_offset),
+            farmer_protocol_info,
+            plot_kzg_parameters_id,
+            kzg,
+            global_challenge,
+            solution_range,
+            PieceSector::new(plot_sector_size, piece_offset, piece),
+        )?);
+    }
+
+    Ok(eligible_sectors)
+}
+
+/// Create TLS acceptor for plot server from PEM files with certificate chain and private key.
+///
+/// If `client_ca_certificates` PEM file is specified, clients have to present certificate issued
+/// by one of certificate authorities from it, otherwise any client is accepted.
+pub fn tls_acceptor(
+    certificate_chain: &Path,
+    private_key: &Path,
+    client_ca_certificates: Option<&Path>,
+) -> io::Result<TlsAcceptor> {
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match client_ca_certificates {
+        Some(client_ca_certificates) => builder.with_client_cert_verifier(
+            AllowAnyAuthenticatedClient::new(read_root_certificates(client_ca_certificates)?),
+        ),
+        None => builder.with_no_client_auth(),
+    };
+    let config = builder
+        .with_single_cert(
+            read_certificates(certificate_chain)?,
+            read_private_key(private_key)?,
+        )
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Create TLS connector for [`RemoteReadAt`] that trusts certificate authorities from PEM file.
+///
+/// `client_identity` is a pair of PEM files with certificate chain and private key presented to
+/// plot server that requires client authentication.
+pub fn tls_connector(
+    ca_certificates: &Path,
+    client_identity: Option<(&Path, &Path)>,
+) -> io::Result<TlsConnector> {
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(read_root_certificates(ca_certificates)?);
+    let config = match client_identity {
+        Some((certificate_chain, private_key)) => builder
+            .with_single_cert(
+                read_certificates(certificate_chain)?,
+                read_private_key(private_key)?,
+            )
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?,
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+fn read_root_certificates(path: &Path) -> io::Result<RootCertStore> {
+    let mut root_certificates = RootCertStore::empty();
+    for certificate in read_certificates(path)? {
+        root_certificates
+            .add(&certificate)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    }
+
+    Ok(root_certificates)
+}
+
+fn read_certificates(path: &Path) -> io::Result<Vec<Certificate>> {
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+    if certificates.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("No certificates found in {}", path.display()),
+        ));
+    }
+
+    Ok(certificates.into_iter().map(Certificate).collect())
+}
+
+fn read_private_key(path: &Path) -> io::Result<PrivateKey> {
+    let mut private_keys =
+        rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(path)?))?;
+    if private_keys.is_empty() {
+        private_keys = rustls_pemfile::rsa_private_keys(&mut BufReader::new(File::open(path)?))?;
+    }
+
+    private_keys
+        .into_iter()
+        .next()
+        .map(PrivateKey)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No private key found in {}", path.display()),
+            )
+        })
+}
+
+/// Open plot file of the plot stored in `directory` for serving with [`serve_plot()`], plot split
+/// into shards is not supported
+pub fn open_plot_file(directory: &Path) -> Result<File, SingleDiskPlotError> {
+    let info = SingleDiskPlotInfo::load_from(directory)?.ok_or_else(|| {
+        SingleDiskPlotError::PlotInfoNotFound {
+            directory: directory.to_path_buf(),
+        }
+    })?;
+    if info.plot_shards().is_some() {
+        return Err(SingleDiskPlotError::OfflineAccessOfShardedPlot {
+            directory: directory.to_path_buf(),
+        });
+    }
+
+    let plot_file = OpenOptions::new()
+        .read(true)
+        .open(directory.join(SingleDiskPlot::PLOT_FILE))?;
+    plot_file.advise_random_access()?;
+
+    Ok(plot_file)
+}
+
+/// Serve positional reads of `plot_file` and [`PlotMetadata`] of the plot in `directory` to
+/// connections accepted by `listener`, runs until accepting new connections fails.
+///
+/// At most `max_connections` connections are served at the same time, further connections are
+/// only accepted once some of the existing connections are closed. Plot must not be farmed or
+/// plotted while being served, metadata requests fail if it is.
+pub async fn serve_plot(
+    listener: TcpListener,
+    directory: PathBuf,
+    plot_file: Arc<File>,
+    tls_acceptor: Option<TlsAcceptor>,
+    max_connections: NonZeroUsize,
+) -> io::Result<()> {
+    info!(
+        address = %listener.local_addr()?,
+        tls = %tls_acceptor.is_some(),
+        %max_connections,
+        "Serving plot"
+    );
+
+    let directory = Arc::new(directory);
+    let connection_permits = Arc::new(Semaphore::new(max_connections.get()));
+
+    loop {
+        let connection_permit = Arc::clone(&connection_permits)
+            .acquire_owned()
+            .await
+            .expect("Semaphore is never closed; qed");
+        let (stream, peer_address) = listener.accept().await?;
+        let directory = Arc::clone(&directory);
+        let plot_file = Arc::clone(&plot_file);
+        let tls_acceptor = tls_acceptor.clone();
+
+        tokio::spawn(async move {
+            let _connection_permit = connection_permit;
+
+            let result = match tls_acceptor {
+                Some(tls_acceptor) => {
+                    match with_handshake_timeout(tls_acceptor.accept(stream)).await {
+                        Ok(stream) => serve_connection(stream, directory, plot_file).await,
+                        Err(error) => Err(error),
+                    }
+                }
+                None => serve_connection(stream, directory, plot_file).await,
+            };
+
+            if let Err(error) = result {
+                debug!(%error, %peer_address, "Plot connection closed with error");
+            }
+        });
+    }
+}
+
+async fn serve_connection<S>(
+    mut stream: S,
+    directory: Arc<PathBuf>,
+    plot_file: Arc<File>,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    with_handshake_timeout(async {
+        stream.write_all(&[PROTOCOL_VERSION]).await?;
+        stream.flush().await?;
+        check_protocol_version(&mut stream).await
+    })
+    .await?;
+
+    loop {
+        let request = match read_frame::<_, Request>(&mut stream, MAX_REQUEST_FRAME_SIZE).await {
+            Ok(request) => request,
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                // Connection closed by the client
+                return Ok(());
+            }
+            Err(error) => {
+                return Err(error);
+            }
+        };
+
+        let directory = Arc::clone(&directory);
+        let plot_file = Arc::clone(&plot_file);
+        let response = match tokio::task::spawn_blocking(move || match request {
+            Request::Read(ranges) => read_file_ranges(&plot_file, &ranges)
+                .map(Response::Read)
+                .map_err(|error| error.to_string()),
+            Request::Metadata => PlotMetadata::read_from(&directory)
+                .map(Response::Metadata)
+                .map_err(|error| error.to_string()),
+        })
+        .await
+        {
+            Ok(Ok(response)) => response,
+            Ok(Err(error)) => Response::Err(error),
+            Err(error) => Response::Err(error.to_string()),
+        };
+
+        write_frame(&mut stream, &response, MAX_RESPONSE_FRAME_SIZE).await?;
+    }
+}
+
+async fn with_handshake_timeout<F, T>(future: F) -> io::Result<T>
+where
+    F: Future<Output = io::Result<T>>,
+{
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, future)
+        .await
+        .unwrap_or_else(|_elapsed| {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Handshake didn't complete in time",
+            ))
+        })
+}
+
+async fn check_protocol_version<S>(stream: &mut S) -> io::Result<()>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut version = [0];
+    stream.read_exact(&mut version).await?;
+    if version[0] != PROTOCOL_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Unsupported protocol version {}, expected {PROTOCOL_VERSION}",
+                version[0]
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn write_frame<S, T>(stream: &mut S, message: &T, max_length: u32) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+    T: Encode,
+{
+    // Length prefix and payload are written at once, so that they are sent in a single packet
+    let mut frame = Vec::with_capacity(4 + message.size_hint());
+    frame.extend_from_slice(&[0; 4]);
+    message.encode_to(&mut frame);
+    let length = u32::try_from(frame.len() - 4)
+        .ok()
+        .filter(|&length| length <= max_length)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Frame of {} bytes is too large", frame.len() - 4),
+            )
+        })?;
+    frame[..4].copy_from_slice(&length.to_le_bytes());
+
+    stream.write_all(&frame).await?;
+    stream.flush().await
+}
+
+async fn read_frame<S, T>(stream: &mut S, max_length: u32) -> io::Result<T>
+where
+    S: AsyncRead + Unpin,
+    T: Decode,
+{
+    let mut length = [0; 4];
+    stream.read_exact(&mut length).await?;
+    let length = u32::from_le_bytes(length);
+    if length > max_length {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame of {length} bytes is too large"),
+        ));
+    }
+
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload).await?;
+
+    T::decode(&mut payload.as_slice())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T> Connection for T where T: AsyncRead + AsyncWrite + Unpin + Send {}
+
+/// Ranges to read after coalescing adjacent and overlapping ranges
+#[derive(Debug)]
+struct CoalescedRanges {
+    ranges: Vec<ReadRange>,
+    /// Index of coalesced range and offset within it for every original range
+    sources: Vec<(usize, usize)>,
+}
+
+impl CoalescedRanges {
+    fn new(ranges: &[ReadRange]) -> Self {
+        let mut order = (0..ranges.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| ranges[index].offset);
+
+        let mut coalesced_ranges = Vec::<ReadRange>::with_capacity(ranges.len());
+        let mut sources = vec![(0, 0); ranges.len()];
+        for index in order {
+            let range = ranges[index];
+
+            if let Some(last_range) = coalesced_ranges.last_mut() {
+                let coalesced_length = last_range.end().max(range.end()) - last_range.offset;
+                if range.offset <= last_range.end() && coalesced_length <= u64::from(u32::MAX) {
+                    sources[index] = (
+                        coalesced_ranges.len() - 1,
+                        (range.offset - last_range.offset) as usize,
+                    );
+                    last_range.length = coalesced_length as u32;
+                    continue;
+                }
+            }
+
+            sources[index] = (coalesced_ranges.len(), 0);
+            coalesced_ranges.push(range);
+        }
+
+        Self {
+            ranges: coalesced_ranges,
+            sources,
+        }
+    }
+
+    /// Split contents of coalesced ranges back into contents of `original_ranges`
+    fn split(
+        &self,
+        contents: Vec<Vec<u8>>,
+        original_ranges: &[ReadRange],
+    ) -> io::Result<Vec<Vec<u8>>> {
+        let contents_match = contents.len() == self.ranges.len()
+            && contents
+                .iter()
+                .zip(&self.ranges)
+                .all(|(contents, range)| contents.len() == range.length as usize);
+        if !contents_match {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Plot server returned contents that don't match requested ranges",
+            ));
+        }
+
+        Ok(self
+            .sources
+            .iter()
+            .zip(original_ranges)
+            .map(|(&(index, offset), range)| {
+                contents[index][offset..][..range.length as usize].to_vec()
+            })
+            .collect())
+    }
+}
+
+/// Reader of plot file served by plot server with [`serve_plot()`].
+///
+/// Connections are pooled, adjacent and overlapping ranges are coalesced before being sent to the
+/// server.
+pub struct RemoteReadAt {
+    address: SocketAddr,
+    tls: Option<(TlsConnector, ServerName)>,
+    idle_connections: Mutex<Vec<Box<dyn Connection>>>,
+    reconnect_errors: Mutex<RepeatedErrors<io::ErrorKind>>,
+}
+
+impl fmt::Debug for RemoteReadAt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteReadAt")
+            .field("address", &self.address)
+            .field("tls", &self.tls.is_some())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl AuditReader for RemoteReadAt {
+    async fn read_ranges(&self, ranges: &[ReadRange]) -> io::Result<Vec<Vec<u8>>> {
+        let coalesced_ranges = CoalescedRanges::new(ranges);
+
+        match self
+            .request(&Request::Read(coalesced_ranges.ranges.clone()))
+            .await?
+        {
+            Response::Read(contents) => coalesced_ranges.split(contents, ranges),
+            response => Err(unexpected_response(response)),
+        }
+    }
+}
+
+impl RemoteReadAt {
+    /// Connect to plot server at `address`, `tls` is a connector together with server name that
+    /// certificate of the server is verified against.
+    ///
+    /// The first connection is established right away to detect misconfiguration early.
+    pub async fn connect(
+        address: SocketAddr,
+        tls: Option<(TlsConnector, &str)>,
+    ) -> io::Result<Self> {
+        let tls = tls
+            .map(|(tls_connector, server_name)| {
+                ServerName::try_from(server_name)
+                    .map(|server_name| (tls_connector, server_name))
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+            })
+            .transpose()?;
+
+        let remote_read_at = Self {
+            address,
+            tls,
+            idle_connections: Mutex::default(),
+            reconnect_errors: Mutex::default(),
+        };
+
+        let connection = remote_read_at.connect_once().await?;
+        remote_read_at.idle_connections.lock().push(connection);
+
+        Ok(remote_read_at)
+    }
+
+    async fn connect_once(&self) -> io::Result<Box<dyn Connection>> {
+        let stream = TcpStream::connect(self.address).await?;
+        // Requests are small and latency-sensitive
+        stream.set_nodelay(true)?;
+
+        let mut connection: Box<dyn Connection> = match &self.tls {
+            Some((tls_connector, server_name)) => {
+                Box::new(tls_connector.connect(server_name.clone(), stream).await?)
+            }
+            None => Box::new(stream),
+        };
+
+        connection.write_all(&[PROTOCOL_VERSION]).await?;
+        connection.flush().await?;
+        check_protocol_version(&mut connection).await?;
+
+        Ok(connection)
+    }
+
+    /// Get metadata of the plot, it changes as sectors are plotted, so it should be requested
+    /// again before every audit
+    pub async fn plot_metadata(&self) -> io::Result<PlotMetadata> {
+        match self.request(&Request::Metadata).await? {
+            Response::Metadata(plot_metadata) => Ok(plot_metadata),
+            response => Err(unexpected_response(response)),
+        }
+    }
+
+    /// Send request over idle connection if there is one or over a new connection otherwise
+    async fn request(&self, request: &Request) -> io::Result<Response> {
+        let idle_connection = self.idle_connections.lock().pop();
+        match idle_connection {
+            Some(connection) => match self.request_over(connection, request).await {
+                Ok(response) => {
+                    self.reconnect_errors.lock().success();
+                    Ok(response)
+                }
+                Err(error) => {
+                    // Idle connection may have been closed by the server in the meantime
+                    let maybe_repeats = self.reconnect_errors.lock().error(error.kind());
+                    if let Some(repeats) = maybe_repeats {
+                        debug!(
+                            %error,
+                            %repeats,
+                            "Idle connection to plot server failed, reconnecting"
+                        );
+                    }
+                    self.request_over(self.connect_once().await?, request).await
+                }
+            },
+            None => self.request_over(self.connect_once().await?, request).await,
+        }
+    }
+
+    /// Send request over `connection`, connection is returned to the pool if request succeeds
+    async fn request_over(
+        &self,
+        mut connection: Box<dyn Connection>,
+        request: &Request,
+    ) -> io::Result<Response> {
+        write_frame(&mut connection, request, MAX_REQUEST_FRAME_SIZE).await?;
+        let response = read_frame(&mut connection, MAX_RESPONSE_FRAME_SIZE).await?;
+
+        let mut idle_connections = self.idle_connections.lock();
+        if idle_connections.len() < MAX_IDLE_CONNECTIONS {
+            idle_connections.push(connection);
+        }
+
+        Ok(response)
+    }
+}
+
+/// Error for response that doesn't match request, [`Response::Err`] is turned into error as is
+fn unexpected_response(response: Response) -> io::Error {
+    match response {
+        Response::Err(error) => io::Error::new(io::ErrorKind::Other, error),
+        _ => io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Plot server returned response that doesn't match request",
+        ),
+    }
+}
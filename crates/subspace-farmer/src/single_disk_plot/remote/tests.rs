@@ -0,0 +1,456 @@
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::plotting::plot_sector;
+use crate::single_disk_plot::remote::{
+    audit_sectors, open_plot_file, serve_plot, tls_acceptor, tls_connector, AuditReader,
+    CoalescedRanges, ReadRange, RemoteReadAt, MAX_RANGES_PER_REQUEST, MAX_REQUEST_FRAME_SIZE,
+    PROTOCOL_VERSION,
+};
+use crate::single_disk_plot::sharded_plot_file::PlotShards;
+use crate::single_disk_plot::{SingleDiskPlotError, SingleDiskPlotId, SingleDiskPlotInfo};
+use crate::testing::fixtures::{farmer_protocol_info, DerivedPieceReceiver};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::io::{Cursor, Write};
+use std::net::SocketAddr;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fs, io};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{plot_sector_size, PublicKey, SectorIndex};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+
+/// Directory with certificates issued by test certificate authority in `ca.pem`, `server.pem` is
+/// issued for `localhost`
+fn testdata() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src/single_disk_plot/remote/testdata")
+}
+
+/// Serve plot file with `contents` in the background, returns address of the server
+async fn serve_contents(
+    directory: &TempDir,
+    contents: &[u8],
+    tls_acceptor: Option<TlsAcceptor>,
+    max_connections: usize,
+) -> SocketAddr {
+    let plot_file_path = directory.path().join("plot.bin");
+    fs::write(&plot_file_path, contents).unwrap();
+    let plot_file = Arc::new(fs::File::open(&plot_file_path).unwrap());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(serve_plot(
+        listener,
+        directory.path().to_path_buf(),
+        plot_file,
+        tls_acceptor,
+        NonZeroUsize::new(max_connections).unwrap(),
+    ));
+
+    address
+}
+
+#[test]
+fn coalescing() {
+    let ranges = [
+        ReadRange {
+            offset: 100,
+            length: 10,
+        },
+        ReadRange {
+            offset: 0,
+            length: 10,
+        },
+        // Adjacent to the previous one
+        ReadRange {
+            offset: 10,
+            length: 5,
+        },
+        // Overlaps with the first one
+        ReadRange {
+            offset: 105,
+            length: 10,
+        },
+        // Contained within the first one
+        ReadRange {
+            offset: 101,
+            length: 2,
+        },
+    ];
+
+    let coalesced_ranges = CoalescedRanges::new(&ranges);
+    assert_eq!(
+        coalesced_ranges.ranges,
+        vec![
+            ReadRange {
+                offset: 0,
+                length: 15,
+            },
+            ReadRange {
+                offset: 100,
+                length: 15,
+            },
+        ]
+    );
+
+    let contents = coalesced_ranges
+        .ranges
+        .iter()
+        .map(|range| {
+            (range.offset..range.offset + u64::from(range.length))
+                .map(|byte| byte as u8)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let split_contents = coalesced_ranges.split(contents, &ranges).unwrap();
+    for (range, contents) in ranges.iter().zip(split_contents) {
+        assert_eq!(
+            contents,
+            (range.offset..range.offset + u64::from(range.length))
+                .map(|byte| byte as u8)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // Contents that don't match requested ranges are rejected
+    assert!(coalesced_ranges
+        .split(vec![vec![0; 15], vec![0; 14]], &ranges)
+        .is_err());
+    assert!(coalesced_ranges.split(vec![vec![0; 15]], &ranges).is_err());
+}
+
+#[tokio::test]
+async fn remote_audit() {
+    let directory = TempDir::new().unwrap();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let first_sector_index = SectorIndex::new(100);
+    let farmer_protocol_info = FarmerProtocolInfo {
+        genesis_hash: rand::random(),
+        ..farmer_protocol_info()
+    };
+    let sector_count = 3;
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+    let mut plot = Vec::new();
+    for sector_offset in 0..sector_count {
+        let mut sector = vec![0; plot_sector_size as usize];
+        plot_sector(
+            &public_key,
+            first_sector_index.offset(sector_offset),
+            &DerivedPieceReceiver,
+            &AtomicBool::new(false),
+            &farmer_protocol_info,
+            sector.as_mut_slice(),
+            std::io::sink(),
+        )
+        .await
+        .unwrap();
+        plot.extend_from_slice(&sector);
+    }
+
+    let plot_file_path = directory.path().join("plot.bin");
+    fs::File::create(&plot_file_path)
+        .unwrap()
+        .write_all(&plot)
+        .unwrap();
+    let plot_file = Arc::new(fs::File::open(&plot_file_path).unwrap());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(serve_plot(
+        listener,
+        directory.path().to_path_buf(),
+        Arc::clone(&plot_file),
+        None,
+        NonZeroUsize::new(4).unwrap(),
+    ));
+    let remote_read_at = RemoteReadAt::connect(address, None).await.unwrap();
+
+    // Arbitrary and overlapping ranges are read the same way as from the file directly
+    let ranges = [
+        ReadRange {
+            offset: plot_sector_size,
+            length: 1000,
+        },
+        ReadRange {
+            offset: 7,
+            length: 100,
+        },
+        ReadRange {
+            offset: plot_sector_size + 500,
+            length: 1000,
+        },
+    ];
+    let contents = remote_read_at.read_ranges(&ranges).await.unwrap();
+    for (range, contents) in ranges.iter().zip(&contents) {
+        let start = range.offset as usize;
+        assert_eq!(contents, &plot[start..][..range.length as usize]);
+    }
+    assert_eq!(
+        contents,
+        plot_file.as_ref().read_ranges(&ranges).await.unwrap()
+    );
+
+    // Reading beyond the end of the plot fails, but connection remains usable
+    assert!(remote_read_at
+        .read_ranges(&[ReadRange {
+            offset: plot.len() as u64 - 10,
+            length: 20,
+        }])
+        .await
+        .is_err());
+    assert_eq!(remote_read_at.read_ranges(&ranges).await.unwrap(), contents);
+
+    let global_challenge = rand::random();
+    // Maximum solution range makes sure every sector is eligible
+    let solution_range = u64::MAX;
+    let sector_offsets = (0..sector_count).collect::<Vec<_>>();
+
+    let remote_eligible_sectors = audit_sectors(
+        &remote_read_at,
+        &public_key,
+        first_sector_index,
+        &sector_offsets,
+        &farmer_protocol_info,
+        1,
+        KzgParametersId::TEST,
+        &kzg,
+        &global_challenge,
+        solution_range,
+    )
+    .await
+    .unwrap();
+    let local_eligible_sectors = audit_sectors(
+        plot_file.as_ref(),
+        &public_key,
+        first_sector_index,
+        &sector_offsets,
+        &farmer_protocol_info,
+        1,
+        KzgParametersId::TEST,
+        &kzg,
+        &global_challenge,
+        solution_range,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(remote_eligible_sectors.len(), sector_count as usize);
+    assert_eq!(local_eligible_sectors.len(), sector_count as usize);
+    for ((sector, remote_eligible_sector), local_eligible_sector) in plot
+        .chunks_exact(plot_sector_size as usize)
+        .zip(&remote_eligible_sectors)
+        .zip(&local_eligible_sectors)
+    {
+        let expected_eligible_sector = audit_sector(
+            &public_key,
+            remote_eligible_sector.sector_index,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            solution_range,
+            Cursor::new(sector),
+        )
+        .unwrap()
+        .unwrap();
+
+        for eligible_sector in [remote_eligible_sector, local_eligible_sector] {
+            assert_eq!(
+                eligible_sector.sector_index,
+                expected_eligible_sector.sector_index
+            );
+            assert_eq!(
+                eligible_sector.audit_piece_offset,
+                expected_eligible_sector.audit_piece_offset
+            );
+            assert_eq!(
+                eligible_sector.encoded_piece,
+                expected_eligible_sector.encoded_piece
+            );
+            assert_eq!(eligible_sector.chunk, expected_eligible_sector.chunk);
+        }
+    }
+}
+
+/// Reader of plot where every piece is filled with zeroes, records number of ranges in every read
+#[derive(Default)]
+struct RecordingReader {
+    reads: Mutex<Vec<usize>>,
+}
+
+#[async_trait]
+impl AuditReader for RecordingReader {
+    async fn read_ranges(&self, ranges: &[ReadRange]) -> io::Result<Vec<Vec<u8>>> {
+        self.reads.lock().push(ranges.len());
+        Ok(ranges
+            .iter()
+            .map(|range| vec![0; range.length as usize])
+            .collect())
+    }
+}
+
+#[tokio::test]
+async fn chunked_audit() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let reader = RecordingReader::default();
+    let first_sector_index = SectorIndex::new(0);
+    let sector_offsets = (0..MAX_RANGES_PER_REQUEST as u64 * 2 + 1).collect::<Vec<_>>();
+
+    let eligible_sectors = audit_sectors(
+        &reader,
+        &PublicKey::from(rand::random::<[u8; 32]>()),
+        first_sector_index,
+        &sector_offsets,
+        &farmer_protocol_info(),
+        1,
+        KzgParametersId::TEST,
+        &kzg,
+        &rand::random(),
+        u64::MAX,
+    )
+    .await
+    .unwrap();
+
+    // Audit that doesn't fit into a single request is split, pieces are still matched with sectors
+    // they belong to
+    assert_eq!(
+        *reader.reads.lock(),
+        vec![MAX_RANGES_PER_REQUEST, MAX_RANGES_PER_REQUEST, 1]
+    );
+    assert_eq!(eligible_sectors.len(), sector_offsets.len());
+    for (eligible_sector, &sector_offset) in eligible_sectors.iter().zip(&sector_offsets) {
+        assert_eq!(
+            eligible_sector.sector_index,
+            first_sector_index.offset(sector_offset)
+        );
+    }
+}
+
+#[test]
+fn open_sharded_plot_file() {
+    let directory = TempDir::new().unwrap();
+
+    assert!(matches!(
+        open_plot_file(directory.path()),
+        Err(SingleDiskPlotError::PlotInfoNotFound { .. })
+    ));
+
+    let allocated_space = 1024 * 1024;
+    SingleDiskPlotInfo::new(
+        SingleDiskPlotId::new(),
+        rand::random(),
+        PublicKey::from(rand::random::<[u8; 32]>()),
+        SectorIndex::new(0),
+        allocated_space,
+    )
+    .with_plot_shards(PlotShards::new(
+        allocated_space,
+        NonZeroU64::new(allocated_space / 2).unwrap(),
+    ))
+    .store_to(directory.path())
+    .unwrap();
+
+    assert!(matches!(
+        open_plot_file(directory.path()),
+        Err(SingleDiskPlotError::OfflineAccessOfShardedPlot { .. })
+    ));
+}
+
+#[tokio::test]
+async fn oversized_request_frame() {
+    let directory = TempDir::new().unwrap();
+    let address = serve_contents(&directory, &[0; 1024], None, 4).await;
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(&[PROTOCOL_VERSION]).await.unwrap();
+    let mut version = [0];
+    stream.read_exact(&mut version).await.unwrap();
+    assert_eq!(version, [PROTOCOL_VERSION]);
+
+    // Connection is closed right after length prefix, payload is never allocated
+    stream
+        .write_all(&(MAX_REQUEST_FRAME_SIZE + 1).to_le_bytes())
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    assert!(response.is_empty());
+}
+
+#[tokio::test]
+async fn connection_limit() {
+    let directory = TempDir::new().unwrap();
+    let address = serve_contents(&directory, &[0; 1024], None, 1).await;
+
+    let remote_read_at = RemoteReadAt::connect(address, None).await.unwrap();
+
+    // Connection beyond the limit is not served while the first one is open
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let mut version = [0];
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), stream.read_exact(&mut version))
+            .await
+            .is_err()
+    );
+
+    drop(remote_read_at);
+    tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut version))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(version, [PROTOCOL_VERSION]);
+}
+
+#[tokio::test]
+async fn client_authentication() {
+    let directory = TempDir::new().unwrap();
+    let contents = (0..1024).map(|byte| byte as u8).collect::<Vec<_>>();
+    let testdata = testdata();
+    let tls_acceptor = tls_acceptor(
+        &testdata.join("server.pem"),
+        &testdata.join("server-key.pem"),
+        Some(&testdata.join("ca.pem")),
+    )
+    .unwrap();
+    let address = serve_contents(&directory, &contents, Some(tls_acceptor), 4).await;
+
+    // Client without certificate is rejected
+    let tls_connector_without_identity = tls_connector(&testdata.join("ca.pem"), None).unwrap();
+    assert!(
+        RemoteReadAt::connect(address, Some((tls_connector_without_identity, "localhost")))
+            .await
+            .is_err()
+    );
+
+    let tls_connector = tls_connector(
+        &testdata.join("ca.pem"),
+        Some((
+            &testdata.join("client.pem"),
+            &testdata.join("client-key.pem"),
+        )),
+    )
+    .unwrap();
+    let remote_read_at = RemoteReadAt::connect(address, Some((tls_connector, "localhost")))
+        .await
+        .unwrap();
+    assert_eq!(
+        remote_read_at
+            .read_ranges(&[ReadRange {
+                offset: 10,
+                length: 20,
+            }])
+            .await
+            .unwrap(),
+        vec![contents[10..30].to_vec()]
+    );
+}
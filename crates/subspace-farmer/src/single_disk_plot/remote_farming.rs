@@ -0,0 +1,188 @@
+//! Farming of plot served by plot server.
+//!
+//! Plot is audited through [`RemoteReadAt`] using [`PlotMetadata`] requested from the plot server
+//! every slot, solutions are created and rewards are signed locally with identity plot was created
+//! with, so identity never has to be stored on the machine with the plot.
+
+#[cfg(test)]
+mod tests;
+
+use crate::identity::Identity;
+use crate::repeated_errors::RepeatedErrors;
+use crate::rpc_client::RpcClient;
+use crate::single_disk_plot::remote::{audit_sectors, PlotMetadata, RemoteReadAt};
+use crate::single_disk_plot::FarmingError;
+use futures::StreamExt;
+use parity_scale_codec::Encode;
+use std::collections::BTreeMap;
+use std::io;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{PublicKey, Solution};
+use subspace_rpc_primitives::{FarmerProtocolInfo, SlotInfo, SolutionResponse};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+/// Audit plot served by plot server that `reader` is connected to for `slot_info` and create
+/// solutions for sectors that are within voting solution range.
+///
+/// `identity` must be the one plot was created with and `kzg` must use the same parameters.
+pub async fn audit_remote_plot(
+    reader: &RemoteReadAt,
+    identity: &Identity,
+    reward_address: PublicKey,
+    kzg: &Kzg,
+    slot_info: &SlotInfo,
+) -> Result<Vec<Solution<PublicKey, PublicKey>>, FarmingError> {
+    let PlotMetadata {
+        public_key,
+        first_sector_index,
+        farmer_protocol_info,
+        kzg_parameters_id,
+        sector_alignment,
+        sectors,
+    } = reader.plot_metadata().await?;
+
+    let identity_public_key = PublicKey::from(identity.public_key().to_bytes());
+    if public_key != identity_public_key {
+        return Err(FarmingError::RemoteIdentityMismatch {
+            plot: public_key,
+            identity: identity_public_key,
+        });
+    }
+
+    // Sectors are audited with rotation they were plotted under, sectors being replotted are
+    // skipped
+    let mut sector_offsets_by_rotation = BTreeMap::<u64, Vec<u64>>::new();
+    for (sector_offset, sector) in sectors.iter().enumerate() {
+        if let Some(sector) = sector {
+            sector_offsets_by_rotation
+                .entry(sector.rotation)
+                .or_default()
+                .push(sector_offset as u64);
+        }
+    }
+
+    let mut solutions = Vec::new();
+    for (rotation, sector_offsets) in sector_offsets_by_rotation {
+        let eligible_sectors = audit_sectors(
+            reader,
+            &public_key,
+            first_sector_index,
+            &sector_offsets,
+            &FarmerProtocolInfo {
+                rotation,
+                ..farmer_protocol_info
+            },
+            sector_alignment,
+            kzg_parameters_id,
+            kzg,
+            &slot_info.global_challenge,
+            slot_info.voting_solution_range,
+        )
+        .await?;
+
+        for eligible_sector in eligible_sectors {
+            let sector_metadata = eligible_sector
+                .sector_index
+                .offset_from(first_sector_index)
+                .and_then(|sector_offset| sectors.get(sector_offset as usize).copied().flatten())
+                .expect("Only sectors with metadata are audited; qed")
+                .sector_metadata
+                .encode();
+
+            if let Some(solution) = eligible_sector.try_into_solution(
+                identity,
+                reward_address,
+                &farmer_protocol_info,
+                sector_metadata.as_slice(),
+            )? {
+                solutions.push(solution);
+            }
+        }
+    }
+
+    Ok(solutions)
+}
+
+/// Farm plot served by plot server that `reader` is connected to, runs until slot info
+/// subscription ends.
+///
+/// Rewards for solutions are not signed here, [`reward_signing`](crate::reward_signing) needs to
+/// run alongside with the same identity.
+pub async fn farm_remote_plot<RC>(
+    reader: RemoteReadAt,
+    rpc_client: RC,
+    identity: Identity,
+    reward_address: PublicKey,
+    kzg: Kzg,
+) -> Result<(), FarmingError>
+where
+    RC: RpcClient,
+{
+    info!(?reader, "Subscribing to slot info notifications");
+    let mut slot_info_notifications = rpc_client
+        .subscribe_slot_info()
+        .await
+        .map_err(|error| FarmingError::FailedToGetFarmerProtocolInfo { error })?;
+
+    let mut audit_errors = RepeatedErrors::<io::ErrorKind>::default();
+    let mut submission_errors = RepeatedErrors::<()>::default();
+
+    while let Some(slot_info) = slot_info_notifications.next().await {
+        let span = info_span!("remote_audit", %slot_info.slot_number);
+        debug!(parent: &span, ?slot_info, "New slot");
+
+        let audit_result = audit_remote_plot(&reader, &identity, reward_address, &kzg, &slot_info)
+            .instrument(span)
+            .await;
+        let solutions = match audit_result {
+            Ok(solutions) => {
+                if let Some(suppressed) = audit_errors.success() {
+                    info!(
+                        %suppressed,
+                        "Remote plot audit recovered after repeated errors"
+                    );
+                }
+                solutions
+            }
+            // Plot server may be temporarily unavailable, the next slot is audited again
+            Err(FarmingError::Io(error)) => {
+                if let Some(repeats) = audit_errors.error(error.kind()) {
+                    warn!(%error, %repeats, "Failed to audit remote plot");
+                }
+                continue;
+            }
+            Err(error) => {
+                return Err(error);
+            }
+        };
+
+        if !solutions.is_empty() {
+            debug!(solutions = %solutions.len(), "Solutions found");
+        }
+
+        let submission_result = rpc_client
+            .submit_solution_response(SolutionResponse {
+                slot_number: slot_info.slot_number,
+                solutions,
+            })
+            .await;
+        match submission_result {
+            Ok(()) => {
+                if let Some(suppressed) = submission_errors.success() {
+                    info!(
+                        %suppressed,
+                        "Solutions submission recovered after repeated errors"
+                    );
+                }
+            }
+            Err(error) => {
+                // Node may be temporarily unavailable, next slot will be submitted again
+                if let Some(repeats) = submission_errors.error(()) {
+                    warn!(%error, %repeats, "Failed to submit solutions response");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use crate::identity::Identity;
+use crate::single_disk_plot::plot_writer::PlotWriter;
+use crate::single_disk_plot::read_only::PlotReader;
+use crate::single_disk_plot::remote::{
+    open_plot_file, serve_plot, RemoteReadAt, RemoteSectorMetadata,
+};
+use crate::single_disk_plot::remote_farming::audit_remote_plot;
+use crate::single_disk_plot::FarmingError;
+use crate::testing::fixtures::{archived_segment, farmer_protocol_info};
+use crate::testing::MapPieceReceiver;
+use parity_scale_codec::Encode;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{PublicKey, SectorIndex, SlotNumber, PIECES_IN_SEGMENT};
+use subspace_rpc_primitives::{FarmerProtocolInfo, SlotInfo};
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+const FIRST_SECTOR_INDEX: SectorIndex = SectorIndex::new(7);
+const SECTOR_COUNT: u64 = 2;
+
+/// Create plot with `identity` in `directory` and serve it in the background, returns reader
+/// connected to the plot server
+async fn serve_test_plot(directory: &Path, identity: &Identity, kzg: &Kzg) -> RemoteReadAt {
+    let farmer_protocol_info = FarmerProtocolInfo {
+        genesis_hash: rand::random(),
+        total_pieces: NonZeroU64::new(u64::from(PIECES_IN_SEGMENT)).unwrap(),
+        ..farmer_protocol_info()
+    };
+    let piece_receiver = MapPieceReceiver::from_archived_segments([&archived_segment(kzg)]);
+    let cancelled = AtomicBool::new(false);
+
+    let mut plot_writer = PlotWriter::create(
+        directory,
+        PublicKey::from(identity.public_key().to_bytes()),
+        FIRST_SECTOR_INDEX,
+        SECTOR_COUNT,
+        farmer_protocol_info,
+        KzgParametersId::TEST,
+    )
+    .unwrap();
+    for _ in 0..SECTOR_COUNT {
+        plot_writer
+            .plot_sector(&piece_receiver, &cancelled)
+            .await
+            .unwrap();
+    }
+    // Plot that is being plotted can't be served
+    drop(plot_writer);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(serve_plot(
+        listener,
+        directory.to_path_buf(),
+        Arc::new(open_plot_file(directory).unwrap()),
+        None,
+        NonZeroUsize::new(4).unwrap(),
+    ));
+
+    RemoteReadAt::connect(address, None).await.unwrap()
+}
+
+fn slot_info() -> SlotInfo {
+    SlotInfo {
+        slot_number: SlotNumber::new(1),
+        global_challenge: rand::random(),
+        // Maximum solution range makes sure every sector is eligible
+        solution_range: u64::MAX,
+        voting_solution_range: u64::MAX,
+    }
+}
+
+#[tokio::test]
+async fn remote_plot_metadata() {
+    let directory = TempDir::new().unwrap();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let identity = Identity::generate();
+    let reader = serve_test_plot(directory.path(), &identity, &kzg).await;
+
+    let plot_metadata = reader.plot_metadata().await.unwrap();
+
+    let plot_reader = PlotReader::open(directory.path()).unwrap();
+    assert_eq!(plot_metadata.public_key, *plot_reader.info().public_key());
+    assert_eq!(plot_metadata.first_sector_index, FIRST_SECTOR_INDEX);
+    assert_eq!(
+        plot_metadata.farmer_protocol_info,
+        *plot_reader.farmer_protocol_info()
+    );
+    assert_eq!(plot_metadata.kzg_parameters_id, KzgParametersId::TEST);
+    assert_eq!(
+        plot_metadata.sector_alignment,
+        plot_reader.sector_alignment()
+    );
+    assert_eq!(plot_metadata.sector_count(), SECTOR_COUNT);
+    for (sector_offset, sector) in plot_metadata.sectors.iter().enumerate() {
+        let record = plot_reader
+            .read_sector_metadata_record(sector_offset as u64)
+            .unwrap();
+        assert_eq!(
+            sector,
+            &Some(RemoteSectorMetadata {
+                rotation: record.rotation,
+                sector_metadata: record.sector_metadata,
+            })
+        );
+    }
+}
+
+#[tokio::test]
+async fn remote_plot_audit() {
+    let directory = TempDir::new().unwrap();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let identity = Identity::generate();
+    let reader = serve_test_plot(directory.path(), &identity, &kzg).await;
+    let reward_address = PublicKey::from(rand::random::<[u8; 32]>());
+    let slot_info = slot_info();
+
+    let solutions = audit_remote_plot(&reader, &identity, reward_address, &kzg, &slot_info)
+        .await
+        .unwrap();
+
+    // Solutions are the same as the ones created from local plot, except chunk signature that is
+    // randomized
+    let plot_reader = PlotReader::open(directory.path()).unwrap();
+    assert_eq!(solutions.len() as u64, SECTOR_COUNT);
+    for (sector_offset, solution) in (0..SECTOR_COUNT).zip(&solutions) {
+        let sector_metadata = plot_reader
+            .read_sector_metadata_record(sector_offset)
+            .unwrap()
+            .sector_metadata
+            .encode();
+        let expected_solution = plot_reader
+            .audit_sector(
+                sector_offset,
+                &kzg,
+                &slot_info.global_challenge,
+                slot_info.voting_solution_range,
+            )
+            .unwrap()
+            .unwrap()
+            .try_into_solution(
+                &identity,
+                reward_address,
+                plot_reader.farmer_protocol_info(),
+                sector_metadata.as_slice(),
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(solution.public_key, expected_solution.public_key);
+        assert_eq!(solution.reward_address, reward_address);
+        assert_eq!(solution.sector_index, expected_solution.sector_index);
+        assert_eq!(solution.total_pieces, expected_solution.total_pieces);
+        assert_eq!(solution.piece_offset, expected_solution.piece_offset);
+        assert_eq!(
+            solution.piece_record_hash,
+            expected_solution.piece_record_hash
+        );
+        assert_eq!(solution.chunk, expected_solution.chunk);
+    }
+}
+
+#[tokio::test]
+async fn remote_plot_identity_mismatch() {
+    let directory = TempDir::new().unwrap();
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let reader = serve_test_plot(directory.path(), &Identity::generate(), &kzg).await;
+
+    // Solutions of plot created with a different identity would be rejected by the node
+    assert!(matches!(
+        audit_remote_plot(
+            &reader,
+            &Identity::generate(),
+            PublicKey::from(rand::random::<[u8; 32]>()),
+            &kzg,
+            &slot_info(),
+        )
+        .await,
+        Err(FarmingError::RemoteIdentityMismatch { .. })
+    ));
+}
@@ -0,0 +1,123 @@
+//! Per-sector locks that keep farming from reading sectors that are being written.
+//!
+//! Plotting and farming share the same plot file. Audits of sectors other than the one being
+//! written are safe, but sector that is being replotted contains a mix of old and new contents
+//! until plotting finishes. Plotting holds write lock of the sector for the whole duration of
+//! (re)plotting, while farming holds read lock of every sector it audits. Farming never waits for
+//! plotting: sector that is locked for writing is skipped in that slot.
+
+#[cfg(test)]
+mod tests;
+
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SectorState {
+    /// Sector is being read by this many readers
+    Reading(usize),
+    /// Sector is being written
+    Writing,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// States of locked sectors by sector offset, sectors that are not locked are not present
+    sectors: Mutex<HashMap<u64, SectorState>>,
+    sector_unlocked: Condvar,
+}
+
+/// Read-write locks of individual sectors of a plot.
+///
+/// Cheap to clone, all clones share the same state.
+#[derive(Debug, Default, Clone)]
+pub struct SectorLocks {
+    inner: Arc<Inner>,
+}
+
+impl SectorLocks {
+    /// Lock sector at `sector_offset` for reading, `None` if sector is being written and should be
+    /// skipped
+    pub fn try_read(&self, sector_offset: u64) -> Option<SectorReadGuard<'_>> {
+        let mut sectors = self.inner.sectors.lock();
+        match sectors
+            .entry(sector_offset)
+            .or_insert(SectorState::Reading(0))
+        {
+            SectorState::Reading(readers) => {
+                *readers += 1;
+            }
+            SectorState::Writing => {
+                return None;
+            }
+        }
+
+        Some(SectorReadGuard {
+            sector_locks: self,
+            sector_offset,
+        })
+    }
+
+    /// Lock sector at `sector_offset` for writing, waits for readers and other writers of the
+    /// sector to finish first
+    pub fn write(&self, sector_offset: u64) -> SectorWriteGuard<'_> {
+        let mut sectors = self.inner.sectors.lock();
+        while sectors.contains_key(&sector_offset) {
+            self.inner.sector_unlocked.wait(&mut sectors);
+        }
+        sectors.insert(sector_offset, SectorState::Writing);
+
+        SectorWriteGuard {
+            sector_locks: self,
+            sector_offset,
+        }
+    }
+
+    /// Whether sector at `sector_offset` is being written right now
+    pub fn is_writing(&self, sector_offset: u64) -> bool {
+        self.inner.sectors.lock().get(&sector_offset) == Some(&SectorState::Writing)
+    }
+
+    fn finish_read(&self, sector_offset: u64) {
+        let mut sectors = self.inner.sectors.lock();
+        if let Some(SectorState::Reading(readers)) = sectors.get_mut(&sector_offset) {
+            *readers -= 1;
+            if *readers == 0 {
+                sectors.remove(&sector_offset);
+                self.inner.sector_unlocked.notify_all();
+            }
+        }
+    }
+
+    fn finish_write(&self, sector_offset: u64) {
+        self.inner.sectors.lock().remove(&sector_offset);
+        self.inner.sector_unlocked.notify_all();
+    }
+}
+
+/// Sector is locked for reading while this guard is alive
+#[derive(Debug)]
+pub struct SectorReadGuard<'a> {
+    sector_locks: &'a SectorLocks,
+    sector_offset: u64,
+}
+
+impl Drop for SectorReadGuard<'_> {
+    fn drop(&mut self) {
+        self.sector_locks.finish_read(self.sector_offset);
+    }
+}
+
+/// Sector is locked for writing while this guard is alive
+#[derive(Debug)]
+pub struct SectorWriteGuard<'a> {
+    sector_locks: &'a SectorLocks,
+    sector_offset: u64,
+}
+
+impl Drop for SectorWriteGuard<'_> {
+    fn drop(&mut self) {
+        self.sector_locks.finish_write(self.sector_offset);
+    }
+}
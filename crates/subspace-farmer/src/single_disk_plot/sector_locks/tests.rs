@@ -0,0 +1,112 @@
+use crate::single_disk_plot::sector_locks::SectorLocks;
+use rand::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn basic() {
+    let sector_locks = SectorLocks::default();
+
+    let read_guard = sector_locks.try_read(0).unwrap();
+    // Multiple readers are allowed
+    let second_read_guard = sector_locks.try_read(0).unwrap();
+    assert!(!sector_locks.is_writing(0));
+
+    let locked_for_writing = AtomicBool::new(false);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let _write_guard = sector_locks.write(0);
+            assert!(sector_locks.is_writing(0));
+            locked_for_writing.store(true, Ordering::Release);
+        });
+
+        // Writer waits for all readers
+        thread::sleep(Duration::from_millis(50));
+        assert!(!locked_for_writing.load(Ordering::Acquire));
+        drop(read_guard);
+        thread::sleep(Duration::from_millis(50));
+        assert!(!locked_for_writing.load(Ordering::Acquire));
+        drop(second_read_guard);
+    });
+    assert!(locked_for_writing.load(Ordering::Acquire));
+
+    let write_guard = sector_locks.write(0);
+    // Sector being written is skipped, other sectors are not affected
+    assert!(sector_locks.try_read(0).is_none());
+    assert!(sector_locks.try_read(1).is_some());
+    drop(write_guard);
+    assert!(!sector_locks.is_writing(0));
+    assert!(sector_locks.try_read(0).is_some());
+}
+
+#[test]
+fn no_torn_reads() {
+    const SECTORS: usize = 8;
+    const SECTOR_SIZE: usize = 256;
+
+    let sector_locks = SectorLocks::default();
+    // Every byte of a sector is set to the same value, torn read would observe different values
+    let sectors = (0..SECTORS)
+        .map(|_| {
+            (0..SECTOR_SIZE)
+                .map(|_| AtomicU8::new(0))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let stop = AtomicBool::new(false);
+    let audits = AtomicU64::new(0);
+    let skipped = AtomicU64::new(0);
+
+    thread::scope(|scope| {
+        // Replotting random sectors
+        scope.spawn(|| {
+            let mut rng = thread_rng();
+            for generation in 1..=200u8 {
+                let sector_offset = rng.gen_range(0..SECTORS);
+                let _write_guard = sector_locks.write(sector_offset as u64);
+                for (byte_offset, byte) in sectors[sector_offset].iter().enumerate() {
+                    byte.store(generation, Ordering::Relaxed);
+                    if byte_offset % 64 == 0 {
+                        thread::yield_now();
+                    }
+                }
+            }
+            stop.store(true, Ordering::Release);
+        });
+
+        // Hammering audits
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let mut rng = thread_rng();
+                while !stop.load(Ordering::Acquire) {
+                    let sector_offset = rng.gen_range(0..SECTORS);
+                    let _read_guard = match sector_locks.try_read(sector_offset as u64) {
+                        Some(read_guard) => read_guard,
+                        None => {
+                            skipped.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                    };
+
+                    let sector = &sectors[sector_offset];
+                    let first_byte = sector[0].load(Ordering::Relaxed);
+                    for byte in sector {
+                        assert_eq!(
+                            byte.load(Ordering::Relaxed),
+                            first_byte,
+                            "Torn read of sector {sector_offset}"
+                        );
+                    }
+                    audits.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    assert!(audits.load(Ordering::Relaxed) > 0);
+    // No locks are left behind
+    for sector_offset in 0..SECTORS as u64 {
+        assert!(!sector_locks.is_writing(sector_offset));
+    }
+}
@@ -0,0 +1,357 @@
+//! Sector metadata file.
+//!
+//! Sector metadata is stored in a separate file as fixed-size records, so that it can be memory
+//! mapped and metadata of any sector accessed by index without reading the rest of the file.
+//! File starts with [`SectorMetadataFileHeader`] padded to the size of one record, followed by one
+//! record per sector. Every record is padded to a cache line and written with a single copy before
+//! sector count is increased, so readers never observe partially written record.
+//!
+//! Record is [`SectorMetadataRecord`], which contains encoded [`SectorMetadata`] followed by
+//! encoded `Option<SlotNumber>` of the slot that was current when sector was plotted, which is zero
+//! (`None`) in records written before it was stored. Rotation and checksum of sector contents
+//! follow at fixed offsets, they are zero in older records too. See [`SectorMetadataRecord`] for exact byte
+//! layout, external tools should use it instead of decoding records manually.
+//!
+//! Plots created before this file existed stored tightly packed sector metadata in plot metadata
+//! file after [`RESERVED_PLOT_METADATA`] bytes, such plots are migrated on open.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::{SectorMetadata, SingleDiskPlotError, RESERVED_PLOT_METADATA};
+use parity_scale_codec::{Decode, Encode};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::{fs, io, mem};
+use subspace_core_primitives::{SectorIndex, SegmentIndex, SlotNumber};
+use thiserror::Error;
+
+/// Size of one record of sector metadata file, equal to cache line size
+pub const SECTOR_METADATA_RECORD_SIZE: usize = 64;
+/// Size of [`SectorChecksum`]
+pub const SECTOR_CHECKSUM_SIZE: usize = 16;
+
+/// Checksum of plotted sector contents, the first [`SECTOR_CHECKSUM_SIZE`] bytes of BLAKE2b-256
+/// hash of the sector. Only meant to detect disk corruption, truncation keeps it within record.
+pub type SectorChecksum = [u8; SECTOR_CHECKSUM_SIZE];
+
+/// Header of sector metadata file, encoding is pinned by golden test vectors
+#[derive(Debug, Encode, Decode)]
+pub(super) struct SectorMetadataFileHeader {
+    pub(super) version: u8,
+    /// Size of one record in bytes
+    pub(super) record_size: u32,
+}
+
+impl SectorMetadataFileHeader {
+    const LATEST_VERSION: u8 = SectorMetadataRecord::LATEST_VERSION;
+
+    fn new() -> Self {
+        Self {
+            version: Self::LATEST_VERSION,
+            record_size: SECTOR_METADATA_RECORD_SIZE as u32,
+        }
+    }
+}
+
+/// Offset of the record of sector at `sector_offset` within sector metadata file
+pub(crate) fn sector_metadata_record_offset(sector_offset: u64) -> u64 {
+    // The first record is occupied by header
+    (sector_offset + 1) * SECTOR_METADATA_RECORD_SIZE as u64
+}
+
+/// Size of sector metadata file with records for `sector_count` sectors
+pub(crate) fn sector_metadata_file_size(sector_count: u64) -> u64 {
+    sector_metadata_record_offset(sector_count)
+}
+
+/// Errors that happen during decoding of [`SectorMetadataRecord`]
+#[derive(Debug, Error)]
+pub enum SectorMetadataRecordError {
+    /// Record was written by unsupported version of the format
+    #[error(
+        "Unsupported sector metadata version {version}, latest supported version is {}",
+        SectorMetadataRecord::LATEST_VERSION
+    )]
+    UnsupportedVersion {
+        /// Version of the format
+        version: u8,
+    },
+    /// Record doesn't have expected size
+    #[error("Sector metadata record has {size} bytes, expected {SECTOR_METADATA_RECORD_SIZE}")]
+    InvalidSize {
+        /// Size of the record
+        size: usize,
+    },
+    /// Failed to decode record contents
+    #[error("Failed to decode sector metadata record: {0}")]
+    Decode(#[from] parity_scale_codec::Error),
+}
+
+/// Single record of sector metadata file, this is the stable format for tools that read sector
+/// metadata.
+///
+/// Version of the format is stored in the header of sector metadata file. Record of version `0` is
+/// [`SECTOR_METADATA_RECORD_SIZE`] bytes, all integers are little-endian:
+///
+/// | Offset | Size | Contents                                                                  |
+/// |--------|------|---------------------------------------------------------------------------|
+/// | 0      | 8    | [`SectorMetadata::total_pieces`], never zero in valid records             |
+/// | 8      | 8    | [`SectorMetadata::expires_at`]                                            |
+/// | 16     | 1    | `1` if slot sector was plotted at is known, `0` otherwise                 |
+/// | 17     | 8    | Slot sector was plotted at, only present if previous byte is `1`          |
+/// | 25     | 8    | Rotation of the global salt sector was encoded with, `0` if no rotation   |
+/// | 33     | 1    | `1` if checksum of sector contents is known, `0` otherwise                |
+/// | 34     | 16   | [`SectorChecksum`], only present if previous byte is `1`                  |
+/// | ...    | ...  | Zero padding up to [`SECTOR_METADATA_RECORD_SIZE`]                         |
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SectorMetadataRecord {
+    /// Sector metadata
+    pub sector_metadata: SectorMetadata,
+    /// Slot that was current when sector was plotted, `None` if unknown
+    pub plotted_at_slot: Option<SlotNumber>,
+    /// Rotation of the global salt sector was encoded with, `0` for sectors plotted without
+    /// rotation, including all sectors plotted before it was recorded
+    pub rotation: u64,
+    /// Checksum of sector contents, `None` for sectors plotted before it was recorded
+    pub checksum: Option<SectorChecksum>,
+}
+
+impl SectorMetadataRecord {
+    /// Latest version of the format, the only one supported right now
+    pub const LATEST_VERSION: u8 = 0;
+    /// Offset of rotation within record, right after the largest encoding of plotted-at slot
+    const ROTATION_OFFSET: usize = 25;
+    /// Offset of checksum within record, right after rotation
+    const CHECKSUM_OFFSET: usize = Self::ROTATION_OFFSET + mem::size_of::<u64>();
+
+    /// Encode record with the latest version of the format
+    pub fn encode(&self) -> [u8; SECTOR_METADATA_RECORD_SIZE] {
+        let mut record = [0; SECTOR_METADATA_RECORD_SIZE];
+        let mut output = record.as_mut_slice();
+        self.sector_metadata.encode_to(&mut output);
+        self.plotted_at_slot.encode_to(&mut output);
+        record[Self::ROTATION_OFFSET..][..mem::size_of::<u64>()]
+            .copy_from_slice(&self.rotation.to_le_bytes());
+        let mut output = &mut record[Self::CHECKSUM_OFFSET..];
+        self.checksum.encode_to(&mut output);
+        record
+    }
+
+    /// Decode record that was encoded with format `version`
+    pub fn decode(record: &[u8], version: u8) -> Result<Self, SectorMetadataRecordError> {
+        if version != Self::LATEST_VERSION {
+            return Err(SectorMetadataRecordError::UnsupportedVersion { version });
+        }
+        if record.len() != SECTOR_METADATA_RECORD_SIZE {
+            return Err(SectorMetadataRecordError::InvalidSize { size: record.len() });
+        }
+
+        let rotation = u64::decode(&mut &record[Self::ROTATION_OFFSET..])?;
+        let checksum = Option::<SectorChecksum>::decode(&mut &record[Self::CHECKSUM_OFFSET..])?;
+        let mut record = record;
+        Ok(Self {
+            sector_metadata: SectorMetadata::decode(&mut record)?,
+            plotted_at_slot: Option::<SlotNumber>::decode(&mut record)?,
+            rotation,
+            checksum,
+        })
+    }
+}
+
+/// Record of sector at `sector_offset` in contents of sector metadata file, empty if out of range
+fn sector_metadata_record(sector_metadata_file_contents: &[u8], sector_offset: u64) -> &[u8] {
+    usize::try_from(sector_metadata_record_offset(sector_offset))
+        .ok()
+        .and_then(|offset| {
+            sector_metadata_file_contents
+                .get(offset..)?
+                .get(..SECTOR_METADATA_RECORD_SIZE)
+        })
+        .unwrap_or_default()
+}
+
+/// Decode record of sector at `sector_offset` from contents of sector metadata file
+pub(crate) fn read_sector_metadata_record(
+    sector_metadata_file_contents: &[u8],
+    sector_offset: u64,
+) -> Result<SectorMetadataRecord, SectorMetadataRecordError> {
+    SectorMetadataRecord::decode(
+        sector_metadata_record(sector_metadata_file_contents, sector_offset),
+        SectorMetadataRecord::LATEST_VERSION,
+    )
+}
+
+/// Decode sector metadata of sector at `sector_offset` from contents of sector metadata file
+pub(crate) fn read_sector_metadata(
+    sector_metadata_file_contents: &[u8],
+    sector_offset: u64,
+) -> Result<SectorMetadata, SectorMetadataRecordError> {
+    read_sector_metadata_record(sector_metadata_file_contents, sector_offset)
+        .map(|record| record.sector_metadata)
+}
+
+/// Information about plotted sector stored in its sector metadata record
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SectorInfo {
+    /// Sector index
+    pub index: SectorIndex,
+    /// Slot that was current when sector was plotted, `None` if unknown (sector was plotted before
+    /// it was recorded or before the first slot was received)
+    pub plotted_at_slot: Option<SlotNumber>,
+    /// Segment index of archived history at which sector expires, `0` for invalid sectors
+    pub expires_at: SegmentIndex,
+    /// Rotation of the global salt sector was encoded with, `0` for invalid sectors
+    pub rotation: u64,
+    /// Sector metadata record was decoded successfully, sectors with invalid records are corrupted
+    /// and should be replotted
+    pub valid: bool,
+}
+
+/// Information about the first `sector_count` sectors from contents of sector metadata file, sector
+/// at offset `0` has `first_sector_index`
+pub(crate) fn sector_infos<C>(
+    sector_metadata_file_contents: C,
+    first_sector_index: SectorIndex,
+    sector_count: u64,
+) -> impl Iterator<Item = SectorInfo>
+where
+    C: AsRef<[u8]>,
+{
+    (0..sector_count).map(move |sector_offset| {
+        let index = first_sector_index.offset(sector_offset);
+        let decoded =
+            read_sector_metadata_record(sector_metadata_file_contents.as_ref(), sector_offset);
+
+        match decoded {
+            Ok(record) => SectorInfo {
+                index,
+                plotted_at_slot: record.plotted_at_slot,
+                expires_at: record.sector_metadata.expires_at,
+                rotation: record.rotation,
+                valid: true,
+            },
+            Err(_error) => SectorInfo {
+                index,
+                plotted_at_slot: None,
+                expires_at: SegmentIndex::ZERO,
+                rotation: 0,
+                valid: false,
+            },
+        }
+    })
+}
+
+/// Open sector metadata file at `path` for reading and writing, creating it if necessary.
+///
+/// When file doesn't exist yet, metadata of `sector_count` sectors is migrated from plot
+/// `metadata_file` if it was stored there by older versions of the farmer, after which that part
+/// of plot metadata file is truncated.
+pub(crate) fn open_sector_metadata_file(
+    path: &Path,
+    metadata_file: &File,
+    sector_count: u64,
+) -> Result<File, SingleDiskPlotError> {
+    if !path.exists() {
+        // Written under temporary name and renamed, so interrupted migration is simply restarted
+        let tmp_path = migration_tmp_path(path);
+        let tmp_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        tmp_file.write_all_at(&SectorMetadataFileHeader::new().encode(), 0)?;
+
+        let mut legacy_record = vec![0; SectorMetadata::encoded_size()];
+        let mut record = [0; SECTOR_METADATA_RECORD_SIZE];
+        for sector_offset in 0..sector_count {
+            metadata_file.read_exact_at(
+                &mut legacy_record,
+                RESERVED_PLOT_METADATA + sector_offset * SectorMetadata::encoded_size() as u64,
+            )?;
+            record[..legacy_record.len()].copy_from_slice(&legacy_record);
+            tmp_file.write_all_at(&record, sector_metadata_record_offset(sector_offset))?;
+        }
+        tmp_file.sync_all()?;
+        drop(tmp_file);
+
+        fs::rename(&tmp_path, path)?;
+    }
+
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    check_header(&file)?;
+
+    // Migration may have been interrupted after sector metadata file was created, so this is
+    // checked every time
+    if metadata_file.metadata()?.len() > RESERVED_PLOT_METADATA {
+        metadata_file.set_len(RESERVED_PLOT_METADATA)?;
+    }
+
+    Ok(file)
+}
+
+/// Remove files left behind by interrupted migration of sector metadata file at `path`, returns
+/// number of bytes reclaimed.
+///
+/// Must only be called after [`open_sector_metadata_file()`] succeeded, since such files are
+/// abandoned at that point.
+pub(crate) fn remove_abandoned_files(path: &Path) -> io::Result<u64> {
+    let tmp_path = migration_tmp_path(path);
+    let size = match fs::metadata(&tmp_path) {
+        Ok(metadata) => metadata.len(),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(0);
+        }
+        Err(error) => {
+            return Err(error);
+        }
+    };
+    fs::remove_file(tmp_path)?;
+
+    Ok(size)
+}
+
+fn migration_tmp_path(path: &Path) -> PathBuf {
+    path.with_extension("tmp")
+}
+
+/// Open existing sector metadata file at `path` for reading, `None` is returned if plot wasn't
+/// migrated yet and sector metadata is still stored in plot metadata file
+pub(crate) fn open_sector_metadata_file_read_only(
+    path: &Path,
+) -> Result<Option<File>, SingleDiskPlotError> {
+    let file = match OpenOptions::new().read(true).open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(None);
+        }
+        Err(error) => {
+            return Err(error.into());
+        }
+    };
+    check_header(&file)?;
+
+    Ok(Some(file))
+}
+
+fn check_header(file: &File) -> Result<(), SingleDiskPlotError> {
+    let mut header_bytes = [0; SECTOR_METADATA_RECORD_SIZE];
+    file.read_exact_at(&mut header_bytes, 0)?;
+    let header = SectorMetadataFileHeader::decode(&mut header_bytes.as_slice())
+        .map_err(SingleDiskPlotError::FailedToDecodeSectorMetadataHeader)?;
+
+    if header.version > SectorMetadataFileHeader::LATEST_VERSION {
+        return Err(SingleDiskPlotError::UnexpectedSectorMetadataVersion(
+            header.version,
+        ));
+    }
+    if header.record_size != SECTOR_METADATA_RECORD_SIZE as u32 {
+        return Err(SingleDiskPlotError::UnexpectedSectorMetadataRecordSize(
+            header.record_size,
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::sector_metadata::{
+    open_sector_metadata_file, open_sector_metadata_file_read_only, read_sector_metadata,
+    remove_abandoned_files, sector_infos, sector_metadata_file_size, sector_metadata_record_offset,
+    SectorInfo, SectorMetadataFileHeader, SectorMetadataRecord, SectorMetadataRecordError,
+    SECTOR_CHECKSUM_SIZE, SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::{SectorMetadata, SingleDiskPlotError, RESERVED_PLOT_METADATA};
+use parity_scale_codec::Encode;
+use std::fs;
+use std::fs::OpenOptions;
+use std::num::NonZeroU64;
+use std::path::Path;
+use subspace_core_primitives::{SectorIndex, SegmentIndex, SlotNumber};
+use tempfile::tempdir;
+
+fn sector_metadata(sector_offset: u64) -> SectorMetadata {
+    SectorMetadata {
+        total_pieces: NonZeroU64::new(100 + sector_offset).unwrap(),
+        expires_at: sector_offset,
+    }
+}
+
+/// Create plot metadata file the way older versions of the farmer did, with tightly packed sector
+/// metadata after reserved space
+fn create_legacy_metadata_file(path: &Path, sector_count: u64) -> fs::File {
+    let metadata_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)
+        .unwrap();
+    metadata_file.set_len(RESERVED_PLOT_METADATA).unwrap();
+    for sector_offset in 0..sector_count {
+        metadata_file
+            .write_all_at(
+                &sector_metadata(sector_offset).encode(),
+                RESERVED_PLOT_METADATA + sector_offset * SectorMetadata::encoded_size() as u64,
+            )
+            .unwrap();
+    }
+
+    metadata_file
+}
+
+#[test]
+fn migrate_legacy_metadata() {
+    let directory = tempdir().unwrap();
+    let sector_count = 3;
+    let metadata_file =
+        create_legacy_metadata_file(&directory.path().join("metadata.bin"), sector_count);
+    let path = directory.path().join("sector_metadata.bin");
+
+    let sector_metadata_file =
+        open_sector_metadata_file(&path, &metadata_file, sector_count).unwrap();
+
+    // Sector metadata is no longer stored in plot metadata file
+    assert_eq!(
+        metadata_file.metadata().unwrap().len(),
+        RESERVED_PLOT_METADATA
+    );
+    assert!(!path.with_extension("tmp").exists());
+
+    let contents = fs::read(&path).unwrap();
+    assert_eq!(
+        contents.len() as u64,
+        sector_metadata_file_size(sector_count)
+    );
+    for sector_offset in 0..sector_count {
+        assert_eq!(
+            read_sector_metadata(&contents, sector_offset)
+                .unwrap()
+                .encode(),
+            sector_metadata(sector_offset).encode()
+        );
+    }
+    assert!(read_sector_metadata(&contents, sector_count).is_err());
+
+    // Opening again doesn't touch already migrated file
+    drop(sector_metadata_file);
+    open_sector_metadata_file(&path, &metadata_file, sector_count).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), contents);
+    assert!(open_sector_metadata_file_read_only(&path)
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn abandoned_files_removed() {
+    let directory = tempdir().unwrap();
+    let metadata_file = create_legacy_metadata_file(&directory.path().join("metadata.bin"), 0);
+    let path = directory.path().join("sector_metadata.bin");
+    open_sector_metadata_file(&path, &metadata_file, 0).unwrap();
+
+    // Nothing to remove
+    assert_eq!(remove_abandoned_files(&path).unwrap(), 0);
+
+    fs::write(path.with_extension("tmp"), [1; 100]).unwrap();
+    assert_eq!(remove_abandoned_files(&path).unwrap(), 100);
+    assert!(!path.with_extension("tmp").exists());
+    // Sector metadata file itself is untouched
+    assert!(open_sector_metadata_file_read_only(&path)
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn read_only_not_migrated() {
+    let directory = tempdir().unwrap();
+
+    assert!(
+        open_sector_metadata_file_read_only(&directory.path().join("sector_metadata.bin"))
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn unsupported_header() {
+    let directory = tempdir().unwrap();
+    let metadata_file = create_legacy_metadata_file(&directory.path().join("metadata.bin"), 0);
+    let path = directory.path().join("sector_metadata.bin");
+
+    let write_header = |header: SectorMetadataFileHeader| {
+        let mut header_bytes = header.encode();
+        header_bytes.resize(SECTOR_METADATA_RECORD_SIZE, 0);
+        fs::write(&path, header_bytes).unwrap();
+    };
+
+    write_header(SectorMetadataFileHeader {
+        version: SectorMetadataFileHeader::LATEST_VERSION + 1,
+        record_size: SECTOR_METADATA_RECORD_SIZE as u32,
+    });
+    assert!(matches!(
+        open_sector_metadata_file(&path, &metadata_file, 0),
+        Err(SingleDiskPlotError::UnexpectedSectorMetadataVersion(_))
+    ));
+
+    write_header(SectorMetadataFileHeader {
+        version: SectorMetadataFileHeader::LATEST_VERSION,
+        record_size: 32,
+    });
+    assert!(matches!(
+        open_sector_metadata_file_read_only(&path),
+        Err(SingleDiskPlotError::UnexpectedSectorMetadataRecordSize(32))
+    ));
+}
+
+#[test]
+fn sector_infos_enumeration() {
+    let first_sector_index = SectorIndex::new(100);
+    let plotted_at_slots = [
+        None,
+        Some(SlotNumber::new(5)),
+        Some(SlotNumber::new(u64::MAX)),
+        Some(SlotNumber::new(7)),
+    ];
+    let mut contents = vec![0; sector_metadata_file_size(plotted_at_slots.len() as u64) as usize];
+    for (sector_offset, plotted_at_slot) in plotted_at_slots.into_iter().enumerate() {
+        contents[sector_metadata_record_offset(sector_offset as u64) as usize..]
+            [..SECTOR_METADATA_RECORD_SIZE]
+            .copy_from_slice(
+                &SectorMetadataRecord {
+                    sector_metadata: sector_metadata(sector_offset as u64),
+                    plotted_at_slot,
+                    rotation: sector_offset as u64,
+                    checksum: None,
+                }
+                .encode(),
+            );
+    }
+    // Corrupt the last record, total pieces can't be zero
+    contents[sector_metadata_record_offset(3) as usize..][..8].fill(0);
+
+    let sectors = sector_infos(&contents, first_sector_index, 4).collect::<Vec<_>>();
+    assert_eq!(
+        sectors,
+        vec![
+            SectorInfo {
+                index: SectorIndex::new(100),
+                plotted_at_slot: None,
+                expires_at: SegmentIndex::ZERO,
+                rotation: 0,
+                valid: true,
+            },
+            SectorInfo {
+                index: SectorIndex::new(101),
+                plotted_at_slot: Some(SlotNumber::new(5)),
+                expires_at: SegmentIndex::ONE,
+                rotation: 1,
+                valid: true,
+            },
+            SectorInfo {
+                index: SectorIndex::new(102),
+                plotted_at_slot: Some(SlotNumber::new(u64::MAX)),
+                expires_at: SegmentIndex::new(2),
+                rotation: 2,
+                valid: true,
+            },
+            SectorInfo {
+                index: SectorIndex::new(103),
+                plotted_at_slot: None,
+                expires_at: SegmentIndex::ZERO,
+                rotation: 0,
+                valid: false,
+            },
+        ]
+    );
+
+    // Only requested number of sectors is enumerated, records beyond the file are invalid
+    assert_eq!(sector_infos(&contents, first_sector_index, 2).count(), 2);
+    assert!(
+        !sector_infos(&contents, first_sector_index, 5)
+            .nth(4)
+            .unwrap()
+            .valid
+    );
+}
+
+#[test]
+fn record_round_trip() {
+    let record = SectorMetadataRecord {
+        sector_metadata: sector_metadata(5),
+        plotted_at_slot: Some(SlotNumber::new(0x0102030405060708)),
+        rotation: 3,
+        checksum: Some([7; SECTOR_CHECKSUM_SIZE]),
+    };
+    let encoded = record.encode();
+
+    // Layout is documented and must not change
+    let mut expected = [0; SECTOR_METADATA_RECORD_SIZE];
+    expected[..8].copy_from_slice(&105u64.to_le_bytes());
+    expected[8..16].copy_from_slice(&5u64.to_le_bytes());
+    expected[16] = 1;
+    expected[17..25].copy_from_slice(&0x0102030405060708u64.to_le_bytes());
+    expected[25..33].copy_from_slice(&3u64.to_le_bytes());
+    expected[33] = 1;
+    expected[34..50].copy_from_slice(&[7; SECTOR_CHECKSUM_SIZE]);
+    assert_eq!(encoded, expected);
+
+    assert_eq!(
+        SectorMetadataRecord::decode(&encoded, SectorMetadataRecord::LATEST_VERSION).unwrap(),
+        record
+    );
+
+    // Records written before plotted-at slot, rotation and checksum were stored
+    let record = SectorMetadataRecord {
+        plotted_at_slot: None,
+        rotation: 0,
+        checksum: None,
+        ..record
+    };
+    let mut encoded = [0; SECTOR_METADATA_RECORD_SIZE];
+    encoded[..SectorMetadata::encoded_size()].copy_from_slice(&record.sector_metadata.encode());
+    assert_eq!(record.encode(), encoded);
+    assert_eq!(
+        SectorMetadataRecord::decode(&encoded, SectorMetadataRecord::LATEST_VERSION).unwrap(),
+        record
+    );
+}
+
+#[test]
+fn record_unsupported() {
+    let encoded = SectorMetadataRecord {
+        sector_metadata: sector_metadata(1),
+        plotted_at_slot: None,
+        rotation: 0,
+        checksum: None,
+    }
+    .encode();
+
+    assert!(matches!(
+        SectorMetadataRecord::decode(&encoded, SectorMetadataRecord::LATEST_VERSION + 1),
+        Err(SectorMetadataRecordError::UnsupportedVersion { version })
+            if version == SectorMetadataRecord::LATEST_VERSION + 1
+    ));
+    assert!(matches!(
+        SectorMetadataRecord::decode(&encoded[..32], SectorMetadataRecord::LATEST_VERSION),
+        Err(SectorMetadataRecordError::InvalidSize { size: 32 })
+    ));
+    // Total pieces can't be zero
+    assert!(matches!(
+        SectorMetadataRecord::decode(
+            &[0; SECTOR_METADATA_RECORD_SIZE],
+            SectorMetadataRecord::LATEST_VERSION
+        ),
+        Err(SectorMetadataRecordError::Decode(_))
+    ));
+}
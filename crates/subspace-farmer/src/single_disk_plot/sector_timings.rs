@@ -0,0 +1,201 @@
+//! Export of timings of individual sectors for offline analysis.
+//!
+//! Pushed metrics and plotting stats are aggregated over the whole plot, which makes it hard to
+//! compare disks and machines of a fleet or to find individual slow sectors. When export is
+//! enabled, every plotted sector and every audit of a sector appends a row
+//! `sector_index, plot_ms, audit_ms, solutions` to a sink. Only fields that are known for the
+//! event are filled, the rest are empty in CSV and `null` in JSON.
+//!
+//! Every plotted sector is audited in every slot, so the sink receives a row per sector per slot
+//! and grows quickly, export is meant to be enabled for limited periods of time.
+
+#[cfg(test)]
+mod tests;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use subspace_core_primitives::SectorIndex;
+use tracing::warn;
+
+/// Header of CSV export
+const CSV_HEADER: &str = "sector_index,plot_ms,audit_ms,solutions";
+
+/// Format sector timings are exported in
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SectorTimingsFormat {
+    /// Comma-separated values with a header
+    Csv,
+    /// One JSON object per line
+    Json,
+}
+
+impl SectorTimingsFormat {
+    /// Name of the file in plot directory sector timings are exported to
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::Csv => "sector_timings.csv",
+            Self::Json => "sector_timings.json",
+        }
+    }
+}
+
+/// Single row of sector timings export
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SectorTiming {
+    /// Index of the sector
+    pub sector_index: u64,
+    /// Time spent plotting the sector in milliseconds, only present when sector was plotted
+    pub plot_ms: Option<u64>,
+    /// Time spent auditing the sector (and creating solution if it was eligible) in milliseconds,
+    /// only present when sector was audited
+    pub audit_ms: Option<u64>,
+    /// Number of solutions audit has produced, only present when sector was audited
+    pub solutions: Option<u64>,
+}
+
+impl SectorTiming {
+    fn write_csv<W>(&self, mut sink: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        fn field(value: Option<u64>) -> String {
+            value.map(|value| value.to_string()).unwrap_or_default()
+        }
+
+        writeln!(
+            sink,
+            "{},{},{},{}",
+            self.sector_index,
+            field(self.plot_ms),
+            field(self.audit_ms),
+            field(self.solutions),
+        )
+    }
+
+    fn write_json<W>(&self, mut sink: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        serde_json::to_writer(&mut sink, self)?;
+        writeln!(sink)
+    }
+}
+
+struct Inner {
+    format: SectorTimingsFormat,
+    /// `None` once writing to the sink has failed
+    sink: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+/// Exporter of sector timings into a sink.
+///
+/// Failure to write to the sink is logged and stops the export, farming is never affected by it.
+///
+/// Cheap to clone, all clones share the same state.
+#[derive(Clone)]
+pub struct SectorTimings {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for SectorTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SectorTimings")
+            .field("format", &self.inner.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SectorTimings {
+    /// Export sector timings into `sink`, CSV header is written right away.
+    ///
+    /// Rows are written to the sink as they are recorded, wrap it into [`BufWriter`] if writes are
+    /// expensive.
+    pub fn new<W>(format: SectorTimingsFormat, sink: W) -> io::Result<Self>
+    where
+        W: Write + Send + 'static,
+    {
+        Self::with_header(format, Box::new(sink), true)
+    }
+
+    /// Export sector timings into file at `path`, rows are appended if file exists already and CSV
+    /// header is only written into empty file
+    pub fn open(format: SectorTimingsFormat, path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        let empty = file.metadata()?.len() == 0;
+
+        Self::with_header(format, Box::new(BufWriter::new(file)), empty)
+    }
+
+    fn with_header(
+        format: SectorTimingsFormat,
+        mut sink: Box<dyn Write + Send>,
+        write_header: bool,
+    ) -> io::Result<Self> {
+        if write_header && format == SectorTimingsFormat::Csv {
+            writeln!(sink, "{CSV_HEADER}")?;
+            sink.flush()?;
+        }
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                format,
+                sink: Mutex::new(Some(sink)),
+            }),
+        })
+    }
+
+    /// Record that sector was plotted in `plot_time`, sink is flushed afterwards
+    pub fn sector_plotted(&self, sector_index: SectorIndex, plot_time: Duration) {
+        self.record(&SectorTiming {
+            sector_index: sector_index.get(),
+            plot_ms: Some(plot_time.as_millis() as u64),
+            audit_ms: None,
+            solutions: None,
+        });
+        self.flush();
+    }
+
+    /// Record that sector was audited in `audit_time` and produced `solutions`.
+    ///
+    /// Sink is not flushed, call [`Self::flush()`] once all sectors are audited.
+    pub fn sector_audited(&self, sector_index: SectorIndex, audit_time: Duration, solutions: u64) {
+        self.record(&SectorTiming {
+            sector_index: sector_index.get(),
+            plot_ms: None,
+            audit_ms: Some(audit_time.as_millis() as u64),
+            solutions: Some(solutions),
+        });
+    }
+
+    /// Write arbitrary row into the sink
+    pub fn record(&self, timing: &SectorTiming) {
+        self.write_with(|sink| match self.inner.format {
+            SectorTimingsFormat::Csv => timing.write_csv(sink),
+            SectorTimingsFormat::Json => timing.write_json(sink),
+        });
+    }
+
+    /// Flush rows written so far
+    pub fn flush(&self) {
+        self.write_with(|sink| sink.flush());
+    }
+
+    fn write_with<F>(&self, f: F)
+    where
+        F: FnOnce(&mut dyn Write) -> io::Result<()>,
+    {
+        let mut maybe_sink = self.inner.sink.lock();
+        if let Some(sink) = maybe_sink.as_mut() {
+            if let Err(error) = f(sink.as_mut()) {
+                warn!(%error, "Failed to export sector timings, export is stopped");
+                maybe_sink.take();
+            }
+        }
+    }
+}
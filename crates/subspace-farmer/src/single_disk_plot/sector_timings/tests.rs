@@ -0,0 +1,176 @@
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use crate::single_disk_plot::sector_timings::{
+    SectorTiming, SectorTimings, SectorTimingsFormat, CSV_HEADER,
+};
+use crate::testing::fixtures::{farmer_protocol_info, DerivedPieceReceiver};
+use futures::executor::block_on;
+use parking_lot::Mutex;
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{PublicKey, SectorIndex, SolutionRange};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tempfile::TempDir;
+
+/// Sink that can be inspected while exporter still holds it
+#[derive(Debug, Default, Clone)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct FailingSink;
+
+impl Write for FailingSink {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::Other.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::ErrorKind::Other.into())
+    }
+}
+
+fn parse_csv(contents: &str) -> Vec<SectorTiming> {
+    let mut lines = contents.lines();
+    assert_eq!(lines.next(), Some(CSV_HEADER));
+
+    lines
+        .map(|line| {
+            let fields = line.split(',').collect::<Vec<_>>();
+            assert_eq!(fields.len(), 4);
+            let optional_field = |index: usize| {
+                (!fields[index].is_empty()).then(|| fields[index].parse::<u64>().unwrap())
+            };
+
+            SectorTiming {
+                sector_index: fields[0].parse().unwrap(),
+                plot_ms: optional_field(1),
+                audit_ms: optional_field(2),
+                solutions: optional_field(3),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn export_sector_timings() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let farmer_protocol_info = FarmerProtocolInfo {
+        genesis_hash: rand::random(),
+        ..farmer_protocol_info()
+    };
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    let sectors = (0..2)
+        .map(|sector_offset| {
+            let sector_index = SectorIndex::new(sector_offset);
+            let mut sector = Vec::new();
+            let plotting_started_at = Instant::now();
+            block_on(plot_sector(
+                &public_key,
+                sector_index,
+                &DerivedPieceReceiver,
+                &AtomicBool::new(false),
+                &farmer_protocol_info,
+                SectorBuffer::new(&mut sector),
+                io::sink(),
+            ))
+            .unwrap();
+
+            (sector_index, sector, plotting_started_at.elapsed())
+        })
+        .collect::<Vec<_>>();
+
+    for format in [SectorTimingsFormat::Csv, SectorTimingsFormat::Json] {
+        let buffer = SharedBuffer::default();
+        let sector_timings = SectorTimings::new(format, buffer.clone()).unwrap();
+        let mut expected_rows = Vec::new();
+
+        for (sector_index, _sector, plot_time) in &sectors {
+            sector_timings.sector_plotted(*sector_index, *plot_time);
+            expected_rows.push(SectorTiming {
+                sector_index: sector_index.get(),
+                plot_ms: Some(plot_time.as_millis() as u64),
+                audit_ms: None,
+                solutions: None,
+            });
+        }
+
+        for (sector_index, sector, _plot_time) in &sectors {
+            let audit_started_at = Instant::now();
+            let maybe_eligible_sector = audit_sector(
+                &public_key,
+                *sector_index,
+                &farmer_protocol_info,
+                KzgParametersId::TEST,
+                &kzg,
+                &rand::random(),
+                SolutionRange::MAX,
+                Cursor::new(sector),
+            )
+            .unwrap();
+            let audit_time = audit_started_at.elapsed();
+            // Solution range covers everything, so every eligible sector counts as a solution
+            let solutions = u64::from(maybe_eligible_sector.is_some());
+
+            sector_timings.sector_audited(*sector_index, audit_time, solutions);
+            expected_rows.push(SectorTiming {
+                sector_index: sector_index.get(),
+                plot_ms: None,
+                audit_ms: Some(audit_time.as_millis() as u64),
+                solutions: Some(solutions),
+            });
+        }
+        sector_timings.flush();
+
+        let contents = String::from_utf8(buffer.0.lock().clone()).unwrap();
+        let rows = match format {
+            SectorTimingsFormat::Csv => parse_csv(&contents),
+            SectorTimingsFormat::Json => contents
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect(),
+        };
+        assert_eq!(rows, expected_rows);
+    }
+}
+
+#[test]
+fn append_to_file() {
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().join(SectorTimingsFormat::Csv.file_name());
+
+    for sector_index in 0..2 {
+        SectorTimings::open(SectorTimingsFormat::Csv, &path)
+            .unwrap()
+            .sector_plotted(SectorIndex::new(sector_index), Default::default());
+    }
+
+    // Header is only written once, rows of both exporters are preserved
+    assert_eq!(
+        parse_csv(&fs::read_to_string(&path).unwrap())
+            .iter()
+            .map(|row| row.sector_index)
+            .collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+
+    // Failing sink doesn't panic, export just stops
+    assert!(SectorTimings::new(SectorTimingsFormat::Csv, FailingSink).is_err());
+    let sector_timings = SectorTimings::new(SectorTimingsFormat::Json, FailingSink).unwrap();
+    sector_timings.sector_audited(SectorIndex::new(0), Default::default(), 0);
+    assert!(sector_timings.inner.sink.lock().is_none());
+}
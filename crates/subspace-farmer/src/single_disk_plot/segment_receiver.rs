@@ -0,0 +1,464 @@
+#[cfg(test)]
+mod tests;
+
+use crate::clock::{Clock, TokioClock};
+use crate::memory_budget::{MemoryBudget, MemoryCategory};
+use crate::piece_cache::FarmerPieceCache;
+use crate::root_block_store::RootBlockStore;
+use crate::single_disk_plot::piece_receiver::{
+    PieceDownload, PieceDownloads, PieceReceiver, MAX_RETRY_INTERVAL,
+};
+use crate::{RpcClient, RpcClientError};
+use async_trait::async_trait;
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use futures::FutureExt;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subspace_archiving::archiver::is_piece_valid;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{Piece, PieceIndex, RecordsRoot, SegmentIndex, PIECE_SIZE};
+use subspace_rpc_primitives::{SegmentPieces, MAX_PIECES_PER_SEGMENT_REQUEST};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::{debug, trace, warn};
+
+/// Number of attempts to request a chunk of segment pieces from the node, node rejects requests
+/// while it is busy serving other segment requests
+const SEGMENT_REQUEST_ATTEMPTS: usize = 3;
+/// Delay before requesting chunk of segment pieces again after failed attempt
+const SEGMENT_REQUEST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Errors happening when downloading archived segment from the node
+#[derive(Debug, Error)]
+pub enum SegmentDownloadError {
+    /// Request to the node failed
+    #[error("Failed to request pieces of segment {segment_index} from node: {error}")]
+    Rpc {
+        /// Segment index
+        segment_index: SegmentIndex,
+        /// Lower-level error
+        error: RpcClientError,
+    },
+    /// Node returned pieces starting at a different position than requested
+    #[error(
+        "Node returned pieces of segment {segment_index} starting at position {actual} instead of \
+        {expected}"
+    )]
+    UnexpectedPosition {
+        /// Segment index
+        segment_index: SegmentIndex,
+        /// Requested position
+        expected: u32,
+        /// Position node returned pieces from
+        actual: u32,
+    },
+    /// Node doesn't have all pieces of the segment
+    #[error("Node returned only {pieces} out of {expected} pieces of segment {segment_index}")]
+    IncompleteSegment {
+        /// Segment index
+        segment_index: SegmentIndex,
+        /// Number of pieces node returned
+        pieces: u32,
+        /// Number of pieces in segment
+        expected: u32,
+    },
+    /// Records root returned by the node doesn't match root block in root block store or records
+    /// root returned with earlier pieces of the same segment
+    #[error("Node returned unexpected records root of segment {segment_index}")]
+    RecordsRootMismatch {
+        /// Segment index
+        segment_index: SegmentIndex,
+    },
+    /// Piece returned by the node failed verification
+    #[error("Piece at position {position} of segment {segment_index} failed verification")]
+    InvalidPiece {
+        /// Segment index
+        segment_index: SegmentIndex,
+        /// Position of the piece in segment
+        position: u32,
+    },
+}
+
+/// Segment download that failed, segment is downloaded again once `retry_at` comes
+struct FailedSegmentDownload {
+    backoff: ExponentialBackoff,
+    retry_at: Instant,
+}
+
+#[derive(Default)]
+struct SegmentDownloadStates {
+    /// Segments that were downloaded successfully, they are not downloaded again
+    downloaded: HashSet<SegmentIndex>,
+    /// Segments whose download failed, removed once segment is downloaded successfully
+    failed: HashMap<SegmentIndex, FailedSegmentDownload>,
+}
+
+struct Inner<RC, C> {
+    rpc_client: RC,
+    piece_cache: FarmerPieceCache,
+    root_block_store: RootBlockStore,
+    kzg: Kzg,
+    memory_budget: MemoryBudget,
+    record_size: u32,
+    /// Number of data and parity pieces in a segment
+    pieces_in_segment: u32,
+    clock: C,
+    in_flight_segments: Semaphore,
+    /// Downloads in progress, pieces of the whole segment are shared by all requests waiting for it
+    downloads: PieceDownloads<SegmentIndex, Arc<Vec<Piece>>>,
+    states: Mutex<SegmentDownloadStates>,
+}
+
+/// Piece receiver that downloads whole archived segments from the node piece belongs to, verifies
+/// all of their pieces and adds them to piece cache, so that the rest of the pieces of the same
+/// segment are retrieved from the cache afterwards.
+///
+/// Meant to be the first source of pieces during initial plotting, when most of the history is not
+/// in piece cache yet and trusted local node has all of it. Every segment is downloaded at most
+/// once, pieces that were evicted from piece cache since then and pieces of segments that node
+/// didn't archive yet are reported as not found, so they are retrieved from the next source in the
+/// chain. Segment that failed to download is downloaded again after exponential backoff (so that
+/// node that doesn't support segment requests or is overloaded isn't asked about every single
+/// piece), its pieces are reported as not found in the meantime.
+///
+/// Number of segments downloaded at the same time is capped, requests for pieces of other segments
+/// wait for download of one of the segments to finish. Node limits number of concurrent segment
+/// requests on its side too and rejects requests above its limit, those are retried a few times.
+pub struct SegmentPieceReceiver<RC, C = TokioClock> {
+    inner: Arc<Inner<RC, C>>,
+}
+
+impl<RC> SegmentPieceReceiver<RC>
+where
+    RC: RpcClient,
+{
+    /// Create new piece receiver that downloads at most `max_in_flight_segments` segments at a
+    /// time, records roots of segments are checked against root blocks in `root_block_store`
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        rpc_client: RC,
+        piece_cache: FarmerPieceCache,
+        root_block_store: RootBlockStore,
+        kzg: Kzg,
+        memory_budget: MemoryBudget,
+        record_size: u32,
+        recorded_history_segment_size: u32,
+        max_in_flight_segments: NonZeroUsize,
+    ) -> Self {
+        Self::with_clock(
+            rpc_client,
+            piece_cache,
+            root_block_store,
+            kzg,
+            memory_budget,
+            record_size,
+            recorded_history_segment_size,
+            max_in_flight_segments,
+            TokioClock,
+        )
+    }
+}
+
+impl<RC, C> SegmentPieceReceiver<RC, C>
+where
+    RC: RpcClient,
+    C: Clock + 'static,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_clock(
+        rpc_client: RC,
+        piece_cache: FarmerPieceCache,
+        root_block_store: RootBlockStore,
+        kzg: Kzg,
+        memory_budget: MemoryBudget,
+        record_size: u32,
+        recorded_history_segment_size: u32,
+        max_in_flight_segments: NonZeroUsize,
+        clock: C,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                rpc_client,
+                piece_cache,
+                root_block_store,
+                kzg,
+                memory_budget,
+                record_size,
+                pieces_in_segment: recorded_history_segment_size / record_size * 2,
+                clock,
+                in_flight_segments: Semaphore::new(max_in_flight_segments.get()),
+                // Pieces of the whole segment are added to piece cache instead of being kept here
+                downloads: PieceDownloads::new(Duration::ZERO),
+                states: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Download that is in progress, new download is started if there is none, `None` if segment
+    /// was downloaded before or its download failed recently
+    fn get_or_start(&self, segment_index: SegmentIndex) -> Option<PieceDownload<Arc<Vec<Piece>>>> {
+        {
+            let states = self.inner.states.lock();
+            if states.downloaded.contains(&segment_index) {
+                return None;
+            }
+            if let Some(failed) = states.failed.get(&segment_index) {
+                if failed.retry_at > self.inner.clock.now() {
+                    return None;
+                }
+            }
+        }
+
+        Some(self.inner.downloads.get_or_start(segment_index, || {
+            let inner = Arc::clone(&self.inner);
+            async move {
+                let result = inner.download_segment(segment_index).await;
+                inner.finish(segment_index, &result);
+
+                result.map_err(|error| Arc::new(error) as Arc<dyn Error + Send + Sync + 'static>)
+            }
+            .boxed()
+        }))
+    }
+}
+
+impl<RC, C> Inner<RC, C>
+where
+    RC: RpcClient,
+    C: Clock,
+{
+    /// Remember outcome of segment download, so that downloaded segment isn't downloaded again and
+    /// failed segment is downloaded again after backoff
+    fn finish(
+        &self,
+        segment_index: SegmentIndex,
+        result: &Result<Option<Arc<Vec<Piece>>>, SegmentDownloadError>,
+    ) {
+        let mut states = self.states.lock();
+        match result {
+            Ok(Some(_pieces)) => {
+                states.failed.remove(&segment_index);
+                states.downloaded.insert(segment_index);
+            }
+            Ok(None) => {
+                // Segment that is not archived yet will be archived eventually, it is requested
+                // again right away
+            }
+            Err(error) => {
+                let now = self.clock.now();
+                let failed =
+                    states
+                        .failed
+                        .entry(segment_index)
+                        .or_insert_with(|| FailedSegmentDownload {
+                            backoff: ExponentialBackoff {
+                                max_interval: MAX_RETRY_INTERVAL,
+                                max_elapsed_time: None,
+                                ..ExponentialBackoff::default()
+                            },
+                            retry_at: now,
+                        });
+                // Randomization may take delay above maximum interval otherwise
+                let delay = failed
+                    .backoff
+                    .next_backoff()
+                    .expect("Backoff without maximum elapsed time never ends; qed")
+                    .min(MAX_RETRY_INTERVAL);
+                failed.retry_at = now + delay;
+
+                debug!(%segment_index, %error, ?delay, "Segment download failed, will retry later");
+            }
+        }
+    }
+
+    async fn download_segment(
+        &self,
+        segment_index: SegmentIndex,
+    ) -> Result<Option<Arc<Vec<Piece>>>, SegmentDownloadError> {
+        let _permit = self
+            .in_flight_segments
+            .acquire()
+            .await
+            .expect("Semaphore is never closed; qed");
+        let _memory_reservation = self.memory_budget.register(
+            MemoryCategory::Downloads,
+            u64::from(self.pieces_in_segment) * PIECE_SIZE as u64,
+        );
+
+        debug!(%segment_index, "Downloading segment from node");
+
+        let mut expected_records_root = None::<RecordsRoot>;
+        let mut pieces = Vec::with_capacity(self.pieces_in_segment as usize);
+        while (pieces.len() as u32) < self.pieces_in_segment {
+            let first_position = pieces.len() as u32;
+            let limit =
+                (self.pieces_in_segment - first_position).min(MAX_PIECES_PER_SEGMENT_REQUEST);
+
+            let segment_pieces = match self
+                .request_segment_pieces(segment_index, first_position, limit)
+                .await?
+            {
+                Some(segment_pieces) => segment_pieces,
+                None => {
+                    trace!(%segment_index, "Segment is not archived by node yet");
+
+                    return Ok(None);
+                }
+            };
+
+            if segment_pieces.first_position != first_position {
+                return Err(SegmentDownloadError::UnexpectedPosition {
+                    segment_index,
+                    expected: first_position,
+                    actual: segment_pieces.first_position,
+                });
+            }
+            if segment_pieces.pieces.is_empty() {
+                return Err(SegmentDownloadError::IncompleteSegment {
+                    segment_index,
+                    pieces: first_position,
+                    expected: self.pieces_in_segment,
+                });
+            }
+
+            let records_root = match expected_records_root {
+                Some(records_root) => {
+                    if !records_root.ct_eq(&segment_pieces.records_root) {
+                        return Err(SegmentDownloadError::RecordsRootMismatch { segment_index });
+                    }
+                    records_root
+                }
+                None => {
+                    // Node is trusted when root block is not in the store yet, the same way as
+                    // when records root of a single piece is requested from it
+                    if let Some(root_block) = self.root_block_store.get(segment_index) {
+                        if !root_block
+                            .records_root()
+                            .ct_eq(&segment_pieces.records_root)
+                        {
+                            return Err(SegmentDownloadError::RecordsRootMismatch {
+                                segment_index,
+                            });
+                        }
+                    }
+                    *expected_records_root.insert(segment_pieces.records_root)
+                }
+            };
+
+            // Pieces beyond requested limit are ignored
+            for piece in segment_pieces.pieces.into_iter().take(limit as usize) {
+                let position = pieces.len() as u32;
+                if !is_piece_valid(
+                    &self.kzg,
+                    self.pieces_in_segment,
+                    &piece,
+                    records_root,
+                    position,
+                    self.record_size,
+                ) {
+                    warn!(%segment_index, %position, "Node returned invalid piece");
+
+                    return Err(SegmentDownloadError::InvalidPiece {
+                        segment_index,
+                        position,
+                    });
+                }
+
+                pieces.push(piece);
+            }
+        }
+
+        // Only pieces of the whole segment that passed verification are cached
+        let first_piece_index = segment_index.get() * PieceIndex::from(self.pieces_in_segment);
+        self.piece_cache
+            .add_pieces((first_piece_index..).zip(pieces.iter().cloned()));
+
+        debug!(%segment_index, "Segment downloaded from node");
+
+        Ok(Some(Arc::new(pieces)))
+    }
+
+    async fn request_segment_pieces(
+        &self,
+        segment_index: SegmentIndex,
+        first_position: u32,
+        limit: u32,
+    ) -> Result<Option<SegmentPieces>, SegmentDownloadError> {
+        let mut attempt = 1;
+        loop {
+            match self
+                .rpc_client
+                .segment_pieces(segment_index, first_position, limit)
+                .await
+            {
+                Ok(maybe_segment_pieces) => {
+                    return Ok(maybe_segment_pieces);
+                }
+                Err(error) if attempt < SEGMENT_REQUEST_ATTEMPTS => {
+                    debug!(
+                        %segment_index,
+                        %first_position,
+                        %error,
+                        %attempt,
+                        "Failed to request segment pieces from node, retrying"
+                    );
+
+                    self.clock
+                        .sleep_until(self.clock.now() + SEGMENT_REQUEST_RETRY_DELAY)
+                        .await;
+                    attempt += 1;
+                }
+                Err(error) => {
+                    return Err(SegmentDownloadError::Rpc {
+                        segment_index,
+                        error,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<RC, C> PieceReceiver for SegmentPieceReceiver<RC, C>
+where
+    RC: RpcClient,
+    C: Clock + 'static,
+{
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let pieces_in_segment = PieceIndex::from(self.inner.pieces_in_segment);
+        let segment_index = SegmentIndex::new(piece_index / pieces_in_segment);
+        let position = (piece_index % pieces_in_segment) as usize;
+
+        let shared_download = match self.get_or_start(segment_index) {
+            None => {
+                trace!(
+                    %piece_index,
+                    %segment_index,
+                    "Segment was downloaded before or its download failed recently, piece is not \
+                    available"
+                );
+
+                return Ok(None);
+            }
+            Some(PieceDownload::Recent(pieces)) => {
+                return Ok(pieces.get(position).cloned());
+            }
+            Some(PieceDownload::InFlight {
+                shared_download, ..
+            }) => shared_download,
+        };
+
+        let maybe_pieces = shared_download.await?;
+
+        Ok(maybe_pieces.and_then(|pieces| pieces.get(position).cloned()))
+    }
+}
@@ -0,0 +1,193 @@
+use crate::clock::TestClock;
+use crate::memory_budget::MemoryBudget;
+use crate::piece_cache::{EvictionPolicy, FarmerPieceCache};
+use crate::root_block_store::RootBlockStore;
+use crate::single_disk_plot::piece_receiver::{PieceReceiver, MAX_RETRY_INTERVAL};
+use crate::single_disk_plot::segment_receiver::{SegmentDownloadError, SegmentPieceReceiver};
+use crate::testing::fixtures::{archived_segment, TestRpcClient};
+use futures::future::join_all;
+use std::error::Error;
+use std::num::NonZeroUsize;
+use std::slice;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    PieceIndex, SegmentIndex, PIECES_IN_SEGMENT, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+};
+use tempfile::{tempdir, TempDir};
+
+fn segment_piece_receiver(
+    rpc_client: TestRpcClient,
+    kzg: Kzg,
+    clock: TestClock,
+) -> (
+    SegmentPieceReceiver<TestRpcClient, TestClock>,
+    FarmerPieceCache,
+    RootBlockStore,
+    TempDir,
+) {
+    let directory = tempdir().unwrap();
+    let root_block_store = RootBlockStore::open(directory.path()).unwrap();
+    let memory_budget = MemoryBudget::new(None);
+    let piece_cache = FarmerPieceCache::new(
+        PIECES_IN_SEGMENT as usize,
+        EvictionPolicy::Lru,
+        root_block_store.clone(),
+        &memory_budget,
+    );
+
+    let piece_receiver = SegmentPieceReceiver::with_clock(
+        rpc_client,
+        piece_cache.clone(),
+        root_block_store.clone(),
+        kzg,
+        memory_budget,
+        RECORD_SIZE,
+        RECORDED_HISTORY_SEGMENT_SIZE,
+        NonZeroUsize::new(1).unwrap(),
+        clock,
+    );
+
+    (piece_receiver, piece_cache, root_block_store, directory)
+}
+
+#[tokio::test]
+async fn segment_download() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let archived_segment = archived_segment(&kzg);
+    let rpc_client = TestRpcClient::new(slice::from_ref(&archived_segment));
+    let (piece_receiver, piece_cache, _root_block_store, _directory) =
+        segment_piece_receiver(rpc_client.clone(), kzg, TestClock::new());
+
+    let piece_indexes = [0, 5, PieceIndex::from(PIECES_IN_SEGMENT) - 1];
+    let pieces = join_all(
+        piece_indexes
+            .iter()
+            .map(|&piece_index| piece_receiver.get_piece(piece_index)),
+    )
+    .await;
+
+    let expected_pieces = archived_segment.pieces.as_pieces().collect::<Vec<_>>();
+    for (piece_index, piece) in piece_indexes.into_iter().zip(pieces) {
+        assert_eq!(
+            piece.unwrap().unwrap().as_ref(),
+            expected_pieces[piece_index as usize]
+        );
+    }
+    // Concurrent requests share a single download of the whole segment in chunks
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 4);
+    assert_eq!(piece_cache.len(), PIECES_IN_SEGMENT as usize);
+    assert_eq!(
+        piece_cache.get_piece(42).unwrap().as_ref(),
+        expected_pieces[42]
+    );
+
+    // Segment is not downloaded again, the rest of its pieces are retrieved from piece cache
+    assert!(piece_receiver.get_piece(42).await.unwrap().is_none());
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 4);
+
+    // Segment that is not archived yet is requested again later
+    let next_segment_piece_index = PieceIndex::from(PIECES_IN_SEGMENT);
+    assert!(piece_receiver
+        .get_piece(next_segment_piece_index)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(piece_receiver
+        .get_piece(next_segment_piece_index)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 6);
+}
+
+#[tokio::test]
+async fn segment_download_invalid_piece() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let archived_segment = archived_segment(&kzg);
+    let mut rpc_client = TestRpcClient::new(slice::from_ref(&archived_segment));
+    Arc::get_mut(&mut rpc_client.segments)
+        .unwrap()
+        .get_mut(&SegmentIndex::ZERO)
+        .unwrap()
+        .1[70][0] ^= 1;
+    let (piece_receiver, piece_cache, _root_block_store, _directory) =
+        segment_piece_receiver(rpc_client, kzg, TestClock::new());
+
+    let error = piece_receiver.get_piece(0).await.unwrap_err();
+    assert!(matches!(
+        error
+            .downcast_ref::<Arc<dyn Error + Send + Sync>>()
+            .unwrap()
+            .downcast_ref::<SegmentDownloadError>()
+            .unwrap(),
+        SegmentDownloadError::InvalidPiece { position: 70, .. }
+    ));
+    // Nothing is cached unless the whole segment is valid
+    assert!(piece_cache.is_empty());
+}
+
+#[tokio::test]
+async fn segment_download_records_root_mismatch() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let archived_segment = archived_segment(&kzg);
+    let rpc_client = TestRpcClient::new(slice::from_ref(&archived_segment));
+    let (piece_receiver, piece_cache, root_block_store, _directory) =
+        segment_piece_receiver(rpc_client, kzg.clone(), TestClock::new());
+
+    // Root block of a different history is already known to the farmer
+    root_block_store
+        .add(&[archived_segment(&kzg).root_block])
+        .unwrap();
+
+    let error = piece_receiver.get_piece(0).await.unwrap_err();
+    assert!(matches!(
+        error
+            .downcast_ref::<Arc<dyn Error + Send + Sync>>()
+            .unwrap()
+            .downcast_ref::<SegmentDownloadError>()
+            .unwrap(),
+        SegmentDownloadError::RecordsRootMismatch { .. }
+    ));
+    assert!(piece_cache.is_empty());
+}
+
+#[tokio::test]
+async fn segment_download_retry() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let archived_segment = archived_segment(&kzg);
+    let rpc_client = TestRpcClient::new(slice::from_ref(&archived_segment));
+    rpc_client.invalid_responses.store(2, Ordering::SeqCst);
+    let clock = TestClock::new();
+    let (piece_receiver, piece_cache, _root_block_store, _directory) =
+        segment_piece_receiver(rpc_client.clone(), kzg, clock.clone());
+
+    assert!(piece_receiver.get_piece(0).await.is_err());
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 1);
+
+    // Failed segment is not requested again until backoff ends
+    assert!(piece_receiver.get_piece(1).await.unwrap().is_none());
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 1);
+
+    // Segment is downloaded again once backoff ends
+    clock.advance(MAX_RETRY_INTERVAL);
+    assert!(piece_receiver.get_piece(1).await.is_err());
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 2);
+    assert!(piece_receiver.get_piece(1).await.unwrap().is_none());
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 2);
+
+    clock.advance(MAX_RETRY_INTERVAL);
+    assert_eq!(
+        piece_receiver.get_piece(1).await.unwrap().unwrap().as_ref(),
+        archived_segment.pieces.as_pieces().nth(1).unwrap()
+    );
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 6);
+    assert_eq!(piece_cache.len(), PIECES_IN_SEGMENT as usize);
+
+    // Successfully downloaded segment is never downloaded again
+    clock.advance(MAX_RETRY_INTERVAL);
+    assert!(piece_receiver.get_piece(2).await.unwrap().is_none());
+    assert_eq!(rpc_client.requests.load(Ordering::SeqCst), 6);
+}
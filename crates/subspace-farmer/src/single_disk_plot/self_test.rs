@@ -0,0 +1,261 @@
+//! Self-test of the whole farming pipeline.
+//!
+//! Deterministic segment of archived history is created, one sector is plotted from its pieces in
+//! memory and audited with solution range that makes any chunk win. Resulting solution is then
+//! verified the same way node does it, including witness of the piece against records root of the
+//! segment. Every phase is timed, which gives a rough idea about performance of the machine too.
+
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::plotting::{plot_sector, PlotSectorError, SectorBuffer};
+use crate::single_disk_plot::{FarmingError, SectorMetadata};
+use crate::testing::MapPieceReceiver;
+use futures::executor::block_on;
+use rand::prelude::*;
+use schnorrkel::{ExpansionMode, MiniSecretKey};
+use std::io::Cursor;
+use std::num::NonZeroU16;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+use subspace_archiving::archiver::{
+    is_piece_record_hash_valid, Archiver, ArchiverInstantiationError,
+};
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, PieceIndex, PublicKey, Randomness, RecordsRoot, SectorId,
+    SectorIndex, SegmentIndex, SlotNumber, Solution, SolutionRange, PIECES_IN_SEGMENT,
+    RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+};
+use subspace_rpc_primitives::{FarmerProtocolInfo, FarmerProtocolInfoError};
+use subspace_solving::{derive_global_challenge, verify_chunk_signature};
+use subspace_verification::is_within_solution_range;
+use thiserror::Error;
+use tracing::debug;
+
+/// Index of the sector that is plotted
+const SECTOR_INDEX: SectorIndex = SectorIndex::ZERO;
+/// Slot that audit is done for
+const SLOT_NUMBER: SlotNumber = SlotNumber::new(1);
+/// Seed for archived history and farmer keypair, so that results are reproducible
+const SEED: [u8; 32] = [1; 32];
+
+/// Errors that prevent self-test from completing
+#[derive(Debug, Error)]
+pub enum SelfTestError {
+    /// `space_l` can't be used for plotting
+    #[error("Invalid farmer protocol info: {0}")]
+    InvalidProtocolInfo(#[from] FarmerProtocolInfoError),
+    /// Failed to instantiate archiver
+    #[error("Failed to instantiate archiver: {0}")]
+    Archiver(#[from] ArchiverInstantiationError),
+    /// Failed to plot sector
+    #[error("Failed to plot sector: {0}")]
+    Plotting(#[from] PlotSectorError),
+    /// Failed to audit sector or create solution
+    #[error("Failed to audit sector: {0}")]
+    Farming(#[from] FarmingError),
+}
+
+/// Report of [`self_test()`]
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    /// Size of plotted sector in bytes
+    pub plot_sector_size: u64,
+    /// Time to archive one segment of history
+    pub archiving_time: Duration,
+    /// Time to plot one sector in memory
+    pub plotting_time: Duration,
+    /// Time to audit the sector
+    pub audit_time: Duration,
+    /// Time to decode winning piece and create solution
+    pub proving_time: Duration,
+    /// Time to verify solution
+    pub verification_time: Duration,
+    /// Audit produced a solution
+    pub solution_found: bool,
+    /// Solution is within solution range of the audit
+    pub within_solution_range: bool,
+    /// Chunk signature of the solution is valid
+    pub chunk_signature_valid: bool,
+    /// Witness of the piece in solution is valid for records root of the segment
+    pub witness_valid: bool,
+}
+
+impl SelfTestReport {
+    /// Whether every step of the pipeline succeeded
+    pub fn success(&self) -> bool {
+        self.solution_found
+            && self.within_solution_range
+            && self.chunk_signature_valid
+            && self.witness_valid
+    }
+}
+
+/// Plot one sector with `space_l` in memory, audit it, create solution and verify it with `kzg`.
+///
+/// NOTE: This function does CPU-heavy work, it must be running in a separate thread in order to
+/// prevent blocking an executor.
+pub fn self_test(space_l: NonZeroU16, kzg: &Kzg) -> Result<SelfTestReport, SelfTestError> {
+    let farmer_protocol_info = FarmerProtocolInfo::builder()
+        .record_size(RECORD_SIZE)
+        .recorded_history_segment_size(RECORDED_HISTORY_SEGMENT_SIZE)
+        .total_pieces(u64::from(PIECES_IN_SEGMENT))
+        .space_l(space_l.get())
+        .sector_expiration(SegmentIndex::ONE)
+        .build()?;
+    let plot_sector_size = plot_sector_size(space_l);
+    let keypair = MiniSecretKey::from_bytes(&SEED)
+        .expect("32 bytes can always build a key; qed")
+        .expand_to_keypair(ExpansionMode::Ed25519);
+    let public_key = PublicKey::from(keypair.public.to_bytes());
+
+    let start = Instant::now();
+    let mut block = vec![0u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
+    StdRng::from_seed(SEED).fill(block.as_mut_slice());
+    let archived_segment = Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone())?
+        .add_block(block, Default::default())
+        .into_iter()
+        .next()
+        .expect("Block of the size of the segment always fills the first segment; qed");
+    let records_root = archived_segment.root_block.records_root();
+    let piece_receiver = MapPieceReceiver::from_archived_segments([&archived_segment]);
+    let archiving_time = start.elapsed();
+    debug!(?archiving_time, "Segment archived");
+
+    let start = Instant::now();
+    let mut sector = Vec::with_capacity(plot_sector_size as usize);
+    let mut sector_metadata = Vec::with_capacity(SectorMetadata::encoded_size());
+    block_on(plot_sector(
+        &public_key,
+        SECTOR_INDEX,
+        &piece_receiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut sector),
+        &mut sector_metadata,
+    ))?;
+    let plotting_time = start.elapsed();
+    debug!(?plotting_time, "Sector plotted");
+
+    let global_challenge = derive_global_challenge(&Randomness::default(), SLOT_NUMBER);
+    let start = Instant::now();
+    let maybe_eligible_sector = audit_sector(
+        &public_key,
+        SECTOR_INDEX,
+        &farmer_protocol_info,
+        kzg.id(),
+        kzg,
+        &global_challenge,
+        // Any chunk is within the largest solution range
+        SolutionRange::MAX,
+        Cursor::new(&sector),
+    )?;
+    let audit_time = start.elapsed();
+    debug!(?audit_time, "Sector audited");
+
+    let start = Instant::now();
+    let maybe_solution = match maybe_eligible_sector {
+        Some(eligible_sector) => eligible_sector.try_into_solution(
+            &keypair,
+            public_key,
+            &farmer_protocol_info,
+            sector_metadata.as_slice(),
+        )?,
+        None => None,
+    };
+    let proving_time = start.elapsed();
+    debug!(?proving_time, "Solution created");
+
+    let start = Instant::now();
+    let verification = maybe_solution
+        .as_ref()
+        .map(|solution| {
+            verify(
+                kzg,
+                &records_root,
+                &global_challenge,
+                solution,
+                &SectorId::new_rotated(
+                    &solution.public_key,
+                    solution.sector_index,
+                    farmer_protocol_info.rotation,
+                ),
+                PIECES_IN_SEGMENT,
+            )
+        })
+        .unwrap_or_default();
+    let verification_time = start.elapsed();
+    debug!(?verification_time, "Solution verified");
+
+    Ok(SelfTestReport {
+        plot_sector_size,
+        archiving_time,
+        plotting_time,
+        audit_time,
+        proving_time,
+        verification_time,
+        solution_found: maybe_solution.is_some(),
+        within_solution_range: verification.within_solution_range,
+        chunk_signature_valid: verification.chunk_signature_valid,
+        witness_valid: verification.witness_valid,
+    })
+}
+
+/// Result of [`verify()`]
+#[derive(Debug, Default, Copy, Clone)]
+pub(super) struct SolutionVerification {
+    pub(super) within_solution_range: bool,
+    pub(super) chunk_signature_valid: bool,
+    pub(super) witness_valid: bool,
+}
+
+impl SolutionVerification {
+    /// Whether solution passed all checks
+    pub(super) fn valid(&self) -> bool {
+        self.within_solution_range && self.chunk_signature_valid && self.witness_valid
+    }
+}
+
+/// Verify solution created from sector with `sector_id` with the largest solution range, piece in
+/// solution must belong to segment with `records_root`
+pub(super) fn verify(
+    kzg: &Kzg,
+    records_root: &RecordsRoot,
+    global_challenge: &Blake2b256Hash,
+    solution: &Solution<PublicKey, PublicKey>,
+    sector_id: &SectorId,
+    pieces_in_segment: u32,
+) -> SolutionVerification {
+    let local_challenge = sector_id.derive_local_challenge(global_challenge);
+    let within_solution_range = is_within_solution_range(
+        local_challenge,
+        solution.chunk.expand(local_challenge),
+        SolutionRange::MAX,
+    );
+
+    let chunk_signature_valid = schnorrkel::PublicKey::from_bytes(solution.public_key.as_ref())
+        .and_then(|public_key| {
+            verify_chunk_signature(&solution.chunk, &solution.chunk_signature, &public_key)
+        })
+        .is_ok();
+
+    let piece_index = sector_id.derive_piece_index(solution.piece_offset, solution.total_pieces);
+    let position = u32::try_from(piece_index % PieceIndex::from(pieces_in_segment))
+        .expect("Position within segment always fits into u32; qed");
+    let witness_valid = is_piece_record_hash_valid(
+        kzg,
+        pieces_in_segment,
+        &solution.piece_record_hash,
+        records_root,
+        &solution.piece_witness,
+        position,
+    );
+
+    SolutionVerification {
+        within_solution_range,
+        chunk_signature_valid,
+        witness_valid,
+    }
+}
@@ -0,0 +1,34 @@
+use crate::single_disk_plot::self_test::{self_test, SelfTestError};
+use std::num::NonZeroU16;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::plot_sector_size;
+
+#[test]
+fn self_test_succeeds() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let space_l = NonZeroU16::new(16).unwrap();
+
+    let report = self_test(space_l, &kzg).unwrap();
+    assert!(report.success(), "{report:?}");
+    assert_eq!(report.plot_sector_size, plot_sector_size(space_l));
+    for duration in [
+        report.archiving_time,
+        report.plotting_time,
+        report.audit_time,
+        report.proving_time,
+        report.verification_time,
+    ] {
+        assert!(!duration.is_zero(), "{report:?}");
+    }
+}
+
+#[test]
+fn self_test_invalid_space_l() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+
+    assert!(matches!(
+        self_test(NonZeroU16::new(2).unwrap(), &kzg),
+        Err(SelfTestError::InvalidProtocolInfo(_))
+    ));
+}
@@ -0,0 +1,309 @@
+//! Plot stored in a ring of shard files of limited size.
+//!
+//! Some file systems limit the size of a single file (FAT32, some network shares and object
+//! storage mounts), plot of any size can be stored on them by splitting it into shards of at most
+//! `shard_size` bytes each: `plot.bin.0`, `plot.bin.1` and so on. Offsets within plot are routed to
+//! the shard that holds them and reads or writes that cross shard boundary are split between
+//! shards, so the rest of the farmer sees a single contiguous plot.
+//!
+//! Shard layout is recorded in plot info when plot is created and never changes afterwards.
+//! Sharded plots are accessed with positional reads and writes only, they are never memory mapped
+//! and can't be used with overlay, offline tools, remote plot server and format migration.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::storage_backend::{PlotData, StorageBackend};
+use crate::single_disk_plot::SingleDiskPlot;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use tracing::info;
+
+/// Layout of plot that is split into shard files, recorded in plot info when plot is created
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlotShards {
+    /// Size of every shard except the last one in bytes
+    pub shard_size: NonZeroU64,
+    /// Number of shard files
+    pub shard_count: u64,
+}
+
+impl PlotShards {
+    /// Layout of plot of `plot_size` bytes split into shards of at most `shard_size` bytes, plot
+    /// always has at least one shard
+    pub fn new(plot_size: u64, shard_size: NonZeroU64) -> Self {
+        let shard_count =
+            plot_size / shard_size.get() + u64::from(plot_size % shard_size.get() != 0);
+
+        Self {
+            shard_size,
+            shard_count: shard_count.max(1),
+        }
+    }
+}
+
+/// Plot file split into shards, reads and writes are routed to shards by offset within plot
+#[derive(Debug)]
+pub(crate) struct ShardedPlotFile {
+    shards: Vec<File>,
+    shard_size: u64,
+}
+
+impl ShardedPlotFile {
+    /// Path to shard at `shard_index` of plot stored in `directory`
+    pub(crate) fn shard_path(directory: &Path, shard_index: u64) -> PathBuf {
+        directory.join(format!("{}.{shard_index}", SingleDiskPlot::PLOT_FILE))
+    }
+
+    /// Open shards of plot stored in `directory` for reading and writing, shards that don't exist
+    /// yet are created empty
+    pub(crate) fn open(directory: &Path, plot_shards: PlotShards) -> io::Result<Self> {
+        let shards = (0..plot_shards.shard_count)
+            .map(|shard_index| {
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .open(Self::shard_path(directory, shard_index))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            shards,
+            shard_size: plot_shards.shard_size.get(),
+        })
+    }
+
+    /// Combined size in bytes of shards of plot stored in `directory` that exist already
+    pub(crate) fn existing_len(directory: &Path, plot_shards: PlotShards) -> io::Result<u64> {
+        (0..plot_shards.shard_count).try_fold(0, |existing_len, shard_index| {
+            match fs::metadata(Self::shard_path(directory, shard_index)) {
+                Ok(metadata) => Ok(existing_len + metadata.len()),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(existing_len),
+                Err(error) => Err(error),
+            }
+        })
+    }
+
+    /// Make sure every shard has as many bytes allocated as it holds in plot of `plot_size` bytes
+    /// according to the storage backend
+    pub(crate) fn allocate(
+        &self,
+        storage_backend: StorageBackend,
+        plot_size: u64,
+    ) -> io::Result<()> {
+        self.check_capacity(plot_size)?;
+
+        for (shard, shard_len) in self.shards_with_len(plot_size) {
+            storage_backend.preallocate(shard, shard_len)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sync contents of all shards to disk
+    pub(crate) fn sync_all(&self) -> io::Result<()> {
+        self.shards.iter().try_for_each(File::sync_all)
+    }
+
+    /// Delete shards of plot stored in `directory`
+    pub(crate) fn wipe(directory: &Path, plot_shards: PlotShards) -> io::Result<()> {
+        for shard_index in 0..plot_shards.shard_count {
+            let path = Self::shard_path(directory, shard_index);
+            if path.exists() {
+                info!("Deleting plot shard at {}", path.display());
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn shards_with_len(&self, plot_size: u64) -> impl Iterator<Item = (&File, u64)> + '_ {
+        let shard_size = self.shard_size;
+
+        self.shards
+            .iter()
+            .enumerate()
+            .map(move |(shard_index, shard)| {
+                let shard_start = shard_index as u64 * shard_size;
+                (shard, plot_size.saturating_sub(shard_start).min(shard_size))
+            })
+    }
+
+    /// Number of bytes all shards can hold together
+    fn capacity(&self) -> u64 {
+        (self.shards.len() as u64).saturating_mul(self.shard_size)
+    }
+
+    fn check_capacity(&self, plot_size: u64) -> io::Result<()> {
+        if plot_size > self.capacity() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Plot of {plot_size} bytes doesn't fit into {} shards of {} bytes",
+                    self.shards.len(),
+                    self.shard_size
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Shard that holds byte at `offset` within plot and offset of that byte within shard
+    fn locate(&self, offset: u64) -> io::Result<(&File, u64)> {
+        usize::try_from(offset / self.shard_size)
+            .ok()
+            .and_then(|shard_index| self.shards.get(shard_index))
+            .map(|shard| (shard, offset % self.shard_size))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("Offset {offset} is beyond the last plot shard"),
+                )
+            })
+    }
+}
+
+impl FileExt for ShardedPlotFile {
+    fn preallocate(&self, len: u64) -> io::Result<()> {
+        self.check_capacity(len)?;
+
+        for (shard, shard_len) in self.shards_with_len(len) {
+            shard.preallocate(shard_len)?;
+        }
+
+        Ok(())
+    }
+
+    fn advise_random_access(&self) -> io::Result<()> {
+        self.shards.iter().try_for_each(File::advise_random_access)
+    }
+
+    fn drop_cache(&self, mut offset: u64, len: u64) -> io::Result<()> {
+        // Length of `0` means until the end of the last shard
+        let end = if len == 0 {
+            self.capacity()
+        } else {
+            offset.saturating_add(len)
+        };
+        while offset < end {
+            let (shard, shard_offset) = self.locate(offset)?;
+            let shard_len = (self.shard_size - shard_offset).min(end - offset);
+            shard.drop_cache(shard_offset, shard_len)?;
+            offset += shard_len;
+        }
+
+        Ok(())
+    }
+
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        // Reads that cross shard boundary are split between shards
+        while !buf.is_empty() {
+            let (shard, shard_offset) = self.locate(offset)?;
+            let len = (self.shard_size - shard_offset).min(buf.len() as u64) as usize;
+            let (chunk, rest) = buf.split_at_mut(len);
+
+            shard.read_exact_at(chunk, shard_offset)?;
+
+            buf = rest;
+            offset += len as u64;
+        }
+
+        Ok(())
+    }
+
+    fn write_all_at(&self, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+        // Writes that cross shard boundary are split between shards
+        while !buf.is_empty() {
+            let (shard, shard_offset) = self.locate(offset)?;
+            let len = (self.shard_size - shard_offset).min(buf.len() as u64) as usize;
+            let (chunk, rest) = buf.split_at(len);
+
+            shard.write_all_at(chunk, shard_offset)?;
+
+            buf = rest;
+            offset += len as u64;
+        }
+
+        Ok(())
+    }
+}
+
+/// Plot file that is either a single file or split into shards
+#[derive(Debug)]
+pub(crate) enum PlotFile {
+    /// Whole plot is stored in a single file
+    Single(File),
+    /// Plot is split into shards
+    Sharded(ShardedPlotFile),
+}
+
+impl PlotFile {
+    /// Plot data that reads from this plot file with positional reads
+    pub(crate) fn plot_data(&self) -> PlotData<'_> {
+        match self {
+            Self::Single(file) => PlotData::File(file),
+            Self::Sharded(sharded_plot_file) => PlotData::Sharded(sharded_plot_file),
+        }
+    }
+
+    /// File to memory map if storage backend allows it, sharded plots are never memory mapped
+    pub(crate) fn mmap_file(&self, storage_backend: StorageBackend) -> Option<&File> {
+        match self {
+            Self::Single(file) if storage_backend.use_mmap() => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Sync contents of plot file to disk
+    pub(crate) fn sync_all(&self) -> io::Result<()> {
+        match self {
+            Self::Single(file) => file.sync_all(),
+            Self::Sharded(sharded_plot_file) => sharded_plot_file.sync_all(),
+        }
+    }
+}
+
+impl FileExt for PlotFile {
+    fn preallocate(&self, len: u64) -> io::Result<()> {
+        match self {
+            Self::Single(file) => file.preallocate(len),
+            Self::Sharded(sharded_plot_file) => sharded_plot_file.preallocate(len),
+        }
+    }
+
+    fn advise_random_access(&self) -> io::Result<()> {
+        match self {
+            Self::Single(file) => file.advise_random_access(),
+            Self::Sharded(sharded_plot_file) => sharded_plot_file.advise_random_access(),
+        }
+    }
+
+    fn drop_cache(&self, offset: u64, len: u64) -> io::Result<()> {
+        match self {
+            Self::Single(file) => file.drop_cache(offset, len),
+            Self::Sharded(sharded_plot_file) => sharded_plot_file.drop_cache(offset, len),
+        }
+    }
+
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        match self {
+            Self::Single(file) => file.read_exact_at(buf, offset),
+            Self::Sharded(sharded_plot_file) => sharded_plot_file.read_exact_at(buf, offset),
+        }
+    }
+
+    fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        match self {
+            Self::Single(file) => file.write_all_at(buf, offset),
+            Self::Sharded(sharded_plot_file) => sharded_plot_file.write_all_at(buf, offset),
+        }
+    }
+}
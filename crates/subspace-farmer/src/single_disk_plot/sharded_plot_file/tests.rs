@@ -0,0 +1,210 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::plotting::plot_sector;
+use crate::single_disk_plot::sharded_plot_file::{PlotShards, ShardedPlotFile};
+use crate::single_disk_plot::storage_backend::{PlotData, SectorFileWriter, StorageBackend};
+use crate::testing::fixtures::{archived_segment, farmer_protocol_info};
+use crate::testing::MapPieceReceiver;
+use futures::executor::block_on;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::num::{NonZeroU16, NonZeroU64};
+use std::sync::atomic::AtomicBool;
+use std::{fs, io};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, PublicKey, SolutionRange, PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tempfile::TempDir;
+
+#[test]
+fn plot_shards_layout() {
+    let shard_size = NonZeroU64::new(10).unwrap();
+
+    assert_eq!(PlotShards::new(0, shard_size).shard_count, 1);
+    assert_eq!(PlotShards::new(10, shard_size).shard_count, 1);
+    assert_eq!(PlotShards::new(11, shard_size).shard_count, 2);
+    assert_eq!(PlotShards::new(30, shard_size).shard_count, 3);
+    assert_eq!(
+        PlotShards::new(u64::MAX, NonZeroU64::new(1).unwrap()).shard_count,
+        u64::MAX
+    );
+}
+
+#[test]
+fn reads_and_writes_across_shard_boundaries() {
+    let directory = TempDir::new().unwrap();
+    let plot_size = 30;
+    let plot_shards = PlotShards::new(plot_size, NonZeroU64::new(7).unwrap());
+    assert_eq!(plot_shards.shard_count, 5);
+
+    let plot_file = ShardedPlotFile::open(directory.path(), plot_shards).unwrap();
+    plot_file
+        .allocate(StorageBackend::Network, plot_size)
+        .unwrap();
+    assert_eq!(
+        ShardedPlotFile::existing_len(directory.path(), plot_shards).unwrap(),
+        plot_size
+    );
+    // Shards can't hold more than 5 * 7 bytes
+    assert!(plot_file.preallocate(36).is_err());
+
+    let contents = (0..plot_size as u8).collect::<Vec<_>>();
+    // Write spans three shards
+    plot_file.write_all_at(&contents[..20], 0).unwrap();
+    plot_file.write_all_at(&contents[20..], 20).unwrap();
+
+    let mut buffer = vec![0; contents.len()];
+    plot_file.read_exact_at(&mut buffer, 0).unwrap();
+    assert_eq!(buffer, contents);
+
+    // Read that starts in the middle of one shard and ends in the middle of another one
+    let mut buffer = vec![0; 12];
+    plot_file.read_exact_at(&mut buffer, 5).unwrap();
+    assert_eq!(buffer, contents[5..17]);
+    PlotData::Sharded(&plot_file)
+        .read_exact_at(&mut buffer, 13)
+        .unwrap();
+    assert_eq!(buffer, contents[13..25]);
+
+    // Every shard holds its own part of the plot
+    for (shard_index, shard_contents) in contents.chunks(7).enumerate() {
+        assert_eq!(
+            fs::read(ShardedPlotFile::shard_path(
+                directory.path(),
+                shard_index as u64
+            ))
+            .unwrap(),
+            shard_contents
+        );
+    }
+
+    // Nothing can be read or written beyond the last shard
+    let mut buffer = vec![0; 2];
+    assert_eq!(
+        plot_file.read_exact_at(&mut buffer, 35).unwrap_err().kind(),
+        io::ErrorKind::UnexpectedEof
+    );
+    assert!(plot_file.write_all_at(&buffer, 35).is_err());
+
+    plot_file.sync_all().unwrap();
+    drop(plot_file);
+    ShardedPlotFile::wipe(directory.path(), plot_shards).unwrap();
+    assert_eq!(
+        ShardedPlotFile::existing_len(directory.path(), plot_shards).unwrap(),
+        0
+    );
+}
+
+#[test]
+fn sectors_spanning_shard_boundaries_audit_correctly() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let piece_receiver = MapPieceReceiver::from_archived_segments([&archived_segment(&kzg)]);
+
+    let farmer_protocol_info = FarmerProtocolInfo {
+        total_pieces: NonZeroU64::new(1).unwrap(),
+        space_l: NonZeroU16::new(20).unwrap(),
+        ..farmer_protocol_info()
+    };
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+    let sector_count = 2;
+    let plot_size = plot_sector_size * sector_count;
+
+    let directory = TempDir::new().unwrap();
+    let plot_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(directory.path().join("plot.bin"))
+        .unwrap();
+    plot_file.preallocate(plot_size).unwrap();
+
+    // Tiny shards whose boundaries don't match boundaries of sectors and pieces, so every sector
+    // and some of the pieces span multiple shards
+    let shards_directory = TempDir::new().unwrap();
+    let shard_size = NonZeroU64::new(PIECE_SIZE as u64 * 3 + 1000).unwrap();
+    let plot_shards = PlotShards::new(plot_size, shard_size);
+    assert!(plot_shards.shard_count > sector_count * 2);
+    let sharded_plot_file = ShardedPlotFile::open(shards_directory.path(), plot_shards).unwrap();
+    sharded_plot_file.preallocate(plot_size).unwrap();
+
+    let cancelled = AtomicBool::new(false);
+    for sector_offset in 0..sector_count {
+        block_on(plot_sector(
+            &public_key,
+            sector_offset,
+            &piece_receiver,
+            &cancelled,
+            &farmer_protocol_info,
+            SectorFileWriter::new(&plot_file, sector_offset, plot_sector_size).unwrap(),
+            io::sink(),
+        ))
+        .unwrap();
+        block_on(plot_sector(
+            &public_key,
+            sector_offset,
+            &piece_receiver,
+            &cancelled,
+            &farmer_protocol_info,
+            SectorFileWriter::new(&sharded_plot_file, sector_offset, plot_sector_size).unwrap(),
+            io::sink(),
+        ))
+        .unwrap();
+    }
+
+    let global_challenge: Blake2b256Hash = rand::random();
+    for sector_offset in 0..sector_count {
+        let mut sector = Vec::new();
+        PlotData::File(&plot_file)
+            .sector(sector_offset, plot_sector_size)
+            .unwrap()
+            .read_to_end(&mut sector)
+            .unwrap();
+        let mut sharded_sector = Vec::new();
+        PlotData::Sharded(&sharded_plot_file)
+            .sector(sector_offset, plot_sector_size)
+            .unwrap()
+            .read_to_end(&mut sharded_sector)
+            .unwrap();
+        assert_eq!(sector.len() as u64, plot_sector_size);
+        assert!(sector == sharded_sector);
+
+        let eligible_sector = audit_sector(
+            &public_key,
+            sector_offset,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            SolutionRange::MAX,
+            PlotData::File(&plot_file)
+                .sector(sector_offset, plot_sector_size)
+                .unwrap(),
+        )
+        .unwrap()
+        .expect("Max solution range always results in eligible sector");
+        let eligible_sector_sharded = audit_sector(
+            &public_key,
+            sector_offset,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            SolutionRange::MAX,
+            PlotData::Sharded(&sharded_plot_file)
+                .sector(sector_offset, plot_sector_size)
+                .unwrap(),
+        )
+        .unwrap()
+        .expect("Max solution range always results in eligible sector");
+
+        assert_eq!(eligible_sector.chunk, eligible_sector_sharded.chunk);
+        assert_eq!(
+            eligible_sector.encoded_piece,
+            eligible_sector_sharded.encoded_piece
+        );
+    }
+}
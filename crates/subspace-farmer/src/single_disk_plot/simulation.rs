@@ -0,0 +1,381 @@
+//! Farming simulation for capacity planning.
+//!
+//! A handful of real sectors is plotted on the disk being evaluated and audited with random
+//! challenges, measured latencies are then extrapolated to the desired plot size to tell whether
+//! audits fit into slot deadline. Expected number of solutions is computed separately from plot
+//! size, total space pledged to the network and solution range, no disk access is involved there.
+
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::farming::{audit_sector, read_winning_piece};
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use crate::single_disk_plot::plot_layout_calculator::PlotLayoutCalculator;
+use crate::single_disk_plot::plotting::{
+    plot_sector, PlotSectorError, PlottedSector, SectorBuffer,
+};
+use crate::single_disk_plot::FarmingError;
+use async_trait::async_trait;
+use futures::executor::block_on;
+use memmap2::Mmap;
+use rand::prelude::*;
+use serde::{Serialize, Serializer};
+use std::error::Error;
+use std::io;
+use std::io::{Cursor, Write};
+use std::num::NonZeroU64;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    expected_solutions_per_slot, plot_sector_size, sector_solution_probability,
+    solution_range_for_sectors, Piece, PieceIndex, PublicKey, SectorIndex, SolutionRange,
+    PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use thiserror::Error;
+use tracing::debug;
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// Errors that happen during simulation
+#[derive(Debug, Error)]
+pub enum SimulationError {
+    /// Plot size is too small to contain a single sector
+    #[error("Plot size {plot_size} is too small to contain a single sector of {plot_sector_size}")]
+    PlotTooSmall {
+        /// Plot size
+        plot_size: u64,
+        /// Size of one sector
+        plot_sector_size: u64,
+    },
+    /// Failed to plot sample sector
+    #[error("Failed to plot sample sector: {0}")]
+    Plotting(#[from] PlotSectorError),
+    /// Failed to audit sample sector
+    #[error("Failed to audit sample sector: {0}")]
+    Farming(#[from] FarmingError),
+    /// I/O error occurred
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Options for [`simulate()`]
+#[derive(Debug)]
+pub struct SimulationOptions<'a> {
+    /// Directory on the disk being evaluated, sample sectors are plotted into a temporary file in
+    /// it that is removed afterwards
+    pub directory: &'a Path,
+    /// Farmer protocol info sample sectors are plotted with
+    pub farmer_protocol_info: FarmerProtocolInfo,
+    /// Number of real sectors to plot and audit
+    pub sample_sectors: NonZeroU64,
+    /// Number of slots to audit sample sectors for
+    pub sample_slots: NonZeroU64,
+    /// Plot size in bytes to extrapolate measurements to
+    pub plot_size: u64,
+    /// Slot duration, all sectors must be audited within one slot
+    pub slot_duration: Duration,
+    /// Slot probability
+    pub slot_probability: (u64, u64),
+    /// Total space in bytes pledged to the network, including simulated plot
+    pub total_space_pledged: u64,
+    /// Solution range, derived from total space pledged and slot probability if not specified
+    pub solution_range: Option<SolutionRange>,
+}
+
+/// Expected number of solutions of a plot
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectedSolutions {
+    /// Solution range expectations are computed for
+    pub solution_range: SolutionRange,
+    /// Number of sectors in the plot
+    pub sector_count: u64,
+    /// Total number of sectors pledged to the network, including the plot
+    pub total_sector_count: u64,
+    /// Probability of a single sector to produce a solution in a slot
+    pub sector_solution_probability: f64,
+    /// Expected number of solutions of the plot per slot
+    pub solutions_per_slot: f64,
+    /// Expected number of solutions of the plot per day
+    pub solutions_per_day: f64,
+    /// Expected number of solutions of the whole network per day
+    pub network_solutions_per_day: f64,
+    /// Share of the plot in space pledged to the network
+    pub network_share: f64,
+}
+
+/// Compute expected number of solutions of a plot with `sector_count` sectors
+///
+/// `solution_range` is derived from `total_space_pledged` and `slot_probability` the same way
+/// solution range adjustment would do if not specified.
+pub fn expected_solutions(
+    sector_count: u64,
+    plot_sector_size: u64,
+    total_space_pledged: u64,
+    slot_duration: Duration,
+    slot_probability: (u64, u64),
+    solution_range: Option<SolutionRange>,
+) -> ExpectedSolutions {
+    let total_sector_count = (total_space_pledged / plot_sector_size).max(sector_count);
+    let solution_range = solution_range
+        .unwrap_or_else(|| solution_range_for_sectors(total_sector_count, slot_probability));
+    let slots_per_day = SECONDS_PER_DAY / slot_duration.as_secs_f64();
+    let solutions_per_slot = expected_solutions_per_slot(sector_count, solution_range);
+
+    ExpectedSolutions {
+        solution_range,
+        sector_count,
+        total_sector_count,
+        sector_solution_probability: sector_solution_probability(solution_range),
+        solutions_per_slot,
+        solutions_per_day: solutions_per_slot * slots_per_day,
+        network_solutions_per_day: expected_solutions_per_slot(total_sector_count, solution_range)
+            * slots_per_day,
+        network_share: if total_sector_count == 0 {
+            0.0
+        } else {
+            sector_count as f64 / total_sector_count as f64
+        },
+    }
+}
+
+/// Report of [`simulate()`], durations are serialized as fractional seconds
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationReport {
+    /// Size of one sector in bytes
+    pub plot_sector_size: u64,
+    /// Number of sectors in the plot measurements are extrapolated to
+    pub target_sector_count: u64,
+    /// Number of sectors that were actually plotted and audited
+    pub sample_sectors: u64,
+    /// Number of slots sample sectors were audited for
+    pub sample_slots: u64,
+    /// Average time to plot and write one sector, excluding piece retrieval from the network
+    #[serde(serialize_with = "serialize_duration")]
+    pub sector_plotting_time: Duration,
+    /// Extrapolated time to plot the whole plot, excluding piece retrieval from the network
+    #[serde(serialize_with = "serialize_duration")]
+    pub plotting_time: Duration,
+    /// Average time to audit one sector
+    #[serde(serialize_with = "serialize_duration")]
+    pub sector_audit_time: Duration,
+    /// Longest time to audit one sector
+    #[serde(serialize_with = "serialize_duration")]
+    pub max_sector_audit_time: Duration,
+    /// Average time to read and decode winning piece from a sector
+    #[serde(serialize_with = "serialize_duration")]
+    pub piece_read_time: Duration,
+    /// Extrapolated time to audit all sectors of the plot and read one winning piece
+    #[serde(serialize_with = "serialize_duration")]
+    pub slot_time: Duration,
+    /// Slot duration
+    #[serde(serialize_with = "serialize_duration")]
+    pub slot_duration: Duration,
+    /// Whether the whole plot can be audited within slot duration
+    pub meets_slot_deadline: bool,
+    /// Largest number of sectors that can be audited within slot duration on this disk
+    pub max_sector_count: u64,
+    /// Expected number of solutions
+    pub expected_solutions: ExpectedSolutions,
+}
+
+fn serialize_duration<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Returns pseudo-random pieces derived from piece index, plotting and auditing costs don't depend
+/// on piece contents
+struct SimulationPieceReceiver;
+
+#[async_trait]
+impl PieceReceiver for SimulationPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        let mut piece = Piece::default();
+        StdRng::seed_from_u64(piece_index).fill(&mut *piece);
+
+        Ok(Some(piece))
+    }
+}
+
+/// Plot sample sectors on disk, measure plotting, audit and read latencies and extrapolate them to
+/// the plot size from `options`.
+///
+/// NOTE: This function does blocking disk I/O and CPU-heavy work, it must be running in a separate
+/// thread in order to prevent blocking an executor.
+pub fn simulate(options: SimulationOptions<'_>) -> Result<SimulationReport, SimulationError> {
+    let SimulationOptions {
+        directory,
+        farmer_protocol_info,
+        sample_sectors,
+        sample_slots,
+        plot_size,
+        slot_duration,
+        slot_probability,
+        total_space_pledged,
+        solution_range,
+    } = options;
+
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+    let target_sector_count =
+        PlotLayoutCalculator::new(&farmer_protocol_info).sectors_for_space(plot_size);
+    if target_sector_count == 0 {
+        return Err(SimulationError::PlotTooSmall {
+            plot_size,
+            plot_sector_size,
+        });
+    }
+
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let cancelled = AtomicBool::new(false);
+
+    let mut plot_file = tempfile::tempfile_in(directory)?;
+    plot_file.preallocate(plot_sector_size * sample_sectors.get())?;
+    plot_file.advise_random_access()?;
+
+    let mut sector = Vec::with_capacity(plot_sector_size as usize);
+    let mut plotted_sectors = Vec::with_capacity(sample_sectors.get() as usize);
+    let mut total_plotting_time = Duration::ZERO;
+    for sector_index in (0..sample_sectors.get()).map(SectorIndex::new) {
+        sector.clear();
+        let start = Instant::now();
+        let plotted_sector = block_on(plot_sector(
+            &public_key,
+            sector_index,
+            &SimulationPieceReceiver,
+            &cancelled,
+            &farmer_protocol_info,
+            SectorBuffer::new(&mut sector),
+            io::sink(),
+        ))?;
+        plot_file.write_all(&sector)?;
+        total_plotting_time += start.elapsed();
+
+        debug!(%sector_index, "Sample sector plotted");
+        plotted_sectors.push(plotted_sector);
+    }
+    // Dirty pages can't be dropped from page cache, make sure everything is on disk
+    plot_file.sync_all()?;
+
+    let mut audit_times = Vec::with_capacity((sample_sectors.get() * sample_slots.get()) as usize);
+    let mut total_piece_read_time = Duration::ZERO;
+    for _slot in 0..sample_slots.get() {
+        // Audits are supposed to hit the disk rather than page cache, pages that are still mapped
+        // are not dropped, so memory mapping is re-created after cache is dropped
+        plot_file.drop_cache(0, 0)?;
+        let plot_mmap = unsafe { Mmap::map(&plot_file)? };
+        #[cfg(unix)]
+        {
+            plot_mmap.advise(memmap2::Advice::Random)?;
+        }
+
+        let global_challenge = rand::random();
+        for (sector, plotted_sector) in plot_mmap
+            .chunks_exact(plot_sector_size as usize)
+            .zip(&plotted_sectors)
+        {
+            let start = Instant::now();
+            audit_sector(
+                &public_key,
+                plotted_sector.sector_index,
+                &farmer_protocol_info,
+                KzgParametersId::TEST,
+                &kzg,
+                &global_challenge,
+                SolutionRange::MAX,
+                Cursor::new(sector),
+            )?;
+            audit_times.push(start.elapsed());
+        }
+
+        for (sector, plotted_sector) in plot_mmap
+            .chunks_exact(plot_sector_size as usize)
+            .zip(&plotted_sectors)
+        {
+            total_piece_read_time +=
+                read_random_piece(&farmer_protocol_info, sector, plotted_sector)?;
+        }
+    }
+
+    let sample_audits = audit_times.len() as u32;
+    let sector_audit_time = audit_times.iter().sum::<Duration>() / sample_audits;
+    let max_sector_audit_time = audit_times.iter().max().copied().unwrap_or_default();
+    let piece_read_time = total_piece_read_time / sample_audits;
+    let sector_plotting_time = total_plotting_time / sample_sectors.get() as u32;
+    let slot_time = sector_audit_time.mul_f64(target_sector_count as f64) + piece_read_time;
+
+    Ok(SimulationReport {
+        plot_sector_size,
+        target_sector_count,
+        sample_sectors: sample_sectors.get(),
+        sample_slots: sample_slots.get(),
+        sector_plotting_time,
+        plotting_time: sector_plotting_time.mul_f64(target_sector_count as f64),
+        sector_audit_time,
+        max_sector_audit_time,
+        piece_read_time,
+        slot_time,
+        slot_duration,
+        meets_slot_deadline: slot_time <= slot_duration,
+        max_sector_count: max_sector_count(slot_duration, sector_audit_time, piece_read_time),
+        expected_solutions: expected_solutions(
+            target_sector_count,
+            plot_sector_size,
+            total_space_pledged,
+            slot_duration,
+            slot_probability,
+            solution_range,
+        ),
+    })
+}
+
+/// Read and decode piece at random offset the same way as it is done for winning chunk, returns
+/// time it took
+fn read_random_piece(
+    farmer_protocol_info: &FarmerProtocolInfo,
+    sector: &[u8],
+    plotted_sector: &PlottedSector,
+) -> Result<Duration, FarmingError> {
+    let pieces_in_sector = sector.len() as u64 / PIECE_SIZE as u64;
+    let chunk_offset =
+        thread_rng().gen_range(0..pieces_in_sector) * PIECE_SIZE as u64 * u64::from(u8::BITS);
+
+    let start = Instant::now();
+    read_winning_piece(
+        &plotted_sector.sector_id,
+        farmer_protocol_info,
+        Cursor::new(sector),
+        &plotted_sector.sector_metadata,
+        chunk_offset,
+    )?;
+
+    Ok(start.elapsed())
+}
+
+/// Largest number of sectors whose audit along with reading one winning piece fits into slot
+fn max_sector_count(
+    slot_duration: Duration,
+    sector_audit_time: Duration,
+    piece_read_time: Duration,
+) -> u64 {
+    let audit_budget = slot_duration.saturating_sub(piece_read_time);
+    if sector_audit_time.is_zero() {
+        return u64::MAX;
+    }
+
+    (audit_budget.as_nanos() / sector_audit_time.as_nanos())
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
@@ -0,0 +1,124 @@
+use crate::single_disk_plot::plot_layout_calculator::PlotLayoutCalculator;
+use crate::single_disk_plot::simulation::{
+    expected_solutions, simulate, SimulationError, SimulationOptions,
+};
+use crate::testing::fixtures::farmer_protocol_info;
+use std::fs;
+use std::num::NonZeroU64;
+use std::time::Duration;
+use subspace_core_primitives::{plot_sector_size, SolutionRange};
+use tempfile::tempdir;
+
+#[test]
+fn expected_solutions_known_values() {
+    // Solution range is derived from total space pledged, the whole network produces a solution
+    // every slot and plot has a quarter of the space
+    let expected = expected_solutions(1, 1024, 4 * 1024, Duration::from_secs(1), (1, 1), None);
+    assert_eq!(expected.solution_range, 1 << 62);
+    assert_eq!(expected.total_sector_count, 4);
+    assert_eq!(expected.sector_solution_probability, 0.25);
+    assert_eq!(expected.solutions_per_slot, 0.25);
+    assert_eq!(expected.solutions_per_day, 21_600.0);
+    assert_eq!(expected.network_solutions_per_day, 86_400.0);
+    assert_eq!(expected.network_share, 0.25);
+
+    // Explicit solution range is used as is
+    let expected = expected_solutions(
+        1,
+        1024,
+        4 * 1024,
+        Duration::from_secs(1),
+        (1, 1),
+        Some(SolutionRange::MAX / 1024),
+    );
+    assert_eq!(expected.solution_range, SolutionRange::MAX / 1024);
+    assert_eq!(expected.solutions_per_day, 86_400.0 / 1024.0);
+    assert_eq!(expected.network_share, 0.25);
+
+    // Network produces solutions with slot probability when solution range is derived
+    let expected = expected_solutions(
+        1000,
+        1024,
+        1_000_000 * 1024,
+        Duration::from_secs(1),
+        (1, 6),
+        None,
+    );
+    assert!((expected.network_solutions_per_day - 14_400.0).abs() < 1e-3);
+    assert!((expected.solutions_per_day - 14.4).abs() < 1e-6);
+
+    // Plot can't be larger than the whole network
+    let expected = expected_solutions(10, 1024, 0, Duration::from_secs(1), (1, 1), None);
+    assert_eq!(expected.total_sector_count, 10);
+    assert_eq!(expected.network_share, 1.0);
+}
+
+#[test]
+fn simulation() {
+    let directory = tempdir().unwrap();
+    let farmer_protocol_info = farmer_protocol_info();
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+    let report = simulate(SimulationOptions {
+        directory: directory.path(),
+        farmer_protocol_info,
+        sample_sectors: NonZeroU64::new(2).unwrap(),
+        sample_slots: NonZeroU64::new(3).unwrap(),
+        plot_size: PlotLayoutCalculator::new(&farmer_protocol_info).space_for_sectors(1000) + 1,
+        slot_duration: Duration::from_secs(1),
+        slot_probability: (1, 6),
+        total_space_pledged: plot_sector_size * 10_000,
+        solution_range: None,
+    })
+    .unwrap();
+
+    assert_eq!(report.plot_sector_size, plot_sector_size);
+    assert_eq!(report.target_sector_count, 1000);
+    assert_eq!(report.sample_sectors, 2);
+    assert_eq!(report.sample_slots, 3);
+    assert!(report.sector_audit_time <= report.max_sector_audit_time);
+    assert_eq!(
+        report.plotting_time,
+        report.sector_plotting_time.mul_f64(1000.0)
+    );
+    assert_eq!(
+        report.slot_time,
+        report.sector_audit_time.mul_f64(1000.0) + report.piece_read_time
+    );
+    assert_eq!(
+        report.meets_slot_deadline,
+        report.slot_time <= report.slot_duration
+    );
+    assert_eq!(report.expected_solutions.sector_count, 1000);
+    assert_eq!(report.expected_solutions.total_sector_count, 10_000);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["targetSectorCount"], 1000);
+    assert_eq!(json["slotDuration"], 1.0);
+    assert_eq!(json["expectedSolutions"]["networkShare"], 0.1);
+
+    // Sample sectors are removed afterwards
+    assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn plot_too_small() {
+    let directory = tempdir().unwrap();
+    let farmer_protocol_info = farmer_protocol_info();
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+
+    assert!(matches!(
+        simulate(SimulationOptions {
+            directory: directory.path(),
+            farmer_protocol_info,
+            sample_sectors: NonZeroU64::new(1).unwrap(),
+            sample_slots: NonZeroU64::new(1).unwrap(),
+            plot_size: plot_sector_size - 1,
+            slot_duration: Duration::from_secs(1),
+            slot_probability: (1, 6),
+            total_space_pledged: plot_sector_size,
+            solution_range: None,
+        }),
+        Err(SimulationError::PlotTooSmall { plot_size, .. }) if plot_size == plot_sector_size - 1
+    ));
+}
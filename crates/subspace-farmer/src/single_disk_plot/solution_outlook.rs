@@ -0,0 +1,193 @@
+//! Outlook of solutions produced by a plot.
+//!
+//! Solution range shrinks as more space is pledged to the network and small plots may go for days
+//! without a single solution, which is indistinguishable from a broken farmer by looking at logs
+//! alone. Outlook tracks how close audited chunks that missed solution range were and how many
+//! solutions are expected at current solution range, so that farmer can tell "unlucky" from
+//! "broken". Outlook is logged periodically and stored next to the plot for `info` command.
+
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::farming::BestMiss;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fmt, fs, io};
+use subspace_core_primitives::{expected_solutions_per_slot, SolutionRange};
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+/// Slot duration assumed until slot duration is observed
+const DEFAULT_SLOT_DURATION: Duration = Duration::from_secs(1);
+/// Number of slots expected solutions per slot are averaged over
+const ROLLING_SLOTS: f64 = 600.0;
+/// Plot is considered to be hopeless when it is expected to produce less than one solution per
+/// this many days
+const HOPELESS_DAYS: f64 = 7.0;
+
+/// Snapshot of solution outlook of a plot
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SolutionOutlookReport {
+    /// Solution range sectors were audited with in the latest slot
+    pub solution_range: SolutionRange,
+    /// Number of sectors audited in the latest slot
+    pub audited_sectors: u64,
+    /// How many times the closest miss since previous report was outside of solution range, see
+    /// [`BestMiss::outside_ratio()`], `None` if there were no misses
+    pub best_miss_ratio: Option<f64>,
+    /// Expected number of solutions per slot, averaged over recent slots
+    pub expected_solutions_per_slot: f64,
+    /// Observed slot duration
+    pub slot_duration: Duration,
+}
+
+impl fmt::Display for SolutionOutlookReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.best_miss_ratio {
+            Some(best_miss_ratio) => write!(
+                f,
+                "best miss was {best_miss_ratio:.1}× outside solution range"
+            )?,
+            None => write!(f, "no misses")?,
+        }
+        write!(
+            f,
+            "; expected ~{:.2} solutions/day",
+            self.expected_solutions_per_day()
+        )
+    }
+}
+
+impl SolutionOutlookReport {
+    const FILE_NAME: &'static str = "solution_outlook.json";
+
+    /// Expected number of solutions per day
+    pub fn expected_solutions_per_day(&self) -> f64 {
+        self.expected_solutions_per_slot * SECONDS_PER_DAY / self.slot_duration.as_secs_f64()
+    }
+
+    /// Expected number of days until the next solution, infinite if no solutions are expected
+    pub fn expected_days_to_solution(&self) -> f64 {
+        1.0 / self.expected_solutions_per_day()
+    }
+
+    /// Whether plot is expected to produce less than one solution a week even though it has
+    /// sectors to audit
+    pub fn is_hopeless(&self) -> bool {
+        self.audited_sectors > 0 && self.expected_days_to_solution() > HOPELESS_DAYS
+    }
+
+    /// Load report stored in plot `directory`, `None` if there is no report yet
+    pub fn load_from(directory: &Path) -> io::Result<Option<Self>> {
+        let bytes = match fs::read(directory.join(Self::FILE_NAME)) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                return if error.kind() == io::ErrorKind::NotFound {
+                    Ok(None)
+                } else {
+                    Err(error)
+                };
+            }
+        };
+
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Store report atomically, so that interruption never leaves report file half-written
+    pub(crate) fn store_to(&self, directory: &Path) -> io::Result<()> {
+        let temporary_path = directory.join(format!("{}.tmp", Self::FILE_NAME));
+        fs::write(
+            &temporary_path,
+            serde_json::to_vec(self).expect("Report serialization never fails; qed"),
+        )?;
+        fs::rename(temporary_path, directory.join(Self::FILE_NAME))
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    report_interval: Duration,
+    /// Rolling average of expected solutions per slot
+    expected_solutions_per_slot: Option<f64>,
+    /// Closest miss since previous report
+    best_miss: BestMiss,
+    last_reported_at: Option<Instant>,
+    latest: Option<SolutionOutlookReport>,
+}
+
+/// Solution outlook of a plot, updated after every audited slot.
+///
+/// Cheap to clone, all clones share the same state.
+#[derive(Debug, Clone)]
+pub struct SolutionOutlook {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl SolutionOutlook {
+    /// Create new instance, reports are produced once per `report_interval`
+    pub fn new(report_interval: Duration) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                report_interval,
+                expected_solutions_per_slot: None,
+                best_miss: BestMiss::default(),
+                last_reported_at: None,
+                latest: None,
+            })),
+        }
+    }
+
+    /// Latest outlook, `None` until the first slot is audited
+    pub fn latest(&self) -> Option<SolutionOutlookReport> {
+        self.inner.lock().latest
+    }
+
+    /// Update outlook after `audited_sectors` were audited with `solution_range` in a slot at
+    /// `now`, closest miss of the slot is `best_miss`.
+    ///
+    /// Returns report once per report interval, closest miss is tracked from scratch after that.
+    pub(crate) fn slot_audited(
+        &self,
+        solution_range: SolutionRange,
+        audited_sectors: u64,
+        best_miss: BestMiss,
+        slot_duration: Option<Duration>,
+        now: Instant,
+    ) -> Option<SolutionOutlookReport> {
+        let mut inner = self.inner.lock();
+
+        let slot_expected_solutions = expected_solutions_per_slot(audited_sectors, solution_range);
+        let expected_solutions_per_slot = match inner.expected_solutions_per_slot {
+            Some(average) => average + (slot_expected_solutions - average) / ROLLING_SLOTS,
+            None => slot_expected_solutions,
+        };
+        inner
+            .expected_solutions_per_slot
+            .replace(expected_solutions_per_slot);
+        inner.best_miss.merge(best_miss);
+
+        let report = SolutionOutlookReport {
+            solution_range,
+            audited_sectors,
+            best_miss_ratio: inner.best_miss.outside_ratio(solution_range),
+            expected_solutions_per_slot,
+            slot_duration: slot_duration.unwrap_or(DEFAULT_SLOT_DURATION),
+        };
+        inner.latest.replace(report);
+
+        let last_reported_at = *inner.last_reported_at.get_or_insert(now);
+        if now.saturating_duration_since(last_reported_at) < inner.report_interval {
+            return None;
+        }
+
+        inner.last_reported_at.replace(now);
+        inner.best_miss = BestMiss::default();
+
+        Some(report)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::single_disk_plot::farming::BestMiss;
+use crate::single_disk_plot::solution_outlook::{SolutionOutlook, SolutionOutlookReport};
+use std::time::{Duration, Instant};
+use subspace_core_primitives::SolutionRange;
+use tempfile::TempDir;
+
+fn miss_at(distance: SolutionRange) -> BestMiss {
+    let mut best_miss = BestMiss::default();
+    best_miss.record(distance);
+    best_miss
+}
+
+#[test]
+fn best_miss() {
+    let mut best_miss = BestMiss::default();
+    assert_eq!(best_miss.distance(), None);
+    assert_eq!(best_miss.outside_ratio(100), None);
+
+    best_miss.record(300);
+    best_miss.record(500);
+    assert_eq!(best_miss.distance(), Some(300));
+    best_miss.merge(BestMiss::default());
+    assert_eq!(best_miss.distance(), Some(300));
+    best_miss.merge(miss_at(160));
+    assert_eq!(best_miss.distance(), Some(160));
+    assert_eq!(best_miss.outside_ratio(100), Some(3.2));
+}
+
+#[test]
+fn solution_outlook() {
+    let solution_outlook = SolutionOutlook::new(Duration::from_secs(60));
+    let start = Instant::now();
+    let solution_range = 1_000_000;
+    assert_eq!(solution_outlook.latest(), None);
+
+    // First slot starts report interval
+    assert_eq!(
+        solution_outlook.slot_audited(solution_range, 10, miss_at(solution_range), None, start),
+        None
+    );
+    let latest = solution_outlook.latest().unwrap();
+    assert_eq!(latest.audited_sectors, 10);
+    assert_eq!(latest.best_miss_ratio, Some(2.0));
+    assert_eq!(latest.slot_duration, Duration::from_secs(1));
+
+    // Closest miss over the whole interval is reported
+    let report = solution_outlook
+        .slot_audited(
+            solution_range,
+            10,
+            miss_at(solution_range * 4),
+            Some(Duration::from_secs(2)),
+            start + Duration::from_secs(60),
+        )
+        .unwrap();
+    assert_eq!(report.best_miss_ratio, Some(2.0));
+    assert_eq!(report.slot_duration, Duration::from_secs(2));
+    assert_eq!(solution_outlook.latest(), Some(report));
+
+    // Closest miss is tracked from scratch after report
+    solution_outlook.slot_audited(
+        solution_range,
+        10,
+        BestMiss::default(),
+        None,
+        start + Duration::from_secs(61),
+    );
+    assert_eq!(solution_outlook.latest().unwrap().best_miss_ratio, None);
+}
+
+#[test]
+fn expected_solutions_are_averaged() {
+    let solution_outlook = SolutionOutlook::new(Duration::from_secs(60));
+    let now = Instant::now();
+
+    solution_outlook.slot_audited(SolutionRange::MAX, 100, BestMiss::default(), None, now);
+    let expected = solution_outlook
+        .latest()
+        .unwrap()
+        .expected_solutions_per_slot;
+    assert!((expected - 100.0).abs() < 1e-9);
+
+    // Single slot without audited sectors barely moves the average
+    solution_outlook.slot_audited(SolutionRange::MAX, 0, BestMiss::default(), None, now);
+    let expected = solution_outlook
+        .latest()
+        .unwrap()
+        .expected_solutions_per_slot;
+    assert!(expected > 99.0 && expected < 100.0);
+}
+
+#[test]
+fn solution_outlook_report() {
+    let report = SolutionOutlookReport {
+        solution_range: 100,
+        audited_sectors: 1,
+        best_miss_ratio: Some(3.24),
+        expected_solutions_per_slot: 1.0 / (24.0 * 60.0 * 60.0),
+        slot_duration: Duration::from_secs(1),
+    };
+    assert!((report.expected_solutions_per_day() - 1.0).abs() < 1e-9);
+    assert!(!report.is_hopeless());
+    assert_eq!(
+        report.to_string(),
+        "best miss was 3.2× outside solution range; expected ~1.00 solutions/day"
+    );
+
+    let hopeless_report = SolutionOutlookReport {
+        best_miss_ratio: None,
+        expected_solutions_per_slot: 0.0,
+        ..report
+    };
+    assert!(hopeless_report.is_hopeless());
+    assert_eq!(
+        hopeless_report.to_string(),
+        "no misses; expected ~0.00 solutions/day"
+    );
+
+    let directory = TempDir::new().unwrap();
+    assert_eq!(
+        SolutionOutlookReport::load_from(directory.path()).unwrap(),
+        None
+    );
+    report.store_to(directory.path()).unwrap();
+    assert_eq!(
+        SolutionOutlookReport::load_from(directory.path()).unwrap(),
+        Some(report)
+    );
+}
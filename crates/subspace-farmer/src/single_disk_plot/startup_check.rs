@@ -0,0 +1,254 @@
+//! Startup check of plot read path and proving.
+//!
+//! Nothing reads plotted sectors or creates proofs until a chunk wins, which may take hours, so
+//! broken disks and corrupted KZG parameters would go unnoticed for that long. Startup check reads
+//! one whole sector, audits it with solution range that makes any chunk win, creates solution and
+//! verifies it the same way node does it.
+
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::farming::{audit_sector, EligibleSector};
+use crate::single_disk_plot::sector_metadata::{SectorMetadataRecord, SectorMetadataRecordError};
+use crate::single_disk_plot::self_test::verify;
+use crate::single_disk_plot::FarmingError;
+use parity_scale_codec::Encode;
+use schnorrkel::Keypair;
+use std::io;
+use std::io::{Seek, SeekFrom};
+use std::time::{Duration, Instant};
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, PieceIndex, PublicKey, RecordsRoot, SectorId, SectorIndex,
+    SegmentIndex, SolutionRange,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use thiserror::Error;
+use tracing::debug;
+
+/// Number of random challenges tried before giving up, last bits of records that don't form a
+/// whole chunk are never eligible, so a single challenge may not produce a solution
+const AUDIT_ATTEMPTS: usize = 10;
+
+/// Errors of [`startup_check()`], each corresponds to a specific part of the farming pipeline
+#[derive(Debug, Error)]
+pub enum StartupCheckError {
+    /// Sector metadata record can't be decoded
+    #[error("Sector metadata record of sector {sector_index} is invalid: {error}")]
+    InvalidSectorMetadata {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Lower-level error
+        error: SectorMetadataRecordError,
+    },
+    /// Disk returned an error while reading sector
+    #[error("Failed to read sector {sector_index} from disk: {error}")]
+    DiskRead {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Lower-level error
+        error: io::Error,
+    },
+    /// Audit or solution creation failed, usually due to corrupted KZG parameters or sector
+    #[error("Failed to create proof from sector {sector_index}: {error}")]
+    Proving {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Lower-level error
+        error: FarmingError,
+    },
+    /// Solution couldn't be created from any of the audited chunks
+    #[error("No solution was created from sector {sector_index}, sector is likely corrupted")]
+    NoSolution {
+        /// Sector index
+        sector_index: SectorIndex,
+    },
+    /// Records root of the segment is unknown
+    #[error("Records root of segment {segment_index} is unknown, can't verify solution")]
+    UnknownRecordsRoot {
+        /// Segment index
+        segment_index: SegmentIndex,
+    },
+    /// Solution was created, but didn't pass verification
+    #[error(
+        "Solution created from sector {sector_index} is invalid (within solution range: \
+        {within_solution_range}, chunk signature valid: {chunk_signature_valid}, witness valid: \
+        {witness_valid})"
+    )]
+    InvalidSolution {
+        /// Sector index
+        sector_index: SectorIndex,
+        /// Solution is within solution range of the audit
+        within_solution_range: bool,
+        /// Chunk signature of the solution is valid
+        chunk_signature_valid: bool,
+        /// Witness of the piece in solution is valid for records root of the segment
+        witness_valid: bool,
+    },
+}
+
+/// Report of successful [`startup_check()`]
+#[derive(Debug, Clone)]
+pub struct StartupCheckReport {
+    /// Index of the checked sector
+    pub sector_index: SectorIndex,
+    /// Time to read the whole sector from disk
+    pub read_time: Duration,
+    /// Time to audit the sector
+    pub audit_time: Duration,
+    /// Time to decode winning piece and create solution, including witness
+    pub proving_time: Duration,
+    /// Time to verify solution
+    pub verification_time: Duration,
+}
+
+/// Read the whole `sector` with `sector_index` from disk, audit it with the largest solution range,
+/// create solution and verify it.
+///
+/// `records_root` returns records root of the segment, which is used to verify witness of the
+/// piece in solution.
+///
+/// NOTE: This function does blocking I/O and CPU-heavy work, it must be running in a separate
+/// thread in order to prevent blocking an executor.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn startup_check<S, RR>(
+    keypair: &Keypair,
+    reward_address: PublicKey,
+    farmer_protocol_info: &FarmerProtocolInfo,
+    plot_kzg_parameters_id: KzgParametersId,
+    kzg: &Kzg,
+    sector_index: SectorIndex,
+    mut sector: S,
+    sector_metadata_record: &[u8],
+    records_root: RR,
+) -> Result<StartupCheckReport, StartupCheckError>
+where
+    S: io::Read + io::Seek,
+    RR: FnOnce(SegmentIndex) -> Option<RecordsRoot>,
+{
+    let record =
+        SectorMetadataRecord::decode(sector_metadata_record, SectorMetadataRecord::LATEST_VERSION)
+            .map_err(|error| StartupCheckError::InvalidSectorMetadata {
+                sector_index,
+                error,
+            })?;
+    // Sector is checked with rotation it was plotted under
+    let farmer_protocol_info = FarmerProtocolInfo {
+        rotation: record.rotation,
+        ..*farmer_protocol_info
+    };
+    let public_key = PublicKey::from(keypair.public.to_bytes());
+    let disk_read = |error| StartupCheckError::DiskRead {
+        sector_index,
+        error,
+    };
+
+    let start = Instant::now();
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+    let read =
+        io::copy(&mut (&mut sector).take(plot_sector_size), &mut io::sink()).map_err(disk_read)?;
+    if read != plot_sector_size {
+        return Err(disk_read(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("sector is {read} bytes instead of {plot_sector_size}"),
+        )));
+    }
+    sector.seek(SeekFrom::Start(0)).map_err(disk_read)?;
+    let read_time = start.elapsed();
+    debug!(%sector_index, ?read_time, "Sector read");
+
+    let start = Instant::now();
+    let mut audit = || -> Result<Option<(Blake2b256Hash, EligibleSector)>, FarmingError> {
+        for _ in 0..AUDIT_ATTEMPTS {
+            let global_challenge = rand::random();
+            let maybe_eligible_sector = audit_sector(
+                &public_key,
+                sector_index,
+                &farmer_protocol_info,
+                plot_kzg_parameters_id,
+                kzg,
+                &global_challenge,
+                // Any chunk is within the largest solution range
+                SolutionRange::MAX,
+                &mut sector,
+            )?;
+            if let Some(eligible_sector) = maybe_eligible_sector {
+                return Ok(Some((global_challenge, eligible_sector)));
+            }
+        }
+
+        Ok(None)
+    };
+    let (global_challenge, eligible_sector) = match audit() {
+        Ok(Some(audited)) => audited,
+        Ok(None) => {
+            return Err(StartupCheckError::NoSolution { sector_index });
+        }
+        Err(FarmingError::Io(error)) => {
+            return Err(disk_read(error));
+        }
+        Err(error) => {
+            return Err(StartupCheckError::Proving {
+                sector_index,
+                error,
+            });
+        }
+    };
+    let audit_time = start.elapsed();
+    debug!(%sector_index, ?audit_time, "Sector audited");
+
+    let start = Instant::now();
+    let solution = eligible_sector
+        .try_into_solution(
+            keypair,
+            reward_address,
+            &farmer_protocol_info,
+            record.sector_metadata.encode().as_slice(),
+        )
+        .map_err(|error| StartupCheckError::Proving {
+            sector_index,
+            error,
+        })?
+        .ok_or(StartupCheckError::NoSolution { sector_index })?;
+    let proving_time = start.elapsed();
+    debug!(%sector_index, ?proving_time, "Solution created");
+
+    let start = Instant::now();
+    let sector_id = SectorId::new_rotated(&public_key, sector_index, record.rotation);
+    let pieces_in_segment = farmer_protocol_info.recorded_history_segment_size
+        / farmer_protocol_info.record_size.get()
+        * 2;
+    let segment_index = SegmentIndex::new(
+        sector_id.derive_piece_index(solution.piece_offset, solution.total_pieces)
+            / PieceIndex::from(pieces_in_segment),
+    );
+    let records_root = records_root(segment_index)
+        .ok_or(StartupCheckError::UnknownRecordsRoot { segment_index })?;
+    let verification = verify(
+        kzg,
+        &records_root,
+        &global_challenge,
+        &solution,
+        &sector_id,
+        pieces_in_segment,
+    );
+    let verification_time = start.elapsed();
+    debug!(%sector_index, ?verification_time, "Solution verified");
+
+    if !verification.valid() {
+        return Err(StartupCheckError::InvalidSolution {
+            sector_index,
+            within_solution_range: verification.within_solution_range,
+            chunk_signature_valid: verification.chunk_signature_valid,
+            witness_valid: verification.witness_valid,
+        });
+    }
+
+    Ok(StartupCheckReport {
+        sector_index,
+        read_time,
+        audit_time,
+        proving_time,
+        verification_time,
+    })
+}
@@ -0,0 +1,193 @@
+use crate::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use crate::single_disk_plot::sector_metadata::SectorMetadataRecord;
+use crate::single_disk_plot::startup_check::{startup_check, StartupCheckError};
+use crate::single_disk_plot::SectorMetadata;
+use crate::testing::MapPieceReceiver;
+use futures::executor::block_on;
+use parity_scale_codec::Decode;
+use rand::prelude::*;
+use schnorrkel::{ExpansionMode, Keypair, MiniSecretKey};
+use std::io;
+use std::io::{Cursor, SeekFrom};
+use std::sync::atomic::AtomicBool;
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    PublicKey, RecordsRoot, SectorIndex, SegmentIndex, PIECES_IN_SEGMENT,
+    RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+const SECTOR_INDEX: SectorIndex = SectorIndex::new(3);
+
+struct Plotted {
+    keypair: Keypair,
+    farmer_protocol_info: FarmerProtocolInfo,
+    kzg: Kzg,
+    records_root: RecordsRoot,
+    sector: Vec<u8>,
+    record: [u8; 64],
+}
+
+fn plot() -> Plotted {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let farmer_protocol_info = FarmerProtocolInfo::builder()
+        .record_size(RECORD_SIZE)
+        .recorded_history_segment_size(RECORDED_HISTORY_SEGMENT_SIZE)
+        .total_pieces(u64::from(PIECES_IN_SEGMENT))
+        .space_l(16)
+        .sector_expiration(SegmentIndex::ONE)
+        .build()
+        .unwrap();
+    let keypair = MiniSecretKey::from_bytes(&rand::random::<[u8; 32]>())
+        .unwrap()
+        .expand_to_keypair(ExpansionMode::Ed25519);
+    let public_key = PublicKey::from(keypair.public.to_bytes());
+
+    let mut block = vec![0u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
+    thread_rng().fill(block.as_mut_slice());
+    let archived_segment = Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone())
+        .unwrap()
+        .add_block(block, Default::default())
+        .into_iter()
+        .next()
+        .unwrap();
+
+    let mut sector = Vec::new();
+    let mut sector_metadata = Vec::new();
+    block_on(plot_sector(
+        &public_key,
+        SECTOR_INDEX,
+        &MapPieceReceiver::from_archived_segments([&archived_segment]),
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut sector),
+        &mut sector_metadata,
+    ))
+    .unwrap();
+    let record = SectorMetadataRecord {
+        sector_metadata: SectorMetadata::decode(&mut sector_metadata.as_slice()).unwrap(),
+        plotted_at_slot: None,
+        rotation: 0,
+        checksum: None,
+    }
+    .encode();
+
+    Plotted {
+        keypair,
+        farmer_protocol_info,
+        kzg,
+        records_root: archived_segment.root_block.records_root(),
+        sector,
+        record,
+    }
+}
+
+fn check<S>(
+    plotted: &Plotted,
+    sector: S,
+    records_root: Option<RecordsRoot>,
+) -> Result<(), StartupCheckError>
+where
+    S: io::Read + io::Seek,
+{
+    startup_check(
+        &plotted.keypair,
+        PublicKey::from(plotted.keypair.public.to_bytes()),
+        &plotted.farmer_protocol_info,
+        plotted.kzg.id(),
+        &plotted.kzg,
+        SECTOR_INDEX,
+        sector,
+        &plotted.record,
+        |segment_index| {
+            assert_eq!(segment_index, 0);
+            records_root
+        },
+    )
+    .map(|_report| ())
+}
+
+/// Disk that fails every read
+struct FailingSector;
+
+impl io::Read for FailingSector {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::from_raw_os_error(5))
+    }
+}
+
+impl io::Seek for FailingSector {
+    fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Ok(0)
+    }
+}
+
+#[test]
+fn startup_check_succeeds() {
+    let plotted = plot();
+
+    let report = startup_check(
+        &plotted.keypair,
+        PublicKey::from(plotted.keypair.public.to_bytes()),
+        &plotted.farmer_protocol_info,
+        plotted.kzg.id(),
+        &plotted.kzg,
+        SECTOR_INDEX,
+        Cursor::new(&plotted.sector),
+        &plotted.record,
+        |_segment_index| Some(plotted.records_root),
+    )
+    .unwrap();
+    assert_eq!(report.sector_index, SECTOR_INDEX);
+    assert!(!report.proving_time.is_zero());
+}
+
+#[test]
+fn startup_check_diagnosis() {
+    let plotted = plot();
+
+    assert!(matches!(
+        check(&plotted, FailingSector, Some(plotted.records_root)),
+        Err(StartupCheckError::DiskRead { sector_index, .. }) if sector_index == SECTOR_INDEX
+    ));
+    assert!(matches!(
+        check(
+            &plotted,
+            Cursor::new(&plotted.sector[..plotted.sector.len() - 1]),
+            Some(plotted.records_root)
+        ),
+        Err(StartupCheckError::DiskRead { error, .. })
+            if error.kind() == io::ErrorKind::UnexpectedEof
+    ));
+    assert!(matches!(
+        check(&plotted, Cursor::new(&plotted.sector), None),
+        Err(StartupCheckError::UnknownRecordsRoot { segment_index })
+            if segment_index == SegmentIndex::ZERO
+    ));
+
+    // Witness of the piece doesn't match unrelated records root
+    assert!(matches!(
+        check(
+            &plotted,
+            Cursor::new(&plotted.sector),
+            Some(RecordsRoot::default())
+        ),
+        Err(StartupCheckError::InvalidSolution {
+            witness_valid: false,
+            ..
+        })
+    ));
+
+    let mut plotted = plotted;
+    plotted.record = [0; 64];
+    assert!(matches!(
+        check(
+            &plotted,
+            Cursor::new(&plotted.sector),
+            Some(plotted.records_root)
+        ),
+        Err(StartupCheckError::InvalidSectorMetadata { .. })
+    ));
+}
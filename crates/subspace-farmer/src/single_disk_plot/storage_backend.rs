@@ -0,0 +1,595 @@
+#[cfg(test)]
+mod tests;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::overlay::PlotOverlay;
+use crate::single_disk_plot::plot_layout_calculator::PlotLayoutCalculator;
+use crate::single_disk_plot::plotting::SectorDestination;
+use crate::single_disk_plot::sharded_plot_file::ShardedPlotFile;
+use crate::single_disk_plot::{SingleDiskPlotError, SingleDiskPlotId};
+use memmap2::{Mmap, MmapMut, MmapOptions};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Deref;
+use std::path::Path;
+use subspace_core_primitives::PIECE_SIZE;
+
+/// Storage backend determines how plot files are accessed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageBackend {
+    /// Local file system, plot is memory mapped for auditing and plotting
+    Local,
+    /// Network file system (NFS, SMB/CIFS, etc.).
+    ///
+    /// `mmap` and `madvise` semantics are unreliable on such file systems and `fallocate` may not
+    /// be supported, so in this mode plot data and metadata are accessed with positional reads and
+    /// writes (`pread`/`pwrite`) instead of memory mapping and files are extended with `set_len`
+    /// instead of being preallocated.
+    ///
+    /// This comes at a performance cost: every audit results in a network round-trip per sector and
+    /// there is no page cache sharing between plotting and auditing, so auditing of large plots may
+    /// not fit into slot time on slow networks. Also `set_len` creates sparse files, meaning running
+    /// out of space on the remote end will only be discovered during plotting.
+    Network,
+}
+
+impl StorageBackend {
+    /// Detect storage backend based on the type of file system `path` is located on.
+    ///
+    /// Only supported on Linux, other platforms always return [`StorageBackend::Local`].
+    #[cfg(target_os = "linux")]
+    pub fn detect(path: &Path) -> io::Result<Self> {
+        use std::ffi::CString;
+        use std::mem::MaybeUninit;
+        use std::os::unix::ffi::OsStrExt;
+
+        const NFS_SUPER_MAGIC: u32 = 0x6969;
+        const SMB_SUPER_MAGIC: u32 = 0x517B;
+        const CIFS_MAGIC_NUMBER: u32 = 0xFF534D42;
+        const SMB2_MAGIC_NUMBER: u32 = 0xFE534D42;
+
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        let mut stat = MaybeUninit::<libc::statfs>::uninit();
+        if unsafe { libc::statfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: Initialized by successful `statfs` call above
+        let stat = unsafe { stat.assume_init() };
+
+        // Magic numbers are 32-bit, but `f_type` type differs between platforms
+        let file_system_type = stat.f_type as u32;
+
+        Ok(match file_system_type {
+            NFS_SUPER_MAGIC | SMB_SUPER_MAGIC | CIFS_MAGIC_NUMBER | SMB2_MAGIC_NUMBER => {
+                Self::Network
+            }
+            _ => Self::Local,
+        })
+    }
+
+    /// Detect storage backend based on the type of file system `path` is located on.
+    ///
+    /// Only supported on Linux, other platforms always return [`StorageBackend::Local`].
+    #[cfg(not(target_os = "linux"))]
+    pub fn detect(_path: &Path) -> io::Result<Self> {
+        Ok(Self::Local)
+    }
+
+    /// Whether plot files should be memory mapped
+    pub fn use_mmap(&self) -> bool {
+        matches!(self, Self::Local)
+    }
+
+    /// Make sure file has specified number of bytes allocated for it according to the backend
+    pub(crate) fn preallocate(&self, file: &File, len: u64) -> io::Result<()> {
+        match self {
+            Self::Local => file.preallocate(len),
+            Self::Network => {
+                if file.metadata()?.len() < len {
+                    file.set_len(len)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Distance in bytes between starts of consecutive sectors of `plot_sector_size` bytes that start
+/// at offsets that are multiples of `sector_alignment`, alignment must be a power of two.
+///
+/// All functions that locate sectors within plot expect stride rather than sector size.
+pub(crate) fn sector_stride(
+    plot_sector_size: u64,
+    sector_alignment: u64,
+) -> Result<u64, SingleDiskPlotError> {
+    let error = || SingleDiskPlotError::InvalidSectorAlignment {
+        sector_alignment,
+        plot_sector_size,
+    };
+
+    if !sector_alignment.is_power_of_two() {
+        return Err(error());
+    }
+
+    plot_sector_size
+        .checked_add(sector_alignment - 1)
+        .map(|padded_sector_size| padded_sector_size & !(sector_alignment - 1))
+        .ok_or_else(error)
+}
+
+/// Size in bytes of plot with `sector_count` sectors of `sector_size` bytes each
+pub(crate) fn plot_size(sector_count: u64, sector_size: u64) -> Result<u64, SingleDiskPlotError> {
+    // Multiplication can only overflow for non-zero number of sectors
+    sector_count.checked_mul(sector_size).ok_or_else(|| {
+        SingleDiskPlotError::SectorIndexOutOfRange {
+            sector_offset: sector_count - 1,
+            plot_sector_size: sector_size,
+        }
+    })
+}
+
+/// Same as [`plot_size()`], but also checks that plot of this size can be memory mapped on this
+/// platform
+pub(crate) fn plot_mmap_len(
+    sector_count: u64,
+    sector_size: u64,
+) -> Result<usize, SingleDiskPlotError> {
+    usize::try_from(plot_size(sector_count, sector_size)?).map_err(|_error| {
+        SingleDiskPlotError::SectorIndexOutOfRange {
+            sector_offset: sector_count - 1,
+            plot_sector_size: sector_size,
+        }
+    })
+}
+
+/// Number of sectors plot with `allocated_space` bytes has when fully plotted, followed by the
+/// number of sectors of plots created before metadata overhead was accounted for
+pub(crate) fn target_sector_counts(
+    allocated_space: u64,
+    plot_sector_size: u64,
+    sector_stride: u64,
+) -> [u64; 2] {
+    [
+        PlotLayoutCalculator::with_sector_stride(plot_sector_size, sector_stride)
+            .sectors_for_space(allocated_space),
+        allocated_space / sector_stride,
+    ]
+}
+
+/// Check that space allocated for plot, number of sectors recorded in plot metadata and size of
+/// one sector recomputed from recorded protocol parameters describe the plot file of
+/// `plot_file_size` bytes.
+///
+/// Plot whose layout is inconsistent was likely created with different protocol parameters, it
+/// can't be opened since sector boundaries would be misinterpreted.
+pub(crate) fn check_plot_layout(
+    id: SingleDiskPlotId,
+    allocated_space: u64,
+    sector_count: u64,
+    plot_sector_size: u64,
+    plot_file_size: u64,
+) -> Result<(), SingleDiskPlotError> {
+    // Callers pass sector stride as sector size, target sector count only depends on stride
+    let consistent = target_sector_counts(allocated_space, plot_sector_size, plot_sector_size)
+        .into_iter()
+        .any(|target_sector_count| {
+            sector_count <= target_sector_count
+                && plot_size(target_sector_count, plot_sector_size)
+                    .map(|expected_plot_file_size| expected_plot_file_size == plot_file_size)
+                    .unwrap_or_default()
+        });
+
+    if consistent {
+        Ok(())
+    } else {
+        Err(SingleDiskPlotError::InconsistentPlotLayout {
+            id,
+            allocated_space,
+            sector_count,
+            plot_sector_size,
+            plot_file_size,
+        })
+    }
+}
+
+/// Offset in bytes of the sector at `sector_offset` (in sectors) within plot, checks that the whole
+/// sector is addressable
+pub(crate) fn sector_start(
+    sector_offset: u64,
+    sector_size: u64,
+) -> Result<u64, SingleDiskPlotError> {
+    let sector_end = sector_offset
+        .checked_add(1)
+        .ok_or(SingleDiskPlotError::SectorIndexOutOfRange {
+            sector_offset,
+            plot_sector_size: sector_size,
+        })
+        .and_then(|sector_count| plot_size(sector_count, sector_size))?;
+
+    Ok(sector_end - sector_size)
+}
+
+/// Read-only access to plot data that is either memory mapped or read with positional reads
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum PlotData<'a> {
+    /// Memory mapped plot contents
+    Mmap(&'a [u8]),
+    /// Plot file
+    File(&'a File),
+    /// Plot split into shard files
+    Sharded(&'a ShardedPlotFile),
+    /// Read-only base layer of the plot with writable overlay on top of it
+    Layered {
+        /// Base layer and sectors written to overlay
+        plot_overlay: &'a PlotOverlay,
+        /// Overlay plot file
+        overlay: &'a PlotData<'a>,
+    },
+}
+
+impl<'a> PlotData<'a> {
+    /// Read exact number of bytes at a specific offset
+    pub(crate) fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        match self {
+            Self::Mmap(bytes) => {
+                let source = usize::try_from(offset)
+                    .ok()
+                    .and_then(|offset| bytes.get(offset..)?.get(..buf.len()))
+                    .ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")
+                    })?;
+                buf.copy_from_slice(source);
+                Ok(())
+            }
+            Self::File(file) => file.read_exact_at(buf, offset),
+            Self::Sharded(sharded_plot_file) => sharded_plot_file.read_exact_at(buf, offset),
+            Self::Layered {
+                plot_overlay,
+                overlay,
+            } => plot_overlay.read_exact_at(*overlay, buf, offset),
+        }
+    }
+
+    /// Plot data that reads sectors from base layer of `plot_overlay` unless they were written to
+    /// overlay, in which case they are read from `self`. Returns `self` if plot has no overlay.
+    pub(crate) fn layered(&'a self, plot_overlay: Option<&'a PlotOverlay>) -> PlotData<'a> {
+        match plot_overlay {
+            Some(plot_overlay) => plot_overlay.plot_data(self),
+            None => *self,
+        }
+    }
+
+    /// Reader of the sector at `sector_offset` (in sectors) within plot
+    pub(crate) fn sector(
+        self,
+        sector_offset: u64,
+        sector_size: u64,
+    ) -> Result<SectorReader<'a>, SingleDiskPlotError> {
+        Ok(SectorReader {
+            plot_data: self,
+            sector_start: sector_start(sector_offset, sector_size)?,
+            sector_size,
+            position: 0,
+        })
+    }
+}
+
+/// Reader of a single sector within plot, cursor starts at the beginning of the sector
+#[derive(Debug)]
+pub(crate) struct SectorReader<'a> {
+    plot_data: PlotData<'a>,
+    sector_start: u64,
+    sector_size: u64,
+    position: u64,
+}
+
+impl Read for SectorReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.sector_size.saturating_sub(self.position);
+        let len = (buf.len() as u64).min(remaining) as usize;
+        self.plot_data
+            .read_exact_at(&mut buf[..len], self.sector_start + self.position)?;
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for SectorReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.sector_size, pos)?;
+        Ok(self.position)
+    }
+}
+
+/// Writer of a single sector within plot file (or anything else that supports positional writes,
+/// like plot split into shards) using positional writes.
+///
+/// Partially written contents are left in place on abort, sector is not visible to farming until
+/// its metadata is written anyway.
+#[derive(Debug)]
+pub(crate) struct SectorFileWriter<'a, F = File> {
+    file: &'a F,
+    sector_start: u64,
+}
+
+impl<'a, F> SectorFileWriter<'a, F>
+where
+    F: FileExt,
+{
+    pub(crate) fn new(
+        file: &'a F,
+        sector_offset: u64,
+        sector_size: u64,
+    ) -> Result<Self, SingleDiskPlotError> {
+        Ok(Self {
+            file,
+            sector_start: sector_start(sector_offset, sector_size)?,
+        })
+    }
+}
+
+impl<F> SectorDestination for SectorFileWriter<'_, F>
+where
+    F: FileExt,
+{
+    fn write_sector_chunk(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        self.file.write_all_at(bytes, self.sector_start + offset)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn abort(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sector of which only a single piece is available, reading anything else results in error.
+///
+/// Auditing only reads one piece per sector, so it can be done with just that piece fetched ahead
+/// of time.
+#[derive(Debug)]
+pub(crate) struct PieceSector<'a> {
+    sector_size: u64,
+    piece_offset: u64,
+    piece: &'a [u8],
+    position: u64,
+}
+
+impl<'a> PieceSector<'a> {
+    /// Create sector of `sector_size` bytes where `piece` is located at `piece_offset` (in pieces)
+    pub(crate) fn new(sector_size: u64, piece_offset: u64, piece: &'a [u8]) -> Self {
+        Self {
+            sector_size,
+            piece_offset,
+            piece,
+            position: 0,
+        }
+    }
+}
+
+impl Read for PieceSector<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let piece_start = self.piece_offset * PIECE_SIZE as u64;
+        let source = self
+            .position
+            .checked_sub(piece_start)
+            .and_then(|offset_in_piece| self.piece.get(usize::try_from(offset_in_piece).ok()?..))
+            .filter(|source| !source.is_empty())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Sector contents at offset {} are not available",
+                        self.position
+                    ),
+                )
+            })?;
+
+        let read = source.len().min(buf.len());
+        buf[..read].copy_from_slice(&source[..read]);
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Seek for PieceSector<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = seek_position(self.position, self.sector_size, pos)?;
+        Ok(self.position)
+    }
+}
+
+pub(crate) fn seek_position(position: u64, size: u64, pos: SeekFrom) -> io::Result<u64> {
+    let (base, offset) = match pos {
+        SeekFrom::Start(offset) => {
+            return Ok(offset);
+        }
+        SeekFrom::End(offset) => (size, offset),
+        SeekFrom::Current(offset) => (position, offset),
+    };
+
+    u64::try_from(i128::from(base) + i128::from(offset)).map_err(|_error| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative position",
+        )
+    })
+}
+
+/// Read-only access to the beginning of metadata file that is either memory mapped or read with
+/// positional reads.
+///
+/// Positional reads go to the file every time, so records written by plotting become visible right
+/// away, just like with memory mapping.
+#[derive(Debug)]
+pub(crate) enum MetadataFile {
+    /// Memory mapped contents
+    Mmap(Mmap),
+    /// Metadata file
+    File {
+        /// Metadata file
+        file: File,
+        /// Number of accessible bytes at the beginning of the file
+        len: usize,
+    },
+}
+
+impl MetadataFile {
+    /// Access the first `len` bytes of `file` the way storage backend allows
+    pub(crate) fn open(
+        storage_backend: StorageBackend,
+        file: &File,
+        len: usize,
+    ) -> io::Result<Self> {
+        if storage_backend.use_mmap() {
+            let mmap = unsafe { MmapOptions::new().len(len).map(file)? };
+
+            Ok(Self::Mmap(mmap))
+        } else {
+            Ok(Self::File {
+                file: file.try_clone()?,
+                len,
+            })
+        }
+    }
+
+    /// Advise OS/file system that contents will be accessed randomly
+    pub(crate) fn advise_random_access(&self) -> io::Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::Mmap(mmap) => mmap.advise(memmap2::Advice::Random),
+            #[cfg(not(unix))]
+            Self::Mmap(_mmap) => Ok(()),
+            Self::File { file, .. } => file.advise_random_access(),
+        }
+    }
+
+    /// All accessible contents
+    pub(crate) fn contents(&self) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::Mmap(mmap) => Ok(Cow::Borrowed(mmap)),
+            Self::File { len, .. } => self.read_at(0, *len),
+        }
+    }
+
+    /// `len` bytes of contents at `offset`
+    pub(crate) fn read_at(&self, offset: usize, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::Mmap(mmap) => contents_range(mmap, offset, len).map(Cow::Borrowed),
+            Self::File {
+                file,
+                len: accessible_len,
+            } => {
+                if offset.saturating_add(len) > *accessible_len {
+                    return Err(out_of_range());
+                }
+                let mut contents = vec![0; len];
+                file.read_exact_at(&mut contents, offset as u64)?;
+
+                Ok(Cow::Owned(contents))
+            }
+        }
+    }
+}
+
+/// Writable beginning of metadata file that is either memory mapped or kept in memory and written
+/// through to the file with positional writes.
+///
+/// Contents kept in memory don't observe writes made through other handles, so there must be only
+/// one writer of the file.
+#[derive(Debug)]
+pub(crate) enum MetadataFileMut {
+    /// Memory mapped contents
+    Mmap(MmapMut),
+    /// Metadata file
+    File {
+        /// Metadata file
+        file: File,
+        /// Copy of accessible contents of the file
+        contents: Vec<u8>,
+    },
+}
+
+impl MetadataFileMut {
+    /// Access the first `len` bytes of `file` the way storage backend allows
+    pub(crate) fn open(
+        storage_backend: StorageBackend,
+        file: &File,
+        len: usize,
+    ) -> io::Result<Self> {
+        if storage_backend.use_mmap() {
+            let mmap = unsafe { MmapOptions::new().len(len).map_mut(file)? };
+
+            Ok(Self::Mmap(mmap))
+        } else {
+            let mut contents = vec![0; len];
+            file.read_exact_at(&mut contents, 0)?;
+
+            Ok(Self::File {
+                file: file.try_clone()?,
+                contents,
+            })
+        }
+    }
+
+    /// Write `bytes` at `offset`, contents kept in memory are only updated once write to the file
+    /// succeeded
+    pub(crate) fn write_at(&mut self, bytes: &[u8], offset: usize) -> io::Result<()> {
+        match self {
+            Self::Mmap(mmap) => {
+                contents_range_mut(mmap, offset, bytes.len())?.copy_from_slice(bytes);
+            }
+            Self::File { file, contents } => {
+                let target = contents_range_mut(contents, offset, bytes.len())?;
+                file.write_all_at(bytes, offset as u64)?;
+                target.copy_from_slice(bytes);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Deref for MetadataFileMut {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Mmap(mmap) => mmap,
+            Self::File { contents, .. } => contents,
+        }
+    }
+}
+
+fn contents_range(contents: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    contents
+        .get(offset..)
+        .and_then(|contents| contents.get(..len))
+        .ok_or_else(out_of_range)
+}
+
+fn contents_range_mut(contents: &mut [u8], offset: usize, len: usize) -> io::Result<&mut [u8]> {
+    contents
+        .get_mut(offset..)
+        .and_then(|contents| contents.get_mut(..len))
+        .ok_or_else(out_of_range)
+}
+
+fn out_of_range() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "Range is beyond accessible contents of metadata file",
+    )
+}
@@ -0,0 +1,262 @@
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::plotting::plot_sector;
+use crate::single_disk_plot::storage_backend::{
+    plot_mmap_len, plot_size, sector_start, sector_stride, MetadataFile, MetadataFileMut, PlotData,
+    SectorFileWriter, StorageBackend,
+};
+use crate::single_disk_plot::SingleDiskPlotError;
+use crate::testing::fixtures::{archived_segment, farmer_protocol_info};
+use crate::testing::MapPieceReceiver;
+use futures::executor::block_on;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::num::{NonZeroU16, NonZeroU64};
+use std::sync::atomic::AtomicBool;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    plot_sector_size, Blake2b256Hash, PublicKey, SolutionRange, PIECE_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tempfile::TempDir;
+
+#[test]
+fn network_backend_plotting_and_auditing() {
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let piece_receiver = MapPieceReceiver::from_archived_segments([&archived_segment(&kzg)]);
+
+    let farmer_protocol_info = FarmerProtocolInfo {
+        total_pieces: NonZeroU64::new(1).unwrap(),
+        space_l: NonZeroU16::new(20).unwrap(),
+        ..farmer_protocol_info()
+    };
+    let plot_sector_size = plot_sector_size(farmer_protocol_info.space_l);
+    let sector_count = 2;
+
+    let directory = TempDir::new().unwrap();
+    let plot_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(directory.path().join("plot.bin"))
+        .unwrap();
+
+    let storage_backend = StorageBackend::Network;
+    assert!(!storage_backend.use_mmap());
+
+    storage_backend
+        .preallocate(&plot_file, plot_sector_size * sector_count)
+        .unwrap();
+    assert_eq!(
+        plot_file.metadata().unwrap().len(),
+        plot_sector_size * sector_count
+    );
+
+    let cancelled = AtomicBool::new(false);
+    for sector_offset in 0..sector_count {
+        block_on(plot_sector(
+            &public_key,
+            sector_offset,
+            &piece_receiver,
+            &cancelled,
+            &farmer_protocol_info,
+            SectorFileWriter::new(&plot_file, sector_offset, plot_sector_size).unwrap(),
+            io::sink(),
+        ))
+        .unwrap();
+    }
+
+    let global_challenge: Blake2b256Hash = rand::random();
+    for sector_offset in 0..sector_count {
+        let eligible_sector = audit_sector(
+            &public_key,
+            sector_offset,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            SolutionRange::MAX,
+            PlotData::File(&plot_file)
+                .sector(sector_offset, plot_sector_size)
+                .unwrap(),
+        )
+        .unwrap()
+        .expect("Max solution range always results in eligible sector");
+
+        // Reading the same sector with memory mapping must produce identical results
+        let plot_mmap = unsafe { memmap2::Mmap::map(&plot_file).unwrap() };
+        let eligible_sector_mmap = audit_sector(
+            &public_key,
+            sector_offset,
+            &farmer_protocol_info,
+            KzgParametersId::TEST,
+            &kzg,
+            &global_challenge,
+            SolutionRange::MAX,
+            PlotData::Mmap(&plot_mmap)
+                .sector(sector_offset, plot_sector_size)
+                .unwrap(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(eligible_sector.chunk, eligible_sector_mmap.chunk);
+        assert_eq!(
+            eligible_sector.encoded_piece,
+            eligible_sector_mmap.encoded_piece
+        );
+    }
+}
+
+#[test]
+fn sector_alignment() {
+    // 2.5 MiB
+    let plot_sector_size = plot_sector_size(NonZeroU16::new(20).unwrap());
+
+    assert_eq!(
+        sector_stride(plot_sector_size, 1).unwrap(),
+        plot_sector_size
+    );
+    assert_eq!(
+        sector_stride(plot_sector_size, PIECE_SIZE as u64).unwrap(),
+        plot_sector_size
+    );
+    assert_eq!(
+        sector_stride(plot_sector_size, 1024 * 1024).unwrap(),
+        3 * 1024 * 1024
+    );
+    assert_eq!(
+        sector_stride(plot_sector_size, 4 * 1024 * 1024).unwrap(),
+        4 * 1024 * 1024
+    );
+    assert_eq!(
+        sector_start(3, sector_stride(plot_sector_size, 1024 * 1024).unwrap()).unwrap(),
+        9 * 1024 * 1024
+    );
+
+    for sector_alignment in [0, 3, 4096 + 512] {
+        assert!(matches!(
+            sector_stride(plot_sector_size, sector_alignment),
+            Err(SingleDiskPlotError::InvalidSectorAlignment {
+                sector_alignment: error_sector_alignment,
+                ..
+            }) if error_sector_alignment == sector_alignment
+        ));
+    }
+}
+
+#[test]
+fn sector_offset_overflow() {
+    let plot_sector_size = plot_sector_size(NonZeroU16::new(20).unwrap());
+    // Last sector that still fits entirely into `u64` address space
+    let max_sector_offset = u64::MAX / plot_sector_size - 1;
+
+    assert_eq!(
+        sector_start(max_sector_offset, plot_sector_size).unwrap(),
+        max_sector_offset * plot_sector_size
+    );
+    assert_eq!(
+        plot_size(max_sector_offset + 1, plot_sector_size).unwrap(),
+        (max_sector_offset + 1) * plot_sector_size
+    );
+
+    for sector_offset in [max_sector_offset + 1, u64::MAX] {
+        assert!(matches!(
+            sector_start(sector_offset, plot_sector_size),
+            Err(SingleDiskPlotError::SectorIndexOutOfRange {
+                sector_offset: error_sector_offset,
+                plot_sector_size: error_plot_sector_size,
+            }) if error_sector_offset == sector_offset
+                && error_plot_sector_size == plot_sector_size
+        ));
+    }
+    assert!(matches!(
+        plot_size(max_sector_offset + 2, plot_sector_size),
+        Err(SingleDiskPlotError::SectorIndexOutOfRange { .. })
+    ));
+    assert!(matches!(
+        plot_mmap_len(max_sector_offset + 2, plot_sector_size),
+        Err(SingleDiskPlotError::SectorIndexOutOfRange { .. })
+    ));
+    if usize::BITS < u64::BITS {
+        assert!(matches!(
+            plot_mmap_len(usize::MAX as u64 / plot_sector_size + 1, plot_sector_size),
+            Err(SingleDiskPlotError::SectorIndexOutOfRange { .. })
+        ));
+    }
+
+    let directory = TempDir::new().unwrap();
+    let plot_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(directory.path().join("plot.bin"))
+        .unwrap();
+
+    assert!(matches!(
+        PlotData::File(&plot_file).sector(max_sector_offset + 1, plot_sector_size),
+        Err(SingleDiskPlotError::SectorIndexOutOfRange { .. })
+    ));
+    assert!(matches!(
+        PlotData::Mmap(&[]).sector(u64::MAX, plot_sector_size),
+        Err(SingleDiskPlotError::SectorIndexOutOfRange { .. })
+    ));
+    assert!(matches!(
+        SectorFileWriter::new(&plot_file, u64::MAX, plot_sector_size),
+        Err(SingleDiskPlotError::SectorIndexOutOfRange { .. })
+    ));
+
+    // Sector at the boundary is addressable, reading it fails gracefully instead of wrapping
+    let mut sector = PlotData::Mmap(&[])
+        .sector(max_sector_offset, plot_sector_size)
+        .unwrap();
+    assert_eq!(
+        sector.read(&mut [0; 8]).unwrap_err().kind(),
+        io::ErrorKind::UnexpectedEof
+    );
+}
+
+#[test]
+fn metadata_file_writes_are_visible() {
+    let directory = TempDir::new().unwrap();
+
+    for storage_backend in [StorageBackend::Local, StorageBackend::Network] {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(directory.path().join("metadata.bin"))
+            .unwrap();
+        file.write_all_at(&[1, 2, 3, 4, 5, 6, 7, 8], 0).unwrap();
+
+        // Only the beginning of the file is accessible
+        let mut metadata_file_mut = MetadataFileMut::open(storage_backend, &file, 6).unwrap();
+        let metadata_file = MetadataFile::open(storage_backend, &file, 6).unwrap();
+        assert_eq!(
+            matches!(metadata_file, MetadataFile::Mmap(_)),
+            storage_backend.use_mmap()
+        );
+        assert_eq!(&*metadata_file_mut, &[1, 2, 3, 4, 5, 6]);
+
+        metadata_file_mut.write_at(&[9, 9], 2).unwrap();
+        assert_eq!(&*metadata_file_mut, &[1, 2, 9, 9, 5, 6]);
+        // Readers see writes right away
+        assert_eq!(&*metadata_file.contents().unwrap(), &[1, 2, 9, 9, 5, 6]);
+        assert_eq!(&*metadata_file.read_at(3, 2).unwrap(), &[9, 5]);
+        let mut contents = [0; 8];
+        file.read_exact_at(&mut contents, 0).unwrap();
+        assert_eq!(contents, [1, 2, 9, 9, 5, 6, 7, 8]);
+
+        // Nothing beyond accessible contents can be read or written
+        assert_eq!(
+            metadata_file.read_at(5, 2).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert!(metadata_file_mut.write_at(&[0; 2], 5).is_err());
+        assert_eq!(&*metadata_file_mut, &[1, 2, 9, 9, 5, 6]);
+    }
+}
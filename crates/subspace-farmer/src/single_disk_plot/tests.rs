@@ -0,0 +1,508 @@
+mod compatibility;
+
+use crate::file_ext::FileExt;
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use crate::single_disk_plot::plotting::{plot_sector, SequentialSectorDestination};
+use crate::single_disk_plot::sector_locks::SectorLocks;
+use crate::single_disk_plot::sector_metadata::{
+    read_sector_metadata_record, sector_metadata_file_size, sector_metadata_record_offset,
+    SectorMetadataRecord, SECTOR_CHECKSUM_SIZE, SECTOR_METADATA_RECORD_SIZE,
+};
+use crate::single_disk_plot::storage_backend::{MetadataFileMut, StorageBackend};
+use crate::single_disk_plot::{
+    catch_sector_panic, rebind_sector_expirations, stale_rotation_sector_offset, thread_name,
+    FarmingError, Handlers, PlotFiles, PlotMetadataHeader, PlotProtocolInfo, PlotSectorAlignment,
+    SectorMetadata, SingleDiskPlotError, SingleDiskPlotId, RESERVED_PLOT_METADATA,
+};
+use crate::testing::fixtures::farmer_protocol_info;
+use async_trait::async_trait;
+use futures::executor::block_on;
+use memmap2::{Mmap, MmapMut};
+use parity_scale_codec::{Decode, Encode};
+use parking_lot::Mutex;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::num::{NonZeroU16, NonZeroU64};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::{fs, io};
+use subspace_core_primitives::crypto::kzg::KzgParametersId;
+use subspace_core_primitives::{Piece, PieceIndex, PublicKey, SectorIndex, SegmentIndex};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use tempfile::TempDir;
+
+struct PanickingPieceReceiver;
+
+#[async_trait]
+impl PieceReceiver for PanickingPieceReceiver {
+    async fn get_piece(
+        &self,
+        _piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        panic!("Piece receiver panicked");
+    }
+}
+
+fn assert_same_protocol_info(left: &FarmerProtocolInfo, right: &FarmerProtocolInfo) {
+    assert_eq!(
+        PlotProtocolInfo::new(*left, KzgParametersId::TEST),
+        PlotProtocolInfo::new(*right, KzgParametersId::TEST)
+    );
+}
+
+#[test]
+fn protocol_info_round_trip() {
+    let farmer_protocol_info = farmer_protocol_info();
+    let kzg_parameters_id = KzgParametersId(1);
+
+    let encoded = PlotProtocolInfo::new(farmer_protocol_info, kzg_parameters_id).encode();
+    // Version is encoded first, so it can be used to upgrade older versions on decoding
+    assert_eq!(encoded[0], 1);
+    let decoded = PlotProtocolInfo::decode(&mut encoded.as_slice()).unwrap();
+    assert_eq!(decoded.kzg_parameters_id(), kzg_parameters_id);
+    assert_same_protocol_info(&FarmerProtocolInfo::from(decoded), &farmer_protocol_info);
+
+    // Plots created before KZG parameters identifier was stored used test parameters
+    let encoded_v0 = PlotProtocolInfo::V0 {
+        genesis_hash: farmer_protocol_info.genesis_hash,
+        record_size: farmer_protocol_info.record_size,
+        recorded_history_segment_size: farmer_protocol_info.recorded_history_segment_size,
+        total_pieces: farmer_protocol_info.total_pieces,
+        space_l: farmer_protocol_info.space_l,
+        sector_expiration: farmer_protocol_info.sector_expiration,
+    }
+    .encode();
+    assert_eq!(encoded_v0[0], 0);
+    let decoded_v0 = PlotProtocolInfo::decode(&mut encoded_v0.as_slice()).unwrap();
+    assert_eq!(decoded_v0.kzg_parameters_id(), KzgParametersId::TEST);
+    assert_same_protocol_info(&FarmerProtocolInfo::from(decoded_v0), &farmer_protocol_info);
+
+    let directory = TempDir::new().unwrap();
+    let metadata_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(directory.path().join("metadata.bin"))
+        .unwrap();
+    metadata_file.set_len(RESERVED_PLOT_METADATA).unwrap();
+    let id = SingleDiskPlotId::new();
+
+    let mut metadata_header = PlotMetadataHeader {
+        version: 0,
+        sector_count: 0,
+    };
+    metadata_file
+        .write_all_at(&metadata_header.encode(), 0)
+        .unwrap();
+
+    // Protocol info is stored on first open and header is upgraded
+    let (stored, stored_kzg_parameters_id) = PlotProtocolInfo::load_or_store(
+        id,
+        &metadata_file,
+        &mut metadata_header,
+        farmer_protocol_info,
+        kzg_parameters_id,
+    )
+    .unwrap();
+    assert_same_protocol_info(&stored, &farmer_protocol_info);
+    assert_eq!(stored_kzg_parameters_id, kzg_parameters_id);
+    assert_eq!(metadata_header.version, 1);
+
+    let mut header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+    metadata_file.read_exact_at(&mut header_bytes, 0).unwrap();
+    let mut metadata_header = PlotMetadataHeader::decode(&mut header_bytes.as_slice()).unwrap();
+    assert_eq!(metadata_header.version, 1);
+
+    // On restart stored protocol info is used even if node reports different parameters
+    let node_farmer_protocol_info = FarmerProtocolInfo {
+        total_pieces: NonZeroU64::new(512).unwrap(),
+        space_l: NonZeroU16::new(21).unwrap(),
+        ..farmer_protocol_info
+    };
+    let (stored, stored_kzg_parameters_id) = PlotProtocolInfo::load_or_store(
+        id,
+        &metadata_file,
+        &mut metadata_header,
+        node_farmer_protocol_info,
+        KzgParametersId::TEST,
+    )
+    .unwrap();
+    assert_same_protocol_info(&stored, &farmer_protocol_info);
+    assert_eq!(stored_kzg_parameters_id, kzg_parameters_id);
+
+    // Sector expiration follows the node and is stored for subsequent opens
+    let node_farmer_protocol_info = FarmerProtocolInfo {
+        sector_expiration: SegmentIndex::new(200),
+        ..node_farmer_protocol_info
+    };
+    let expected_protocol_info = FarmerProtocolInfo {
+        sector_expiration: node_farmer_protocol_info.sector_expiration,
+        ..farmer_protocol_info
+    };
+    let (stored, stored_kzg_parameters_id) = PlotProtocolInfo::load_or_store(
+        id,
+        &metadata_file,
+        &mut metadata_header,
+        node_farmer_protocol_info,
+        KzgParametersId::TEST,
+    )
+    .unwrap();
+    assert_same_protocol_info(&stored, &expected_protocol_info);
+    assert_eq!(stored_kzg_parameters_id, kzg_parameters_id);
+    let (stored, stored_kzg_parameters_id) = PlotProtocolInfo::load(&metadata_file).unwrap();
+    assert_same_protocol_info(&stored, &expected_protocol_info);
+    assert_eq!(stored_kzg_parameters_id, kzg_parameters_id);
+}
+
+#[test]
+fn protocol_info_genesis_hash_mismatch() {
+    let farmer_protocol_info = farmer_protocol_info();
+
+    let directory = TempDir::new().unwrap();
+    let metadata_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(directory.path().join("metadata.bin"))
+        .unwrap();
+    metadata_file.set_len(RESERVED_PLOT_METADATA).unwrap();
+    let id = SingleDiskPlotId::new();
+
+    let mut metadata_header = PlotMetadataHeader {
+        version: 0,
+        sector_count: 0,
+    };
+    metadata_file
+        .write_all_at(&metadata_header.encode(), 0)
+        .unwrap();
+
+    PlotProtocolInfo::load_or_store(
+        id,
+        &metadata_file,
+        &mut metadata_header,
+        farmer_protocol_info,
+        KzgParametersId::TEST,
+    )
+    .unwrap();
+
+    let other_chain_protocol_info = FarmerProtocolInfo {
+        genesis_hash: rand::random(),
+        ..farmer_protocol_info
+    };
+    let result = PlotProtocolInfo::load_or_store(
+        id,
+        &metadata_file,
+        &mut metadata_header,
+        other_chain_protocol_info,
+        KzgParametersId::TEST,
+    );
+    assert!(matches!(
+        result,
+        Err(SingleDiskPlotError::WrongChain { id: error_id, .. }) if error_id == id
+    ));
+}
+
+#[test]
+fn sector_alignment_round_trip() {
+    let directory = TempDir::new().unwrap();
+    let open_metadata_file = |name: &str| {
+        let metadata_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(directory.path().join(name))
+            .unwrap();
+        metadata_file.set_len(RESERVED_PLOT_METADATA).unwrap();
+        metadata_file
+    };
+    let sector_alignment = NonZeroU64::new(4096).unwrap();
+
+    for (name, sector_count, expected_sector_alignment) in [
+        // New plot uses requested alignment
+        ("new.bin", 0, sector_alignment.get()),
+        // Sectors of plot created before alignment was stored are located back to back
+        ("existing.bin", 5, 1),
+    ] {
+        let metadata_file = open_metadata_file(name);
+        let mut metadata_header = PlotMetadataHeader {
+            version: 0,
+            sector_count,
+        };
+        metadata_file
+            .write_all_at(&metadata_header.encode(), 0)
+            .unwrap();
+
+        let initial_sector_alignment =
+            PlotSectorAlignment::initial(&metadata_header, sector_alignment);
+        // Protocol info needs to be stored first, alignment is stored right after it
+        PlotProtocolInfo::load_or_store(
+            SingleDiskPlotId::new(),
+            &metadata_file,
+            &mut metadata_header,
+            farmer_protocol_info(),
+            KzgParametersId::TEST,
+        )
+        .unwrap();
+        assert_eq!(
+            PlotSectorAlignment::load(&metadata_file, &metadata_header).unwrap(),
+            1
+        );
+        assert_eq!(
+            PlotSectorAlignment::load_or_store(
+                &metadata_file,
+                &mut metadata_header,
+                initial_sector_alignment
+            )
+            .unwrap(),
+            expected_sector_alignment
+        );
+        assert_eq!(metadata_header.version, PlotMetadataHeader::LATEST_VERSION);
+
+        // Stored alignment is used on restart regardless of requested one
+        let mut header_bytes = vec![0; PlotMetadataHeader::encoded_size()];
+        metadata_file.read_exact_at(&mut header_bytes, 0).unwrap();
+        let mut metadata_header = PlotMetadataHeader::decode(&mut header_bytes.as_slice()).unwrap();
+        assert_eq!(metadata_header.version, PlotMetadataHeader::LATEST_VERSION);
+        assert_eq!(
+            PlotSectorAlignment::load_or_store(&metadata_file, &mut metadata_header, 512).unwrap(),
+            expected_sector_alignment
+        );
+    }
+}
+
+#[test]
+fn stale_rotation_sector() {
+    let rotations = [2, 2, 1, 2];
+    let mut contents = vec![0; sector_metadata_file_size(rotations.len() as u64) as usize];
+    for (sector_offset, rotation) in rotations.into_iter().enumerate() {
+        contents[sector_metadata_record_offset(sector_offset as u64) as usize..]
+            [..SECTOR_METADATA_RECORD_SIZE]
+            .copy_from_slice(
+                &SectorMetadataRecord {
+                    sector_metadata: SectorMetadata {
+                        total_pieces: NonZeroU64::new(256).unwrap(),
+                        expires_at: SegmentIndex::new(100),
+                    },
+                    plotted_at_slot: None,
+                    rotation,
+                    checksum: None,
+                }
+                .encode(),
+            );
+    }
+
+    assert_eq!(stale_rotation_sector_offset(&contents, 2, 2), None);
+    assert_eq!(stale_rotation_sector_offset(&contents, 4, 2), Some(2));
+    assert_eq!(stale_rotation_sector_offset(&contents, 4, 1), Some(0));
+    // Sector with cleared record was interrupted while being replotted
+    contents[sector_metadata_record_offset(1) as usize..][..SECTOR_METADATA_RECORD_SIZE].fill(0);
+    assert_eq!(stale_rotation_sector_offset(&contents, 4, 2), Some(1));
+}
+
+#[test]
+fn sector_expirations_rebound() {
+    let farmer_protocol_info = FarmerProtocolInfo {
+        sector_expiration: SegmentIndex::new(200),
+        rotation: 2,
+        ..farmer_protocol_info()
+    };
+    // Sectors were plotted with sector expiration of `100` segments when history was short enough
+    // to not contribute to expiration
+    let old_expires_at = SegmentIndex::new(100);
+    // Fourth sector is plotted under stale rotation and must be fully replotted instead
+    let rotations = [2, 2, 2, 1];
+    let mut contents = vec![0; sector_metadata_file_size(rotations.len() as u64) as usize];
+    for (sector_offset, rotation) in rotations.into_iter().enumerate() {
+        contents[sector_metadata_record_offset(sector_offset as u64) as usize..]
+            [..SECTOR_METADATA_RECORD_SIZE]
+            .copy_from_slice(
+                &SectorMetadataRecord {
+                    sector_metadata: SectorMetadata {
+                        total_pieces: farmer_protocol_info.total_pieces,
+                        expires_at: old_expires_at,
+                    },
+                    plotted_at_slot: Some(1),
+                    rotation,
+                    checksum: Some([42; SECTOR_CHECKSUM_SIZE]),
+                }
+                .encode(),
+            );
+    }
+    // Third sector was interrupted while being replotted
+    contents[sector_metadata_record_offset(2) as usize..][..SECTOR_METADATA_RECORD_SIZE].fill(0);
+
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().join("sector_metadata.bin");
+    fs::write(&path, &contents).unwrap();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .unwrap();
+    // Rebound records are written through to the file without memory mapping
+    let mut sector_metadata_file =
+        MetadataFileMut::open(StorageBackend::Network, &file, contents.len()).unwrap();
+
+    let sector_locks = SectorLocks::default();
+    // Nothing to do while sector expiration stays the same
+    let unchanged_protocol_info = FarmerProtocolInfo {
+        sector_expiration: old_expires_at,
+        ..farmer_protocol_info
+    };
+    assert_eq!(
+        rebind_sector_expirations(
+            &mut sector_metadata_file,
+            4,
+            &sector_locks,
+            10,
+            &unchanged_protocol_info
+        )
+        .unwrap(),
+        0
+    );
+    assert_eq!(fs::read(&path).unwrap(), contents);
+
+    assert_eq!(
+        rebind_sector_expirations(
+            &mut sector_metadata_file,
+            4,
+            &sector_locks,
+            10,
+            &farmer_protocol_info
+        )
+        .unwrap(),
+        2
+    );
+    let contents = fs::read(&path).unwrap();
+    assert_eq!(&*sector_metadata_file, contents.as_slice());
+    for sector_offset in 0..2 {
+        let record = read_sector_metadata_record(&contents, sector_offset).unwrap();
+        assert_eq!(
+            record,
+            SectorMetadataRecord {
+                sector_metadata: SectorMetadata {
+                    total_pieces: farmer_protocol_info.total_pieces,
+                    expires_at: farmer_protocol_info.sector_expiration,
+                },
+                plotted_at_slot: Some(10),
+                rotation: 2,
+                checksum: Some([42; SECTOR_CHECKSUM_SIZE]),
+            }
+        );
+    }
+    assert!(read_sector_metadata_record(&contents, 2).is_err());
+    assert_eq!(
+        read_sector_metadata_record(&contents, 3)
+            .unwrap()
+            .sector_metadata
+            .expires_at,
+        old_expires_at
+    );
+    // Sectors are not locked afterwards
+    assert!(sector_locks.try_read(0).is_some());
+}
+
+#[test]
+fn thread_names() {
+    let public_key = PublicKey::from([0x3f; 32]);
+    assert_eq!(thread_name(&public_key, "plotting"), "plot-3f3f-plotting");
+    assert_eq!(thread_name(&public_key, "farming"), "plot-3f3f-farming");
+}
+
+#[test]
+fn sector_panic_is_reported() {
+    let handlers = Handlers::default();
+    let reported_errors = Arc::new(Mutex::new(Vec::new()));
+    let _handler_id = handlers.farming_error.add(Arc::new({
+        let reported_errors = Arc::clone(&reported_errors);
+
+        move |error: &FarmingError| {
+            if let FarmingError::Panicked {
+                sector_index,
+                message,
+            } = error
+            {
+                reported_errors
+                    .lock()
+                    .push((*sector_index, message.clone()));
+            }
+        }
+    }));
+
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::new(7);
+    let maybe_plotting_result = catch_sector_panic(sector_index, &handlers, || {
+        block_on(plot_sector(
+            &public_key,
+            sector_index,
+            &PanickingPieceReceiver,
+            &AtomicBool::new(false),
+            &farmer_protocol_info(),
+            SequentialSectorDestination::new(io::sink()),
+            io::sink(),
+        ))
+    });
+    assert!(maybe_plotting_result.is_none());
+    assert_eq!(
+        *reported_errors.lock(),
+        vec![(sector_index, "Piece receiver panicked".to_string())]
+    );
+
+    // Panic didn't propagate, the same thread keeps processing sectors and nothing is reported
+    // for sectors processed without panics
+    assert_eq!(
+        catch_sector_panic(SectorIndex::new(8), &handlers, || 42),
+        Some(42)
+    );
+    assert_eq!(reported_errors.lock().len(), 1);
+}
+
+#[test]
+fn plot_files_sync_all() {
+    let directory = TempDir::new().unwrap();
+    let open = |name: &str| {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(directory.path().join(name))
+            .unwrap();
+        file.set_len(4096).unwrap();
+        file
+    };
+    let plot_files = PlotFiles {
+        plot_file: Arc::new(open("plot.bin")),
+        sector_metadata_file: open("sector_metadata.bin"),
+        metadata_file: open("metadata.bin"),
+    };
+
+    // Plotting writes through memory mappings and positional writes, neither is synced right away
+    {
+        let mut plot_mmap = unsafe { MmapMut::map_mut(&*plot_files.plot_file).unwrap() };
+        plot_mmap[..4].copy_from_slice(&[1, 2, 3, 4]);
+        let mut sector_metadata_mmap =
+            unsafe { MmapMut::map_mut(&plot_files.sector_metadata_file).unwrap() };
+        sector_metadata_mmap[..4].copy_from_slice(&[5, 6, 7, 8]);
+        plot_files
+            .metadata_file
+            .write_all_at(&[9, 10, 11, 12], 0)
+            .unwrap();
+
+        plot_files.sync_all().unwrap();
+    }
+    drop(plot_files);
+
+    // Files are opened from scratch with cold memory mappings
+    for (name, expected) in [
+        ("plot.bin", [1, 2, 3, 4]),
+        ("sector_metadata.bin", [5, 6, 7, 8]),
+        ("metadata.bin", [9, 10, 11, 12]),
+    ] {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(directory.path().join(name))
+            .unwrap();
+        let mmap = unsafe { Mmap::map(&file).unwrap() };
+        assert_eq!(&mmap[..4], &expected, "Contents of {name} didn't survive");
+    }
+}
@@ -0,0 +1,157 @@
+//! Golden test vectors for data farmer stores on disk.
+//!
+//! Any change of below encodings makes existing plots unreadable, so these tests must only be
+//! updated deliberately, together with migration of existing plots.
+
+use crate::single_disk_plot::sector_metadata::SectorMetadataFileHeader;
+use crate::single_disk_plot::{
+    PlotMetadataHeader, PlotProtocolInfo, SectorMetadata, SingleDiskPlotId, SingleDiskPlotInfo,
+};
+use parity_scale_codec::{Decode, Encode};
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use subspace_core_primitives::crypto::kzg::KzgParametersId;
+use subspace_core_primitives::{PublicKey, SectorIndex, SegmentIndex};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+/// SCALE encoding of [`farmer_protocol_info()`]
+const FARMER_PROTOCOL_INFO_GOLDEN: &str = "\
+    000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\
+    000f0000\
+    00800700\
+    0001000000000000\
+    1400\
+    6400000000000000";
+
+/// JSON encoding of [`single_disk_plot_info()`]
+const SINGLE_DISK_PLOT_INFO_GOLDEN: &str = "{\"v0\":{\
+    \"id\":\"01GH8B3K2Q9Y7C4V5N6M8P0R1S\",\
+    \"genesisHash\":\"000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\",\
+    \"publicKey\":\"202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f\",\
+    \"firstSectorIndex\":100,\
+    \"allocatedSpace\":1073741824\
+    }}";
+
+/// SCALE encoding of [`PlotMetadataHeader`] with version 1 and 3 sectors
+const PLOT_METADATA_HEADER_GOLDEN: &str = "010300000000000000";
+
+/// SCALE encoding of [`SectorMetadata`] with 256 total pieces expiring at segment 5
+const SECTOR_METADATA_GOLDEN: &str = "00010000000000000500000000000000";
+
+/// SCALE encoding of [`SectorMetadataFileHeader`] with version 0 and 64 bytes records
+const SECTOR_METADATA_FILE_HEADER_GOLDEN: &str = "0040000000";
+
+fn farmer_protocol_info() -> FarmerProtocolInfo {
+    FarmerProtocolInfo {
+        genesis_hash: core::array::from_fn(|index| index as u8),
+        record_size: NonZeroU32::new(3840).unwrap(),
+        recorded_history_segment_size: 491520,
+        total_pieces: NonZeroU64::new(256).unwrap(),
+        space_l: NonZeroU16::new(20).unwrap(),
+        sector_expiration: SegmentIndex::new(100),
+        rotation: 0,
+    }
+}
+
+fn single_disk_plot_info() -> SingleDiskPlotInfo {
+    SingleDiskPlotInfo::new(
+        SingleDiskPlotId::Ulid("01GH8B3K2Q9Y7C4V5N6M8P0R1S".parse().unwrap()),
+        farmer_protocol_info().genesis_hash,
+        PublicKey::from(core::array::from_fn::<u8, 32, _>(|index| {
+            0x20 + index as u8
+        })),
+        SectorIndex::new(100),
+        1024 * 1024 * 1024,
+    )
+}
+
+/// Decode golden SCALE encoding, check that it re-encodes into exactly the same bytes and return
+/// decoded value
+fn decode_golden<T>(golden: &str) -> T
+where
+    T: Encode + Decode,
+{
+    let golden = hex::decode(golden).unwrap();
+    let mut input = golden.as_slice();
+    let decoded = T::decode(&mut input).unwrap();
+    assert!(input.is_empty(), "Golden vector must be decoded fully");
+    assert_eq!(decoded.encode(), golden);
+
+    decoded
+}
+
+#[test]
+fn farmer_protocol_info_encoding() {
+    assert_eq!(
+        decode_golden::<FarmerProtocolInfo>(FARMER_PROTOCOL_INFO_GOLDEN),
+        farmer_protocol_info()
+    );
+}
+
+#[test]
+fn plot_protocol_info_encoding() {
+    // V0 is a version byte followed by farmer protocol info
+    let plot_protocol_info_v0 =
+        decode_golden::<PlotProtocolInfo>(&format!("00{FARMER_PROTOCOL_INFO_GOLDEN}"));
+    assert_eq!(
+        plot_protocol_info_v0.kzg_parameters_id(),
+        KzgParametersId::TEST
+    );
+    assert_eq!(
+        FarmerProtocolInfo::from(plot_protocol_info_v0),
+        farmer_protocol_info()
+    );
+
+    // V1 additionally stores identifier of KZG parameters at the end
+    let plot_protocol_info_v1 =
+        decode_golden::<PlotProtocolInfo>(&format!("01{FARMER_PROTOCOL_INFO_GOLDEN}0100"));
+    assert_eq!(
+        plot_protocol_info_v1,
+        PlotProtocolInfo::new(farmer_protocol_info(), KzgParametersId(1))
+    );
+}
+
+#[test]
+fn plot_metadata_header_encoding() {
+    let metadata_header = decode_golden::<PlotMetadataHeader>(PLOT_METADATA_HEADER_GOLDEN);
+    assert_eq!(metadata_header.version, 1);
+    assert_eq!(metadata_header.sector_count, 3);
+    assert_eq!(
+        PlotMetadataHeader::encoded_size(),
+        PLOT_METADATA_HEADER_GOLDEN.len() / 2
+    );
+}
+
+#[test]
+fn sector_metadata_encoding() {
+    let sector_metadata = decode_golden::<SectorMetadata>(SECTOR_METADATA_GOLDEN);
+    assert_eq!(sector_metadata.total_pieces.get(), 256);
+    assert_eq!(sector_metadata.expires_at, 5);
+    assert_eq!(
+        SectorMetadata::encoded_size(),
+        SECTOR_METADATA_GOLDEN.len() / 2
+    );
+}
+
+#[test]
+fn sector_metadata_file_header_encoding() {
+    let header = decode_golden::<SectorMetadataFileHeader>(SECTOR_METADATA_FILE_HEADER_GOLDEN);
+    assert_eq!(header.version, 0);
+    assert_eq!(header.record_size, 64);
+}
+
+#[test]
+fn single_disk_plot_info_encoding() {
+    // Plot info is stored as JSON rather than SCALE
+    let decoded = serde_json::from_str::<SingleDiskPlotInfo>(SINGLE_DISK_PLOT_INFO_GOLDEN).unwrap();
+    let expected = single_disk_plot_info();
+    assert_eq!(decoded.id(), expected.id());
+    assert_eq!(decoded.genesis_hash(), expected.genesis_hash());
+    assert_eq!(decoded.public_key(), expected.public_key());
+    assert_eq!(decoded.first_sector_index(), expected.first_sector_index());
+    assert_eq!(decoded.allocated_space(), expected.allocated_space());
+
+    assert_eq!(
+        serde_json::to_string(&expected).unwrap(),
+        SINGLE_DISK_PLOT_INFO_GOLDEN
+    );
+}
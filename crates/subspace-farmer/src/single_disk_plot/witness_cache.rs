@@ -0,0 +1,161 @@
+//! Cache of piece witnesses that were already verified during plotting.
+//!
+//! Every piece is verified against records root of its segment before it is plotted, which is a
+//! KZG pairing check and dominates CPU usage of plotting on small networks. With small
+//! `total_pieces` the same pieces are plotted into many sectors and verified again every time.
+//! Cache remembers witnesses that were verified successfully by record hash, so the same piece
+//! only needs to be hashed to be accepted again. Cache is limited by the number of entries and hit
+//! rate is tracked.
+
+#[cfg(test)]
+mod tests;
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use subspace_archiving::archiver::is_piece_record_hash_valid;
+use subspace_core_primitives::crypto::blake2b_256_254_hash;
+use subspace_core_primitives::crypto::kzg::{Kzg, Witness};
+use subspace_core_primitives::{Blake2b256Hash, RecordsRoot, WITNESS_SIZE};
+
+/// Statistics of witness cache usage
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct WitnessCacheStats {
+    /// Number of verifications that found witness in cache
+    pub hits: u64,
+    /// Number of verifications that had to verify witness
+    pub misses: u64,
+}
+
+impl WitnessCacheStats {
+    /// Share of verifications that found witness in cache, `None` if cache wasn't used yet
+    pub fn hit_rate(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        (total > 0).then(|| self.hits as f64 / total as f64)
+    }
+}
+
+/// Witness that was verified successfully against records root at specific position
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct VerifiedWitness {
+    records_root: RecordsRoot,
+    position: u32,
+    witness: [u8; WITNESS_SIZE as usize],
+}
+
+impl VerifiedWitness {
+    /// Whether witness is the same as `other`, records roots are compared in constant time
+    fn matches(&self, other: &Self) -> bool {
+        // Non-short-circuiting `&`, so that records roots are always compared
+        self.records_root.ct_eq(&other.records_root)
+            & (self.position == other.position)
+            & (self.witness == other.witness)
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// Verified witnesses by record hash, `None` when cache is disabled
+    witnesses: Option<LruCache<Blake2b256Hash, VerifiedWitness>>,
+    stats: WitnessCacheStats,
+}
+
+/// LRU cache of verified piece witnesses keyed by record hash, can be cheaply cloned and shared
+/// between piece receivers
+#[derive(Debug, Clone)]
+pub struct WitnessCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl WitnessCache {
+    /// Create cache that will hold at most `max_entries` witnesses, `0` disables the cache
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                witnesses: (max_entries > 0).then(|| LruCache::new(max_entries)),
+                stats: WitnessCacheStats::default(),
+            })),
+        }
+    }
+
+    /// Whether cache is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.inner.lock().witnesses.is_some()
+    }
+
+    /// Same as [`subspace_archiving::archiver::is_piece_valid()`], but witness is not verified
+    /// again if the same witness was verified for the same record, records root and position
+    /// before
+    pub fn is_piece_valid(
+        &self,
+        kzg: &Kzg,
+        num_pieces_in_segment: u32,
+        piece: &[u8],
+        records_root: RecordsRoot,
+        position: u32,
+        record_size: u32,
+    ) -> bool {
+        if piece.len() != (record_size + WITNESS_SIZE) as usize {
+            return false;
+        }
+
+        let (record, witness_bytes) = piece.split_at(record_size as usize);
+        let witness_bytes: [u8; WITNESS_SIZE as usize] = match witness_bytes.try_into() {
+            Ok(witness_bytes) => witness_bytes,
+            Err(_) => {
+                return false;
+            }
+        };
+        let record_hash = blake2b_256_254_hash(record);
+        let verified_witness = VerifiedWitness {
+            records_root,
+            position,
+            witness: witness_bytes,
+        };
+
+        {
+            let mut inner = self.inner.lock();
+            let inner = &mut *inner;
+            if let Some(witnesses) = &mut inner.witnesses {
+                if witnesses
+                    .get(&record_hash)
+                    .map_or(false, |cached| cached.matches(&verified_witness))
+                {
+                    inner.stats.hits += 1;
+                    return true;
+                }
+                inner.stats.misses += 1;
+            }
+        }
+
+        // Lock is not held during verification, concurrent verifications of the same piece will
+        // both verify it
+        let witness = match Witness::try_from_bytes(&witness_bytes) {
+            Ok(witness) => witness,
+            Err(_) => {
+                return false;
+            }
+        };
+        if !is_piece_record_hash_valid(
+            kzg,
+            num_pieces_in_segment,
+            &record_hash,
+            &records_root,
+            &witness,
+            position,
+        ) {
+            return false;
+        }
+
+        if let Some(witnesses) = &mut self.inner.lock().witnesses {
+            witnesses.put(record_hash, verified_witness);
+        }
+
+        true
+    }
+
+    /// Statistics of cache usage so far
+    pub fn stats(&self) -> WitnessCacheStats {
+        self.inner.lock().stats
+    }
+}
@@ -0,0 +1,80 @@
+use crate::single_disk_plot::witness_cache::{WitnessCache, WitnessCacheStats};
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{PIECES_IN_SEGMENT, RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE};
+
+#[test]
+fn witness_cache() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let archived_segment = Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone())
+        .unwrap()
+        .add_block(
+            (0..RECORDED_HISTORY_SEGMENT_SIZE)
+                .map(|_| rand::random())
+                .collect(),
+            Default::default(),
+        )
+        .into_iter()
+        .next()
+        .unwrap();
+    let records_root = archived_segment.root_block.records_root();
+    let pieces = archived_segment.pieces.as_pieces().collect::<Vec<_>>();
+
+    let witness_cache = WitnessCache::new(1);
+    assert!(witness_cache.is_enabled());
+    assert_eq!(witness_cache.stats().hit_rate(), None);
+
+    let is_piece_valid = |piece: &[u8], position| {
+        witness_cache.is_piece_valid(
+            &kzg,
+            PIECES_IN_SEGMENT,
+            piece,
+            records_root,
+            position,
+            RECORD_SIZE,
+        )
+    };
+
+    // Verified and cached
+    assert!(is_piece_valid(pieces[0], 0));
+    // Cached witness is accepted without verification
+    assert!(is_piece_valid(pieces[0], 0));
+    assert_eq!(
+        witness_cache.stats(),
+        WitnessCacheStats { hits: 1, misses: 1 }
+    );
+
+    // Same record at a different position is verified again and rejected
+    assert!(!is_piece_valid(pieces[0], 1));
+    // Same record with a different witness is verified again and rejected
+    let mut tampered_piece = pieces[0].to_vec();
+    tampered_piece[RECORD_SIZE as usize..].copy_from_slice(&pieces[1][RECORD_SIZE as usize..]);
+    assert!(!is_piece_valid(&tampered_piece, 0));
+    assert_eq!(
+        witness_cache.stats(),
+        WitnessCacheStats { hits: 1, misses: 3 }
+    );
+
+    // Least recently used witness is evicted
+    assert!(is_piece_valid(pieces[1], 1));
+    assert!(is_piece_valid(pieces[0], 0));
+    assert_eq!(
+        witness_cache.stats(),
+        WitnessCacheStats { hits: 1, misses: 5 }
+    );
+    assert_eq!(witness_cache.stats().hit_rate(), Some(1.0 / 6.0));
+
+    // Disabled cache still verifies pieces, but doesn't track anything
+    let witness_cache = WitnessCache::new(0);
+    assert!(!witness_cache.is_enabled());
+    assert!(witness_cache.is_piece_valid(
+        &kzg,
+        PIECES_IN_SEGMENT,
+        pieces[0],
+        records_root,
+        0,
+        RECORD_SIZE,
+    ));
+    assert_eq!(witness_cache.stats(), WitnessCacheStats::default());
+}
@@ -0,0 +1,91 @@
+//! Utilities for tests of farmer and crates that build on top of it.
+
+#[cfg(test)]
+pub(crate) mod fixtures;
+#[cfg(test)]
+mod tests;
+
+use crate::single_disk_plot::farming::PieceStore;
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::error::Error;
+use subspace_archiving::archiver::ArchivedSegment;
+use subspace_core_primitives::{Piece, PieceIndex};
+
+/// Piece receiver that serves pieces from memory, pieces that were not inserted are reported as
+/// not found.
+///
+/// Unlike returning the same piece for every index, pieces of archived segments are distinct and
+/// can be verified against records roots of corresponding segments.
+#[derive(Debug, Default, Clone)]
+pub struct MapPieceReceiver {
+    pieces: HashMap<PieceIndex, Piece>,
+}
+
+#[async_trait]
+impl PieceReceiver for MapPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(self.pieces.get(&piece_index).cloned())
+    }
+}
+
+impl PieceStore for MapPieceReceiver {
+    fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(self.pieces.get(&piece_index).cloned())
+    }
+}
+
+impl MapPieceReceiver {
+    /// Create empty piece receiver
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create piece receiver with pieces of all provided archived segments
+    pub fn from_archived_segments<'a, I>(archived_segments: I) -> Self
+    where
+        I: IntoIterator<Item = &'a ArchivedSegment>,
+    {
+        let mut piece_receiver = Self::new();
+        for archived_segment in archived_segments {
+            piece_receiver.insert_segment(archived_segment);
+        }
+        piece_receiver
+    }
+
+    /// Insert a single piece, returns previous piece with the same index if there was one
+    pub fn insert(&mut self, piece_index: PieceIndex, piece: Piece) -> Option<Piece> {
+        self.pieces.insert(piece_index, piece)
+    }
+
+    /// Insert all pieces of archived segment at their indexes in archived history
+    pub fn insert_segment(&mut self, archived_segment: &ArchivedSegment) {
+        let pieces_in_segment = archived_segment.pieces.count() as PieceIndex;
+        let first_piece_index =
+            archived_segment.root_block.segment_index().get() * pieces_in_segment;
+
+        for (piece_index, piece) in (first_piece_index..).zip(archived_segment.pieces.as_pieces()) {
+            self.pieces.insert(
+                piece_index,
+                Piece::try_from(piece).expect("Archived segment contains valid pieces; qed"),
+            );
+        }
+    }
+
+    /// Number of pieces available
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    /// Whether there are no pieces available
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+}
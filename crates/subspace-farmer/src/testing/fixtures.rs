@@ -0,0 +1,260 @@
+//! Fixtures shared by tests of different modules of the farmer.
+
+use crate::rpc_client::{Error as RpcError, RpcClient};
+use crate::single_disk_plot::farming::PieceStore;
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt};
+use parking_lot::Mutex;
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use subspace_archiving::archiver::{ArchivedSegment, Archiver};
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    Piece, PieceIndex, RecordsRoot, SegmentIndex, PIECES_IN_SEGMENT, PIECE_SIZE,
+    RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
+};
+use subspace_rpc_primitives::{
+    FarmerProtocolInfo, RewardSignatureResponse, RewardSigningInfo, SegmentPieces, SlotInfo,
+    SolutionResponse,
+};
+
+/// Protocol info of history with `1024` pieces and the smallest sectors that still contain a few
+/// pieces, tests override fields they care about
+pub(crate) fn farmer_protocol_info() -> FarmerProtocolInfo {
+    FarmerProtocolInfo {
+        genesis_hash: Default::default(),
+        record_size: NonZeroU32::new(RECORD_SIZE).unwrap(),
+        recorded_history_segment_size: RECORDED_HISTORY_SEGMENT_SIZE,
+        total_pieces: NonZeroU64::new(1024).unwrap(),
+        space_l: NonZeroU16::new(16).unwrap(),
+        sector_expiration: SegmentIndex::ONE,
+        rotation: 0,
+    }
+}
+
+/// Piece that is derived from piece index only, it is not valid for any records root, use
+/// [`archived_segment()`] with [`MapPieceReceiver`](crate::testing::MapPieceReceiver) when pieces
+/// need to be verified
+pub(crate) fn piece(piece_index: PieceIndex) -> Piece {
+    let mut piece = vec![0u8; PIECE_SIZE];
+    StdRng::seed_from_u64(piece_index).fill(piece.as_mut_slice());
+
+    Piece::try_from(piece.as_slice()).unwrap()
+}
+
+/// Piece receiver and piece store that return [`piece()`] for every piece index
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct DerivedPieceReceiver;
+
+#[async_trait]
+impl PieceReceiver for DerivedPieceReceiver {
+    async fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Some(piece(piece_index)))
+    }
+}
+
+impl PieceStore for DerivedPieceReceiver {
+    fn get_piece(
+        &self,
+        piece_index: PieceIndex,
+    ) -> Result<Option<Piece>, Box<dyn Error + Send + Sync + 'static>> {
+        Ok(Some(piece(piece_index)))
+    }
+}
+
+/// The first archived segment of a history made of blocks with random contents
+pub(crate) fn archived_segment(kzg: &Kzg) -> ArchivedSegment {
+    archived_segments(kzg, 1).remove(0)
+}
+
+/// The first `count` archived segments of a history made of blocks with random contents, every
+/// segment extends the chain of root blocks of the previous one
+pub(crate) fn archived_segments(kzg: &Kzg, count: usize) -> Vec<ArchivedSegment> {
+    let mut archiver =
+        Archiver::new(RECORD_SIZE, RECORDED_HISTORY_SEGMENT_SIZE, kzg.clone()).unwrap();
+
+    let mut archived_segments = Vec::with_capacity(count);
+    while archived_segments.len() < count {
+        let mut block = vec![0u8; RECORDED_HISTORY_SEGMENT_SIZE as usize];
+        thread_rng().fill(block.as_mut_slice());
+        archived_segments.extend(archiver.add_block(block, Default::default()));
+    }
+    archived_segments.truncate(count);
+
+    archived_segments
+}
+
+/// RPC client of a node that knows history made of provided archived segments.
+///
+/// Records roots, pieces and segment pieces of that history are served, archived segments provided
+/// with [`Self::with_subscription()`] are streamed to the only subscriber. Node never has slots or
+/// reward signing requests and accepts every solution and signature. Counters and fault injection
+/// are shared between clones.
+#[derive(Clone)]
+pub(crate) struct TestRpcClient {
+    /// Records root and pieces of every segment known to the node
+    pub(crate) segments: Arc<HashMap<SegmentIndex, (RecordsRoot, Vec<Piece>)>>,
+    archived_segments: Arc<Mutex<Option<Vec<ArchivedSegment>>>>,
+    /// Number of archived segments pulled from subscription
+    pub(crate) pulled: Arc<AtomicUsize>,
+    /// Number of segment pieces requests
+    pub(crate) requests: Arc<AtomicUsize>,
+    /// Number of upcoming segment pieces responses whose first piece is corrupted
+    pub(crate) invalid_responses: Arc<AtomicUsize>,
+}
+
+impl TestRpcClient {
+    /// Node that knows `archived_segments`, subscription to archived segments is empty
+    pub(crate) fn new(archived_segments: &[ArchivedSegment]) -> Self {
+        let segments = archived_segments
+            .iter()
+            .map(|archived_segment| {
+                let pieces = archived_segment
+                    .pieces
+                    .as_pieces()
+                    .map(|piece| Piece::try_from(piece).unwrap())
+                    .collect();
+
+                (
+                    archived_segment.root_block.segment_index(),
+                    (archived_segment.root_block.records_root(), pieces),
+                )
+            })
+            .collect();
+
+        Self {
+            segments: Arc::new(segments),
+            archived_segments: Arc::new(Mutex::new(Some(Vec::new()))),
+            pulled: Arc::default(),
+            requests: Arc::default(),
+            invalid_responses: Arc::default(),
+        }
+    }
+
+    /// Stream `archived_segments` to the subscriber, they don't need to be known to the node
+    pub(crate) fn with_subscription(self, archived_segments: Vec<ArchivedSegment>) -> Self {
+        self.archived_segments.lock().replace(archived_segments);
+        self
+    }
+}
+
+#[async_trait]
+impl RpcClient for TestRpcClient {
+    async fn farmer_protocol_info(&self) -> Result<FarmerProtocolInfo, RpcError> {
+        Ok(farmer_protocol_info())
+    }
+
+    async fn subscribe_slot_info(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = SlotInfo> + Send + 'static>>, RpcError> {
+        Ok(Box::pin(stream::pending()))
+    }
+
+    async fn submit_solution_response(
+        &self,
+        _solution_response: SolutionResponse,
+    ) -> Result<(), RpcError> {
+        Ok(())
+    }
+
+    async fn subscribe_reward_signing(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = RewardSigningInfo> + Send + 'static>>, RpcError> {
+        Ok(Box::pin(stream::pending()))
+    }
+
+    async fn submit_reward_signature(
+        &self,
+        _reward_signature: RewardSignatureResponse,
+    ) -> Result<(), RpcError> {
+        Ok(())
+    }
+
+    async fn subscribe_archived_segments(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = ArchivedSegment> + Send + 'static>>, RpcError> {
+        let archived_segments = self
+            .archived_segments
+            .lock()
+            .take()
+            .ok_or("Test RPC client supports only one subscription to archived segments")?;
+        let pulled = Arc::clone(&self.pulled);
+
+        Ok(Box::pin(stream::iter(archived_segments).inspect(
+            move |_archived_segment| {
+                pulled.fetch_add(1, Ordering::SeqCst);
+            },
+        )))
+    }
+
+    async fn records_roots(
+        &self,
+        segment_indexes: Vec<SegmentIndex>,
+    ) -> Result<Vec<Option<RecordsRoot>>, RpcError> {
+        Ok(segment_indexes
+            .into_iter()
+            .map(|segment_index| {
+                self.segments
+                    .get(&segment_index)
+                    .map(|(records_root, _pieces)| *records_root)
+            })
+            .collect())
+    }
+
+    async fn get_piece(&self, piece_index: PieceIndex) -> Result<Option<Piece>, RpcError> {
+        let pieces_in_segment = u64::from(PIECES_IN_SEGMENT);
+
+        Ok(self
+            .segments
+            .get(&SegmentIndex::new(piece_index / pieces_in_segment))
+            .and_then(|(_records_root, pieces)| {
+                pieces.get((piece_index % pieces_in_segment) as usize)
+            })
+            .cloned())
+    }
+
+    async fn segment_pieces(
+        &self,
+        segment_index: SegmentIndex,
+        first_position: u32,
+        limit: u32,
+    ) -> Result<Option<SegmentPieces>, RpcError> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let invalid = self
+            .invalid_responses
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |invalid_responses| {
+                invalid_responses.checked_sub(1)
+            })
+            .is_ok();
+
+        Ok(self
+            .segments
+            .get(&segment_index)
+            .map(|(records_root, pieces)| {
+                let mut pieces = pieces
+                    .iter()
+                    .skip(first_position as usize)
+                    .take(limit as usize)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if invalid {
+                    pieces[0][0] ^= 1;
+                }
+
+                SegmentPieces {
+                    records_root: *records_root,
+                    first_position,
+                    pieces,
+                }
+            }))
+    }
+}
@@ -0,0 +1,98 @@
+use crate::single_disk_plot::farming::audit_sector;
+use crate::single_disk_plot::piece_receiver::PieceReceiver;
+use crate::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use crate::testing::fixtures::{archived_segment, farmer_protocol_info};
+use crate::testing::MapPieceReceiver;
+use futures::executor::block_on;
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::num::NonZeroU64;
+use std::sync::atomic::AtomicBool;
+use subspace_archiving::archiver::is_piece_valid;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::{Kzg, KzgParametersId};
+use subspace_core_primitives::{
+    PieceIndex, PublicKey, SectorIndex, SolutionRange, PIECES_IN_SEGMENT, RECORD_SIZE,
+};
+use subspace_rpc_primitives::FarmerProtocolInfo;
+
+#[test]
+fn plot_archived_segment() {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let archived_segment = archived_segment(&kzg);
+    let records_root = archived_segment.root_block.records_root();
+
+    let piece_receiver = MapPieceReceiver::from_archived_segments([&archived_segment]);
+    assert_eq!(piece_receiver.len(), PIECES_IN_SEGMENT as usize);
+
+    // Unknown pieces are not found
+    assert!(
+        block_on(piece_receiver.get_piece(PieceIndex::from(PIECES_IN_SEGMENT)))
+            .unwrap()
+            .is_none()
+    );
+
+    let farmer_protocol_info = FarmerProtocolInfo {
+        total_pieces: NonZeroU64::new(u64::from(PIECES_IN_SEGMENT)).unwrap(),
+        ..farmer_protocol_info()
+    };
+    let public_key = PublicKey::from(rand::random::<[u8; 32]>());
+    let sector_index = SectorIndex::ZERO;
+
+    let mut sector = Vec::new();
+    let plotted_sector = block_on(plot_sector(
+        &public_key,
+        sector_index,
+        &piece_receiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut sector),
+        std::io::sink(),
+    ))
+    .unwrap();
+
+    // Every plotted piece is distinct and valid for its position in archived history
+    let mut plotted_pieces = HashSet::new();
+    for piece_index in plotted_sector.piece_indexes.iter().copied() {
+        let piece = block_on(piece_receiver.get_piece(piece_index))
+            .unwrap()
+            .unwrap();
+        assert!(is_piece_valid(
+            &kzg,
+            PIECES_IN_SEGMENT,
+            &piece,
+            records_root,
+            piece_index as u32,
+            RECORD_SIZE,
+        ));
+        plotted_pieces.insert((piece_index, piece.to_vec()));
+    }
+    let distinct_piece_indexes = plotted_sector
+        .piece_indexes
+        .iter()
+        .collect::<HashSet<_>>()
+        .len();
+    assert_eq!(
+        plotted_pieces
+            .iter()
+            .map(|(_piece_index, piece)| piece)
+            .collect::<HashSet<_>>()
+            .len(),
+        distinct_piece_indexes
+    );
+
+    // Sector is eligible for any challenge with the widest solution range
+    let eligible_sector = audit_sector(
+        &public_key,
+        sector_index,
+        &farmer_protocol_info,
+        KzgParametersId::TEST,
+        &kzg,
+        &rand::random(),
+        SolutionRange::MAX,
+        Cursor::new(&sector),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(eligible_sector.sector_index, sector_index);
+}
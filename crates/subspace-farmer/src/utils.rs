@@ -53,3 +53,19 @@ impl Deref for JoinOnDrop {
         self.0.as_ref().expect("Only dropped in Drop impl; qed")
     }
 }
+
+/// Lower scheduling priority of the current thread, used for background work that must not compete
+/// with latency-sensitive auditing
+pub(crate) fn lower_thread_priority() {
+    #[cfg(target_os = "linux")]
+    {
+        // On Linux niceness is per-thread, so using thread ID here only affects current thread
+        let thread_id = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, thread_id, 10) } != 0 {
+            tracing::debug!(
+                error = %std::io::Error::last_os_error(),
+                "Failed to lower thread priority"
+            );
+        }
+    }
+}
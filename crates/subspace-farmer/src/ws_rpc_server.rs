@@ -1,8 +1,12 @@
+use crate::memory_budget::{MemoryBudget, MemoryUsage};
 use crate::object_mappings::{ObjectMappingError, ObjectMappings};
+use crate::piece_serving::{PeerServingStats, PieceServer, PieceServingLimits};
 use jsonrpsee::core::error::Error;
 use jsonrpsee::proc_macros::rpc;
 use parity_scale_codec::{Compact, CompactLen, Decode, Encode};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 use subspace_archiving::archiver::{Segment, SegmentItem};
@@ -133,6 +137,26 @@ pub struct Object {
     data: Vec<u8>,
 }
 
+/// Memory usage of the farmer along with configured budget
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryBudgetInfo {
+    /// Memory budget in bytes, `None` if not limited
+    pub limit: Option<u64>,
+    /// Current memory usage per category
+    pub usage: MemoryUsage,
+}
+
+/// Piece serving limits along with statistics of peers
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PieceServingInfo {
+    /// Current limits
+    pub limits: PieceServingLimits,
+    /// Serving statistics per peer ID
+    pub peers: HashMap<String, PeerServingStats>,
+}
+
 #[rpc(server, client)]
 pub trait Rpc {
     /// Get single piece by its index
@@ -142,6 +166,18 @@ pub trait Rpc {
     /// Find object by its ID
     #[method(name = "findObject", blocking)]
     fn find_object(&self, object_id: HexBlake2b256Hash) -> Result<Option<Object>, Error>;
+
+    /// Get current memory usage and memory budget
+    #[method(name = "getMemoryUsage")]
+    fn get_memory_usage(&self) -> Result<MemoryBudgetInfo, Error>;
+
+    /// Get piece serving limits and bytes served to every peer
+    #[method(name = "getPieceServing")]
+    fn get_piece_serving(&self) -> Result<PieceServingInfo, Error>;
+
+    /// Change piece serving limits
+    #[method(name = "setPieceServingLimits")]
+    fn set_piece_serving_limits(&self, limits: PieceServingLimits) -> Result<(), Error>;
 }
 
 /// Farmer RPC server implementation.
@@ -150,6 +186,8 @@ pub struct RpcServerImpl {
     pieces_in_segment: u32,
     piece_getter: Arc<dyn PieceGetter + Send + Sync + 'static>,
     object_mappings: Arc<Vec<ObjectMappings>>,
+    memory_budget: MemoryBudget,
+    piece_server: PieceServer,
 }
 
 impl RpcServerImpl {
@@ -158,12 +196,16 @@ impl RpcServerImpl {
         recorded_history_segment_size: u32,
         piece_getter: Arc<dyn PieceGetter + Send + Sync + 'static>,
         object_mappings: Arc<Vec<ObjectMappings>>,
+        memory_budget: MemoryBudget,
+        piece_server: PieceServer,
     ) -> Self {
         Self {
             record_size,
             pieces_in_segment: recorded_history_segment_size / record_size * 2,
             piece_getter,
             object_mappings,
+            memory_budget,
+            piece_server,
         }
     }
 
@@ -307,7 +349,7 @@ impl RpcServerImpl {
         offset: u32,
         object_id: &str,
     ) -> Result<Vec<u8>, Error> {
-        let segment_index = piece_index / u64::from(self.pieces_in_segment);
+        let segment_index = SegmentIndex::new(piece_index / u64::from(self.pieces_in_segment));
         let piece_position_in_segment = piece_index % u64::from(self.pieces_in_segment);
         let offset_in_segment =
             piece_position_in_segment * u64::from(self.record_size) + u64::from(offset);
@@ -348,7 +390,7 @@ impl RpcServerImpl {
                 segment_item => {
                     error!(
                         ?segment_item,
-                        offset_in_segment, segment_index, object_id, "Unexpected segment item",
+                        offset_in_segment, %segment_index, object_id, "Unexpected segment item",
                     );
 
                     return Err(Error::Custom(format!(
@@ -363,7 +405,7 @@ impl RpcServerImpl {
             return Ok(data);
         }
 
-        for segment_index in segment_index + 1.. {
+        for segment_index in (segment_index.get() + 1..).map(SegmentIndex::new) {
             let Segment::V0 { items } = self.read_segment(segment_index)?;
             for segment_item in items {
                 if let SegmentItem::BlockContinuation { bytes, .. } = segment_item {
@@ -389,7 +431,7 @@ impl RpcServerImpl {
 
     /// Read the whole segment by its index (just records, skipping witnesses)
     fn read_segment(&self, segment_index: SegmentIndex) -> Result<Segment, Error> {
-        let first_piece_in_segment = segment_index * SegmentIndex::from(self.pieces_in_segment);
+        let first_piece_in_segment = segment_index.get() * PieceIndex::from(self.pieces_in_segment);
         let mut segment_bytes =
             Vec::<u8>::with_capacity((self.pieces_in_segment * self.record_size) as usize);
 
@@ -400,7 +442,7 @@ impl RpcServerImpl {
 
         let segment = Segment::decode(&mut segment_bytes.as_slice()).map_err(|error| {
             error!(
-                index = segment_index,
+                index = %segment_index,
                 %error,
                 "Failed to decode segment of archival history on retrieval",
             );
@@ -481,4 +523,29 @@ impl RpcServer for RpcServerImpl {
             data,
         }))
     }
+
+    fn get_memory_usage(&self) -> Result<MemoryBudgetInfo, Error> {
+        Ok(MemoryBudgetInfo {
+            limit: self.memory_budget.limit().map(NonZeroU64::get),
+            usage: self.memory_budget.usage(),
+        })
+    }
+
+    fn get_piece_serving(&self) -> Result<PieceServingInfo, Error> {
+        Ok(PieceServingInfo {
+            limits: self.piece_server.limits(),
+            peers: self
+                .piece_server
+                .stats()
+                .into_iter()
+                .map(|(peer_id, stats)| (peer_id.to_string(), stats))
+                .collect(),
+        })
+    }
+
+    fn set_piece_serving_limits(&self, limits: PieceServingLimits) -> Result<(), Error> {
+        self.piece_server.set_limits(limits);
+
+        Ok(())
+    }
 }
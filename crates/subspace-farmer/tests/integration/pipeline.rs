@@ -0,0 +1,157 @@
+//! Whole farming pipeline from archiving of history to verification of solution by the node, every
+//! stage reports failure with its own message so that regressions are easy to localize.
+
+use futures::executor::block_on;
+use schnorrkel::{ExpansionMode, MiniSecretKey};
+use std::io::Cursor;
+use std::sync::atomic::AtomicBool;
+use subspace_archiving::archiver::Archiver;
+use subspace_core_primitives::crypto::kzg;
+use subspace_core_primitives::crypto::kzg::Kzg;
+use subspace_core_primitives::{
+    plot_sector_size, PieceIndex, PublicKey, Randomness, SectorId, SectorIndex, SegmentIndex,
+    SlotNumber, SolutionRange, RECORD_SIZE,
+};
+use subspace_farmer::single_disk_plot::farming::audit_sector;
+use subspace_farmer::single_disk_plot::plotting::{plot_sector, SectorBuffer};
+use subspace_farmer::single_disk_plot::SectorMetadata;
+use subspace_farmer::testing::MapPieceReceiver;
+use subspace_rpc_primitives::FarmerProtocolInfo;
+use subspace_solving::derive_global_challenge;
+use subspace_verification::{verify_solution, PieceCheckParams, VerifySolutionParams};
+
+// This is data + parity shards
+const PIECES_IN_SEGMENT: u32 = 8;
+// In terms of source data that can be stored in the segment, not the size after archiving
+const SEGMENT_SIZE: u32 = RECORD_SIZE * PIECES_IN_SEGMENT / 2;
+const SECTOR_INDEX: SectorIndex = SectorIndex::new(3);
+/// Audit doesn't find a chunk for some challenges, slots are tried one by one up to this one
+const MAX_SLOT: u64 = 100;
+
+fn run_pipeline(space_l: u16) {
+    let kzg = Kzg::new(kzg::test_public_parameters());
+    let keypair = MiniSecretKey::from_bytes(&[space_l as u8; 32])
+        .unwrap()
+        .expand_to_keypair(ExpansionMode::Ed25519);
+    let public_key = PublicKey::from(keypair.public.to_bytes());
+    let reward_address = PublicKey::from([2; 32]);
+    let global_randomness = Randomness::default();
+
+    // Archiving
+    let mut archiver = Archiver::new(RECORD_SIZE, SEGMENT_SIZE, kzg.clone())
+        .unwrap_or_else(|error| panic!("space_l {space_l}: failed to create archiver: {error}"));
+    let block = (0..SEGMENT_SIZE).map(|byte| byte as u8).collect::<Vec<_>>();
+    let archived_segment = archiver
+        .add_block(block, Default::default())
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| panic!("space_l {space_l}: archiving produced no segment"));
+    let records_root = archived_segment.root_block.records_root();
+
+    // Piece source
+    let piece_receiver = MapPieceReceiver::from_archived_segments([&archived_segment]);
+    assert_eq!(
+        piece_receiver.len(),
+        PIECES_IN_SEGMENT as usize,
+        "space_l {space_l}: piece source doesn't have all pieces of archived segment"
+    );
+
+    // Plotting
+    let farmer_protocol_info = FarmerProtocolInfo::builder()
+        .record_size(RECORD_SIZE)
+        .recorded_history_segment_size(SEGMENT_SIZE)
+        .total_pieces(u64::from(PIECES_IN_SEGMENT))
+        .space_l(space_l)
+        .sector_expiration(SegmentIndex::ONE)
+        .build()
+        .unwrap_or_else(|error| panic!("space_l {space_l}: invalid protocol info: {error}"));
+    let mut sector = Vec::new();
+    let mut sector_metadata = Vec::new();
+    block_on(plot_sector(
+        &public_key,
+        SECTOR_INDEX,
+        &piece_receiver,
+        &AtomicBool::new(false),
+        &farmer_protocol_info,
+        SectorBuffer::new(&mut sector),
+        &mut sector_metadata,
+    ))
+    .unwrap_or_else(|error| panic!("space_l {space_l}: plotting failed: {error}"));
+    assert_eq!(
+        sector.len() as u64,
+        plot_sector_size(farmer_protocol_info.space_l),
+        "space_l {space_l}: plotted sector has unexpected size"
+    );
+    assert_eq!(
+        sector_metadata.len(),
+        SectorMetadata::encoded_size(),
+        "space_l {space_l}: sector metadata has unexpected size"
+    );
+
+    // Auditing, any chunk is within the largest solution range
+    let (slot, eligible_sector) = (1..=MAX_SLOT)
+        .map(SlotNumber::new)
+        .find_map(|slot| {
+            audit_sector(
+                &public_key,
+                SECTOR_INDEX,
+                &farmer_protocol_info,
+                kzg.id(),
+                &kzg,
+                &derive_global_challenge(&global_randomness, slot),
+                SolutionRange::MAX,
+                Cursor::new(&sector),
+            )
+            .unwrap_or_else(|error| {
+                panic!("space_l {space_l}: audit of slot {slot} failed: {error}")
+            })
+            .map(|eligible_sector| (slot, eligible_sector))
+        })
+        .unwrap_or_else(|| panic!("space_l {space_l}: audit found no eligible chunk"));
+
+    // Proving
+    let solution = eligible_sector
+        .try_into_solution(
+            &keypair,
+            reward_address,
+            &farmer_protocol_info,
+            sector_metadata.as_slice(),
+        )
+        .unwrap_or_else(|error| panic!("space_l {space_l}: proving failed: {error}"))
+        .unwrap_or_else(|| panic!("space_l {space_l}: proving produced no solution"));
+
+    // Verification
+    let piece_index = SectorId::new(&public_key, SECTOR_INDEX)
+        .derive_piece_index(solution.piece_offset, solution.total_pieces);
+    let position = u32::try_from(piece_index % PieceIndex::from(PIECES_IN_SEGMENT)).unwrap();
+    verify_solution(
+        &solution,
+        slot,
+        VerifySolutionParams {
+            global_randomness: &global_randomness,
+            solution_range: SolutionRange::MAX,
+            piece_check_params: Some(PieceCheckParams {
+                records_root: &records_root,
+                position,
+                kzg: &kzg,
+                pieces_in_segment: PIECES_IN_SEGMENT,
+            }),
+        },
+    )
+    .unwrap_or_else(|error| panic!("space_l {space_l}: verification failed: {error:?}"));
+}
+
+#[test]
+fn pipeline_space_l_16() {
+    run_pipeline(16);
+}
+
+#[test]
+fn pipeline_space_l_18() {
+    run_pipeline(18);
+}
+
+#[test]
+fn pipeline_space_l_20() {
+    run_pipeline(20);
+}
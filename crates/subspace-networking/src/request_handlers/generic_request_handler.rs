@@ -16,8 +16,10 @@
 
 use crate::request_responses::{IncomingRequest, OutgoingResponse, ProtocolConfig, RequestHandler};
 use async_trait::async_trait;
-use futures::channel::mpsc;
+use futures::channel::{mpsc, oneshot};
+use futures::future::BoxFuture;
 use futures::prelude::*;
+use futures::stream::FuturesUnordered;
 use libp2p::PeerId;
 use parity_scale_codec::{Decode, Encode};
 use std::sync::Arc;
@@ -27,19 +29,23 @@ use tracing::{debug, trace};
 const REQUESTS_BUFFER_SIZE: usize = 50;
 
 /// Generic request with associated response
-pub trait GenericRequest: Encode + Decode + 'static {
+pub trait GenericRequest: Encode + Decode + Send + 'static {
     const PROTOCOL_NAME: &'static str;
     const LOG_TARGET: &'static str;
     /// Response type that corresponds to this request
-    type Response: Encode + Decode + 'static;
+    type Response: Encode + Decode + Send + 'static;
 }
 
+type RequestHandlerFn<Request> = Arc<
+    dyn (Fn(PeerId, Request) -> BoxFuture<'static, Option<<Request as GenericRequest>::Response>>)
+        + Send
+        + Sync
+        + 'static,
+>;
+
 pub struct GenericRequestHandler<Request: GenericRequest> {
     request_receiver: mpsc::Receiver<IncomingRequest>,
-    #[allow(clippy::type_complexity)]
-    request_handler: Arc<
-        dyn (Fn(&Request) -> Option<<Request as GenericRequest>::Response>) + Send + Sync + 'static,
-    >,
+    request_handler: RequestHandlerFn<Request>,
     protocol_config: ProtocolConfig,
 }
 
@@ -47,6 +53,16 @@ impl<Request: GenericRequest> GenericRequestHandler<Request> {
     pub fn create<F>(request_handler: F) -> Box<dyn RequestHandler>
     where
         F: (Fn(&Request) -> Option<Request::Response>) + Send + Sync + 'static,
+    {
+        Self::create_async(move |_peer, request| future::ready(request_handler(&request)))
+    }
+
+    /// Same as [`Self::create()`], but handler also gets ID of the requesting peer and answers
+    /// asynchronously, requests are handled concurrently while their responses are pending.
+    pub fn create_async<F, Fut>(request_handler: F) -> Box<dyn RequestHandler>
+    where
+        F: (Fn(PeerId, Request) -> Fut) + Send + Sync + 'static,
+        Fut: Future<Output = Option<Request::Response>> + Send + 'static,
     {
         let (request_sender, request_receiver) = mpsc::channel(REQUESTS_BUFFER_SIZE);
 
@@ -55,23 +71,71 @@ impl<Request: GenericRequest> GenericRequestHandler<Request> {
 
         Box::new(Self {
             request_receiver,
-            request_handler: Arc::new(request_handler),
+            request_handler: Arc::new(move |peer, request| request_handler(peer, request).boxed()),
             protocol_config,
         })
     }
 
     // Invokes external protocol handler.
     fn handle_request(
-        &mut self,
+        request_handler: &RequestHandlerFn<Request>,
         peer: PeerId,
         payload: Vec<u8>,
-    ) -> Result<Vec<u8>, RequestHandlerError> {
+    ) -> Result<BoxFuture<'static, Option<Request::Response>>, RequestHandlerError> {
         trace!(%peer, protocol=Request::LOG_TARGET, "Handling request...");
         let request = Request::decode(&mut payload.as_slice())
             .map_err(|_| RequestHandlerError::InvalidRequestFormat)?;
-        let response = (self.request_handler)(&request);
 
-        Ok(response.ok_or(RequestHandlerError::NoResponse)?.encode())
+        Ok(request_handler(peer, request))
+    }
+}
+
+/// Encode response and send it back to the peer
+fn send_response<Request: GenericRequest>(
+    peer: PeerId,
+    response: Result<Vec<u8>, RequestHandlerError>,
+    pending_response: oneshot::Sender<OutgoingResponse>,
+) {
+    match response {
+        Ok(response_data) => {
+            let response = OutgoingResponse {
+                result: Ok(response_data),
+                sent_feedback: None,
+            };
+
+            match pending_response.send(response) {
+                Ok(()) => trace!(target = Request::LOG_TARGET, %peer, "Handled request",),
+                Err(_) => debug!(
+                    target = Request::LOG_TARGET,
+                    protocol = Request::PROTOCOL_NAME,
+                    %peer,
+                    "Failed to handle request: {}",
+                    RequestHandlerError::SendResponse
+                ),
+            };
+        }
+        Err(e) => {
+            debug!(
+                target = Request::LOG_TARGET,
+                protocol = Request::PROTOCOL_NAME,
+                %e,
+                "Failed to handle request.",
+            );
+
+            let response = OutgoingResponse {
+                result: Err(()),
+                sent_feedback: None,
+            };
+
+            if pending_response.send(response).is_err() {
+                debug!(
+                    target = Request::LOG_TARGET,
+                    protocol = Request::PROTOCOL_NAME,
+                    %peer,
+                    "Failed to handle request: {}", RequestHandlerError::SendResponse
+                );
+            };
+        }
     }
 }
 
@@ -79,55 +143,43 @@ impl<Request: GenericRequest> GenericRequestHandler<Request> {
 impl<Request: GenericRequest> RequestHandler for GenericRequestHandler<Request> {
     /// Run [`RequestHandler`].
     async fn run(&mut self) {
-        while let Some(request) = self.request_receiver.next().await {
-            let IncomingRequest {
-                peer,
-                payload,
-                pending_response,
-            } = request;
-
-            match self.handle_request(peer, payload) {
-                Ok(response_data) => {
-                    let response = OutgoingResponse {
-                        result: Ok(response_data),
-                        sent_feedback: None,
-                    };
-
-                    match pending_response.send(response) {
-                        Ok(()) => trace!(target = Request::LOG_TARGET, %peer, "Handled request",),
-                        Err(_) => debug!(
-                            target = Request::LOG_TARGET,
-                            protocol = Request::PROTOCOL_NAME,
-                            %peer,
-                            "Failed to handle request: {}",
-                            RequestHandlerError::SendResponse
-                        ),
-                    };
-                }
-                Err(e) => {
-                    debug!(
-                        target = Request::LOG_TARGET,
-                        protocol = Request::PROTOCOL_NAME,
-                        %e,
-                        "Failed to handle request.",
-                    );
-
-                    let response = OutgoingResponse {
-                        result: Err(()),
-                        sent_feedback: None,
+        let mut pending_responses = FuturesUnordered::new();
+
+        loop {
+            futures::select! {
+                request = self.request_receiver.next() => {
+                    let IncomingRequest {
+                        peer,
+                        payload,
+                        pending_response,
+                    } = match request {
+                        Some(request) => request,
+                        None => {
+                            break;
+                        }
                     };
 
-                    if pending_response.send(response).is_err() {
-                        debug!(
-                            target = Request::LOG_TARGET,
-                            protocol = Request::PROTOCOL_NAME,
-                            %peer,
-                            "Failed to handle request: {}", RequestHandlerError::SendResponse
-                        );
-                    };
+                    match Self::handle_request(&self.request_handler, peer, payload) {
+                        Ok(response) => {
+                            pending_responses.push(async move {
+                                let response = response
+                                    .await
+                                    .map(|response| response.encode())
+                                    .ok_or(RequestHandlerError::NoResponse);
+                                send_response::<Request>(peer, response, pending_response);
+                            });
+                        }
+                        Err(error) => {
+                            send_response::<Request>(peer, Err(error), pending_response);
+                        }
+                    }
                 }
+                () = pending_responses.select_next_some() => {}
             }
         }
+
+        // Answer requests that were received before the channel was closed
+        while pending_responses.next().await.is_some() {}
     }
 
     fn protocol_config(&self) -> ProtocolConfig {
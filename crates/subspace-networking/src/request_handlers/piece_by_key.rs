@@ -3,8 +3,11 @@
 //! Handle (i.e. answer) incoming pieces requests from a remote peer received via
 //! `RequestResponsesBehaviour` with generic [`GenericRequestHandler`].
 
+#[cfg(test)]
+mod tests;
+
 use crate::request_handlers::generic_request_handler::{GenericRequest, GenericRequestHandler};
-use parity_scale_codec::{Decode, Encode};
+use parity_scale_codec::{Decode, Encode, Input};
 use subspace_core_primitives::{Piece, PieceIndex, PieceIndexHash};
 
 //TODO: rename all module names if we keep this enum
@@ -30,10 +33,33 @@ impl GenericRequest for PieceByHashRequest {
 }
 
 /// Piece-by-hash protocol response.
-#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+#[derive(Debug, PartialEq, Eq, Clone, Encode)]
 pub struct PieceByHashResponse {
     /// Returned data.
     pub piece: Option<Piece>,
+    /// When piece is missing because request was rejected due to load, number of milliseconds
+    /// after which request should be retried.
+    ///
+    /// Added after the protocol was released, so it is the last field and is decoded as `None`
+    /// when missing from responses of older peers.
+    pub retry_after_ms: Option<u64>,
+}
+
+impl Decode for PieceByHashResponse {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, parity_scale_codec::Error> {
+        let piece = Decode::decode(input)?;
+        // Older peers don't send `retry_after_ms` at all
+        let retry_after_ms = if input.remaining_len()? == Some(0) {
+            None
+        } else {
+            Decode::decode(input)?
+        };
+
+        Ok(Self {
+            piece,
+            retry_after_ms,
+        })
+    }
 }
 
 //TODO: remove attribute on the first usage
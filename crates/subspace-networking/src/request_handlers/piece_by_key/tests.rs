@@ -0,0 +1,52 @@
+use super::PieceByHashResponse;
+use parity_scale_codec::{Decode, Encode};
+use subspace_core_primitives::Piece;
+
+#[test]
+fn response_round_trip() {
+    for response in [
+        PieceByHashResponse {
+            piece: Some(Piece::default()),
+            retry_after_ms: None,
+        },
+        PieceByHashResponse {
+            piece: None,
+            retry_after_ms: Some(1_500),
+        },
+    ] {
+        let encoded = response.encode();
+        assert_eq!(
+            PieceByHashResponse::decode(&mut encoded.as_slice()).unwrap(),
+            response
+        );
+    }
+}
+
+#[test]
+fn response_without_retry_after_decodes() {
+    // Response of older peers is just an optional piece
+    for piece in [Some(Piece::default()), None] {
+        let encoded = piece.encode();
+        assert_eq!(
+            PieceByHashResponse::decode(&mut encoded.as_slice()).unwrap(),
+            PieceByHashResponse {
+                piece,
+                retry_after_ms: None,
+            }
+        );
+    }
+}
+
+#[test]
+fn response_decodes_with_older_peers() {
+    // Older peers decode just an optional piece and ignore the rest of the response
+    let response = PieceByHashResponse {
+        piece: Some(Piece::default()),
+        retry_after_ms: Some(1_500),
+    };
+    let encoded = response.encode();
+    assert_eq!(
+        Option::<Piece>::decode(&mut encoded.as_slice()).unwrap(),
+        response.piece
+    );
+}
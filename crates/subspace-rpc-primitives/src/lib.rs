@@ -15,17 +15,32 @@
 
 //! Primitives for Subspace RPC.
 
+#[cfg(test)]
+mod tests;
+
+use parity_scale_codec::{Decode, Encode};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
 use subspace_core_primitives::{
-    Blake2b256Hash, PublicKey, RewardSignature, SegmentIndex, SlotNumber, Solution, SolutionRange,
+    plot_sector_size, Blake2b256Hash, Piece, PublicKey, RecordsRoot, RewardSignature, SegmentIndex,
+    SlotNumber, Solution, SolutionRange, PIECE_SIZE,
 };
+use thiserror::Error;
 
 /// Defines a limit for segment indexes array. It affects storage access on the runtime side.
 pub const MAX_SEGMENT_INDEXES_PER_REQUEST: usize = 300;
 
-/// Information about the protocol necessary for farmer operation
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+/// Max number of pieces of archived segment returned in a single response, keeps size of the
+/// response (pieces are hex-encoded) around 4 MiB.
+pub const MAX_PIECES_PER_SEGMENT_REQUEST: u32 = 64;
+
+/// Information about the protocol necessary for farmer operation.
+///
+/// SCALE encoding is stored by farmer on disk and is pinned by golden test vectors, fields must
+/// never be reordered or changed, add new fields at the end instead. Fields that change during
+/// lifetime of the plot and are not stored on disk are skipped in SCALE encoding.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Encode, Decode)]
 #[serde(rename_all = "camelCase")]
 pub struct FarmerProtocolInfo {
     /// Genesis hash of the chain
@@ -41,6 +56,209 @@ pub struct FarmerProtocolInfo {
     pub space_l: NonZeroU16,
     /// Number of segments after which sector expires
     pub sector_expiration: SegmentIndex,
+    /// Current rotation of the global salt new sectors are encoded with, `0` on networks that don't
+    /// support rotation. Every sector records rotation it was plotted with instead of storing it
+    /// here.
+    #[codec(skip)]
+    #[serde(default)]
+    pub rotation: u64,
+}
+
+impl FarmerProtocolInfo {
+    /// Minimum supported value of `space_l`
+    pub const MIN_SPACE_L: u16 = 3;
+    /// Maximum supported value of `space_l`, size of the sector in bits overflows `u64` for larger
+    /// values
+    pub const MAX_SPACE_L: u16 = 58;
+
+    /// Create builder that validates farmer protocol info before constructing it
+    pub fn builder() -> FarmerProtocolInfoBuilder {
+        FarmerProtocolInfoBuilder::default()
+    }
+}
+
+/// Single violated invariant of [`FarmerProtocolInfo`]
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum FarmerProtocolInfoViolation {
+    /// Field must not be zero
+    #[error("`{field}` must not be zero")]
+    Zero {
+        /// Name of the field
+        field: &'static str,
+    },
+    /// Segment size must be divisible by record size
+    #[error(
+        "Recorded history segment size {recorded_history_segment_size} is not divisible by record \
+        size {record_size}"
+    )]
+    SegmentSizeNotMultipleOfRecordSize {
+        /// Record size
+        record_size: u32,
+        /// Recorded history segment size
+        recorded_history_segment_size: u32,
+    },
+    /// `space_l` is out of supported range
+    #[error(
+        "`space_l` {space_l} is out of supported range {}..={}",
+        FarmerProtocolInfo::MIN_SPACE_L,
+        FarmerProtocolInfo::MAX_SPACE_L
+    )]
+    SpaceLOutOfRange {
+        /// `space_l`
+        space_l: u16,
+    },
+    /// Sector size corresponding to `space_l` is not divisible by piece size
+    #[error("Sector size of `space_l` {space_l} is not divisible by piece size {PIECE_SIZE}")]
+    SectorSizeNotMultipleOfPieceSize {
+        /// `space_l`
+        space_l: u16,
+    },
+}
+
+/// Error returned by [`FarmerProtocolInfoBuilder::build()`], lists all violated invariants
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub struct FarmerProtocolInfoError {
+    /// Violated invariants
+    pub violations: Vec<FarmerProtocolInfoViolation>,
+}
+
+impl fmt::Display for FarmerProtocolInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid farmer protocol info: ")?;
+        for (index, violation) in self.violations.iter().enumerate() {
+            if index > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{violation}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builder of [`FarmerProtocolInfo`] created with [`FarmerProtocolInfo::builder()`], all fields
+/// except genesis hash must be set to non-zero values
+#[derive(Debug, Default, Clone)]
+pub struct FarmerProtocolInfoBuilder {
+    genesis_hash: [u8; 32],
+    record_size: u32,
+    recorded_history_segment_size: u32,
+    total_pieces: u64,
+    space_l: u16,
+    sector_expiration: SegmentIndex,
+    rotation: u64,
+}
+
+impl FarmerProtocolInfoBuilder {
+    /// Genesis hash of the chain
+    pub fn genesis_hash(mut self, genesis_hash: [u8; 32]) -> Self {
+        self.genesis_hash = genesis_hash;
+        self
+    }
+
+    /// The size of data in one piece (in bytes)
+    pub fn record_size(mut self, record_size: u32) -> Self {
+        self.record_size = record_size;
+        self
+    }
+
+    /// Recorded history is encoded and plotted in segments of this size (in bytes)
+    pub fn recorded_history_segment_size(mut self, recorded_history_segment_size: u32) -> Self {
+        self.recorded_history_segment_size = recorded_history_segment_size;
+        self
+    }
+
+    /// Total number of pieces stored on the network
+    pub fn total_pieces(mut self, total_pieces: u64) -> Self {
+        self.total_pieces = total_pieces;
+        self
+    }
+
+    /// Space parameter for proof-of-replication in bits
+    pub fn space_l(mut self, space_l: u16) -> Self {
+        self.space_l = space_l;
+        self
+    }
+
+    /// Number of segments after which sector expires
+    pub fn sector_expiration(mut self, sector_expiration: SegmentIndex) -> Self {
+        self.sector_expiration = sector_expiration;
+        self
+    }
+
+    /// Current rotation of the global salt, `0` (default) if network doesn't support rotation
+    pub fn rotation(mut self, rotation: u64) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Validate all invariants and build farmer protocol info
+    pub fn build(self) -> Result<FarmerProtocolInfo, FarmerProtocolInfoError> {
+        let mut violations = Vec::new();
+
+        let record_size = NonZeroU32::new(self.record_size);
+        let total_pieces = NonZeroU64::new(self.total_pieces);
+        let space_l = NonZeroU16::new(self.space_l);
+        for (field, is_zero) in [
+            ("record_size", record_size.is_none()),
+            (
+                "recorded_history_segment_size",
+                self.recorded_history_segment_size == 0,
+            ),
+            ("total_pieces", total_pieces.is_none()),
+            ("space_l", space_l.is_none()),
+            (
+                "sector_expiration",
+                self.sector_expiration == SegmentIndex::ZERO,
+            ),
+        ] {
+            if is_zero {
+                violations.push(FarmerProtocolInfoViolation::Zero { field });
+            }
+        }
+
+        if let Some(record_size) = record_size {
+            if self.recorded_history_segment_size % record_size.get() != 0 {
+                violations.push(
+                    FarmerProtocolInfoViolation::SegmentSizeNotMultipleOfRecordSize {
+                        record_size: record_size.get(),
+                        recorded_history_segment_size: self.recorded_history_segment_size,
+                    },
+                );
+            }
+        }
+
+        if let Some(space_l) = space_l {
+            if !(FarmerProtocolInfo::MIN_SPACE_L..=FarmerProtocolInfo::MAX_SPACE_L)
+                .contains(&space_l.get())
+            {
+                violations.push(FarmerProtocolInfoViolation::SpaceLOutOfRange {
+                    space_l: space_l.get(),
+                });
+            } else if plot_sector_size(space_l) % PIECE_SIZE as u64 != 0 {
+                violations.push(
+                    FarmerProtocolInfoViolation::SectorSizeNotMultipleOfPieceSize {
+                        space_l: space_l.get(),
+                    },
+                );
+            }
+        }
+
+        match (record_size, total_pieces, space_l) {
+            (Some(record_size), Some(total_pieces), Some(space_l)) if violations.is_empty() => {
+                Ok(FarmerProtocolInfo {
+                    genesis_hash: self.genesis_hash,
+                    record_size,
+                    recorded_history_segment_size: self.recorded_history_segment_size,
+                    total_pieces,
+                    space_l,
+                    sector_expiration: self.sector_expiration,
+                    rotation: self.rotation,
+                })
+            }
+            _ => Err(FarmerProtocolInfoError { violations }),
+        }
+    }
 }
 
 /// Information about new slot that just arrived
@@ -92,3 +310,16 @@ pub struct RewardSignatureResponse {
     /// Pre-header or vote hash signature.
     pub signature: Option<RewardSignature>,
 }
+
+/// Consecutive pieces of archived segment together with records root of the segment they are to be
+/// verified against.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentPieces {
+    /// Records root of the segment
+    pub records_root: RecordsRoot,
+    /// Position of the first piece in the segment
+    pub first_position: u32,
+    /// Pieces starting at `first_position`, fewer than requested at the end of the segment
+    pub pieces: Vec<Piece>,
+}
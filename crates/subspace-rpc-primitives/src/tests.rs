@@ -0,0 +1,145 @@
+use crate::{
+    FarmerProtocolInfo, FarmerProtocolInfoBuilder, FarmerProtocolInfoError,
+    FarmerProtocolInfoViolation,
+};
+use parity_scale_codec::{Decode, Encode};
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64};
+use subspace_core_primitives::SegmentIndex;
+
+/// SCALE encoding of [`farmer_protocol_info()`], must only change deliberately together with
+/// migration of data stored on disk
+const FARMER_PROTOCOL_INFO_GOLDEN: &str = "\
+    000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\
+    000f0000\
+    00800700\
+    0001000000000000\
+    1400\
+    6400000000000000";
+
+fn farmer_protocol_info() -> FarmerProtocolInfo {
+    FarmerProtocolInfo {
+        genesis_hash: core::array::from_fn(|index| index as u8),
+        record_size: NonZeroU32::new(3840).unwrap(),
+        recorded_history_segment_size: 491520,
+        total_pieces: NonZeroU64::new(256).unwrap(),
+        space_l: NonZeroU16::new(20).unwrap(),
+        sector_expiration: SegmentIndex::new(100),
+        rotation: 0,
+    }
+}
+
+#[test]
+fn farmer_protocol_info_encoding_compatibility() {
+    let golden = hex::decode(FARMER_PROTOCOL_INFO_GOLDEN).unwrap();
+
+    let decoded = FarmerProtocolInfo::decode(&mut golden.as_slice()).unwrap();
+    assert_eq!(decoded, farmer_protocol_info());
+    assert_eq!(decoded.encode(), golden);
+
+    // Rotation is not stored
+    let rotated = FarmerProtocolInfo {
+        rotation: 5,
+        ..farmer_protocol_info()
+    };
+    assert_eq!(rotated.encode(), golden);
+}
+
+fn valid_builder() -> FarmerProtocolInfoBuilder {
+    let farmer_protocol_info = farmer_protocol_info();
+
+    FarmerProtocolInfo::builder()
+        .genesis_hash(farmer_protocol_info.genesis_hash)
+        .record_size(farmer_protocol_info.record_size.get())
+        .recorded_history_segment_size(farmer_protocol_info.recorded_history_segment_size)
+        .total_pieces(farmer_protocol_info.total_pieces.get())
+        .space_l(farmer_protocol_info.space_l.get())
+        .sector_expiration(farmer_protocol_info.sector_expiration)
+}
+
+fn violations(builder: FarmerProtocolInfoBuilder) -> Vec<FarmerProtocolInfoViolation> {
+    let FarmerProtocolInfoError { violations } = builder.build().unwrap_err();
+    violations
+}
+
+#[test]
+fn builder_valid() {
+    assert_eq!(valid_builder().build().unwrap(), farmer_protocol_info());
+    assert_eq!(valid_builder().rotation(3).build().unwrap().rotation, 3);
+}
+
+#[test]
+fn builder_zero_fields() {
+    assert_eq!(
+        violations(FarmerProtocolInfo::builder()),
+        vec![
+            FarmerProtocolInfoViolation::Zero {
+                field: "record_size"
+            },
+            FarmerProtocolInfoViolation::Zero {
+                field: "recorded_history_segment_size"
+            },
+            FarmerProtocolInfoViolation::Zero {
+                field: "total_pieces"
+            },
+            FarmerProtocolInfoViolation::Zero { field: "space_l" },
+            FarmerProtocolInfoViolation::Zero {
+                field: "sector_expiration"
+            },
+        ]
+    );
+
+    assert_eq!(
+        violations(valid_builder().total_pieces(0)),
+        vec![FarmerProtocolInfoViolation::Zero {
+            field: "total_pieces"
+        }]
+    );
+}
+
+#[test]
+fn builder_segment_size_not_multiple_of_record_size() {
+    assert_eq!(
+        violations(valid_builder().recorded_history_segment_size(491521)),
+        vec![
+            FarmerProtocolInfoViolation::SegmentSizeNotMultipleOfRecordSize {
+                record_size: 3840,
+                recorded_history_segment_size: 491521,
+            }
+        ]
+    );
+}
+
+#[test]
+fn builder_space_l() {
+    for space_l in [
+        FarmerProtocolInfo::MIN_SPACE_L - 1,
+        FarmerProtocolInfo::MAX_SPACE_L + 1,
+    ] {
+        assert_eq!(
+            violations(valid_builder().space_l(space_l)),
+            vec![FarmerProtocolInfoViolation::SpaceLOutOfRange { space_l }]
+        );
+    }
+
+    // Sector of 17 * 2^17 bits is not divisible by piece size
+    assert_eq!(
+        violations(valid_builder().space_l(17)),
+        vec![FarmerProtocolInfoViolation::SectorSizeNotMultipleOfPieceSize { space_l: 17 }]
+    );
+}
+
+#[test]
+fn builder_lists_all_violations() {
+    let error = valid_builder()
+        .record_size(1000)
+        .space_l(17)
+        .sector_expiration(SegmentIndex::ZERO)
+        .build()
+        .unwrap_err();
+
+    assert_eq!(error.violations.len(), 3);
+    let message = error.to_string();
+    for violation in &error.violations {
+        assert!(message.contains(&violation.to_string()));
+    }
+}
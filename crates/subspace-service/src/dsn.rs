@@ -3,7 +3,7 @@ use sc_consensus_subspace::{ArchivedSegmentNotification, SubspaceLink};
 use sp_core::traits::SpawnEssentialNamed;
 use sp_runtime::traits::Block as BlockT;
 use std::sync::Arc;
-use subspace_core_primitives::{Piece, PieceIndex, PieceIndexHash, PIECES_IN_SEGMENT};
+use subspace_core_primitives::{Piece, PieceIndex, PieceIndexHash, SegmentIndex};
 use subspace_networking::libp2p::{identity, Multiaddr};
 use subspace_networking::{
     BootstrappedNetworkingParameters, CreationError, CustomRecordStore, MemoryProviderStorage,
@@ -62,7 +62,10 @@ where
                 None
             };
 
-            Some(PieceByHashResponse { piece: result })
+            Some(PieceByHashResponse {
+                piece: result,
+                retry_after_ms: None,
+            })
         })],
         record_store: CustomRecordStore::new(
             MemoryRecordStorage::default(),
@@ -97,13 +100,13 @@ where
             async move {
                 trace!("Subspace DSN archiver started.");
 
-                let mut last_published_segment_index: Option<u64> = None;
+                let mut last_published_segment_index: Option<SegmentIndex> = None;
                 while let Some(ArchivedSegmentNotification {
                     archived_segment, ..
                 }) = archived_segment_notification_stream.next().await
                 {
                     let segment_index = archived_segment.root_block.segment_index();
-                    let first_piece_index = segment_index * u64::from(PIECES_IN_SEGMENT);
+                    let first_piece_index = segment_index.first_piece_index();
 
                     info!(%segment_index, "Processing a segment.");
 
@@ -24,7 +24,9 @@ use merlin::Transcript;
 use schnorrkel::vrf::{VRFInOut, VRFOutput, VRFProof};
 use schnorrkel::{Keypair, PublicKey, SignatureResult};
 use subspace_core_primitives::crypto::blake2b_256_hash_list;
-use subspace_core_primitives::{Blake2b256Hash, Chunk, ChunkSignature, Randomness, SectorId};
+use subspace_core_primitives::{
+    Blake2b256Hash, Chunk, ChunkSignature, Randomness, SectorId, SlotNumber,
+};
 
 const CHUNK_SIGNATURE_LABEL: &[u8] = b"subspace_chunk_signature";
 
@@ -33,8 +35,8 @@ pub const REWARD_SIGNING_CONTEXT: &[u8] = b"subspace_reward";
 
 // TODO: Separate type for global challenge
 /// Derive global slot challenge from global randomness.
-pub fn derive_global_challenge(global_randomness: &Randomness, slot: u64) -> Blake2b256Hash {
-    blake2b_256_hash_list(&[global_randomness, &slot.to_le_bytes()])
+pub fn derive_global_challenge(global_randomness: &Randomness, slot: SlotNumber) -> Blake2b256Hash {
+    blake2b_256_hash_list(&[global_randomness, &slot.get().to_le_bytes()])
 }
 
 /// Transcript used for creation and verification of VRF signatures for chunks.
@@ -128,7 +128,7 @@ pub struct VerifySolutionParams<'a> {
 /// Solution verification
 pub fn verify_solution<'a, FarmerPublicKey, RewardAddress>(
     solution: &'a Solution<FarmerPublicKey, RewardAddress>,
-    slot: u64,
+    slot: SlotNumber,
     params: VerifySolutionParams<'_>,
 ) -> Result<(), Error>
 where
@@ -215,7 +215,7 @@ pub fn derive_next_solution_range(
     era_duration: BlockNumber,
 ) -> u64 {
     // calculate total slots within this era
-    let era_slot_count = current_slot - start_slot;
+    let era_slot_count = u64::from(current_slot - start_slot);
 
     // Now we need to re-calculate solution range. The idea here is to keep block production at
     // the same pace while space pledged on the network changes. For this we adjust previous
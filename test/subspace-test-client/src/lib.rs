@@ -36,7 +36,7 @@ use subspace_core_primitives::crypto::kzg::{Kzg, Witness};
 use subspace_core_primitives::crypto::{blake2b_256_254_hash, kzg};
 use subspace_core_primitives::objects::BlockObjectMapping;
 use subspace_core_primitives::{
-    Chunk, Piece, PieceIndex, PublicKey, SectorId, Solution, PIECE_SIZE,
+    Chunk, Piece, PieceIndex, PublicKey, SectorId, SectorIndex, Solution, PIECE_SIZE,
     RECORDED_HISTORY_SEGMENT_SIZE, RECORD_SIZE,
 };
 use subspace_runtime_primitives::opaque::Block;
@@ -137,7 +137,7 @@ async fn start_farming<Client>(
     let chunks_in_sector = u64::from(RECORD_SIZE) * u64::from(u8::BITS) / u64::from(space_l.get());
     let archived_segment = archived_segment_receiver.await.unwrap();
     let total_pieces = NonZeroU64::new(archived_segment.pieces.count() as PieceIndex).unwrap();
-    let sector_index = 0;
+    let sector_index = SectorIndex::ZERO;
 
     let mut new_slot_notification_stream = new_slot_notification_stream.subscribe();
 